@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Combining the PSBTs returned by the participants of a coordinated transaction.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use psbt_v0_bench::psbts_from_participants;
+
+fn combine(c: &mut Criterion) {
+    let (psbt, others) = psbts_from_participants(100);
+
+    // Cloning the PSBTs is setup, only combining them is measured.
+    c.bench_function("combine 100 PSBTs of 300 inputs", |b| {
+        b.iter_batched(
+            || (psbt.clone(), others.clone()),
+            |(mut psbt, others)| {
+                for other in others {
+                    psbt.combine(other).unwrap();
+                }
+                psbt
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("combine_many 100 PSBTs of 300 inputs", |b| {
+        b.iter_batched(
+            || (psbt.clone(), others.clone()),
+            |(mut psbt, others)| {
+                psbt.combine_many(others).unwrap();
+                psbt
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("combine a PSBT of 300 inputs with itself", |b| {
+        b.iter_batched(
+            || (psbt.clone(), psbt.clone()),
+            |(mut psbt, other)| {
+                psbt.combine(other).unwrap();
+                psbt
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, combine);
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! PSBTs for the benchmarks in `benches/`.
+//!
+//! Run the benchmarks with `cargo bench` from this directory, after copying `../Cargo-recent.lock`
+//! to `Cargo.lock`.
+
+use psbt_v0::bitcoin::bip32::{DerivationPath, Fingerprint};
+use psbt_v0::bitcoin::hashes::Hash;
+use psbt_v0::bitcoin::secp256k1::{Secp256k1, SecretKey};
+use psbt_v0::bitcoin::{ecdsa, Amount, OutPoint, PublicKey, ScriptBuf, TxIn, TxOut, Txid};
+use psbt_v0::{Input, Output, Psbt};
+
+/// A coordinator's copy of a PSBT with three P2WPKH inputs per participant, and the copies
+/// returned by the participants, each having signed the three inputs it owns.
+///
+/// # Panics
+///
+/// If `participants` is zero or more than 255.
+pub fn psbts_from_participants(participants: u8) -> (Psbt, Vec<Psbt>) {
+    assert!(participants > 0, "no participants");
+
+    let secp = Secp256k1::new();
+    let sig = ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+        .expect("valid signature");
+    let keys = (1..=participants)
+        .map(|i| {
+            let sk = SecretKey::from_slice(&[i; 32]).expect("valid key");
+            PublicKey::new(sk.public_key(&secp))
+        })
+        .collect::<Vec<_>>();
+
+    let num_inputs = 3 * keys.len();
+    let mut psbt = Psbt::with_capacity(num_inputs, 1);
+    for vout in 0..num_inputs {
+        let pk = keys[vout / 3];
+        let mut input = Input {
+            witness_utxo: Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().expect("compressed")),
+            }),
+            ..Default::default()
+        };
+        input.bip32_derivation.insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+        let txin = TxIn {
+            previous_output: OutPoint { txid: Txid::all_zeros(), vout: vout as u32 },
+            ..Default::default()
+        };
+        psbt.push_input(txin, input).expect("unique outpoint");
+    }
+    psbt.push_output(TxOut::NULL, Output::default());
+
+    let others = keys
+        .iter()
+        .enumerate()
+        .map(|(i, pk)| {
+            let mut other = psbt.clone();
+            for input in &mut other.inputs[3 * i..3 * i + 3] {
+                input.partial_sigs.insert(*pk, sig);
+            }
+            other
+        })
+        .collect();
+    (psbt, others)
+}
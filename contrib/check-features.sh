@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+#
+# Check every pair of optional features, with and without the standard library.
+#
+# CI tests each feature on its own and all of them together, this catches a feature that only
+# compiles because another one it does not enable is usually enabled too.
+
+set -euo pipefail
+
+REPO_DIR=$(git rev-parse --show-toplevel)
+# shellcheck source=./test_vars.sh
+. "$REPO_DIR/contrib/test_vars.sh"
+
+check() {
+    echo "checking features: $*"
+    cargo check --all-targets "$@"
+}
+
+read -r -a with_std <<< "$FEATURES_WITH_STD"
+read -r -a without_std <<< "$FEATURES_WITHOUT_STD"
+
+for ((i = 0; i < ${#without_std[@]}; i++)); do
+    for ((j = i + 1; j < ${#without_std[@]}; j++)); do
+        check --no-default-features --features "${without_std[i]} ${without_std[j]}"
+    done
+done
+
+for ((i = 0; i < ${#with_std[@]}; i++)); do
+    for ((j = i + 1; j < ${#with_std[@]}; j++)); do
+        check --features "${with_std[i]} ${with_std[j]}"
+    done
+done
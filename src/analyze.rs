@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Analysis of a PSBT in the style of Bitcoin Core's `analyzepsbt` RPC.
+//!
+//! [`Psbt::analyze`] reports, per input and for the whole PSBT, which BIP 174 role has to act
+//! next and what it is missing, and estimates the fee rate of the final transaction. Wallet
+//! backends can use it instead of calling `bitcoind` for this.
+
+use core::fmt;
+
+use bitcoin::hashes::Hash;
+use bitcoin::{Amount, FeeRate, PubkeyHash, PublicKey, Script, ScriptHash, WScriptHash};
+
+use crate::prelude::*;
+use crate::{Input, Psbt, SignerKey};
+
+impl Psbt {
+    /// Analyzes this PSBT, see [`PsbtAnalysis`].
+    ///
+    /// Signatures are counted, not verified. The signatures an input is missing are only listed
+    /// for scripts [`Input::signatures_needed`] understands.
+    pub fn analyze(&self) -> PsbtAnalysis {
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| match (input.is_finalized(), self.spend_utxo(index)) {
+                (true, utxo) => InputAnalysis {
+                    has_utxo: utxo.is_ok(),
+                    is_final: true,
+                    next_role: Role::Extractor,
+                    missing: MissingItems::default(),
+                },
+                (false, Ok(utxo)) => input.analyze(&utxo.script_pubkey),
+                (false, Err(_)) => InputAnalysis {
+                    has_utxo: false,
+                    is_final: false,
+                    next_role: Role::Updater,
+                    missing: MissingItems::default(),
+                },
+            })
+            .collect::<Vec<_>>();
+        let next_role = inputs.iter().map(|input| input.next_role).min().unwrap_or(Role::Updater);
+
+        let fee = self.fee().ok();
+        let weight = self.estimate_weight().ok();
+        let estimated_fee_rate = match (fee, weight) {
+            (Some(fee), Some(weight)) if weight.to_wu() > 0 =>
+                Some(FeeRate::from_sat_per_kwu(fee.to_sat().saturating_mul(1000) / weight.to_wu())),
+            _ => None,
+        };
+        PsbtAnalysis {
+            inputs,
+            next_role,
+            fee,
+            estimated_vsize: weight.map(|weight| weight.to_vbytes_ceil()),
+            estimated_fee_rate,
+        }
+    }
+}
+
+impl Input {
+    /// Analyzes a non-finalized input spending `spk`.
+    fn analyze(&self, spk: &Script) -> InputAnalysis {
+        let mut missing = MissingItems::default();
+        let mut script = spk;
+        if spk.is_p2sh() {
+            match self.redeem_script {
+                Some(ref redeem_script) => script = redeem_script,
+                None => {
+                    let hash = <[u8; 20]>::try_from(&spk.as_bytes()[2..22]).expect("20 bytes");
+                    missing.redeem_script = Some(ScriptHash::from_byte_array(hash));
+                }
+            }
+        }
+        if script.is_p2wsh() && self.witness_script.is_none() {
+            let hash = <[u8; 32]>::try_from(&script.as_bytes()[2..]).expect("32 bytes");
+            missing.witness_script = Some(WScriptHash::from_byte_array(hash));
+        }
+        if (script.is_p2pkh() || script.is_p2wpkh()) && self.partial_sigs.is_empty() {
+            let hash = &script.as_bytes()[if script.is_p2pkh() { 3 } else { 2 }..][..20];
+            let known = self
+                .bip32_derivation
+                .keys()
+                .any(|pk| PublicKey::new(*pk).pubkey_hash()[..] == *hash);
+            if !known {
+                let hash = <[u8; 20]>::try_from(hash).expect("20 bytes");
+                missing.pubkeys.push(PubkeyHash::from_byte_array(hash));
+            }
+        }
+
+        let next_role = if !missing.is_empty() {
+            Role::Updater
+        } else if self.is_satisfiable(spk) {
+            Role::Finalizer
+        } else {
+            if let Some(needed) = self.signatures_needed(spk) {
+                missing.signatures = needed.signers;
+            }
+            Role::Signer
+        };
+        InputAnalysis { has_utxo: true, is_final: false, next_role, missing }
+    }
+}
+
+/// The analysis of a PSBT, returned by [`Psbt::analyze`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtAnalysis {
+    /// The analysis of each input, in order.
+    pub inputs: Vec<InputAnalysis>,
+    /// The role that has to act next on the PSBT, the earliest role any input needs.
+    pub next_role: Role,
+    /// The fee of the transaction, if all UTXOs are known and it is not negative.
+    pub fee: Option<Amount>,
+    /// The estimated virtual size of the final transaction, see [`Psbt::estimate_weight`].
+    pub estimated_vsize: Option<u64>,
+    /// The estimated fee rate of the final transaction.
+    pub estimated_fee_rate: Option<FeeRate>,
+}
+
+/// The analysis of a single input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputAnalysis {
+    /// Whether the UTXO spent by the input is known.
+    pub has_utxo: bool,
+    /// Whether the input is finalized.
+    pub is_final: bool,
+    /// The role that has to act next on the input.
+    pub next_role: Role,
+    /// What the input is missing.
+    pub missing: MissingItems,
+}
+
+/// The data an input is missing, the fields of `missing` in `analyzepsbt`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MissingItems {
+    /// The hashes of the public keys a P2PKH or P2WPKH script commits to that are not known.
+    pub pubkeys: Vec<PubkeyHash>,
+    /// The keys that can provide a missing signature.
+    pub signatures: Vec<SignerKey>,
+    /// The hash of the redeem script, if it is missing.
+    pub redeem_script: Option<ScriptHash>,
+    /// The hash of the witness script, if it is missing.
+    pub witness_script: Option<WScriptHash>,
+}
+
+impl MissingItems {
+    /// Returns true if nothing is missing.
+    pub fn is_empty(&self) -> bool {
+        self.pubkeys.is_empty()
+            && self.signatures.is_empty()
+            && self.redeem_script.is_none()
+            && self.witness_script.is_none()
+    }
+}
+
+/// A BIP 174 role that acts on a PSBT after it is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Role {
+    /// Adds the UTXOs, scripts and key origins of the inputs.
+    Updater,
+    /// Adds signatures.
+    Signer,
+    /// Builds the final scriptSig and witness of the inputs.
+    Finalizer,
+    /// Extracts the final transaction.
+    Extractor,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Role::Updater => "updater",
+            Role::Signer => "signer",
+            Role::Finalizer => "finalizer",
+            Role::Extractor => "extractor",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::{absolute, ecdsa, transaction, ScriptBuf, Transaction, TxIn, TxOut, Witness};
+
+    use super::*;
+
+    #[test]
+    fn analyze() {
+        let secp = Secp256k1::new();
+        let pk =
+            PublicKey::new(secp256k1::SecretKey::from_slice(&[1; 32]).unwrap().public_key(&secp));
+        let wpkh = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap());
+        let utxo = TxOut { value: Amount::from_sat(10_000), script_pubkey: wpkh.to_p2sh() };
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 2],
+            output: vec![TxOut {
+                value: Amount::from_sat(15_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        })
+        .unwrap();
+        let analysis = psbt.analyze();
+        assert_eq!(analysis.next_role, Role::Updater);
+        assert!(!analysis.inputs[0].has_utxo);
+        assert_eq!((analysis.fee, analysis.estimated_vsize), (None, None));
+
+        psbt.inputs[0].witness_utxo = Some(utxo.clone());
+        psbt.inputs[1].witness_utxo = Some(utxo);
+        psbt.inputs[1].final_script_sig = Some(ScriptBuf::new());
+        psbt.inputs[1].final_script_witness = Some(Witness::default());
+        let analysis = psbt.analyze();
+        assert_eq!(analysis.inputs[0].missing.redeem_script, Some(wpkh.script_hash()));
+        assert_eq!(analysis.inputs[1].next_role, Role::Extractor);
+        assert_eq!(analysis.fee, Some(Amount::from_sat(5_000)));
+
+        psbt.inputs[0].redeem_script = Some(wpkh);
+        let analysis = psbt.analyze();
+        assert_eq!(analysis.inputs[0].missing.pubkeys, vec![pk.pubkey_hash()]);
+        assert_eq!(analysis.next_role, Role::Updater);
+
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+        let analysis = psbt.analyze();
+        assert_eq!(analysis.next_role, Role::Signer);
+        assert_eq!(analysis.inputs[0].missing.signatures, vec![SignerKey::Ecdsa(pk)]);
+        assert!(analysis.estimated_vsize.is_some());
+        assert!(analysis.estimated_fee_rate.unwrap() > FeeRate::BROADCAST_MIN);
+
+        let sig =
+            ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+                .unwrap();
+        psbt.inputs[0].partial_sigs.insert(pk, sig);
+        let analysis = psbt.analyze();
+        assert_eq!(analysis.next_role, Role::Finalizer);
+        assert!(analysis.inputs[0].missing.is_empty());
+    }
+}
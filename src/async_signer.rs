@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Signers that complete asynchronously, e.g. over WebUSB or a remote signing service.
+//!
+//! In a browser a signer can not block until the device or server answers, so the blocking
+//! [`PsbtSigner`] does not fit. An [`AsyncPsbtSigner`] returns a future instead. The adapters
+//! mirror those of [`PsbtSigner`] for backends returning the whole signed PSBT or only their
+//! signatures, and [`SyncSigner`] lets software keys and other signers that do not wait be driven
+//! alongside them.
+//!
+//! The futures are `Send` except on `wasm32`, where JavaScript futures are not, see [`MaybeSend`].
+
+use core::future::{self, Future};
+use core::pin::Pin;
+
+use crate::external_signer::signature_counts;
+use crate::prelude::*;
+use crate::{Input, Psbt, PsbtSigner, SignOutcome, SignerError};
+
+/// The future returned by [`AsyncPsbtSigner::sign_psbt`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type SignFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<SignOutcome, SignerError>> + Send + 'a>>;
+
+/// The future returned by [`AsyncPsbtSigner::sign_psbt`].
+#[cfg(target_arch = "wasm32")]
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = Result<SignOutcome, SignerError>> + 'a>>;
+
+/// Implemented by every `Send` type, and by every type on `wasm32`.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + ?Sized> MaybeSend for T {}
+
+/// Implemented by every `Send` type, and by every type on `wasm32`.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// A signer that adds its signatures to a PSBT asynchronously.
+///
+/// The trait is object safe, errors from the device or transport are reported as strings.
+pub trait AsyncPsbtSigner {
+    /// Adds this signer's signatures to `psbt`.
+    ///
+    /// # Errors
+    ///
+    /// If the signer fails or returns data that does not belong to `psbt`. The PSBT may have been
+    /// partially signed.
+    fn sign_psbt<'a>(&'a mut self, psbt: &'a mut Psbt) -> SignFuture<'a>;
+}
+
+impl<S: AsyncPsbtSigner + ?Sized> AsyncPsbtSigner for Box<S> {
+    fn sign_psbt<'a>(&'a mut self, psbt: &'a mut Psbt) -> SignFuture<'a> {
+        (**self).sign_psbt(psbt)
+    }
+}
+
+/// Adapts an asynchronous signer that returns the whole signed PSBT.
+///
+/// The signer is given a copy of the PSBT, and what it returns is combined into the one being
+/// signed, see [`FullPsbtSigner`](crate::FullPsbtSigner).
+pub struct AsyncFullPsbtSigner<F>(pub F);
+
+impl<F, Fut> AsyncPsbtSigner for AsyncFullPsbtSigner<F>
+where
+    F: FnMut(Psbt) -> Fut + MaybeSend,
+    Fut: Future<Output = Result<Psbt, String>> + MaybeSend + 'static,
+{
+    fn sign_psbt<'a>(&'a mut self, psbt: &'a mut Psbt) -> SignFuture<'a> {
+        let signing = (self.0)(psbt.clone());
+        Box::pin(async move {
+            let signed = signing.await.map_err(SignerError::Device)?;
+            let before = signature_counts(psbt);
+            psbt.combine_many(core::iter::once(signed)).map_err(SignerError::Combine)?;
+            Ok(SignOutcome::compare(&before, psbt))
+        })
+    }
+}
+
+/// Adapts an asynchronous signer that only returns what it added, as input index and an input map
+/// holding the new signatures.
+///
+/// The signer is given a copy of the PSBT, see [`PartialSigner`](crate::PartialSigner).
+pub struct AsyncPartialSigner<F>(pub F);
+
+impl<F, Fut> AsyncPsbtSigner for AsyncPartialSigner<F>
+where
+    F: FnMut(Psbt) -> Fut + MaybeSend,
+    Fut: Future<Output = Result<Vec<(usize, Input)>, String>> + MaybeSend + 'static,
+{
+    fn sign_psbt<'a>(&'a mut self, psbt: &'a mut Psbt) -> SignFuture<'a> {
+        let signing = (self.0)(psbt.clone());
+        Box::pin(async move {
+            let inputs = signing.await.map_err(SignerError::Device)?;
+            let before = signature_counts(psbt);
+            for (index, input) in inputs {
+                psbt.merge_input(index, input).map_err(SignerError::Combine)?;
+            }
+            Ok(SignOutcome::compare(&before, psbt))
+        })
+    }
+}
+
+/// Drives a [`PsbtSigner`] that does not need to wait, e.g. a
+/// [`KeySigner`](crate::KeySigner), as an [`AsyncPsbtSigner`].
+///
+/// The returned future is ready immediately, the signing happens when `sign_psbt` is called.
+pub struct SyncSigner<S>(pub S);
+
+impl<S: PsbtSigner> AsyncPsbtSigner for SyncSigner<S> {
+    fn sign_psbt<'a>(&'a mut self, psbt: &'a mut Psbt) -> SignFuture<'a> {
+        Box::pin(future::ready(self.0.sign_psbt(psbt)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::{
+        absolute, ecdsa, transaction, Amount, NetworkKind, PrivateKey, ScriptBuf, Transaction,
+        TxIn, TxOut,
+    };
+
+    use super::*;
+    use crate::KeySigner;
+
+    /// Polls `future` until it is ready, the futures in these tests never wait on anything.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        fn noop_raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker { noop_raw_waker() }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(core::ptr::null(), &VTABLE)
+        }
+
+        let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn async_signers() {
+        let secp = Secp256k1::new();
+        let keys = (1..=2u8)
+            .map(|i| {
+                let sk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+                let sk = PrivateKey::new(sk, NetworkKind::Test);
+                (sk.public_key(&secp), sk)
+            })
+            .collect::<Vec<_>>();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            let pk = keys[index.min(1)].0;
+            input.witness_utxo = Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+            });
+            input
+                .bip32_derivation
+                .insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+        }
+        let sig =
+            ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+                .unwrap();
+        let pk = keys[1].0;
+
+        let mut signers: Vec<Box<dyn AsyncPsbtSigner>> = vec![
+            Box::new(SyncSigner(KeySigner::new(BTreeMap::from([keys[0]])))),
+            Box::new(AsyncFullPsbtSigner(move |mut psbt: Psbt| async move {
+                psbt.inputs[1].partial_sigs.insert(pk, sig);
+                Ok(psbt)
+            })),
+            Box::new(AsyncPartialSigner(move |_: Psbt| async move {
+                let mut input = Input::default();
+                input.partial_sigs.insert(pk, sig);
+                Ok(vec![(2, input)])
+            })),
+        ];
+        let mut outcomes = vec![];
+        for signer in &mut signers {
+            outcomes.push(block_on(signer.sign_psbt(&mut psbt)).unwrap().signed_inputs);
+        }
+        assert_eq!(outcomes, vec![vec![0], vec![1], vec![2]]);
+        assert!(psbt.inputs.iter().all(|input| input.partial_sigs.len() == 1));
+
+        let mut failing =
+            AsyncPartialSigner(|_: Psbt| async { Err("device disconnected".to_owned()) });
+        assert_eq!(
+            block_on(failing.sign_psbt(&mut psbt)),
+            Err(SignerError::Device("device disconnected".to_owned()))
+        );
+    }
+}
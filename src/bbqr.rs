@@ -0,0 +1,379 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BBQr encoding for transferring PSBTs as a sequence of QR codes.
+//!
+//! [BBQr] splits data into parts that each fit in a QR code, an air-gapped signer scans the parts
+//! in any order and reassembles the PSBT. Every part starts with an eight character header: `B$`,
+//! the encoding, the file type (`P` for PSBTs), then the number of parts and the index of the
+//! part, each as two base 36 digits.
+//!
+//! Only the uncompressed hex and base32 encodings are supported, parts using the zlib compressed
+//! encoding are rejected with [`BbqrError::UnsupportedEncoding`].
+//!
+//! [BBQr]: https://github.com/coinkite/BBQr/blob/master/BBQr.md
+
+use core::fmt;
+
+use bitcoin::hex::{DisplayHex as _, FromHex as _};
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::{Error, Psbt};
+
+const HEADER_LEN: usize = 8;
+const FILE_TYPE_PSBT: char = 'P';
+const MAX_PARTS: usize = 36 * 36 - 1;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// The encoding of the data in BBQr parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BbqrEncoding {
+    /// Upper case hex, two characters per byte.
+    Hex,
+    /// RFC 4648 base32 without padding, eight characters per five bytes.
+    Base32,
+}
+
+impl BbqrEncoding {
+    fn to_char(self) -> char {
+        match self {
+            BbqrEncoding::Hex => 'H',
+            BbqrEncoding::Base32 => '2',
+        }
+    }
+
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'H' => Some(BbqrEncoding::Hex),
+            '2' => Some(BbqrEncoding::Base32),
+            _ => None,
+        }
+    }
+
+    /// The number of characters that encode a whole number of bytes.
+    fn unit(self) -> usize {
+        match self {
+            BbqrEncoding::Hex => 2,
+            BbqrEncoding::Base32 => 8,
+        }
+    }
+
+    fn encode(self, data: &[u8]) -> String {
+        match self {
+            BbqrEncoding::Hex => data.to_upper_hex_string(),
+            BbqrEncoding::Base32 => base32_encode(data),
+        }
+    }
+
+    fn decode(self, s: &str) -> Option<Vec<u8>> {
+        match self {
+            BbqrEncoding::Hex => Vec::from_hex(s).ok(),
+            BbqrEncoding::Base32 => base32_decode(s),
+        }
+    }
+}
+
+impl Psbt {
+    /// Splits the serialized PSBT into BBQr parts of at most `max_len` characters each.
+    ///
+    /// All parts but the last have the same length, each should be displayed as its own QR code.
+    ///
+    /// # Errors
+    ///
+    /// If `max_len` is too small to hold a header and any data, or the PSBT would need more than
+    /// 1295 parts.
+    pub fn to_bbqr(
+        &self,
+        encoding: BbqrEncoding,
+        max_len: usize,
+    ) -> Result<Vec<String>, BbqrError> {
+        let data = encoding.encode(&self.serialize());
+
+        let unit = encoding.unit();
+        let part_len = max_len.saturating_sub(HEADER_LEN) / unit * unit;
+        if part_len == 0 {
+            return Err(BbqrError::MaxLenTooSmall { max_len });
+        }
+        let count = (data.len() + part_len - 1) / part_len;
+        if count > MAX_PARTS {
+            return Err(BbqrError::TooManyParts { count });
+        }
+
+        let parts = data
+            .as_bytes()
+            .chunks(part_len)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut part = String::with_capacity(HEADER_LEN + chunk.len());
+                part.push_str("B$");
+                part.push(encoding.to_char());
+                part.push(FILE_TYPE_PSBT);
+                push_base36(&mut part, count);
+                push_base36(&mut part, index);
+                part.push_str(core::str::from_utf8(chunk).expect("encoding is ASCII"));
+                part
+            })
+            .collect();
+        Ok(parts)
+    }
+
+    /// Reassembles a PSBT from its BBQr parts.
+    ///
+    /// The parts may be given in any order and the same part may be given more than once, as
+    /// happens when scanning an animated QR code.
+    pub fn from_bbqr<I, S>(parts: I) -> Result<Self, BbqrError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut header: Option<(BbqrEncoding, usize)> = None;
+        let mut data: BTreeMap<usize, String> = BTreeMap::new();
+
+        for part in parts {
+            let part = part.as_ref();
+            let (encoding, count, index) = parse_header(part)?;
+            match header {
+                Some(h) if h != (encoding, count) => return Err(BbqrError::InconsistentParts),
+                _ => header = Some((encoding, count)),
+            }
+            let chunk = &part[HEADER_LEN..];
+            if data.get(&index).map_or(false, |existing| existing != chunk) {
+                return Err(BbqrError::InconsistentParts);
+            }
+            data.insert(index, chunk.to_owned());
+        }
+
+        let (encoding, count) = header.ok_or(BbqrError::MissingPart { index: 0 })?;
+        if let Some(index) = (0..count).find(|index| !data.contains_key(index)) {
+            return Err(BbqrError::MissingPart { index });
+        }
+
+        let encoded = data.into_values().collect::<String>();
+        let bytes = encoding.decode(&encoded).ok_or(BbqrError::InvalidData)?;
+        Psbt::deserialize(&bytes).map_err(BbqrError::Psbt)
+    }
+}
+
+/// Parses the header of `part`, returning the encoding, number of parts, and index of the part.
+fn parse_header(part: &str) -> Result<(BbqrEncoding, usize, usize), BbqrError> {
+    // A header is ASCII, checking this first makes the slicing below fall on char boundaries.
+    let header = part
+        .get(..HEADER_LEN)
+        .filter(|header| header.is_ascii())
+        .ok_or(BbqrError::InvalidHeader)?;
+    let mut chars = header.chars();
+    if chars.next() != Some('B') || chars.next() != Some('$') {
+        return Err(BbqrError::InvalidHeader);
+    }
+    let encoding = chars.next().ok_or(BbqrError::InvalidHeader)?;
+    let encoding =
+        BbqrEncoding::from_char(encoding).ok_or(BbqrError::UnsupportedEncoding { encoding })?;
+    match chars.next() {
+        Some(FILE_TYPE_PSBT) => {}
+        Some(file_type) => return Err(BbqrError::UnsupportedFileType { file_type }),
+        None => return Err(BbqrError::InvalidHeader),
+    }
+
+    let count = parse_base36(&header[4..6]).ok_or(BbqrError::InvalidHeader)?;
+    let index = parse_base36(&header[6..8]).ok_or(BbqrError::InvalidHeader)?;
+    if count == 0 || index >= count {
+        return Err(BbqrError::InvalidHeader);
+    }
+    Ok((encoding, count, index))
+}
+
+/// Appends `n` as two upper case base 36 digits.
+fn push_base36(s: &mut String, n: usize) {
+    let digit =
+        |d: usize| char::from_digit(d as u32, 36).expect("less than 36").to_ascii_uppercase();
+    s.push(digit(n / 36));
+    s.push(digit(n % 36));
+}
+
+fn parse_base36(s: &str) -> Option<usize> {
+    if !s.bytes().all(|b| b.is_ascii_digit() || b.is_ascii_uppercase()) {
+        return None;
+    }
+    usize::from_str_radix(s, 36).ok()
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut s = String::with_capacity((data.len() * 8 + 4) / 5);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for &byte in data {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            s.push(BASE32_ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        s.push(BASE32_ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+    }
+    s
+}
+
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(s.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for c in s.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u16;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // Any remaining bits are padding and must be zero.
+    if bits >= 5 || buffer != 0 {
+        return None;
+    }
+    Some(data)
+}
+
+/// Error returned by [`Psbt::to_bbqr`] and [`Psbt::from_bbqr`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BbqrError {
+    /// The maximum part length does not leave room for any data after the header.
+    MaxLenTooSmall {
+        /// The maximum part length.
+        max_len: usize,
+    },
+    /// The PSBT does not fit in the maximum number of parts.
+    TooManyParts {
+        /// The number of parts needed.
+        count: usize,
+    },
+    /// A part does not start with a valid BBQr header.
+    InvalidHeader,
+    /// A part uses an encoding that is not supported.
+    UnsupportedEncoding {
+        /// The encoding character in the header.
+        encoding: char,
+    },
+    /// A part does not contain a PSBT.
+    UnsupportedFileType {
+        /// The file type character in the header.
+        file_type: char,
+    },
+    /// The parts disagree on the encoding or number of parts, or on the data of a part.
+    InconsistentParts,
+    /// A part is missing.
+    MissingPart {
+        /// The index of the first missing part.
+        index: usize,
+    },
+    /// The reassembled data is not validly encoded.
+    InvalidData,
+    /// The reassembled data is not a valid PSBT.
+    Psbt(Error),
+}
+
+bitcoin_internals::impl_from_infallible!(BbqrError);
+
+impl fmt::Display for BbqrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BbqrError::*;
+
+        match *self {
+            MaxLenTooSmall { max_len } =>
+                write!(f, "a maximum part length of {} leaves no room for data", max_len),
+            TooManyParts { count } =>
+                write!(f, "{} parts exceeds the maximum of {} parts", count, MAX_PARTS),
+            InvalidHeader => f.write_str("invalid BBQr header"),
+            UnsupportedEncoding { encoding } =>
+                write!(f, "unsupported BBQr encoding '{}'", encoding),
+            UnsupportedFileType { file_type } =>
+                write!(f, "BBQr file type '{}' is not a PSBT", file_type),
+            InconsistentParts => f.write_str("the BBQr parts are inconsistent"),
+            MissingPart { index } => write!(f, "BBQr part {} is missing", index),
+            InvalidData => f.write_str("the BBQr data is not validly encoded"),
+            Psbt(ref e) => write_err!(f, "the BBQr data is not a valid PSBT"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BbqrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use BbqrError::*;
+
+        match *self {
+            Psbt(ref e) => Some(e),
+            MaxLenTooSmall { .. }
+            | TooManyParts { .. }
+            | InvalidHeader
+            | UnsupportedEncoding { .. }
+            | UnsupportedFileType { .. }
+            | InconsistentParts
+            | MissingPart { .. }
+            | InvalidData => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{Amount, OutPoint, ScriptBuf, TxOut};
+
+    use super::*;
+    use crate::PsbtBuilder;
+
+    #[test]
+    fn bbqr_roundtrip() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+        assert!(base32_decode("MZXW6YTBOJ").is_none());
+
+        let mut psbt = PsbtBuilder::new()
+            .input(OutPoint::null())
+            .output(TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() })
+            .build();
+        psbt.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(2_000), script_pubkey: ScriptBuf::new() });
+
+        for encoding in [BbqrEncoding::Hex, BbqrEncoding::Base32] {
+            let mut parts = psbt.to_bbqr(encoding, 50).unwrap();
+            assert!(parts.len() > 1);
+            assert!(parts.iter().all(|part| part.len() <= 50));
+            assert_eq!(&parts[1][..8], &format!("B${}P{:02}01", encoding.to_char(), parts.len()));
+
+            // Parts may arrive out of order and repeated.
+            parts.reverse();
+            parts.push(parts[0].clone());
+            assert_eq!(Psbt::from_bbqr(&parts).unwrap(), psbt);
+
+            parts.retain(|part| &part[6..8] != "01");
+            assert!(matches!(Psbt::from_bbqr(&parts), Err(BbqrError::MissingPart { index: 1 })));
+        }
+
+        let single = psbt.to_bbqr(BbqrEncoding::Hex, 1000).unwrap();
+        assert_eq!(single.len(), 1);
+        assert!(single[0].starts_with("B$HP0100"));
+
+        assert!(matches!(
+            psbt.to_bbqr(BbqrEncoding::Base32, 15),
+            Err(BbqrError::MaxLenTooSmall { max_len: 15 })
+        ));
+        assert!(matches!(
+            Psbt::from_bbqr(["B$ZP0100abcd"]),
+            Err(BbqrError::UnsupportedEncoding { encoding: 'Z' })
+        ));
+        assert!(matches!(
+            Psbt::from_bbqr(["B$HT0100abcd"]),
+            Err(BbqrError::UnsupportedFileType { file_type: 'T' })
+        ));
+    }
+
+    #[test]
+    fn bbqr_non_ascii_header() {
+        assert!(matches!(Psbt::from_bbqr(["B$HP0\u{e9}0DEAD"]), Err(BbqrError::InvalidHeader)));
+        assert!(matches!(Psbt::from_bbqr(["B$HP01\u{e9}DEAD"]), Err(BbqrError::InvalidHeader)));
+    }
+}
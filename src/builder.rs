@@ -0,0 +1,769 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The BIP 174 Creator role, and builders for the input and output maps.
+
+use core::fmt;
+
+use bitcoin::bip32::KeySource;
+use bitcoin::script::PushBytes;
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TapTree};
+#[cfg(feature = "miniscript")]
+use bitcoin::FeeRate;
+use bitcoin::{
+    absolute, secp256k1, transaction, Amount, OutPoint, Script, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, XOnlyPublicKey,
+};
+#[cfg(feature = "miniscript")]
+use bitcoin_internals::write_err;
+#[cfg(feature = "miniscript")]
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+
+use crate::prelude::*;
+#[cfg(feature = "miniscript")]
+use crate::{Error, EstimateWeightError, UpdateError};
+use crate::{Input, OpReturnError, Output, Psbt, PsbtSighashType};
+
+/// Builds a PSBT by adding inputs and outputs one at a time.
+///
+/// Inputs are added by the outpoint they spend, so the unsigned transaction never has a
+/// scriptSig or witness as required by BIP 174.
+///
+/// ```
+/// # use psbt_v0::bitcoin::{absolute, Amount, OutPoint, ScriptBuf, TxOut};
+/// # use psbt_v0::PsbtBuilder;
+/// let psbt = PsbtBuilder::new()
+///     .lock_time(absolute::LockTime::from_height(800_000).unwrap())
+///     .input(OutPoint::null())
+///     .output(TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() })
+///     .build();
+/// assert_eq!(psbt.inputs.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtBuilder {
+    psbt: Psbt,
+    /// The change descriptor and the index to derive it at.
+    #[cfg(feature = "miniscript")]
+    change: Option<(Descriptor<DescriptorPublicKey>, u32)>,
+    /// The fee rate the change output is sized for.
+    #[cfg(feature = "miniscript")]
+    fee_rate: FeeRate,
+}
+
+impl PsbtBuilder {
+    /// Creates a builder for a version 2 transaction with a zero lock time and no inputs or
+    /// outputs.
+    pub fn new() -> Self {
+        PsbtBuilder {
+            psbt: Psbt::with_capacity(0, 0),
+            #[cfg(feature = "miniscript")]
+            change: None,
+            #[cfg(feature = "miniscript")]
+            fee_rate: FeeRate::BROADCAST_MIN,
+        }
+    }
+
+    /// Sets the version of the unsigned transaction.
+    pub fn version(mut self, version: transaction::Version) -> Self {
+        self.psbt.unsigned_tx.version = version;
+        self
+    }
+
+    /// Sets the lock time of the unsigned transaction.
+    pub fn lock_time(mut self, lock_time: absolute::LockTime) -> Self {
+        self.psbt.unsigned_tx.lock_time = lock_time;
+        self
+    }
+
+    /// Adds an input spending `previous_output` with an empty input map.
+    ///
+    /// The sequence number is [`Sequence::ENABLE_RBF_NO_LOCKTIME`], which signals replaceability
+    /// and enables the lock time. Use [`PsbtBuilder::input_with`] to set a different sequence.
+    pub fn input(self, previous_output: OutPoint) -> Self {
+        self.input_with(previous_output, Sequence::ENABLE_RBF_NO_LOCKTIME, Input::default())
+    }
+
+    /// Adds an input spending `previous_output` with the given sequence number and input map.
+    pub fn input_with(
+        mut self,
+        previous_output: OutPoint,
+        sequence: Sequence,
+        input: Input,
+    ) -> Self {
+        let txin = TxIn { previous_output, sequence, ..Default::default() };
+        self.psbt.push_input(txin, input).expect("scriptSig and witness are empty");
+        self
+    }
+
+    /// Adds `txout` to the unsigned transaction with an empty output map.
+    pub fn output(self, txout: TxOut) -> Self { self.output_with(txout, Output::default()) }
+
+    /// Adds `txout` to the unsigned transaction with the given output map.
+    pub fn output_with(mut self, txout: TxOut, output: Output) -> Self {
+        self.psbt.push_output(txout, output);
+        self
+    }
+
+    /// Adds a zero value `OP_RETURN` output carrying `data`, e.g. a timestamp commitment.
+    ///
+    /// The output is recognized by [`Output::is_op_return`]. It is never dust, a zero value is
+    /// what relay policy expects of an unspendable output, and no descriptor derives it so it is
+    /// never taken for change.
+    ///
+    /// # Errors
+    ///
+    /// If the script pubkey would be larger than [`Psbt::MAX_STANDARD_OP_RETURN_SIZE`], the data
+    /// carrier limit, leaving room for at most 80 bytes of data.
+    pub fn add_data_output(self, data: &[u8]) -> Result<Self, OpReturnError> {
+        let output_index = self.psbt.outputs.len();
+        // `OP_RETURN`, the push opcode with its length and the data.
+        let push = match data.len() {
+            0..=75 => 1,
+            76..=0xff => 2,
+            0x100..=0xffff => 3,
+            _ => 5,
+        };
+        let size = 1 + push + data.len();
+        if size > Psbt::MAX_STANDARD_OP_RETURN_SIZE {
+            return Err(OpReturnError::TooLarge { output_index, size });
+        }
+        let data = <&PushBytes>::try_from(data).expect("data is short enough to push");
+        let script_pubkey = ScriptBuf::new_op_return(data);
+        Ok(self.output(TxOut { value: Amount::ZERO, script_pubkey }))
+    }
+
+    /// Sets the descriptor of the change output added by [`PsbtBuilder::build_with_change`],
+    /// derived at `next_index`, the wallet's first unused change index.
+    #[cfg(feature = "miniscript")]
+    pub fn set_change_descriptor(
+        mut self,
+        descriptor: Descriptor<DescriptorPublicKey>,
+        next_index: u32,
+    ) -> Self {
+        self.change = Some((descriptor, next_index));
+        self
+    }
+
+    /// Sets the fee rate the change output is sized for, [`FeeRate::BROADCAST_MIN`] by default.
+    #[cfg(feature = "miniscript")]
+    pub fn fee_rate(mut self, fee_rate: FeeRate) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Returns the PSBT, without a change output even if a change descriptor is set.
+    pub fn build(self) -> Psbt { self.psbt }
+
+    /// Returns the PSBT with a change output paying what the inputs have left after the outputs
+    /// and the fee at the fee rate.
+    ///
+    /// The change output is appended, with the output map filled in from the change descriptor
+    /// as by [`Psbt::update_output_with_descriptor`] so signers recognize it as change, see
+    /// [`Psbt::verify_change`]. The fee is computed from [`Psbt::estimate_weight`], so the inputs
+    /// need their UTXOs and scripts. If the change would be dust it is left to the fee instead.
+    /// Without a change descriptor this is [`PsbtBuilder::build`].
+    ///
+    /// # Errors
+    ///
+    /// If the fee or weight can not be computed, the change descriptor can not be derived, or the
+    /// inputs do not pay for the outputs and the fee.
+    #[cfg(feature = "miniscript")]
+    pub fn build_with_change(mut self) -> Result<Psbt, ChangeOutputError> {
+        use ChangeOutputError::*;
+
+        let (descriptor, index) = match self.change.take() {
+            Some(change) => change,
+            None => return Ok(self.build()),
+        };
+        let fee_rate = self.fee_rate;
+        let mut psbt = self.psbt;
+        // What the inputs pay on top of the outputs.
+        let available = psbt.fee().map_err(Fee)?;
+
+        let script_pubkey =
+            descriptor.at_derivation_index(index).map_err(UpdateError::from)?.script_pubkey();
+        let dust = script_pubkey.minimal_non_dust();
+        psbt.push_output(TxOut { value: Amount::ZERO, script_pubkey }, Output::default());
+        let change_index = psbt.outputs.len() - 1;
+        psbt.update_output_with_descriptor(change_index, &descriptor, index)?;
+
+        let fee = fee_rate.fee_wu(psbt.estimate_weight()?).ok_or(FeeOverflow)?;
+        match available.checked_sub(fee) {
+            Some(change) if change >= dust => {
+                psbt.unsigned_tx.output[change_index].value = change;
+                return Ok(psbt);
+            }
+            _ => {
+                psbt.unsigned_tx.output.pop();
+                psbt.outputs.pop();
+            }
+        }
+
+        let fee = fee_rate.fee_wu(psbt.estimate_weight()?).ok_or(FeeOverflow)?;
+        if available < fee {
+            return Err(InsufficientFunds { fee, available });
+        }
+        Ok(psbt)
+    }
+}
+
+impl Default for PsbtBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+impl Input {
+    /// Returns a builder for an input map, see [`InputBuilder`].
+    pub fn builder() -> InputBuilder { InputBuilder::default() }
+}
+
+/// Builds an input map, checking that it has the fields needed to sign for the spent output.
+///
+/// ```
+/// # use psbt_v0::bitcoin::{Amount, ScriptBuf, TxOut};
+/// # use psbt_v0::{BuildMapError, Input};
+/// let witness_script = ScriptBuf::from_bytes(vec![0x51]);
+/// let utxo = TxOut { value: Amount::from_sat(1_000), script_pubkey: witness_script.to_p2wsh() };
+/// let builder = Input::builder().witness_utxo(utxo);
+/// assert_eq!(builder.clone().build(), Err(BuildMapError::MissingWitnessScript));
+/// let input = builder.witness_script(witness_script).build().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputBuilder {
+    input: Input,
+    /// The index of the spent output in the non-witness UTXO.
+    vout: Option<u32>,
+}
+
+impl InputBuilder {
+    /// Sets the output spent by the input, for inputs spending a segwit output.
+    pub fn witness_utxo(mut self, utxo: TxOut) -> Self {
+        self.input.witness_utxo = Some(utxo);
+        self
+    }
+
+    /// Sets the transaction whose output at `vout` is spent by the input.
+    pub fn non_witness_utxo(mut self, tx: Transaction, vout: u32) -> Self {
+        self.input.non_witness_utxo = Some(tx);
+        self.vout = Some(vout);
+        self
+    }
+
+    /// Sets the redeem script, for inputs spending a P2SH output.
+    pub fn redeem_script(mut self, script: ScriptBuf) -> Self {
+        self.input.redeem_script = Some(script);
+        self
+    }
+
+    /// Sets the witness script, for inputs spending a P2WSH output.
+    pub fn witness_script(mut self, script: ScriptBuf) -> Self {
+        self.input.witness_script = Some(script);
+        self
+    }
+
+    /// Adds the origin of a key the input is signed with.
+    pub fn bip32_derivation(mut self, pk: secp256k1::PublicKey, source: KeySource) -> Self {
+        self.input.bip32_derivation.insert(pk, source);
+        self
+    }
+
+    /// Sets the sighash type signatures must use.
+    pub fn sighash_type(mut self, sighash_type: PsbtSighashType) -> Self {
+        self.input.sighash_type = Some(sighash_type);
+        self
+    }
+
+    /// Sets the Taproot internal key.
+    pub fn tap_internal_key(mut self, key: XOnlyPublicKey) -> Self {
+        self.input.tap_internal_key = Some(key);
+        self
+    }
+
+    /// Sets the Taproot merkle root.
+    pub fn tap_merkle_root(mut self, root: TapNodeHash) -> Self {
+        self.input.tap_merkle_root = Some(root);
+        self
+    }
+
+    /// Adds a Taproot leaf script along with its control block.
+    pub fn tap_script(
+        mut self,
+        control_block: ControlBlock,
+        script: ScriptBuf,
+        leaf_version: LeafVersion,
+    ) -> Self {
+        self.input.tap_scripts.insert(control_block, (script, leaf_version));
+        self
+    }
+
+    /// Adds the origin of a Taproot key and the leaves it is used in.
+    pub fn tap_key_origin(
+        mut self,
+        key: XOnlyPublicKey,
+        leaf_hashes: Vec<TapLeafHash>,
+        source: KeySource,
+    ) -> Self {
+        self.input.tap_key_origins.insert(key, (leaf_hashes, source));
+        self
+    }
+
+    /// Returns the input map after checking that it is consistent.
+    ///
+    /// The spent output must be known. Inputs spending segwit outputs, including P2SH wrapped
+    /// ones, need the witness UTXO and the others the non-witness UTXO. The redeem script of a
+    /// P2SH output, and the witness script of a P2WSH output, are required and must hash to the
+    /// script they are committed to. Taproot fields need a Taproot output and the leaf scripts and
+    /// merkle root the internal key.
+    pub fn build(self) -> Result<Input, BuildMapError> {
+        use BuildMapError::*;
+
+        let input = self.input;
+        let mut spk = input.witness_utxo.as_ref().map(|utxo| &utxo.script_pubkey);
+        if let (Some(tx), Some(vout)) = (&input.non_witness_utxo, self.vout) {
+            let txout = tx.output.get(vout as usize).ok_or(UtxoOutOfRange { vout })?;
+            if spk.map_or(false, |spk| *spk != txout.script_pubkey) {
+                return Err(UtxoMismatch);
+            }
+            spk = Some(&txout.script_pubkey);
+        }
+        let spk = spk.ok_or(MissingUtxo)?;
+
+        let program = check_scripts(spk, &input.redeem_script, &input.witness_script)?;
+        if program.is_witness_program() {
+            if input.witness_utxo.is_none() {
+                return Err(MissingWitnessUtxo);
+            }
+        } else if input.non_witness_utxo.is_none() {
+            return Err(MissingNonWitnessUtxo);
+        }
+
+        let tap_fields = input.tap_internal_key.is_some()
+            || input.tap_merkle_root.is_some()
+            || !input.tap_scripts.is_empty()
+            || !input.tap_key_origins.is_empty();
+        if tap_fields && !spk.is_p2tr() {
+            return Err(NotTaproot);
+        }
+        if (input.tap_merkle_root.is_some() || !input.tap_scripts.is_empty())
+            && input.tap_internal_key.is_none()
+        {
+            return Err(MissingInternalKey);
+        }
+        Ok(input)
+    }
+}
+
+impl Output {
+    /// Returns a builder for the output map of an output paying to `script_pubkey`, see
+    /// [`OutputBuilder`].
+    pub fn builder(script_pubkey: ScriptBuf) -> OutputBuilder {
+        OutputBuilder { script_pubkey, output: Output::default() }
+    }
+}
+
+/// Builds an output map, checking that it describes the output's script pubkey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputBuilder {
+    script_pubkey: ScriptBuf,
+    output: Output,
+}
+
+impl OutputBuilder {
+    /// Sets the redeem script, for P2SH outputs.
+    pub fn redeem_script(mut self, script: ScriptBuf) -> Self {
+        self.output.redeem_script = Some(script);
+        self
+    }
+
+    /// Sets the witness script, for P2WSH outputs.
+    pub fn witness_script(mut self, script: ScriptBuf) -> Self {
+        self.output.witness_script = Some(script);
+        self
+    }
+
+    /// Adds the origin of a key of the output.
+    pub fn bip32_derivation(mut self, pk: secp256k1::PublicKey, source: KeySource) -> Self {
+        self.output.bip32_derivation.insert(pk, source);
+        self
+    }
+
+    /// Sets the Taproot internal key.
+    pub fn tap_internal_key(mut self, key: XOnlyPublicKey) -> Self {
+        self.output.tap_internal_key = Some(key);
+        self
+    }
+
+    /// Sets the Taproot script tree.
+    pub fn tap_tree(mut self, tree: TapTree) -> Self {
+        self.output.tap_tree = Some(tree);
+        self
+    }
+
+    /// Adds the origin of a Taproot key and the leaves it is used in.
+    pub fn tap_key_origin(
+        mut self,
+        key: XOnlyPublicKey,
+        leaf_hashes: Vec<TapLeafHash>,
+        source: KeySource,
+    ) -> Self {
+        self.output.tap_key_origins.insert(key, (leaf_hashes, source));
+        self
+    }
+
+    /// Returns the output map after checking that it is consistent with the script pubkey.
+    ///
+    /// The redeem script of a P2SH output, and the witness script of a P2WSH output, are required
+    /// and must hash to the script they are committed to. Taproot outputs need the internal key,
+    /// and Taproot fields a Taproot output.
+    pub fn build(self) -> Result<Output, BuildMapError> {
+        use BuildMapError::*;
+
+        let output = self.output;
+        check_scripts(&self.script_pubkey, &output.redeem_script, &output.witness_script)?;
+        let tap_fields = output.tap_internal_key.is_some()
+            || output.tap_tree.is_some()
+            || !output.tap_key_origins.is_empty();
+        if self.script_pubkey.is_p2tr() {
+            if output.tap_internal_key.is_none() {
+                return Err(MissingInternalKey);
+            }
+        } else if tap_fields {
+            return Err(NotTaproot);
+        }
+        Ok(output)
+    }
+}
+
+/// Checks that the redeem and witness scripts are present, and hash to their commitment, for
+/// `spk`. Returns the script pubkey, or the redeem script if `spk` is P2SH.
+fn check_scripts<'a>(
+    spk: &'a Script,
+    redeem_script: &'a Option<ScriptBuf>,
+    witness_script: &Option<ScriptBuf>,
+) -> Result<&'a Script, BuildMapError> {
+    use BuildMapError::*;
+
+    let mut program = spk;
+    if spk.is_p2sh() {
+        let redeem_script = redeem_script.as_ref().ok_or(MissingRedeemScript)?;
+        if redeem_script.to_p2sh() != *spk {
+            return Err(RedeemScriptMismatch);
+        }
+        program = redeem_script;
+    } else if redeem_script.is_some() {
+        return Err(RedeemScriptMismatch);
+    }
+
+    if program.is_p2wsh() {
+        let witness_script = witness_script.as_ref().ok_or(MissingWitnessScript)?;
+        if witness_script.to_p2wsh() != *program {
+            return Err(WitnessScriptMismatch);
+        }
+    } else if witness_script.is_some() {
+        return Err(WitnessScriptMismatch);
+    }
+    Ok(program)
+}
+
+/// Error building an input or output map, see [`InputBuilder::build`] and
+/// [`OutputBuilder::build`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildMapError {
+    /// Neither the witness nor the non-witness UTXO is set.
+    MissingUtxo,
+    /// The non-witness UTXO has no output at the index.
+    UtxoOutOfRange {
+        /// The index of the spent output.
+        vout: u32,
+    },
+    /// The witness UTXO is not the spent output of the non-witness UTXO.
+    UtxoMismatch,
+    /// The input spends a segwit output but has no witness UTXO.
+    MissingWitnessUtxo,
+    /// The input spends a non-segwit output but has no non-witness UTXO.
+    MissingNonWitnessUtxo,
+    /// The script pubkey is P2SH but there is no redeem script.
+    MissingRedeemScript,
+    /// The redeem script does not hash to the script pubkey, or is set for a non-P2SH output.
+    RedeemScriptMismatch,
+    /// The witness program is P2WSH but there is no witness script.
+    MissingWitnessScript,
+    /// The witness script does not hash to the witness program, or is set for a non-P2WSH output.
+    WitnessScriptMismatch,
+    /// Taproot fields are set for an output that is not Taproot.
+    NotTaproot,
+    /// The Taproot internal key is required but not set.
+    MissingInternalKey,
+}
+
+bitcoin_internals::impl_from_infallible!(BuildMapError);
+
+impl fmt::Display for BuildMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BuildMapError::*;
+
+        match *self {
+            MissingUtxo => f.write_str("the spent output is not set"),
+            UtxoOutOfRange { vout } =>
+                write!(f, "the non-witness UTXO has no output at index {}", vout),
+            UtxoMismatch => f.write_str("the witness UTXO does not match the non-witness UTXO"),
+            MissingWitnessUtxo =>
+                f.write_str("the input spends a segwit output without a witness UTXO"),
+            MissingNonWitnessUtxo =>
+                f.write_str("the input spends a non-segwit output without a non-witness UTXO"),
+            MissingRedeemScript => f.write_str("the redeem script of a P2SH output is not set"),
+            RedeemScriptMismatch =>
+                f.write_str("the redeem script does not match the script pubkey"),
+            MissingWitnessScript => f.write_str("the witness script of a P2WSH output is not set"),
+            WitnessScriptMismatch =>
+                f.write_str("the witness script does not match the witness program"),
+            NotTaproot => f.write_str("taproot fields are set for a non-taproot output"),
+            MissingInternalKey => f.write_str("the taproot internal key is not set"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildMapError {}
+
+/// Error returned by [`PsbtBuilder::build_with_change`].
+#[cfg(feature = "miniscript")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ChangeOutputError {
+    /// The value the inputs pay on top of the outputs can not be computed.
+    Fee(Error),
+    /// The weight of the transaction can not be estimated.
+    EstimateWeight(EstimateWeightError),
+    /// The change output can not be derived from the change descriptor.
+    Update(UpdateError),
+    /// The fee overflows.
+    FeeOverflow,
+    /// The inputs do not pay for the outputs and the fee.
+    InsufficientFunds {
+        /// The fee at the fee rate, without a change output.
+        fee: Amount,
+        /// The value the inputs pay on top of the outputs.
+        available: Amount,
+    },
+}
+
+#[cfg(feature = "miniscript")]
+bitcoin_internals::impl_from_infallible!(ChangeOutputError);
+
+#[cfg(feature = "miniscript")]
+impl fmt::Display for ChangeOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ChangeOutputError::*;
+
+        match *self {
+            Fee(ref e) => write_err!(f, "can not compute the value left for the fee"; e),
+            EstimateWeight(ref e) => write_err!(f, "can not estimate the transaction weight"; e),
+            Update(ref e) => write_err!(f, "can not derive the change output"; e),
+            FeeOverflow => f.write_str("the fee overflows"),
+            InsufficientFunds { fee, available } => write!(
+                f,
+                "the inputs have {} left after the outputs but the fee is {}",
+                available, fee
+            ),
+        }
+    }
+}
+
+#[cfg(all(feature = "miniscript", feature = "std"))]
+impl std::error::Error for ChangeOutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ChangeOutputError::*;
+
+        match *self {
+            Fee(ref e) => Some(e),
+            EstimateWeight(ref e) => Some(e),
+            Update(ref e) => Some(e),
+            FeeOverflow | InsufficientFunds { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "miniscript")]
+impl From<EstimateWeightError> for ChangeOutputError {
+    fn from(e: EstimateWeightError) -> Self { Self::EstimateWeight(e) }
+}
+
+#[cfg(feature = "miniscript")]
+impl From<UpdateError> for ChangeOutputError {
+    fn from(e: UpdateError) -> Self { Self::Update(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{Amount, Txid};
+
+    use super::*;
+
+    #[test]
+    fn add_data_output() {
+        let psbt = PsbtBuilder::new()
+            .input(OutPoint::null())
+            .add_data_output(&[0xAA; 80])
+            .unwrap()
+            .build();
+        let txout = &psbt.unsigned_tx.output[0];
+        assert_eq!(txout.value, Amount::ZERO);
+        assert_eq!(txout.script_pubkey.len(), Psbt::MAX_STANDARD_OP_RETURN_SIZE);
+        assert!(psbt.outputs[0].is_op_return(&txout.script_pubkey));
+        assert_eq!(txout.script_pubkey.minimal_non_dust(), Amount::ZERO);
+        assert_eq!(psbt.validate_op_returns(), Ok(()));
+
+        assert_eq!(
+            PsbtBuilder::new().add_data_output(&[0xAA; 81]),
+            Err(OpReturnError::TooLarge { output_index: 0, size: 84 })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "miniscript")]
+    fn build_with_change() {
+        let descriptor = "wpkh([d34db33f/84'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)"
+            .parse::<Descriptor<DescriptorPublicKey>>()
+            .unwrap();
+        let spk = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+        let builder = |input_value, output_value| {
+            let utxo = TxOut { value: Amount::from_sat(input_value), script_pubkey: spk.clone() };
+            PsbtBuilder::new()
+                .input_with(
+                    OutPoint::null(),
+                    Sequence::MAX,
+                    Input { witness_utxo: Some(utxo), ..Default::default() },
+                )
+                .output(TxOut { value: Amount::from_sat(output_value), script_pubkey: spk.clone() })
+                .set_change_descriptor(descriptor.clone(), 7)
+                .fee_rate(FeeRate::from_sat_per_vb_unchecked(2))
+        };
+
+        let psbt = builder(100_000, 50_000).build_with_change().unwrap();
+        assert_eq!(psbt.outputs.len(), 2);
+        let fee = FeeRate::from_sat_per_vb_unchecked(2).fee_wu(psbt.estimate_weight().unwrap());
+        assert_eq!(psbt.fee().unwrap(), fee.unwrap());
+        assert_eq!(psbt.verify_change(&descriptor, 0..10).unwrap(), vec![None, Some(7)]);
+        assert_eq!(psbt.outputs[1].bip32_derivation.len(), 1);
+
+        // Change that would be dust goes to the fee.
+        let psbt = builder(50_500, 50_000).build_with_change().unwrap();
+        assert_eq!(psbt.outputs.len(), 1);
+        assert_eq!(psbt.fee().unwrap(), Amount::from_sat(500));
+
+        assert!(matches!(
+            builder(50_100, 50_000).build_with_change(),
+            Err(ChangeOutputError::InsufficientFunds { available, .. })
+                if available == Amount::from_sat(100)
+        ));
+    }
+
+    #[test]
+    fn build_psbt() {
+        let outpoint = |vout| OutPoint { txid: Txid::all_zeros(), vout };
+        let txout = TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() };
+        let input = Input {
+            sighash_type: Some(bitcoin::EcdsaSighashType::All.into()),
+            ..Default::default()
+        };
+
+        let psbt = PsbtBuilder::new()
+            .version(transaction::Version::ONE)
+            .lock_time(absolute::LockTime::from_height(100).unwrap())
+            .input(outpoint(0))
+            .input_with(outpoint(1), Sequence::MAX, input.clone())
+            .output(txout.clone())
+            .build();
+
+        assert_eq!(psbt.unsigned_tx.version, transaction::Version::ONE);
+        assert_eq!(psbt.unsigned_tx.lock_time, absolute::LockTime::from_height(100).unwrap());
+        assert_eq!(psbt.input_sequence(0), Some(Sequence::ENABLE_RBF_NO_LOCKTIME));
+        assert_eq!(psbt.input_sequence(1), Some(Sequence::MAX));
+        assert_eq!(psbt.inputs, vec![Input::default(), input]);
+        assert_eq!(psbt.unsigned_tx.output, vec![txout]);
+        assert_eq!(psbt.outputs.len(), 1);
+
+        // The result is a valid unsigned transaction PSBT.
+        assert!(Psbt::from_unsigned_tx(psbt.unsigned_tx).is_ok());
+    }
+
+    #[test]
+    fn build_maps() {
+        use BuildMapError::*;
+
+        let witness_script = ScriptBuf::from_bytes(vec![0x51]);
+        let p2wsh =
+            TxOut { value: Amount::from_sat(1_000), script_pubkey: witness_script.to_p2wsh() };
+        assert_eq!(Input::builder().build(), Err(MissingUtxo));
+        assert_eq!(Input::builder().witness_utxo(p2wsh.clone()).build(), Err(MissingWitnessScript));
+        let input = Input::builder()
+            .witness_utxo(p2wsh.clone())
+            .witness_script(witness_script.clone())
+            .build()
+            .unwrap();
+        assert_eq!(input.witness_script, Some(witness_script.clone()));
+        assert_eq!(
+            Input::builder()
+                .witness_utxo(p2wsh.clone())
+                .witness_script(ScriptBuf::from_bytes(vec![0x52]))
+                .build(),
+            Err(WitnessScriptMismatch)
+        );
+
+        // Nested segwit needs both scripts and the witness UTXO.
+        let redeem_script = witness_script.to_p2wsh();
+        let p2sh = TxOut { value: Amount::from_sat(1_000), script_pubkey: redeem_script.to_p2sh() };
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![p2sh.clone()],
+        };
+        let nested = Input::builder()
+            .redeem_script(redeem_script.clone())
+            .witness_script(witness_script.clone());
+        assert_eq!(nested.clone().build(), Err(MissingUtxo));
+        assert_eq!(nested.clone().non_witness_utxo(tx.clone(), 0).build(), Err(MissingWitnessUtxo));
+        assert_eq!(
+            nested.clone().non_witness_utxo(tx.clone(), 1).build(),
+            Err(UtxoOutOfRange { vout: 1 })
+        );
+        assert_eq!(
+            nested.clone().non_witness_utxo(tx.clone(), 0).witness_utxo(p2wsh.clone()).build(),
+            Err(UtxoMismatch)
+        );
+        assert!(nested.non_witness_utxo(tx, 0).witness_utxo(p2sh).build().is_ok());
+
+        let legacy =
+            TxOut { value: Amount::from_sat(1_000), script_pubkey: witness_script.clone() };
+        assert_eq!(Input::builder().witness_utxo(legacy).build(), Err(MissingNonWitnessUtxo));
+        let key = "e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c"
+            .parse::<XOnlyPublicKey>()
+            .unwrap();
+        assert_eq!(
+            Input::builder()
+                .witness_utxo(p2wsh)
+                .witness_script(witness_script.clone())
+                .tap_internal_key(key)
+                .build(),
+            Err(NotTaproot)
+        );
+
+        let p2tr = ScriptBuf::new_p2tr_tweaked(
+            bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(key),
+        );
+        assert_eq!(Output::builder(p2tr.clone()).build(), Err(MissingInternalKey));
+        assert!(Output::builder(p2tr).tap_internal_key(key).build().is_ok());
+        assert_eq!(
+            Output::builder(redeem_script.to_p2sh()).redeem_script(redeem_script.clone()).build(),
+            Err(MissingWitnessScript)
+        );
+        let output = Output::builder(redeem_script.to_p2sh())
+            .redeem_script(redeem_script)
+            .witness_script(witness_script)
+            .build()
+            .unwrap();
+        assert!(output.witness_script.is_some());
+    }
+}
@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Replacing a PSBT by one paying a higher fee, as described in BIP 125.
+
+use core::fmt;
+#[cfg(feature = "miniscript")]
+use core::ops::Range;
+
+use bitcoin::{Amount, FeeRate, Sequence};
+use bitcoin_internals::write_err;
+#[cfg(feature = "miniscript")]
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+
+#[cfg(feature = "miniscript")]
+use crate::UpdateError;
+use crate::{Error, EstimateWeightError, IndexOutOfBoundsError, Psbt};
+
+impl Psbt {
+    /// Returns an unsigned copy of this PSBT that pays a higher fee out of output `change_index`.
+    ///
+    /// The new fee pays `fee_rate` for the estimated weight of the finalized transaction, see
+    /// [`Psbt::estimate_weight`], and at least the fee of this PSBT plus the minimum relay fee for
+    /// the replacement as BIP 125 requires. The difference is taken from the change output.
+    ///
+    /// Every input signals replaceability and all signatures and final scriptSigs and witnesses
+    /// are removed, they do not sign the new transaction. All other fields, including the
+    /// Taproot fields, are kept. Inputs this PSBT has finalized may lack the data to sign them
+    /// again if the finalizer removed it.
+    ///
+    /// # Errors
+    ///
+    /// If the fee or the weight of this PSBT can not be computed, or the change output can not
+    /// pay the higher fee without becoming dust.
+    pub fn bump_fee(&self, change_index: usize, fee_rate: FeeRate) -> Result<Psbt, BumpFeeError> {
+        self.check_output_index_is_within_bounds(change_index)?;
+        let fee = self.fee().map_err(BumpFeeError::Fee)?;
+        let weight = self.estimate_weight()?;
+
+        let min_fee = FeeRate::BROADCAST_MIN
+            .fee_wu(weight)
+            .and_then(|relay_fee| fee.checked_add(relay_fee))
+            .ok_or(BumpFeeError::FeeOverflow)?;
+        let new_fee = fee_rate.fee_wu(weight).ok_or(BumpFeeError::FeeOverflow)?.max(min_fee);
+
+        let mut psbt = self.clone();
+        let change = &mut psbt.unsigned_tx.output[change_index];
+        let required = new_fee - fee + change.script_pubkey.minimal_non_dust();
+        if change.value < required {
+            return Err(BumpFeeError::InsufficientChange { required, available: change.value });
+        }
+        change.value -= new_fee - fee;
+
+        for txin in &mut psbt.unsigned_tx.input {
+            if !txin.sequence.is_rbf() {
+                txin.sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+            }
+        }
+        for input in &mut psbt.inputs {
+            input.partial_sigs.clear();
+            input.tap_key_sig = None;
+            input.tap_script_sigs.clear();
+            input.musig2_pub_nonces.clear();
+            input.musig2_partial_sigs.clear();
+            input.final_script_sig = None;
+            input.final_script_witness = None;
+        }
+        Ok(psbt)
+    }
+
+    /// Returns an unsigned copy of this PSBT that pays a higher fee out of its change output.
+    ///
+    /// The change output is the first output that derives from `descriptor` at an index in
+    /// `range`, see [`Psbt::verify_change`]. Otherwise the same as [`Psbt::bump_fee`].
+    #[cfg(feature = "miniscript")]
+    pub fn bump_fee_with_descriptor(
+        &self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        range: Range<u32>,
+        fee_rate: FeeRate,
+    ) -> Result<Psbt, BumpFeeError> {
+        let change_index = self
+            .verify_change(descriptor, range)?
+            .iter()
+            .position(Option::is_some)
+            .ok_or(BumpFeeError::NoChange)?;
+        self.bump_fee(change_index, fee_rate)
+    }
+}
+
+/// Error returned by [`Psbt::bump_fee`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BumpFeeError {
+    /// The change output index is out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The fee of the original PSBT can not be computed.
+    Fee(Error),
+    /// The weight of the transaction can not be estimated.
+    EstimateWeight(EstimateWeightError),
+    /// The new fee overflows.
+    FeeOverflow,
+    /// The change output can not pay the higher fee.
+    InsufficientChange {
+        /// The smallest change value that pays the higher fee and is not dust.
+        required: Amount,
+        /// The value of the change output.
+        available: Amount,
+    },
+    /// No output derives from the change descriptor.
+    #[cfg(feature = "miniscript")]
+    NoChange,
+    /// The change descriptor can not be derived.
+    #[cfg(feature = "miniscript")]
+    Update(UpdateError),
+}
+
+bitcoin_internals::impl_from_infallible!(BumpFeeError);
+
+impl fmt::Display for BumpFeeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BumpFeeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "change output index out of bounds"; e),
+            Fee(ref e) => write_err!(f, "can not compute the fee of the original PSBT"; e),
+            EstimateWeight(ref e) => write_err!(f, "can not estimate the transaction weight"; e),
+            FeeOverflow => f.write_str("the new fee overflows"),
+            InsufficientChange { required, available } => write!(
+                f,
+                "the change output has {} but needs {} to pay the higher fee",
+                available, required
+            ),
+            #[cfg(feature = "miniscript")]
+            NoChange => f.write_str("no output derives from the change descriptor"),
+            #[cfg(feature = "miniscript")]
+            Update(ref e) => write_err!(f, "can not derive the change descriptor"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BumpFeeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use BumpFeeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            Fee(ref e) => Some(e),
+            EstimateWeight(ref e) => Some(e),
+            FeeOverflow | InsufficientChange { .. } => None,
+            #[cfg(feature = "miniscript")]
+            NoChange => None,
+            #[cfg(feature = "miniscript")]
+            Update(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for BumpFeeError {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+impl From<EstimateWeightError> for BumpFeeError {
+    fn from(e: EstimateWeightError) -> Self { Self::EstimateWeight(e) }
+}
+
+#[cfg(feature = "miniscript")]
+impl From<UpdateError> for BumpFeeError {
+    fn from(e: UpdateError) -> Self { Self::Update(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::{
+        absolute, ecdsa, transaction, CompressedPublicKey, ScriptBuf, Transaction, TxIn, TxOut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn bump_fee() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = CompressedPublicKey(sk.public_key(&secp));
+        let spk = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash());
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { sequence: Sequence::MAX, ..Default::default() }],
+            output: vec![
+                TxOut { value: Amount::from_sat(50_000), script_pubkey: spk.clone() },
+                TxOut { value: Amount::from_sat(49_000), script_pubkey: spk.clone() },
+            ],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(100_000), script_pubkey: spk });
+        psbt.inputs[0].tap_internal_key = Some(pk.0.x_only_public_key().0);
+        let sig =
+            ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+                .unwrap();
+        psbt.inputs[0].partial_sigs.insert(pk.into(), sig);
+
+        let fee_rate = FeeRate::from_sat_per_vb(20).unwrap();
+        let bumped = psbt.bump_fee(1, fee_rate).unwrap();
+        let weight = bumped.estimate_weight().unwrap();
+        assert_eq!(bumped.fee().unwrap(), fee_rate.fee_wu(weight).unwrap());
+        assert_eq!(bumped.unsigned_tx.output[0].value, Amount::from_sat(50_000));
+        assert!(bumped.unsigned_tx.is_explicitly_rbf());
+        assert!(bumped.inputs[0].partial_sigs.is_empty());
+        assert_eq!(bumped.inputs[0].tap_internal_key, psbt.inputs[0].tap_internal_key);
+
+        // A lower fee rate still pays more than the original fee.
+        let bumped = psbt.bump_fee(1, FeeRate::ZERO).unwrap();
+        assert_eq!(
+            bumped.fee().unwrap(),
+            Amount::from_sat(1_000) + FeeRate::BROADCAST_MIN.fee_wu(weight).unwrap()
+        );
+
+        assert!(matches!(
+            psbt.bump_fee(1, FeeRate::from_sat_per_vb(1_000).unwrap()),
+            Err(BumpFeeError::InsufficientChange { .. })
+        ));
+        assert!(matches!(psbt.bump_fee(2, fee_rate), Err(BumpFeeError::IndexOutOfBounds(_))));
+    }
+}
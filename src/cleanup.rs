@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Removing data a PSBT no longer needs.
+//!
+//! Every combine round takes the union of what the parties sent, so signatures and key origins
+//! that can not contribute to the final transaction accumulate. [`Psbt::cleanup`] removes them.
+
+use bitcoin::{PublicKey, Script, TapLeafHash};
+
+use crate::prelude::*;
+use crate::{script, Input, Psbt};
+
+impl Psbt {
+    /// Removes data that can not contribute to the final transaction.
+    ///
+    /// - Finalized inputs keep only their UTXOs, final scriptSig and witness, proprietary and
+    ///   unknown fields, as a BIP 174 finalizer leaves them.
+    /// - ECDSA signatures and BIP 32 derivations of keys that are not in the script spent are
+    ///   removed. The keys are only known for single key and `OP_CHECKMULTISIG` scripts, and with
+    ///   the `miniscript` feature for any miniscript, inputs spending other scripts are left as
+    ///   they are.
+    /// - Taproot script path signatures for leaves not in `tap_scripts`, and all of them if the
+    ///   input has a key path signature, are removed.
+    /// - The leaf hashes of Taproot key origins are limited to the leaves in `tap_scripts`, and
+    ///   origins of keys left with no leaf that are not the internal key are removed. Nothing is
+    ///   removed if the internal key is not known.
+    ///
+    /// Finally the PSBT is normalized, see [`Psbt::normalize`].
+    pub fn cleanup(&mut self) {
+        for index in 0..self.inputs.len() {
+            let spk = self.spend_utxo(index).map(|utxo| utxo.script_pubkey.clone());
+            let input = &mut self.inputs[index];
+            if input.is_finalized() {
+                input.cleanup_finalized();
+            } else if let Ok(spk) = spk {
+                if spk.is_p2tr() {
+                    input.cleanup_taproot();
+                } else if let Some(keys) = input.script_keys(&spk) {
+                    input.partial_sigs.retain(|pk, _| keys.contains(pk));
+                    input.bip32_derivation.retain(|pk, _| keys.iter().any(|key| key.inner == *pk));
+                }
+            }
+        }
+        self.normalize();
+    }
+}
+
+impl Input {
+    /// Removes everything but the fields a finalized input keeps.
+    fn cleanup_finalized(&mut self) {
+        *self = Input {
+            non_witness_utxo: self.non_witness_utxo.take(),
+            witness_utxo: self.witness_utxo.take(),
+            final_script_sig: self.final_script_sig.take(),
+            final_script_witness: self.final_script_witness.take(),
+            proprietary: core::mem::take(&mut self.proprietary),
+            unknown: core::mem::take(&mut self.unknown),
+            ..Default::default()
+        };
+    }
+
+    /// Removes Taproot signatures and key origins that no spend path uses.
+    fn cleanup_taproot(&mut self) {
+        let leaves = self
+            .tap_scripts
+            .values()
+            .map(|(script, version)| TapLeafHash::from_script(script, *version))
+            .collect::<BTreeSet<_>>();
+        if self.tap_key_sig.is_some() {
+            self.tap_script_sigs.clear();
+        }
+        self.tap_script_sigs.retain(|(_, leaf_hash), _| leaves.contains(leaf_hash));
+
+        let internal_key = match self.tap_internal_key {
+            Some(internal_key) => internal_key,
+            None => return,
+        };
+        self.tap_key_origins.retain(|key, (leaf_hashes, _)| {
+            leaf_hashes.retain(|leaf_hash| leaves.contains(leaf_hash));
+            *key == internal_key || !leaf_hashes.is_empty()
+        });
+    }
+
+    /// Returns the keys of the non-Taproot script locking `spk`, if the script is understood.
+    fn script_keys(&self, spk: &Script) -> Option<BTreeSet<PublicKey>> {
+        let mut script = spk;
+        let mut segwit = spk.is_witness_program();
+        if script.is_p2sh() {
+            script = self.redeem_script.as_ref()?;
+            segwit = script.is_witness_program();
+        }
+        if script.is_p2wsh() {
+            script = self.witness_script.as_ref()?;
+        }
+
+        if script.is_p2pkh() || script.is_p2wpkh() {
+            // Only the hash is in the script, keep the keys hashing to it.
+            let hash = &script.as_bytes()[if script.is_p2pkh() { 3 } else { 2 }..][..20];
+            let known = self
+                .partial_sigs
+                .keys()
+                .copied()
+                .chain(self.bip32_derivation.keys().map(|pk| PublicKey::new(*pk)));
+            return Some(known.filter(|pk| pk.pubkey_hash()[..] == *hash).collect());
+        }
+        if script.is_p2pk() {
+            let pk = PublicKey::from_slice(&script.as_bytes()[1..script.len() - 1]).ok()?;
+            return Some([pk].into_iter().collect());
+        }
+        if let Some((_, keys)) = script::multisig(script) {
+            return Some(keys.into_iter().collect());
+        }
+        miniscript_keys(script, segwit)
+    }
+}
+
+#[cfg(feature = "miniscript")]
+fn miniscript_keys(script: &Script, segwit: bool) -> Option<BTreeSet<PublicKey>> {
+    use miniscript::{Legacy, Miniscript, ScriptContext, Segwitv0};
+
+    fn keys<Ctx: ScriptContext<Key = PublicKey>>(script: &Script) -> Option<BTreeSet<PublicKey>> {
+        let ms = Miniscript::<PublicKey, Ctx>::parse_insane(script).ok()?;
+        // Keys committed to by hash only are not known from the script.
+        if ms.iter().any(|node| matches!(node.node, miniscript::Terminal::RawPkH(_))) {
+            return None;
+        }
+        Some(ms.iter_pk().collect())
+    }
+
+    if segwit {
+        keys::<Segwitv0>(script)
+    } else {
+        keys::<Legacy>(script)
+    }
+}
+
+#[cfg(not(feature = "miniscript"))]
+fn miniscript_keys(_: &Script, _: bool) -> Option<BTreeSet<PublicKey>> { None }
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::taproot::LeafVersion;
+    use bitcoin::{taproot, ScriptBuf, Witness, XOnlyPublicKey};
+
+    use super::*;
+    use crate::test_utils::{dummy_sig, Fixture, ScriptType};
+
+    fn xonly(pk: PublicKey) -> XOnlyPublicKey { XOnlyPublicKey::from(pk.inner) }
+
+    #[test]
+    fn cleanup_removes_keys_not_in_script() {
+        let mut fixture = Fixture::new(ScriptType::P2wsh);
+        let other = fixture.public_key(3);
+        let input = &mut fixture.psbt.inputs[0];
+        let keys = input.bip32_derivation.keys().map(|pk| PublicKey::new(*pk)).collect::<Vec<_>>();
+        for pk in keys.iter().chain([&other]) {
+            input.partial_sigs.insert(*pk, dummy_sig());
+        }
+        input
+            .bip32_derivation
+            .insert(other.inner, (Fingerprint::default(), DerivationPath::master()));
+
+        fixture.psbt.cleanup();
+
+        let input = &fixture.psbt.inputs[0];
+        assert_eq!(input.partial_sigs.keys().copied().collect::<Vec<_>>(), keys);
+        assert_eq!(input.bip32_derivation.len(), 3);
+        assert!(!input.bip32_derivation.contains_key(&other.inner));
+    }
+
+    #[test]
+    fn cleanup_keeps_keys_hashing_to_script() {
+        let mut fixture = Fixture::new(ScriptType::P2wpkh);
+        let pk = fixture.public_key(0);
+        let other = fixture.public_key(3);
+        let input = &mut fixture.psbt.inputs[0];
+        input.partial_sigs.insert(pk, dummy_sig());
+        input.partial_sigs.insert(other, dummy_sig());
+
+        fixture.psbt.cleanup();
+
+        let input = &fixture.psbt.inputs[0];
+        assert_eq!(input.partial_sigs.keys().copied().collect::<Vec<_>>(), vec![pk]);
+        assert!(input.bip32_derivation.contains_key(&pk.inner));
+    }
+
+    #[test]
+    fn cleanup_removes_unknown_leaves() {
+        let mut fixture = Fixture::new(ScriptType::P2trScript);
+        let internal_key = xonly(fixture.public_key(0));
+        let signer = xonly(fixture.public_key(1));
+        let other = xonly(fixture.public_key(2));
+        let origin = (Fingerprint::default(), DerivationPath::master());
+        let tap_sig = taproot::Signature::from_slice(&[1; 64]).unwrap();
+        let other_leaf_hash = TapLeafHash::from_script(&ScriptBuf::new(), LeafVersion::TapScript);
+
+        let input = &mut fixture.psbt.inputs[0];
+        let leaf_hash = input.tap_key_origins[&signer].0[0];
+        input.tap_key_origins.get_mut(&signer).unwrap().0.push(other_leaf_hash);
+        input.tap_key_origins.insert(other, (vec![other_leaf_hash], origin.clone()));
+        input.tap_key_origins.insert(internal_key, (vec![], origin));
+        input.tap_script_sigs.insert((signer, leaf_hash), tap_sig);
+        input.tap_script_sigs.insert((other, other_leaf_hash), tap_sig);
+
+        fixture.psbt.cleanup();
+
+        let input = &fixture.psbt.inputs[0];
+        assert_eq!(input.tap_script_sigs.keys().collect::<Vec<_>>(), vec![&(signer, leaf_hash)]);
+        assert_eq!(input.tap_key_origins.len(), 2);
+        assert_eq!(input.tap_key_origins[&signer].0, vec![leaf_hash]);
+        assert!(input.tap_key_origins[&internal_key].0.is_empty());
+    }
+
+    #[test]
+    fn cleanup_key_path_signature_removes_script_signatures() {
+        let mut fixture = Fixture::new(ScriptType::P2trScript);
+        let signer = xonly(fixture.public_key(1));
+        let tap_sig = taproot::Signature::from_slice(&[1; 64]).unwrap();
+        let input = &mut fixture.psbt.inputs[0];
+        let leaf_hash = input.tap_key_origins[&signer].0[0];
+        input.tap_script_sigs.insert((signer, leaf_hash), tap_sig);
+        input.tap_key_sig = Some(tap_sig);
+
+        fixture.psbt.cleanup();
+
+        assert!(fixture.psbt.inputs[0].tap_script_sigs.is_empty());
+        assert_eq!(fixture.psbt.inputs[0].tap_key_sig, Some(tap_sig));
+    }
+
+    #[test]
+    fn cleanup_finalized() {
+        let mut fixture = Fixture::new(ScriptType::P2wpkh);
+        let pk = fixture.public_key(0);
+        let input = &mut fixture.psbt.inputs[0];
+        input.partial_sigs.insert(pk, dummy_sig());
+        input.sighash_type = Some(bitcoin::EcdsaSighashType::All.into());
+        input.final_script_witness = Some(Witness::from_slice(&[&[1u8][..], &pk.to_bytes()]));
+
+        fixture.psbt.cleanup();
+
+        let input = &fixture.psbt.inputs[0];
+        assert!(input.partial_sigs.is_empty() && input.bip32_derivation.is_empty());
+        assert!(input.sighash_type.is_none());
+        assert!(input.witness_utxo.is_some() && input.final_script_witness.is_some());
+    }
+}
@@ -0,0 +1,401 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Checking that this crate and Bitcoin Core agree on a PSBT.
+//!
+//! This crate has diverged from `bitcoin::psbt` and supports fields Core does not, so the only way
+//! to know that a PSBT it produces is understood by Core is to ask Core. [`Psbt::check_conformance`]
+//! sends a PSBT through `walletprocesspsbt`, `finalizepsbt` and `analyzepsbt` and compares the
+//! results, field by field, with what this crate does. The RPC client is provided by the caller
+//! through the [`CoreRpc`] trait so that no particular client has to be depended on. The internal
+//! `_test-core` feature implements it for the `corepc` client used by this crate's own tests.
+
+use core::fmt;
+
+use bitcoin::Amount;
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::{DiffError, Error, Psbt, PsbtDiff};
+
+/// The RPC calls of Bitcoin Core used to check conformance.
+///
+/// PSBTs are passed in their binary serialization, implementations base64 encode and decode them
+/// for the RPC. Errors are reported as strings.
+pub trait CoreRpc {
+    /// Calls `walletprocesspsbt` with `finalize` set to false, which updates and signs the PSBT
+    /// with the wallet's keys.
+    fn wallet_process_psbt(&mut self, psbt: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Calls `finalizepsbt` with `extract` set to false and returns the PSBT.
+    fn finalize_psbt(&mut self, psbt: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Calls `analyzepsbt`.
+    fn analyze_psbt(&mut self, psbt: &[u8]) -> Result<CoreAnalysis, String>;
+}
+
+/// Calls Core using the `corepc` client of a `corepc_node::Node`.
+///
+/// The PSBTs are base64 encoded and decoded here, not by the client, so that the bytes checked are
+/// the ones Core sent.
+#[cfg(feature = "_test-core")]
+impl CoreRpc for corepc_node::Client {
+    fn wallet_process_psbt(&mut self, psbt: &[u8]) -> Result<Vec<u8>, String> {
+        use corepc_node::serde_json::Value;
+
+        // sign, sighashtype, bip32derivs, finalize.
+        let args =
+            [Value::Bool(true), Value::from("DEFAULT"), Value::Bool(true), Value::Bool(false)];
+        corepc_call_psbt(self, "walletprocesspsbt", psbt, &args)
+    }
+
+    fn finalize_psbt(&mut self, psbt: &[u8]) -> Result<Vec<u8>, String> {
+        // extract.
+        corepc_call_psbt(self, "finalizepsbt", psbt, &[false.into()])
+    }
+
+    fn analyze_psbt(&mut self, psbt: &[u8]) -> Result<CoreAnalysis, String> {
+        use base64::prelude::{Engine as _, BASE64_STANDARD};
+        use corepc_node::serde_json::Value;
+
+        let result = self
+            .call::<Value>("analyzepsbt", &[BASE64_STANDARD.encode(psbt).into()])
+            .map_err(|e| e.to_string())?;
+        let inputs = result["inputs"]
+            .as_array()
+            .ok_or("no inputs in result")?
+            .iter()
+            .map(|input| CoreInputAnalysis {
+                has_utxo: input["has_utxo"].as_bool().unwrap_or(false),
+                is_final: input["is_final"].as_bool().unwrap_or(false),
+            })
+            .collect();
+        let fee = match result["fee"].as_f64() {
+            Some(btc) => Some(Amount::from_btc(btc).map_err(|e| e.to_string())?),
+            None => None,
+        };
+        Ok(CoreAnalysis { inputs, fee })
+    }
+}
+
+/// Calls `method` with the base64 encoding of `psbt` followed by `args`, returns the decoded
+/// `psbt` field of the result.
+#[cfg(feature = "_test-core")]
+fn corepc_call_psbt(
+    client: &corepc_node::Client,
+    method: &str,
+    psbt: &[u8],
+    args: &[corepc_node::serde_json::Value],
+) -> Result<Vec<u8>, String> {
+    use base64::prelude::{Engine as _, BASE64_STANDARD};
+    use corepc_node::serde_json::Value;
+
+    let mut params = vec![Value::from(BASE64_STANDARD.encode(psbt))];
+    params.extend_from_slice(args);
+    let result = client.call::<Value>(method, &params).map_err(|e| e.to_string())?;
+    let base64 = result["psbt"].as_str().ok_or("no psbt in result")?;
+    BASE64_STANDARD.decode(base64).map_err(|e| e.to_string())
+}
+
+/// The parts of the result of `analyzepsbt` that are compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoreAnalysis {
+    /// The analysis of each input, in order.
+    pub inputs: Vec<CoreInputAnalysis>,
+    /// The `fee` field, if present.
+    pub fee: Option<Amount>,
+}
+
+/// The analysis of an input by `analyzepsbt`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CoreInputAnalysis {
+    /// The `has_utxo` field.
+    pub has_utxo: bool,
+    /// The `is_final` field.
+    pub is_final: bool,
+}
+
+/// A Bitcoin Core RPC call returning a PSBT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoreMethod {
+    /// `walletprocesspsbt`.
+    WalletProcessPsbt,
+    /// `finalizepsbt`.
+    FinalizePsbt,
+    /// `analyzepsbt`.
+    AnalyzePsbt,
+}
+
+impl fmt::Display for CoreMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            CoreMethod::WalletProcessPsbt => "walletprocesspsbt",
+            CoreMethod::FinalizePsbt => "finalizepsbt",
+            CoreMethod::AnalyzePsbt => "analyzepsbt",
+        })
+    }
+}
+
+/// A difference between what Bitcoin Core and this crate make of a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConformanceIssue {
+    /// The PSBT returned by Core does not serialize back to the same bytes.
+    Reserialized {
+        /// The call that returned the PSBT.
+        method: CoreMethod,
+    },
+    /// The PSBT returned by Core has fields that differ from the expected ones.
+    ///
+    /// For `walletprocesspsbt` these are the fields of the PSBT sent that Core changed or removed,
+    /// fields it added are expected. For `finalizepsbt`, only checked with the `miniscript`
+    /// feature, this is the difference between this crate's finalization and Core's.
+    Fields {
+        /// The call that returned the PSBT.
+        method: CoreMethod,
+        /// The difference from the expected PSBT to Core's.
+        diff: PsbtDiff,
+    },
+    /// Core and this crate disagree on whether the UTXO of an input is known.
+    HasUtxo {
+        /// The index of the input.
+        input_index: usize,
+        /// Whether Core knows the UTXO.
+        core: bool,
+    },
+    /// Core and this crate disagree on whether an input is finalized.
+    IsFinal {
+        /// The index of the input.
+        input_index: usize,
+        /// Whether Core considers the input final.
+        core: bool,
+    },
+    /// Core and this crate compute a different fee.
+    Fee {
+        /// The fee reported by Core.
+        core: Option<Amount>,
+        /// The fee computed by [`Psbt::fee`].
+        ours: Option<Amount>,
+    },
+}
+
+impl fmt::Display for ConformanceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConformanceIssue::*;
+
+        match *self {
+            Reserialized { method } =>
+                write!(f, "the PSBT returned by {} does not reserialize to the same bytes", method),
+            Fields { method, ref diff } =>
+                write!(f, "the PSBT returned by {} differs by {} bytes", method, diff.size()),
+            HasUtxo { input_index, core } =>
+                write!(f, "core says input {} has a UTXO: {}, we disagree", input_index, core),
+            IsFinal { input_index, core } =>
+                write!(f, "core says input {} is final: {}, we disagree", input_index, core),
+            Fee { core, ours } => write!(f, "core computes fee {:?}, we compute {:?}", core, ours),
+        }
+    }
+}
+
+impl Psbt {
+    /// Checks that Bitcoin Core agrees with this crate on this PSBT.
+    ///
+    /// The PSBT is processed by Core's wallet, the result finalized and analyzed by Core. Every
+    /// PSBT returned must reserialize to the bytes Core sent, and the fields Core changes must be
+    /// the expected ones. With the `miniscript` feature the PSBT is also finalized with
+    /// `Psbt::finalize_mut` and compared with Core's finalization. The analysis of the
+    /// finalized PSBT must agree with [`Psbt::spend_utxo`], [`Input::is_finalized`] and
+    /// [`Psbt::fee`].
+    ///
+    /// Returns the finalized PSBT and the issues found, no issues means Core and this crate agree.
+    ///
+    /// [`Input::is_finalized`]: crate::Input::is_finalized
+    pub fn check_conformance<R: CoreRpc>(
+        &self,
+        rpc: &mut R,
+    ) -> Result<(Psbt, Vec<ConformanceIssue>), ConformanceError> {
+        let mut issues = Vec::new();
+
+        let processed = call(rpc, CoreMethod::WalletProcessPsbt, self, &mut issues)?;
+        let mut diff = self.diff(&processed)?;
+        for map in
+            core::iter::once(&mut diff.global).chain(&mut diff.inputs).chain(&mut diff.outputs)
+        {
+            map.added.clear();
+        }
+        if !diff.is_empty() {
+            issues.push(ConformanceIssue::Fields { method: CoreMethod::WalletProcessPsbt, diff });
+        }
+
+        let finalized = call(rpc, CoreMethod::FinalizePsbt, &processed, &mut issues)?;
+        #[cfg(feature = "miniscript")]
+        {
+            let mut ours = processed.clone();
+            let _ = ours.finalize_mut();
+            let diff = ours.diff(&finalized)?;
+            if !diff.is_empty() {
+                issues.push(ConformanceIssue::Fields { method: CoreMethod::FinalizePsbt, diff });
+            }
+        }
+
+        let analysis = rpc.analyze_psbt(&finalized.serialize()).map_err(|message| {
+            ConformanceError::Rpc { method: CoreMethod::AnalyzePsbt, message }
+        })?;
+        if analysis.inputs.len() != finalized.inputs.len() {
+            return Err(ConformanceError::InputCount);
+        }
+        for (input_index, core) in analysis.inputs.iter().enumerate() {
+            if core.has_utxo != finalized.spend_utxo(input_index).is_ok() {
+                issues.push(ConformanceIssue::HasUtxo { input_index, core: core.has_utxo });
+            }
+            if core.is_final != finalized.inputs[input_index].is_finalized() {
+                issues.push(ConformanceIssue::IsFinal { input_index, core: core.is_final });
+            }
+        }
+        let ours = finalized.fee().ok();
+        if analysis.fee != ours {
+            issues.push(ConformanceIssue::Fee { core: analysis.fee, ours });
+        }
+
+        Ok((finalized, issues))
+    }
+}
+
+/// Sends `psbt` to Core and parses the PSBT returned, checking it reserializes to the same bytes.
+fn call<R: CoreRpc>(
+    rpc: &mut R,
+    method: CoreMethod,
+    psbt: &Psbt,
+    issues: &mut Vec<ConformanceIssue>,
+) -> Result<Psbt, ConformanceError> {
+    let bytes = psbt.serialize();
+    let bytes = match method {
+        CoreMethod::WalletProcessPsbt => rpc.wallet_process_psbt(&bytes),
+        CoreMethod::FinalizePsbt => rpc.finalize_psbt(&bytes),
+        CoreMethod::AnalyzePsbt => unreachable!("analyzepsbt does not return a PSBT"),
+    }
+    .map_err(|message| ConformanceError::Rpc { method, message })?;
+
+    let returned =
+        Psbt::deserialize(&bytes).map_err(|error| ConformanceError::Parse { method, error })?;
+    if returned.serialize() != bytes {
+        issues.push(ConformanceIssue::Reserialized { method });
+    }
+    Ok(returned)
+}
+
+/// Error checking conformance with Bitcoin Core, see [`Psbt::check_conformance`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConformanceError {
+    /// An RPC call failed.
+    Rpc {
+        /// The call that failed.
+        method: CoreMethod,
+        /// The error returned by the RPC client.
+        message: String,
+    },
+    /// The PSBT returned by Core can not be parsed.
+    Parse {
+        /// The call that returned the PSBT.
+        method: CoreMethod,
+        /// The parse error.
+        error: Error,
+    },
+    /// Core returned a PSBT with another unsigned transaction.
+    UnsignedTxMismatch,
+    /// The analysis returned by Core does not have one entry per input.
+    InputCount,
+}
+
+bitcoin_internals::impl_from_infallible!(ConformanceError);
+
+impl fmt::Display for ConformanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConformanceError::*;
+
+        match *self {
+            Rpc { method, ref message } => write!(f, "{} failed: {}", method, message),
+            Parse { method, ref error } =>
+                write_err!(f, "the PSBT returned by {} can not be parsed", method; error),
+            UnsignedTxMismatch => f.write_str("core returned a PSBT for another transaction"),
+            InputCount => f.write_str("the analysis does not have one entry per input"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConformanceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ConformanceError::*;
+
+        match *self {
+            Parse { ref error, .. } => Some(error),
+            Rpc { .. } | UnsignedTxMismatch | InputCount => None,
+        }
+    }
+}
+
+impl From<DiffError> for ConformanceError {
+    fn from(e: DiffError) -> Self {
+        match e {
+            DiffError::UnsignedTxMismatch => ConformanceError::UnsignedTxMismatch,
+            DiffError::Parse(error) =>
+                ConformanceError::Parse { method: CoreMethod::WalletProcessPsbt, error },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{absolute, transaction, ScriptBuf, Transaction, TxIn, TxOut};
+
+    use super::*;
+
+    /// Mimics Core on a PSBT with one input spending an unknown output.
+    struct MockCore {
+        drop_unknown: bool,
+    }
+
+    impl CoreRpc for MockCore {
+        fn wallet_process_psbt(&mut self, psbt: &[u8]) -> Result<Vec<u8>, String> {
+            let mut psbt = Psbt::deserialize(psbt).map_err(|e| e.to_string())?;
+            if self.drop_unknown {
+                psbt.unknown.clear();
+            }
+            Ok(psbt.serialize())
+        }
+
+        fn finalize_psbt(&mut self, psbt: &[u8]) -> Result<Vec<u8>, String> { Ok(psbt.to_vec()) }
+
+        fn analyze_psbt(&mut self, psbt: &[u8]) -> Result<CoreAnalysis, String> {
+            let psbt = Psbt::deserialize(psbt).map_err(|e| e.to_string())?;
+            let inputs =
+                vec![CoreInputAnalysis { has_utxo: false, is_final: false }; psbt.inputs.len()];
+            Ok(CoreAnalysis { inputs, fee: Some(Amount::ZERO) })
+        }
+    }
+
+    #[test]
+    fn check_conformance() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new() }],
+        })
+        .unwrap();
+        psbt.unknown.insert(crate::raw::Key { type_value: 0x42, key_data: vec![] }, vec![0x01]);
+
+        let (finalized, issues) =
+            psbt.check_conformance(&mut MockCore { drop_unknown: false }).unwrap();
+        assert_eq!(finalized, psbt);
+        assert_eq!(issues, vec![ConformanceIssue::Fee { core: Some(Amount::ZERO), ours: None }]);
+
+        let (_, issues) = psbt.check_conformance(&mut MockCore { drop_unknown: true }).unwrap();
+        assert!(matches!(
+            issues[0],
+            ConformanceIssue::Fields { method: CoreMethod::WalletProcessPsbt, ref diff }
+                if diff.global.removed.len() == 1
+        ));
+    }
+}
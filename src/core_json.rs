@@ -0,0 +1,897 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Serde support for the JSON layout of Bitcoin Core's `decodepsbt` RPC.
+//!
+//! The derived serde implementation of [`Psbt`] mirrors the structure of this crate's types.
+//! Tooling exchanging JSON with `bitcoind` instead expects the `decodepsbt` layout: a decoded
+//! transaction, scripts as hex annotated with their assembly, type and address, and one object
+//! per input and output. [`CoreJson`] serializes a PSBT in that layout and [`DecodedPsbt`] parses
+//! it back.
+//!
+//! Only the hex encoded data is read when parsing. Annotations such as `asm`, `type`, `address`,
+//! the transaction ids and sizes, and `fee` are ignored. Fields Core does not know about, e.g. the
+//! silent payment fields, are listed under `unknown` as Core does, and are recognized again when
+//! parsing.
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, KeySource, Xpub};
+use bitcoin::hex::{DisplayHex, FromHex};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapTree};
+use bitcoin::{
+    absolute, consensus, transaction, Address, Amount, Network, OutPoint, Script, ScriptBuf,
+    Sequence, Transaction, TxIn, TxOut, Txid, Witness, Wtxid,
+};
+
+use crate::map::Map;
+use crate::prelude::*;
+use crate::serde::{self, de, Deserializer, Serializer};
+use crate::serialize::{Deserialize, Serialize};
+use crate::{map, raw, Input, MapLocation, Output, Psbt, PsbtSighashType};
+
+impl Psbt {
+    /// Returns a view of this PSBT that serializes in the layout of Bitcoin Core's `decodepsbt`.
+    ///
+    /// `network` is used to render the addresses of script pubkeys.
+    pub fn to_core_json(&self, network: Network) -> CoreJson<'_> {
+        CoreJson { psbt: self, network }
+    }
+}
+
+/// A PSBT serialized in the JSON layout of Bitcoin Core's `decodepsbt` RPC.
+///
+/// Created by [`Psbt::to_core_json`].
+#[derive(Debug, Clone, Copy)]
+pub struct CoreJson<'a> {
+    psbt: &'a Psbt,
+    network: Network,
+}
+
+impl<'a> serde::Serialize for CoreJson<'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&JsonPsbt::new(self.psbt, self.network), s)
+    }
+}
+
+/// A PSBT deserialized from the JSON layout of Bitcoin Core's `decodepsbt` RPC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedPsbt(pub Psbt);
+
+impl<'de> serde::Deserialize<'de> for DecodedPsbt {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let json = <JsonPsbt as serde::Deserialize>::deserialize(d)?;
+        json.into_psbt().map(DecodedPsbt)
+    }
+}
+
+impl From<DecodedPsbt> for Psbt {
+    fn from(decoded: DecodedPsbt) -> Self { decoded.0 }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonPsbt {
+    tx: JsonTx,
+    #[serde(default)]
+    global_xpubs: Vec<JsonKeySource>,
+    #[serde(default)]
+    psbt_version: u32,
+    #[serde(default)]
+    proprietary: Vec<JsonProprietary>,
+    #[serde(default)]
+    unknown: BTreeMap<String, String>,
+    inputs: Vec<JsonInput>,
+    outputs: Vec<JsonOutput>,
+    #[serde(
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none",
+        with = "bitcoin::amount::serde::as_btc::opt"
+    )]
+    fee: Option<Amount>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonTx {
+    #[serde(skip_deserializing)]
+    txid: Option<Txid>,
+    #[serde(skip_deserializing)]
+    hash: Option<Wtxid>,
+    version: i32,
+    #[serde(skip_deserializing)]
+    size: usize,
+    #[serde(skip_deserializing)]
+    vsize: usize,
+    #[serde(skip_deserializing)]
+    weight: u64,
+    locktime: u32,
+    vin: Vec<JsonTxIn>,
+    vout: Vec<JsonTxOut>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonTxIn {
+    txid: Txid,
+    vout: u32,
+    #[serde(rename = "scriptSig")]
+    script_sig: JsonScript,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    txinwitness: Vec<String>,
+    sequence: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonTxOut {
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    value: Amount,
+    #[serde(skip_deserializing)]
+    n: u32,
+    #[serde(rename = "scriptPubKey")]
+    script_pubkey: JsonScript,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonUtxo {
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    amount: Amount,
+    #[serde(rename = "scriptPubKey")]
+    script_pubkey: JsonScript,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonScript {
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    asm: Option<String>,
+    hex: String,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    #[serde(rename = "type", skip_deserializing, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+}
+
+/// A BIP 32 derivation, `pubkey` is the extended key for global xpubs.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonKeySource {
+    #[serde(alias = "xpub")]
+    pubkey: String,
+    master_fingerprint: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    leaf_hashes: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonProprietary {
+    identifier: String,
+    subtype: u8,
+    key: String,
+    value: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonTapScriptSig {
+    pubkey: String,
+    leaf_hash: String,
+    sig: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonTapScript {
+    script: String,
+    leaf_ver: u8,
+    control_blocks: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonTapLeaf {
+    depth: u8,
+    leaf_ver: u8,
+    script: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonMusig2Participants {
+    aggregate_pubkey: String,
+    participant_pubkeys: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonMusig2PubNonce {
+    participant_pubkey: String,
+    aggregate_pubkey: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    leaf_hash: Option<String>,
+    pubnonce: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde")]
+struct JsonMusig2PartialSig {
+    participant_pubkey: String,
+    aggregate_pubkey: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    leaf_hash: Option<String>,
+    partial_sig: String,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde", default)]
+struct JsonInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    non_witness_utxo: Option<JsonTx>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    witness_utxo: Option<JsonUtxo>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    partial_signatures: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sighash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redeem_script: Option<JsonScript>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    witness_script: Option<JsonScript>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bip32_derivs: Vec<JsonKeySource>,
+    #[serde(rename = "final_scriptSig", skip_serializing_if = "Option::is_none")]
+    final_script_sig: Option<JsonScript>,
+    #[serde(rename = "final_scriptwitness", skip_serializing_if = "Option::is_none")]
+    final_script_witness: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    ripemd160_preimages: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    sha256_preimages: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    hash160_preimages: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    hash256_preimages: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taproot_key_path_sig: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    taproot_script_path_sigs: Vec<JsonTapScriptSig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    taproot_scripts: Vec<JsonTapScript>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    taproot_bip32_derivs: Vec<JsonKeySource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taproot_internal_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taproot_merkle_root: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    musig2_participant_pubkeys: Vec<JsonMusig2Participants>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    musig2_pubnonces: Vec<JsonMusig2PubNonce>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    musig2_partial_sigs: Vec<JsonMusig2PartialSig>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    unknown: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    proprietary: Vec<JsonProprietary>,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "actual_serde", default)]
+struct JsonOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redeem_script: Option<JsonScript>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    witness_script: Option<JsonScript>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bip32_derivs: Vec<JsonKeySource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    taproot_internal_key: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    taproot_tree: Vec<JsonTapLeaf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    taproot_bip32_derivs: Vec<JsonKeySource>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    musig2_participant_pubkeys: Vec<JsonMusig2Participants>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    unknown: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    proprietary: Vec<JsonProprietary>,
+}
+
+impl JsonPsbt {
+    fn new(psbt: &Psbt, network: Network) -> Self {
+        JsonPsbt {
+            tx: JsonTx::new(&psbt.unsigned_tx, network),
+            global_xpubs: psbt
+                .xpub
+                .iter()
+                .map(|(xpub, source)| JsonKeySource::new(xpub.to_string(), source, &[]))
+                .collect(),
+            psbt_version: psbt.version,
+            proprietary: proprietary(&psbt.proprietary),
+            unknown: psbt.unknown.iter().map(|(key, value)| unknown(key, value)).collect(),
+            inputs: psbt.inputs.iter().map(|input| JsonInput::new(input, network)).collect(),
+            outputs: psbt.outputs.iter().map(JsonOutput::new).collect(),
+            fee: psbt.fee().ok(),
+        }
+    }
+
+    fn into_psbt<E: de::Error>(self) -> Result<Psbt, E> {
+        let mut psbt = Psbt::from_unsigned_tx(self.tx.into_tx()?).map_err(E::custom)?;
+        if self.inputs.len() != psbt.inputs.len() || self.outputs.len() != psbt.outputs.len() {
+            return Err(E::custom("number of inputs or outputs does not match the transaction"));
+        }
+
+        psbt.version = self.psbt_version;
+        for xpub in self.global_xpubs {
+            let key = xpub.pubkey.parse::<Xpub>().map_err(E::custom)?;
+            psbt.xpub.insert(key, xpub.key_source()?);
+        }
+        psbt.proprietary = parse_proprietary(self.proprietary)?;
+        for (key, value) in self.unknown {
+            let pair = parse_unknown(&key, &value)?;
+            psbt.unknown.insert(pair.key, pair.value);
+        }
+        for (input, json) in psbt.inputs.iter_mut().zip(self.inputs) {
+            json.decode(input)?;
+        }
+        for (output, json) in psbt.outputs.iter_mut().zip(self.outputs) {
+            json.decode(output)?;
+        }
+        Ok(psbt)
+    }
+}
+
+impl JsonTx {
+    fn new(tx: &Transaction, network: Network) -> Self {
+        JsonTx {
+            txid: Some(tx.compute_txid()),
+            hash: Some(tx.compute_wtxid()),
+            version: tx.version.0,
+            size: tx.total_size(),
+            vsize: tx.vsize(),
+            weight: tx.weight().to_wu(),
+            locktime: tx.lock_time.to_consensus_u32(),
+            vin: tx
+                .input
+                .iter()
+                .map(|txin| JsonTxIn {
+                    txid: txin.previous_output.txid,
+                    vout: txin.previous_output.vout,
+                    script_sig: JsonScript::new_script_sig(&txin.script_sig),
+                    txinwitness: txin
+                        .witness
+                        .iter()
+                        .map(|item| item.to_lower_hex_string())
+                        .collect(),
+                    sequence: txin.sequence.to_consensus_u32(),
+                })
+                .collect(),
+            vout: tx
+                .output
+                .iter()
+                .enumerate()
+                .map(|(n, txout)| JsonTxOut {
+                    value: txout.value,
+                    n: n as u32,
+                    script_pubkey: JsonScript::new(&txout.script_pubkey, Some(network)),
+                })
+                .collect(),
+        }
+    }
+
+    fn into_tx<E: de::Error>(self) -> Result<Transaction, E> {
+        let input = self
+            .vin
+            .into_iter()
+            .map(|txin| {
+                let witness = txin
+                    .txinwitness
+                    .iter()
+                    .map(|item| Vec::<u8>::from_hex(item).map_err(E::custom))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(TxIn {
+                    previous_output: OutPoint { txid: txin.txid, vout: txin.vout },
+                    script_sig: txin.script_sig.script()?,
+                    sequence: Sequence::from_consensus(txin.sequence),
+                    witness: Witness::from_slice(&witness),
+                })
+            })
+            .collect::<Result<_, E>>()?;
+        let output = self
+            .vout
+            .into_iter()
+            .map(|txout| {
+                Ok(TxOut { value: txout.value, script_pubkey: txout.script_pubkey.script()? })
+            })
+            .collect::<Result<_, E>>()?;
+
+        Ok(Transaction {
+            version: transaction::Version(self.version),
+            lock_time: absolute::LockTime::from_consensus(self.locktime),
+            input,
+            output,
+        })
+    }
+}
+
+impl JsonScript {
+    /// Describes `script`, including its address if `network` is given.
+    fn new(script: &Script, network: Option<Network>) -> Self {
+        JsonScript {
+            asm: Some(script.to_asm_string()),
+            hex: script.to_hex_string(),
+            address: network
+                .and_then(|network| Address::from_script(script, network).ok())
+                .map(|address| address.to_string()),
+            kind: Some(script_type(script).to_owned()),
+        }
+    }
+
+    /// Describes a scriptSig, which Core annotates with its assembly only.
+    fn new_script_sig(script: &Script) -> Self {
+        JsonScript { kind: None, ..JsonScript::new(script, None) }
+    }
+
+    fn script<E: de::Error>(&self) -> Result<ScriptBuf, E> {
+        ScriptBuf::from_hex(&self.hex).map_err(E::custom)
+    }
+}
+
+impl JsonKeySource {
+    fn new(pubkey: String, (fingerprint, path): &KeySource, leaf_hashes: &[TapLeafHash]) -> Self {
+        JsonKeySource {
+            pubkey,
+            master_fingerprint: fingerprint.to_string(),
+            path: if path.is_empty() { "m".to_owned() } else { format!("m/{}", path) },
+            leaf_hashes: leaf_hashes.iter().map(to_hex).collect(),
+        }
+    }
+
+    fn key_source<E: de::Error>(&self) -> Result<KeySource, E> {
+        let fingerprint = self.master_fingerprint.parse::<Fingerprint>().map_err(E::custom)?;
+        let path = self.path.parse::<DerivationPath>().map_err(E::custom)?;
+        Ok((fingerprint, path))
+    }
+
+    fn leaf_hashes<E: de::Error>(&self) -> Result<Vec<TapLeafHash>, E> {
+        self.leaf_hashes.iter().map(|hash| from_hex(hash)).collect()
+    }
+}
+
+impl JsonInput {
+    fn new(input: &Input, network: Network) -> Self {
+        let mut taproot_scripts = BTreeMap::<_, Vec<&ControlBlock>>::new();
+        for (control_block, (script, leaf_ver)) in &input.tap_scripts {
+            taproot_scripts
+                .entry((script, leaf_ver.to_consensus()))
+                .or_default()
+                .push(control_block);
+        }
+
+        JsonInput {
+            non_witness_utxo: input.non_witness_utxo.as_ref().map(|tx| JsonTx::new(tx, network)),
+            witness_utxo: input.witness_utxo.as_ref().map(|utxo| JsonUtxo {
+                amount: utxo.value,
+                script_pubkey: JsonScript::new(&utxo.script_pubkey, Some(network)),
+            }),
+            partial_signatures: input
+                .partial_sigs
+                .iter()
+                .map(|(pubkey, sig)| (to_hex(pubkey), to_hex(sig)))
+                .collect(),
+            sighash: input.sighash_type.map(sighash_to_str),
+            redeem_script: input.redeem_script.as_ref().map(|s| JsonScript::new(s, None)),
+            witness_script: input.witness_script.as_ref().map(|s| JsonScript::new(s, None)),
+            bip32_derivs: input
+                .bip32_derivation
+                .iter()
+                .map(|(pubkey, source)| JsonKeySource::new(to_hex(pubkey), source, &[]))
+                .collect(),
+            final_script_sig: input
+                .final_script_sig
+                .as_ref()
+                .map(|s| JsonScript::new_script_sig(s)),
+            final_script_witness: input
+                .final_script_witness
+                .as_ref()
+                .map(|witness| witness.iter().map(|item| item.to_lower_hex_string()).collect()),
+            ripemd160_preimages: preimages(&input.ripemd160_preimages),
+            sha256_preimages: preimages(&input.sha256_preimages),
+            hash160_preimages: preimages(&input.hash160_preimages),
+            hash256_preimages: preimages(&input.hash256_preimages),
+            taproot_key_path_sig: input.tap_key_sig.as_ref().map(to_hex),
+            taproot_script_path_sigs: input
+                .tap_script_sigs
+                .iter()
+                .map(|((pubkey, leaf_hash), sig)| JsonTapScriptSig {
+                    pubkey: to_hex(pubkey),
+                    leaf_hash: to_hex(leaf_hash),
+                    sig: to_hex(sig),
+                })
+                .collect(),
+            taproot_scripts: taproot_scripts
+                .into_iter()
+                .map(|((script, leaf_ver), control_blocks)| JsonTapScript {
+                    script: script.to_hex_string(),
+                    leaf_ver,
+                    control_blocks: control_blocks.into_iter().map(to_hex).collect(),
+                })
+                .collect(),
+            taproot_bip32_derivs: tap_key_origins(&input.tap_key_origins),
+            taproot_internal_key: input.tap_internal_key.as_ref().map(to_hex),
+            taproot_merkle_root: input.tap_merkle_root.as_ref().map(to_hex),
+            musig2_participant_pubkeys: musig2_participants(&input.musig2_participant_pubkeys),
+            musig2_pubnonces: input
+                .musig2_pub_nonces
+                .iter()
+                .map(|((participant, aggregate, leaf_hash), nonce)| JsonMusig2PubNonce {
+                    participant_pubkey: to_hex(participant),
+                    aggregate_pubkey: to_hex(aggregate),
+                    leaf_hash: leaf_hash.as_ref().map(to_hex),
+                    pubnonce: to_hex(nonce),
+                })
+                .collect(),
+            musig2_partial_sigs: input
+                .musig2_partial_sigs
+                .iter()
+                .map(|((participant, aggregate, leaf_hash), sig)| JsonMusig2PartialSig {
+                    participant_pubkey: to_hex(participant),
+                    aggregate_pubkey: to_hex(aggregate),
+                    leaf_hash: leaf_hash.as_ref().map(to_hex),
+                    partial_sig: to_hex(sig),
+                })
+                .collect(),
+            unknown: unknown_pairs(input.get_pairs(), MapLocation::Input(0)),
+            proprietary: proprietary(&input.proprietary),
+        }
+    }
+
+    fn decode<E: de::Error>(self, input: &mut Input) -> Result<(), E> {
+        input.non_witness_utxo = self.non_witness_utxo.map(JsonTx::into_tx).transpose()?;
+        input.witness_utxo = self
+            .witness_utxo
+            .map(|utxo| {
+                Ok(TxOut { value: utxo.amount, script_pubkey: utxo.script_pubkey.script()? })
+            })
+            .transpose()?;
+        for (pubkey, sig) in &self.partial_signatures {
+            input.partial_sigs.insert(from_hex(pubkey)?, from_hex(sig)?);
+        }
+        input.sighash_type = self.sighash.as_deref().map(sighash_from_str).transpose()?;
+        input.redeem_script = self.redeem_script.map(|s| s.script()).transpose()?;
+        input.witness_script = self.witness_script.map(|s| s.script()).transpose()?;
+        for deriv in &self.bip32_derivs {
+            input.bip32_derivation.insert(from_hex(&deriv.pubkey)?, deriv.key_source()?);
+        }
+        input.final_script_sig = self.final_script_sig.map(|s| s.script()).transpose()?;
+        input.final_script_witness = self
+            .final_script_witness
+            .map(|items| {
+                let items = items
+                    .iter()
+                    .map(|item| Vec::<u8>::from_hex(item).map_err(E::custom))
+                    .collect::<Result<Vec<_>, E>>()?;
+                Ok(Witness::from_slice(&items))
+            })
+            .transpose()?;
+        input.ripemd160_preimages = parse_preimages(&self.ripemd160_preimages)?;
+        input.sha256_preimages = parse_preimages(&self.sha256_preimages)?;
+        input.hash160_preimages = parse_preimages(&self.hash160_preimages)?;
+        input.hash256_preimages = parse_preimages(&self.hash256_preimages)?;
+        input.tap_key_sig = self.taproot_key_path_sig.as_deref().map(from_hex).transpose()?;
+        for sig in &self.taproot_script_path_sigs {
+            input
+                .tap_script_sigs
+                .insert((from_hex(&sig.pubkey)?, from_hex(&sig.leaf_hash)?), from_hex(&sig.sig)?);
+        }
+        for leaf in &self.taproot_scripts {
+            let script = ScriptBuf::from_hex(&leaf.script).map_err(E::custom)?;
+            let leaf_ver = LeafVersion::from_consensus(leaf.leaf_ver).map_err(E::custom)?;
+            for control_block in &leaf.control_blocks {
+                input.tap_scripts.insert(from_hex(control_block)?, (script.clone(), leaf_ver));
+            }
+        }
+        input.tap_key_origins = parse_tap_key_origins(&self.taproot_bip32_derivs)?;
+        input.tap_internal_key = self.taproot_internal_key.as_deref().map(from_hex).transpose()?;
+        input.tap_merkle_root = self.taproot_merkle_root.as_deref().map(from_hex).transpose()?;
+        input.musig2_participant_pubkeys =
+            parse_musig2_participants(&self.musig2_participant_pubkeys)?;
+        for nonce in &self.musig2_pubnonces {
+            let key =
+                musig2_key(&nonce.participant_pubkey, &nonce.aggregate_pubkey, &nonce.leaf_hash)?;
+            input.musig2_pub_nonces.insert(key, from_hex(&nonce.pubnonce)?);
+        }
+        for sig in &self.musig2_partial_sigs {
+            let key = musig2_key(&sig.participant_pubkey, &sig.aggregate_pubkey, &sig.leaf_hash)?;
+            input.musig2_partial_sigs.insert(key, from_hex(&sig.partial_sig)?);
+        }
+        input.proprietary = parse_proprietary(self.proprietary)?;
+        for (key, value) in &self.unknown {
+            input.insert_pair(parse_unknown(key, value)?).map_err(E::custom)?;
+        }
+        Ok(())
+    }
+}
+
+impl JsonOutput {
+    fn new(output: &Output) -> Self {
+        JsonOutput {
+            redeem_script: output.redeem_script.as_ref().map(|s| JsonScript::new(s, None)),
+            witness_script: output.witness_script.as_ref().map(|s| JsonScript::new(s, None)),
+            bip32_derivs: output
+                .bip32_derivation
+                .iter()
+                .map(|(pubkey, source)| JsonKeySource::new(to_hex(pubkey), source, &[]))
+                .collect(),
+            taproot_internal_key: output.tap_internal_key.as_ref().map(to_hex),
+            taproot_tree: output
+                .tap_tree
+                .iter()
+                .flat_map(TapTree::script_leaves)
+                .map(|leaf| JsonTapLeaf {
+                    depth: leaf.merkle_branch().len() as u8,
+                    leaf_ver: leaf.version().to_consensus(),
+                    script: leaf.script().to_hex_string(),
+                })
+                .collect(),
+            taproot_bip32_derivs: tap_key_origins(&output.tap_key_origins),
+            musig2_participant_pubkeys: musig2_participants(&output.musig2_participant_pubkeys),
+            unknown: unknown_pairs(output.get_pairs(), MapLocation::Output(0)),
+            proprietary: proprietary(&output.proprietary),
+        }
+    }
+
+    fn decode<E: de::Error>(self, output: &mut Output) -> Result<(), E> {
+        output.redeem_script = self.redeem_script.map(|s| s.script()).transpose()?;
+        output.witness_script = self.witness_script.map(|s| s.script()).transpose()?;
+        for deriv in &self.bip32_derivs {
+            output.bip32_derivation.insert(from_hex(&deriv.pubkey)?, deriv.key_source()?);
+        }
+        output.tap_internal_key = self.taproot_internal_key.as_deref().map(from_hex).transpose()?;
+        if !self.taproot_tree.is_empty() {
+            // Reuse the BIP 371 encoding: the depth, leaf version and script of each leaf.
+            let mut bytes = vec![];
+            for leaf in &self.taproot_tree {
+                let script = ScriptBuf::from_hex(&leaf.script).map_err(E::custom)?;
+                bytes.extend([leaf.depth, leaf.leaf_ver]);
+                bytes.extend(consensus::serialize(&script));
+            }
+            output.tap_tree = Some(TapTree::deserialize(&bytes).map_err(E::custom)?);
+        }
+        output.tap_key_origins = parse_tap_key_origins(&self.taproot_bip32_derivs)?;
+        output.musig2_participant_pubkeys =
+            parse_musig2_participants(&self.musig2_participant_pubkeys)?;
+        output.proprietary = parse_proprietary(self.proprietary)?;
+        for (key, value) in &self.unknown {
+            output.insert_pair(parse_unknown(key, value)?).map_err(E::custom)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name Bitcoin Core uses for the type of `script`.
+fn script_type(script: &Script) -> &'static str {
+    if script.is_p2pk() {
+        "pubkey"
+    } else if script.is_p2pkh() {
+        "pubkeyhash"
+    } else if script.is_p2sh() {
+        "scripthash"
+    } else if script.is_multisig() {
+        "multisig"
+    } else if script.is_op_return() {
+        "nulldata"
+    } else if script.is_p2wpkh() {
+        "witness_v0_keyhash"
+    } else if script.is_p2wsh() {
+        "witness_v0_scripthash"
+    } else if script.is_p2tr() {
+        "witness_v1_taproot"
+    } else if script.is_witness_program() {
+        "witness_unknown"
+    } else {
+        "nonstandard"
+    }
+}
+
+/// Formats `sighash_type` the way Core does: `ALL`, `NONE|ANYONECANPAY`, etc.
+fn sighash_to_str(sighash_type: PsbtSighashType) -> String {
+    sighash_type.to_string().replace("SIGHASH_", "")
+}
+
+fn sighash_from_str<E: de::Error>(s: &str) -> Result<PsbtSighashType, E> {
+    if s.starts_with("0x") {
+        return s.parse().map_err(E::custom);
+    }
+    let s = s.split('|').map(|part| format!("SIGHASH_{}", part)).collect::<Vec<_>>().join("|");
+    s.parse().map_err(E::custom)
+}
+
+fn to_hex<T: Serialize>(value: &T) -> String { value.serialize().to_lower_hex_string() }
+
+fn from_hex<T: Deserialize, E: de::Error>(s: &str) -> Result<T, E> {
+    let bytes = Vec::<u8>::from_hex(s).map_err(E::custom)?;
+    T::deserialize(&bytes).map_err(E::custom)
+}
+
+fn preimages<H: Serialize>(preimages: &BTreeMap<H, Vec<u8>>) -> BTreeMap<String, String> {
+    preimages
+        .iter()
+        .map(|(hash, preimage)| (to_hex(hash), preimage.to_lower_hex_string()))
+        .collect()
+}
+
+fn parse_preimages<H: Deserialize + Ord, E: de::Error>(
+    preimages: &BTreeMap<String, String>,
+) -> Result<BTreeMap<H, Vec<u8>>, E> {
+    preimages.iter().map(|(hash, preimage)| Ok((from_hex(hash)?, from_hex(preimage)?))).collect()
+}
+
+fn tap_key_origins(
+    origins: &BTreeMap<bitcoin::XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>,
+) -> Vec<JsonKeySource> {
+    origins
+        .iter()
+        .map(|(pubkey, (leaf_hashes, source))| {
+            JsonKeySource::new(to_hex(pubkey), source, leaf_hashes)
+        })
+        .collect()
+}
+
+fn parse_tap_key_origins<E: de::Error>(
+    derivs: &[JsonKeySource],
+) -> Result<BTreeMap<bitcoin::XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>, E> {
+    derivs
+        .iter()
+        .map(|deriv| Ok((from_hex(&deriv.pubkey)?, (deriv.leaf_hashes()?, deriv.key_source()?))))
+        .collect()
+}
+
+fn musig2_participants(
+    participants: &BTreeMap<bitcoin::secp256k1::PublicKey, Vec<bitcoin::secp256k1::PublicKey>>,
+) -> Vec<JsonMusig2Participants> {
+    participants
+        .iter()
+        .map(|(aggregate, participants)| JsonMusig2Participants {
+            aggregate_pubkey: to_hex(aggregate),
+            participant_pubkeys: participants.iter().map(to_hex).collect(),
+        })
+        .collect()
+}
+
+fn parse_musig2_participants<E: de::Error>(
+    participants: &[JsonMusig2Participants],
+) -> Result<BTreeMap<bitcoin::secp256k1::PublicKey, Vec<bitcoin::secp256k1::PublicKey>>, E> {
+    participants
+        .iter()
+        .map(|entry| {
+            let keys = entry.participant_pubkeys.iter().map(|key| from_hex(key));
+            Ok((from_hex(&entry.aggregate_pubkey)?, keys.collect::<Result<_, E>>()?))
+        })
+        .collect()
+}
+
+fn musig2_key<E: de::Error>(
+    participant: &str,
+    aggregate: &str,
+    leaf_hash: &Option<String>,
+) -> Result<crate::Musig2Key, E> {
+    let leaf_hash = leaf_hash.as_deref().map(from_hex).transpose()?;
+    Ok((from_hex(participant)?, from_hex(aggregate)?, leaf_hash))
+}
+
+fn proprietary(map: &BTreeMap<raw::ProprietaryKey, Vec<u8>>) -> Vec<JsonProprietary> {
+    map.iter()
+        .map(|(key, value)| JsonProprietary {
+            identifier: key.prefix.to_lower_hex_string(),
+            subtype: key.subtype,
+            key: key.key.to_lower_hex_string(),
+            value: value.to_lower_hex_string(),
+        })
+        .collect()
+}
+
+fn parse_proprietary<E: de::Error>(
+    entries: Vec<JsonProprietary>,
+) -> Result<BTreeMap<raw::ProprietaryKey, Vec<u8>>, E> {
+    entries
+        .into_iter()
+        .map(|entry| {
+            let key = raw::ProprietaryKey {
+                prefix: from_hex(&entry.identifier)?,
+                subtype: entry.subtype,
+                key: from_hex(&entry.key)?,
+            };
+            Ok((key, from_hex(&entry.value)?))
+        })
+        .collect()
+}
+
+/// Core lists unknown fields with the key type byte prepended to the key data.
+fn unknown(key: &raw::Key, value: &[u8]) -> (String, String) {
+    let mut bytes = vec![key.type_value];
+    bytes.extend_from_slice(&key.key_data);
+    (bytes.to_lower_hex_string(), value.to_lower_hex_string())
+}
+
+/// Returns the pairs of a map that Core does not understand, i.e. unknown and silent payment fields.
+fn unknown_pairs(pairs: Vec<raw::Pair>, location: MapLocation) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .filter(|pair| {
+            let name = map::field_name(location, pair.key.type_value);
+            name == "unknown" || name.starts_with("sp_")
+        })
+        .map(|pair| unknown(&pair.key, &pair.value))
+        .collect()
+}
+
+fn parse_unknown<E: de::Error>(key: &str, value: &str) -> Result<raw::Pair, E> {
+    let key = Vec::<u8>::from_hex(key).map_err(E::custom)?;
+    let (&type_value, key_data) =
+        key.split_first().ok_or_else(|| E::custom("empty unknown key"))?;
+    Ok(raw::Pair {
+        key: raw::Key { type_value, key_data: key_data.to_vec() },
+        value: from_hex(value)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hex::FromHex;
+
+    use super::*;
+
+    #[test]
+    fn core_json_roundtrip() {
+        // BIP 174 test vector: a P2SH-P2WPKH input and a P2PKH input, with derivation paths.
+        let hex = "70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac000000000001076a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682dc86e2d73fa882920001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000";
+        let psbt = Psbt::deserialize(&Vec::<u8>::from_hex(hex).unwrap()).unwrap();
+
+        let json = serde_json::to_value(psbt.to_core_json(Network::Bitcoin)).unwrap();
+        assert_eq!(json["psbt_version"], 0);
+        assert_eq!(json["tx"]["txid"], psbt.unsigned_tx.compute_txid().to_string());
+        let txout = &psbt.unsigned_tx.output[0];
+        let vout = &json["tx"]["vout"][0];
+        assert_eq!(vout["value"], txout.value.to_btc());
+        assert_eq!(vout["scriptPubKey"]["type"], "pubkeyhash");
+        assert_eq!(
+            vout["scriptPubKey"]["address"],
+            Address::from_script(&txout.script_pubkey, Network::Bitcoin).unwrap().to_string()
+        );
+        let input = &json["inputs"][1];
+        assert_eq!(input["witness_utxo"]["scriptPubKey"]["type"], "scripthash");
+        assert_eq!(input["redeem_script"]["type"], "witness_v0_keyhash");
+        assert!(json["inputs"][0]["final_scriptSig"]["asm"].is_string());
+        assert!(json["inputs"][0]["final_scriptSig"].get("type").is_none());
+
+        let decoded: DecodedPsbt = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.0, psbt);
+    }
+
+    #[test]
+    fn core_json_unknown_and_sighash() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() }],
+        })
+        .unwrap();
+        psbt.inputs[0].sighash_type =
+            Some(bitcoin::sighash::EcdsaSighashType::SinglePlusAnyoneCanPay.into());
+        psbt.inputs[0].unknown.insert(raw::Key { type_value: 0x7f, key_data: vec![1] }, vec![2]);
+        psbt.outputs[0].sp_v0_label = Some(7);
+
+        let json = serde_json::to_value(psbt.to_core_json(Network::Bitcoin)).unwrap();
+        assert_eq!(json["inputs"][0]["sighash"], "SINGLE|ANYONECANPAY");
+        assert_eq!(json["inputs"][0]["unknown"]["7f01"], "02");
+        // Core does not know the silent payment fields, they are listed as unknown.
+        assert_eq!(json["outputs"][0]["unknown"].as_object().unwrap().len(), 1);
+
+        let decoded: DecodedPsbt = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.0, psbt);
+    }
+}
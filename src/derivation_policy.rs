@@ -0,0 +1,351 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Restricting the derivation paths a signer signs with.
+//!
+//! A PSBT tells the signer which keys to sign with through the BIP 32 derivations of its inputs.
+//! A signer that derives whatever key it is asked for can be made to sign for, or send change to,
+//! keys at paths its wallet never scans, e.g. `m/0/0/0/0/0/2147483647`, and the coins are only
+//! recoverable by whoever knows the path. A [`DerivationPolicy`] lists the paths the signer
+//! accepts.
+
+use core::fmt;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath};
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+
+use crate::prelude::*;
+use crate::{GetKey, KeyRequest, Psbt, SigningErrors, SigningKeysMap};
+
+/// The BIP 32 derivation paths a signer accepts.
+///
+/// ```
+/// # use psbt_v0::bitcoin::bip32::DerivationPath;
+/// # use psbt_v0::DerivationPolicy;
+/// let policy = DerivationPolicy::standard();
+/// assert!(policy.check(&"m/84'/0'/0'/1/7".parse::<DerivationPath>().unwrap()).is_ok());
+/// assert!(policy.check(&"m/0/0/0/0/0/0".parse::<DerivationPath>().unwrap()).is_err());
+///
+/// let policy = policy.allow_purpose(1017);
+/// assert!(policy.check(&"m/1017'/0'/0'".parse::<DerivationPath>().unwrap()).is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DerivationPolicy {
+    /// The allowed hardened purposes, any purpose if empty.
+    purposes: Vec<u32>,
+    /// The allowed hardened coin types, any coin type if empty.
+    coin_types: Vec<u32>,
+    /// Whether the purpose, coin type and account levels must be present and hardened.
+    hardened_account: bool,
+    /// The largest unhardened child number.
+    max_index: u32,
+    /// The largest number of levels.
+    max_depth: usize,
+}
+
+impl DerivationPolicy {
+    /// Allows every derivation path.
+    pub fn permissive() -> Self {
+        DerivationPolicy {
+            purposes: vec![],
+            coin_types: vec![],
+            hardened_account: false,
+            max_index: u32::MAX,
+            max_depth: usize::MAX,
+        }
+    }
+
+    /// Allows the BIP 44, 48, 49, 84 and 86 accounts on mainnet and testnet, with at most six
+    /// levels and unhardened child numbers below 1,000,000.
+    pub fn standard() -> Self {
+        DerivationPolicy {
+            purposes: vec![44, 48, 49, 84, 86],
+            coin_types: vec![0, 1],
+            hardened_account: true,
+            max_index: 999_999,
+            max_depth: 6,
+        }
+    }
+
+    /// Returns this policy with the hardened purpose `purpose` allowed too.
+    ///
+    /// A policy allowing any purpose is left unchanged.
+    pub fn allow_purpose(mut self, purpose: u32) -> Self {
+        if !self.purposes.is_empty() && !self.purposes.contains(&purpose) {
+            self.purposes.push(purpose);
+        }
+        self
+    }
+
+    /// Returns this policy with the hardened coin type `coin_type` allowed too.
+    ///
+    /// A policy allowing any coin type is left unchanged.
+    pub fn allow_coin_type(mut self, coin_type: u32) -> Self {
+        if !self.coin_types.is_empty() && !self.coin_types.contains(&coin_type) {
+            self.coin_types.push(coin_type);
+        }
+        self
+    }
+
+    /// Returns this policy with `max_index` as the largest unhardened child number allowed.
+    pub fn max_index(mut self, max_index: u32) -> Self {
+        self.max_index = max_index;
+        self
+    }
+
+    /// Returns this policy with `max_depth` as the largest number of levels allowed.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns this policy requiring, or not, hardened purpose, coin type and account levels.
+    ///
+    /// Hardened levels are then not allowed after an unhardened one either.
+    pub fn hardened_account(mut self, hardened_account: bool) -> Self {
+        self.hardened_account = hardened_account;
+        self
+    }
+
+    /// Checks `path`, a derivation path from the master key, against this policy.
+    pub fn check(&self, path: &DerivationPath) -> Result<(), DerivationPolicyError> {
+        let children = path.as_ref();
+        if children.len() > self.max_depth {
+            return Err(DerivationPolicyError::TooDeep {
+                depth: children.len(),
+                max_depth: self.max_depth,
+            });
+        }
+
+        if self.hardened_account && children.len() < 3 {
+            return Err(DerivationPolicyError::MissingLevels { depth: children.len() });
+        }
+        for (level, allowed) in [&self.purposes, &self.coin_types].iter().enumerate() {
+            if allowed.is_empty() {
+                continue;
+            }
+            let child = match children.get(level) {
+                Some(child) => *child,
+                None => return Err(DerivationPolicyError::MissingLevels { depth: children.len() }),
+            };
+            let allows = match child {
+                ChildNumber::Hardened { index } => allowed.contains(&index),
+                ChildNumber::Normal { .. } => false,
+            };
+            if !allows {
+                return Err(if level == 0 {
+                    DerivationPolicyError::Purpose(child)
+                } else {
+                    DerivationPolicyError::CoinType(child)
+                });
+            }
+        }
+
+        let mut unhardened = false;
+        for (level, child) in children.iter().enumerate() {
+            match *child {
+                ChildNumber::Hardened { .. } if self.hardened_account && unhardened =>
+                    return Err(DerivationPolicyError::Unhardened(*child)),
+                ChildNumber::Hardened { .. } => {}
+                ChildNumber::Normal { .. } if self.hardened_account && level < 3 =>
+                    return Err(DerivationPolicyError::Unhardened(*child)),
+                ChildNumber::Normal { index } if index > self.max_index =>
+                    return Err(DerivationPolicyError::IndexTooLarge(*child)),
+                ChildNumber::Normal { .. } => unhardened = true,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the first key origin of input `input_index` of `psbt` that `k` has the key for and
+    /// this policy does not allow.
+    ///
+    /// Keys `k` only finds by their public key are not derived by the signer and not checked.
+    pub(crate) fn disallowed_input<C: Signing, K: GetKey>(
+        &self,
+        psbt: &Psbt,
+        input_index: usize,
+        k: &K,
+        secp: &Secp256k1<C>,
+    ) -> Option<DerivationPolicyError> {
+        let input = psbt.inputs.get(input_index)?;
+        let ecdsa = input.bip32_derivation.values();
+        let taproot = input.tap_key_origins.values().map(|(_, source)| source);
+        ecdsa
+            .chain(taproot)
+            .filter(|source| {
+                matches!(k.get_key(&KeyRequest::Bip32((*source).clone()), secp), Ok(Some(_)))
+            })
+            .find_map(|(_, path)| self.check(path).err())
+    }
+}
+
+impl Psbt {
+    /// Signs the PSBT like [`Psbt::sign`], refusing to sign inputs asking for a key at a path
+    /// `policy` does not allow.
+    ///
+    /// Only the key origins `k` has the key for are checked. Inputs that are refused are reported
+    /// with [`SignError::DisallowedDerivation`] and not signed at all, the others are signed.
+    ///
+    /// [`SignError::DisallowedDerivation`]: crate::SignError::DisallowedDerivation
+    pub fn sign_with_derivation_policy<C, K>(
+        &mut self,
+        k: &K,
+        secp: &Secp256k1<C>,
+        policy: &DerivationPolicy,
+    ) -> Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>
+    where
+        C: Signing + Verification,
+        K: GetKey,
+    {
+        self.sign_inputs(0..self.inputs.len(), k, secp, None, Some(policy))
+    }
+}
+
+/// Error returned when a derivation path is not allowed by a [`DerivationPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DerivationPolicyError {
+    /// The path has more levels than allowed.
+    TooDeep {
+        /// The number of levels of the path.
+        depth: usize,
+        /// The largest number of levels allowed.
+        max_depth: usize,
+    },
+    /// The path lacks the purpose, coin type or account level.
+    MissingLevels {
+        /// The number of levels of the path.
+        depth: usize,
+    },
+    /// The purpose is not allowed.
+    Purpose(ChildNumber),
+    /// The coin type is not allowed.
+    CoinType(ChildNumber),
+    /// An account level is unhardened, or a hardened level follows an unhardened one.
+    Unhardened(ChildNumber),
+    /// An unhardened child number is larger than allowed.
+    IndexTooLarge(ChildNumber),
+}
+
+bitcoin_internals::impl_from_infallible!(DerivationPolicyError);
+
+impl fmt::Display for DerivationPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DerivationPolicyError::*;
+
+        match *self {
+            TooDeep { depth, max_depth } => write!(
+                f,
+                "derivation path of {} levels, the policy allows at most {}",
+                depth, max_depth
+            ),
+            MissingLevels { depth } => write!(
+                f,
+                "derivation path of {} levels lacks the purpose, coin type or account",
+                depth
+            ),
+            Purpose(child) => write!(f, "purpose {} is not allowed by the policy", child),
+            CoinType(child) => write!(f, "coin type {} is not allowed by the policy", child),
+            Unhardened(child) =>
+                write!(f, "child number {} is not hardened where the policy requires", child),
+            IndexTooLarge(child) =>
+                write!(f, "child number {} is larger than the policy allows", child),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DerivationPolicyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use DerivationPolicyError::*;
+
+        match *self {
+            TooDeep { .. }
+            | MissingLevels { .. }
+            | Purpose(_)
+            | CoinType(_)
+            | Unhardened(_)
+            | IndexTooLarge(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::{absolute, transaction, Amount, Network, ScriptBuf, Transaction, TxIn, TxOut};
+
+    use super::*;
+    use crate::SignError;
+
+    #[test]
+    fn derivation_policy() {
+        let check = |policy: &DerivationPolicy, path: &str| policy.check(&path.parse().unwrap());
+        let child = |path: &str| path.parse::<ChildNumber>().unwrap();
+        let policy = DerivationPolicy::standard();
+        assert_eq!(check(&policy, "m/48'/1'/0'/2'/0/3"), Ok(()));
+        assert_eq!(
+            check(&policy, "m/0/0/0/0/0/0/0"),
+            Err(DerivationPolicyError::TooDeep { depth: 7, max_depth: 6 })
+        );
+        assert_eq!(
+            check(&policy, "m/84'/0'"),
+            Err(DerivationPolicyError::MissingLevels { depth: 2 })
+        );
+        assert_eq!(check(&policy, "m/0/0/0"), Err(DerivationPolicyError::Purpose(child("0"))));
+        assert_eq!(
+            check(&policy, "m/84'/2'/0'"),
+            Err(DerivationPolicyError::CoinType(child("2'")))
+        );
+        assert_eq!(
+            check(&policy, "m/84'/0'/0"),
+            Err(DerivationPolicyError::Unhardened(child("0")))
+        );
+        assert_eq!(
+            check(&policy, "m/84'/0'/0'/0/1'"),
+            Err(DerivationPolicyError::Unhardened(child("1'")))
+        );
+        assert_eq!(
+            check(&policy, "m/84'/0'/0'/0/1000000"),
+            Err(DerivationPolicyError::IndexTooLarge(child("1000000")))
+        );
+        assert_eq!(check(&DerivationPolicy::permissive(), "m/0/0/0/0/0/0/0"), Ok(()));
+
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Testnet, &[1; 32]).unwrap();
+        let fingerprint = master.fingerprint(&secp);
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        for (input, path) in psbt.inputs.iter_mut().zip(["m/84'/1'/0'/0/0", "m/0/0/0/0/0/0"]) {
+            let path = path.parse::<DerivationPath>().unwrap();
+            let pk = master.derive_priv(&secp, &path).unwrap().private_key.public_key(&secp);
+            input.witness_utxo = Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(
+                    &bitcoin::CompressedPublicKey(pk).wpubkey_hash(),
+                ),
+            });
+            input.bip32_derivation.insert(pk, (fingerprint, path));
+        }
+        // A cosigner's key at any path is not ours to check.
+        let other = Xpriv::new_master(Network::Testnet, &[2; 32]).unwrap();
+        let other_pk = other.private_key.public_key(&secp);
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(other_pk, (other.fingerprint(&secp), "m/0/1/2/3/4/5/6".parse().unwrap()));
+
+        let (used, errors) = psbt.sign_with_derivation_policy(&master, &secp, &policy).unwrap_err();
+        assert!(used.contains_key(&0));
+        assert_eq!(
+            errors[&1],
+            SignError::DisallowedDerivation(DerivationPolicyError::Purpose(child("0")))
+        );
+        assert!(psbt.inputs[1].partial_sigs.is_empty());
+        assert!(psbt.sign(&master, &secp).is_ok());
+    }
+}
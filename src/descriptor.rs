@@ -0,0 +1,397 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Functionality that requires output descriptors from the `miniscript` crate.
+
+use core::fmt;
+
+use bitcoin::{ScriptBuf, Weight, Witness};
+use bitcoin_internals::write_err;
+use miniscript::descriptor::{DefiniteDescriptorKey, Descriptor};
+use miniscript::{MiniscriptKey, Satisfier, ToPublicKey};
+
+use crate::prelude::*;
+use crate::{IndexOutOfBoundsError, InferError, Input, Psbt};
+
+impl Psbt {
+    /// Returns the maximum weight of the transaction once every input is satisfied.
+    ///
+    /// `descriptors` maps each input index to the descriptor of the output the input spends. The
+    /// weight of the unsigned transaction is summed with the maximum satisfaction weight of each
+    /// descriptor, accounting for the segwit marker and flag if any input spends a segwit output.
+    ///
+    /// # Errors
+    ///
+    /// If an input does not have a descriptor, a descriptor is given for an input index that does
+    /// not exist, or a descriptor can not be satisfied.
+    pub fn max_satisfaction_weight(
+        &self,
+        descriptors: &BTreeMap<usize, Descriptor<DefiniteDescriptorKey>>,
+    ) -> Result<Weight, WeightError> {
+        if let Some((&index, _)) = descriptors.range(self.inputs.len()..).next() {
+            return Err(IndexOutOfBoundsError::Inputs { index, length: self.inputs.len() }.into());
+        }
+
+        let mut weight = self.unsigned_tx.weight();
+        let mut segwit = false;
+        for input_index in 0..self.inputs.len() {
+            let descriptor = descriptors
+                .get(&input_index)
+                .ok_or(WeightError::MissingDescriptor { input_index })?;
+            segwit |= descriptor.desc_type().segwit_version().is_some();
+            weight += descriptor.max_weight_to_satisfy()?;
+        }
+
+        if segwit {
+            // The segwit marker and flag, and the witness element count of every input.
+            weight += Weight::from_witness_data_size(2 + self.inputs.len() as u64);
+        }
+        Ok(weight)
+    }
+
+    /// Finalizes the input at `input_index` using `satisfier` to satisfy `descriptor`.
+    ///
+    /// The satisfaction is driven entirely by `satisfier`, the signatures and preimages stored in
+    /// the input are only used if the satisfier provides them. This allows finalizing inputs with
+    /// conditions the PSBT has no fields for, e.g., a preimage revealed by an external oracle.
+    ///
+    /// On success the final scriptSig and witness are set and, as required by BIP 174, all other
+    /// data except the UTXOs, proprietary, and unknown fields is removed from the input.
+    ///
+    /// # Errors
+    ///
+    /// If the input's UTXO is missing, `descriptor` does not describe the output the input spends,
+    /// or the satisfier can not satisfy `descriptor`. The input is not modified on error.
+    pub fn finalize_input_with<Pk, S>(
+        &mut self,
+        input_index: usize,
+        descriptor: &Descriptor<Pk>,
+        satisfier: S,
+    ) -> Result<(), FinalizeError>
+    where
+        Pk: MiniscriptKey + ToPublicKey,
+        S: Satisfier<Pk>,
+    {
+        self.checked_input(input_index)?;
+        let utxo = self.spend_utxo(input_index).map_err(|_| FinalizeError::MissingUtxo)?;
+        if utxo.script_pubkey != descriptor.script_pubkey() {
+            return Err(FinalizeError::ScriptPubkeyMismatch);
+        }
+
+        let is_p2tr = utxo.script_pubkey.is_p2tr();
+        let (witness, script_sig) = descriptor.get_satisfaction(satisfier)?;
+        // Index checked above.
+        set_final(&mut self.inputs[input_index], is_p2tr, script_sig, witness);
+        Ok(())
+    }
+}
+
+/// Sets the final scriptSig and witness of `input`.
+///
+/// As required by BIP 174, all other data except the UTXOs, proprietary, and unknown fields is
+/// removed from the input.
+///
+/// The Taproot annex of the input, if any, is appended to the witness of a Taproot spend, as
+/// indicated by `is_p2tr`, and dropped otherwise.
+pub(crate) fn set_final(
+    input: &mut Input,
+    is_p2tr: bool,
+    script_sig: ScriptBuf,
+    mut witness: Vec<Vec<u8>>,
+) {
+    if let (Some(annex), true) = (input.tap_annex.take(), is_p2tr && !witness.is_empty()) {
+        witness.push(annex);
+    }
+    *input = Input {
+        non_witness_utxo: input.non_witness_utxo.take(),
+        witness_utxo: input.witness_utxo.take(),
+        final_script_sig: if script_sig.is_empty() { None } else { Some(script_sig) },
+        final_script_witness: if witness.is_empty() {
+            None
+        } else {
+            Some(Witness::from_slice(&witness))
+        },
+        proprietary: core::mem::take(&mut input.proprietary),
+        unknown: core::mem::take(&mut input.unknown),
+        ..Default::default()
+    };
+}
+
+/// Error finalizing a PSBT input.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FinalizeError {
+    /// Input index out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The input has no UTXO information.
+    MissingUtxo,
+    /// The descriptor does not match the script pubkey of the output being spent.
+    ScriptPubkeyMismatch,
+    /// The descriptor could not be satisfied.
+    Satisfaction(miniscript::Error),
+    /// No partial signature has a public key matching the key hash being spent.
+    MissingPubkey,
+    /// The output being spent is a P2SH but the input has no redeem script.
+    MissingRedeemScript,
+    /// The output being spent is a P2WSH but the input has no witness script.
+    MissingWitnessScript,
+    /// The redeem script does not hash to the script pubkey being spent.
+    RedeemScriptMismatch,
+    /// The witness script does not hash to the witness program being spent.
+    WitnessScriptMismatch,
+    /// The input has a redeem script but the output being spent is not a P2SH.
+    UnexpectedRedeemScript,
+    /// The input has a witness script but the output being spent is not a P2WSH.
+    UnexpectedWitnessScript,
+    /// The script being spent is not a valid miniscript.
+    InvalidScript(miniscript::Error),
+    /// There is no key spend signature and none of the Taproot leaf scripts can be satisfied.
+    TaprootUnsatisfiable,
+}
+
+bitcoin_internals::impl_from_infallible!(FinalizeError);
+
+impl fmt::Display for FinalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FinalizeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            MissingUtxo => f.write_str("UTXO information is not present in the input"),
+            ScriptPubkeyMismatch =>
+                f.write_str("descriptor does not match the script pubkey of the spent output"),
+            Satisfaction(ref e) => write_err!(f, "failed to satisfy descriptor"; e),
+            MissingPubkey => f.write_str("no partial signature matches the spent public key hash"),
+            MissingRedeemScript => f.write_str("input spends a P2SH but has no redeem script"),
+            MissingWitnessScript => f.write_str("input spends a P2WSH but has no witness script"),
+            RedeemScriptMismatch =>
+                f.write_str("redeem script does not match the script pubkey of the spent output"),
+            WitnessScriptMismatch =>
+                f.write_str("witness script does not match the witness program being spent"),
+            UnexpectedRedeemScript =>
+                f.write_str("input has a redeem script but does not spend a P2SH"),
+            UnexpectedWitnessScript =>
+                f.write_str("input has a witness script but does not spend a P2WSH"),
+            InvalidScript(ref e) => write_err!(f, "spent script is not a valid miniscript"; e),
+            TaprootUnsatisfiable => f.write_str("no Taproot spend path can be satisfied"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FinalizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use FinalizeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            Satisfaction(ref e) | InvalidScript(ref e) => Some(e),
+            MissingUtxo
+            | ScriptPubkeyMismatch
+            | MissingPubkey
+            | MissingRedeemScript
+            | MissingWitnessScript
+            | RedeemScriptMismatch
+            | WitnessScriptMismatch
+            | UnexpectedRedeemScript
+            | UnexpectedWitnessScript
+            | TaprootUnsatisfiable => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for FinalizeError {
+    fn from(e: IndexOutOfBoundsError) -> Self { FinalizeError::IndexOutOfBounds(e) }
+}
+
+impl From<miniscript::Error> for FinalizeError {
+    fn from(e: miniscript::Error) -> Self { FinalizeError::Satisfaction(e) }
+}
+
+impl From<InferError> for FinalizeError {
+    fn from(e: InferError) -> Self {
+        match e {
+            InferError::MissingRedeemScript => FinalizeError::MissingRedeemScript,
+            InferError::MissingWitnessScript => FinalizeError::MissingWitnessScript,
+            InferError::RedeemScriptMismatch => FinalizeError::RedeemScriptMismatch,
+            InferError::WitnessScriptMismatch => FinalizeError::WitnessScriptMismatch,
+            InferError::UnexpectedRedeemScript => FinalizeError::UnexpectedRedeemScript,
+            InferError::UnexpectedWitnessScript => FinalizeError::UnexpectedWitnessScript,
+            InferError::MissingPubkey => FinalizeError::MissingPubkey,
+            InferError::InvalidScript(e) => FinalizeError::InvalidScript(e),
+            InferError::ScriptPubkeyMismatch => FinalizeError::ScriptPubkeyMismatch,
+            InferError::MissingTapInternalKey
+            | InferError::IncompleteTapTree
+            | InferError::UnknownLeafVersion => FinalizeError::TaprootUnsatisfiable,
+        }
+    }
+}
+
+/// Error calculating the weight of a PSBT using descriptors.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum WeightError {
+    /// No descriptor was provided for an input.
+    MissingDescriptor {
+        /// The index of the input without a descriptor.
+        input_index: usize,
+    },
+    /// A descriptor was provided for an input that does not exist.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The descriptor can not be satisfied.
+    Miniscript(miniscript::Error),
+}
+
+bitcoin_internals::impl_from_infallible!(WeightError);
+
+impl fmt::Display for WeightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use WeightError::*;
+
+        match *self {
+            MissingDescriptor { input_index } =>
+                write!(f, "no descriptor provided for input {}", input_index),
+            IndexOutOfBounds(ref e) => write_err!(f, "descriptor index out of bounds"; e),
+            Miniscript(ref e) => write_err!(f, "descriptor can not be satisfied"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WeightError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use WeightError::*;
+
+        match *self {
+            MissingDescriptor { .. } => None,
+            IndexOutOfBounds(ref e) => Some(e),
+            Miniscript(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for WeightError {
+    fn from(e: IndexOutOfBoundsError) -> Self { WeightError::IndexOutOfBounds(e) }
+}
+
+impl From<miniscript::Error> for WeightError {
+    fn from(e: miniscript::Error) -> Self { WeightError::Miniscript(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::{absolute, ecdsa, transaction, Amount, PublicKey, Transaction, TxIn, TxOut};
+    use miniscript::Preimage32;
+
+    use super::*;
+
+    /// Satisfies with a signature from the PSBT and a preimage only it knows.
+    struct OracleSatisfier<'a> {
+        input: &'a Input,
+        preimage: Preimage32,
+    }
+
+    impl Satisfier<DefiniteDescriptorKey> for OracleSatisfier<'_> {
+        fn lookup_ecdsa_sig(&self, pk: &DefiniteDescriptorKey) -> Option<ecdsa::Signature> {
+            self.input.partial_sig(&pk.to_public_key())
+        }
+
+        fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<Preimage32> {
+            if sha256::Hash::hash(&self.preimage) == *hash {
+                Some(self.preimage)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn finalize_input_with_custom_satisfier() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let preimage = [0x42; 32];
+
+        let descriptor = Descriptor::<DefiniteDescriptorKey>::from_str(&format!(
+            "wsh(and_v(v:pk({}),sha256({})))",
+            pk,
+            sha256::Hash::hash(&preimage)
+        ))
+        .unwrap();
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        let witness_utxo =
+            TxOut { value: Amount::from_sat(10_000), script_pubkey: descriptor.script_pubkey() };
+        psbt.inputs[0].witness_utxo = Some(witness_utxo.clone());
+        psbt.inputs[0].witness_script = Some(descriptor.explicit_script().unwrap());
+        let sig = secp.sign_ecdsa(&Message::from_digest([1; 32]), &sk);
+        psbt.inputs[0].partial_sigs.insert(pk, ecdsa::Signature::sighash_all(sig));
+        // Not a Taproot spend, so the annex is not appended to the witness.
+        psbt.inputs[0].tap_annex = Some(vec![0x50]);
+
+        // The preimage is not in the PSBT so it can only come from the satisfier.
+        let input = psbt.inputs[0].clone();
+        let satisfier = OracleSatisfier { input: &input, preimage: [0; 32] };
+        assert!(matches!(
+            psbt.finalize_input_with(0, &descriptor, satisfier),
+            Err(FinalizeError::Satisfaction(_))
+        ));
+        assert_eq!(psbt.inputs[0], input);
+
+        let satisfier = OracleSatisfier { input: &input, preimage };
+        psbt.finalize_input_with(0, &descriptor, satisfier).unwrap();
+
+        let finalized = &psbt.inputs[0];
+        let witness = finalized.final_script_witness.as_ref().unwrap();
+        assert_eq!(witness.nth(0), Some(&preimage[..]));
+        assert_eq!(witness.last(), Some(input.witness_script.as_ref().unwrap().as_bytes()));
+        assert!(finalized.partial_sigs.is_empty());
+        assert!(finalized.witness_script.is_none());
+        assert!(finalized.tap_annex.is_none());
+        assert_eq!(finalized.witness_utxo, Some(witness_utxo));
+    }
+
+    #[test]
+    fn max_satisfaction_weight_wsh_multi() {
+        let descriptor = Descriptor::<DefiniteDescriptorKey>::from_str(
+            "wsh(multi(2,\
+             03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7,\
+             03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb,\
+             03d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a))",
+        )
+        .unwrap();
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let psbt = Psbt::from_unsigned_tx(unsigned_tx.clone()).unwrap();
+
+        let mut descriptors = BTreeMap::new();
+        assert_eq!(
+            psbt.max_satisfaction_weight(&descriptors),
+            Err(WeightError::MissingDescriptor { input_index: 0 })
+        );
+
+        descriptors.insert(0, descriptor);
+        // Witness: element count, empty element for the CHECKMULTISIG bug, two 73 byte
+        // signatures and the 105 byte witness script with its length prefix.
+        let satisfaction = Weight::from_witness_data_size(1 + 1 + 2 * 73 + 1 + 105);
+        let marker_and_flag = Weight::from_witness_data_size(2);
+        assert_eq!(
+            psbt.max_satisfaction_weight(&descriptors),
+            Ok(unsigned_tx.weight() + marker_and_flag + satisfaction)
+        );
+    }
+}
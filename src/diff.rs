@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Computing and applying the difference between two versions of a PSBT.
+//!
+//! Signing ceremonies over constrained transports (NFC, QR codes) only need to send what a round
+//! added, usually a few signatures, instead of the whole PSBT. The difference is expressed in raw
+//! key-value pairs so it round trips every field, including unknown and proprietary ones.
+
+use core::fmt;
+
+use bitcoin::Txid;
+use bitcoin_internals::write_err;
+
+use crate::map::Map;
+use crate::prelude::*;
+use crate::serialize::Serialize;
+use crate::{raw, Error, Psbt};
+
+impl Psbt {
+    /// Returns the fields `other` adds to, changes in or removes from this PSBT.
+    ///
+    /// Both PSBTs must have the same unsigned transaction, so the transaction itself is never part
+    /// of the difference.
+    pub fn diff(&self, other: &Psbt) -> Result<PsbtDiff, DiffError> {
+        let txid = self.unsigned_tx.compute_txid();
+        if txid != other.unsigned_tx.compute_txid() {
+            return Err(DiffError::UnsignedTxMismatch);
+        }
+
+        let global = MapDiff::new(self.get_pairs(), other.get_pairs());
+        let inputs = self
+            .inputs
+            .iter()
+            .zip(&other.inputs)
+            .map(|(ours, theirs)| MapDiff::new(ours.get_pairs(), theirs.get_pairs()))
+            .collect();
+        let outputs = self
+            .outputs
+            .iter()
+            .zip(&other.outputs)
+            .map(|(ours, theirs)| MapDiff::new(ours.get_pairs(), theirs.get_pairs()))
+            .collect();
+
+        Ok(PsbtDiff { txid, global, inputs, outputs })
+    }
+
+    /// Applies a difference returned by [`Psbt::diff`] to this PSBT.
+    ///
+    /// The patched PSBT is parsed again, so a difference that would produce invalid fields is
+    /// rejected with [`DiffError::Parse`]. This PSBT is unchanged if an error is returned.
+    pub fn apply(&mut self, diff: &PsbtDiff) -> Result<(), DiffError> {
+        if diff.txid != self.unsigned_tx.compute_txid()
+            || diff.inputs.len() != self.inputs.len()
+            || diff.outputs.len() != self.outputs.len()
+        {
+            return Err(DiffError::UnsignedTxMismatch);
+        }
+
+        let mut bytes = b"psbt\xff".to_vec();
+        diff.global.patch(self.get_pairs(), &mut bytes);
+        for (input, diff) in self.inputs.iter().zip(&diff.inputs) {
+            diff.patch(input.get_pairs(), &mut bytes);
+        }
+        for (output, diff) in self.outputs.iter().zip(&diff.outputs) {
+            diff.patch(output.get_pairs(), &mut bytes);
+        }
+
+        let patched = Psbt::deserialize(&bytes).map_err(DiffError::Parse)?;
+        if patched.unsigned_tx != self.unsigned_tx {
+            return Err(DiffError::UnsignedTxMismatch);
+        }
+        *self = patched;
+        Ok(())
+    }
+}
+
+/// The difference between two versions of a PSBT, returned by [`Psbt::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+pub struct PsbtDiff {
+    /// The txid of the unsigned transaction both versions share.
+    pub txid: Txid,
+    /// The difference of the global maps.
+    pub global: MapDiff,
+    /// The difference of each input map, in order.
+    pub inputs: Vec<MapDiff>,
+    /// The difference of each output map, in order.
+    pub outputs: Vec<MapDiff>,
+}
+
+impl PsbtDiff {
+    /// Returns true if both versions of the PSBT are the same.
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty()
+            && self.inputs.iter().all(MapDiff::is_empty)
+            && self.outputs.iter().all(MapDiff::is_empty)
+    }
+
+    /// Returns the PSBT serialized size of the pairs in this difference.
+    pub fn size(&self) -> usize { self.maps().map(MapDiff::size).sum() }
+
+    fn maps(&self) -> impl Iterator<Item = &MapDiff> {
+        core::iter::once(&self.global).chain(&self.inputs).chain(&self.outputs)
+    }
+}
+
+/// The difference between two versions of a single PSBT map.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+pub struct MapDiff {
+    /// Pairs whose key is only in the newer version.
+    pub added: Vec<raw::Pair>,
+    /// Pairs whose key is in both versions, with the value of the newer version.
+    pub changed: Vec<raw::Pair>,
+    /// Keys that are only in the older version.
+    pub removed: Vec<raw::Key>,
+}
+
+impl MapDiff {
+    fn new(ours: Vec<raw::Pair>, theirs: Vec<raw::Pair>) -> Self {
+        let mut ours =
+            ours.into_iter().map(|pair| (pair.key, pair.value)).collect::<BTreeMap<_, _>>();
+        let mut diff = MapDiff::default();
+        for pair in theirs {
+            match ours.remove(&pair.key) {
+                None => diff.added.push(pair),
+                Some(value) if value != pair.value => diff.changed.push(pair),
+                Some(_) => {}
+            }
+        }
+        diff.removed = ours.into_keys().collect();
+        diff
+    }
+
+    /// Returns true if both versions of the map are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Returns the PSBT serialized size of the pairs and keys in this difference.
+    pub fn size(&self) -> usize {
+        let pairs = self.added.iter().chain(&self.changed).map(|pair| pair.serialize().len());
+        let keys = self.removed.iter().map(|key| key.serialize().len());
+        pairs.chain(keys).sum()
+    }
+
+    /// Applies this difference to `pairs` and appends the serialized map to `bytes`.
+    fn patch(&self, pairs: Vec<raw::Pair>, bytes: &mut Vec<u8>) {
+        let mut pairs =
+            pairs.into_iter().map(|pair| (pair.key, pair.value)).collect::<BTreeMap<_, _>>();
+        for key in &self.removed {
+            pairs.remove(key);
+        }
+        for pair in self.added.iter().chain(&self.changed) {
+            pairs.insert(pair.key.clone(), pair.value.clone());
+        }
+        for (key, value) in pairs {
+            bytes.extend(raw::Pair { key, value }.serialize());
+        }
+        bytes.push(0x00);
+    }
+}
+
+/// Error computing or applying the difference between two PSBTs.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DiffError {
+    /// The PSBTs do not share the same unsigned transaction.
+    UnsignedTxMismatch,
+    /// The patched PSBT is invalid.
+    Parse(Error),
+}
+
+bitcoin_internals::impl_from_infallible!(DiffError);
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DiffError::*;
+
+        match *self {
+            UnsignedTxMismatch => f.write_str("the PSBTs have different unsigned transactions"),
+            Parse(ref e) => write_err!(f, "the patched PSBT is invalid"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DiffError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use DiffError::*;
+
+        match *self {
+            UnsignedTxMismatch => None,
+            Parse(ref e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::{absolute, ecdsa, transaction, Amount, PublicKey, Transaction, TxIn, TxOut};
+
+    use super::*;
+
+    #[test]
+    fn diff_and_apply() {
+        let secp = Secp256k1::new();
+        let pk =
+            PublicKey::new(secp256k1::SecretKey::from_slice(&[1; 32]).unwrap().public_key(&secp));
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(1_000),
+                script_pubkey: Default::default(),
+            }],
+        };
+        let mut ours = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        ours.inputs[0].witness_utxo = Some(TxOut::NULL);
+        ours.inputs[1].unknown.insert(raw::Key { type_value: 0xf0, key_data: vec![] }, vec![1]);
+
+        let mut theirs = ours.clone();
+        let sig =
+            ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+                .unwrap();
+        theirs.inputs[0].partial_sigs.insert(pk, sig);
+        theirs.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(2_000), script_pubkey: Default::default() });
+        theirs.inputs[1].unknown.clear();
+
+        let diff = ours.diff(&theirs).unwrap();
+        assert!(diff.global.is_empty());
+        assert_eq!(diff.inputs[0].added.len(), 1);
+        assert_eq!(diff.inputs[0].changed.len(), 1);
+        assert_eq!(diff.inputs[1].removed.len(), 1);
+        assert!(diff.outputs[0].is_empty());
+        assert!(diff.size() < theirs.serialize().len());
+
+        ours.apply(&diff).unwrap();
+        assert_eq!(ours, theirs);
+        assert!(ours.diff(&theirs).unwrap().is_empty());
+
+        let mut other = tx;
+        other.lock_time = absolute::LockTime::from_consensus(1);
+        let mut other = Psbt::from_unsigned_tx(other).unwrap();
+        assert!(matches!(ours.diff(&other), Err(DiffError::UnsignedTxMismatch)));
+        assert!(matches!(other.apply(&diff), Err(DiffError::UnsignedTxMismatch)));
+    }
+}
@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! A human readable, multi-line rendering of a PSBT for debugging.
+
+use core::fmt;
+
+use bitcoin::hex::DisplayHex;
+
+use crate::map::{self, Map};
+use crate::{raw, MapLocation, Psbt};
+
+/// Byte strings longer than this are truncated.
+const MAX_HEX_BYTES: usize = 32;
+
+impl Psbt {
+    /// Returns a multi-line, human readable rendering of this PSBT.
+    ///
+    /// Every field of the global map, the inputs and the outputs is printed by name with long
+    /// values truncated. Inputs are annotated with their [`InputStatus`](crate::InputStatus) and the fee is shown if it
+    /// can be computed. The format is meant for humans and stable, but not for parsing.
+    ///
+    /// With the `base64` feature this is also what the alternate [`Display`](core::fmt::Display)
+    /// form `{:#}` prints.
+    pub fn dump(&self) -> PsbtDump<'_> { PsbtDump(self) }
+}
+
+/// A multi-line, human readable rendering of a PSBT, created by [`Psbt::dump`].
+#[derive(Debug, Clone, Copy)]
+pub struct PsbtDump<'a>(&'a Psbt);
+
+impl<'a> fmt::Display for PsbtDump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let psbt = self.0;
+        let tx = &psbt.unsigned_tx;
+
+        writeln!(
+            f,
+            "psbt v{}: txid {}, version {}, locktime {}",
+            psbt.version,
+            tx.compute_txid(),
+            tx.version.0,
+            tx.lock_time.to_consensus_u32()
+        )?;
+        match psbt.fee() {
+            Ok(fee) => writeln!(f, "  fee: {} sat", fee.to_sat())?,
+            Err(_) => writeln!(f, "  fee: unknown")?,
+        }
+        // The unsigned transaction is summarized above and by the input and output headers.
+        for pair in psbt.get_pairs().iter().filter(|pair| pair.key.type_value != 0x00) {
+            write_pair(f, MapLocation::Global, pair)?;
+        }
+
+        let status = psbt.role_status();
+        for (index, (input, txin)) in psbt.inputs.iter().zip(&tx.input).enumerate() {
+            writeln!(
+                f,
+                "input {}: {}, sequence {:#010x}, {}",
+                index,
+                txin.previous_output,
+                txin.sequence.to_consensus_u32(),
+                status.inputs[index]
+            )?;
+            for pair in input.get_pairs() {
+                write_pair(f, MapLocation::Input(index), &pair)?;
+            }
+        }
+
+        for (index, (output, txout)) in psbt.outputs.iter().zip(&tx.output).enumerate() {
+            writeln!(
+                f,
+                "output {}: {} sat to {}",
+                index,
+                txout.value.to_sat(),
+                Truncated(txout.script_pubkey.as_bytes())
+            )?;
+            for pair in output.get_pairs() {
+                write_pair(f, MapLocation::Output(index), &pair)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_pair(f: &mut fmt::Formatter<'_>, location: MapLocation, pair: &raw::Pair) -> fmt::Result {
+    let name = map::field_name(location, pair.key.type_value);
+    if name == "unknown" {
+        write!(f, "  unknown {:#04x}", pair.key.type_value)?;
+    } else {
+        write!(f, "  {}", name)?;
+    }
+    if !pair.key.key_data.is_empty() {
+        write!(f, " {}", Truncated(&pair.key.key_data))?;
+    }
+    writeln!(f, ": {}", Truncated(&pair.value))
+}
+
+/// Hex encodes bytes, truncating long byte strings.
+struct Truncated<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Truncated<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.0;
+        if bytes.len() <= MAX_HEX_BYTES {
+            write!(f, "{:x}", bytes.as_hex())
+        } else {
+            write!(
+                f,
+                "{:x}..{:x} ({} bytes)",
+                bytes[..16].as_hex(),
+                bytes[bytes.len() - 4..].as_hex(),
+                bytes.len()
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hex::FromHex;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn dump() {
+        let hex = "70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac000000000001076a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682dc86e2d73fa882920001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000";
+        let psbt = Psbt::deserialize(&Vec::<u8>::from_hex(hex).unwrap()).unwrap();
+        let dump = psbt.dump().to_string();
+        let lines = dump.lines().collect::<Vec<_>>();
+
+        assert!(lines[0].starts_with("psbt v0: txid "));
+        assert_eq!(lines[1], "  fee: unknown");
+        assert!(lines[2].starts_with("input 0: ") && lines[2].ends_with(", finalized"));
+        assert_eq!(
+            lines[3],
+            "  final_script_sig: 47304402204759661797c01b036b2592..3fa88292 (106 bytes)"
+        );
+        assert!(lines[4].ends_with(", ready to sign"));
+        assert_eq!(
+            lines[5],
+            "  witness_utxo: 00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787"
+        );
+        assert_eq!(
+            lines[7],
+            "output 0: 199900000 sat to 76a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac"
+        );
+        assert_eq!(lines.len(), 9);
+
+        #[cfg(feature = "base64")]
+        assert_eq!(format!("{:#}", psbt), dump);
+    }
+}
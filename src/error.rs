@@ -8,7 +8,7 @@ use bitcoin::{ecdsa, hashes, io, key, secp256k1, taproot, Transaction};
 use bitcoin_internals::write_err;
 
 use crate::prelude::*;
-use crate::raw;
+use crate::{map, raw, MapLocation};
 
 /// Enum for marking psbt hash error.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -85,6 +85,13 @@ pub enum Error {
     InvalidEcdsaSignature(ecdsa::Error),
     /// Parsing error indicating invalid Taproot signatures
     InvalidTaprootSignature(taproot::SigFromSliceError),
+    /// A fixed size key or value has the wrong length.
+    InvalidLength {
+        /// The length of the key or value.
+        expected: usize,
+        /// The length found.
+        found: usize,
+    },
     /// Parsing error indicating invalid control block
     InvalidControlBlock,
     /// Parsing error indicating invalid leaf version
@@ -95,10 +102,28 @@ pub enum Error {
     TapTree(taproot::IncompleteBuilderError),
     /// Error related to an xpub key
     XPubKey(&'static str),
+    /// Parsing error indicating an invalid MuSig2 value
+    Musig2(&'static str),
+    /// Parsing error indicating an invalid silent payment value
+    SilentPayment(&'static str),
     /// Error related to PSBT version
     Version(&'static str),
     /// PSBT data is not consumed entirely
     PartialDataConsumption,
+    /// The unsigned transaction has more inputs than allowed.
+    TooManyInputs {
+        /// The maximum number of inputs allowed.
+        max: usize,
+    },
+    /// The unsigned transaction has more outputs than allowed.
+    TooManyOutputs {
+        /// The maximum number of outputs allowed.
+        max: usize,
+    },
+    /// Maps were already read from the stream passed to [`crate::PsbtReader::into_psbt`].
+    PartiallyRead,
+    /// A key-value pair is invalid, see [`PairError`].
+    Pair(Box<PairError>),
     /// I/O error.
     Io(io::Error),
 }
@@ -148,14 +173,23 @@ impl fmt::Display for Error {
             InvalidXOnlyPublicKey => f.write_str("invalid xonly public key"),
             InvalidEcdsaSignature(ref e) => write_err!(f, "invalid ECDSA signature"; e),
             InvalidTaprootSignature(ref e) => write_err!(f, "invalid Taproot signature"; e),
+            InvalidLength { expected, found } =>
+                write!(f, "invalid length {}, expected {} bytes", found, expected),
             InvalidControlBlock => f.write_str("invalid control block"),
             InvalidLeafVersion => f.write_str("invalid leaf version"),
             Taproot(s) => write!(f, "Taproot error -  {}", s),
             TapTree(ref e) => write_err!(f, "Taproot tree error"; e),
             XPubKey(s) => write!(f, "xpub key error -  {}", s),
+            Musig2(s) => write!(f, "MuSig2 error - {}", s),
+            SilentPayment(s) => write!(f, "silent payment error - {}", s),
             Version(s) => write!(f, "version error {}", s),
             PartialDataConsumption =>
                 f.write_str("data not consumed entirely when explicitly deserializing"),
+            TooManyInputs { max } => write!(f, "PSBT has more than the maximum of {} inputs", max),
+            TooManyOutputs { max } =>
+                write!(f, "PSBT has more than the maximum of {} outputs", max),
+            PartiallyRead => f.write_str("maps were already read from the PSBT stream"),
+            Pair(ref e) => fmt::Display::fmt(e, f),
             Io(ref e) => write_err!(f, "I/O error"; e),
         }
     }
@@ -170,6 +204,7 @@ impl std::error::Error for Error {
             InvalidHash(ref e) => Some(e),
             ConsensusEncoding(ref e) => Some(e),
             Io(ref e) => Some(e),
+            Pair(ref e) => Some(&e.error),
             InvalidMagic
             | MissingUtxo
             | InvalidSeparator
@@ -192,17 +227,107 @@ impl std::error::Error for Error {
             | InvalidXOnlyPublicKey
             | InvalidEcdsaSignature(_)
             | InvalidTaprootSignature(_)
+            | InvalidLength { .. }
             | InvalidControlBlock
             | InvalidLeafVersion
             | Taproot(_)
             | TapTree(_)
             | XPubKey(_)
+            | Musig2(_)
+            | SilentPayment(_)
             | Version(_)
-            | PartialDataConsumption => None,
+            | PartialDataConsumption
+            | TooManyInputs { .. }
+            | TooManyOutputs { .. }
+            | PartiallyRead => None,
+        }
+    }
+}
+
+/// Error returned when decoding a PSBT under a size budget, see [`Psbt::from_reader_limited`].
+///
+/// [`Psbt::from_reader_limited`]: crate::Psbt::from_reader_limited
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The PSBT is invalid.
+    Decode(Error),
+    /// Decoding the PSBT requires reading more bytes than allowed.
+    TooLarge {
+        /// The maximum number of bytes allowed.
+        max: u64,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(DecodeError);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodeError::*;
+
+        match *self {
+            Decode(ref e) => write_err!(f, "can not decode the PSBT"; e),
+            TooLarge { max } => write!(f, "PSBT data exceeds the maximum of {} bytes", max),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use DecodeError::*;
+
+        match *self {
+            Decode(ref e) => Some(e),
+            TooLarge { .. } => None,
         }
     }
 }
 
+impl From<Error> for DecodeError {
+    fn from(e: Error) -> Self { Self::Decode(e) }
+}
+
+/// A key-value pair that failed to decode, with the map it is in.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct PairError {
+    /// The map the pair is in.
+    pub location: MapLocation,
+    /// The key of the pair.
+    pub key: raw::Key,
+    /// Why the pair is invalid.
+    pub error: Error,
+}
+
+impl PairError {
+    /// Wraps the error decoding the pair with key `key` in the map at `location`.
+    pub(crate) fn wrap(location: MapLocation, key: raw::Key) -> impl FnOnce(Error) -> Error {
+        move |error| Error::Pair(Box::new(PairError { location, key, error }))
+    }
+
+    /// Returns the name of the PSBT field the pair belongs to, e.g., `partial_sigs`.
+    pub fn field(&self) -> &'static str { map::field_name(self.location, self.key.type_value) }
+}
+
+impl fmt::Display for PairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_err!(
+            f,
+            "invalid {} (key type {:#04x}) in the {} map",
+            self.field(),
+            self.key.type_value,
+            self.location;
+            self.error
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PairError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { Some(&self.error) }
+}
+
 impl From<hashes::FromSliceError> for Error {
     fn from(e: hashes::FromSliceError) -> Error { Error::InvalidHash(e) }
 }
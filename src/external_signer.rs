@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! A common interface for hardware wallets, remote signers and software keys.
+//!
+//! Signers differ in what they return: HWI and most hardware wallet clients return the whole
+//! signed PSBT, other devices only return the signatures they made. The adapters in this module
+//! turn both conventions into a [`PsbtSigner`] so a coordinator can drive a list of
+//! `Box<dyn PsbtSigner>` without caring which kind each one is.
+
+use core::fmt;
+
+use bitcoin::secp256k1::{All, Secp256k1};
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::{CombineError, GetKey, Input, Psbt, SigningErrors};
+
+/// A signer that adds its signatures to a PSBT.
+///
+/// The trait is object safe, errors from the device or transport are reported as strings.
+pub trait PsbtSigner {
+    /// Adds this signer's signatures to `psbt`.
+    ///
+    /// # Errors
+    ///
+    /// If the signer fails or returns data that does not belong to `psbt`. The PSBT may have been
+    /// partially signed.
+    fn sign_psbt(&mut self, psbt: &mut Psbt) -> Result<SignOutcome, SignerError>;
+}
+
+/// The inputs a [`PsbtSigner`] added signatures to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignOutcome {
+    /// The indices of the inputs that have more signatures than before, in order.
+    pub signed_inputs: Vec<usize>,
+}
+
+impl SignOutcome {
+    /// Returns the inputs of `after` that have more signatures than in `before`.
+    pub(crate) fn compare(before: &[usize], after: &Psbt) -> Self {
+        let signed_inputs = signature_counts(after)
+            .into_iter()
+            .zip(before)
+            .enumerate()
+            .filter(|(_, (after, before))| after > before)
+            .map(|(index, _)| index)
+            .collect();
+        SignOutcome { signed_inputs }
+    }
+
+    /// Returns true if no input was signed.
+    pub fn is_empty(&self) -> bool { self.signed_inputs.is_empty() }
+}
+
+/// Returns the number of signatures of each input, finalized inputs count as signed.
+pub(crate) fn signature_counts(psbt: &Psbt) -> Vec<usize> {
+    psbt.inputs
+        .iter()
+        .map(|input| {
+            input.partial_sigs.len()
+                + input.tap_script_sigs.len()
+                + input.musig2_partial_sigs.len()
+                + usize::from(input.tap_key_sig.is_some())
+                + usize::from(input.is_finalized())
+        })
+        .collect()
+}
+
+/// Adapts a signer that returns the whole signed PSBT, e.g. HWI's `signtx`.
+///
+/// The returned PSBT is combined into the one being signed, so a device that drops fields it does
+/// not understand loses nothing.
+pub struct FullPsbtSigner<F>(pub F);
+
+impl<F> PsbtSigner for FullPsbtSigner<F>
+where
+    F: FnMut(&Psbt) -> Result<Psbt, String>,
+{
+    fn sign_psbt(&mut self, psbt: &mut Psbt) -> Result<SignOutcome, SignerError> {
+        let before = signature_counts(psbt);
+        let signed = (self.0)(psbt).map_err(SignerError::Device)?;
+        psbt.combine_many(core::iter::once(signed)).map_err(SignerError::Combine)?;
+        Ok(SignOutcome::compare(&before, psbt))
+    }
+}
+
+/// Adapts a signer that only returns what it added, as input index and an input map holding the
+/// new signatures.
+pub struct PartialSigner<F>(pub F);
+
+impl<F> PsbtSigner for PartialSigner<F>
+where
+    F: FnMut(&Psbt) -> Result<Vec<(usize, Input)>, String>,
+{
+    fn sign_psbt(&mut self, psbt: &mut Psbt) -> Result<SignOutcome, SignerError> {
+        let before = signature_counts(psbt);
+        for (index, input) in (self.0)(psbt).map_err(SignerError::Device)? {
+            psbt.merge_input(index, input).map_err(SignerError::Combine)?;
+        }
+        Ok(SignOutcome::compare(&before, psbt))
+    }
+}
+
+/// Signs with software keys, see [`Psbt::sign`].
+pub struct KeySigner<K> {
+    keys: K,
+    secp: Secp256k1<All>,
+}
+
+impl<K: GetKey> KeySigner<K> {
+    /// Creates a signer using the private keys `keys` provides.
+    pub fn new(keys: K) -> Self { KeySigner { keys, secp: Secp256k1::new() } }
+}
+
+impl<K: GetKey> PsbtSigner for KeySigner<K> {
+    fn sign_psbt(&mut self, psbt: &mut Psbt) -> Result<SignOutcome, SignerError> {
+        let before = signature_counts(psbt);
+        psbt.sign(&self.keys, &self.secp).map_err(|(_, errors)| SignerError::Sign(errors))?;
+        Ok(SignOutcome::compare(&before, psbt))
+    }
+}
+
+/// Error returned by a [`PsbtSigner`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SignerError {
+    /// The device or transport failed.
+    Device(String),
+    /// The signer returned data that can not be combined into the PSBT.
+    Combine(CombineError),
+    /// Signing with a software key failed.
+    Sign(SigningErrors),
+}
+
+bitcoin_internals::impl_from_infallible!(SignerError);
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SignerError::*;
+
+        match *self {
+            Device(ref msg) => write!(f, "signer failed: {}", msg),
+            Combine(ref e) => write_err!(f, "the signer returned data not matching the PSBT"; e),
+            Sign(ref errors) => write!(f, "failed to sign {} inputs", errors.len()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SignerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SignerError::*;
+
+        match *self {
+            Combine(ref e) => Some(e),
+            Device(_) | Sign(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::{
+        absolute, ecdsa, secp256k1, transaction, Amount, NetworkKind, PrivateKey, ScriptBuf,
+        Transaction, TxIn, TxOut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn psbt_signers() {
+        let secp = Secp256k1::new();
+        let keys = (1..=2u8)
+            .map(|i| {
+                let sk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+                let sk = PrivateKey::new(sk, NetworkKind::Test);
+                (sk.public_key(&secp), sk)
+            })
+            .collect::<Vec<_>>();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            let pk = keys[index.min(1)].0;
+            input.witness_utxo = Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+            });
+            input
+                .bip32_derivation
+                .insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+        }
+        let sig =
+            ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+                .unwrap();
+
+        let mut signers: Vec<Box<dyn PsbtSigner>> = vec![
+            Box::new(KeySigner::new(BTreeMap::from([keys[0]]))),
+            Box::new(FullPsbtSigner(|psbt: &Psbt| {
+                let mut psbt = psbt.clone();
+                psbt.inputs[1].partial_sigs.insert(keys[1].0, sig);
+                // Devices may drop fields they do not understand.
+                psbt.inputs[0] = Input::default();
+                Ok(psbt)
+            })),
+            Box::new(PartialSigner(|_: &Psbt| {
+                let mut input = Input::default();
+                input.partial_sigs.insert(keys[1].0, sig);
+                Ok(vec![(2, input)])
+            })),
+        ];
+        let outcomes = signers
+            .iter_mut()
+            .map(|signer| signer.sign_psbt(&mut psbt).unwrap().signed_inputs)
+            .collect::<Vec<_>>();
+        assert_eq!(outcomes, vec![vec![0], vec![1], vec![2]]);
+        assert!(psbt.inputs.iter().all(|input| input.partial_sigs.len() == 1));
+
+        let mut failing = PartialSigner(|_: &Psbt| Err("device disconnected".to_owned()));
+        assert_eq!(
+            failing.sign_psbt(&mut psbt),
+            Err(SignerError::Device("device disconnected".to_owned()))
+        );
+        let mut wrong_tx = FullPsbtSigner(|psbt: &Psbt| {
+            let mut psbt = psbt.clone();
+            psbt.unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+            Ok(psbt)
+        });
+        assert!(matches!(wrong_tx.sign_psbt(&mut psbt), Err(SignerError::Combine(_))));
+    }
+}
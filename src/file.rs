@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Reading and writing PSBT files.
+//!
+//! BIP 174 specifies `.psbt` files as the binary serialization, yet wallets also save and
+//! exchange base64 text, sometimes with a byte order mark, line wrapping or a trailing newline,
+//! and occasionally hex. A [`PsbtFile`] accepts all of these and remembers which one it read so
+//! that it can be written back the same way.
+
+use core::fmt;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::Path;
+
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use bitcoin::hex::{FromHex, HexToBytesError};
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::stream::{MAGIC_BYTES, PSBT_SERPARATOR};
+use crate::{Error, Psbt};
+
+/// The UTF-8 byte order mark some editors put at the start of text files.
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// The encoding of a PSBT file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileFormat {
+    /// The binary serialization, as specified by BIP 174.
+    Binary,
+    /// Base64 text.
+    Base64,
+    /// Hex text.
+    Hex,
+}
+
+/// A PSBT read from or to be written to a file, along with the encoding of the file.
+///
+/// ```
+/// # use psbt_v0::{FileFormat, PsbtFile};
+/// let base64 = "cHNidP8BADMCAAAAAQAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA/////wD/////AAAAAAAAAA==";
+/// let file = PsbtFile::from_bytes(format!("\u{feff}{}\r\n", base64).as_bytes()).unwrap();
+/// assert_eq!(file.format(), FileFormat::Base64);
+/// assert_eq!(file.to_bytes(), format!("{}\n", base64).into_bytes());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtFile {
+    psbt: Psbt,
+    format: FileFormat,
+}
+
+impl PsbtFile {
+    /// Creates a file holding `psbt`, to be written in `format`.
+    pub fn new(psbt: Psbt, format: FileFormat) -> Self { PsbtFile { psbt, format } }
+
+    /// Returns the PSBT.
+    pub fn psbt(&self) -> &Psbt { &self.psbt }
+
+    /// Returns the PSBT, for updating it before writing it back.
+    pub fn psbt_mut(&mut self) -> &mut Psbt { &mut self.psbt }
+
+    /// Returns the PSBT, consuming the file.
+    pub fn into_psbt(self) -> Psbt { self.psbt }
+
+    /// Returns the encoding of the file.
+    pub fn format(&self) -> FileFormat { self.format }
+
+    /// Decodes the contents of a PSBT file.
+    ///
+    /// Files starting with the PSBT magic bytes are binary. Anything else is text: a byte order
+    /// mark and all whitespace are ignored, and the text is hex if it starts with the hex encoding
+    /// of the magic bytes and base64 otherwise.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PsbtFileError> {
+        if bytes.starts_with(MAGIC_BYTES) && bytes.get(MAGIC_BYTES.len()) == Some(&PSBT_SERPARATOR)
+        {
+            let psbt = Psbt::deserialize(bytes).map_err(PsbtFileError::Psbt)?;
+            return Ok(PsbtFile { psbt, format: FileFormat::Binary });
+        }
+
+        let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+        let text = core::str::from_utf8(bytes).map_err(|_| PsbtFileError::NotPsbt)?;
+        let text = text.chars().filter(|c| !c.is_ascii_whitespace()).collect::<String>();
+        if text.is_empty() {
+            return Err(PsbtFileError::NotPsbt);
+        }
+
+        const HEX_MAGIC: &str = "70736274ff";
+        let (data, format) =
+            if text.get(..HEX_MAGIC.len()).map_or(false, |s| s.eq_ignore_ascii_case(HEX_MAGIC)) {
+                (Vec::from_hex(&text).map_err(PsbtFileError::Hex)?, FileFormat::Hex)
+            } else {
+                (BASE64_STANDARD.decode(&text).map_err(PsbtFileError::Base64)?, FileFormat::Base64)
+            };
+        let psbt = Psbt::deserialize(&data).map_err(PsbtFileError::Psbt)?;
+        Ok(PsbtFile { psbt, format })
+    }
+
+    /// Encodes the PSBT in the format of the file, text formats end in a newline.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.format {
+            FileFormat::Binary => self.psbt.serialize(),
+            FileFormat::Base64 => format!("{}\n", self.psbt.to_base64()).into_bytes(),
+            FileFormat::Hex => format!("{}\n", self.psbt.serialize_hex()).into_bytes(),
+        }
+    }
+
+    /// Reads the PSBT file at `path`, see [`PsbtFile::from_bytes`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PsbtFileError> {
+        let bytes = fs::read(path).map_err(PsbtFileError::Io)?;
+        PsbtFile::from_bytes(&bytes)
+    }
+
+    /// Writes the PSBT file to `path`, replacing any existing file.
+    ///
+    /// The file is written to a temporary file in the same directory, flushed to disk and then
+    /// renamed, so `path` either has its previous contents or the whole new PSBT.
+    pub fn save_atomic<P: AsRef<Path>>(&self, path: P) -> Result<(), PsbtFileError> {
+        let path = path.as_ref();
+        let file_name = path.file_name().ok_or_else(|| {
+            PsbtFileError::Io(io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))
+        })?;
+        let mut tmp_name = file_name.to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let write = || -> io::Result<()> {
+            let mut file = fs::File::create(&tmp_path)?;
+            file.write_all(&self.to_bytes())?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, path)
+        };
+        write().map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            PsbtFileError::Io(e)
+        })
+    }
+}
+
+impl From<Psbt> for PsbtFile {
+    /// Creates a binary file holding `psbt`.
+    fn from(psbt: Psbt) -> Self { PsbtFile::new(psbt, FileFormat::Binary) }
+}
+
+/// Error reading or writing a PSBT file.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PsbtFileError {
+    /// Error accessing the file.
+    Io(io::Error),
+    /// The file is neither binary nor text.
+    NotPsbt,
+    /// Error in the base64 encoding of the file.
+    Base64(base64::DecodeError),
+    /// Error in the hex encoding of the file.
+    Hex(HexToBytesError),
+    /// Error decoding the PSBT.
+    Psbt(Error),
+}
+
+bitcoin_internals::impl_from_infallible!(PsbtFileError);
+
+impl fmt::Display for PsbtFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PsbtFileError::*;
+
+        match *self {
+            Io(ref e) => write_err!(f, "error accessing the PSBT file"; e),
+            NotPsbt => f.write_str("the file is not a binary or text PSBT"),
+            Base64(ref e) => write_err!(f, "error in the base64 encoding of the PSBT file"; e),
+            Hex(ref e) => write_err!(f, "error in the hex encoding of the PSBT file"; e),
+            Psbt(ref e) => write_err!(f, "error decoding the PSBT file"; e),
+        }
+    }
+}
+
+impl std::error::Error for PsbtFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use PsbtFileError::*;
+
+        match *self {
+            Io(ref e) => Some(e),
+            Base64(ref e) => Some(e),
+            Hex(ref e) => Some(e),
+            Psbt(ref e) => Some(e),
+            NotPsbt => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{absolute, transaction, Transaction, TxIn};
+
+    use super::*;
+
+    #[test]
+    fn psbt_file() {
+        let psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        let binary = psbt.serialize();
+        let base64 = psbt.to_base64();
+
+        let file = PsbtFile::from_bytes(&binary).unwrap();
+        assert_eq!((file.psbt(), file.format()), (&psbt, FileFormat::Binary));
+        assert_eq!(file.to_bytes(), binary);
+
+        let (head, tail) = base64.split_at(10);
+        let wrapped = format!("\u{feff}  {}\r\n{}\n\n", head, tail);
+        let file = PsbtFile::from_bytes(wrapped.as_bytes()).unwrap();
+        assert_eq!((file.psbt(), file.format()), (&psbt, FileFormat::Base64));
+
+        let hex = psbt.serialize_hex().to_uppercase();
+        let file = PsbtFile::from_bytes(hex.as_bytes()).unwrap();
+        assert_eq!((file.psbt(), file.format()), (&psbt, FileFormat::Hex));
+
+        assert!(matches!(PsbtFile::from_bytes(b""), Err(PsbtFileError::NotPsbt)));
+        assert!(matches!(PsbtFile::from_bytes(&[0xff, 0xfe]), Err(PsbtFileError::NotPsbt)));
+        assert!(matches!(PsbtFile::from_bytes(b"not base64!"), Err(PsbtFileError::Base64(_))));
+        assert!(matches!(PsbtFile::from_bytes(&binary[..10]), Err(PsbtFileError::Psbt(_))));
+
+        let dir = std::env::temp_dir().join(format!("psbt-v0-file-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tx.psbt");
+        fs::write(&path, b"previous").unwrap();
+        PsbtFile::new(psbt.clone(), FileFormat::Base64).save_atomic(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), format!("{}\n", base64).into_bytes());
+        assert_eq!(PsbtFile::load(&path).unwrap().into_psbt(), psbt);
+        assert!(!dir.join("tx.psbt.tmp").exists());
+        assert!(matches!(PsbtFile::load(dir.join("missing.psbt")), Err(PsbtFileError::Io(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,549 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The BIP 174 Finalizer role.
+//!
+//! Inputs are finalized by inferring a descriptor from the script pubkey being spent and the
+//! redeem and witness scripts of the input (see [`Input::infer_descriptor`]), then satisfying it using miniscript with the
+//! signatures and preimages in the input. Taproot inputs are spent using the key spend signature
+//! if there is one, otherwise using the cheapest satisfiable leaf in `tap_scripts`.
+
+use bitcoin::hashes::{hash160, sha256d, Hash};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash};
+use bitcoin::{
+    absolute, ecdsa, relative, taproot, transaction, PublicKey, Script, ScriptBuf, Witness,
+    XOnlyPublicKey,
+};
+use miniscript::{ExtParams, Miniscript, MiniscriptKey, Preimage32, Satisfier, Tap, ToPublicKey};
+
+use crate::descriptor::set_final;
+use crate::prelude::*;
+use crate::{FinalizeError, Input, Psbt};
+
+impl Psbt {
+    /// Finalizes all inputs of this PSBT that are not finalized yet.
+    ///
+    /// Inputs that can not be finalized are left unchanged, the other inputs are finalized even
+    /// if some fail. See [`Psbt::finalize_input`] for how each input is finalized.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of all inputs that could not be finalized, keyed by input index.
+    pub fn finalize_mut(&mut self) -> Result<(), BTreeMap<usize, FinalizeError>> {
+        let mut errors = BTreeMap::new();
+        for index in 0..self.inputs.len() {
+            if self.inputs[index].is_finalized() {
+                continue;
+            }
+            if let Err(e) = self.finalize_input(index) {
+                errors.insert(index, e);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Finalizes all inputs of this PSBT, see [`Psbt::finalize_mut`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the partially finalized PSBT along with the errors of all inputs that could not
+    /// be finalized, keyed by input index.
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt`.
+    pub fn finalize(mut self) -> Result<Psbt, (Psbt, BTreeMap<usize, FinalizeError>)> {
+        match self.finalize_mut() {
+            Ok(()) => Ok(self),
+            Err(errors) => Err((self, errors)),
+        }
+    }
+
+    /// Finalizes the input at `input_index`.
+    ///
+    /// The scripts of the input are interpreted as miniscript and satisfied using the partial
+    /// signatures, Taproot signatures, and hash preimages of the input, and the lock time and
+    /// sequence of the unsigned transaction. Keys only committed to by hash (e.g., `pkh`
+    /// fragments) are looked up in the BIP 32 derivations.
+    ///
+    /// On success the final scriptSig and witness are set and, as required by BIP 174, all other
+    /// data except the UTXOs, proprietary, and unknown fields is removed from the input. Use
+    /// [`Psbt::finalize_mut_with_retention`] to remove the proprietary and unknown fields too.
+    ///
+    /// # Errors
+    ///
+    /// If the input's UTXO is missing, its scripts are inconsistent with the output it spends or
+    /// are not valid miniscript, or it can not be satisfied. The input is not modified on error.
+    pub fn finalize_input(&mut self, input_index: usize) -> Result<(), FinalizeError> {
+        self.checked_input(input_index)?;
+        let spk =
+            &self.spend_utxo(input_index).map_err(|_| FinalizeError::MissingUtxo)?.script_pubkey;
+        let satisfier = PsbtInputSatisfier::new(self, input_index);
+
+        let is_p2tr = spk.is_p2tr();
+        let (witness, script_sig) = if is_p2tr {
+            (tap_witness(&satisfier)?, ScriptBuf::new())
+        } else {
+            let descriptor = self.inputs[input_index].infer_descriptor(spk)?;
+            descriptor.get_satisfaction(satisfier)?
+        };
+        set_final(&mut self.inputs[input_index], is_p2tr, script_sig, witness);
+        Ok(())
+    }
+}
+
+impl Input {
+    /// Returns true if miniscript can satisfy `spk` using the data in this input.
+    ///
+    /// The scripts are interpreted as in [`Psbt::finalize_input`], without a transaction to
+    /// check lock times against.
+    pub(crate) fn miniscript_satisfiable(&self, spk: &Script) -> bool {
+        let satisfier = PsbtInputSatisfier { source: Source::Input(self) };
+        if spk.is_p2tr() {
+            return tap_witness(&satisfier).is_ok();
+        }
+        match self.infer_descriptor(spk) {
+            Ok(descriptor) => descriptor.get_satisfaction(satisfier).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Returns the witness of the cheapest Taproot spend path that `satisfier` can satisfy.
+fn tap_witness(satisfier: &PsbtInputSatisfier) -> Result<Vec<Vec<u8>>, FinalizeError> {
+    let input = satisfier.input();
+    if let Some(sig) = input.tap_key_sig {
+        return Ok(vec![sig.to_vec()]);
+    }
+
+    let keys = input
+        .tap_key_origins
+        .keys()
+        .map(|pk| (pk.to_pubkeyhash(miniscript::SigType::Schnorr), *pk))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut cheapest: Option<(usize, Vec<Vec<u8>>)> = None;
+    for (control_block, (script, ver)) in &input.tap_scripts {
+        if *ver != LeafVersion::TapScript {
+            continue;
+        }
+        let ms = match Miniscript::<XOnlyPublicKey, Tap>::parse_with_ext(
+            script,
+            &ExtParams::allow_all(),
+        ) {
+            Ok(ms) => ms.substitute_raw_pkh(&keys),
+            Err(_) => continue,
+        };
+        let mut witness = match ms.satisfy(satisfier) {
+            Ok(witness) => witness,
+            Err(_) => continue,
+        };
+        witness.push(script.to_bytes());
+        witness.push(control_block.serialize());
+
+        let size = Witness::from_slice(&witness).size();
+        if cheapest.as_ref().map(|(min, _)| size < *min).unwrap_or(true) {
+            cheapest = Some((size, witness));
+        }
+    }
+    cheapest.map(|(_, witness)| witness).ok_or(FinalizeError::TaprootUnsatisfiable)
+}
+
+/// A miniscript [`Satisfier`] using the data in a PSBT input.
+///
+/// Signatures and preimages are looked up in the input, lock times are checked against the
+/// unsigned transaction. Can be used with [`Psbt::finalize_input_with`] to finalize an input
+/// using a known descriptor.
+#[derive(Debug, Clone, Copy)]
+pub struct PsbtInputSatisfier<'a> {
+    source: Source<'a>,
+}
+
+/// Where a [`PsbtInputSatisfier`] finds the input and the transaction spending it.
+#[derive(Debug, Clone, Copy)]
+enum Source<'a> {
+    /// The input at the index of a PSBT, lock times are checked against its unsigned transaction.
+    Psbt(&'a Psbt, usize),
+    /// A lone input, there is no transaction so all lock times are taken as satisfied.
+    Input(&'a Input),
+}
+
+impl<'a> PsbtInputSatisfier<'a> {
+    /// Creates a satisfier for the input at `index` of `psbt`.
+    ///
+    /// # Panics
+    ///
+    /// The satisfier panics when used if `index` is out of bounds.
+    pub fn new(psbt: &'a Psbt, index: usize) -> Self {
+        PsbtInputSatisfier { source: Source::Psbt(psbt, index) }
+    }
+
+    fn input(&self) -> &'a Input {
+        match self.source {
+            Source::Psbt(psbt, index) => &psbt.inputs[index],
+            Source::Input(input) => input,
+        }
+    }
+}
+
+impl<Pk: MiniscriptKey + ToPublicKey> Satisfier<Pk> for PsbtInputSatisfier<'_> {
+    fn lookup_ecdsa_sig(&self, pk: &Pk) -> Option<ecdsa::Signature> {
+        self.input().partial_sigs.get(&pk.to_public_key()).copied()
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<taproot::Signature> { self.input().tap_key_sig }
+
+    fn lookup_tap_leaf_script_sig(
+        &self,
+        pk: &Pk,
+        leaf_hash: &TapLeafHash,
+    ) -> Option<taproot::Signature> {
+        self.input().tap_script_sigs.get(&(pk.to_x_only_pubkey(), *leaf_hash)).copied()
+    }
+
+    fn lookup_tap_control_block_map(
+        &self,
+    ) -> Option<&BTreeMap<ControlBlock, (ScriptBuf, LeafVersion)>> {
+        Some(&self.input().tap_scripts)
+    }
+
+    fn lookup_raw_pkh_pk(&self, hash: &hash160::Hash) -> Option<PublicKey> {
+        self.input()
+            .bip32_derivation
+            .keys()
+            .map(|pk| PublicKey::new(*pk))
+            .find(|pk| pk.pubkey_hash().to_raw_hash() == *hash)
+    }
+
+    fn lookup_raw_pkh_ecdsa_sig(
+        &self,
+        hash: &hash160::Hash,
+    ) -> Option<(PublicKey, ecdsa::Signature)> {
+        self.input()
+            .partial_sigs
+            .iter()
+            .find(|(pk, _)| pk.pubkey_hash().to_raw_hash() == *hash)
+            .map(|(pk, sig)| (*pk, *sig))
+    }
+
+    fn lookup_raw_pkh_tap_leaf_script_sig(
+        &self,
+        (hash, leaf_hash): &(hash160::Hash, TapLeafHash),
+    ) -> Option<(XOnlyPublicKey, taproot::Signature)> {
+        self.input()
+            .tap_script_sigs
+            .iter()
+            .find(|((pk, lh), _)| {
+                pk.to_pubkeyhash(miniscript::SigType::Schnorr) == *hash && lh == leaf_hash
+            })
+            .map(|((pk, _), sig)| (*pk, *sig))
+    }
+
+    fn lookup_sha256(&self, hash: &Pk::Sha256) -> Option<Preimage32> {
+        self.input().sha256_preimages.get(&Pk::to_sha256(hash)).and_then(|p| preimage32(p))
+    }
+
+    fn lookup_hash256(&self, hash: &Pk::Hash256) -> Option<Preimage32> {
+        let hash = sha256d::Hash::from_byte_array(Pk::to_hash256(hash).to_byte_array());
+        self.input().hash256_preimages.get(&hash).and_then(|p| preimage32(p))
+    }
+
+    fn lookup_ripemd160(&self, hash: &Pk::Ripemd160) -> Option<Preimage32> {
+        self.input().ripemd160_preimages.get(&Pk::to_ripemd160(hash)).and_then(|p| preimage32(p))
+    }
+
+    fn lookup_hash160(&self, hash: &Pk::Hash160) -> Option<Preimage32> {
+        self.input().hash160_preimages.get(&Pk::to_hash160(hash)).and_then(|p| preimage32(p))
+    }
+
+    fn check_older(&self, n: relative::LockTime) -> bool {
+        let (tx, index) = match self.source {
+            Source::Psbt(psbt, index) => (&psbt.unsigned_tx, index),
+            Source::Input(_) => return true,
+        };
+        if tx.version < transaction::Version::TWO {
+            return false;
+        }
+        match tx.input[index].sequence.to_relative_lock_time() {
+            Some(lock_time) => n.is_implied_by(lock_time),
+            None => false,
+        }
+    }
+
+    fn check_after(&self, n: absolute::LockTime) -> bool {
+        let (tx, index) = match self.source {
+            Source::Psbt(psbt, index) => (&psbt.unsigned_tx, index),
+            Source::Input(_) => return true,
+        };
+        tx.input[index].enables_lock_time() && n.is_implied_by(tx.lock_time)
+    }
+}
+
+/// Returns `preimage` as a 32 byte array, miniscript only supports preimages of this length.
+fn preimage32(preimage: &[u8]) -> Option<Preimage32> { preimage.try_into().ok() }
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+    use bitcoin::{Amount, TapSighashType, Transaction, TxIn, TxOut};
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    #[test]
+    fn finalize_mut() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sks =
+            [SecretKey::from_slice(&[1; 32]).unwrap(), SecretKey::from_slice(&[2; 32]).unwrap()];
+        let pks = sks.map(|sk| PublicKey::new(sk.public_key(&secp)));
+        let sigs = sks.map(|sk| ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk)));
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 4],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        let utxo = |script_pubkey| Some(TxOut { value: Amount::from_sat(10_000), script_pubkey });
+
+        // P2WPKH.
+        let wpkh = |pk: &PublicKey| ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap());
+        psbt.inputs[0].witness_utxo = utxo(wpkh(&pks[0]));
+        psbt.inputs[0].partial_sigs.insert(pks[0], sigs[0]);
+
+        // P2WSH 2-of-2 multisig.
+        let descriptor =
+            Descriptor::<PublicKey>::from_str(&format!("wsh(multi(2,{},{}))", pks[0], pks[1]))
+                .unwrap();
+        let witness_script = descriptor.explicit_script().unwrap();
+        psbt.inputs[1].witness_utxo = utxo(descriptor.script_pubkey());
+        psbt.inputs[1].witness_script = Some(witness_script.clone());
+        psbt.inputs[1].partial_sigs.insert(pks[0], sigs[0]);
+        psbt.inputs[1].partial_sigs.insert(pks[1], sigs[1]);
+
+        // P2TR key spend.
+        let keypair = Keypair::from_secret_key(&secp, &sks[0]);
+        let (internal_key, _) = keypair.x_only_public_key();
+        let tap_sig = taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            sighash_type: TapSighashType::Default,
+        };
+        psbt.inputs[2].witness_utxo = utxo(ScriptBuf::new_p2tr(&secp, internal_key, None));
+        psbt.inputs[2].tap_internal_key = Some(internal_key);
+        psbt.inputs[2].tap_key_sig = Some(tap_sig);
+
+        // P2WPKH without a signature.
+        psbt.inputs[3].witness_utxo = utxo(wpkh(&pks[1]));
+        let unsigned = psbt.inputs[3].clone();
+
+        let errors = psbt.finalize_mut().unwrap_err();
+        assert_eq!(errors.into_iter().collect::<Vec<_>>(), vec![(3, FinalizeError::MissingPubkey)]);
+        assert_eq!(psbt.inputs[3], unsigned);
+
+        let witness =
+            |index: usize| psbt.inputs[index].final_script_witness.clone().unwrap().to_vec();
+        assert_eq!(witness(0), vec![sigs[0].to_vec(), pks[0].to_bytes()]);
+        assert_eq!(
+            witness(1),
+            vec![vec![], sigs[0].to_vec(), sigs[1].to_vec(), witness_script.into_bytes()]
+        );
+        assert_eq!(witness(2), vec![tap_sig.to_vec()]);
+
+        // Redundant fields are cleared.
+        for input in &psbt.inputs[..3] {
+            assert!(input.final_script_sig.is_none());
+            assert!(input.partial_sigs.is_empty());
+            assert!(input.witness_script.is_none());
+            assert!(input.tap_key_sig.is_none() && input.tap_internal_key.is_none());
+            assert!(input.witness_utxo.is_some());
+        }
+    }
+
+    #[test]
+    fn finalize_raw_pkh() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk));
+
+        // The witness script only commits to the hash of the key.
+        let descriptor = Descriptor::<PublicKey>::from_str(&format!("wsh(pkh({}))", pk)).unwrap();
+        let witness_script = descriptor.explicit_script().unwrap();
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: descriptor.script_pubkey(),
+        });
+        psbt.inputs[0].witness_script = Some(witness_script.clone());
+        psbt.inputs[0].partial_sigs.insert(pk, sig);
+
+        psbt.finalize_input(0).unwrap();
+        assert_eq!(
+            psbt.inputs[0].final_script_witness.clone().unwrap().to_vec(),
+            vec![sig.to_vec(), pk.to_bytes(), witness_script.into_bytes()]
+        );
+    }
+
+    #[test]
+    fn finalize_legacy() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sks = [1u8, 2, 3].map(|i| SecretKey::from_slice(&[i; 32]).unwrap());
+        let pks = sks.map(|sk| PublicKey::new(sk.public_key(&secp)));
+        let uncompressed = PublicKey::new_uncompressed(pks[2].inner);
+        let sigs = sks.map(|sk| ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk)));
+
+        let multi = |threshold: usize, pks: &[PublicKey]| {
+            pks.iter()
+                .fold(bitcoin::script::Builder::new().push_int(threshold as i64), |b, pk| {
+                    b.push_key(pk)
+                })
+                .push_int(pks.len() as i64)
+                .push_opcode(bitcoin::opcodes::all::OP_CHECKMULTISIG)
+                .into_script()
+        };
+        let redeem_script = multi(2, &pks);
+        let uncompressed_redeem_script = multi(2, &[pks[0], uncompressed]);
+        let spks = [
+            ScriptBuf::new_p2pkh(&pks[0].pubkey_hash()),
+            ScriptBuf::new_p2pkh(&uncompressed.pubkey_hash()),
+            redeem_script.to_p2sh(),
+            uncompressed_redeem_script.to_p2sh(),
+            multi(1, &pks[..2]),
+            ScriptBuf::new_p2pk(&pks[1]),
+        ];
+        let prev = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: spks
+                .iter()
+                .map(|spk| TxOut { value: Amount::from_sat(10_000), script_pubkey: spk.clone() })
+                .collect(),
+        };
+        let txid = prev.compute_txid();
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..spks.len() as u32)
+                .map(|vout| TxIn {
+                    previous_output: bitcoin::OutPoint { txid, vout },
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        for input in &mut psbt.inputs {
+            input.non_witness_utxo = Some(prev.clone());
+        }
+        psbt.inputs[0].partial_sigs.insert(pks[0], sigs[0]);
+        psbt.inputs[1].partial_sigs.insert(uncompressed, sigs[2]);
+        psbt.inputs[2].redeem_script = Some(redeem_script.clone());
+        psbt.inputs[2].partial_sigs.insert(pks[2], sigs[2]);
+        psbt.inputs[2].partial_sigs.insert(pks[0], sigs[0]);
+        psbt.inputs[3].redeem_script = Some(uncompressed_redeem_script.clone());
+        psbt.inputs[3].partial_sigs.insert(pks[0], sigs[0]);
+        psbt.inputs[3].partial_sigs.insert(uncompressed, sigs[2]);
+        psbt.inputs[4].partial_sigs.insert(pks[1], sigs[1]);
+        psbt.inputs[5].partial_sigs.insert(pks[1], sigs[1]);
+
+        let estimate = psbt.estimate_weight().unwrap();
+        psbt.finalize_mut().unwrap();
+        assert!(psbt.inputs.iter().all(|input| input.final_script_witness.is_none()));
+        let script_sig = |index: usize| psbt.inputs[index].final_script_sig.clone().unwrap();
+        let push = |builder: bitcoin::script::Builder, data: &[u8]| {
+            builder.push_slice(<&bitcoin::script::PushBytes>::try_from(data).unwrap())
+        };
+        let sig = |i: usize| sigs[i].to_vec();
+        let expected = |pushes: Vec<Vec<u8>>| {
+            pushes
+                .iter()
+                .fold(bitcoin::script::Builder::new(), |b, data| push(b, data))
+                .into_script()
+        };
+
+        assert_eq!(script_sig(0), expected(vec![sig(0), pks[0].to_bytes()]));
+        assert_eq!(script_sig(1), expected(vec![sig(2), uncompressed.to_bytes()]));
+        // The dummy element, then the signatures in the order of the keys.
+        assert_eq!(script_sig(2), expected(vec![vec![], sig(0), sig(2), redeem_script.to_bytes()]));
+        assert_eq!(
+            script_sig(3),
+            expected(vec![vec![], sig(0), sig(2), uncompressed_redeem_script.to_bytes()])
+        );
+        assert_eq!(script_sig(4), expected(vec![vec![], sig(1)]));
+        assert_eq!(script_sig(5), expected(vec![sig(1)]));
+
+        let weight = psbt.extract_tx_unchecked_fee_rate().weight();
+        assert!(estimate >= weight, "{} < {}", estimate, weight);
+        // At most a byte per signature too much.
+        assert!(
+            estimate - weight <= bitcoin::Weight::from_non_witness_data_size(8),
+            "{} {}",
+            estimate,
+            weight
+        );
+    }
+
+    #[test]
+    fn is_satisfiable_preimage() {
+        use bitcoin::hashes::sha256;
+
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk));
+        let preimage = [2; 32];
+        let hash = sha256::Hash::hash(&preimage);
+
+        // Not one of the standard templates, only miniscript understands it.
+        let descriptor = Descriptor::<PublicKey>::from_str(&format!(
+            "wsh(and_v(v:pk({}),sha256({})))",
+            pk, hash
+        ))
+        .unwrap();
+        let spk = descriptor.script_pubkey();
+        let mut input = Input {
+            witness_script: Some(descriptor.explicit_script().unwrap()),
+            ..Default::default()
+        };
+        input.partial_sigs.insert(pk, sig);
+        assert!(!input.is_satisfiable(&spk));
+
+        input.sha256_preimages.insert(hash, preimage.to_vec());
+        assert!(input.is_satisfiable(&spk));
+    }
+
+    #[test]
+    fn is_satisfiable_lock_time() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk));
+
+        let descriptor =
+            Descriptor::<PublicKey>::from_str(&format!("wsh(and_v(v:pk({}),after(100)))", pk))
+                .unwrap();
+        let mut input = Input {
+            witness_script: Some(descriptor.explicit_script().unwrap()),
+            ..Default::default()
+        };
+        input.partial_sigs.insert(pk, sig);
+
+        // The lock time is set by the transaction, not the input.
+        assert!(input.is_satisfiable(&descriptor.script_pubkey()));
+    }
+}
@@ -0,0 +1,535 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Generation of random, valid PSBTs for property tests and fuzzing.
+//!
+//! Every field of the PSBT maps is populated some of the time, values are valid in the sense that
+//! they survive a round trip through the BIP-174 serialization.
+//!
+//! With the `arbitrary` feature, [`Psbt`], [`Input`], and [`Output`] implement
+//! [`arbitrary::Arbitrary`] and [`PsbtPair`] generates the PSBTs two signers return to a combiner.
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::{Arbitrary, Unstructured};
+use bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, KeySource, Xpriv, Xpub};
+use bitcoin::hashes::{hash160, ripemd160, sha256, sha256d, Hash};
+use bitcoin::secp256k1::{self, All, Message, Secp256k1, SecretKey};
+use bitcoin::taproot::{
+    ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TapTree, TaprootBuilder,
+    TaprootMerkleBranch, TAPROOT_ANNEX_PREFIX,
+};
+use bitcoin::{
+    absolute, ecdsa, key, taproot, transaction, Amount, EcdsaSighashType, NetworkKind, OutPoint,
+    PublicKey, ScriptBuf, Sequence, TapSighashType, Transaction, TxIn, TxOut, Txid, Witness,
+    XOnlyPublicKey,
+};
+
+use crate::map::Map;
+use crate::prelude::*;
+use crate::{
+    raw, DleqProof, Input, Musig2Key, Musig2PartialSig, Musig2PubNonce, Output, Psbt,
+    PsbtSighashType,
+};
+
+/// Key type used for unknown key-value pairs, not defined for any of the PSBT maps.
+const UNKNOWN_KEY_TYPE: u8 = 0xE0;
+
+/// A generator of random PSBT data.
+pub(crate) struct Gen<'s> {
+    /// The source of randomness.
+    next: Box<dyn FnMut() -> u64 + 's>,
+    secp: Secp256k1<All>,
+}
+
+impl Gen<'static> {
+    /// Creates a generator, the same `seed` always generates the same values.
+    #[cfg(test)]
+    pub(crate) fn new(seed: u64) -> Self {
+        // The xorshift state must not be zero.
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        let next = move || {
+            // xorshift64*
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        };
+        Gen { next: Box::new(next), secp: Secp256k1::new() }
+    }
+}
+
+impl<'s> Gen<'s> {
+    /// Creates a generator drawing its values from the fuzzer input `u`.
+    ///
+    /// Once `u` is exhausted every value drawn is zero, the generated data is still valid.
+    #[cfg(feature = "arbitrary")]
+    fn from_unstructured<'a: 's>(u: &'s mut Unstructured<'a>) -> Self {
+        Gen { next: Box::new(move || u.arbitrary().unwrap_or(0)), secp: Secp256k1::new() }
+    }
+
+    fn u64(&mut self) -> u64 { (self.next)() }
+
+    fn u32(&mut self) -> u32 { self.u64() as u32 }
+
+    fn u8(&mut self) -> u8 { self.u64() as u8 }
+
+    /// Returns a number in the range `0..n`.
+    fn below(&mut self, n: usize) -> usize { (self.u64() % n as u64) as usize }
+
+    /// Returns true roughly half of the time.
+    fn coin(&mut self) -> bool { self.u64() & 1 == 1 }
+
+    fn bytes(&mut self, max_len: usize) -> Vec<u8> {
+        let len = self.below(max_len + 1);
+        (0..len).map(|_| self.u8()).collect()
+    }
+
+    fn array32(&mut self) -> [u8; 32] {
+        let mut buf = [0; 32];
+        buf.iter_mut().for_each(|b| *b = self.u8());
+        buf
+    }
+
+    fn maybe<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        if self.coin() {
+            Some(f(self))
+        } else {
+            None
+        }
+    }
+
+    fn map<K: Ord, V>(
+        &mut self,
+        max_len: usize,
+        mut f: impl FnMut(&mut Self) -> (K, V),
+    ) -> BTreeMap<K, V> {
+        let len = self.below(max_len + 1);
+        (0..len).map(|_| f(self)).collect()
+    }
+
+    fn secret_key(&mut self) -> SecretKey {
+        // Out of range bytes are rare for random data, but all zero for an exhausted fuzzer input.
+        SecretKey::from_slice(&self.array32())
+            .unwrap_or_else(|_| SecretKey::from_slice(&[1; 32]).expect("valid secret key"))
+    }
+
+    fn secp_public_key(&mut self) -> secp256k1::PublicKey {
+        let sk = self.secret_key();
+        sk.public_key(&self.secp)
+    }
+
+    fn public_key(&mut self) -> PublicKey {
+        let inner = self.secp_public_key();
+        PublicKey { compressed: self.coin(), inner }
+    }
+
+    fn x_only_public_key(&mut self) -> XOnlyPublicKey {
+        self.secp_public_key().x_only_public_key().0
+    }
+
+    fn message(&mut self) -> Message { Message::from_digest(self.array32()) }
+
+    fn script(&mut self) -> ScriptBuf { ScriptBuf::from_bytes(self.bytes(40)) }
+
+    fn key_source(&mut self) -> KeySource {
+        let fingerprint = Fingerprint::from(self.u32().to_be_bytes());
+        let len = self.below(5);
+        let path = (0..len).map(|_| ChildNumber::from(self.u32())).collect::<DerivationPath>();
+        (fingerprint, path)
+    }
+
+    fn xpub(&mut self) -> Xpub {
+        let seed = self.array32();
+        let xpriv = Xpriv::new_master(NetworkKind::Test, &seed).expect("32 byte seed is valid");
+        Xpub::from_priv(&self.secp, &xpriv)
+    }
+
+    fn ecdsa_sig(&mut self) -> ecdsa::Signature {
+        use EcdsaSighashType::*;
+
+        let (msg, sk) = (self.message(), self.secret_key());
+        let types =
+            [All, None, Single, AllPlusAnyoneCanPay, NonePlusAnyoneCanPay, SinglePlusAnyoneCanPay];
+        ecdsa::Signature {
+            signature: self.secp.sign_ecdsa(&msg, &sk),
+            sighash_type: types[self.below(types.len())],
+        }
+    }
+
+    fn taproot_sig(&mut self) -> taproot::Signature {
+        use TapSighashType::*;
+
+        let (msg, sk) = (self.message(), self.secret_key());
+        let keypair = key::Keypair::from_secret_key(&self.secp, &sk);
+        let types = [
+            Default,
+            All,
+            None,
+            Single,
+            AllPlusAnyoneCanPay,
+            NonePlusAnyoneCanPay,
+            SinglePlusAnyoneCanPay,
+        ];
+        taproot::Signature {
+            signature: self.secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            sighash_type: types[self.below(types.len())],
+        }
+    }
+
+    fn leaf_hash(&mut self) -> TapLeafHash { TapLeafHash::from_byte_array(self.array32()) }
+
+    fn musig2_participants(&mut self) -> (secp256k1::PublicKey, Vec<secp256k1::PublicKey>) {
+        let len = self.below(3);
+        (self.secp_public_key(), (0..len).map(|_| self.secp_public_key()).collect())
+    }
+
+    fn musig2_key(&mut self) -> Musig2Key {
+        (self.secp_public_key(), self.secp_public_key(), self.maybe(Self::leaf_hash))
+    }
+
+    fn musig2_pub_nonce(&mut self) -> Musig2PubNonce {
+        let mut nonce = [0; 66];
+        nonce[..32].copy_from_slice(&self.array32());
+        nonce[32..64].copy_from_slice(&self.array32());
+        nonce[64] = self.u8();
+        nonce[65] = self.u8();
+        Musig2PubNonce::from_byte_array(nonce)
+    }
+
+    fn dleq_proof(&mut self) -> DleqProof {
+        let mut proof = [0; 64];
+        proof[..32].copy_from_slice(&self.array32());
+        proof[32..].copy_from_slice(&self.array32());
+        DleqProof::from_byte_array(proof)
+    }
+
+    fn control_block(&mut self) -> ControlBlock {
+        let len = self.below(4);
+        let branch =
+            (0..len).map(|_| TapNodeHash::from_byte_array(self.array32())).collect::<Vec<_>>();
+        ControlBlock {
+            leaf_version: LeafVersion::TapScript,
+            output_key_parity: if self.coin() {
+                secp256k1::Parity::Odd
+            } else {
+                secp256k1::Parity::Even
+            },
+            internal_key: self.x_only_public_key(),
+            merkle_branch: TaprootMerkleBranch::try_from(branch).expect("branch is short"),
+        }
+    }
+
+    fn tap_tree(&mut self) -> TapTree {
+        // Depths of a complete tree in depth first order.
+        let shapes: [&[u8]; 3] = [&[0], &[1, 1], &[1, 2, 2]];
+        let mut builder = TaprootBuilder::new();
+        for depth in shapes[self.below(shapes.len())] {
+            builder = builder.add_leaf(*depth, self.script()).expect("valid tree shape");
+        }
+        TapTree::try_from(builder).expect("tree is complete")
+    }
+
+    fn proprietary(&mut self) -> (raw::ProprietaryKey, Vec<u8>) {
+        let key =
+            raw::ProprietaryKey { prefix: self.bytes(8), subtype: self.u8(), key: self.bytes(8) };
+        (key, self.bytes(16))
+    }
+
+    fn unknown(&mut self) -> (raw::Key, Vec<u8>) {
+        (raw::Key { type_value: UNKNOWN_KEY_TYPE, key_data: self.bytes(8) }, self.bytes(16))
+    }
+
+    fn preimage<H: Hash>(&mut self) -> (H, Vec<u8>) {
+        let preimage = self.bytes(32);
+        (<H as Hash>::hash(&preimage), preimage)
+    }
+
+    fn txin(&mut self) -> TxIn {
+        TxIn {
+            previous_output: OutPoint {
+                txid: Txid::from_byte_array(self.array32()),
+                vout: self.u32(),
+            },
+            sequence: Sequence(self.u32()),
+            ..Default::default()
+        }
+    }
+
+    fn txout(&mut self) -> TxOut {
+        TxOut { value: Amount::from_sat(self.u64() >> 20), script_pubkey: self.script() }
+    }
+
+    fn witness(&mut self) -> Witness {
+        let len = self.below(4);
+        Witness::from_slice(&(0..len).map(|_| self.bytes(32)).collect::<Vec<_>>())
+    }
+
+    fn transaction(&mut self) -> Transaction {
+        let num_inputs = 1 + self.below(3);
+        let num_outputs = self.below(3);
+        let mut input = (0..num_inputs).map(|_| self.txin()).collect::<Vec<_>>();
+        for txin in &mut input {
+            txin.script_sig = self.script();
+            txin.witness = self.witness();
+        }
+        Transaction {
+            version: transaction::Version(self.u32() as i32),
+            lock_time: absolute::LockTime::from_consensus(self.u32()),
+            input,
+            output: (0..num_outputs).map(|_| self.txout()).collect(),
+        }
+    }
+
+    fn input(&mut self) -> Input {
+        let mut input = Input {
+            non_witness_utxo: self.maybe(Self::transaction),
+            witness_utxo: self.maybe(Self::txout),
+            partial_sigs: self.map(2, |g| (g.public_key(), g.ecdsa_sig())),
+            sighash_type: self.maybe(|g| PsbtSighashType::from_u32(g.u32())),
+            redeem_script: self.maybe(Self::script),
+            witness_script: self.maybe(Self::script),
+            bip32_derivation: self.map(2, |g| (g.secp_public_key(), g.key_source())),
+            final_script_sig: self.maybe(Self::script),
+            final_script_witness: self.maybe(Self::witness),
+            ripemd160_preimages: self.map(2, Self::preimage::<ripemd160::Hash>),
+            sha256_preimages: self.map(2, Self::preimage::<sha256::Hash>),
+            hash160_preimages: self.map(2, Self::preimage::<hash160::Hash>),
+            hash256_preimages: self.map(2, Self::preimage::<sha256d::Hash>),
+            tap_key_sig: self.maybe(Self::taproot_sig),
+            tap_script_sigs: self
+                .map(2, |g| ((g.x_only_public_key(), g.leaf_hash()), g.taproot_sig())),
+            tap_scripts: self.map(2, |g| (g.control_block(), (g.script(), LeafVersion::TapScript))),
+            tap_key_origins: self.map(2, |g| {
+                let len = g.below(3);
+                (g.x_only_public_key(), ((0..len).map(|_| g.leaf_hash()).collect(), g.key_source()))
+            }),
+            tap_internal_key: self.maybe(Self::x_only_public_key),
+            tap_merkle_root: self.maybe(|g| TapNodeHash::from_byte_array(g.array32())),
+            tap_annex: self.maybe(|g| {
+                let mut annex = vec![TAPROOT_ANNEX_PREFIX];
+                annex.extend(g.bytes(8));
+                annex
+            }),
+            musig2_participant_pubkeys: self.map(2, Self::musig2_participants),
+            musig2_pub_nonces: self.map(2, |g| (g.musig2_key(), g.musig2_pub_nonce())),
+            musig2_partial_sigs: self
+                .map(2, |g| (g.musig2_key(), Musig2PartialSig::from_byte_array(g.array32()))),
+            sp_ecdh_shares: self.map(2, |g| (g.secp_public_key(), g.secp_public_key())),
+            sp_dleq_proofs: self.map(2, |g| (g.secp_public_key(), g.dleq_proof())),
+            proprietary: self.map(2, Self::proprietary),
+            unknown: self.map(2, Self::unknown),
+        };
+        // Decoding rejects the annex on an input that does not spend a Taproot output.
+        if input.tap_annex.is_some() {
+            input.redeem_script = None;
+            input.witness_script = None;
+            if let Some(ref mut utxo) = input.witness_utxo {
+                let output_key =
+                    key::TweakedPublicKey::dangerous_assume_tweaked(self.x_only_public_key());
+                utxo.script_pubkey = ScriptBuf::new_p2tr_tweaked(output_key);
+            }
+        }
+        input
+    }
+
+    fn output(&mut self) -> Output {
+        Output {
+            redeem_script: self.maybe(Self::script),
+            witness_script: self.maybe(Self::script),
+            bip32_derivation: self.map(2, |g| (g.secp_public_key(), g.key_source())),
+            tap_internal_key: self.maybe(Self::x_only_public_key),
+            tap_tree: self.maybe(Self::tap_tree),
+            tap_key_origins: self.map(2, |g| {
+                let len = g.below(3);
+                (g.x_only_public_key(), ((0..len).map(|_| g.leaf_hash()).collect(), g.key_source()))
+            }),
+            musig2_participant_pubkeys: self.map(2, Self::musig2_participants),
+            sp_v0_info: self.maybe(|g| (g.secp_public_key(), g.secp_public_key())),
+            sp_v0_label: self.maybe(Self::u32),
+            proprietary: self.map(2, Self::proprietary),
+            unknown: self.map(2, Self::unknown),
+        }
+    }
+
+    /// Generates a random PSBT.
+    pub(crate) fn psbt(&mut self) -> Psbt {
+        let mut unsigned_tx = self.transaction();
+        for txin in &mut unsigned_tx.input {
+            txin.script_sig = ScriptBuf::new();
+            txin.witness = Witness::new();
+        }
+
+        Psbt {
+            inputs: (0..unsigned_tx.input.len()).map(|_| self.input()).collect(),
+            outputs: (0..unsigned_tx.output.len()).map(|_| self.output()).collect(),
+            unsigned_tx,
+            version: 0,
+            xpub: self.map(2, |g| (g.xpub(), g.key_source())),
+            proprietary: self.map(2, Self::proprietary),
+            unknown: self.map(2, Self::unknown),
+        }
+    }
+
+    /// Generates a random PSBT and two PSBTs that each have some of its fields.
+    ///
+    /// Every field is in at least one of the pair, so combining the pair in either order gives
+    /// back the full PSBT. This is what two signers in a ceremony return to a combiner.
+    pub(crate) fn psbt_pair(&mut self) -> (Psbt, Psbt, Psbt) {
+        let full = self.psbt();
+        let (mut a, mut b) = (full.clone(), full.clone());
+
+        for xpub in full.xpub.keys() {
+            match self.below(3) {
+                0 => a.xpub.remove(xpub),
+                1 => b.xpub.remove(xpub),
+                _ => None,
+            };
+        }
+        for (index, input) in full.inputs.iter().enumerate() {
+            let (ours, theirs) = self.split_pairs(input);
+            a.inputs[index] = Input::default();
+            b.inputs[index] = Input::default();
+            ours.into_iter()
+                .for_each(|pair| a.inputs[index].insert_pair(pair).expect("valid pair"));
+            theirs
+                .into_iter()
+                .for_each(|pair| b.inputs[index].insert_pair(pair).expect("valid pair"));
+            // The combiner drops the non-witness UTXO when it takes the witness UTXO from the
+            // other PSBT, so both signers have the UTXOs, as they need to sign anyway.
+            for input in [&mut a.inputs[index], &mut b.inputs[index]] {
+                input.non_witness_utxo = full.inputs[index].non_witness_utxo.clone();
+                input.witness_utxo = full.inputs[index].witness_utxo.clone();
+            }
+        }
+        for (index, output) in full.outputs.iter().enumerate() {
+            let (ours, theirs) = self.split_pairs(output);
+            a.outputs[index] = Output::default();
+            b.outputs[index] = Output::default();
+            ours.into_iter()
+                .for_each(|pair| a.outputs[index].insert_pair(pair).expect("valid pair"));
+            theirs
+                .into_iter()
+                .for_each(|pair| b.outputs[index].insert_pair(pair).expect("valid pair"));
+        }
+        (full, a, b)
+    }
+
+    /// Assigns every pair of `map` to the first, the second or both of the returned lists.
+    fn split_pairs(&mut self, map: &impl Map) -> (Vec<raw::Pair>, Vec<raw::Pair>) {
+        let (mut ours, mut theirs) = (vec![], vec![]);
+        for pair in map.get_pairs() {
+            match self.below(3) {
+                0 => ours.push(pair),
+                1 => theirs.push(pair),
+                _ => {
+                    ours.push(pair.clone());
+                    theirs.push(pair);
+                }
+            }
+        }
+        (ours, theirs)
+    }
+}
+
+/// A PSBT and two PSBTs that each have some of its fields.
+///
+/// Every field is in at least one of the pair and both share the unsigned transaction, so
+/// combining the pair in either order gives back [`PsbtPair::full`]. This is what two signers in
+/// a ceremony return to a combiner, for fuzzing combiners.
+///
+/// ```
+/// # use arbitrary::{Arbitrary, Unstructured};
+/// # use psbt_v0::PsbtPair;
+/// let mut u = Unstructured::new(&[0x2a; 1024]);
+/// let PsbtPair { full, mut first, second } = PsbtPair::arbitrary(&mut u)?;
+/// first.combine(second).expect("the pair is combinable");
+/// assert_eq!(first, full);
+/// # Ok::<_, arbitrary::Error>(())
+/// ```
+#[cfg(feature = "arbitrary")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtPair {
+    /// The PSBT with all fields.
+    pub full: Psbt,
+    /// A PSBT with some of the fields of `full`.
+    pub first: Psbt,
+    /// A PSBT with the fields of `full` missing from `first`, and some of the others.
+    pub second: Psbt,
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for PsbtPair {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let (full, first, second) = Gen::from_unstructured(u).psbt_pair();
+        Ok(PsbtPair { full, first, second })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Psbt {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Gen::from_unstructured(u).psbt())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Input {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Gen::from_unstructured(u).input())
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Output {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Gen::from_unstructured(u).output())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_generated_psbts() {
+        for seed in 0..256 {
+            let psbt = Gen::new(seed).psbt();
+            let encoded = psbt.serialize();
+            let decoded = Psbt::deserialize(&encoded).unwrap_or_else(|e| {
+                panic!("seed {}: failed to deserialize: {}", seed, e);
+            });
+            assert_eq!(decoded, psbt, "seed {}", seed);
+            assert_eq!(decoded.serialize(), encoded, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn combine_generated_pairs() {
+        for seed in 0..256 {
+            let (full, a, b) = Gen::new(seed).psbt_pair();
+            let mut ab = a.clone();
+            ab.combine(b.clone()).unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+            assert_eq!(ab, full, "seed {}", seed);
+            let mut ba = b;
+            ba.combine(a).unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+            assert_eq!(ba, full, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary")]
+    fn arbitrary_psbts() {
+        // An empty input generates the smallest PSBT, random data a bit of everything.
+        let data =
+            (0..4096u32).map(|i| (i.wrapping_mul(0x9E37_79B9) >> 24) as u8).collect::<Vec<_>>();
+        for len in [0, 1, 64, 4096] {
+            let mut u = Unstructured::new(&data[..len]);
+            let psbt = Psbt::arbitrary(&mut u).unwrap();
+            assert_eq!(Psbt::deserialize(&psbt.serialize()).unwrap(), psbt, "len {}", len);
+
+            let mut u = Unstructured::new(&data[..len]);
+            let PsbtPair { full, mut first, second } = PsbtPair::arbitrary(&mut u).unwrap();
+            first.combine(second).unwrap();
+            assert_eq!(first, full, "len {}", len);
+        }
+    }
+}
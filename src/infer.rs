@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Reconstructing the descriptor an input spends from its scripts and keys.
+//!
+//! Auditing tools use this to see the policy a PSBT actually spends instead of trusting the
+//! descriptor the coordinator claims. The inferred descriptor is only returned if it produces the
+//! script pubkey being spent.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use bitcoin::key::{Parity, XOnlyPublicKey};
+use bitcoin::script::Instruction;
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TapNodeHash};
+use bitcoin::{PublicKey, Script, ScriptBuf};
+use bitcoin_internals::write_err;
+use miniscript::descriptor::{Descriptor, TapTree};
+use miniscript::{
+    BareCtx, ExtParams, Legacy, Miniscript, ScriptContext, Segwitv0, Tap, TranslateErr,
+    TranslatePk, Translator,
+};
+
+use crate::prelude::*;
+use crate::Input;
+
+impl Input {
+    /// Returns the descriptor this input, spending `spk`, satisfies.
+    ///
+    /// The descriptor is built from the redeem and witness scripts and the Taproot internal key
+    /// and leaf scripts, falling back to the final scriptSig and witness of a finalized input.
+    /// The key of a key hash output is looked up in the partial signatures, the BIP 32
+    /// derivations and the final scriptSig or witness. Keys only committed to by hash in a
+    /// script (e.g., `pkh` fragments) are left as `expr_raw_pkh`, the finalizer looks them up
+    /// when satisfying the descriptor. Taproot keys are given even parity.
+    ///
+    /// # Errors
+    ///
+    /// If a script or key needed is missing, the redeem or witness script does not match the
+    /// output being spent or is present when that output does not use it, a script is not a
+    /// valid miniscript, the Taproot tree is incomplete or the descriptor does not produce
+    /// `spk`.
+    pub fn infer_descriptor(&self, spk: &Script) -> Result<Descriptor<PublicKey>, InferError> {
+        let descriptor = self.infer_unchecked(spk)?;
+        if descriptor.script_pubkey() != *spk {
+            return Err(InferError::ScriptPubkeyMismatch);
+        }
+        Ok(descriptor)
+    }
+
+    fn infer_unchecked(&self, spk: &Script) -> Result<Descriptor<PublicKey>, InferError> {
+        if spk.is_p2tr() {
+            let key = self.tap_internal_key.ok_or(InferError::MissingTapInternalKey)?;
+            let tree = if self.tap_scripts.is_empty() { None } else { Some(self.tap_tree()?) };
+            return Ok(Descriptor::new_tr(even_key(&key), tree)?);
+        }
+
+        if spk.is_p2sh() {
+            let redeem_script = self.redeem_script(spk)?;
+            if redeem_script.is_p2wsh() {
+                let witness_script = self.witness_script(&redeem_script)?;
+                return Ok(Descriptor::new_sh_wsh(parse::<Segwitv0>(&witness_script)?)?);
+            }
+            if self.witness_script.is_some() {
+                return Err(InferError::UnexpectedWitnessScript);
+            }
+            return Ok(if redeem_script.is_p2wpkh() {
+                Descriptor::new_sh_wpkh(self.key_for(&redeem_script)?)?
+            } else {
+                Descriptor::new_sh(parse::<Legacy>(&redeem_script)?)?
+            });
+        }
+
+        if self.redeem_script.is_some() {
+            return Err(InferError::UnexpectedRedeemScript);
+        }
+        if spk.is_p2wsh() {
+            let witness_script = self.witness_script(spk)?;
+            return Ok(Descriptor::new_wsh(parse::<Segwitv0>(&witness_script)?)?);
+        }
+        if self.witness_script.is_some() {
+            return Err(InferError::UnexpectedWitnessScript);
+        }
+        if spk.is_p2pkh() {
+            return Ok(Descriptor::new_pkh(self.key_for(spk)?)?);
+        }
+        if spk.is_p2wpkh() {
+            return Ok(Descriptor::new_wpkh(self.key_for(spk)?)?);
+        }
+        Ok(Descriptor::new_bare(parse::<BareCtx>(spk)?)?)
+    }
+
+    /// Returns the redeem script hashing to the P2SH `spk`, or the last push of the final
+    /// scriptSig.
+    fn redeem_script(&self, spk: &Script) -> Result<ScriptBuf, InferError> {
+        if let Some(ref script) = self.redeem_script {
+            if script.to_p2sh() != *spk {
+                return Err(InferError::RedeemScriptMismatch);
+            }
+            return Ok(script.clone());
+        }
+        let script_sig = self.final_script_sig.as_ref().ok_or(InferError::MissingRedeemScript)?;
+        match script_sig.instructions().last() {
+            Some(Ok(Instruction::PushBytes(bytes))) =>
+                Ok(ScriptBuf::from(bytes.as_bytes().to_vec())),
+            _ => Err(InferError::MissingRedeemScript),
+        }
+    }
+
+    /// Returns the witness script hashing to the P2WSH `program`, or the last element of the
+    /// final witness.
+    fn witness_script(&self, program: &Script) -> Result<ScriptBuf, InferError> {
+        if let Some(ref script) = self.witness_script {
+            if script.to_p2wsh() != *program {
+                return Err(InferError::WitnessScriptMismatch);
+            }
+            return Ok(script.clone());
+        }
+        self.final_script_witness
+            .as_ref()
+            .and_then(|witness| witness.last())
+            .map(|script| ScriptBuf::from(script.to_vec()))
+            .ok_or(InferError::MissingWitnessScript)
+    }
+
+    /// Returns the key hashed in the P2PKH or P2WPKH `script`.
+    fn key_for(&self, script: &Script) -> Result<PublicKey, InferError> {
+        let segwit = script.is_p2wpkh();
+        let hash = &script.as_bytes()[if segwit { 2 } else { 3 }..][..20];
+
+        let script_sig = self.final_script_sig.iter().flat_map(|script| {
+            script.instructions().filter_map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes()),
+                _ => None,
+            })
+        });
+        let pushed = script_sig
+            .chain(self.final_script_witness.iter().flat_map(|witness| witness.iter()))
+            .filter_map(|bytes| PublicKey::from_slice(bytes).ok());
+        self.partial_sigs
+            .keys()
+            .copied()
+            .chain(self.bip32_derivation.keys().map(|pk| PublicKey::new(*pk)))
+            .chain(pushed)
+            .find(|pk| {
+                if segwit {
+                    pk.wpubkey_hash().map_or(false, |h| h[..] == *hash)
+                } else {
+                    pk.pubkey_hash()[..] == *hash
+                }
+            })
+            .ok_or(InferError::MissingPubkey)
+    }
+
+    /// Rebuilds the Taproot tree from the leaf scripts and their control blocks.
+    ///
+    /// Branch hashes sort their children, so the order of siblings is lost and the leaves of the
+    /// tree returned may be in a different order than in the original, it has the same root.
+    fn tap_tree(&self) -> Result<TapTree<PublicKey>, InferError> {
+        let mut leaves = vec![];
+        for (control_block, (script, leaf_version)) in &self.tap_scripts {
+            if *leaf_version != LeafVersion::TapScript {
+                return Err(InferError::UnknownLeafVersion);
+            }
+            let mut node = TapNodeHash::from(TapLeafHash::from_script(script, *leaf_version));
+            let mut path = vec![node];
+            for sibling in control_block.merkle_branch.iter() {
+                node = TapNodeHash::from_node_hashes(node, *sibling);
+                path.push(node);
+            }
+            path.reverse();
+            let ms = parse::<Tap>(script)?.translate_pk(&mut EvenKeys).map_err(|e| match e {
+                TranslateErr::TranslatorErr(never) => match never {},
+                TranslateErr::OuterError(e) => InferError::InvalidScript(e),
+            })?;
+            leaves.push((path, ms));
+        }
+        build_tree(&leaves, 0)
+    }
+}
+
+/// Builds the subtree of `leaves`, whose paths from the root agree up to `depth`.
+fn build_tree(
+    leaves: &[(Vec<TapNodeHash>, Miniscript<PublicKey, Tap>)],
+    depth: usize,
+) -> Result<TapTree<PublicKey>, InferError> {
+    if let [(path, ms)] = leaves {
+        if path.len() == depth + 1 {
+            return Ok(TapTree::Leaf(sync::Arc::new(ms.clone())));
+        }
+    }
+    let child = |leaf: &(Vec<TapNodeHash>, _)| leaf.0.get(depth + 1).copied();
+    let first = child(&leaves[0]).ok_or(InferError::IncompleteTapTree)?;
+    let (left, right): (Vec<_>, Vec<_>) =
+        leaves.iter().cloned().partition(|leaf| child(leaf) == Some(first));
+    // A hidden branch leaves one side empty.
+    if right.is_empty() || right.iter().any(|leaf| child(leaf).is_none()) {
+        return Err(InferError::IncompleteTapTree);
+    }
+    Ok(TapTree::combine(build_tree(&left, depth + 1)?, build_tree(&right, depth + 1)?))
+}
+
+/// Parses `script`, allowing `pkh` fragments whose key is only known by its hash.
+fn parse<Ctx: ScriptContext>(script: &Script) -> Result<Miniscript<Ctx::Key, Ctx>, InferError> {
+    Miniscript::parse_with_ext(script, &ExtParams::allow_all()).map_err(InferError::InvalidScript)
+}
+
+fn even_key(key: &XOnlyPublicKey) -> PublicKey { PublicKey::new(key.public_key(Parity::Even)) }
+
+/// Gives x-only keys even parity.
+struct EvenKeys;
+
+impl Translator<XOnlyPublicKey, PublicKey, Infallible> for EvenKeys {
+    fn pk(&mut self, pk: &XOnlyPublicKey) -> Result<PublicKey, Infallible> { Ok(even_key(pk)) }
+
+    miniscript::translate_hash_clone!(XOnlyPublicKey, PublicKey, Infallible);
+}
+
+/// Error returned by [`Input::infer_descriptor`].
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum InferError {
+    /// The output being spent is a P2SH but the input has no redeem script.
+    MissingRedeemScript,
+    /// The output being spent is a P2WSH but the input has no witness script.
+    MissingWitnessScript,
+    /// The redeem script does not hash to the script pubkey being spent.
+    RedeemScriptMismatch,
+    /// The witness script does not hash to the witness program being spent.
+    WitnessScriptMismatch,
+    /// The input has a redeem script but the output being spent is not a P2SH.
+    UnexpectedRedeemScript,
+    /// The input has a witness script but the output being spent is not a P2WSH.
+    UnexpectedWitnessScript,
+    /// No key of the input matches the key hash being spent.
+    MissingPubkey,
+    /// The output being spent is a Taproot output but the input has no internal key.
+    MissingTapInternalKey,
+    /// The leaf scripts do not cover the whole Taproot tree.
+    IncompleteTapTree,
+    /// A leaf script has a leaf version other than Tapscript.
+    UnknownLeafVersion,
+    /// A script being spent is not a valid miniscript.
+    InvalidScript(miniscript::Error),
+    /// The inferred descriptor does not produce the script pubkey being spent.
+    ScriptPubkeyMismatch,
+}
+
+bitcoin_internals::impl_from_infallible!(InferError);
+
+impl fmt::Display for InferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use InferError::*;
+
+        match *self {
+            MissingRedeemScript => f.write_str("input spends a P2SH but has no redeem script"),
+            MissingWitnessScript => f.write_str("input spends a P2WSH but has no witness script"),
+            RedeemScriptMismatch =>
+                f.write_str("redeem script does not match the script pubkey of the spent output"),
+            WitnessScriptMismatch =>
+                f.write_str("witness script does not match the witness program being spent"),
+            UnexpectedRedeemScript =>
+                f.write_str("input has a redeem script but does not spend a P2SH"),
+            UnexpectedWitnessScript =>
+                f.write_str("input has a witness script but does not spend a P2WSH"),
+            MissingPubkey => f.write_str("no key of the input matches the spent public key hash"),
+            MissingTapInternalKey =>
+                f.write_str("input spends a Taproot output but has no internal key"),
+            IncompleteTapTree => f.write_str("the leaf scripts do not cover the Taproot tree"),
+            UnknownLeafVersion => f.write_str("a leaf script is not a Tapscript"),
+            InvalidScript(ref e) => write_err!(f, "spent script is not a valid miniscript"; e),
+            ScriptPubkeyMismatch =>
+                f.write_str("inferred descriptor does not match the script pubkey being spent"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InferError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use InferError::*;
+
+        match *self {
+            InvalidScript(ref e) => Some(e),
+            MissingRedeemScript
+            | MissingWitnessScript
+            | RedeemScriptMismatch
+            | WitnessScriptMismatch
+            | UnexpectedRedeemScript
+            | UnexpectedWitnessScript
+            | MissingPubkey
+            | MissingTapInternalKey
+            | IncompleteTapTree
+            | UnknownLeafVersion
+            | ScriptPubkeyMismatch => None,
+        }
+    }
+}
+
+impl From<miniscript::Error> for InferError {
+    fn from(e: miniscript::Error) -> Self { InferError::InvalidScript(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::opcodes::all::OP_CHECKSIG;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::taproot::TaprootBuilder;
+    use bitcoin::Witness;
+
+    use super::*;
+
+    #[test]
+    fn infer_descriptor() {
+        let secp = Secp256k1::new();
+        let pk = |i| PublicKey::new(SecretKey::from_slice(&[i; 32]).unwrap().public_key(&secp));
+
+        let expected =
+            Descriptor::<PublicKey>::from_str(&format!("wsh(multi(2,{},{}))", pk(1), pk(2)))
+                .unwrap();
+        let spk = expected.script_pubkey();
+        let input = Input {
+            witness_script: Some(expected.explicit_script().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(input.infer_descriptor(&spk), Ok(expected));
+        let p2wpkh = ScriptBuf::new_p2wpkh(&pk(1).wpubkey_hash().unwrap());
+        assert_eq!(input.infer_descriptor(&p2wpkh), Err(InferError::UnexpectedWitnessScript));
+        assert_eq!(Input::default().infer_descriptor(&p2wpkh), Err(InferError::MissingPubkey));
+        let other = ScriptBuf::new_p2wsh(&ScriptBuf::new().wscript_hash());
+        assert_eq!(input.infer_descriptor(&other), Err(InferError::WitnessScriptMismatch));
+
+        // The key of a `pkh` fragment is only known by its hash.
+        let pkh = Descriptor::<PublicKey>::from_str(&format!("wsh(pkh({}))", pk(1))).unwrap();
+        let input =
+            Input { witness_script: Some(pkh.explicit_script().unwrap()), ..Default::default() };
+        let descriptor = input.infer_descriptor(&pkh.script_pubkey()).unwrap();
+        assert_eq!(descriptor.script_pubkey(), pkh.script_pubkey());
+
+        // A finalized input only has its final witness.
+        let expected = Descriptor::new_sh_wpkh(pk(3)).unwrap();
+        let input = Input {
+            final_script_sig: Some(
+                ScriptBuf::builder()
+                    .push_slice(
+                        <&bitcoin::script::PushBytes>::try_from(
+                            ScriptBuf::new_p2wpkh(&pk(3).wpubkey_hash().unwrap()).as_bytes(),
+                        )
+                        .unwrap(),
+                    )
+                    .into_script(),
+            ),
+            final_script_witness: Some(Witness::from_slice(&[vec![0; 72], pk(3).to_bytes()])),
+            ..Default::default()
+        };
+        assert_eq!(input.infer_descriptor(&expected.script_pubkey()), Ok(expected));
+
+        // Three leaves at depths 1, 2 and 2.
+        let leaf = |i| {
+            ScriptBuf::builder()
+                .push_x_only_key(&pk(i).inner.x_only_public_key().0)
+                .push_opcode(OP_CHECKSIG)
+                .into_script()
+        };
+        let internal_key = pk(4).inner.x_only_public_key().0;
+        let info = TaprootBuilder::new()
+            .add_leaf(1, leaf(5))
+            .unwrap()
+            .add_leaf(2, leaf(6))
+            .unwrap()
+            .add_leaf(2, leaf(7))
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+        let spk = ScriptBuf::new_p2tr_tweaked(info.output_key());
+        let mut input = Input { tap_internal_key: Some(internal_key), ..Default::default() };
+        for i in 5..=7 {
+            let script_ver = (leaf(i), LeafVersion::TapScript);
+            input.tap_scripts.insert(info.control_block(&script_ver).unwrap(), script_ver);
+        }
+        let descriptor = input.infer_descriptor(&spk).unwrap();
+        assert!(matches!(descriptor, Descriptor::Tr(ref tr) if tr.tap_tree().is_some()));
+
+        let first = input.tap_scripts.keys().next().unwrap().clone();
+        input.tap_scripts.remove(&first);
+        assert_eq!(input.infer_descriptor(&spk), Err(InferError::IncompleteTapTree));
+        input.tap_scripts.clear();
+        assert_eq!(input.infer_descriptor(&spk), Err(InferError::ScriptPubkeyMismatch));
+    }
+}
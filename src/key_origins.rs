@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Filtering of key origins and derivation of the keys a signer needs.
+//!
+//! A signer holding many wallets should not derive every path in a PSBT, most belong to other
+//! cosigners. These helpers select the paths under one master fingerprint.
+
+use bitcoin::bip32::{Fingerprint, KeySource, Xpriv};
+use bitcoin::secp256k1::{Secp256k1, Signing};
+use bitcoin::{PrivateKey, PublicKey};
+
+use crate::prelude::*;
+use crate::{GetKey, GetKeyError, KeyRequest, Psbt};
+
+impl Psbt {
+    /// Removes every BIP 32 derivation and Taproot key origin not under `fingerprint`.
+    ///
+    /// Inputs, outputs and the global xpubs are pruned. Origins of other cosigners are removed
+    /// from outputs too, so prune a copy if the PSBT is passed on to them afterwards.
+    pub fn prune_key_origins(&mut self, fingerprint: Fingerprint) {
+        self.xpub.retain(|_, (fp, _)| *fp == fingerprint);
+        for input in &mut self.inputs {
+            input.bip32_derivation.retain(|_, (fp, _)| *fp == fingerprint);
+            input.tap_key_origins.retain(|_, (_, (fp, _))| *fp == fingerprint);
+        }
+        for output in &mut self.outputs {
+            output.bip32_derivation.retain(|_, (fp, _)| *fp == fingerprint);
+            output.tap_key_origins.retain(|_, (_, (fp, _))| *fp == fingerprint);
+        }
+    }
+
+    /// Returns the key origins under `fingerprint` that sign the inputs, without duplicates.
+    pub fn input_key_sources(&self, fingerprint: Fingerprint) -> BTreeSet<KeySource> {
+        self.inputs
+            .iter()
+            .flat_map(|input| {
+                let ecdsa = input.bip32_derivation.values();
+                let taproot = input.tap_key_origins.values().map(|(_, source)| source);
+                ecdsa.chain(taproot)
+            })
+            .filter(|(fp, _)| *fp == fingerprint)
+            .cloned()
+            .collect()
+    }
+
+    /// Derives from `xpriv` the private keys of the inputs' key origins it is the source of.
+    ///
+    /// `xpriv` is either the master key or a direct child of it, see the [`GetKey`]
+    /// implementation of [`Xpriv`]. Only the paths in the inputs are derived and a key is only
+    /// returned if it matches the public key recorded for its path. The returned map can be used
+    /// with [`Psbt::sign`] in place of `xpriv`.
+    pub fn derive_signing_keys<C: Signing>(
+        &self,
+        xpriv: &Xpriv,
+        secp: &Secp256k1<C>,
+    ) -> Result<BTreeMap<PublicKey, PrivateKey>, GetKeyError> {
+        let mut keys = BTreeMap::new();
+        for input in &self.inputs {
+            for (pubkey, source) in &input.bip32_derivation {
+                if let Some(sk) = xpriv.get_key(&KeyRequest::Bip32(source.clone()), secp)? {
+                    let pk = sk.public_key(secp);
+                    if pk.inner == *pubkey {
+                        keys.insert(pk, sk);
+                    }
+                }
+            }
+            for (xonly, (_, source)) in &input.tap_key_origins {
+                if let Some(sk) = xpriv.get_key(&KeyRequest::Bip32(source.clone()), secp)? {
+                    let pk = sk.public_key(secp);
+                    if pk.inner.x_only_public_key().0 == *xonly {
+                        keys.insert(pk, sk);
+                    }
+                }
+            }
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Xpub};
+    use bitcoin::{absolute, transaction, Network, Transaction, TxIn};
+
+    use super::*;
+
+    #[test]
+    fn key_origins() {
+        let secp = Secp256k1::new();
+        let mine = Xpriv::new_master(Network::Bitcoin, &[1; 32]).unwrap();
+        let theirs = Xpriv::new_master(Network::Bitcoin, &[2; 32]).unwrap();
+        let (fp, other_fp) = (mine.fingerprint(&secp), theirs.fingerprint(&secp));
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        for (xpriv, path) in [(&mine, "m/84'/0'/0'/0/1"), (&theirs, "m/84'/0'/0'/0/1")] {
+            let path = path.parse::<DerivationPath>().unwrap();
+            let pk = Xpub::from_priv(&secp, &xpriv.derive_priv(&secp, &path).unwrap());
+            psbt.inputs[0].bip32_derivation.insert(pk.public_key, (xpriv.fingerprint(&secp), path));
+        }
+        // A bogus claim: my fingerprint and a path, but someone else's key.
+        let bogus = theirs.derive_priv(&secp, &"m/0".parse::<DerivationPath>().unwrap()).unwrap();
+        let bogus = bogus.private_key.public_key(&secp);
+        psbt.inputs[0].bip32_derivation.insert(bogus, (fp, "m/0".parse().unwrap()));
+
+        assert_eq!(psbt.input_key_sources(fp).len(), 2);
+        let keys = psbt.derive_signing_keys(&mine, &secp).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert!(!keys.keys().any(|pk| pk.inner == bogus));
+
+        psbt.prune_key_origins(other_fp);
+        assert_eq!(psbt.inputs[0].bip32_derivation.len(), 1);
+        assert!(psbt.derive_signing_keys(&mine, &secp).unwrap().is_empty());
+    }
+}
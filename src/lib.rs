@@ -4,10 +4,19 @@
 //!
 //! Implementation of the Partially Signed Bitcoin Transaction Format as defined in [BIP-174].
 //!
+//! The [`Psbt`] type is a version 0 PSBT. Version 2 PSBTs ([BIP-370]) do not contain an
+//! unsigned transaction, the transaction fields are spread over the input and output maps.
+//! [`PsbtV2`] holds a version 2 PSBT whose inputs and outputs can be added incrementally, and
+//! converts to and from [`Psbt`]. [`VersionedPsbt`] holds a PSBT of either version, and
+//! [`Psbt::deserialize_any_version`] decodes both versions, converting a version 2 PSBT to
+//! version 0 when every field needed to build the transaction is present.
+//!
 //! [BIP-174]: <https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki>
+//! [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
 
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 // Experimental features we need.
+#![cfg_attr(bench, feature(test))]
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 // Coding conventions
 #![warn(missing_docs)]
@@ -16,6 +25,9 @@
 #[macro_use]
 extern crate alloc;
 
+#[cfg(bench)]
+extern crate test;
+
 #[cfg(feature = "serde")]
 #[macro_use]
 extern crate actual_serde as serde;
@@ -23,26 +35,92 @@ extern crate actual_serde as serde;
 /// Re-export of the `rust-bitcoin` crate.
 pub extern crate bitcoin;
 
+/// Re-export of the `rust-miniscript` crate.
+#[cfg(feature = "miniscript")]
+pub extern crate miniscript;
+
 #[macro_use]
 mod macros;
+mod analyze;
+#[cfg(feature = "async")]
+mod async_signer;
+#[cfg(feature = "bbqr")]
+mod bbqr;
+mod builder;
+mod bump_fee;
+mod cleanup;
+mod conformance;
+#[cfg(feature = "serde")]
+mod core_json;
+mod derivation_policy;
+#[cfg(feature = "miniscript")]
+mod descriptor;
+mod diff;
+mod dump;
 mod error;
+mod external_signer;
+#[cfg(all(feature = "std", feature = "base64"))]
+mod file;
+#[cfg(feature = "miniscript")]
+mod finalizer;
+#[cfg(any(test, feature = "arbitrary"))]
+mod generator;
+#[cfg(feature = "miniscript")]
+mod infer;
+mod key_origins;
 mod map;
+mod merge;
+mod musig2;
+mod normalize;
+mod ownership;
+mod payjoin;
+mod proprietary;
+mod redact;
+mod reorder;
+mod retention;
+mod sanity;
 #[cfg(feature = "serde")]
 mod serde_utils;
+mod session;
+mod shard;
+mod sighash_policy;
+mod signers;
+mod size;
+mod standardness;
+mod status;
+mod stream;
+mod strict;
+#[cfg(feature = "miniscript")]
+mod timelock;
+mod unknown;
+#[cfg(feature = "miniscript")]
+mod updater;
+mod updaters;
+mod utxos;
+mod v2;
+mod verify;
+mod weight;
+mod xpubs;
 
 pub mod raw;
+mod script;
 pub mod serialize;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
-use core::{cmp, fmt};
+use core::{cmp, fmt, mem};
 #[cfg(feature = "std")]
 use std::collections::{HashMap, HashSet};
 
-use bitcoin::bip32::{self, DerivationPath, KeySource, Xpriv, Xpub};
-use bitcoin::blockdata::transaction::{self, Transaction, TxOut};
+use bitcoin::bip32::{self, DerivationPath, Fingerprint, KeySource, Xpriv, Xpub};
+use bitcoin::blockdata::transaction::{self, OutPoint, Sequence, Transaction, TxIn, TxOut};
 use bitcoin::key::{PrivateKey, PublicKey, TapTweak, XOnlyPublicKey};
-use bitcoin::secp256k1::{Keypair, Message, Secp256k1, Signing, Verification};
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::{self, Keypair, Message, Secp256k1, Signing, Verification};
 use bitcoin::sighash::{self, EcdsaSighashType, Prevouts, SighashCache};
-use bitcoin::{ecdsa, taproot, Amount, FeeRate, TapLeafHash, TapSighashType};
+use bitcoin::{
+    absolute, ecdsa, taproot, Amount, FeeRate, TapLeafHash, TapSighashType, Txid, Weight,
+};
 use bitcoin_internals::write_err;
 
 use crate::prelude::*;
@@ -50,8 +128,68 @@ use crate::prelude::*;
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
-    map::{Input, Output, PsbtSighashType},
-    error::Error,
+    analyze::{InputAnalysis, MissingItems, PsbtAnalysis, Role},
+    builder::{BuildMapError, InputBuilder, OutputBuilder, PsbtBuilder},
+    bump_fee::BumpFeeError,
+    conformance::{ConformanceError, ConformanceIssue, CoreAnalysis, CoreInputAnalysis, CoreMethod, CoreRpc},
+    derivation_policy::{DerivationPolicy, DerivationPolicyError},
+    diff::{DiffError, MapDiff, PsbtDiff},
+    dump::PsbtDump,
+    map::{
+        DleqProof, Input, InputRef, InsertSigError, Musig2Key, Musig2PartialSig, Musig2PubNonce, Output, OutputRef,
+        PsbtSighashType, SetScriptError, SpendKind, SpendUtxoError, TapError, TapSpendPath,
+    },
+    merge::{MergeConflict, Resolution},
+    musig2::{musig2_aggregate_key, Musig2AggNonce, Musig2Error, Musig2SecNonce},
+    error::{DecodeError, Error, PairError},
+    external_signer::{FullPsbtSigner, KeySigner, PartialSigner, PsbtSigner, SignOutcome, SignerError},
+    ownership::{OwnershipError, OwnershipProof},
+    payjoin::{PayjoinError, PayjoinParams},
+    proprietary::ProprietaryField,
+    redact::{Redaction, RedactOptions},
+    reorder::ReorderError,
+    retention::FieldRetention,
+    sanity::{CheckInputError, SanityError},
+    session::{SessionError, SigningSession},
+    sighash_policy::{SighashPolicy, SighashPolicyError},
+    size::{FieldSize, MapSize, SizeReport},
+    signers::{SignaturesNeeded, SignerKey},
+    standardness::{StandardnessIssue, StandardnessPolicy},
+    status::{InputStatus, MissingField, PsbtStatus},
+    stream::{PsbtReader, PsbtWriter},
+    strict::StrictError,
+    unknown::KnownKeyError,
+    updaters::{ChainUpdater, UpdateReport, Updater, UpdaterError},
+    utxos::{CompactPsbt, PopulateUtxosError, UtxoPolicy},
+    v2::{
+        ConvertV0Error, ConvertV2Error, DecodeAnyError, GlobalsV2, InputV2, ModifyError, OutputV2, PsbtV2,
+        VersionedPsbt,
+    },
+    verify::{InputSigs, VerifySigError},
+    weight::{EstimateInputError, EstimateWeightError},
+    xpubs::XpubError,
+};
+#[cfg(feature = "async")]
+pub use self::async_signer::{
+    AsyncFullPsbtSigner, AsyncPartialSigner, AsyncPsbtSigner, MaybeSend, SignFuture, SyncSigner,
+};
+#[cfg(feature = "bbqr")]
+pub use self::bbqr::{BbqrEncoding, BbqrError};
+#[cfg(feature = "serde")]
+pub use self::core_json::{CoreJson, DecodedPsbt};
+#[cfg(all(feature = "std", feature = "base64"))]
+pub use self::file::{FileFormat, PsbtFile, PsbtFileError};
+#[cfg(feature = "arbitrary")]
+pub use self::generator::PsbtPair;
+#[cfg(feature = "miniscript")]
+pub use self::{
+    builder::ChangeOutputError,
+    descriptor::{FinalizeError, WeightError},
+    finalizer::PsbtInputSatisfier,
+    infer::InferError,
+    timelock::TimelockError,
+    updater::{ChangeError, DescriptorCache, UpdateError},
+    updaters::DescriptorUpdater,
 };
 
 /// A Partially Signed Transaction.
@@ -60,6 +198,10 @@ pub use self::{
 #[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
 pub struct Psbt {
     /// The unsigned transaction, scriptSigs and witnesses for each input must be empty.
+    ///
+    /// Adding or removing inputs and outputs here does not update `inputs` and `outputs`, prefer
+    /// [`Psbt::push_input`], [`Psbt::remove_input`] and friends. This field may become private in
+    /// a future breaking release.
     pub unsigned_tx: Transaction,
     /// The version number of this PSBT. If omitted, the version number is 0.
     pub version: u32,
@@ -93,17 +235,33 @@ impl Psbt {
     ///
     /// The function panics if the length of transaction inputs is not equal to the length of PSBT inputs.
     pub fn iter_funding_utxos(&self) -> impl Iterator<Item = Result<&TxOut, Error>> {
-        assert_eq!(self.inputs.len(), self.unsigned_tx.input.len());
-        self.unsigned_tx.input.iter().zip(&self.inputs).map(|(tx_input, psbt_input)| {
-            match (&psbt_input.witness_utxo, &psbt_input.non_witness_utxo) {
-                (Some(witness_utxo), _) => Ok(witness_utxo),
-                (None, Some(non_witness_utxo)) => {
-                    let vout = tx_input.previous_output.vout as usize;
-                    non_witness_utxo.output.get(vout).ok_or(Error::PsbtUtxoOutOfbounds)
-                }
-                (None, None) => Err(Error::MissingUtxo),
-            }
-        })
+        self.input_pairs().map(|input| input.funding_utxo())
+    }
+
+    /// Checks that no two inputs spend the same outpoint.
+    ///
+    /// A transaction spending an outpoint more than once is invalid. [`Psbt::sanity_check`] runs
+    /// this check too.
+    ///
+    /// # Errors
+    ///
+    /// Returns the indices of all inputs that spend an outpoint already spent by an earlier input.
+    pub fn check_duplicate_inputs(&self) -> Result<(), Vec<usize>> {
+        let mut seen = BTreeSet::new();
+        let duplicates = self
+            .unsigned_tx
+            .input
+            .iter()
+            .enumerate()
+            .filter(|(_, txin)| !seen.insert(txin.previous_output))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        if duplicates.is_empty() {
+            Ok(())
+        } else {
+            Err(duplicates)
+        }
     }
 
     /// Checks that unsigned transaction does not have scriptSig's or witness data.
@@ -141,6 +299,218 @@ impl Psbt {
         Ok(psbt)
     }
 
+    /// Creates an empty PSBT with space reserved for `inputs` inputs and `outputs` outputs.
+    ///
+    /// The unsigned transaction is version 2 with a zero lock time. Use [`Psbt::push_input`] and
+    /// [`Psbt::push_output`] to fill it without reallocating.
+    pub fn with_capacity(inputs: usize, outputs: usize) -> Self {
+        Psbt {
+            unsigned_tx: Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: Vec::with_capacity(inputs),
+                output: Vec::with_capacity(outputs),
+            },
+            xpub: Default::default(),
+            version: 0,
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: Vec::with_capacity(inputs),
+            outputs: Vec::with_capacity(outputs),
+        }
+    }
+
+    /// Appends an input to the unsigned transaction along with its PSBT input map.
+    ///
+    /// The signatures of the other inputs are left alone, use [`Psbt::insert_input`] to add an
+    /// input to a PSBT that is already signed.
+    ///
+    /// # Errors
+    ///
+    /// If `txin` has a non-empty scriptSig or witness, as required for the unsigned transaction.
+    pub fn push_input(&mut self, txin: TxIn, input: Input) -> Result<(), Error> {
+        if !txin.script_sig.is_empty() {
+            return Err(Error::UnsignedTxHasScriptSigs);
+        }
+        if !txin.witness.is_empty() {
+            return Err(Error::UnsignedTxHasScriptWitnesses);
+        }
+        self.unsigned_tx.input.push(txin);
+        self.inputs.push(input);
+        Ok(())
+    }
+
+    /// Appends an output to the unsigned transaction along with its PSBT output map.
+    ///
+    /// The signatures of the inputs are left alone, use [`Psbt::insert_output`] to add an output
+    /// to a PSBT that is already signed.
+    pub fn push_output(&mut self, txout: TxOut, output: Output) {
+        self.unsigned_tx.output.push(txout);
+        self.outputs.push(output);
+    }
+
+    /// Inserts an input into the unsigned transaction at `input_index` along with its PSBT input
+    /// map, shifting the inputs after it.
+    ///
+    /// The signatures of the other inputs that the change invalidates are removed: those that do
+    /// not use `SIGHASH_ANYONECANPAY`, and the `SIGHASH_SINGLE` signatures of the shifted inputs,
+    /// which now pair with a different output. Finalized inputs are assumed to commit to
+    /// everything and lose their final scriptSig and witness.
+    ///
+    /// # Errors
+    ///
+    /// If `txin` has a non-empty scriptSig or witness.
+    ///
+    /// # Panics
+    ///
+    /// If `input_index` is greater than the number of inputs.
+    pub fn insert_input(
+        &mut self,
+        input_index: usize,
+        txin: TxIn,
+        input: Input,
+    ) -> Result<(), Error> {
+        assert!(input_index <= self.inputs.len().min(self.unsigned_tx.input.len()));
+        if !txin.script_sig.is_empty() {
+            return Err(Error::UnsignedTxHasScriptSigs);
+        }
+        if !txin.witness.is_empty() {
+            return Err(Error::UnsignedTxHasScriptWitnesses);
+        }
+        self.clear_sigs(|index, sighash| {
+            sighash & 0x80 == 0 || (sighash & 0x1f == 0x03 && index >= input_index)
+        });
+        self.unsigned_tx.input.insert(input_index, txin);
+        self.inputs.insert(input_index, input);
+        Ok(())
+    }
+
+    /// Inserts an output into the unsigned transaction at `output_index` along with its PSBT
+    /// output map, shifting the outputs after it.
+    ///
+    /// The signatures that commit to all outputs, and the `SIGHASH_SINGLE` signatures of the
+    /// inputs pairing with the new output or a shifted one, are removed, see
+    /// [`Psbt::insert_input`].
+    ///
+    /// # Panics
+    ///
+    /// If `output_index` is greater than the number of outputs.
+    pub fn insert_output(&mut self, output_index: usize, txout: TxOut, output: Output) {
+        assert!(output_index <= self.outputs.len().min(self.unsigned_tx.output.len()));
+        self.clear_sigs(|input_index, sighash| match sighash & 0x1f {
+            0x02 => false,
+            0x03 => input_index >= output_index,
+            _ => true,
+        });
+        self.unsigned_tx.output.insert(output_index, txout);
+        self.outputs.insert(output_index, output);
+    }
+
+    /// Removes the input at `input_index` from the unsigned transaction along with its PSBT input
+    /// map, and returns them.
+    ///
+    /// The signatures of the other inputs that the change invalidates are removed, see
+    /// [`Psbt::insert_input`]. The removed input is returned unchanged.
+    pub fn remove_input(
+        &mut self,
+        input_index: usize,
+    ) -> Result<(TxIn, Input), IndexOutOfBoundsError> {
+        self.check_index_is_within_bounds(input_index)?;
+        self.clear_sigs(|index, sighash| {
+            index != input_index
+                && (sighash & 0x80 == 0 || (sighash & 0x1f == 0x03 && index > input_index))
+        });
+        Ok((self.unsigned_tx.input.remove(input_index), self.inputs.remove(input_index)))
+    }
+
+    /// Removes the output at `output_index` from the unsigned transaction along with its PSBT
+    /// output map, and returns them.
+    ///
+    /// The signatures that commit to all outputs, and the `SIGHASH_SINGLE` signatures of the
+    /// inputs pairing with the removed output or one after it, are removed, see
+    /// [`Psbt::insert_input`].
+    pub fn remove_output(
+        &mut self,
+        output_index: usize,
+    ) -> Result<(TxOut, Output), IndexOutOfBoundsError> {
+        self.check_output_index_is_within_bounds(output_index)?;
+        self.clear_sigs(|input_index, sighash| match sighash & 0x1f {
+            0x02 => false,
+            0x03 => input_index >= output_index,
+            _ => true,
+        });
+        Ok((self.unsigned_tx.output.remove(output_index), self.outputs.remove(output_index)))
+    }
+
+    /// Removes the signatures for which `invalidated` returns true given the index of their input
+    /// and their sighash type.
+    ///
+    /// Taproot `SIGHASH_DEFAULT` signatures are passed as `SIGHASH_ALL`, MuSig2 partial signatures
+    /// with the sighash type of their input, and the final scriptSig and witness as `SIGHASH_ALL`.
+    fn clear_sigs<F: Fn(usize, u32) -> bool>(&mut self, invalidated: F) {
+        let tap = |sighash_type: TapSighashType| match sighash_type {
+            TapSighashType::Default => 0x01,
+            sighash_type => PsbtSighashType::from(sighash_type).to_u32(),
+        };
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            input.partial_sigs.retain(|_, sig| !invalidated(index, sig.sighash_type.to_u32()));
+            input.tap_script_sigs.retain(|_, sig| !invalidated(index, tap(sig.sighash_type)));
+            if input.tap_key_sig.map_or(false, |sig| invalidated(index, tap(sig.sighash_type))) {
+                input.tap_key_sig = None;
+            }
+            let musig2_sighash = input.taproot_hash_ty().map_or(0x01, tap);
+            if invalidated(index, musig2_sighash) {
+                input.musig2_partial_sigs.clear();
+            }
+            if invalidated(index, 0x01) {
+                input.final_script_sig = None;
+                input.final_script_witness = None;
+            }
+        }
+    }
+
+    /// Returns the sequence number of the input at `input_index`.
+    pub fn input_sequence(&self, input_index: usize) -> Option<Sequence> {
+        self.unsigned_tx.input.get(input_index).map(|txin| txin.sequence)
+    }
+
+    /// Sets the sequence number of the input at `input_index`.
+    ///
+    /// Signatures commit to the sequence numbers of the inputs, changing a sequence number
+    /// invalidates the signatures that commit to it. This function refuses to change the sequence
+    /// number if any signature in the PSBT would be invalidated, i.e., if the input itself has
+    /// any signature or any other input has a signature that does not use `SIGHASH_ANYONECANPAY`
+    /// (and, for ECDSA, uses `SIGHASH_ALL`). Finalized inputs are assumed to commit to all
+    /// sequence numbers.
+    pub fn set_input_sequence(
+        &mut self,
+        input_index: usize,
+        sequence: Sequence,
+    ) -> Result<(), SetSequenceError> {
+        self.check_index_is_within_bounds(input_index)?;
+
+        for (index, input) in self.inputs.iter().enumerate() {
+            let commits = if index == input_index {
+                !input.partial_sigs.is_empty()
+                    || input.tap_key_sig.is_some()
+                    || !input.tap_script_sigs.is_empty()
+            } else {
+                input.partial_sigs.values().any(|sig| sig.sighash_type == EcdsaSighashType::All)
+                    || input
+                        .tap_key_sig
+                        .iter()
+                        .chain(input.tap_script_sigs.values())
+                        .any(|sig| PsbtSighashType::from(sig.sighash_type).to_u32() & 0x80 == 0)
+            };
+            if commits || input.is_finalized() {
+                return Err(SetSequenceError::CommittedBySignature { input_index: index });
+            }
+        }
+
+        self.unsigned_tx.input[input_index].sequence = sequence;
+        Ok(())
+    }
+
     /// The default `max_fee_rate` value used for extracting transactions with [`extract_tx`]
     ///
     /// As of 2023, even the biggest overpayers during the highest fee markets only paid around
@@ -152,6 +522,7 @@ impl Psbt {
     /// An alias for [`extract_tx_fee_rate_limit`].
     ///
     /// [`extract_tx_fee_rate_limit`]: Psbt::extract_tx_fee_rate_limit
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt` or `Transaction`.
     pub fn extract_tx(self) -> Result<Transaction, ExtractTxError> {
         self.internal_extract_tx_with_fee_rate_limit(Self::DEFAULT_MAX_FEE_RATE)
     }
@@ -163,6 +534,7 @@ impl Psbt {
     /// [`ExtractTxError`] variants will contain either the [`Psbt`] itself or the [`Transaction`]
     /// that was extracted. These can be extracted from the Errors in order to recover.
     /// See the error documentation for info on the variants. In general, it covers large fees.
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt` or `Transaction`.
     pub fn extract_tx_fee_rate_limit(self) -> Result<Transaction, ExtractTxError> {
         self.internal_extract_tx_with_fee_rate_limit(Self::DEFAULT_MAX_FEE_RATE)
     }
@@ -174,6 +546,7 @@ impl Psbt {
     /// See [`extract_tx`].
     ///
     /// [`extract_tx`]: Psbt::extract_tx
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt` or `Transaction`.
     pub fn extract_tx_with_fee_rate_limit(
         self,
         max_fee_rate: FeeRate,
@@ -181,6 +554,176 @@ impl Psbt {
         self.internal_extract_tx_with_fee_rate_limit(max_fee_rate)
     }
 
+    /// Extracts the [`Transaction`] from a [`Psbt`] after checking that every input is finalized.
+    ///
+    /// Unlike [`extract_tx`], which silently leaves the scriptSig and witness of an unfinalized
+    /// input empty, this never returns a transaction that is missing input data. Script
+    /// verification is not done, see `Psbt::extract_tx_verified` (`bitcoinconsensus` feature).
+    ///
+    /// # Errors
+    ///
+    /// The same as [`extract_tx`], and [`ExtractTxError::NotFinalized`] if an input is not
+    /// finalized.
+    ///
+    /// [`extract_tx`]: Psbt::extract_tx
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt` or `Transaction`.
+    pub fn extract_tx_strict(self) -> Result<Transaction, ExtractTxError> {
+        if let Some(input_index) = self.inputs.iter().position(|input| !input.is_finalized()) {
+            return Err(ExtractTxError::NotFinalized { input_index, psbt: self });
+        }
+        self.internal_extract_tx_with_fee_rate_limit(Self::DEFAULT_MAX_FEE_RATE)
+    }
+
+    /// Extracts the [`Transaction`] like [`Psbt::extract_tx_strict`] and verifies its input
+    /// scripts.
+    ///
+    /// Every input is verified with `libbitcoinconsensus` against the output it spends, taken from
+    /// its witness UTXO or non-witness UTXO. The `libbitcoinconsensus` of Bitcoin Core 25 does not
+    /// verify Taproot spends, they are only checked to be finalized.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`Psbt::extract_tx_strict`], and [`ExtractTxError::ScriptVerification`] for the
+    /// first input whose script does not verify.
+    #[cfg(feature = "bitcoinconsensus")]
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt` or `Transaction`.
+    pub fn extract_tx_verified(self) -> Result<Transaction, ExtractTxError> {
+        let spent = (0..self.inputs.len())
+            .map(|input_index| self.spend_utxo(input_index).ok().cloned())
+            .collect::<Vec<_>>();
+        let tx = self.extract_tx_strict()?;
+
+        let serialized = bitcoin::consensus::encode::serialize(&tx);
+        for (input_index, spent) in spent.into_iter().enumerate() {
+            let spent = match spent {
+                Some(spent) => spent,
+                None => return Err(ExtractTxError::MissingInputValue { tx }),
+            };
+            if let Err(error) = spent.script_pubkey.verify(input_index, spent.value, &serialized) {
+                return Err(ExtractTxError::ScriptVerification { input_index, error, tx });
+            }
+        }
+        Ok(tx)
+    }
+
+    /// Returns a copy of this PSBT with all signatures, MuSig2 nonces, and finalized scripts
+    /// removed.
+    ///
+    /// UTXOs, scripts, key origins and all other metadata are kept, so the copy can be used to
+    /// start a fresh signing round while keeping the signed original.
+    pub fn clone_unsigned(&self) -> Psbt {
+        let mut psbt = self.clone();
+        for input in &mut psbt.inputs {
+            input.partial_sigs.clear();
+            input.tap_key_sig = None;
+            input.tap_script_sigs.clear();
+            // MuSig2 nonces must never be reused, a new signing round needs new nonces.
+            input.musig2_pub_nonces.clear();
+            input.musig2_partial_sigs.clear();
+            input.final_script_sig = None;
+            input.final_script_witness = None;
+        }
+        psbt
+    }
+
+    /// The maximum number of `OP_RETURN` outputs in a standard transaction.
+    pub const MAX_STANDARD_OP_RETURNS: usize = 1;
+
+    /// The maximum size of the script pubkey of a standard `OP_RETURN` output, in bytes
+    /// (`-datacarriersize`). The push of the data takes up to three bytes of it.
+    pub const MAX_STANDARD_OP_RETURN_SIZE: usize = 83;
+
+    /// Checks the `OP_RETURN` outputs of the unsigned transaction.
+    ///
+    /// An `OP_RETURN` output is unspendable so any value sent to it is burned. Transactions with
+    /// more than [`Psbt::MAX_STANDARD_OP_RETURNS`] `OP_RETURN` outputs, or an `OP_RETURN` script
+    /// pubkey larger than [`Psbt::MAX_STANDARD_OP_RETURN_SIZE`], are not relayed by default.
+    ///
+    /// # Panics
+    ///
+    /// If the length of transaction outputs is not equal to the length of PSBT outputs.
+    pub fn validate_op_returns(&self) -> Result<(), OpReturnError> {
+        assert_eq!(self.outputs.len(), self.unsigned_tx.output.len());
+
+        let mut count = 0;
+        for output in self.output_pairs() {
+            if !output.output().is_op_return(output.script_pubkey()) {
+                continue;
+            }
+            if output.value() != Amount::ZERO {
+                let output_index = output.index();
+                return Err(OpReturnError::NonZeroValue { output_index, value: output.value() });
+            }
+            let size = output.script_pubkey().len();
+            if size > Self::MAX_STANDARD_OP_RETURN_SIZE {
+                return Err(OpReturnError::TooLarge { output_index: output.index(), size });
+            }
+            count += 1;
+        }
+
+        if count > Self::MAX_STANDARD_OP_RETURNS {
+            return Err(OpReturnError::TooMany { count });
+        }
+        Ok(())
+    }
+
+    /// Removes the data an offline signer does not need to sign this PSBT.
+    ///
+    /// The unsigned transaction, UTXOs, scripts, key derivations and sighash types are kept. Global
+    /// xpubs and all proprietary and unknown fields are removed, reducing the size of the PSBT and
+    /// the information exposed to the signing device.
+    pub fn prune_for_signer(&mut self) {
+        self.xpub.clear();
+        self.proprietary.clear();
+        self.unknown.clear();
+        for input in &mut self.inputs {
+            input.proprietary.clear();
+            input.unknown.clear();
+        }
+        for output in &mut self.outputs {
+            output.proprietary.clear();
+            output.unknown.clear();
+        }
+    }
+
+    /// Returns true if every input is finalized and the fee rate is not above
+    /// [`Psbt::DEFAULT_MAX_FEE_RATE`], i.e., if [`Psbt::extract_tx`] would succeed and produce a
+    /// fully signed transaction.
+    pub fn is_ready_to_extract(&self) -> bool { self.clone().extract_tx_strict().is_ok() }
+
+    /// Extracts the transaction as far as it is finalized, along with the indices of the inputs
+    /// that are not.
+    ///
+    /// Finalized inputs get their final scriptSig and witness, the others are left with an empty
+    /// scriptSig and witness. The transaction is not valid unless no index is returned, but it can
+    /// be given to Bitcoin Core's `testmempoolaccept` to check the policy of the finalized inputs,
+    /// and its txid, which does not depend on witnesses, is that of the final transaction if every
+    /// input spends a segwit output.
+    pub fn extract_tx_partial(&self) -> (Transaction, Vec<usize>) {
+        (self.clone().internal_extract_tx(), self.unfinalized_inputs())
+    }
+
+    /// Returns true if every input is finalized, i.e., has a final scriptSig or witness.
+    ///
+    /// Unlike [`Psbt::is_ready_to_extract`] the fee is not checked, nothing is cloned or
+    /// extracted.
+    pub fn is_complete(&self) -> bool { self.inputs.iter().all(Input::is_finalized) }
+
+    /// Returns true if input `input_index` is finalized, false if it is not or does not exist.
+    pub fn is_finalized(&self, input_index: usize) -> bool {
+        self.inputs.get(input_index).map_or(false, Input::is_finalized)
+    }
+
+    /// Returns the indices of the inputs that are not finalized, in order.
+    fn unfinalized_inputs(&self) -> Vec<usize> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| !input.is_finalized())
+            .map(|(input_index, _)| input_index)
+            .collect()
+    }
+
     /// Perform [`extract_tx_fee_rate_limit`] without the fee rate check.
     ///
     /// This can result in a transaction with absurdly high fees. Use with caution.
@@ -192,7 +735,7 @@ impl Psbt {
     fn internal_extract_tx(self) -> Transaction {
         let mut tx: Transaction = self.unsigned_tx;
 
-        for (vin, psbtin) in tx.input.iter_mut().zip(self.inputs.into_iter()) {
+        for (vin, psbtin) in tx.input.iter_mut().zip(self.inputs) {
             vin.script_sig = psbtin.final_script_sig.unwrap_or_default();
             vin.witness = psbtin.final_script_witness.unwrap_or_default();
         }
@@ -201,6 +744,7 @@ impl Psbt {
     }
 
     #[inline]
+    #[allow(clippy::result_large_err)] // The error returns the `Psbt` or `Transaction`.
     fn internal_extract_tx_with_fee_rate_limit(
         self,
         max_fee_rate: FeeRate,
@@ -232,17 +776,99 @@ impl Psbt {
         Ok(tx)
     }
 
+    /// Returns the txid of the unsigned transaction.
+    ///
+    /// All copies of a PSBT that can be combined share this txid, which makes it useful for
+    /// routing PSBTs received from signers to the right session.
+    pub fn unsigned_tx_id(&self) -> Txid { self.unsigned_tx.compute_txid() }
+
+    /// Checks that `other` is a copy of the same PSBT, i.e., that it can be combined with this one.
+    pub fn validate_combine_source(&self, other: &Psbt) -> Result<(), CombineError> {
+        let expected = self.unsigned_tx_id();
+        let actual = other.unsigned_tx_id();
+        if expected != actual {
+            return Err(CombineError::TxidMismatch { expected, actual });
+        }
+        Ok(())
+    }
+
     /// Combines this [`Psbt`] with `other` PSBT as described by BIP 174.
     ///
     /// In accordance with BIP 174 this function is commutative i.e., `A.combine(B) == B.combine(A)`
+    ///
+    /// The unknown and proprietary fields of both PSBTs are kept, see
+    /// [`Psbt::combine_with_retention`].
     pub fn combine(&mut self, other: Self) -> Result<(), Error> {
-        if self.unsigned_tx != other.unsigned_tx {
+        self.combine_with_policy(other, CombinePolicy::Bip174)
+    }
+
+    /// Combines this [`Psbt`] with `other` PSBT using the given [`CombinePolicy`].
+    pub fn combine_with_policy(&mut self, other: Self, policy: CombinePolicy) -> Result<(), Error> {
+        // Hashing the transactions is faster than comparing them, the unsigned transaction has no
+        // witness data.
+        if self.unsigned_tx_id() != other.unsigned_tx_id() {
             return Err(Error::UnexpectedUnsignedTx {
                 expected: Box::new(self.unsigned_tx.clone()),
                 actual: Box::new(other.unsigned_tx),
             });
         }
+        self.combine_same_tx(other, policy).map_err(Error::CombineInconsistentKeySources)
+    }
+
+    /// Combines this [`Psbt`] with `other` like [`Psbt::combine_with_policy`], returning a
+    /// [`CombineError`].
+    pub(crate) fn try_combine_with_policy(
+        &mut self,
+        other: Self,
+        policy: CombinePolicy,
+    ) -> Result<(), CombineError> {
+        let expected = self.unsigned_tx_id();
+        let actual = other.unsigned_tx_id();
+        if expected != actual {
+            return Err(CombineError::TxidMismatch { expected, actual });
+        }
+        self.combine_same_tx(other, policy).map_err(CombineError::InconsistentKeySources)
+    }
+
+    /// Combines `other`, which has the same unsigned transaction, into this PSBT.
+    ///
+    /// Returns the xpub with inconsistent key sources on error.
+    fn combine_same_tx(&mut self, mut other: Self, policy: CombinePolicy) -> Result<(), Box<Xpub>> {
+        self.combine_global(&mut other)?;
+
+        // Inputs and outputs are only moved out of `other` if they add something.
+        for (self_input, other_input) in self.inputs.iter_mut().zip(&mut other.inputs) {
+            if other_input.is_contained_in(self_input) {
+                continue;
+            }
+            match policy {
+                CombinePolicy::Bip174 => self_input.combine(mem::take(other_input)),
+                CombinePolicy::PreferFinalized => {
+                    if self_input.is_finalized() {
+                        continue;
+                    }
+                    if other_input.is_finalized() {
+                        *self_input = mem::take(other_input);
+                    } else {
+                        self_input.combine(mem::take(other_input));
+                    }
+                }
+            }
+        }
+
+        for (self_output, other_output) in self.outputs.iter_mut().zip(&mut other.outputs) {
+            if !other_output.is_contained_in(self_output) {
+                self_output.combine(mem::take(other_output));
+            }
+        }
 
+        Ok(())
+    }
+
+    /// Combines the global map of `other`, except the unsigned transaction, into this PSBT's.
+    ///
+    /// Returns the xpub with inconsistent key sources on error.
+    fn combine_global(&mut self, other: &mut Psbt) -> Result<(), Box<Xpub>> {
         // BIP 174: The Combiner must remove any duplicate key-value pairs, in accordance with
         //          the specification. It can pick arbitrarily when conflicts occur.
 
@@ -250,7 +876,7 @@ impl Psbt {
         self.version = cmp::max(self.version, other.version);
 
         // Merging xpubs
-        for (xpub, (fingerprint1, derivation1)) in other.xpub {
+        for (xpub, (fingerprint1, derivation1)) in mem::take(&mut other.xpub) {
             match self.xpub.entry(xpub) {
                 btree_map::Entry::Vacant(entry) => {
                     entry.insert((fingerprint1, derivation1));
@@ -279,25 +905,207 @@ impl Psbt {
                         entry.insert((fingerprint1, derivation1));
                         continue;
                     }
-                    return Err(Error::CombineInconsistentKeySources(Box::new(xpub)));
+                    return Err(Box::new(xpub));
                 }
             }
         }
 
-        self.proprietary.extend(other.proprietary);
-        self.unknown.extend(other.unknown);
+        map::append(&mut self.proprietary, &mut other.proprietary);
+        map::append(&mut self.unknown, &mut other.unknown);
+        Ok(())
+    }
+
+    /// Combines this [`Psbt`] with each of `others` in turn as described by BIP 174.
+    ///
+    /// Unlike [`Psbt::combine`], which picks arbitrarily between two copies that have different
+    /// values for the same key, a conflict is an error. This lets a coordinator combining PSBTs
+    /// from several signers show which signer disagrees and about what. Global xpubs and versions
+    /// are merged as in [`Psbt::combine`] and are not reported as conflicts.
+    ///
+    /// Copies returned by the signers usually differ from this PSBT in only a few inputs, only
+    /// those are checked for conflicts and combined.
+    ///
+    /// # Errors
+    ///
+    /// If any of `others` is for a different unsigned transaction, has key sources that are
+    /// inconsistent with this PSBT's, or conflicts with this PSBT (see [`CombineConflict`]). On
+    /// error this PSBT is left combined with all PSBTs before the one that failed.
+    pub fn combine_many<I>(&mut self, others: I) -> Result<(), CombineError>
+    where
+        I: IntoIterator<Item = Psbt>,
+    {
+        // Hashing the transactions is faster than comparing them.
+        let expected = self.unsigned_tx_id();
+        for (psbt_index, mut other) in others.into_iter().enumerate() {
+            let actual = other.unsigned_tx_id();
+            if expected != actual {
+                return Err(CombineError::TxidMismatch { expected, actual });
+            }
+
+            // Comparing with this PSBT, which has the contributions of the PSBTs combined so
+            // far, every input changed by them would differ. Only inputs with pairs this PSBT does
+            // not have can conflict or change anything.
+            let inputs = self
+                .inputs
+                .iter()
+                .zip(&other.inputs)
+                .enumerate()
+                .filter_map(|(index, (ours, theirs))| {
+                    (!theirs.is_contained_in(ours)).then_some(index)
+                })
+                .collect::<Vec<_>>();
+            let outputs = self
+                .outputs
+                .iter()
+                .zip(&other.outputs)
+                .enumerate()
+                .filter_map(|(index, (ours, theirs))| {
+                    (!theirs.is_contained_in(ours)).then_some(index)
+                })
+                .collect::<Vec<_>>();
+            if let Some(conflict) = self.first_conflict(&other, psbt_index, &inputs, &outputs) {
+                return Err(CombineError::Conflict(Box::new(conflict)));
+            }
 
-        for (self_input, other_input) in self.inputs.iter_mut().zip(other.inputs.into_iter()) {
-            self_input.combine(other_input);
+            self.combine_global(&mut other).map_err(CombineError::InconsistentKeySources)?;
+            for index in inputs {
+                self.inputs[index].combine(mem::take(&mut other.inputs[index]));
+            }
+            for index in outputs {
+                self.outputs[index].combine(mem::take(&mut other.outputs[index]));
+            }
         }
+        Ok(())
+    }
+
+    /// Returns the first key that has a different value in `other` than in this PSBT, looking only
+    /// at the given inputs and outputs.
+    fn first_conflict(
+        &self,
+        other: &Psbt,
+        psbt_index: usize,
+        inputs: &[usize],
+        outputs: &[usize],
+    ) -> Option<CombineConflict> {
+        let conflict = |location, (key, ours, theirs)| CombineConflict {
+            psbt_index,
+            location,
+            key,
+            ours,
+            theirs,
+        };
 
-        for (self_output, other_output) in self.outputs.iter_mut().zip(other.outputs.into_iter()) {
-            self_output.combine(other_output);
+        // Of the global map only the proprietary and unknown fields can conflict, serializing the
+        // unsigned transaction is only worth it if they differ.
+        if self.proprietary != other.proprietary || self.unknown != other.unknown {
+            if let Some(pair) = map::conflicting_global_pairs(self, other).into_iter().next() {
+                return Some(conflict(MapLocation::Global, pair));
+            }
+        }
+        for &index in inputs {
+            let (ours, theirs) = (&self.inputs[index], &other.inputs[index]);
+            if let Some(pair) = map::conflicting_pairs(ours, theirs).into_iter().next() {
+                return Some(conflict(MapLocation::Input(index), pair));
+            }
+        }
+        for &index in outputs {
+            let (ours, theirs) = (&self.outputs[index], &other.outputs[index]);
+            if let Some(pair) = map::conflicting_pairs(ours, theirs).into_iter().next() {
+                return Some(conflict(MapLocation::Output(index), pair));
+            }
         }
+        None
+    }
 
+    /// Combines `input` with this PSBT's input at `index` (as described by BIP 174).
+    ///
+    /// Useful when a signer returns only its contribution to a single input instead of a whole
+    /// PSBT. The same per-input semantics as [`Psbt::combine`] are applied.
+    ///
+    /// # Errors
+    ///
+    /// If `index` is out of bounds for this PSBT's inputs.
+    pub fn merge_input(&mut self, index: usize, input: Input) -> Result<(), CombineError> {
+        self.check_index_is_within_bounds(index)?;
+        self.inputs[index].combine(input);
         Ok(())
     }
 
+    /// Joins `other`, a PSBT for a different transaction, into this PSBT.
+    ///
+    /// The inputs and outputs of `other` are appended to this PSBT's. Joining changes the data
+    /// that signatures commit to, so every signature whose sighash type commits to data changed
+    /// by the join is removed. Signatures that survive are those using `SIGHASH_ANYONECANPAY`
+    /// combined with `SIGHASH_NONE`, or with `SIGHASH_SINGLE` if the output at the input's index
+    /// is unchanged. MuSig2 partial signatures are removed, with the MuSig2 public nonces, if the
+    /// sighash type of the input commits to data changed by the join.
+    ///
+    /// Returns the removed signatures, indexed by input in the joined PSBT.
+    ///
+    /// # Errors
+    ///
+    /// If the transactions have different versions or lock times, spend the same outpoint, if
+    /// any input is finalized (its signatures can not be checked), or if the xpubs conflict.
+    pub fn merge_respecting_sighash(&mut self, other: Psbt) -> Result<Vec<DroppedSig>, JoinError> {
+        let (expected, actual) = (self.unsigned_tx.version, other.unsigned_tx.version);
+        if expected != actual {
+            return Err(JoinError::VersionMismatch { expected, actual });
+        }
+        let (expected, actual) = (self.unsigned_tx.lock_time, other.unsigned_tx.lock_time);
+        if expected != actual {
+            return Err(JoinError::LockTimeMismatch { expected, actual });
+        }
+        for txin in &other.unsigned_tx.input {
+            let outpoint = txin.previous_output;
+            if self.unsigned_tx.input.iter().any(|txin| txin.previous_output == outpoint) {
+                return Err(JoinError::DuplicateInput { outpoint });
+            }
+        }
+        if let Some(input_index) =
+            self.inputs.iter().chain(&other.inputs).position(Input::is_finalized)
+        {
+            return Err(JoinError::FinalizedInput { input_index });
+        }
+        for (xpub, source) in &other.xpub {
+            if self.xpub.get(xpub).map(|s| s != source).unwrap_or(false) {
+                return Err(JoinError::InconsistentKeySources(Box::new(*xpub)));
+            }
+        }
+
+        let mut joined = self.unsigned_tx.clone();
+        joined.input.extend(other.unsigned_tx.input.iter().cloned());
+        joined.output.extend(other.unsigned_tx.output.iter().cloned());
+
+        let mut dropped = vec![];
+        for (index, input) in self.inputs.iter_mut().enumerate() {
+            drop_invalidated_sigs(
+                input,
+                (index, &self.unsigned_tx),
+                (index, &joined),
+                &mut dropped,
+            );
+        }
+        let offset = self.inputs.len();
+        let Psbt { unsigned_tx, version, xpub, proprietary, unknown, mut inputs, outputs } = other;
+        for (index, input) in inputs.iter_mut().enumerate() {
+            drop_invalidated_sigs(
+                input,
+                (index, &unsigned_tx),
+                (offset + index, &joined),
+                &mut dropped,
+            );
+        }
+
+        self.unsigned_tx = joined;
+        self.version = cmp::max(self.version, version);
+        self.xpub.extend(xpub);
+        self.proprietary.extend(proprietary);
+        self.unknown.extend(unknown);
+        self.inputs.extend(inputs);
+        self.outputs.extend(outputs);
+        Ok(dropped)
+    }
+
     /// Attempts to create _all_ the required signatures for this PSBT using `k`.
     ///
     /// If you just want to sign an input with one specific key consider using `sighash_ecdsa` or
@@ -309,6 +1117,8 @@ impl Psbt {
     ///
     /// If an error is returned some signatures may already have been added to the PSBT. Since
     /// `partial_sigs` is a [`BTreeMap`] it is safe to retry, previous sigs will be overwritten.
+    ///
+    /// Unknown and proprietary fields are left alone, see [`Psbt::sign_with_retention`].
     pub fn sign<C, K>(
         &mut self,
         k: &K,
@@ -318,16 +1128,140 @@ impl Psbt {
         C: Signing + Verification,
         K: GetKey,
     {
-        let tx = self.unsigned_tx.clone(); // clone because we need to mutably borrow when signing.
-        let mut cache = SighashCache::new(&tx);
+        self.sign_inputs(0..self.inputs.len(), k, secp, None, None)
+    }
+
+    /// Attempts to create all the required signatures for this PSBT using `k`, like
+    /// [`Psbt::sign`], spreading the inputs over `threads` threads.
+    ///
+    /// Each thread signs a contiguous range of inputs, with its own sighash cache over the shared
+    /// unsigned transaction. Only the inputs a thread signs are copied, and they are moved back
+    /// once all threads are done. This pays off for PSBTs with hundreds of inputs, for small PSBTs
+    /// [`Psbt::sign`] is faster.
+    /// `threads` is clamped to between one and the number of inputs.
+    ///
+    /// # Returns
+    ///
+    /// The same as [`Psbt::sign`].
+    #[cfg(feature = "std")]
+    pub fn sign_parallel<C, K>(
+        &mut self,
+        k: &K,
+        secp: &Secp256k1<C>,
+        threads: usize,
+    ) -> Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>
+    where
+        C: Signing + Verification,
+        K: GetKey + Sync,
+    {
+        let len = self.inputs.len();
+        let threads = threads.clamp(1, cmp::max(len, 1));
+        let this = &*self;
+        let results = std::thread::scope(|scope| {
+            let handles = (0..threads)
+                .map(|thread| {
+                    let range = len * thread / threads..len * (thread + 1) / threads;
+                    scope.spawn(move || this.sign_range(range, k, secp, None, None))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<Vec<_>>()
+        });
 
         let mut used = BTreeMap::new();
         let mut errors = BTreeMap::new();
-
-        for i in 0..self.inputs.len() {
-            match self.signing_algorithm(i) {
+        for (signed, result) in results {
+            for (index, input) in signed {
+                self.inputs[index] = input;
+            }
+            match result {
+                Ok(keys) => used.extend(keys),
+                Err((keys, errs)) => {
+                    used.extend(keys);
+                    errors.extend(errs);
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(used)
+        } else {
+            Err((used, errors))
+        }
+    }
+
+    /// Implements [`Psbt::sign`] for the inputs in `indices`, refusing to sign inputs whose sighash
+    /// type `policy` does not allow, or that ask for a key at a path `derivation` does not allow.
+    fn sign_inputs<C, K>(
+        &mut self,
+        indices: core::ops::Range<usize>,
+        k: &K,
+        secp: &Secp256k1<C>,
+        policy: Option<&SighashPolicy>,
+        derivation: Option<&DerivationPolicy>,
+    ) -> Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>
+    where
+        C: Signing + Verification,
+        K: GetKey,
+    {
+        let (signed, result) = self.sign_range(indices, k, secp, policy, derivation);
+        for (index, input) in signed {
+            self.inputs[index] = input;
+        }
+        result
+    }
+
+    /// Signs the inputs in `indices` as [`Psbt::sign_inputs`] does, without modifying this PSBT.
+    ///
+    /// Returns the copies of the inputs that were signed, with their indices, along with the
+    /// result of signing. Inputs that are not signed are not copied.
+    fn sign_range<C, K>(
+        &self,
+        indices: core::ops::Range<usize>,
+        k: &K,
+        secp: &Secp256k1<C>,
+        policy: Option<&SighashPolicy>,
+        derivation: Option<&DerivationPolicy>,
+    ) -> (Vec<(usize, Input)>, SigningResult)
+    where
+        C: Signing + Verification,
+        K: GetKey,
+    {
+        let mut cache = SighashCache::new(&self.unsigned_tx);
+
+        let mut signed = vec![];
+        let mut used = BTreeMap::new();
+        let mut errors = BTreeMap::new();
+
+        let have_taproot_prevouts = self.require_all_prevouts_for_taproot().is_ok();
+
+        for i in indices {
+            let input = match self.checked_input(i) {
+                Ok(input) => input,
+                Err(e) => {
+                    errors.insert(i, e.into());
+                    continue;
+                }
+            };
+            // Copied on the first signature.
+            let mut input = Cow::Borrowed(input);
+
+            if let Some(sighash_type) = policy.and_then(|policy| policy.disallowed_input(self, i)) {
+                errors.insert(i, SignError::DisallowedSighashType(sighash_type));
+                continue;
+            }
+            if let Some(e) = derivation.and_then(|policy| policy.disallowed_input(self, i, k, secp))
+            {
+                errors.insert(i, SignError::DisallowedDerivation(e));
+                continue;
+            }
+            match self.signing_algorithm(i) {
+                Ok(SigningAlgorithm::Schnorr) if !have_taproot_prevouts => {
+                    errors.insert(i, SignError::MissingSpendUtxo);
+                }
                 Ok(SigningAlgorithm::Ecdsa) =>
-                    match self.bip32_sign_ecdsa(k, i, &mut cache, secp) {
+                    match self.bip32_sign_ecdsa(k, i, &mut input, &mut cache, secp) {
                         Ok(v) => {
                             used.insert(i, SigningKeys::Ecdsa(v));
                         }
@@ -336,7 +1270,7 @@ impl Psbt {
                         }
                     },
                 Ok(SigningAlgorithm::Schnorr) => {
-                    match self.bip32_sign_schnorr(k, i, &mut cache, secp) {
+                    match self.bip32_sign_schnorr(k, i, &mut input, &mut cache, secp) {
                         Ok(v) => {
                             used.insert(i, SigningKeys::Schnorr(v));
                         }
@@ -349,25 +1283,52 @@ impl Psbt {
                     errors.insert(i, e);
                 }
             }
+            // Signatures made before an error are kept.
+            if let Cow::Owned(input) = input {
+                signed.push((i, input));
+            }
         }
-        if errors.is_empty() {
-            Ok(used)
+        let result = if errors.is_empty() { Ok(used) } else { Err((used, errors)) };
+        (signed, result)
+    }
+
+    /// Checks that the UTXO of every input is known if any input spends a Taproot output.
+    ///
+    /// Taproot sighashes commit to all the outputs spent by the transaction, [`Psbt::sign`] uses
+    /// this check to refuse to sign any Taproot input until all of them are present.
+    ///
+    /// # Errors
+    ///
+    /// Returns the indices of the inputs that are missing UTXO information.
+    pub fn require_all_prevouts_for_taproot(&self) -> Result<(), Vec<usize>> {
+        let mut has_taproot = false;
+        let mut missing = vec![];
+        for input in self.input_pairs() {
+            match input.funding_utxo() {
+                Ok(utxo) => has_taproot |= utxo.script_pubkey.is_p2tr(),
+                Err(_) => missing.push(input.index()),
+            }
+        }
+
+        if has_taproot && !missing.is_empty() {
+            Err(missing)
         } else {
-            Err((used, errors))
+            Ok(())
         }
     }
 
-    /// Attempts to create all signatures required by this PSBT's `bip32_derivation` field, adding
-    /// them to `partial_sigs`.
+    /// Attempts to create all signatures required by the `bip32_derivation` field of the input at
+    /// `input_index`, adding them to the `partial_sigs` of `input`, a copy of that input.
     ///
     /// # Returns
     ///
     /// - Ok: A list of the public keys used in signing.
     /// - Err: Error encountered trying to calculate the sighash AND we had the signing key.
     fn bip32_sign_ecdsa<C, K, T>(
-        &mut self,
+        &self,
         k: &K,
         input_index: usize,
+        signed: &mut Cow<Input>,
         cache: &mut SighashCache<T>,
         secp: &Secp256k1<C>,
     ) -> Result<Vec<PublicKey>, SignError>
@@ -376,43 +1337,84 @@ impl Psbt {
         T: Borrow<Transaction>,
         K: GetKey,
     {
-        let msg_sighash_ty_res = self.sighash_ecdsa(input_index, cache);
-
-        let input = &mut self.inputs[input_index]; // Index checked in call to `sighash_ecdsa`.
-
-        let mut used = vec![]; // List of pubkeys used to sign the input.
+        let input = self.checked_input(input_index)?;
 
+        let mut keys = vec![];
         for (pk, key_source) in input.bip32_derivation.iter() {
-            let sk = if let Ok(Some(sk)) = k.get_key(&KeyRequest::Bip32(key_source.clone()), secp) {
-                sk
+            if let Ok(Some(sk)) = k.get_key(&KeyRequest::Bip32(key_source.clone()), secp) {
+                keys.push(sk);
             } else if let Ok(Some(sk)) = k.get_key(&KeyRequest::Pubkey(PublicKey::new(*pk)), secp) {
-                sk
-            } else {
-                continue;
-            };
-
-            // Only return the error if we have a secret key to sign this input.
-            let (msg, sighash_ty) = match msg_sighash_ty_res {
-                Err(e) => return Err(e),
-                Ok((msg, sighash_ty)) => (msg, sighash_ty),
-            };
-
-            let sig = ecdsa::Signature {
-                signature: secp.sign_ecdsa(&msg, &sk.inner),
-                sighash_type: sighash_ty,
-            };
+                keys.push(sk);
+            }
+        }
 
-            let pk = sk.public_key(secp);
+        let mut used = vec![]; // List of pubkeys used to sign the input.
 
-            input.partial_sigs.insert(pk, sig);
+        // Only returns an error if we have a secret key to sign this input.
+        for sk in keys {
+            let (pk, sig) = self.ecdsa_signature(input_index, &sk, None, cache, secp)?;
+            signed.to_mut().partial_sigs.insert(pk, sig);
             used.push(pk);
         }
 
         Ok(used)
     }
 
-    /// Attempts to create all signatures required by this PSBT's `tap_key_origins` field, adding
-    /// them to `tap_key_sig` or `tap_script_sigs`.
+    /// Signs the ECDSA input at `input_index` with the private key `sk`.
+    ///
+    /// This is the low level signing entry point, it does not check that `sk` is one of the keys
+    /// that the input's script requires. If `sighash_type` is `None` the sighash type of the input
+    /// is used (defaulting to [`EcdsaSighashType::All`]). A `sighash_type` that conflicts with the
+    /// input's sighash type is an error.
+    ///
+    /// # Returns
+    ///
+    /// Whether the signature was added or replaced an existing signature by `sk`.
+    pub fn sign_input<C: Signing>(
+        &mut self,
+        input_index: usize,
+        sk: &PrivateKey,
+        sighash_type: Option<EcdsaSighashType>,
+        secp: &Secp256k1<C>,
+    ) -> Result<SignatureUpdate, SignError> {
+        let (pk, sig) = {
+            let mut cache = SighashCache::new(&self.unsigned_tx);
+            self.ecdsa_signature(input_index, sk, sighash_type, &mut cache, secp)?
+        };
+
+        let input = &mut self.inputs[input_index]; // Index checked when computing the sighash.
+        match input.partial_sigs.insert(pk, sig) {
+            None => Ok(SignatureUpdate::Added),
+            Some(_) => Ok(SignatureUpdate::Replaced),
+        }
+    }
+
+    /// Creates the signature of the ECDSA input at `input_index` with the private key `sk`, see
+    /// [`Psbt::sign_input`].
+    fn ecdsa_signature<C, T>(
+        &self,
+        input_index: usize,
+        sk: &PrivateKey,
+        sighash_type: Option<EcdsaSighashType>,
+        cache: &mut SighashCache<T>,
+        secp: &Secp256k1<C>,
+    ) -> Result<(PublicKey, ecdsa::Signature), SignError>
+    where
+        C: Signing,
+        T: Borrow<Transaction>,
+    {
+        let (msg, sighash_type) = self.sighash_ecdsa_with_type(input_index, cache, sighash_type)?;
+
+        let sig = ecdsa::Signature { signature: secp.sign_ecdsa(&msg, &sk.inner), sighash_type };
+        Ok((sk.public_key(secp), sig))
+    }
+
+    /// Attempts to create all signatures required by the `tap_key_origins` field of the input at
+    /// `input_index`, adding them to the `tap_key_sig` or `tap_script_sigs` of `signed`, a copy of
+    /// that input.
+    ///
+    /// If `tap_internal_key` has no key origin but the signer holds its key, the key path is
+    /// signed and the internal key's origin is added to `tap_key_origins` with no leaf hashes.
     ///
     /// # Returns
     ///
@@ -420,9 +1422,10 @@ impl Psbt {
     ///   return the internal key.
     /// - Err: Error encountered trying to calculate the sighash AND we had the signing key.
     fn bip32_sign_schnorr<C, K, T>(
-        &mut self,
+        &self,
         k: &K,
         input_index: usize,
+        signed: &mut Cow<Input>,
         cache: &mut SighashCache<T>,
         secp: &Secp256k1<C>,
     ) -> Result<Vec<XOnlyPublicKey>, SignError>
@@ -431,7 +1434,7 @@ impl Psbt {
         T: Borrow<Transaction>,
         K: GetKey,
     {
-        let mut input = self.checked_input(input_index)?.clone();
+        let input = self.checked_input(input_index)?;
 
         let mut used = vec![]; // List of pubkeys used to sign the input.
 
@@ -440,6 +1443,8 @@ impl Psbt {
                 k.get_key(&KeyRequest::Bip32(key_source.clone()), secp)
             {
                 secret_key
+            } else if let Ok(Some(secret_key)) = k.get_key(&KeyRequest::XOnlyPubkey(xonly), secp) {
+                secret_key
             } else {
                 continue;
             };
@@ -455,20 +1460,9 @@ impl Psbt {
 
                 // Based on input.tap_internal_key.is_some() alone, it is not sufficient to determine whether it is a key path spend.
                 // According to BIP 371, we also need to consider the condition leaf_hashes.is_empty() for a more accurate determination.
-                if internal_key == xonly && leaf_hashes.is_empty() && input.tap_key_sig.is_none() {
-                    let (msg, sighash_type) = self.sighash_taproot(input_index, cache, None)?;
-                    let key_pair = Keypair::from_secret_key(secp, &sk.inner)
-                        .tap_tweak(secp, input.tap_merkle_root)
-                        .to_inner();
-
-                    #[cfg(feature = "rand-std")]
-                    let signature = secp.sign_schnorr(&msg, &key_pair);
-                    #[cfg(not(feature = "rand-std"))]
-                    let signature = secp.sign_schnorr_no_aux_rand(&msg, &key_pair);
-
-                    let signature = taproot::Signature { signature, sighash_type };
-                    input.tap_key_sig = Some(signature);
-
+                if internal_key == xonly && leaf_hashes.is_empty() && signed.tap_key_sig.is_none() {
+                    let sig = self.sign_taproot_key_spend(input, &sk, input_index, cache, secp)?;
+                    signed.to_mut().tap_key_sig = Some(sig);
                     used.push(internal_key);
                 }
             }
@@ -477,7 +1471,7 @@ impl Psbt {
             if let Some((leaf_hashes, _)) = input.tap_key_origins.get(&xonly) {
                 let leaf_hashes = leaf_hashes
                     .iter()
-                    .filter(|lh| !input.tap_script_sigs.contains_key(&(xonly, **lh)))
+                    .filter(|lh| !signed.tap_script_sigs.contains_key(&(xonly, **lh)))
                     .cloned()
                     .collect::<Vec<_>>();
 
@@ -487,14 +1481,9 @@ impl Psbt {
                     for lh in leaf_hashes {
                         let (msg, sighash_type) =
                             self.sighash_taproot(input_index, cache, Some(lh))?;
-
-                        #[cfg(feature = "rand-std")]
-                        let signature = secp.sign_schnorr(&msg, &key_pair);
-                        #[cfg(not(feature = "rand-std"))]
-                        let signature = secp.sign_schnorr_no_aux_rand(&msg, &key_pair);
-
+                        let signature = sign_schnorr(&msg, &key_pair, secp);
                         let signature = taproot::Signature { signature, sighash_type };
-                        input.tap_script_sigs.insert((xonly, lh), signature);
+                        signed.to_mut().tap_script_sigs.insert((xonly, lh), signature);
                     }
 
                     used.push(sk.public_key(secp).into());
@@ -502,11 +1491,127 @@ impl Psbt {
             }
         }
 
-        self.inputs[input_index] = input; // input_index is checked above.
+        // Key path spend with a key that has no key origin e.g., a non-HD key. The origin of the
+        // internal key is recorded with no leaf hashes, as BIP 371 allows, so that the signing key
+        // can be found without recovering it from the 64 byte signature. A key that is not from
+        // an HD wallet is its own master key, its fingerprint is that of the key itself and its
+        // path is empty, as Bitcoin Core records keys without HD metadata.
+        if let Some(internal_key) = input.tap_internal_key {
+            if signed.tap_key_sig.is_none() && !input.tap_key_origins.contains_key(&internal_key) {
+                if let Ok(Some(sk)) = k.get_key(&KeyRequest::XOnlyPubkey(internal_key), secp) {
+                    let sig = self.sign_taproot_key_spend(input, &sk, input_index, cache, secp)?;
+
+                    let hash = sk.public_key(secp).pubkey_hash();
+                    let fingerprint = Fingerprint::from([hash[0], hash[1], hash[2], hash[3]]);
+                    let signed = signed.to_mut();
+                    signed.tap_key_sig = Some(sig);
+                    signed.add_tap_key_origin(
+                        internal_key,
+                        None,
+                        (fingerprint, DerivationPath::master()),
+                    );
+                    used.push(internal_key);
+                }
+            }
+        }
 
         Ok(used)
     }
 
+    /// Creates a Taproot key path spend signature for `input` using the untweaked secret key `sk`.
+    fn sign_taproot_key_spend<C, T>(
+        &self,
+        input: &Input,
+        sk: &PrivateKey,
+        input_index: usize,
+        cache: &mut SighashCache<T>,
+        secp: &Secp256k1<C>,
+    ) -> Result<taproot::Signature, SignError>
+    where
+        C: Signing + Verification,
+        T: Borrow<Transaction>,
+    {
+        let (msg, sighash_type) = self.sighash_taproot(input_index, cache, None)?;
+        let key_pair = Keypair::from_secret_key(secp, &sk.inner)
+            .tap_tweak(secp, input.tap_merkle_root)
+            .to_inner();
+        let signature = sign_schnorr(&msg, &key_pair, secp);
+        Ok(taproot::Signature { signature, sighash_type })
+    }
+
+    /// Signs the script path spend of input `input_index` through the leaf `leaf_hash` with
+    /// `keypair`, adding the signature to `tap_script_sigs`.
+    ///
+    /// The leaf must be one of the input's `tap_scripts` and its script must contain the x-only
+    /// key of `keypair`. The sighash type is taken from the input, `SIGHASH_DEFAULT` if it has
+    /// none, and the sighash commits to the input's annex, if any.
+    pub fn sign_taproot_leaf<C: Signing>(
+        &mut self,
+        input_index: usize,
+        leaf_hash: TapLeafHash,
+        keypair: &Keypair,
+        secp: &Secp256k1<C>,
+    ) -> Result<taproot::Signature, SignError> {
+        let (xonly, _) = keypair.x_only_public_key();
+        self.check_tap_leaf(input_index, xonly, leaf_hash)?;
+
+        let mut cache = SighashCache::new(&self.unsigned_tx);
+        let (msg, sighash_type) = self.sighash_taproot(input_index, &mut cache, Some(leaf_hash))?;
+        let signature =
+            taproot::Signature { signature: sign_schnorr(&msg, keypair, secp), sighash_type };
+        self.inputs[input_index].tap_script_sigs.insert((xonly, leaf_hash), signature);
+        Ok(signature)
+    }
+
+    /// Adds a script path signature made outside of this crate, e.g. by a hardware wallet from
+    /// a sighash computed with [`Psbt::sighash_taproot`].
+    ///
+    /// The same checks as in [`Psbt::sign_taproot_leaf`] are made, and the signature's sighash
+    /// type must match the input's. The signature itself is not verified. Returns the signature
+    /// `pubkey` had already made for the leaf, if any.
+    pub fn insert_tap_script_sig(
+        &mut self,
+        input_index: usize,
+        pubkey: XOnlyPublicKey,
+        leaf_hash: TapLeafHash,
+        signature: taproot::Signature,
+    ) -> Result<Option<taproot::Signature>, SignError> {
+        self.check_tap_leaf(input_index, pubkey, leaf_hash)?;
+        let input = &mut self.inputs[input_index];
+        let input_ty = input.taproot_hash_ty().map_err(|_| invalid_sighash_type(input))?;
+        if input_ty != signature.sighash_type {
+            return Err(SignError::SighashTypeMismatch {
+                input: input_ty.into(),
+                requested: signature.sighash_type.into(),
+            });
+        }
+        Ok(input.tap_script_sigs.insert((pubkey, leaf_hash), signature))
+    }
+
+    /// Checks the leaf `leaf_hash` is one of the `tap_scripts` of input `input_index` and its
+    /// script contains `pubkey`.
+    fn check_tap_leaf(
+        &self,
+        input_index: usize,
+        pubkey: XOnlyPublicKey,
+        leaf_hash: TapLeafHash,
+    ) -> Result<(), SignError> {
+        let input = self.checked_input(input_index)?;
+        let (script, _) = input
+            .tap_scripts
+            .values()
+            .find(|(script, version)| TapLeafHash::from_script(script, *version) == leaf_hash)
+            .ok_or(SignError::UnknownLeaf(leaf_hash))?;
+        let key = pubkey.serialize();
+        let has_key = script.instructions().any(|instruction| {
+            matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == key)
+        });
+        if !has_key {
+            return Err(SignError::KeyNotInLeaf);
+        }
+        Ok(())
+    }
+
     /// Returns the sighash message to sign an ECDSA input along with the sighash type.
     ///
     /// Uses the [`EcdsaSighashType`] from this input if one is specified. If no sighash type is
@@ -516,6 +1621,17 @@ impl Psbt {
         &self,
         input_index: usize,
         cache: &mut SighashCache<T>,
+    ) -> Result<(Message, EcdsaSighashType), SignError> {
+        self.sighash_ecdsa_with_type(input_index, cache, None)
+    }
+
+    /// Implements [`Psbt::sighash_ecdsa`], using `sighash_type` instead of the input's sighash
+    /// type if it is given.
+    pub(crate) fn sighash_ecdsa_with_type<T: Borrow<Transaction>>(
+        &self,
+        input_index: usize,
+        cache: &mut SighashCache<T>,
+        sighash_type: Option<EcdsaSighashType>,
     ) -> Result<(Message, EcdsaSighashType), SignError> {
         use OutputType::*;
 
@@ -527,7 +1643,17 @@ impl Psbt {
         let utxo = self.spend_utxo(input_index)?;
         let spk = &utxo.script_pubkey; // scriptPubkey for input spend utxo.
 
-        let hash_ty = input.ecdsa_hash_ty().map_err(|_| SignError::InvalidSighashType)?; // Only support standard sighash types.
+        let hash_ty = match (sighash_type, input.sighash_type) {
+            (Some(hash_ty), None) => hash_ty,
+            (Some(hash_ty), Some(input_ty)) if PsbtSighashType::from(hash_ty) == input_ty =>
+                hash_ty,
+            (Some(hash_ty), Some(input_ty)) =>
+                return Err(SignError::SighashTypeMismatch {
+                    input: input_ty,
+                    requested: hash_ty.into(),
+                }),
+            (None, _) => input.ecdsa_hash_ty().map_err(|_| invalid_sighash_type(input))?, // Only support standard sighash types.
+        };
 
         match self.output_type(input_index)? {
             Bare => {
@@ -572,12 +1698,30 @@ impl Psbt {
     /// Returns the sighash message to sign an SCHNORR input along with the sighash type.
     ///
     /// Uses the [`TapSighashType`] from this input if one is specified. If no sighash type is
-    /// specified uses [`TapSighashType::Default`].
-    fn sighash_taproot<T: Borrow<Transaction>>(
+    /// specified uses [`TapSighashType::Default`]. The message is for a script path spend of the
+    /// leaf `leaf_hash` if one is given, otherwise for a key path spend, and commits to the
+    /// input's annex if it has one.
+    ///
+    /// Unless the sighash type is `ANYONECANPAY`, the spent outputs of all inputs are needed.
+    /// Sharing `cache` between calls for the same transaction avoids recomputing the hashes of
+    /// the prevouts, amounts, and sequences.
+    pub fn sighash_taproot<T: Borrow<Transaction>>(
         &self,
         input_index: usize,
         cache: &mut SighashCache<T>,
         leaf_hash: Option<TapLeafHash>,
+    ) -> Result<(Message, TapSighashType), SignError> {
+        self.sighash_taproot_with_type(input_index, cache, leaf_hash, None)
+    }
+
+    /// Implements [`Psbt::sighash_taproot`], using `sighash_type` instead of the input's sighash
+    /// type if it is given.
+    pub(crate) fn sighash_taproot_with_type<T: Borrow<Transaction>>(
+        &self,
+        input_index: usize,
+        cache: &mut SighashCache<T>,
+        leaf_hash: Option<TapLeafHash>,
+        sighash_type: Option<TapSighashType>,
     ) -> Result<(Message, TapSighashType), SignError> {
         use OutputType::*;
 
@@ -589,14 +1733,22 @@ impl Psbt {
 
         match self.output_type(input_index)? {
             Tr => {
-                let hash_ty = input
+                let input_ty = input
                     .sighash_type
-                    .unwrap_or_else(|| TapSighashType::Default.into())
-                    .taproot_hash_ty()
-                    .map_err(|_| SignError::InvalidSighashType)?;
+                    .map(|ty| ty.taproot_hash_ty().map_err(|_| invalid_sighash_type(input)))
+                    .transpose()?;
+                let hash_ty = match (sighash_type, input_ty) {
+                    (Some(hash_ty), Some(input_ty)) if hash_ty != input_ty =>
+                        return Err(SignError::SighashTypeMismatch {
+                            input: input_ty.into(),
+                            requested: hash_ty.into(),
+                        }),
+                    (Some(hash_ty), _) => hash_ty,
+                    (None, input_ty) => input_ty.unwrap_or(TapSighashType::Default),
+                };
 
                 let spend_utxos =
-                    (0..self.inputs.len()).map(|i| self.spend_utxo(i).ok()).collect::<Vec<_>>();
+                    self.input_pairs().map(|input| input.funding_utxo().ok()).collect::<Vec<_>>();
                 let all_spend_utxos;
 
                 let is_anyone_can_pay = PsbtSighashType::from(hash_ty).to_u32() & 0x80 != 0;
@@ -610,19 +1762,29 @@ impl Psbt {
                     all_spend_utxos = spend_utxos.iter().filter_map(|x| *x).collect::<Vec<_>>();
                     Prevouts::All(&all_spend_utxos)
                 } else {
-                    return Err(SignError::MissingSpendUtxo);
+                    let missing =
+                        spend_utxos.iter().position(Option::is_none).expect("one is none");
+                    return Err(if missing == input_index {
+                        SignError::MissingSpendUtxo
+                    } else {
+                        SignError::MissingPrevout { input_index: missing }
+                    });
                 };
 
-                let sighash = if let Some(leaf_hash) = leaf_hash {
-                    cache.taproot_script_spend_signature_hash(
-                        input_index,
-                        &prev_outs,
-                        leaf_hash,
-                        hash_ty,
-                    )?
-                } else {
-                    cache.taproot_key_spend_signature_hash(input_index, &prev_outs, hash_ty)?
-                };
+                let annex = input
+                    .tap_annex
+                    .as_deref()
+                    .map(|annex| sighash::Annex::new(annex).map_err(|_| SignError::InvalidAnnex))
+                    .transpose()?;
+                // Code separators are not supported, the last executed one is none.
+                let leaf_hash = leaf_hash.map(|leaf_hash| (leaf_hash, 0xFFFF_FFFF));
+                let sighash = cache.taproot_signature_hash(
+                    input_index,
+                    &prev_outs,
+                    annex,
+                    leaf_hash,
+                    hash_ty,
+                )?;
                 Ok((Message::from(sighash), hash_ty))
             }
             _ => Err(SignError::Unsupported),
@@ -631,16 +1793,9 @@ impl Psbt {
 
     /// Returns the spending utxo for this PSBT's input at `input_index`.
     pub fn spend_utxo(&self, input_index: usize) -> Result<&TxOut, SignError> {
-        let input = self.checked_input(input_index)?;
-        let utxo = if let Some(witness_utxo) = &input.witness_utxo {
-            witness_utxo
-        } else if let Some(non_witness_utxo) = &input.non_witness_utxo {
-            let vout = self.unsigned_tx.input[input_index].previous_output.vout;
-            &non_witness_utxo.output[vout as usize]
-        } else {
-            return Err(SignError::MissingSpendUtxo);
-        };
-        Ok(utxo)
+        self.check_index_is_within_bounds(input_index)?;
+        let input = self.input_pair(input_index).expect("index checked above");
+        input.funding_utxo().map_err(|_| SignError::MissingSpendUtxo)
     }
 
     /// Gets the input at `input_index` after checking that it is a valid index.
@@ -672,6 +1827,29 @@ impl Psbt {
         Ok(())
     }
 
+    /// Checks `output_index` is within bounds for the PSBT `outputs` array and
+    /// for the PSBT `unsigned_tx` `output` array.
+    fn check_output_index_is_within_bounds(
+        &self,
+        output_index: usize,
+    ) -> Result<(), IndexOutOfBoundsError> {
+        if output_index >= self.outputs.len() {
+            return Err(IndexOutOfBoundsError::Outputs {
+                index: output_index,
+                length: self.outputs.len(),
+            });
+        }
+
+        if output_index >= self.unsigned_tx.output.len() {
+            return Err(IndexOutOfBoundsError::TxOutput {
+                index: output_index,
+                length: self.unsigned_tx.output.len(),
+            });
+        }
+
+        Ok(())
+    }
+
     /// Returns the algorithm used to sign this PSBT's input at `input_index`.
     fn signing_algorithm(&self, input_index: usize) -> Result<SigningAlgorithm, SignError> {
         let output_type = self.output_type(input_index)?;
@@ -682,38 +1860,24 @@ impl Psbt {
     fn output_type(&self, input_index: usize) -> Result<OutputType, SignError> {
         let input = self.checked_input(input_index)?;
         let utxo = self.spend_utxo(input_index)?;
-        let spk = utxo.script_pubkey.clone();
-
-        // Anything that is not segwit and is not p2sh is `Bare`.
-        if !(spk.is_witness_program() || spk.is_p2sh()) {
-            return Ok(OutputType::Bare);
-        }
-
-        if spk.is_p2wpkh() {
-            return Ok(OutputType::Wpkh);
-        }
-
-        if spk.is_p2wsh() {
-            return Ok(OutputType::Wsh);
+        let spk = &utxo.script_pubkey;
+
+        match input.spend_kind(spk) {
+            SpendKind::P2pkh => Ok(OutputType::Bare),
+            SpendKind::P2sh => Ok(OutputType::Sh),
+            SpendKind::P2shWpkh => Ok(OutputType::ShWpkh),
+            SpendKind::P2wpkh => Ok(OutputType::Wpkh),
+            SpendKind::P2shWsh => Ok(OutputType::ShWsh),
+            SpendKind::P2wsh => Ok(OutputType::Wsh),
+            SpendKind::P2trKey | SpendKind::P2trScript => Ok(OutputType::Tr),
+            // A P2SH without its redeem script can only be signed as a legacy P2SH.
+            _ if spk.is_p2sh() => Ok(OutputType::Sh),
+            // Anything that is not segwit and is not p2sh is `Bare`.
+            _ if !spk.is_witness_program() => Ok(OutputType::Bare),
+            // Something is wrong with the input scriptPubkey or we do not know how to sign
+            // because there has been a new softfork that we do not yet support.
+            _ => Err(SignError::UnknownOutputType),
         }
-
-        if spk.is_p2sh() {
-            if input.redeem_script.as_ref().map(|s| s.is_p2wpkh()).unwrap_or(false) {
-                return Ok(OutputType::ShWpkh);
-            }
-            if input.redeem_script.as_ref().map(|x| x.is_p2wsh()).unwrap_or(false) {
-                return Ok(OutputType::ShWsh);
-            }
-            return Ok(OutputType::Sh);
-        }
-
-        if spk.is_p2tr() {
-            return Ok(OutputType::Tr);
-        }
-
-        // Something is wrong with the input scriptPubkey or we do not know how to sign
-        // because there has been a new softfork that we do not yet support.
-        Err(SignError::UnknownOutputType)
     }
 
     /// Calculates transaction fee.
@@ -728,8 +1892,9 @@ impl Psbt {
     /// - [`Error::FeeOverflow`] if an integer overflow occurs.
     pub fn fee(&self) -> Result<Amount, Error> {
         let mut inputs: u64 = 0;
-        for utxo in self.iter_funding_utxos() {
-            inputs = inputs.checked_add(utxo?.value.to_sat()).ok_or(Error::FeeOverflow)?;
+        for input in self.input_pairs() {
+            let utxo = input.funding_utxo()?;
+            inputs = inputs.checked_add(utxo.value.to_sat()).ok_or(Error::FeeOverflow)?;
         }
         let mut outputs: u64 = 0;
         for out in &self.unsigned_tx.output {
@@ -737,77 +1902,403 @@ impl Psbt {
         }
         inputs.checked_sub(outputs).map(Amount::from_sat).ok_or(Error::NegativeFee)
     }
-}
 
-/// Data required to call [`GetKey`] to get the private key to sign an input.
-#[derive(Clone, Debug, PartialEq, Eq)]
-#[non_exhaustive]
-pub enum KeyRequest {
-    /// Request a private key using the associated public key.
-    Pubkey(PublicKey),
-    /// Request a private key using BIP-32 fingerprint and derivation path.
-    Bip32(KeySource),
-}
-
-/// Trait to get a private key from a key request, key is then used to sign an input.
-pub trait GetKey {
-    /// An error occurred while getting the key.
-    type Error: core::fmt::Debug;
-
-    /// Attempts to get the private key for `key_request`.
+    /// Returns the maximum weight of the transaction once every input is spent using the Taproot
+    /// spend path given for it in `paths`.
     ///
-    /// # Returns
-    /// - `Some(key)` if the key is found.
-    /// - `None` if the key was not found but no error was encountered.
-    /// - `Err` if an error was encountered while looking for the key.
-    fn get_key<C: Signing>(
+    /// Every input is expected to spend a Taproot output, see [`Input::tap_satisfaction_weight`]
+    /// for how the weight of each input's witness is calculated.
+    pub fn max_weight_to_satisfy(
         &self,
-        key_request: &KeyRequest,
-        secp: &Secp256k1<C>,
-    ) -> Result<Option<PrivateKey>, Self::Error>;
-}
-
-impl GetKey for Xpriv {
-    type Error = GetKeyError;
+        paths: &BTreeMap<usize, TapSpendPath>,
+    ) -> Result<Weight, TapWeightError> {
+        if let Some((&index, _)) = paths.range(self.inputs.len()..).next() {
+            return Err(IndexOutOfBoundsError::Inputs { index, length: self.inputs.len() }.into());
+        }
 
-    fn get_key<C: Signing>(
-        &self,
-        key_request: &KeyRequest,
-        secp: &Secp256k1<C>,
-    ) -> Result<Option<PrivateKey>, Self::Error> {
-        match key_request {
-            KeyRequest::Pubkey(_) => Err(GetKeyError::NotSupported),
-            KeyRequest::Bip32((fingerprint, path)) => {
-                let key = if self.fingerprint(secp) == *fingerprint {
-                    let k = self.derive_priv(secp, &path)?;
-                    Some(k.to_priv())
-                } else if self.parent_fingerprint == *fingerprint
-                    && !path.is_empty()
-                    && path[0] == self.child_number
-                {
-                    let path = DerivationPath::from_iter(path.into_iter().skip(1).copied());
-                    let k = self.derive_priv(secp, &path)?;
-                    Some(k.to_priv())
-                } else {
-                    None
-                };
-                Ok(key)
-            }
+        // The segwit marker and flag.
+        let mut weight = self.unsigned_tx.weight() + Weight::from_witness_data_size(2);
+        for (input_index, input) in self.inputs.iter().enumerate() {
+            let path =
+                paths.get(&input_index).ok_or(TapWeightError::MissingSpendPath { input_index })?;
+            weight += input
+                .tap_satisfaction_weight(*path)
+                .map_err(|error| TapWeightError::Tap { input_index, error })?;
         }
+        Ok(weight)
     }
-}
 
-/// Map of input index -> signing key for that input (see [`SigningKeys`]).
-pub type SigningKeysMap = BTreeMap<usize, SigningKeys>;
+    /// Discards all but the cheapest satisfiable Taproot script path of every input.
+    ///
+    /// For each input with at least one leaf in `tap_scripts` that the input's script signatures
+    /// can satisfy, only the leaf with the smallest witness (see
+    /// [`Input::tap_satisfaction_weight`]) is kept, along with its script signatures. Finalizing
+    /// the PSBT afterwards uses the cheap path. Inputs with no satisfiable leaf are left untouched.
+    ///
+    /// Returns the indices of the inputs from which leaves were removed.
+    pub fn optimize_taproot_spends(&mut self) -> Vec<usize> {
+        self.inputs
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, input)| input.retain_cheapest_tap_leaf().then_some(index))
+            .collect()
+    }
 
-/// A list of keys used to sign an input.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum SigningKeys {
-    /// Keys used to sign an ECDSA input.
-    Ecdsa(Vec<PublicKey>),
-    /// Keys used to sign a Taproot input.
+    /// Calculates transaction fee, using `lookup` for inputs without UTXO information.
     ///
-    /// - Key path spend: This is the internal key.
+    /// UTXO data embedded in the PSBT is used if it is present, `lookup` is only called with the
+    /// previous outpoint of inputs that lack it (or whose `non_witness_utxo` does not contain the
+    /// spent output). This allows computing the fee for a PSBT when a wallet knows the prevouts
+    /// but they have not been added to the PSBT.
+    ///
+    /// # Panics
+    ///
+    /// If the length of transaction inputs is not equal to the length of PSBT inputs.
+    pub fn fee_with_lookup<F>(&self, lookup: F) -> Result<Amount, FeeError>
+    where
+        F: Fn(&OutPoint) -> Option<TxOut>,
+    {
+        let mut inputs: u64 = 0;
+        for input in self.input_pairs() {
+            let value = match input.funding_utxo() {
+                Ok(utxo) => utxo.value,
+                Err(_) =>
+                    lookup(&input.previous_output())
+                        .ok_or(FeeError::MissingUtxo { input_index: input.index() })?
+                        .value,
+            };
+            inputs = inputs.checked_add(value.to_sat()).ok_or(FeeError::FeeOverflow)?;
+        }
+        let mut outputs: u64 = 0;
+        for out in &self.unsigned_tx.output {
+            outputs = outputs.checked_add(out.value.to_sat()).ok_or(FeeError::FeeOverflow)?;
+        }
+        inputs.checked_sub(outputs).map(Amount::from_sat).ok_or(FeeError::NegativeFee)
+    }
+
+    /// Calculates the fee rate of the finalized transaction.
+    ///
+    /// The weight used is that of the transaction [`Psbt::extract_tx`] would return, so every
+    /// input must have a `final_script_sig` or `final_script_witness`. The fee rate is rounded
+    /// down to the nearest sat/kwu.
+    ///
+    /// # Errors
+    ///
+    /// - [`FeeError::MissingUtxo`] when UTXO information for an input is not present or is invalid.
+    /// - [`FeeError::NotFinalized`] when an input has not been finalized.
+    /// - [`FeeError::NegativeFee`] if the calculated fee is negative.
+    /// - [`FeeError::FeeOverflow`] if an integer overflow occurs.
+    pub fn fee_rate(&self) -> Result<FeeRate, FeeError> {
+        let fee = self.fee_with_lookup(|_| None)?;
+
+        if let Some(input_index) = self.inputs.iter().position(|input| {
+            input.final_script_sig.is_none() && input.final_script_witness.is_none()
+        }) {
+            return Err(FeeError::NotFinalized { input_index });
+        }
+
+        let mut tx = self.unsigned_tx.clone();
+        for (txin, input) in tx.input.iter_mut().zip(&self.inputs) {
+            txin.script_sig = input.final_script_sig.clone().unwrap_or_default();
+            txin.witness = input.final_script_witness.clone().unwrap_or_default();
+        }
+        Ok(FeeRate::from_sat_per_kwu(fee.to_sat().saturating_mul(1000) / tx.weight().to_wu()))
+    }
+
+    /// Tallies the number of entries of each kind of field across the whole PSBT.
+    ///
+    /// Useful as a diagnostic, for example when reporting bugs.
+    pub fn field_summary(&self) -> FieldSummary {
+        let mut summary = FieldSummary {
+            xpubs: self.xpub.len(),
+            proprietary: self.proprietary.len(),
+            unknown: self.unknown.len(),
+            ..Default::default()
+        };
+
+        for input in &self.inputs {
+            summary.partial_sigs += input.partial_sigs.len();
+            summary.bip32_derivations += input.bip32_derivation.len();
+            summary.tap_fields += usize::from(input.tap_key_sig.is_some())
+                + input.tap_script_sigs.len()
+                + input.tap_scripts.len()
+                + input.tap_key_origins.len()
+                + usize::from(input.tap_internal_key.is_some())
+                + usize::from(input.tap_merkle_root.is_some())
+                + input.musig2_participant_pubkeys.len()
+                + input.musig2_pub_nonces.len()
+                + input.musig2_partial_sigs.len();
+            summary.proprietary += input.proprietary.len();
+            summary.unknown += input.unknown.len();
+        }
+
+        for output in &self.outputs {
+            summary.bip32_derivations += output.bip32_derivation.len();
+            summary.tap_fields += usize::from(output.tap_internal_key.is_some())
+                + usize::from(output.tap_tree.is_some())
+                + output.tap_key_origins.len()
+                + output.musig2_participant_pubkeys.len();
+            summary.proprietary += output.proprietary.len();
+            summary.unknown += output.unknown.len();
+        }
+
+        summary
+    }
+}
+
+/// The number of entries of each kind of field in a PSBT, see [`Psbt::field_summary`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct FieldSummary {
+    /// Number of global extended public keys.
+    pub xpubs: usize,
+    /// Number of ECDSA partial signatures across all inputs.
+    pub partial_sigs: usize,
+    /// Number of BIP-32 derivation paths across all inputs and outputs.
+    pub bip32_derivations: usize,
+    /// Number of Taproot related entries across all inputs and outputs.
+    pub tap_fields: usize,
+    /// Number of proprietary entries in the global map, inputs, and outputs.
+    pub proprietary: usize,
+    /// Number of unknown entries in the global map, inputs, and outputs.
+    pub unknown: usize,
+}
+
+/// Creates a Schnorr signature, using auxiliary randomness if it is available.
+fn sign_schnorr<C: Signing>(
+    msg: &Message,
+    key_pair: &Keypair,
+    secp: &Secp256k1<C>,
+) -> secp256k1::schnorr::Signature {
+    #[cfg(feature = "rand-std")]
+    return secp.sign_schnorr(msg, key_pair);
+    #[cfg(not(feature = "rand-std"))]
+    return secp.sign_schnorr_no_aux_rand(msg, key_pair);
+}
+
+/// Removes the signatures of `input` that commit to data changed when the input moves from the
+/// `old` (index, transaction) to the `new` one, recording them in `dropped`.
+fn drop_invalidated_sigs(
+    input: &mut Input,
+    old: (usize, &Transaction),
+    new: (usize, &Transaction),
+    dropped: &mut Vec<DroppedSig>,
+) {
+    let ((old_index, old_tx), (input_index, new_tx)) = (old, new);
+
+    // Inputs are only appended, so comparing lengths is enough.
+    let inputs_changed = old_index != input_index || old_tx.input.len() != new_tx.input.len();
+    let outputs_changed = old_tx.output.len() != new_tx.output.len();
+    let output_unchanged = match (old_tx.output.get(old_index), new_tx.output.get(input_index)) {
+        (Some(old), Some(new)) => old == new,
+        _ => false,
+    };
+    let survives = |(base, anyone_can_pay): (SighashBase, bool)| {
+        if inputs_changed && !anyone_can_pay {
+            return false;
+        }
+        match base {
+            SighashBase::All => !outputs_changed,
+            SighashBase::None => true,
+            SighashBase::Single => output_unchanged,
+        }
+    };
+
+    input.partial_sigs.retain(|pubkey, sig| {
+        let keep = survives(SighashBase::from_ecdsa(sig.sighash_type));
+        if !keep {
+            dropped.push(DroppedSig::Ecdsa { input_index, pubkey: *pubkey });
+        }
+        keep
+    });
+    if let Some(sig) = input.tap_key_sig {
+        if !survives(SighashBase::from_taproot(sig.sighash_type)) {
+            input.tap_key_sig = None;
+            dropped.push(DroppedSig::TapKey { input_index });
+        }
+    }
+    input.tap_script_sigs.retain(|(pubkey, leaf_hash), sig| {
+        let keep = survives(SighashBase::from_taproot(sig.sighash_type));
+        if !keep {
+            dropped.push(DroppedSig::TapScript {
+                input_index,
+                pubkey: *pubkey,
+                leaf_hash: *leaf_hash,
+            });
+        }
+        keep
+    });
+    // The partial signatures commit to the sighash type of the input. Their nonces must not be
+    // used again for the new sighash, the participants start a new signing round.
+    let musig2_sighash = input.taproot_hash_ty().unwrap_or(TapSighashType::All);
+    if !survives(SighashBase::from_taproot(musig2_sighash)) {
+        dropped.extend(
+            input.musig2_partial_sigs.keys().map(|&key| DroppedSig::Musig2 { input_index, key }),
+        );
+        input.musig2_partial_sigs.clear();
+        input.musig2_pub_nonces.clear();
+    }
+}
+
+/// The outputs a sighash type commits to, independent of `SIGHASH_ANYONECANPAY`.
+#[derive(Clone, Copy)]
+enum SighashBase {
+    All,
+    None,
+    Single,
+}
+
+impl SighashBase {
+    /// Returns the base type and whether the `SIGHASH_ANYONECANPAY` flag is set.
+    fn from_ecdsa(sighash_type: EcdsaSighashType) -> (Self, bool) {
+        use EcdsaSighashType::*;
+
+        match sighash_type {
+            All => (SighashBase::All, false),
+            None => (SighashBase::None, false),
+            Single => (SighashBase::Single, false),
+            AllPlusAnyoneCanPay => (SighashBase::All, true),
+            NonePlusAnyoneCanPay => (SighashBase::None, true),
+            SinglePlusAnyoneCanPay => (SighashBase::Single, true),
+        }
+    }
+
+    /// Returns the base type and whether the `SIGHASH_ANYONECANPAY` flag is set.
+    fn from_taproot(sighash_type: TapSighashType) -> (Self, bool) {
+        use TapSighashType::*;
+
+        match sighash_type {
+            Default | All => (SighashBase::All, false),
+            None => (SighashBase::None, false),
+            Single => (SighashBase::Single, false),
+            AllPlusAnyoneCanPay => (SighashBase::All, true),
+            NonePlusAnyoneCanPay => (SighashBase::None, true),
+            SinglePlusAnyoneCanPay => (SighashBase::Single, true),
+        }
+    }
+}
+
+/// Controls how [`Psbt::combine_with_policy`] merges inputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CombinePolicy {
+    /// Combine all key-value pairs as described by BIP 174.
+    #[default]
+    Bip174,
+    /// If either copy of an input is finalized keep the finalized copy as is, skipping the merge
+    /// of signatures and other data for that input.
+    ///
+    /// Speeds up combining many partial copies of a PSBT where some inputs finalize early.
+    PreferFinalized,
+}
+
+/// Extension trait for combining a sequence of PSBTs.
+///
+/// ```
+/// # use psbt_v0::{CombineError, Psbt, PsbtCombineExt};
+/// fn collect(received: Vec<Psbt>) -> Result<Psbt, CombineError> {
+///     received.into_iter().try_combine_all()
+/// }
+/// ```
+pub trait PsbtCombineExt: Iterator<Item = Psbt> + Sized {
+    /// Combines all PSBTs in this iterator as described by BIP 174 (see [`Psbt::combine`]).
+    ///
+    /// # Errors
+    ///
+    /// If the iterator is empty or if any two PSBTs can not be combined.
+    fn try_combine_all(self) -> Result<Psbt, CombineError>;
+}
+
+impl<I: Iterator<Item = Psbt>> PsbtCombineExt for I {
+    fn try_combine_all(mut self) -> Result<Psbt, CombineError> {
+        let mut psbt = self.next().ok_or(CombineError::NoPsbts)?;
+        for other in self {
+            psbt.try_combine_with_policy(other, CombinePolicy::Bip174)?;
+        }
+        Ok(psbt)
+    }
+}
+
+/// Data required to call [`GetKey`] to get the private key to sign an input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyRequest {
+    /// Request a private key using the associated public key.
+    Pubkey(PublicKey),
+    /// Request a private key using BIP-32 fingerprint and derivation path.
+    Bip32(KeySource),
+    /// Request a private key using the associated x-only public key.
+    XOnlyPubkey(XOnlyPublicKey),
+}
+
+/// Trait to get a private key from a key request, key is then used to sign an input.
+pub trait GetKey {
+    /// An error occurred while getting the key.
+    type Error: core::fmt::Debug;
+
+    /// Attempts to get the private key for `key_request`.
+    ///
+    /// # Returns
+    /// - `Some(key)` if the key is found.
+    /// - `None` if the key was not found but no error was encountered.
+    /// - `Err` if an error was encountered while looking for the key.
+    fn get_key<C: Signing>(
+        &self,
+        key_request: &KeyRequest,
+        secp: &Secp256k1<C>,
+    ) -> Result<Option<PrivateKey>, Self::Error>;
+}
+
+impl GetKey for Xpriv {
+    type Error = GetKeyError;
+
+    fn get_key<C: Signing>(
+        &self,
+        key_request: &KeyRequest,
+        secp: &Secp256k1<C>,
+    ) -> Result<Option<PrivateKey>, Self::Error> {
+        match key_request {
+            KeyRequest::Pubkey(_) | KeyRequest::XOnlyPubkey(_) => Err(GetKeyError::NotSupported),
+            KeyRequest::Bip32((fingerprint, path)) => {
+                let key = if self.fingerprint(secp) == *fingerprint {
+                    let k = self.derive_priv(secp, &path)?;
+                    Some(k.to_priv())
+                } else if self.parent_fingerprint == *fingerprint
+                    && !path.is_empty()
+                    && path[0] == self.child_number
+                {
+                    let path = DerivationPath::from_iter(path.into_iter().skip(1).copied());
+                    let k = self.derive_priv(secp, &path)?;
+                    Some(k.to_priv())
+                } else {
+                    None
+                };
+                Ok(key)
+            }
+        }
+    }
+}
+
+/// Map of input index -> signing key for that input (see [`SigningKeys`]).
+pub type SigningKeysMap = BTreeMap<usize, SigningKeys>;
+
+/// What [`Psbt::sign_input`] did with the signature it created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignatureUpdate {
+    /// The input had no signature by the key, the signature was added.
+    Added,
+    /// The input had a signature by the key, it was replaced.
+    Replaced,
+}
+
+/// A list of keys used to sign an input.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SigningKeys {
+    /// Keys used to sign an ECDSA input.
+    Ecdsa(Vec<PublicKey>),
+    /// Keys used to sign a Taproot input.
+    ///
+    /// - Key path spend: This is the internal key.
     /// - Script path spend: This is the pubkey associated with the secret key that signed.
     Schnorr(Vec<XOnlyPublicKey>),
 }
@@ -815,6 +2306,10 @@ pub enum SigningKeys {
 /// Map of input index -> the error encountered while attempting to sign that input.
 pub type SigningErrors = BTreeMap<usize, SignError>;
 
+/// The result of signing several inputs, either every key used or the errors along with the keys
+/// used before them.
+type SigningResult = Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>;
+
 #[rustfmt::skip]
 macro_rules! impl_get_key_for_set {
     ($set:ident) => {
@@ -852,6 +2347,10 @@ impl GetKey for $map<PublicKey, PrivateKey> {
     ) -> Result<Option<PrivateKey>, Self::Error> {
         match key_request {
             KeyRequest::Pubkey(pk) => Ok(self.get(&pk).cloned()),
+            KeyRequest::XOnlyPubkey(xonly) => Ok(self
+                .iter()
+                .find(|(pk, _)| pk.inner.x_only_public_key().0 == *xonly)
+                .map(|(_, sk)| *sk)),
             KeyRequest::Bip32(_) => Err(GetKeyError::NotSupported),
         }
     }
@@ -951,14 +2450,30 @@ pub enum SigningAlgorithm {
 pub enum SignError {
     /// Input index out of bounds.
     IndexOutOfBounds(IndexOutOfBoundsError),
-    /// Invalid Sighash type.
-    InvalidSighashType,
+    /// The sighash type of the input is not valid for its signing algorithm.
+    InvalidSighashType(PsbtSighashType),
+    /// The requested sighash type is not the sighash type of the input.
+    SighashTypeMismatch {
+        /// The sighash type of the input.
+        input: PsbtSighashType,
+        /// The sighash type requested, or used by the signature being inserted.
+        requested: PsbtSighashType,
+    },
+    /// The sighash type is not allowed by the [`SighashPolicy`].
+    DisallowedSighashType(PsbtSighashType),
+    /// The derivation path of a signing key is not allowed by the [`DerivationPolicy`].
+    DisallowedDerivation(DerivationPolicyError),
     /// Missing input utxo.
     MissingInputUtxo,
     /// Missing Redeem script.
     MissingRedeemScript,
     /// Missing spending utxo.
     MissingSpendUtxo,
+    /// The UTXO spent by another input, which a Taproot sighash commits to, is missing.
+    MissingPrevout {
+        /// The index of the input whose UTXO is missing.
+        input_index: usize,
+    },
     /// Missing witness script.
     MissingWitnessScript,
     /// Signing algorithm and key type does not match.
@@ -981,6 +2496,12 @@ pub enum SignError {
     WrongSigningAlgorithm,
     /// Signing request currently unsupported.
     Unsupported,
+    /// The leaf is not one of the input's Taproot leaf scripts.
+    UnknownLeaf(TapLeafHash),
+    /// The leaf script does not contain the signing key.
+    KeyNotInLeaf,
+    /// The Taproot annex of the input does not start with `0x50`.
+    InvalidAnnex,
 }
 
 bitcoin_internals::impl_from_infallible!(SignError);
@@ -991,10 +2512,21 @@ impl fmt::Display for SignError {
 
         match *self {
             IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
-            InvalidSighashType => write!(f, "invalid sighash type"),
+            InvalidSighashType(sighash_type) =>
+                write!(f, "sighash type {} is not valid for the input", sighash_type),
+            SighashTypeMismatch { input, requested } => write!(
+                f,
+                "sighash type {} does not match the input's sighash type {}",
+                requested, input
+            ),
+            DisallowedSighashType(sighash_type) =>
+                write!(f, "sighash type {} is not allowed by the policy", sighash_type),
+            DisallowedDerivation(ref e) => write_err!(f, "disallowed derivation path"; e),
             MissingInputUtxo => write!(f, "missing input utxo in PBST"),
             MissingRedeemScript => write!(f, "missing redeem script"),
             MissingSpendUtxo => write!(f, "missing spend utxo in PSBT"),
+            MissingPrevout { input_index } =>
+                write!(f, "missing the utxo spent by input {}", input_index),
             MissingWitnessScript => write!(f, "missing witness script"),
             MismatchedAlgoKey => write!(f, "signing algorithm and key type does not match"),
             NotEcdsa => write!(f, "attempted to ECDSA sign an non-ECDSA input"),
@@ -1007,6 +2539,10 @@ impl fmt::Display for SignError {
             WrongSigningAlgorithm =>
                 write!(f, "attempt to sign an input with the wrong signing algorithm"),
             Unsupported => write!(f, "signing request currently unsupported"),
+            UnknownLeaf(leaf_hash) =>
+                write!(f, "leaf {} is not a leaf script of the input", leaf_hash),
+            KeyNotInLeaf => write!(f, "the leaf script does not contain the signing key"),
+            InvalidAnnex => write!(f, "the taproot annex does not start with 0x50"),
         }
     }
 }
@@ -1021,10 +2557,14 @@ impl std::error::Error for SignError {
             P2wpkhSighash(ref e) => Some(e),
             TaprootError(ref e) => Some(e),
             IndexOutOfBounds(ref e) => Some(e),
-            InvalidSighashType
+            DisallowedDerivation(ref e) => Some(e),
+            InvalidSighashType(_)
+            | SighashTypeMismatch { .. }
+            | DisallowedSighashType(_)
             | MissingInputUtxo
             | MissingRedeemScript
             | MissingSpendUtxo
+            | MissingPrevout { .. }
             | MissingWitnessScript
             | MismatchedAlgoKey
             | NotEcdsa
@@ -1032,11 +2572,19 @@ impl std::error::Error for SignError {
             | UnknownOutputType
             | KeyNotFound
             | WrongSigningAlgorithm
-            | Unsupported => None,
+            | Unsupported
+            | UnknownLeaf(_)
+            | KeyNotInLeaf
+            | InvalidAnnex => None,
         }
     }
 }
 
+/// Returns the error for an input whose `sighash_type` is not valid for its signing algorithm.
+fn invalid_sighash_type(input: &Input) -> SignError {
+    SignError::InvalidSighashType(input.sighash_type.expect("the default sighash type is valid"))
+}
+
 impl From<sighash::P2wpkhError> for SignError {
     fn from(e: sighash::P2wpkhError) -> Self { Self::P2wpkhSighash(e) }
 }
@@ -1049,123 +2597,644 @@ impl From<sighash::TaprootError> for SignError {
     fn from(e: sighash::TaprootError) -> Self { SignError::TaprootError(e) }
 }
 
-/// This error is returned when extracting a [`Transaction`] from a [`Psbt`].
+/// Errors encountered while combining PSBT data.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
-pub enum ExtractTxError {
-    /// The [`FeeRate`] is too high
-    AbsurdFeeRate {
-        /// The [`FeeRate`]
-        fee_rate: FeeRate,
-        /// The extracted [`Transaction`] (use this to ignore the error)
-        tx: Transaction,
-    },
-    /// One or more of the inputs lacks value information (witness_utxo or non_witness_utxo)
-    MissingInputValue {
-        /// The extracted [`Transaction`] (use this to ignore the error)
-        tx: Transaction,
-    },
-    /// Input value is less than Output Value, and the [`Transaction`] would be invalid.
-    SendingTooMuch {
-        /// The original [`Psbt`] is returned untouched.
-        psbt: Psbt,
+pub enum CombineError {
+    /// Input index out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The PSBTs have different unsigned transactions.
+    TxidMismatch {
+        /// The txid of the unsigned transaction of this PSBT.
+        expected: Txid,
+        /// The txid of the unsigned transaction of the other PSBT.
+        actual: Txid,
     },
+    /// Conflicting key sources for the same xpub.
+    InconsistentKeySources(Box<Xpub>),
+    /// There are no PSBTs to combine.
+    NoPsbts,
+    /// Two PSBTs have different values for the same key.
+    Conflict(Box<CombineConflict>),
+    /// A [`MergeConflict`] was rejected, see [`Psbt::combine_resolving`].
+    Unresolved(Box<MergeConflict>),
 }
 
-bitcoin_internals::impl_from_infallible!(ExtractTxError);
+bitcoin_internals::impl_from_infallible!(CombineError);
 
-impl fmt::Display for ExtractTxError {
+impl fmt::Display for CombineError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use ExtractTxError::*;
+        use CombineError::*;
 
         match *self {
-            AbsurdFeeRate { fee_rate, .. } =>
-                write!(f, "an absurdly high fee rate of {}", fee_rate),
-            MissingInputValue { .. } => write!(
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            TxidMismatch { expected, actual } => write!(
                 f,
-                "one of the inputs lacked value information (witness_utxo or non_witness_utxo)"
+                "unsigned transaction txid mismatch, expected {} got {}",
+                expected, actual
             ),
-            SendingTooMuch { .. } => write!(
+            InconsistentKeySources(ref xpub) => write!(f, "combine conflict: {}", xpub),
+            NoPsbts => f.write_str("no PSBTs to combine"),
+            Conflict(ref conflict) => write!(
                 f,
-                "transaction would be invalid due to output value being greater than input value."
+                "PSBT {} has a conflicting {} in the {} map",
+                conflict.psbt_index,
+                conflict.field(),
+                conflict.location
             ),
+            Unresolved(ref conflict) => write!(f, "unresolved combine conflict: {}", conflict),
         }
     }
 }
 
 #[cfg(feature = "std")]
-impl std::error::Error for ExtractTxError {
+impl std::error::Error for CombineError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        use ExtractTxError::*;
+        use CombineError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            TxidMismatch { .. }
+            | InconsistentKeySources(_)
+            | NoPsbts
+            | Conflict(_)
+            | Unresolved(_) => None,
+        }
+    }
+}
 
+impl From<IndexOutOfBoundsError> for CombineError {
+    fn from(e: IndexOutOfBoundsError) -> Self { CombineError::IndexOutOfBounds(e) }
+}
+
+/// A key that has different values in two PSBTs being combined by [`Psbt::combine_many`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombineConflict {
+    /// The index of the conflicting PSBT in the PSBTs being combined.
+    pub psbt_index: usize,
+    /// The map the key is in.
+    pub location: MapLocation,
+    /// The conflicting key.
+    pub key: raw::Key,
+    /// The serialized value of the key in the PSBT being combined into.
+    pub ours: Vec<u8>,
+    /// The serialized value of the key in the conflicting PSBT.
+    pub theirs: Vec<u8>,
+}
+
+impl CombineConflict {
+    /// Returns the name of the PSBT field the conflicting key belongs to, e.g., `partial_sigs`.
+    pub fn field(&self) -> &'static str { map::field_name(self.location, self.key.type_value) }
+}
+
+/// A key-value map of a PSBT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MapLocation {
+    /// The global map.
+    Global,
+    /// The map of the input at this index.
+    Input(usize),
+    /// The map of the output at this index.
+    Output(usize),
+}
+
+impl fmt::Display for MapLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
-            AbsurdFeeRate { .. } | MissingInputValue { .. } | SendingTooMuch { .. } => None,
+            MapLocation::Global => f.write_str("global"),
+            MapLocation::Input(index) => write!(f, "input {}", index),
+            MapLocation::Output(index) => write!(f, "output {}", index),
         }
     }
 }
 
-/// Input index out of bounds (actual index, maximum index allowed).
+/// A signature removed by [`Psbt::merge_respecting_sighash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DroppedSig {
+    /// An ECDSA signature from `partial_sigs`.
+    Ecdsa {
+        /// The index of the input in the joined PSBT.
+        input_index: usize,
+        /// The public key of the signature.
+        pubkey: PublicKey,
+    },
+    /// The Taproot key spend signature `tap_key_sig`.
+    TapKey {
+        /// The index of the input in the joined PSBT.
+        input_index: usize,
+    },
+    /// A Taproot script spend signature from `tap_script_sigs`.
+    TapScript {
+        /// The index of the input in the joined PSBT.
+        input_index: usize,
+        /// The x-only public key of the signature.
+        pubkey: XOnlyPublicKey,
+        /// The leaf hash of the signature.
+        leaf_hash: TapLeafHash,
+    },
+    /// A MuSig2 partial signature from `musig2_partial_sigs`.
+    ///
+    /// The MuSig2 public nonces of the input are removed with it.
+    Musig2 {
+        /// The index of the input in the joined PSBT.
+        input_index: usize,
+        /// The participant, aggregate key, and leaf hash of the partial signature.
+        key: Musig2Key,
+    },
+}
+
+/// Error returned by [`Psbt::merge_respecting_sighash`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
-pub enum IndexOutOfBoundsError {
-    /// The index is out of bounds for the `psbt.inputs` vector.
-    Inputs {
-        /// Attempted index access.
-        index: usize,
-        /// Length of the PBST inputs vector.
-        length: usize,
+pub enum JoinError {
+    /// The transactions have different versions.
+    VersionMismatch {
+        /// The version of this PSBT's transaction.
+        expected: transaction::Version,
+        /// The version of the other PSBT's transaction.
+        actual: transaction::Version,
     },
-    /// The index is out of bounds for the `psbt.unsigned_tx.input` vector.
-    TxInput {
-        /// Attempted index access.
-        index: usize,
-        /// Length of the PBST's unsigned transaction input vector.
-        length: usize,
+    /// The transactions have different lock times.
+    LockTimeMismatch {
+        /// The lock time of this PSBT's transaction.
+        expected: absolute::LockTime,
+        /// The lock time of the other PSBT's transaction.
+        actual: absolute::LockTime,
+    },
+    /// Both PSBTs spend the same outpoint.
+    DuplicateInput {
+        /// The outpoint spent by both PSBTs.
+        outpoint: OutPoint,
     },
+    /// An input is finalized so its signatures can not be checked.
+    FinalizedInput {
+        /// The index of the input in the joined PSBT.
+        input_index: usize,
+    },
+    /// Conflicting key sources for the same xpub.
+    InconsistentKeySources(Box<Xpub>),
 }
 
-bitcoin_internals::impl_from_infallible!(IndexOutOfBoundsError);
+bitcoin_internals::impl_from_infallible!(JoinError);
 
-impl fmt::Display for IndexOutOfBoundsError {
+impl fmt::Display for JoinError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use IndexOutOfBoundsError::*;
+        use JoinError::*;
 
         match *self {
-            Inputs { ref index, ref length } => write!(
-                f,
-                "index {} is out-of-bounds for PSBT inputs vector length {}",
-                index, length
-            ),
-            TxInput { ref index, ref length } => write!(
-                f,
-                "index {} is out-of-bounds for PSBT unsigned tx input vector length {}",
-                index, length
-            ),
+            VersionMismatch { expected, actual } =>
+                write!(f, "transaction version mismatch, expected {} got {}", expected, actual),
+            LockTimeMismatch { expected, actual } =>
+                write!(f, "transaction lock time mismatch, expected {} got {}", expected, actual),
+            DuplicateInput { outpoint } => write!(f, "both PSBTs spend {}", outpoint),
+            FinalizedInput { input_index } => write!(f, "input {} is finalized", input_index),
+            InconsistentKeySources(ref xpub) => write!(f, "join conflict: {}", xpub),
         }
     }
 }
 
 #[cfg(feature = "std")]
-impl std::error::Error for IndexOutOfBoundsError {
+impl std::error::Error for JoinError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        use IndexOutOfBoundsError::*;
+        use JoinError::*;
 
         match *self {
-            Inputs { .. } | TxInput { .. } => None,
+            VersionMismatch { .. }
+            | LockTimeMismatch { .. }
+            | DuplicateInput { .. }
+            | FinalizedInput { .. }
+            | InconsistentKeySources(_) => None,
         }
     }
 }
 
-#[cfg(feature = "base64")]
-mod display_from_str {
-    use core::fmt::{self, Display, Formatter};
-    use core::str::FromStr;
+/// Error returned by [`Psbt::set_input_sequence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SetSequenceError {
+    /// Input index out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// A signature commits to the sequence number that would be changed.
+    CommittedBySignature {
+        /// The index of the input with the signature.
+        input_index: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(SetSequenceError);
+
+impl fmt::Display for SetSequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SetSequenceError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            CommittedBySignature { input_index } =>
+                write!(f, "a signature on input {} commits to the sequence number", input_index),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SetSequenceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SetSequenceError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            CommittedBySignature { .. } => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for SetSequenceError {
+    fn from(e: IndexOutOfBoundsError) -> Self { SetSequenceError::IndexOutOfBounds(e) }
+}
+
+/// Error returned by [`Psbt::validate_op_returns`] and [`PsbtBuilder::add_data_output`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OpReturnError {
+    /// An `OP_RETURN` output has a non-zero value.
+    NonZeroValue {
+        /// The index of the output.
+        output_index: usize,
+        /// The value that would be burned.
+        value: Amount,
+    },
+    /// The transaction has more `OP_RETURN` outputs than is standard.
+    TooMany {
+        /// The number of `OP_RETURN` outputs.
+        count: usize,
+    },
+    /// The script pubkey of an `OP_RETURN` output is larger than is standard.
+    TooLarge {
+        /// The index of the output.
+        output_index: usize,
+        /// The size of the script pubkey, in bytes.
+        size: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(OpReturnError);
+
+impl fmt::Display for OpReturnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use OpReturnError::*;
+
+        match *self {
+            NonZeroValue { output_index, value } =>
+                write!(f, "OP_RETURN output {} has non-zero value {}", output_index, value),
+            TooMany { count } => write!(
+                f,
+                "{} OP_RETURN outputs exceeds the standard maximum of {}",
+                count,
+                Psbt::MAX_STANDARD_OP_RETURNS
+            ),
+            TooLarge { output_index, size } => write!(
+                f,
+                "OP_RETURN output {} has a {} byte script, exceeding the standard maximum of {}",
+                output_index,
+                size,
+                Psbt::MAX_STANDARD_OP_RETURN_SIZE
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OpReturnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use OpReturnError::*;
+
+        match *self {
+            NonZeroValue { .. } | TooMany { .. } | TooLarge { .. } => None,
+        }
+    }
+}
+
+/// Error returned by [`Psbt::max_weight_to_satisfy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TapWeightError {
+    /// A spend path was given for an input that does not exist.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// No spend path was given for an input.
+    MissingSpendPath {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The weight of an input's spend path could not be calculated.
+    Tap {
+        /// The index of the input.
+        input_index: usize,
+        /// The error calculating the weight.
+        error: TapError,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(TapWeightError);
+
+impl fmt::Display for TapWeightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TapWeightError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "spend path index out of bounds"; e),
+            MissingSpendPath { input_index } =>
+                write!(f, "no spend path given for input {}", input_index),
+            Tap { input_index, ref error } =>
+                write_err!(f, "spend path weight of input {}", input_index; error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TapWeightError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use TapWeightError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            MissingSpendPath { .. } => None,
+            Tap { ref error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for TapWeightError {
+    fn from(e: IndexOutOfBoundsError) -> Self { TapWeightError::IndexOutOfBounds(e) }
+}
+
+/// Errors encountered while calculating the fee of a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FeeError {
+    /// UTXO information for an input is neither in the PSBT nor provided by the lookup.
+    MissingUtxo {
+        /// The index of the input missing UTXO information.
+        input_index: usize,
+    },
+    /// The calculated fee is negative.
+    NegativeFee,
+    /// Integer overflow in fee calculation.
+    FeeOverflow,
+    /// An input has not been finalized, so the transaction weight is not known.
+    NotFinalized {
+        /// The index of the input that is not finalized.
+        input_index: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(FeeError);
+
+impl fmt::Display for FeeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FeeError::*;
+
+        match *self {
+            MissingUtxo { input_index } =>
+                write!(f, "UTXO information is not available for input {}", input_index),
+            NegativeFee => f.write_str("PSBT has a negative fee which is not allowed"),
+            FeeOverflow => f.write_str("integer overflow in fee calculation"),
+            NotFinalized { input_index } => write!(
+                f,
+                "input {} is not finalized, the transaction weight is unknown",
+                input_index
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FeeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use FeeError::*;
+
+        match *self {
+            MissingUtxo { .. } | NegativeFee | FeeOverflow | NotFinalized { .. } => None,
+        }
+    }
+}
+
+/// Extracts the transaction of a PSBT whose inputs are all finalized.
+///
+/// The fee is not checked, use [`Psbt::extract_tx`] to reject absurd fee rates.
+impl TryFrom<&Psbt> for Transaction {
+    type Error = IncompleteError;
+
+    fn try_from(psbt: &Psbt) -> Result<Self, Self::Error> {
+        if !psbt.is_complete() {
+            return Err(IncompleteError::NotFinalized { missing: psbt.unfinalized_inputs() });
+        }
+        Ok(psbt.clone().internal_extract_tx())
+    }
+}
+
+/// Error converting an incomplete PSBT to a [`Transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IncompleteError {
+    /// Some inputs are not finalized.
+    NotFinalized {
+        /// The indices of the inputs that are not finalized, in order.
+        missing: Vec<usize>,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(IncompleteError);
+
+impl fmt::Display for IncompleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IncompleteError::*;
+
+        match *self {
+            NotFinalized { ref missing } => write!(f, "inputs {:?} are not finalized", missing),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IncompleteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use IncompleteError::*;
+
+        match *self {
+            NotFinalized { .. } => None,
+        }
+    }
+}
+
+/// This error is returned when extracting a [`Transaction`] from a [`Psbt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExtractTxError {
+    /// The [`FeeRate`] is too high
+    AbsurdFeeRate {
+        /// The [`FeeRate`]
+        fee_rate: FeeRate,
+        /// The extracted [`Transaction`] (use this to ignore the error)
+        tx: Transaction,
+    },
+    /// One or more of the inputs lacks value information (witness_utxo or non_witness_utxo)
+    MissingInputValue {
+        /// The extracted [`Transaction`] (use this to ignore the error)
+        tx: Transaction,
+    },
+    /// Input value is less than Output Value, and the [`Transaction`] would be invalid.
+    SendingTooMuch {
+        /// The original [`Psbt`] is returned untouched.
+        psbt: Psbt,
+    },
+    /// An input is not finalized, the [`Transaction`] would be missing its scriptSig or witness.
+    NotFinalized {
+        /// The index of the first input that is not finalized.
+        input_index: usize,
+        /// The original [`Psbt`] is returned untouched.
+        psbt: Psbt,
+    },
+    /// The script of an input does not verify against the output it spends.
+    #[cfg(feature = "bitcoinconsensus")]
+    ScriptVerification {
+        /// The index of the first input that does not verify.
+        input_index: usize,
+        /// The error from `libbitcoinconsensus`.
+        error: bitcoin::consensus::validation::BitcoinconsensusError,
+        /// The extracted [`Transaction`] (use this to ignore the error)
+        tx: Transaction,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(ExtractTxError);
+
+impl fmt::Display for ExtractTxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ExtractTxError::*;
+
+        match *self {
+            AbsurdFeeRate { fee_rate, .. } =>
+                write!(f, "an absurdly high fee rate of {}", fee_rate),
+            MissingInputValue { .. } => write!(
+                f,
+                "one of the inputs lacked value information (witness_utxo or non_witness_utxo)"
+            ),
+            SendingTooMuch { .. } => write!(
+                f,
+                "transaction would be invalid due to output value being greater than input value."
+            ),
+            NotFinalized { input_index, .. } => write!(f, "input {} is not finalized", input_index),
+            #[cfg(feature = "bitcoinconsensus")]
+            ScriptVerification { input_index, ref error, .. } =>
+                write_err!(f, "the script of input {} does not verify", input_index; error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExtractTxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ExtractTxError::*;
+
+        match *self {
+            AbsurdFeeRate { .. }
+            | MissingInputValue { .. }
+            | SendingTooMuch { .. }
+            | NotFinalized { .. } => None,
+            #[cfg(feature = "bitcoinconsensus")]
+            ScriptVerification { ref error, .. } => Some(error),
+        }
+    }
+}
+
+/// Input or output index out of bounds (actual index, maximum index allowed).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IndexOutOfBoundsError {
+    /// The index is out of bounds for the `psbt.inputs` vector.
+    Inputs {
+        /// Attempted index access.
+        index: usize,
+        /// Length of the PBST inputs vector.
+        length: usize,
+    },
+    /// The index is out of bounds for the `psbt.unsigned_tx.input` vector.
+    TxInput {
+        /// Attempted index access.
+        index: usize,
+        /// Length of the PBST's unsigned transaction input vector.
+        length: usize,
+    },
+    /// The index is out of bounds for the `psbt.outputs` vector.
+    Outputs {
+        /// Attempted index access.
+        index: usize,
+        /// Length of the PBST outputs vector.
+        length: usize,
+    },
+    /// The index is out of bounds for the `psbt.unsigned_tx.output` vector.
+    TxOutput {
+        /// Attempted index access.
+        index: usize,
+        /// Length of the PBST's unsigned transaction output vector.
+        length: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(IndexOutOfBoundsError);
+
+impl fmt::Display for IndexOutOfBoundsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IndexOutOfBoundsError::*;
+
+        match *self {
+            Inputs { ref index, ref length } => write!(
+                f,
+                "index {} is out-of-bounds for PSBT inputs vector length {}",
+                index, length
+            ),
+            TxInput { ref index, ref length } => write!(
+                f,
+                "index {} is out-of-bounds for PSBT unsigned tx input vector length {}",
+                index, length
+            ),
+            Outputs { ref index, ref length } => write!(
+                f,
+                "index {} is out-of-bounds for PSBT outputs vector length {}",
+                index, length
+            ),
+            TxOutput { ref index, ref length } => write!(
+                f,
+                "index {} is out-of-bounds for PSBT unsigned tx output vector length {}",
+                index, length
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IndexOutOfBoundsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use IndexOutOfBoundsError::*;
+
+        match *self {
+            Inputs { .. } | TxInput { .. } | Outputs { .. } | TxOutput { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "base64")]
+mod display_from_str {
+    use core::fmt::{self, Display, Formatter};
+    use core::str::FromStr;
 
     use base64::display::Base64Display;
     use base64::prelude::{Engine as _, BASE64_STANDARD};
+    use bitcoin::hex::{FromHex, HexToBytesError};
     use bitcoin_internals::write_err;
 
     use super::{Error, Psbt};
+    use crate::prelude::*;
 
     /// Error encountered during PSBT decoding from Base64 string.
     #[derive(Debug)]
@@ -1175,6 +3244,8 @@ mod display_from_str {
         PsbtEncoding(Error),
         /// Error in PSBT Base64 encoding.
         Base64Encoding(::base64::DecodeError),
+        /// Error in PSBT hex encoding.
+        HexEncoding(HexToBytesError),
     }
 
     bitcoin_internals::impl_from_infallible!(PsbtParseError);
@@ -1186,6 +3257,7 @@ mod display_from_str {
             match *self {
                 PsbtEncoding(ref e) => write_err!(f, "error in internal PSBT data structure"; e),
                 Base64Encoding(ref e) => write_err!(f, "error in PSBT base64 encoding"; e),
+                HexEncoding(ref e) => write_err!(f, "error in PSBT hex encoding"; e),
             }
         }
     }
@@ -1198,22 +3270,58 @@ mod display_from_str {
             match self {
                 PsbtEncoding(e) => Some(e),
                 Base64Encoding(e) => Some(e),
+                HexEncoding(e) => Some(e),
             }
         }
     }
 
+    impl Psbt {
+        /// Serializes the PSBT as a base64 string, the encoding used by Bitcoin Core and HWI.
+        pub fn to_base64(&self) -> String { BASE64_STANDARD.encode(self.serialize()) }
+
+        /// Deserializes a PSBT from a base64 string.
+        pub fn from_base64(s: &str) -> Result<Self, PsbtParseError> {
+            let data = BASE64_STANDARD.decode(s).map_err(PsbtParseError::Base64Encoding)?;
+            Psbt::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)
+        }
+
+        /// Serializes the PSBT as a lower case hex string, see [`Psbt::serialize_hex`].
+        pub fn to_hex(&self) -> String { self.serialize_hex() }
+
+        /// Deserializes a PSBT from a hex string.
+        pub fn from_hex(s: &str) -> Result<Self, PsbtParseError> {
+            let data = Vec::from_hex(s).map_err(PsbtParseError::HexEncoding)?;
+            Psbt::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)
+        }
+    }
+
+    /// Formats the PSBT as base64, or as the multi-line dump of [`Psbt::dump`] with `{:#}`.
     impl Display for Psbt {
         fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            if f.alternate() {
+                return Display::fmt(&self.dump(), f);
+            }
             write!(f, "{}", Base64Display::new(&self.serialize(), &BASE64_STANDARD))
         }
     }
 
+    /// Parses a PSBT from base64 or hex.
+    ///
+    /// A string is parsed as hex if it starts with the hex encoding of the PSBT magic bytes and
+    /// separator, no base64 encoded PSBT does.
     impl FromStr for Psbt {
         type Err = PsbtParseError;
 
         fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let data = BASE64_STANDARD.decode(s).map_err(PsbtParseError::Base64Encoding)?;
-            Psbt::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)
+            const HEX_MAGIC: &str = "70736274ff";
+
+            if s.get(..HEX_MAGIC.len())
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case(HEX_MAGIC))
+            {
+                Psbt::from_hex(s)
+            } else {
+                Psbt::from_base64(s)
+            }
         }
     }
 }
@@ -1770,6 +3878,18 @@ mod tests {
                 assert_eq!(base64str.parse::<Psbt>().unwrap(), unserialized);
                 assert_eq!(base64str, unserialized.to_string());
                 assert_eq!(base64str.parse::<Psbt>().unwrap(), hex_psbt(base16str).unwrap());
+
+                assert_eq!(unserialized.to_base64(), base64str);
+                assert_eq!(Psbt::from_base64(base64str).unwrap(), unserialized);
+                assert_eq!(unserialized.to_hex(), base16str);
+                assert_eq!(Psbt::from_hex(base16str).unwrap(), unserialized);
+                // Either encoding is accepted when parsing.
+                assert_eq!(base16str.parse::<Psbt>().unwrap(), unserialized);
+                assert_eq!(base16str.to_uppercase().parse::<Psbt>().unwrap(), unserialized);
+                assert!(matches!(
+                    Psbt::from_hex("70736274fz"),
+                    Err(PsbtParseError::HexEncoding(_))
+                ));
             }
         }
 
@@ -1896,49 +4016,54 @@ mod tests {
         #[test]
         fn invalid_vectors() {
             let err = hex_psbt("70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a075701172102fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa232000000").unwrap_err();
-            assert_eq!(err.to_string(), "invalid xonly public key");
+            assert_eq!(pair_error(&err).field(), "tap_internal_key");
+            assert_eq!(pair_error(&err).location, MapLocation::Input(0));
+            assert_eq!(pair_error(&err).error.to_string(), "invalid length 33, expected 32 bytes");
             let err = hex_psbt("70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757011342173bb3d36c074afb716fec6307a069a2e450b995f3c82785945ab8df0e24260dcd703b0cbf34de399184a9481ac2b3586db6601f026a77f7e4938481bc34751701aa000000").unwrap_err();
             #[cfg(feature = "std")]
-            assert_eq!(err.to_string(), "invalid Taproot signature");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid Taproot signature");
             #[cfg(not(feature = "std"))]
             assert_eq!(
-                err.to_string(),
+                pair_error(&err).error.to_string(),
                 "invalid Taproot signature: invalid taproot signature size: 66"
             );
             let err = hex_psbt("70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757221602fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000800100000000000000000000").unwrap_err();
-            assert_eq!(err.to_string(), "invalid xonly public key");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid length 33, expected 32 bytes");
             let err = hex_psbt("70736274ff01007d020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02887b0100000000001600142382871c7e8421a00093f754d91281e675874b9f606b042a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757000001052102fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa23200").unwrap_err();
-            assert_eq!(err.to_string(), "invalid xonly public key");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid length 33, expected 32 bytes");
             let err = hex_psbt("70736274ff01007d020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02887b0100000000001600142382871c7e8421a00093f754d91281e675874b9f606b042a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a07570000220702fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da7560000800100008000000080010000000000000000").unwrap_err();
-            assert_eq!(err.to_string(), "invalid xonly public key");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid length 33, expected 32 bytes");
             let err = hex_psbt("70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6924214022cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b094089756aa3739ccc689ec0fcf3a360be32cc0b59b16e93a1e8bb4605726b2ca7a3ff706c4176649632b2cc68e1f912b8a578e3719ce7710885c7a966f49bcd43cb0000").unwrap_err();
-            #[cfg(feature = "std")]
-            assert_eq!(err.to_string(), "invalid hash when parsing slice");
-            #[cfg(not(feature = "std"))]
-            assert_eq!(
-                err.to_string(),
-                "invalid hash when parsing slice: invalid slice length 33 (expected 32)"
-            );
+            assert_eq!(pair_error(&err).error.to_string(), "invalid length 65, expected 64 bytes");
             let err = hex_psbt("70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b69241142cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b094289756aa3739ccc689ec0fcf3a360be32cc0b59b16e93a1e8bb4605726b2ca7a3ff706c4176649632b2cc68e1f912b8a578e3719ce7710885c7a966f49bcd43cb01010000").unwrap_err();
             #[cfg(feature = "std")]
-            assert_eq!(err.to_string(), "invalid Taproot signature");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid Taproot signature");
             #[cfg(not(feature = "std"))]
             assert_eq!(
-                err.to_string(),
+                pair_error(&err).error.to_string(),
                 "invalid Taproot signature: invalid taproot signature size: 66"
             );
             let err = hex_psbt("70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b69241142cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b093989756aa3739ccc689ec0fcf3a360be32cc0b59b16e93a1e8bb4605726b2ca7a3ff706c4176649632b2cc68e1f912b8a578e3719ce7710885c7a966f49bcd43cb0000").unwrap_err();
             #[cfg(feature = "std")]
-            assert_eq!(err.to_string(), "invalid Taproot signature");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid Taproot signature");
             #[cfg(not(feature = "std"))]
             assert_eq!(
-                err.to_string(),
+                pair_error(&err).error.to_string(),
                 "invalid Taproot signature: invalid taproot signature size: 57"
             );
             let err = hex_psbt("70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6926315c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f80023202cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc00000").unwrap_err();
-            assert_eq!(err.to_string(), "invalid control block");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid control block");
             let err = hex_psbt("70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6926115c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e123202cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc00000").unwrap_err();
-            assert_eq!(err.to_string(), "invalid control block");
+            assert_eq!(pair_error(&err).error.to_string(), "invalid control block");
+        }
+
+        /// Returns the error of the invalid pair `err` is for.
+        #[track_caller]
+        fn pair_error(err: &Error) -> &PairError {
+            match *err {
+                Error::Pair(ref e) => e,
+                ref e => panic!("not a pair error: {:?}", e),
+            }
         }
 
         fn rtt_psbt(psbt: Psbt) {
@@ -2027,14 +4152,407 @@ mod tests {
                         script_pubkey: ScriptBuf::from_hex("76a914d0c59903c5bac2868760e90fd521a4665aa7652088ac").unwrap(),
                     },
                     TxOut {
-                        value: Amount::from_sat(100_000_000),
-                        script_pubkey: ScriptBuf::from_hex("a9143545e6e33b832c47050f24d3eeb93c9c03948bc787").unwrap(),
+                        value: Amount::from_sat(100_000_000),
+                        script_pubkey: ScriptBuf::from_hex("a9143545e6e33b832c47050f24d3eeb93c9c03948bc787").unwrap(),
+                    },
+                ],
+            },
+            version: 0,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: BTreeMap::new(),
+
+            inputs: vec![
+                Input {
+                    non_witness_utxo: Some(Transaction {
+                        version: transaction::Version::ONE,
+                        lock_time: absolute::LockTime::ZERO,
+                        input: vec![
+                            TxIn {
+                                previous_output: OutPoint {
+                                    txid: "e567952fb6cc33857f392efa3a46c995a28f69cca4bb1b37e0204dab1ec7a389".parse().unwrap(),
+                                    vout: 1,
+                                },
+                                script_sig: ScriptBuf::from_hex("160014be18d152a9b012039daf3da7de4f53349eecb985").unwrap(),
+                                sequence: Sequence::MAX,
+                                witness: Witness::from_slice(&[
+                                    hex!("304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c01"),
+                                    hex!("03d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f2105"),
+                                ]),
+                            },
+                            TxIn {
+                                previous_output: OutPoint {
+                                    txid: "b490486aec3ae671012dddb2bb08466bef37720a533a894814ff1da743aaf886".parse().unwrap(),
+                                    vout: 1,
+                                },
+                                script_sig: ScriptBuf::from_hex("160014fe3e9ef1a745e974d902c4355943abcb34bd5353").unwrap(),
+                                sequence: Sequence::MAX,
+                                witness: Witness::from_slice(&[
+                                    hex!("3045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01"),
+                                    hex!("0223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab3"),
+                                ]),
+                            }
+                        ],
+                        output: vec![
+                            TxOut {
+                                value: Amount::from_sat(200_000_000),
+                                script_pubkey: ScriptBuf::from_hex("76a91485cff1097fd9e008bb34af709c62197b38978a4888ac").unwrap(),
+                            },
+                            TxOut {
+                                value: Amount::from_sat(190_303_501_938),
+                                script_pubkey: ScriptBuf::from_hex("a914339725ba21efd62ac753a9bcd067d6c7a6a39d0587").unwrap(),
+                            },
+                        ],
+                    }),
+                    ..Default::default()
+                },
+            ],
+            outputs: vec![
+                Output {
+                    ..Default::default()
+                },
+                Output {
+                    ..Default::default()
+                },
+            ],
+        };
+        unserialized.inputs[0].hash160_preimages = hash160_preimages;
+        unserialized.inputs[0].sha256_preimages = sha256_preimages;
+
+        let rtt: Psbt = hex_psbt(&unserialized.serialize_hex()).unwrap();
+        assert_eq!(rtt, unserialized);
+
+        // Now add an ripemd160 with incorrect preimage
+        let mut ripemd160_preimages = BTreeMap::new();
+        ripemd160_preimages.insert(ripemd160::Hash::hash(&[17u8]), vec![18u8]);
+        unserialized.inputs[0].ripemd160_preimages = ripemd160_preimages;
+
+        // Now the roundtrip should fail as the preimage is incorrect.
+        let rtt: Result<Psbt, _> = hex_psbt(&unserialized.serialize_hex());
+        assert!(rtt.is_err());
+    }
+
+    #[test]
+    fn serialize_and_deserialize_proprietary() {
+        let mut psbt: Psbt = hex_psbt("70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac000000000001076a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682dc86e2d73fa882920001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000").unwrap();
+        psbt.proprietary.insert(
+            raw::ProprietaryKey { prefix: b"test".to_vec(), subtype: 0u8, key: b"test".to_vec() },
+            b"test".to_vec(),
+        );
+        assert!(!psbt.proprietary.is_empty());
+        let rtt: Psbt = hex_psbt(&psbt.serialize_hex()).unwrap();
+        assert!(!rtt.proprietary.is_empty());
+    }
+
+    // PSBTs taken from BIP 174 test vectors.
+    #[test]
+    fn combine_psbts() {
+        let mut psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+        let psbt2 = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+        let psbt_combined = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+
+        psbt1.combine(psbt2).expect("psbt combine to succeed");
+        assert_eq!(psbt1, psbt_combined);
+    }
+
+    #[test]
+    fn combine_psbts_commutative() {
+        let mut psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+        let mut psbt2 = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+
+        let psbt1_clone = psbt1.clone();
+        let psbt2_clone = psbt2.clone();
+
+        psbt1.combine(psbt2_clone).expect("psbt1 combine to succeed");
+        psbt2.combine(psbt1_clone).expect("psbt2 combine to succeed");
+
+        assert_eq!(psbt1, psbt2);
+    }
+
+    #[test]
+    fn try_combine_all() {
+        let psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+        let psbt2 = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+
+        let mut expected = psbt1.clone();
+        expected.combine(psbt2.clone()).unwrap();
+
+        let combined = vec![psbt1, psbt2.clone(), psbt2].into_iter().try_combine_all();
+        assert_eq!(combined, Ok(expected));
+
+        assert_eq!(Vec::<Psbt>::new().into_iter().try_combine_all(), Err(CombineError::NoPsbts));
+    }
+
+    #[test]
+    fn try_combine_all_errors() {
+        let psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+
+        let mut other_tx = psbt1.clone();
+        other_tx.unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+        assert_eq!(
+            vec![psbt1.clone(), other_tx.clone()].into_iter().try_combine_all(),
+            Err(CombineError::TxidMismatch {
+                expected: psbt1.unsigned_tx_id(),
+                actual: other_tx.unsigned_tx_id(),
+            })
+        );
+
+        let secp = Secp256k1::new();
+        let xpub = Xpub::from_priv(&secp, &Xpriv::new_master(NetworkKind::Main, &[1; 32]).unwrap());
+        let path = |i| DerivationPath::from(vec![ChildNumber::from_normal_idx(i).unwrap()]);
+        let mut ours = psbt1.clone();
+        ours.xpub.insert(xpub, (Fingerprint::from([1; 4]), path(0)));
+        let mut theirs = psbt1;
+        theirs.xpub.insert(xpub, (Fingerprint::from([1; 4]), path(1)));
+        assert_eq!(
+            vec![ours, theirs].into_iter().try_combine_all(),
+            Err(CombineError::InconsistentKeySources(Box::new(xpub)))
+        );
+    }
+
+    #[test]
+    fn combine_many_reports_conflicts() {
+        let psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+        let psbt2 = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+
+        let mut expected = psbt1.clone();
+        expected.combine(psbt2.clone()).unwrap();
+        let mut combined = psbt1.clone();
+        combined.combine_many(vec![psbt2.clone(), psbt2.clone()]).unwrap();
+        assert_eq!(combined, expected);
+
+        // A signer that disagrees about the Taproot merkle root of the first input.
+        let root = |byte| taproot::TapNodeHash::from_byte_array([byte; 32]);
+        let mut base = psbt1.clone();
+        base.inputs[0].tap_merkle_root = Some(root(1));
+        let mut expected = base.clone();
+        expected.combine(psbt2.clone()).unwrap();
+        let mut conflicting = psbt1.clone();
+        conflicting.inputs[0].tap_merkle_root = Some(root(2));
+
+        let mut combined = base;
+        let err = combined.combine_many(vec![psbt2.clone(), conflicting]).unwrap_err();
+        let conflict = match err {
+            CombineError::Conflict(conflict) => conflict,
+            e => panic!("unexpected error: {:?}", e),
+        };
+        assert_eq!(conflict.psbt_index, 1);
+        assert_eq!(conflict.location, MapLocation::Input(0));
+        assert_eq!(conflict.field(), "tap_merkle_root");
+        assert_eq!(conflict.ours, vec![1; 32]);
+        assert_eq!(conflict.theirs, vec![2; 32]);
+        // The PSBTs before the conflicting one are combined.
+        assert_eq!(combined, expected);
+    }
+
+    #[test]
+    fn combine_many_skips_contained_inputs() {
+        let secp = Secp256k1::new();
+        let sig = |i: u8| {
+            let sk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+            let sig = secp.sign_ecdsa(&Message::from_digest([1; 32]), &sk);
+            (PublicKey::new(sk.public_key(&secp)), ecdsa::Signature::sighash_all(sig))
+        };
+        let txin = |vout| TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), vout),
+            ..TxIn::default()
+        };
+        let mut psbt = Psbt::with_capacity(2, 1);
+        psbt.push_input(txin(0), Input::default()).unwrap();
+        psbt.push_input(txin(1), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+
+        // Each signer signs its own input of a copy of the coordinator's PSBT.
+        let mut others = vec![psbt.clone(), psbt.clone()];
+        for (index, other) in others.iter_mut().enumerate() {
+            let (pk, sig) = sig(index as u8 + 1);
+            other.inputs[index].partial_sigs.insert(pk, sig);
+        }
+        // The second signer's copy of the first input adds nothing to the first signer's.
+        assert!(others[1].inputs[0].is_contained_in(&others[0].inputs[0]));
+
+        let mut expected = psbt.clone();
+        for other in others.clone() {
+            expected.combine(other).unwrap();
+        }
+        psbt.combine_many(others).unwrap();
+        assert_eq!(psbt, expected);
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+        assert_eq!(psbt.inputs[1].partial_sigs.len(), 1);
+
+        // A pair in both with another value is not contained.
+        let mut other = psbt.clone();
+        other.inputs[0].partial_sigs.insert(sig(1).0, sig(2).1);
+        assert!(!other.inputs[0].is_contained_in(&psbt.inputs[0]));
+        assert!(other.inputs[1].is_contained_in(&psbt.inputs[1]));
+    }
+
+    #[test]
+    fn merge_respecting_sighash() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sign = |i: u8, sighash_type| {
+            let sk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+            let sig = ecdsa::Signature { signature: secp.sign_ecdsa(&msg, &sk), sighash_type };
+            (PublicKey::new(sk.public_key(&secp)), sig)
+        };
+        let (single_acp, single_acp_sig) = sign(1, EcdsaSighashType::SinglePlusAnyoneCanPay);
+        let (all, all_sig) = sign(2, EcdsaSighashType::All);
+
+        let mut input = Input::default();
+        input.partial_sigs.insert(single_acp, single_acp_sig);
+        input.partial_sigs.insert(all, all_sig);
+
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), input).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+
+        let txin = TxIn { previous_output: OutPoint::new(Txid::all_zeros(), 1), ..TxIn::default() };
+        let mut other = Psbt::with_capacity(1, 0);
+        other.push_input(txin.clone(), Input::default()).unwrap();
+
+        assert_eq!(
+            psbt.clone().merge_respecting_sighash(psbt.clone()),
+            Err(JoinError::DuplicateInput { outpoint: OutPoint::default() })
+        );
+
+        let dropped = psbt.merge_respecting_sighash(other).unwrap();
+        assert_eq!(dropped, vec![DroppedSig::Ecdsa { input_index: 0, pubkey: all }]);
+        assert_eq!(psbt.unsigned_tx.input[1], txin);
+        assert_eq!(psbt.inputs.len(), 2);
+        assert_eq!(psbt.inputs[0].partial_sigs.keys().collect::<Vec<_>>(), vec![&single_acp]);
+    }
+
+    #[test]
+    fn merge_respecting_sighash_musig2() {
+        let secp = Secp256k1::new();
+        let pk = |i: u8| secp256k1::SecretKey::from_slice(&[i; 32]).unwrap().public_key(&secp);
+        let key = (pk(1), pk(2), None);
+
+        let mut input = Input::default();
+        input.musig2_pub_nonces.insert(key, Musig2PubNonce::from_byte_array([2; 66]));
+        input.musig2_partial_sigs.insert(key, Musig2PartialSig::from_byte_array([3; 32]));
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), input).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+
+        let txin = TxIn { previous_output: OutPoint::new(Txid::all_zeros(), 1), ..TxIn::default() };
+        let mut other = Psbt::with_capacity(1, 0);
+        other.push_input(txin, Input::default()).unwrap();
+
+        // A partial signature for `SIGHASH_NONE | SIGHASH_ANYONECANPAY` survives.
+        let mut none_acp = psbt.clone();
+        none_acp.inputs[0].sighash_type = Some(TapSighashType::NonePlusAnyoneCanPay.into());
+        let expected = none_acp.inputs[0].clone();
+        assert_eq!(none_acp.merge_respecting_sighash(other.clone()), Ok(vec![]));
+        assert_eq!(none_acp.inputs[0], expected);
+
+        // With the default sighash type it commits to all inputs.
+        let dropped = psbt.merge_respecting_sighash(other).unwrap();
+        assert_eq!(dropped, vec![DroppedSig::Musig2 { input_index: 0, key }]);
+        assert!(psbt.inputs[0].musig2_partial_sigs.is_empty());
+        assert!(psbt.inputs[0].musig2_pub_nonces.is_empty());
+    }
+
+    #[test]
+    fn combine_prefer_finalized() {
+        let mut partial = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+        let mut finalized = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+        finalized.inputs[0] = Input {
+            non_witness_utxo: finalized.inputs[0].non_witness_utxo.clone(),
+            final_script_sig: Some(ScriptBuf::from_hex("0001").unwrap()),
+            ..Default::default()
+        };
+        let finalized_input = finalized.inputs[0].clone();
+
+        let mut other = partial.clone();
+        other.combine_with_policy(finalized.clone(), CombinePolicy::PreferFinalized).unwrap();
+        assert_eq!(other.inputs[0], finalized_input);
+        assert_eq!(other.inputs[1].partial_sigs.len(), 2); // Non-finalized inputs are combined.
+
+        partial.combine_with_policy(finalized.clone(), CombinePolicy::PreferFinalized).unwrap();
+        finalized.combine_with_policy(other, CombinePolicy::PreferFinalized).unwrap();
+        assert_eq!(partial.inputs[0], finalized_input);
+        assert_eq!(finalized.inputs[0], finalized_input);
+    }
+
+    #[test]
+    fn merge_single_input() {
+        let mut psbt = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
+        let signed = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+        assert_eq!(psbt.inputs[1].partial_sigs.len(), 1);
+
+        psbt.merge_input(1, signed.inputs[1].clone()).expect("index is in bounds");
+
+        assert_eq!(psbt.inputs[1], signed.inputs[1]);
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1); // Other inputs are untouched.
+
+        let err = psbt.merge_input(2, signed.inputs[1].clone()).unwrap_err();
+        assert_eq!(
+            err,
+            CombineError::IndexOutOfBounds(IndexOutOfBoundsError::Inputs { index: 2, length: 2 })
+        );
+    }
+
+    #[cfg(feature = "rand-std")]
+    fn gen_keys() -> (PrivateKey, PublicKey, Secp256k1<All>) {
+        use bitcoin::secp256k1::rand::thread_rng;
+
+        let secp = Secp256k1::new();
+
+        let sk = SecretKey::new(&mut thread_rng());
+        let priv_key = PrivateKey::new(sk, NetworkKind::Test);
+        let pk = PublicKey::from_private_key(&secp, &priv_key);
+
+        (priv_key, pk, secp)
+    }
+
+    #[test]
+    #[cfg(feature = "rand-std")]
+    fn get_key_btree_map() {
+        let (priv_key, pk, secp) = gen_keys();
+
+        let mut key_map = BTreeMap::new();
+        key_map.insert(pk, priv_key);
+
+        let got = key_map.get_key(&KeyRequest::Pubkey(pk), &secp).expect("failed to get key");
+        assert_eq!(got.unwrap(), priv_key)
+    }
+
+    #[test]
+    fn test_fee() {
+        let output_0_val = Amount::from_sat(99_999_699);
+        let output_1_val = Amount::from_sat(100_000_000);
+        let prev_output_val = Amount::from_sat(200_000_000);
+
+        let mut t = Psbt {
+            unsigned_tx: Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::from_consensus(1257139),
+                input: vec![
+                    TxIn {
+                        previous_output: OutPoint {
+                            txid: "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126".parse().unwrap(),
+                            vout: 0,
+                        },
+                        sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
+                        ..Default::default()
+                    }
+                ],
+                output: vec![
+                    TxOut {
+                        value: output_0_val,
+                        script_pubkey:  ScriptBuf::new()
+                    },
+                    TxOut {
+                        value: output_1_val,
+                        script_pubkey:  ScriptBuf::new()
                     },
                 ],
             },
-            version: 0,
             xpub: Default::default(),
-            proprietary: Default::default(),
+            version: 0,
+            proprietary: BTreeMap::new(),
             unknown: BTreeMap::new(),
 
             inputs: vec![
@@ -2048,34 +4566,26 @@ mod tests {
                                     txid: "e567952fb6cc33857f392efa3a46c995a28f69cca4bb1b37e0204dab1ec7a389".parse().unwrap(),
                                     vout: 1,
                                 },
-                                script_sig: ScriptBuf::from_hex("160014be18d152a9b012039daf3da7de4f53349eecb985").unwrap(),
                                 sequence: Sequence::MAX,
-                                witness: Witness::from_slice(&[
-                                    hex!("304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c01"),
-                                    hex!("03d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f2105"),
-                                ]),
+                                ..Default::default()
                             },
                             TxIn {
                                 previous_output: OutPoint {
                                     txid: "b490486aec3ae671012dddb2bb08466bef37720a533a894814ff1da743aaf886".parse().unwrap(),
                                     vout: 1,
                                 },
-                                script_sig: ScriptBuf::from_hex("160014fe3e9ef1a745e974d902c4355943abcb34bd5353").unwrap(),
                                 sequence: Sequence::MAX,
-                                witness: Witness::from_slice(&[
-                                    hex!("3045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01"),
-                                    hex!("0223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab3"),
-                                ]),
+                                ..Default::default()
                             }
                         ],
                         output: vec![
                             TxOut {
-                                value: Amount::from_sat(200_000_000),
-                                script_pubkey: ScriptBuf::from_hex("76a91485cff1097fd9e008bb34af709c62197b38978a4888ac").unwrap(),
+                                value: prev_output_val,
+                                script_pubkey:  ScriptBuf::new()
                             },
                             TxOut {
                                 value: Amount::from_sat(190_303_501_938),
-                                script_pubkey: ScriptBuf::from_hex("a914339725ba21efd62ac753a9bcd067d6c7a6a39d0587").unwrap(),
+                                script_pubkey:  ScriptBuf::new()
                             },
                         ],
                     }),
@@ -2091,191 +4601,766 @@ mod tests {
                 },
             ],
         };
-        unserialized.inputs[0].hash160_preimages = hash160_preimages;
-        unserialized.inputs[0].sha256_preimages = sha256_preimages;
+        assert_eq!(
+            t.fee().expect("fee calculation"),
+            prev_output_val - (output_0_val + output_1_val)
+        );
+        // no previous output
+        let mut t2 = t.clone();
+        t2.inputs[0].non_witness_utxo = None;
+        match t2.fee().unwrap_err() {
+            Error::MissingUtxo => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        //  negative fee
+        let mut t3 = t.clone();
+        t3.unsigned_tx.output[0].value = prev_output_val;
+        match t3.fee().unwrap_err() {
+            Error::NegativeFee => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+        // overflow
+        t.unsigned_tx.output[0].value = Amount::MAX;
+        t.unsigned_tx.output[1].value = Amount::MAX;
+        match t.fee().unwrap_err() {
+            Error::FeeOverflow => {}
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
 
-        let rtt: Psbt = hex_psbt(&unserialized.serialize_hex()).unwrap();
-        assert_eq!(rtt, unserialized);
+    #[test]
+    fn fee_with_lookup() {
+        let outpoint = |vout| OutPoint { txid: bitcoin::Txid::all_zeros(), vout };
+        let txout = |sat| TxOut { value: Amount::from_sat(sat), script_pubkey: ScriptBuf::new() };
 
-        // Now add an ripemd160 with incorrect preimage
-        let mut ripemd160_preimages = BTreeMap::new();
-        ripemd160_preimages.insert(ripemd160::Hash::hash(&[17u8]), vec![18u8]);
-        unserialized.inputs[0].ripemd160_preimages = ripemd160_preimages;
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![
+                TxIn { previous_output: outpoint(0), ..Default::default() },
+                TxIn { previous_output: outpoint(1), ..Default::default() },
+            ],
+            output: vec![txout(2_500)],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(txout(1_000));
 
-        // Now the roundtrip should fail as the preimage is incorrect.
-        let rtt: Result<Psbt, _> = hex_psbt(&unserialized.serialize_hex());
-        assert!(rtt.is_err());
+        // The embedded UTXO takes precedence over the lookup.
+        let lookup = |op: &OutPoint| Some(txout(if op.vout == 0 { 0 } else { 2_000 }));
+        assert_eq!(psbt.fee_with_lookup(lookup), Ok(Amount::from_sat(500)));
+
+        assert_eq!(psbt.fee_with_lookup(|_| None), Err(FeeError::MissingUtxo { input_index: 1 }));
     }
 
     #[test]
-    fn serialize_and_deserialize_proprietary() {
-        let mut psbt: Psbt = hex_psbt("70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac000000000001076a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682dc86e2d73fa882920001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000").unwrap();
-        psbt.proprietary.insert(
-            raw::ProprietaryKey { prefix: b"test".to_vec(), subtype: 0u8, key: b"test".to_vec() },
-            b"test".to_vec(),
+    fn fee_rate() {
+        let txout = |sat| TxOut { value: Amount::from_sat(sat), script_pubkey: ScriptBuf::new() };
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 2],
+            output: vec![txout(1_000)],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(txout(10_000));
+        assert_eq!(psbt.fee_rate(), Err(FeeError::MissingUtxo { input_index: 1 }));
+
+        psbt.inputs[1].witness_utxo = Some(txout(10_000));
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&[vec![0; 72]]));
+        assert_eq!(psbt.fee_rate(), Err(FeeError::NotFinalized { input_index: 1 }));
+
+        psbt.inputs[1].final_script_sig = Some(ScriptBuf::from_bytes(vec![0; 100]));
+        let tx = psbt.clone().extract_tx_unchecked_fee_rate();
+        let expected = FeeRate::from_sat_per_kwu(19_000 * 1000 / tx.weight().to_wu());
+        assert_eq!(psbt.fee_rate(), Ok(expected));
+
+        psbt.unsigned_tx.output[0].value = Amount::from_sat(30_000);
+        assert_eq!(psbt.fee_rate(), Err(FeeError::NegativeFee));
+    }
+
+    #[test]
+    fn prune_for_signer() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let priv_key = PrivateKey::new(sk, NetworkKind::Test);
+        let pk = priv_key.public_key(&secp);
+        let xonly = XOnlyPublicKey::from(pk.inner);
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 3],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+
+        let spks = [
+            ScriptBuf::new_p2pkh(&pk.pubkey_hash()),
+            ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+            ScriptBuf::new_p2tr(&secp, xonly, None),
+        ];
+        let origin = (Fingerprint::default(), DerivationPath::default());
+        for (input, spk) in psbt.inputs.iter_mut().zip(spks) {
+            input.witness_utxo = Some(TxOut { value: Amount::from_sat(10), script_pubkey: spk });
+            input.bip32_derivation.insert(pk.inner, origin.clone());
+            input.unknown.insert(raw::Key { type_value: 0xAA, key_data: vec![] }, vec![]);
+        }
+        psbt.inputs[2].tap_internal_key = Some(xonly);
+        psbt.outputs[0].unknown.insert(raw::Key { type_value: 0xAA, key_data: vec![] }, vec![]);
+        psbt.unknown.insert(raw::Key { type_value: 0xAA, key_data: vec![] }, vec![]);
+
+        psbt.prune_for_signer();
+        assert!(psbt.unknown.is_empty());
+        assert!(psbt.inputs.iter().all(|input| input.unknown.is_empty()));
+        assert!(psbt.outputs[0].unknown.is_empty());
+
+        let mut key_map = BTreeMap::new();
+        key_map.insert(pk, priv_key);
+        let signing_keys = psbt.sign(&key_map, &secp).unwrap();
+
+        assert_eq!(signing_keys[&0], SigningKeys::Ecdsa(vec![pk]));
+        assert_eq!(signing_keys[&1], SigningKeys::Ecdsa(vec![pk]));
+        assert_eq!(signing_keys[&2], SigningKeys::Schnorr(vec![xonly]));
+    }
+
+    #[test]
+    fn require_all_prevouts_for_taproot() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let priv_key = PrivateKey::new(sk, NetworkKind::Test);
+        let pk = priv_key.public_key(&secp);
+        let xonly = XOnlyPublicKey::from(pk.inner);
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 2],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        assert_eq!(psbt.require_all_prevouts_for_taproot(), Ok(()));
+
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, xonly, None),
+        });
+        psbt.inputs[0].tap_internal_key = Some(xonly);
+        // Even with ANYONECANPAY, which only commits to the input's own prevout.
+        psbt.inputs[0].sighash_type = Some(TapSighashType::AllPlusAnyoneCanPay.into());
+        assert_eq!(psbt.require_all_prevouts_for_taproot(), Err(vec![1]));
+
+        let mut key_map = BTreeMap::new();
+        key_map.insert(pk, priv_key);
+        let (_, errors) = psbt.sign(&key_map, &secp).unwrap_err();
+        assert_eq!(errors[&0], SignError::MissingSpendUtxo);
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+    }
+
+    #[test]
+    fn validate_op_returns() {
+        let op_return = |sat| TxOut {
+            value: Amount::from_sat(sat),
+            script_pubkey: ScriptBuf::new_op_return([0xAA; 4]),
+        };
+
+        let mut psbt = Psbt::with_capacity(0, 3);
+        psbt.push_output(TxOut::NULL, Output::default());
+        psbt.push_output(op_return(0), Output::default());
+        assert_eq!(psbt.validate_op_returns(), Ok(()));
+
+        psbt.unsigned_tx.output[1].value = Amount::from_sat(1);
+        assert_eq!(
+            psbt.validate_op_returns(),
+            Err(OpReturnError::NonZeroValue { output_index: 1, value: Amount::from_sat(1) })
         );
-        assert!(!psbt.proprietary.is_empty());
-        let rtt: Psbt = hex_psbt(&psbt.serialize_hex()).unwrap();
-        assert!(!rtt.proprietary.is_empty());
+
+        psbt.unsigned_tx.output[1].value = Amount::ZERO;
+        psbt.push_output(op_return(0), Output::default());
+        assert_eq!(psbt.validate_op_returns(), Err(OpReturnError::TooMany { count: 2 }));
     }
 
-    // PSBTs taken from BIP 174 test vectors.
     #[test]
-    fn combine_psbts() {
+    fn set_input_sequence() {
+        let secp = Secp256k1::new();
+        let sk =
+            PrivateKey::new(secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(), NetworkKind::Test);
+        let pk = sk.public_key(&secp);
+
+        let mut psbt = Psbt::with_capacity(2, 1);
+        for _ in 0..2 {
+            psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        }
+        psbt.push_output(TxOut::NULL, Output::default());
+        for input in &mut psbt.inputs {
+            input.witness_utxo = Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+            });
+        }
+
+        assert_eq!(psbt.set_input_sequence(0, Sequence::ENABLE_RBF_NO_LOCKTIME), Ok(()));
+        assert_eq!(psbt.input_sequence(0), Some(Sequence::ENABLE_RBF_NO_LOCKTIME));
+        assert_eq!(psbt.input_sequence(2), None);
+
+        // A SIGHASH_ALL signature on the other input commits to all sequence numbers.
+        psbt.sign_input(1, &sk, None, &secp).unwrap();
+        assert_eq!(
+            psbt.set_input_sequence(0, Sequence::MAX),
+            Err(SetSequenceError::CommittedBySignature { input_index: 1 })
+        );
+        assert_eq!(psbt.input_sequence(0), Some(Sequence::ENABLE_RBF_NO_LOCKTIME));
+
+        // ANYONECANPAY only commits to the input's own sequence number.
+        psbt.inputs[1].partial_sigs.clear();
+        psbt.sign_input(1, &sk, Some(EcdsaSighashType::AllPlusAnyoneCanPay), &secp).unwrap();
+        assert_eq!(psbt.set_input_sequence(0, Sequence::MAX), Ok(()));
+        assert!(psbt.set_input_sequence(1, Sequence::MAX).is_err());
+    }
+
+    #[test]
+    fn max_weight_to_satisfy() {
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+
+        let mut paths = BTreeMap::new();
+        assert_eq!(
+            psbt.max_weight_to_satisfy(&paths),
+            Err(TapWeightError::MissingSpendPath { input_index: 0 })
+        );
+
+        paths.insert(0, TapSpendPath::KeySpend);
+        let input_weight = psbt.inputs[0].tap_satisfaction_weight(TapSpendPath::KeySpend).unwrap();
+        assert_eq!(
+            psbt.max_weight_to_satisfy(&paths),
+            Ok(psbt.unsigned_tx.weight() + Weight::from_wu(2) + input_weight)
+        );
+    }
+
+    #[test]
+    fn optimize_taproot_spends() {
+        use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+        use bitcoin::script::Builder;
+        use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+
+        let secp = Secp256k1::new();
+        let keypair_a = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let keypair_b = Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let (a, _) = keypair_a.x_only_public_key();
+        let (b, _) = keypair_b.x_only_public_key();
+
+        let pk = Builder::new().push_x_only_key(&a).push_opcode(OP_CHECKSIG).into_script();
+        let multi_a = Builder::new()
+            .push_x_only_key(&a)
+            .push_opcode(OP_CHECKSIG)
+            .push_x_only_key(&b)
+            .push_opcode(OP_CHECKSIGADD)
+            .push_int(2)
+            .push_opcode(OP_NUMEQUAL)
+            .into_script();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, pk.clone())
+            .unwrap()
+            .add_leaf(1, multi_a.clone())
+            .unwrap()
+            .finalize(&secp, a)
+            .unwrap();
+
+        let sign = |keypair: &Keypair| taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&Message::from_digest([1; 32]), keypair),
+            sighash_type: TapSighashType::Default,
+        };
+        let pk_hash = TapLeafHash::from_script(&pk, LeafVersion::TapScript);
+        let multi_a_hash = TapLeafHash::from_script(&multi_a, LeafVersion::TapScript);
+
+        let mut input = Input::default();
+        for script in [&pk, &multi_a] {
+            let leaf = (script.clone(), LeafVersion::TapScript);
+            input.tap_scripts.insert(spend_info.control_block(&leaf).unwrap(), leaf);
+        }
+        input.tap_script_sigs.insert((a, multi_a_hash), sign(&keypair_a));
+        input.tap_script_sigs.insert((b, multi_a_hash), sign(&keypair_b));
+
+        let mut psbt = Psbt::with_capacity(2, 1);
+        psbt.push_input(TxIn::default(), input.clone()).unwrap();
+        psbt.push_input(TxIn::default(), input).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+
+        // Both leaves are satisfiable in the first input.
+        psbt.inputs[0].tap_script_sigs.insert((a, pk_hash), sign(&keypair_a));
+
+        assert_eq!(psbt.optimize_taproot_spends(), vec![0, 1]);
+
+        // The expensive multisig leaf and its signatures are removed.
+        let input = &psbt.inputs[0];
+        assert_eq!(input.tap_scripts.len(), 1);
+        assert!(input.tap_scripts.values().all(|(script, _)| *script == pk));
+        assert_eq!(input.tap_script_sigs.keys().collect::<Vec<_>>(), vec![&(a, pk_hash)]);
+
+        // The only satisfiable leaf is kept, the unsatisfiable one is dropped.
+        let input = &psbt.inputs[1];
+        assert_eq!(input.tap_scripts.len(), 1);
+        assert!(input.tap_scripts.values().all(|(script, _)| *script == multi_a));
+        assert_eq!(input.tap_script_sigs.len(), 2);
+    }
+
+    #[test]
+    fn sighash_taproot() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let xonly = XOnlyPublicKey::from(sk.public_key(&secp));
+        let txout = |sat| TxOut {
+            value: Amount::from_sat(sat),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, xonly, None),
+        };
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 2],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx.clone()).unwrap();
+        psbt.inputs[0].witness_utxo = Some(txout(1_000));
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+
+        // All prevouts are needed for the default sighash type.
+        assert_eq!(
+            psbt.sighash_taproot(0, &mut cache, None),
+            Err(SignError::MissingPrevout { input_index: 1 })
+        );
+        psbt.inputs[1].witness_utxo = Some(txout(2_000));
+
+        let prevouts = [txout(1_000), txout(2_000)];
+        let mut expected_cache = SighashCache::new(&unsigned_tx);
+        let expected = expected_cache
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), TapSighashType::Default)
+            .unwrap();
+        assert_eq!(
+            psbt.sighash_taproot(0, &mut cache, None),
+            Ok((Message::from(expected), TapSighashType::Default))
+        );
+
+        let leaf_hash =
+            TapLeafHash::from_script(&ScriptBuf::new(), taproot::LeafVersion::TapScript);
+        let expected = expected_cache
+            .taproot_script_spend_signature_hash(
+                1,
+                &Prevouts::All(&prevouts),
+                leaf_hash,
+                TapSighashType::Default,
+            )
+            .unwrap();
+        assert_eq!(
+            psbt.sighash_taproot(1, &mut cache, Some(leaf_hash)),
+            Ok((Message::from(expected), TapSighashType::Default))
+        );
+
+        // Only the spent output of the input is needed with `ANYONECANPAY`.
+        psbt.inputs[1].witness_utxo = None;
+        psbt.inputs[0].sighash_type = Some(TapSighashType::AllPlusAnyoneCanPay.into());
+        let expected = expected_cache
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::One(0, &prevouts[0]),
+                TapSighashType::AllPlusAnyoneCanPay,
+            )
+            .unwrap();
+        assert_eq!(
+            psbt.sighash_taproot(0, &mut cache, None),
+            Ok((Message::from(expected), TapSighashType::AllPlusAnyoneCanPay))
+        );
+
+        // A sighash type that is not valid for Taproot is rejected.
+        psbt.inputs[0].sighash_type = Some(PsbtSighashType::from_u32(0x04));
+        assert_eq!(
+            psbt.sighash_taproot(0, &mut cache, None),
+            Err(SignError::InvalidSighashType(PsbtSighashType::from_u32(0x04)))
+        );
+        assert_eq!(psbt.sighash_ecdsa(0, &mut cache), Err(SignError::WrongSigningAlgorithm));
+    }
+
+    #[test]
+    fn tap_annex() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let xonly = XOnlyPublicKey::from(sk.public_key(&secp));
+        let utxo = TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, xonly, None),
+        };
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+        psbt.inputs[0].witness_utxo = Some(utxo.clone());
+        psbt.inputs[0].tap_internal_key = Some(xonly);
+        let weight = psbt.inputs[0].tap_satisfaction_weight(TapSpendPath::KeySpend).unwrap();
+
+        let annex = vec![0x50, 0x01, 0x02];
+        psbt.inputs[0].tap_annex = Some(annex.clone());
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+        let expected = SighashCache::new(&psbt.unsigned_tx)
+            .taproot_signature_hash(
+                0,
+                &Prevouts::All(&[utxo]),
+                Some(sighash::Annex::new(&annex).unwrap()),
+                None,
+                TapSighashType::Default,
+            )
+            .unwrap();
+        assert_eq!(
+            psbt.sighash_taproot(0, &mut cache, None),
+            Ok((Message::from(expected), TapSighashType::Default))
+        );
+        assert_eq!(
+            psbt.inputs[0].tap_satisfaction_weight(TapSpendPath::KeySpend).unwrap(),
+            weight + Weight::from_wu(1 + 3)
+        );
+
+        // The annex is an explicit field on the wire too, not a proprietary one.
+        let decoded = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(decoded.inputs[0].tap_annex, Some(annex.clone()));
+        assert!(decoded.inputs[0].proprietary.is_empty());
+
+        #[cfg(feature = "miniscript")]
+        {
+            let mut signed = psbt.clone();
+            let keys =
+                [(PublicKey::new(sk.public_key(&secp)), PrivateKey::new(sk, NetworkKind::Test))]
+                    .into_iter()
+                    .collect::<BTreeMap<_, _>>();
+            signed.sign(&keys, &secp).unwrap();
+            signed.finalize_mut().unwrap();
+            let witness = signed.inputs[0].final_script_witness.as_ref().unwrap();
+            assert_eq!(witness.len(), 2);
+            assert_eq!(witness.last(), Some(&annex[..]));
+        }
+
+        psbt.inputs[0].tap_annex = Some(vec![0x51]);
+        assert_eq!(psbt.sighash_taproot(0, &mut cache, None), Err(SignError::InvalidAnnex));
+        // It is not decoded either.
+        assert!(Psbt::deserialize(&psbt.serialize()).is_err());
+
+        // Nor is an annex on an input spending a non-Taproot output.
+        psbt.inputs[0].tap_annex = Some(annex);
+        psbt.inputs[0].witness_utxo.as_mut().unwrap().script_pubkey =
+            ScriptBuf::new_p2wpkh(&PublicKey::new(sk.public_key(&secp)).wpubkey_hash().unwrap());
+        assert!(Psbt::deserialize(&psbt.serialize()).is_err());
+    }
+
+    #[test]
+    fn sign_input_sighash_override() {
+        let secp = Secp256k1::new();
+        let sk =
+            PrivateKey::new(secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(), NetworkKind::Test);
+        let pk = sk.public_key(&secp);
+
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+        });
+
+        assert_eq!(
+            psbt.sign_input(0, &sk, Some(EcdsaSighashType::Single), &secp),
+            Ok(SignatureUpdate::Added)
+        );
+        assert_eq!(psbt.inputs[0].partial_sigs[&pk].sighash_type, EcdsaSighashType::Single);
+
+        // Re-signing replaces the signature.
+        assert_eq!(psbt.sign_input(0, &sk, None, &secp), Ok(SignatureUpdate::Replaced));
+        assert_eq!(psbt.inputs[0].partial_sigs[&pk].sighash_type, EcdsaSighashType::All);
+
+        // The override may not conflict with the input's sighash type.
+        psbt.inputs[0].sighash_type = Some(EcdsaSighashType::None.into());
+        assert_eq!(
+            psbt.sign_input(0, &sk, Some(EcdsaSighashType::Single), &secp),
+            Err(SignError::SighashTypeMismatch {
+                input: EcdsaSighashType::None.into(),
+                requested: EcdsaSighashType::Single.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn validate_combine_source() {
         let mut psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
         let psbt2 = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
-        let psbt_combined = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
 
-        psbt1.combine(psbt2).expect("psbt combine to succeed");
-        assert_eq!(psbt1, psbt_combined);
+        assert_eq!(psbt1.unsigned_tx_id(), psbt2.unsigned_tx_id());
+        assert_eq!(psbt1.validate_combine_source(&psbt2), Ok(()));
+        psbt1.combine(psbt2.clone()).expect("combine to succeed");
+
+        let mut other = psbt2.clone();
+        other.unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+        assert!(matches!(
+            psbt2.validate_combine_source(&other),
+            Err(CombineError::TxidMismatch { .. })
+        ));
     }
 
     #[test]
-    fn combine_psbts_commutative() {
-        let mut psbt1 = hex_psbt(include_str!("../tests/data/psbt1.hex")).unwrap();
-        let mut psbt2 = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+    fn clone_unsigned() {
+        let mut psbt = hex_psbt(include_str!("../tests/data/psbt2.hex")).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut::NULL);
+        psbt.inputs[1].final_script_witness = Some(Witness::from_slice(&[vec![0x01]]));
+
+        let unsigned = psbt.clone_unsigned();
+        for input in &unsigned.inputs {
+            assert!(input.partial_sigs.is_empty());
+            assert!(!input.is_finalized());
+        }
+        assert_eq!(unsigned.inputs[0].witness_utxo, Some(TxOut::NULL));
+        assert_eq!(unsigned.inputs[1].witness_script, psbt.inputs[1].witness_script);
+        assert!(!psbt.inputs[0].partial_sigs.is_empty());
+    }
 
-        let psbt1_clone = psbt1.clone();
-        let psbt2_clone = psbt2.clone();
+    #[test]
+    fn is_ready_to_extract() {
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(
+            TxOut { value: Amount::from_sat(99_999_000), script_pubkey: ScriptBuf::new() },
+            Output::default(),
+        );
+        psbt.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(100_000_000), script_pubkey: ScriptBuf::new() });
+        assert!(!psbt.is_ready_to_extract());
 
-        psbt1.combine(psbt2_clone).expect("psbt1 combine to succeed");
-        psbt2.combine(psbt1_clone).expect("psbt2 combine to succeed");
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&[vec![0x01; 72]]));
+        assert!(psbt.is_ready_to_extract());
 
-        assert_eq!(psbt1, psbt2);
+        psbt.unsigned_tx.output[0].value = Amount::ZERO;
+        assert!(!psbt.is_ready_to_extract());
     }
 
-    #[cfg(feature = "rand-std")]
-    fn gen_keys() -> (PrivateKey, PublicKey, Secp256k1<All>) {
-        use bitcoin::secp256k1::rand::thread_rng;
+    #[test]
+    fn extract_tx_strict() {
+        let mut psbt = Psbt::with_capacity(2, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(
+            TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() },
+            Output::default(),
+        );
+        for input in &mut psbt.inputs {
+            input.witness_utxo =
+                Some(TxOut { value: Amount::from_sat(2_000), script_pubkey: ScriptBuf::new() });
+        }
+        let witness = Witness::from_slice(&[vec![0x01; 72]]);
+        psbt.inputs[0].final_script_witness = Some(witness.clone());
+
+        // The unchecked extractor leaves the witness of the second input empty.
+        assert!(psbt.clone().extract_tx().is_ok());
+        match psbt.clone().extract_tx_strict() {
+            Err(ExtractTxError::NotFinalized { input_index, psbt: returned }) => {
+                assert_eq!(input_index, 1);
+                assert_eq!(returned, psbt);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        psbt.inputs[1].final_script_witness = Some(witness.clone());
+        let tx = psbt.extract_tx_strict().unwrap();
+        assert!(tx.input.iter().all(|txin| txin.witness == witness));
+    }
 
+    #[test]
+    #[cfg(feature = "bitcoinconsensus")]
+    fn extract_tx_verified() {
         let secp = Secp256k1::new();
+        let sk =
+            PrivateKey::new(secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(), NetworkKind::Test);
+        let pk = sk.public_key(&secp);
+
+        let mut psbt = Psbt::with_capacity(1, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(
+            TxOut { value: Amount::from_sat(9_000), script_pubkey: ScriptBuf::new() },
+            Output::default(),
+        );
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+        });
+        psbt.sign_input(0, &sk, None, &secp).unwrap();
+        let sig = psbt.inputs[0].partial_sigs[&pk];
+        psbt.inputs[0].final_script_witness =
+            Some(Witness::from_slice(&[sig.to_vec(), pk.to_bytes()]));
+
+        let tx = psbt.clone().extract_tx_verified().unwrap();
+        assert_eq!(tx, psbt.clone().extract_tx_strict().unwrap());
+
+        // A signature for a different amount does not verify.
+        psbt.inputs[0].witness_utxo.as_mut().unwrap().value = Amount::from_sat(10_001);
+        match psbt.extract_tx_verified() {
+            Err(ExtractTxError::ScriptVerification { input_index: 0, tx: returned, .. }) =>
+                assert_eq!(returned, tx),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 
-        let sk = SecretKey::new(&mut thread_rng());
-        let priv_key = PrivateKey::new(sk, NetworkKind::Test);
-        let pk = PublicKey::from_private_key(&secp, &priv_key);
+    #[test]
+    fn extract_tx_partial() {
+        let mut psbt = Psbt::with_capacity(2, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+        let witness = Witness::from_slice(&[vec![0x01; 72]]);
+        psbt.inputs[1].final_script_witness = Some(witness.clone());
+
+        let (tx, incomplete) = psbt.extract_tx_partial();
+        assert_eq!(incomplete, [0]);
+        assert!(tx.input[0].witness.is_empty());
+        assert_eq!(tx.input[1].witness, witness);
+        assert_eq!(tx.compute_txid(), psbt.unsigned_tx.compute_txid());
+
+        psbt.inputs[0].final_script_witness = Some(witness);
+        let (tx, incomplete) = psbt.extract_tx_partial();
+        assert!(incomplete.is_empty());
+        assert_eq!(tx, psbt.extract_tx_unchecked_fee_rate());
+    }
+
+    #[test]
+    fn try_from_complete() {
+        let mut psbt = Psbt::with_capacity(2, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+        psbt.inputs[1].final_script_sig = Some(ScriptBuf::from_bytes(vec![0x51]));
+
+        assert!(!psbt.is_complete());
+        assert!(!psbt.is_finalized(0) && psbt.is_finalized(1) && !psbt.is_finalized(2));
+        assert_eq!(
+            Transaction::try_from(&psbt),
+            Err(IncompleteError::NotFinalized { missing: vec![0] })
+        );
 
-        (priv_key, pk, secp)
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&[vec![0x01]]));
+        assert!(psbt.is_complete());
+        let tx = Transaction::try_from(&psbt).unwrap();
+        assert_eq!(tx, psbt.extract_tx_unchecked_fee_rate());
     }
 
     #[test]
-    #[cfg(feature = "rand-std")]
-    fn get_key_btree_map() {
-        let (priv_key, pk, secp) = gen_keys();
+    fn with_capacity_push() {
+        let mut psbt = Psbt::with_capacity(2, 1);
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+        psbt.push_output(TxOut::NULL, Output::default());
+        assert_eq!(psbt.inputs.len(), psbt.unsigned_tx.input.len());
+        assert_eq!(psbt.outputs.len(), psbt.unsigned_tx.output.len());
+        assert!(psbt.inputs.capacity() >= 2);
+
+        let txin = TxIn { script_sig: ScriptBuf::from_bytes(vec![0x51]), ..Default::default() };
+        assert!(psbt.push_input(txin, Input::default()).is_err());
+        assert_eq!(psbt.inputs.len(), 1);
+    }
 
-        let mut key_map = BTreeMap::new();
-        key_map.insert(pk, priv_key);
+    #[test]
+    fn insert_and_remove_clear_sigs() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let signature = secp.sign_ecdsa(&Message::from_digest([1; 32]), &sk);
+        let sig = |sighash_type| ecdsa::Signature { signature, sighash_type };
+
+        let mut psbt = Psbt::with_capacity(3, 3);
+        for _ in 0..3 {
+            psbt.push_input(TxIn::default(), Input::default()).unwrap();
+            psbt.push_output(TxOut::NULL, Output::default());
+        }
+        let signed = |psbt: &Psbt| -> Vec<bool> {
+            psbt.inputs.iter().map(|input| !input.partial_sigs.is_empty()).collect()
+        };
+        let sign = |psbt: &mut Psbt| {
+            psbt.inputs[0].partial_sigs.insert(pk, sig(EcdsaSighashType::All));
+            psbt.inputs[1].partial_sigs.insert(pk, sig(EcdsaSighashType::SinglePlusAnyoneCanPay));
+            psbt.inputs[2].partial_sigs.insert(pk, sig(EcdsaSighashType::NonePlusAnyoneCanPay));
+        };
 
-        let got = key_map.get_key(&KeyRequest::Pubkey(pk), &secp).expect("failed to get key");
-        assert_eq!(got.unwrap(), priv_key)
+        sign(&mut psbt);
+        psbt.insert_input(3, TxIn::default(), Input::default()).unwrap();
+        assert_eq!(signed(&psbt), [false, true, true, false]);
+
+        sign(&mut psbt);
+        psbt.insert_output(3, TxOut::NULL, Output::default());
+        assert_eq!(signed(&psbt), [false, true, true, false]);
+        psbt.insert_output(1, TxOut::NULL, Output::default());
+        assert_eq!(signed(&psbt), [false, false, true, false]);
+
+        sign(&mut psbt);
+        psbt.inputs[2].final_script_witness = Some(Witness::new());
+        let (_, removed) = psbt.remove_input(0).unwrap();
+        assert!(!removed.partial_sigs.is_empty());
+        // The SIGHASH_SINGLE input now pairs with output 0.
+        assert_eq!(signed(&psbt), [false, true, false]);
+        assert!(psbt.inputs[1].final_script_witness.is_none());
+
+        for input in &mut psbt.inputs {
+            input.partial_sigs.insert(pk, sig(EcdsaSighashType::SinglePlusAnyoneCanPay));
+        }
+        psbt.remove_output(1).unwrap();
+        assert_eq!(signed(&psbt), [true, false, false]);
+        assert_eq!(psbt.outputs.len(), psbt.unsigned_tx.output.len());
+        assert!(psbt.remove_input(3).is_err());
+        assert!(psbt.remove_output(4).is_err());
     }
 
     #[test]
-    fn test_fee() {
-        let output_0_val = Amount::from_sat(99_999_699);
-        let output_1_val = Amount::from_sat(100_000_000);
-        let prev_output_val = Amount::from_sat(200_000_000);
+    fn check_duplicate_inputs() {
+        let txin = |vout| TxIn {
+            previous_output: OutPoint { txid: bitcoin::Txid::all_zeros(), vout },
+            ..Default::default()
+        };
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![txin(0), txin(1), txin(0), txin(0)],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        assert_eq!(psbt.check_duplicate_inputs(), Err(vec![2, 3]));
 
-        let mut t = Psbt {
-            unsigned_tx: Transaction {
-                version: transaction::Version::TWO,
-                lock_time: absolute::LockTime::from_consensus(1257139),
-                input: vec![
-                    TxIn {
-                        previous_output: OutPoint {
-                            txid: "f61b1742ca13176464adb3cb66050c00787bb3a4eead37e985f2df1e37718126".parse().unwrap(),
-                            vout: 0,
-                        },
-                        sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
-                        ..Default::default()
-                    }
-                ],
-                output: vec![
-                    TxOut {
-                        value: output_0_val,
-                        script_pubkey:  ScriptBuf::new()
-                    },
-                    TxOut {
-                        value: output_1_val,
-                        script_pubkey:  ScriptBuf::new()
-                    },
-                ],
-            },
-            xpub: Default::default(),
-            version: 0,
-            proprietary: BTreeMap::new(),
-            unknown: BTreeMap::new(),
+        psbt.unsigned_tx.input.truncate(2);
+        assert_eq!(psbt.check_duplicate_inputs(), Ok(()));
+    }
 
-            inputs: vec![
-                Input {
-                    non_witness_utxo: Some(Transaction {
-                        version: transaction::Version::ONE,
-                        lock_time: absolute::LockTime::ZERO,
-                        input: vec![
-                            TxIn {
-                                previous_output: OutPoint {
-                                    txid: "e567952fb6cc33857f392efa3a46c995a28f69cca4bb1b37e0204dab1ec7a389".parse().unwrap(),
-                                    vout: 1,
-                                },
-                                sequence: Sequence::MAX,
-                                ..Default::default()
-                            },
-                            TxIn {
-                                previous_output: OutPoint {
-                                    txid: "b490486aec3ae671012dddb2bb08466bef37720a533a894814ff1da743aaf886".parse().unwrap(),
-                                    vout: 1,
-                                },
-                                sequence: Sequence::MAX,
-                                ..Default::default()
-                            }
-                        ],
-                        output: vec![
-                            TxOut {
-                                value: prev_output_val,
-                                script_pubkey:  ScriptBuf::new()
-                            },
-                            TxOut {
-                                value: Amount::from_sat(190_303_501_938),
-                                script_pubkey:  ScriptBuf::new()
-                            },
-                        ],
-                    }),
-                    ..Default::default()
-                },
-            ],
-            outputs: vec![
-                Output {
-                    ..Default::default()
-                },
-                Output {
-                    ..Default::default()
-                },
-            ],
+    #[test]
+    fn field_summary() {
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![TxOut::NULL],
         };
-        assert_eq!(
-            t.fee().expect("fee calculation"),
-            prev_output_val - (output_0_val + output_1_val)
-        );
-        // no previous output
-        let mut t2 = t.clone();
-        t2.inputs[0].non_witness_utxo = None;
-        match t2.fee().unwrap_err() {
-            Error::MissingUtxo => {}
-            e => panic!("unexpected error: {:?}", e),
-        }
-        //  negative fee
-        let mut t3 = t.clone();
-        t3.unsigned_tx.output[0].value = prev_output_val;
-        match t3.fee().unwrap_err() {
-            Error::NegativeFee => {}
-            e => panic!("unexpected error: {:?}", e),
-        }
-        // overflow
-        t.unsigned_tx.output[0].value = Amount::MAX;
-        t.unsigned_tx.output[1].value = Amount::MAX;
-        match t.fee().unwrap_err() {
-            Error::FeeOverflow => {}
-            e => panic!("unexpected error: {:?}", e),
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let sig =
+            ecdsa::Signature::sighash_all(secp.sign_ecdsa(&Message::from_digest([1; 32]), &sk));
+        let origin = (Fingerprint::default(), DerivationPath::master());
+        let xonly = XOnlyPublicKey::from(pk.inner);
+
+        for input in &mut psbt.inputs {
+            input.partial_sigs.insert(pk, sig);
+            input.bip32_derivation.insert(pk.inner, origin.clone());
         }
+        psbt.inputs[1].tap_internal_key = Some(xonly);
+        psbt.outputs[0].tap_key_origins.insert(xonly, (vec![], origin));
+        psbt.outputs[0].unknown.insert(raw::Key { type_value: 0xAA, key_data: vec![] }, vec![]);
+        psbt.unknown.insert(raw::Key { type_value: 0xAA, key_data: vec![] }, vec![]);
+
+        let summary = psbt.field_summary();
+        assert_eq!(summary.xpubs, 0);
+        assert_eq!(summary.partial_sigs, 2);
+        assert_eq!(summary.bip32_derivations, 2);
+        assert_eq!(summary.tap_fields, 2);
+        assert_eq!(summary.proprietary, 0);
+        assert_eq!(summary.unknown, 2);
     }
 
     #[test]
@@ -2324,4 +5409,342 @@ mod tests {
         assert_eq!(signing_keys.len(), 1);
         assert_eq!(signing_keys[&0], SigningKeys::Ecdsa(vec![pk]));
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn sign_parallel() {
+        use bitcoin::witness_version::WitnessVersion;
+        use bitcoin::WitnessProgram;
+
+        let secp = Secp256k1::new();
+        let sk = PrivateKey::new(
+            secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+            bitcoin::NetworkKind::Test,
+        );
+        let pk = sk.public_key(&secp);
+        let key_map = BTreeMap::from([(pk, sk)]);
+        let unknown = WitnessProgram::new(WitnessVersion::V4, &[0xaa; 34]).unwrap();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..7)
+                .map(|vout| TxIn {
+                    previous_output: OutPoint { txid: Txid::all_zeros(), vout },
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            let script_pubkey = if index % 3 == 2 {
+                ScriptBuf::new_witness_program(&unknown)
+            } else {
+                ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap())
+            };
+            input.witness_utxo = Some(TxOut { value: Amount::from_sat(10), script_pubkey });
+            input
+                .bip32_derivation
+                .insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+        }
+
+        let mut serial = psbt.clone();
+        let expected = serial.sign(&key_map, &secp).unwrap_err();
+        for threads in [0, 1, 3, 100] {
+            let mut parallel = psbt.clone();
+            assert_eq!(parallel.sign_parallel(&key_map, &secp, threads).unwrap_err(), expected);
+            assert_eq!(parallel, serial);
+        }
+        assert_eq!(expected.1.keys().copied().collect::<Vec<_>>(), vec![2, 5]);
+    }
+
+    #[test]
+    fn sign_taproot_key_spend_records_key_origin() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let priv_key = PrivateKey::new(sk, NetworkKind::Test);
+        let pk = priv_key.public_key(&secp);
+        let (internal_key, _) = pk.inner.x_only_public_key();
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, internal_key, None),
+        });
+        psbt.inputs[0].tap_internal_key = Some(internal_key);
+
+        // A non-HD key, there is no key origin in the PSBT before signing.
+        let mut key_map = BTreeMap::new();
+        key_map.insert(pk, priv_key);
+
+        let signing_keys = psbt.sign(&key_map, &secp).unwrap();
+        assert_eq!(signing_keys[&0], SigningKeys::Schnorr(vec![internal_key]));
+        assert!(psbt.inputs[0].tap_key_sig.is_some());
+
+        // The internal key's origin is present, as its own master key.
+        let (leaf_hashes, (fingerprint, path)) = &psbt.inputs[0].tap_key_origins[&internal_key];
+        assert!(leaf_hashes.is_empty());
+        let hash = pk.pubkey_hash();
+        assert_eq!(*fingerprint, Fingerprint::from([hash[0], hash[1], hash[2], hash[3]]));
+        assert!(path.is_master());
+
+        // An existing origin is kept.
+        let source = (Fingerprint::from([1; 4]), DerivationPath::from(vec![ChildNumber::from(1)]));
+        psbt.inputs[0].tap_key_sig = None;
+        psbt.inputs[0].tap_key_origins.clear();
+        psbt.inputs[0].add_tap_key_origin(internal_key, None, source.clone());
+        psbt.sign(&key_map, &secp).unwrap();
+        assert!(psbt.inputs[0].tap_key_sig.is_some());
+        assert_eq!(psbt.inputs[0].tap_key_origins[&internal_key], (vec![], source));
+    }
+
+    #[test]
+    fn sign_taproot_script_spend_with_key_map() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let priv_key = PrivateKey::new(sk, NetworkKind::Test);
+        let pk = priv_key.public_key(&secp);
+        let (xonly, _) = pk.inner.x_only_public_key();
+        let internal_sk = secp256k1::SecretKey::from_slice(&[2; 32]).unwrap();
+        let (internal_key, _) = internal_sk.x_only_public_key(&secp);
+
+        let script = ScriptBuf::builder()
+            .push_x_only_key(&xonly)
+            .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        let leaf_hash = TapLeafHash::from_script(&script, taproot::LeafVersion::TapScript);
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, internal_key, Some(leaf_hash.into())),
+        });
+        psbt.inputs[0].tap_internal_key = Some(internal_key);
+        psbt.inputs[0].add_tap_key_origin(
+            xonly,
+            Some(leaf_hash),
+            (Fingerprint::from([0xaa; 4]), "m/86'/1'/0'/0/0".parse().unwrap()),
+        );
+
+        // The key origin is not known to the key map, the key is found by its x-only key.
+        let mut key_map = BTreeMap::new();
+        key_map.insert(pk, priv_key);
+
+        let signing_keys = psbt.sign(&key_map, &secp).unwrap();
+        assert_eq!(signing_keys[&0], SigningKeys::Schnorr(vec![xonly]));
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+        assert!(psbt.inputs[0].tap_script_sigs.contains_key(&(xonly, leaf_hash)));
+    }
+
+    #[test]
+    fn sign_taproot_leaf() {
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let (xonly, _) = keypair.x_only_public_key();
+        let (internal_key, _) =
+            Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap().x_only_public_key();
+        let other = Keypair::from_seckey_slice(&secp, &[3; 32]).unwrap();
+
+        let script = ScriptBuf::builder()
+            .push_x_only_key(&xonly)
+            .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        let script_ver = (script, taproot::LeafVersion::TapScript);
+        let leaf_hash = TapLeafHash::from_script(&script_ver.0, script_ver.1);
+        let info = taproot::TaprootBuilder::new()
+            .add_leaf(0, script_ver.0.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(info.output_key()),
+        });
+        psbt.inputs[0].tap_internal_key = Some(internal_key);
+        assert_eq!(
+            psbt.sign_taproot_leaf(0, leaf_hash, &keypair, &secp),
+            Err(SignError::UnknownLeaf(leaf_hash))
+        );
+        psbt.inputs[0].tap_scripts.insert(info.control_block(&script_ver).unwrap(), script_ver);
+        assert_eq!(
+            psbt.sign_taproot_leaf(0, leaf_hash, &other, &secp),
+            Err(SignError::KeyNotInLeaf)
+        );
+
+        let signature = psbt.sign_taproot_leaf(0, leaf_hash, &keypair, &secp).unwrap();
+        assert_eq!(signature.sighash_type, TapSighashType::Default);
+        assert_eq!(psbt.inputs[0].tap_script_sigs[&(xonly, leaf_hash)], signature);
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+        let (msg, _) = psbt.sighash_taproot(0, &mut cache, Some(leaf_hash)).unwrap();
+        secp.verify_schnorr(&signature.signature, &msg, &xonly).unwrap();
+
+        let single = taproot::Signature { sighash_type: TapSighashType::Single, ..signature };
+        assert_eq!(
+            psbt.insert_tap_script_sig(0, xonly, leaf_hash, single),
+            Err(SignError::SighashTypeMismatch {
+                input: TapSighashType::Default.into(),
+                requested: TapSighashType::Single.into(),
+            })
+        );
+        assert_eq!(psbt.insert_tap_script_sig(0, xonly, leaf_hash, signature), Ok(Some(signature)));
+    }
+}
+
+#[cfg(bench)]
+mod benches {
+    use bitcoin::bip32::Fingerprint;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{OutPoint, ScriptBuf, Txid};
+    use test::{black_box, Bencher};
+
+    use super::*;
+
+    const NUM_INPUTS: u32 = 500;
+
+    fn txin(vout: u32) -> TxIn {
+        TxIn { previous_output: OutPoint { txid: Txid::all_zeros(), vout }, ..Default::default() }
+    }
+
+    #[bench]
+    pub fn assemble_500_inputs(bh: &mut Bencher) {
+        bh.iter(|| {
+            let mut psbt = Psbt::from_unsigned_tx(Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            })
+            .unwrap();
+            for vout in 0..NUM_INPUTS {
+                psbt.push_input(txin(vout), Input::default()).unwrap();
+            }
+            psbt.push_output(TxOut::NULL, Output::default());
+            black_box(psbt);
+        });
+    }
+
+    /// A PSBT with ten inputs spending from the same large transaction, each input has a copy of
+    /// that transaction as its `non_witness_utxo`.
+    fn psbt_with_shared_non_witness_utxo() -> Psbt {
+        let prev_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..1_000).map(txin).collect(),
+            output: vec![TxOut::NULL; 10],
+        };
+        let txid = prev_tx.compute_txid();
+
+        let mut psbt = Psbt::with_capacity(10, 1);
+        for vout in 0..10 {
+            let txin = TxIn { previous_output: OutPoint { txid, vout }, ..Default::default() };
+            let input = Input { non_witness_utxo: Some(prev_tx.clone()), ..Default::default() };
+            psbt.push_input(txin, input).unwrap();
+        }
+        psbt.push_output(TxOut::NULL, Output::default());
+        psbt
+    }
+
+    #[bench]
+    pub fn clone_shared_non_witness_utxo(bh: &mut Bencher) {
+        let psbt = psbt_with_shared_non_witness_utxo();
+        bh.iter(|| black_box(psbt.clone()));
+    }
+
+    #[bench]
+    pub fn deserialize_shared_non_witness_utxo(bh: &mut Bencher) {
+        let bytes = psbt_with_shared_non_witness_utxo().serialize();
+        bh.iter(|| black_box(Psbt::deserialize(&bytes).unwrap()));
+    }
+
+    #[bench]
+    pub fn clone_compact_shared_non_witness_utxo(bh: &mut Bencher) {
+        let compact = psbt_with_shared_non_witness_utxo().compact();
+        bh.iter(|| black_box(compact.clone()));
+    }
+
+    #[bench]
+    pub fn deserialize_compact_shared_non_witness_utxo(bh: &mut Bencher) {
+        let bytes = psbt_with_shared_non_witness_utxo().serialize();
+        bh.iter(|| black_box(CompactPsbt::deserialize(&bytes).unwrap()));
+    }
+
+    /// A PSBT with 500 P2WPKH inputs spending outputs of the same key, and that key.
+    fn psbt_with_500_p2wpkh_inputs() -> (Psbt, BTreeMap<PublicKey, PrivateKey>) {
+        let secp = Secp256k1::new();
+        let sk = PrivateKey::new(
+            secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+            bitcoin::NetworkKind::Test,
+        );
+        let pk = sk.public_key(&secp);
+        let utxo = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+        };
+        let mut psbt = Psbt::with_capacity(NUM_INPUTS as usize, 1);
+        for vout in 0..NUM_INPUTS {
+            let mut input = Input { witness_utxo: Some(utxo.clone()), ..Default::default() };
+            input
+                .bip32_derivation
+                .insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+            psbt.push_input(txin(vout), input).unwrap();
+        }
+        psbt.push_output(TxOut::NULL, Output::default());
+        (psbt, BTreeMap::from([(pk, sk)]))
+    }
+
+    #[bench]
+    pub fn sign_500_inputs(bh: &mut Bencher) {
+        let (psbt, keys) = psbt_with_500_p2wpkh_inputs();
+        let secp = Secp256k1::new();
+        bh.iter(|| {
+            let mut psbt = psbt.clone();
+            psbt.sign(&keys, &secp).unwrap();
+            black_box(psbt);
+        });
+    }
+
+    #[bench]
+    pub fn sign_parallel_500_inputs(bh: &mut Bencher) {
+        let (psbt, keys) = psbt_with_500_p2wpkh_inputs();
+        let secp = Secp256k1::new();
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        bh.iter(|| {
+            let mut psbt = psbt.clone();
+            psbt.sign_parallel(&keys, &secp, threads).unwrap();
+            black_box(psbt);
+        });
+    }
+
+    #[bench]
+    pub fn assemble_500_inputs_with_capacity(bh: &mut Bencher) {
+        bh.iter(|| {
+            let mut psbt = Psbt::with_capacity(NUM_INPUTS as usize, 1);
+            for vout in 0..NUM_INPUTS {
+                psbt.push_input(txin(vout), Input::default()).unwrap();
+            }
+            psbt.push_output(TxOut::NULL, Output::default());
+            black_box(psbt);
+        });
+    }
 }
@@ -9,6 +9,67 @@ macro_rules! combine {
     };
 }
 
+/// Implements `Display`, `Debug`, PSBT serialization, and serde for a newtype around a byte
+/// array of length `$len`, deserializing a value of the wrong length is an
+/// `Error::InvalidLength`.
+macro_rules! impl_psbt_byte_array_value {
+    ($ty:ident, $len:literal) => {
+        impl core::fmt::Display for $ty {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                use bitcoin::hex::DisplayHex;
+
+                core::fmt::Display::fmt(&self.0.as_hex(), f)
+            }
+        }
+
+        impl core::fmt::Debug for $ty {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                core::fmt::Display::fmt(self, f)
+            }
+        }
+
+        impl $crate::serialize::Serialize for $ty {
+            fn serialize(&self) -> $crate::prelude::Vec<u8> { self.0.to_vec() }
+        }
+
+        impl $crate::serialize::Deserialize for $ty {
+            fn deserialize(bytes: &[u8]) -> core::result::Result<Self, $crate::Error> {
+                let bytes = <[u8; $len]>::try_from(bytes).map_err(|_| {
+                    $crate::Error::InvalidLength { expected: $len, found: bytes.len() }
+                })?;
+                Ok($ty(bytes))
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl $crate::serde::Serialize for $ty {
+            fn serialize<S: $crate::serde::Serializer>(
+                &self,
+                s: S,
+            ) -> core::result::Result<S::Ok, S::Error> {
+                $crate::serde_utils::hex_bytes::serialize(&&self.0[..], s)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> $crate::serde::Deserialize<'de> for $ty {
+            fn deserialize<D: $crate::serde::Deserializer<'de>>(
+                d: D,
+            ) -> core::result::Result<Self, D::Error> {
+                use $crate::serde::de::Error;
+
+                let bytes: $crate::prelude::Vec<u8> =
+                    $crate::serde_utils::hex_bytes::deserialize(d)?;
+                let len = bytes.len();
+                let bytes = <[u8; $len]>::try_from(bytes).map_err(|_| {
+                    D::Error::invalid_length(len, &concat!(stringify!($len), " bytes"))
+                })?;
+                Ok($ty(bytes))
+            }
+        }
+    };
+}
+
 macro_rules! impl_psbt_de_serialize {
     ($thing:ty) => {
         impl_psbt_serialize!($thing);
@@ -42,28 +103,39 @@ macro_rules! impl_psbtmap_serialize {
     };
 }
 
+/// A map deserialized on its own is reported at `$location`, the map at index 0.
 macro_rules! impl_psbtmap_deserialize {
-    ($thing:ty) => {
+    ($thing:ty, $location:expr) => {
         impl $crate::serialize::Deserialize for $thing {
             fn deserialize(bytes: &[u8]) -> core::result::Result<Self, $crate::Error> {
                 let mut decoder = bytes;
-                Self::decode(&mut decoder)
+                Self::decode(
+                    &mut decoder,
+                    $location,
+                    &$crate::serialize::DeserializeOptions::UNLIMITED,
+                )
             }
         }
     };
 }
 
+/// Invalid pairs are reported as an `Error::Pair` at `location`.
 macro_rules! impl_psbtmap_decoding {
     ($thing:ty) => {
         impl $thing {
             pub(crate) fn decode<R: bitcoin::io::BufRead + ?Sized>(
                 r: &mut R,
+                location: $crate::MapLocation,
+                options: &$crate::serialize::DeserializeOptions,
             ) -> core::result::Result<Self, $crate::Error> {
                 let mut rv: Self = core::default::Default::default();
 
                 loop {
-                    match $crate::raw::Pair::decode(r) {
-                        Ok(pair) => rv.insert_pair(pair)?,
+                    match $crate::raw::Pair::decode(r, options) {
+                        Ok(pair) => {
+                            let wrap = $crate::PairError::wrap(location, pair.key.clone());
+                            rv.insert_pair(pair).map_err(wrap)?
+                        }
                         Err($crate::Error::NoMorePairs) => return Ok(rv),
                         Err(e) => return Err(e),
                     }
@@ -74,10 +146,10 @@ macro_rules! impl_psbtmap_decoding {
 }
 
 macro_rules! impl_psbtmap_ser_de_serialize {
-    ($thing:ty) => {
+    ($thing:ty, $location:expr) => {
         impl_psbtmap_decoding!($thing);
         impl_psbtmap_serialize!($thing);
-        impl_psbtmap_deserialize!($thing);
+        impl_psbtmap_deserialize!($thing, $location);
     };
 }
 
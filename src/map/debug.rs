@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Helpers for the human readable `Debug` output of the PSBT maps.
+
+use core::fmt;
+
+use bitcoin::bip32::KeySource;
+use bitcoin::hex::DisplayHex;
+use bitcoin::taproot::{self, ControlBlock};
+
+use crate::prelude::*;
+use crate::raw;
+
+/// Formats bytes as a hex string.
+pub(super) struct Hex<'a>(pub(super) &'a [u8]);
+
+impl fmt::Debug for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.0.as_hex(), f) }
+}
+
+/// Formats a value using its `Display` implementation.
+pub(super) struct Plain<T>(pub(super) T);
+
+impl<T: fmt::Display> fmt::Debug for Plain<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.0, f) }
+}
+
+/// Formats the key-value pairs yielded by an iterator as a map.
+pub(super) struct Entries<I>(pub(super) I);
+
+impl<I, K, V> fmt::Debug for Entries<I>
+where
+    I: Iterator<Item = (K, V)> + Clone,
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.0.clone()).finish()
+    }
+}
+
+/// Formats a Taproot signature as hex, including the sighash type byte if present.
+pub(super) struct TapSig<'a>(pub(super) &'a taproot::Signature);
+
+impl fmt::Debug for TapSig<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { Hex(&self.0.serialize()).fmt(f) }
+}
+
+/// Formats a control block as hex.
+pub(super) struct Control<'a>(pub(super) &'a ControlBlock);
+
+impl fmt::Debug for Control<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { Hex(&self.0.serialize()).fmt(f) }
+}
+
+/// Formats a MuSig2 participant, aggregate key, and leaf hash as a tuple.
+pub(super) struct Musig2<'a>(pub(super) &'a super::Musig2Key);
+
+impl fmt::Debug for Musig2<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (participant, aggregate, leaf_hash) = self.0;
+        let mut tuple = f.debug_tuple("");
+        tuple.field(&Plain(participant)).field(&Plain(aggregate));
+        if let Some(leaf_hash) = leaf_hash {
+            tuple.field(&Plain(leaf_hash));
+        }
+        tuple.finish()
+    }
+}
+
+/// Formats a key source as `[fingerprint/path]`, the notation used by output descriptors.
+pub(super) struct Source<'a>(pub(super) &'a KeySource);
+
+impl fmt::Debug for Source<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (fingerprint, path) = self.0;
+        write!(f, "[{}", fingerprint)?;
+        for child in path {
+            write!(f, "/{}", child)?;
+        }
+        f.write_str("]")
+    }
+}
+
+/// Formats the proprietary key-value pairs of a map.
+pub(super) struct Proprietary<'a>(pub(super) &'a BTreeMap<raw::ProprietaryKey, Vec<u8>>);
+
+impl fmt::Debug for Proprietary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(key, value)| (ProprietaryKey(key), Hex(value))))
+            .finish()
+    }
+}
+
+/// Formats a proprietary key as `prefix:subtype:key`.
+struct ProprietaryKey<'a>(&'a raw::ProprietaryKey);
+
+impl fmt::Debug for ProprietaryKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.0.prefix.as_hex(), self.0.subtype, self.0.key.as_hex())
+    }
+}
+
+/// Formats the unknown key-value pairs of a map.
+pub(super) struct Unknown<'a>(pub(super) &'a BTreeMap<raw::Key, Vec<u8>>);
+
+impl fmt::Debug for Unknown<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.0.iter().map(|(key, value)| (Key(key), Hex(value)))).finish()
+    }
+}
+
+/// Formats a raw key as `type:key_data`.
+struct Key<'a>(&'a raw::Key);
+
+impl fmt::Debug for Key<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#04x}:{}", self.0.type_value, self.0.key_data.as_hex())
+    }
+}
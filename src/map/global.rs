@@ -8,7 +8,8 @@ use bitcoin::Transaction;
 
 use super::Map;
 use crate::prelude::*;
-use crate::{raw, Error, Psbt};
+use crate::serialize::DeserializeOptions;
+use crate::{raw, Error, MapLocation, PairError, Psbt};
 
 /// Type: Unsigned Transaction PSBT_GLOBAL_UNSIGNED_TX = 0x00
 const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
@@ -19,6 +20,33 @@ const PSBT_GLOBAL_VERSION: u8 = 0xFB;
 /// Type: Proprietary Use Type PSBT_GLOBAL_PROPRIETARY = 0xFC
 const PSBT_GLOBAL_PROPRIETARY: u8 = 0xFC;
 
+/// Returns the name of the field with key type `type_value`, `"unknown"` for unknown key types.
+pub(super) fn field_name(type_value: u8) -> &'static str {
+    match type_value {
+        PSBT_GLOBAL_UNSIGNED_TX => "unsigned_tx",
+        PSBT_GLOBAL_XPUB => "xpub",
+        PSBT_GLOBAL_VERSION => "version",
+        PSBT_GLOBAL_PROPRIETARY => "proprietary",
+        _ => "unknown",
+    }
+}
+
+/// Returns the global keys that conflict between `ours` and `theirs`, see
+/// [`super::conflicting_pairs`].
+///
+/// The unsigned transaction, xpubs, and version are merged by [`Psbt::combine`] so they are not
+/// conflicts.
+pub(crate) fn conflicting_global_pairs(
+    ours: &Psbt,
+    theirs: &Psbt,
+) -> Vec<(raw::Key, Vec<u8>, Vec<u8>)> {
+    let mut pairs = super::conflicting_pairs(ours, theirs);
+    pairs.retain(|(key, _, _)| {
+        !matches!(key.type_value, PSBT_GLOBAL_UNSIGNED_TX | PSBT_GLOBAL_XPUB | PSBT_GLOBAL_VERSION)
+    });
+    pairs
+}
+
 impl Map for Psbt {
     fn get_pairs(&self) -> Vec<raw::Pair> {
         let mut rv: Vec<raw::Pair> = Default::default();
@@ -70,7 +98,10 @@ impl Map for Psbt {
 }
 
 impl Psbt {
-    pub(crate) fn decode_global<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Error> {
+    pub(crate) fn decode_global<R: BufRead + ?Sized>(
+        r: &mut R,
+        options: &DeserializeOptions,
+    ) -> Result<Self, Error> {
         let mut r = r.take(MAX_VEC_SIZE as u64);
         let mut tx: Option<Transaction> = None;
         let mut version: Option<u32> = None;
@@ -78,119 +109,121 @@ impl Psbt {
         let mut xpub_map: BTreeMap<Xpub, (Fingerprint, DerivationPath)> = Default::default();
         let mut proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>> = Default::default();
 
-        loop {
-            match raw::Pair::decode(&mut r) {
-                Ok(pair) => {
-                    match pair.key.type_value {
-                        PSBT_GLOBAL_UNSIGNED_TX => {
-                            // key has to be empty
-                            if pair.key.key_data.is_empty() {
-                                // there can only be one unsigned transaction
-                                if tx.is_none() {
-                                    let vlen: usize = pair.value.len();
-                                    let mut decoder = Cursor::new(pair.value);
-
-                                    // Manually deserialized to ensure 0-input
-                                    // txs without witnesses are deserialized
-                                    // properly.
-                                    tx = Some(Transaction {
-                                        version: Decodable::consensus_decode(&mut decoder)?,
-                                        input: Decodable::consensus_decode(&mut decoder)?,
-                                        output: Decodable::consensus_decode(&mut decoder)?,
-                                        lock_time: Decodable::consensus_decode(&mut decoder)?,
-                                    });
-
-                                    if decoder.position() != vlen as u64 {
-                                        return Err(Error::PartialDataConsumption);
-                                    }
-                                } else {
-                                    return Err(Error::DuplicateKey(pair.key));
-                                }
-                            } else {
-                                return Err(Error::InvalidKey(pair.key));
+        let mut insert_pair = |pair: raw::Pair| -> Result<(), Error> {
+            match pair.key.type_value {
+                PSBT_GLOBAL_UNSIGNED_TX => {
+                    // key has to be empty
+                    if pair.key.key_data.is_empty() {
+                        // there can only be one unsigned transaction
+                        if tx.is_none() {
+                            let vlen: usize = pair.value.len();
+                            let mut decoder = Cursor::new(pair.value);
+
+                            // Manually deserialized to ensure 0-input
+                            // txs without witnesses are deserialized
+                            // properly.
+                            tx = Some(Transaction {
+                                version: Decodable::consensus_decode(&mut decoder)?,
+                                input: Decodable::consensus_decode(&mut decoder)?,
+                                output: Decodable::consensus_decode(&mut decoder)?,
+                                lock_time: Decodable::consensus_decode(&mut decoder)?,
+                            });
+
+                            if decoder.position() != vlen as u64 {
+                                return Err(Error::PartialDataConsumption);
                             }
+                        } else {
+                            return Err(Error::DuplicateKey(pair.key));
                         }
-                        PSBT_GLOBAL_XPUB => {
-                            if !pair.key.key_data.is_empty() {
-                                let xpub = Xpub::decode(&pair.key.key_data)
-                                    .map_err(|_| Error::XPubKey(
-                                        "can't deserialize ExtendedPublicKey from global XPUB key data"
-                                    ))?;
-
-                                if pair.value.is_empty() || pair.value.len() % 4 != 0 {
-                                    return Err(Error::XPubKey(
-                                        "incorrect length of global xpub derivation data",
-                                    ));
-                                }
-
-                                let child_count = pair.value.len() / 4 - 1;
-                                let mut decoder = Cursor::new(pair.value);
-                                let mut fingerprint = [0u8; 4];
-                                decoder.read_exact(&mut fingerprint[..]).map_err(|_| {
-                                    Error::XPubKey("can't read global xpub fingerprint")
-                                })?;
-                                let mut path = Vec::<ChildNumber>::with_capacity(child_count);
-                                while let Ok(index) = u32::consensus_decode(&mut decoder) {
-                                    path.push(ChildNumber::from(index))
-                                }
-                                let derivation = DerivationPath::from(path);
-                                // Keys, according to BIP-174, must be unique
-                                if xpub_map
-                                    .insert(xpub, (Fingerprint::from(fingerprint), derivation))
-                                    .is_some()
-                                {
-                                    return Err(Error::XPubKey("repeated global xpub key"));
-                                }
-                            } else {
-                                return Err(Error::XPubKey(
-                                    "xpub global key must contain serialized Xpub data",
-                                ));
-                            }
+                    } else {
+                        return Err(Error::InvalidKey(pair.key));
+                    }
+                }
+                PSBT_GLOBAL_XPUB => {
+                    if !pair.key.key_data.is_empty() {
+                        let xpub = Xpub::decode(&pair.key.key_data).map_err(|_| {
+                            Error::XPubKey(
+                                "can't deserialize ExtendedPublicKey from global XPUB key data",
+                            )
+                        })?;
+
+                        if pair.value.is_empty() || pair.value.len() % 4 != 0 {
+                            return Err(Error::XPubKey(
+                                "incorrect length of global xpub derivation data",
+                            ));
                         }
-                        PSBT_GLOBAL_VERSION => {
-                            // key has to be empty
-                            if pair.key.key_data.is_empty() {
-                                // there can only be one version
-                                if version.is_none() {
-                                    let vlen: usize = pair.value.len();
-                                    let mut decoder = Cursor::new(pair.value);
-                                    if vlen != 4 {
-                                        return Err(Error::Version(
-                                            "invalid global version value length (must be 4 bytes)",
-                                        ));
-                                    }
-                                    version = Some(Decodable::consensus_decode(&mut decoder)?);
-                                    // We only understand version 0 PSBTs. According to BIP-174 we
-                                    // should throw an error if we see anything other than version 0.
-                                    if version != Some(0) {
-                                        return Err(Error::Version(
-                                            "PSBT versions greater than 0 are not supported",
-                                        ));
-                                    }
-                                } else {
-                                    return Err(Error::DuplicateKey(pair.key));
-                                }
-                            } else {
-                                return Err(Error::InvalidKey(pair.key));
-                            }
+
+                        let child_count = pair.value.len() / 4 - 1;
+                        let mut decoder = Cursor::new(pair.value);
+                        let mut fingerprint = [0u8; 4];
+                        decoder
+                            .read_exact(&mut fingerprint[..])
+                            .map_err(|_| Error::XPubKey("can't read global xpub fingerprint"))?;
+                        let mut path = Vec::<ChildNumber>::with_capacity(child_count);
+                        while let Ok(index) = u32::consensus_decode(&mut decoder) {
+                            path.push(ChildNumber::from(index))
                         }
-                        PSBT_GLOBAL_PROPRIETARY => match proprietary
-                            .entry(raw::ProprietaryKey::try_from(pair.key.clone())?)
+                        let derivation = DerivationPath::from(path);
+                        // Keys, according to BIP-174, must be unique
+                        if xpub_map
+                            .insert(xpub, (Fingerprint::from(fingerprint), derivation))
+                            .is_some()
                         {
-                            btree_map::Entry::Vacant(empty_key) => {
-                                empty_key.insert(pair.value);
+                            return Err(Error::XPubKey("repeated global xpub key"));
+                        }
+                    } else {
+                        return Err(Error::XPubKey(
+                            "xpub global key must contain serialized Xpub data",
+                        ));
+                    }
+                }
+                PSBT_GLOBAL_VERSION => {
+                    // key has to be empty
+                    if pair.key.key_data.is_empty() {
+                        // there can only be one version
+                        if version.is_none() {
+                            let vlen: usize = pair.value.len();
+                            let mut decoder = Cursor::new(pair.value);
+                            if vlen != 4 {
+                                return Err(Error::InvalidLength { expected: 4, found: vlen });
                             }
-                            btree_map::Entry::Occupied(_) =>
-                                return Err(Error::DuplicateKey(pair.key)),
-                        },
-                        _ => match unknowns.entry(pair.key) {
-                            btree_map::Entry::Vacant(empty_key) => {
-                                empty_key.insert(pair.value);
+                            version = Some(Decodable::consensus_decode(&mut decoder)?);
+                            // We only understand version 0 PSBTs. According to BIP-174 we
+                            // should throw an error if we see anything other than version 0.
+                            if version != Some(0) {
+                                return Err(Error::Version(
+                                    "PSBT versions greater than 0 are not supported",
+                                ));
                             }
-                            btree_map::Entry::Occupied(k) =>
-                                return Err(Error::DuplicateKey(k.key().clone())),
-                        },
+                        } else {
+                            return Err(Error::DuplicateKey(pair.key));
+                        }
+                    } else {
+                        return Err(Error::InvalidKey(pair.key));
+                    }
+                }
+                PSBT_GLOBAL_PROPRIETARY =>
+                    match proprietary.entry(raw::ProprietaryKey::try_from(pair.key.clone())?) {
+                        btree_map::Entry::Vacant(empty_key) => {
+                            empty_key.insert(pair.value);
+                        }
+                        btree_map::Entry::Occupied(_) => return Err(Error::DuplicateKey(pair.key)),
+                    },
+                _ => match unknowns.entry(pair.key) {
+                    btree_map::Entry::Vacant(empty_key) => {
+                        empty_key.insert(pair.value);
                     }
+                    btree_map::Entry::Occupied(k) =>
+                        return Err(Error::DuplicateKey(k.key().clone())),
+                },
+            }
+            Ok(())
+        };
+        loop {
+            match raw::Pair::decode(&mut r, options) {
+                Ok(pair) => {
+                    let wrap = PairError::wrap(MapLocation::Global, pair.key.clone());
+                    insert_pair(pair).map_err(wrap)?
                 }
                 Err(Error::NoMorePairs) => break,
                 Err(e) => return Err(e),
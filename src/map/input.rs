@@ -4,14 +4,23 @@ use core::fmt;
 use core::str::FromStr;
 
 use bitcoin::bip32::KeySource;
+use bitcoin::consensus::encode::VarInt;
 use bitcoin::hashes::{hash160, ripemd160, sha256, sha256d};
+use bitcoin::script::Instruction;
 use bitcoin::secp256k1::{self, XOnlyPublicKey};
 use bitcoin::sighash::{
     EcdsaSighashType, InvalidSighashTypeError, NonStandardSighashTypeError, TapSighashType,
 };
-use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapNodeHash};
-use bitcoin::{ecdsa, taproot, PublicKey, ScriptBuf, Transaction, TxOut, Witness};
+use bitcoin::taproot::{
+    ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TapTree, TAPROOT_ANNEX_PREFIX,
+};
+use bitcoin::{
+    ecdsa, taproot, OutPoint, PublicKey, Script, ScriptBuf, Transaction, TxOut, Weight, Witness,
+};
 
+use super::debug::{Control, Entries, Hex, Musig2, Plain, Proprietary, Source, TapSig, Unknown};
+use super::musig::{Musig2PartialSig, Musig2PubNonce};
+use super::silent_payments::DleqProof;
 use super::Map;
 use crate::prelude::*;
 use crate::serialize::Deserialize;
@@ -55,12 +64,91 @@ const PSBT_IN_TAP_BIP32_DERIVATION: u8 = 0x16;
 const PSBT_IN_TAP_INTERNAL_KEY: u8 = 0x17;
 /// Type: Taproot Merkle Root PSBT_IN_TAP_MERKLE_ROOT = 0x18
 const PSBT_IN_TAP_MERKLE_ROOT: u8 = 0x18;
+/// Type: MuSig2 Participant Public Keys PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS = 0x1a
+const PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS: u8 = 0x1a;
+/// Type: MuSig2 Public Nonce PSBT_IN_MUSIG2_PUB_NONCE = 0x1b
+const PSBT_IN_MUSIG2_PUB_NONCE: u8 = 0x1b;
+/// Type: MuSig2 Participant Partial Signature PSBT_IN_MUSIG2_PARTIAL_SIG = 0x1c
+const PSBT_IN_MUSIG2_PARTIAL_SIG: u8 = 0x1c;
+/// Type: Silent Payment ECDH Share PSBT_IN_SP_ECDH_SHARE = 0x1d
+const PSBT_IN_SP_ECDH_SHARE: u8 = 0x1d;
+/// Type: Silent Payment DLEQ Proof PSBT_IN_SP_DLEQ = 0x1e
+const PSBT_IN_SP_DLEQ: u8 = 0x1e;
 /// Type: Proprietary Use Type PSBT_IN_PROPRIETARY = 0xFC
 const PSBT_IN_PROPRIETARY: u8 = 0xFC;
 
+/// The identifier of the proprietary key the Taproot annex is stored under, with subtype
+/// [`TAP_ANNEX_SUBTYPE`] and no key data.
+const TAP_ANNEX_PREFIX: &[u8] = b"annex";
+/// The subtype of the proprietary key the Taproot annex is stored under.
+const TAP_ANNEX_SUBTYPE: u8 = 0x00;
+
+/// Returns the proprietary key the Taproot annex is stored under.
+fn tap_annex_key() -> raw::ProprietaryKey {
+    raw::ProprietaryKey {
+        prefix: TAP_ANNEX_PREFIX.to_vec(),
+        subtype: TAP_ANNEX_SUBTYPE,
+        key: vec![],
+    }
+}
+
+/// Returns the name of the field with key type `type_value`, `"unknown"` for unknown key types.
+pub(super) fn field_name(type_value: u8) -> &'static str {
+    match type_value {
+        PSBT_IN_NON_WITNESS_UTXO => "non_witness_utxo",
+        PSBT_IN_WITNESS_UTXO => "witness_utxo",
+        PSBT_IN_PARTIAL_SIG => "partial_sigs",
+        PSBT_IN_SIGHASH_TYPE => "sighash_type",
+        PSBT_IN_REDEEM_SCRIPT => "redeem_script",
+        PSBT_IN_WITNESS_SCRIPT => "witness_script",
+        PSBT_IN_BIP32_DERIVATION => "bip32_derivation",
+        PSBT_IN_FINAL_SCRIPTSIG => "final_script_sig",
+        PSBT_IN_FINAL_SCRIPTWITNESS => "final_script_witness",
+        PSBT_IN_RIPEMD160 => "ripemd160_preimages",
+        PSBT_IN_SHA256 => "sha256_preimages",
+        PSBT_IN_HASH160 => "hash160_preimages",
+        PSBT_IN_HASH256 => "hash256_preimages",
+        PSBT_IN_TAP_KEY_SIG => "tap_key_sig",
+        PSBT_IN_TAP_SCRIPT_SIG => "tap_script_sigs",
+        PSBT_IN_TAP_LEAF_SCRIPT => "tap_scripts",
+        PSBT_IN_TAP_BIP32_DERIVATION => "tap_key_origins",
+        PSBT_IN_TAP_INTERNAL_KEY => "tap_internal_key",
+        PSBT_IN_TAP_MERKLE_ROOT => "tap_merkle_root",
+        PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS => "musig2_participant_pubkeys",
+        PSBT_IN_MUSIG2_PUB_NONCE => "musig2_pub_nonces",
+        PSBT_IN_MUSIG2_PARTIAL_SIG => "musig2_partial_sigs",
+        PSBT_IN_SP_ECDH_SHARE => "sp_ecdh_shares",
+        PSBT_IN_SP_DLEQ => "sp_dleq_proofs",
+        PSBT_IN_PROPRIETARY => "proprietary",
+        _ => "unknown",
+    }
+}
+
+/// Returns the key type of the first field of `input` that is not allowed in an input spending
+/// `spk`, i.e., a Taproot field in an input spending a non-Taproot output or an ECDSA signature,
+/// redeem script, or witness script in an input spending a Taproot output.
+pub(super) fn disallowed_field(input: &Input, spk: &Script) -> Option<u8> {
+    const TAPROOT_ONLY: &[u8] = &[
+        PSBT_IN_TAP_KEY_SIG,
+        PSBT_IN_TAP_SCRIPT_SIG,
+        PSBT_IN_TAP_LEAF_SCRIPT,
+        PSBT_IN_TAP_BIP32_DERIVATION,
+        PSBT_IN_TAP_INTERNAL_KEY,
+        PSBT_IN_TAP_MERKLE_ROOT,
+        PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS,
+        PSBT_IN_MUSIG2_PUB_NONCE,
+        PSBT_IN_MUSIG2_PARTIAL_SIG,
+    ];
+    const NOT_TAPROOT: &[u8] =
+        &[PSBT_IN_PARTIAL_SIG, PSBT_IN_REDEEM_SCRIPT, PSBT_IN_WITNESS_SCRIPT];
+
+    let disallowed = if spk.is_p2tr() { NOT_TAPROOT } else { TAPROOT_ONLY };
+    input.get_pairs().into_iter().map(|pair| pair.key.type_value).find(|t| disallowed.contains(t))
+}
+
 /// A key-value map for an input of the corresponding index in the unsigned
 /// transaction.
-#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
 pub struct Input {
@@ -119,6 +207,35 @@ pub struct Input {
     pub tap_internal_key: Option<XOnlyPublicKey>,
     /// Taproot Merkle root.
     pub tap_merkle_root: Option<TapNodeHash>,
+    /// Taproot annex, the last witness element of the spend, starting with `0x50`.
+    ///
+    /// Signatures commit to it and finalizing a Taproot spend appends it to the witness, it is
+    /// ignored when finalizing any other spend. Decoding rejects the annex if the input already
+    /// has a non-Taproot witness UTXO, a redeem script or a witness script.
+    ///
+    /// No key type is assigned to the annex, it is serialized as the proprietary key with
+    /// identifier `"annex"`, subtype `0x00` and no key data. No other implementation uses this
+    /// unprefixed identifier, so the annex does not interoperate with other PSBT implementations,
+    /// they keep it as an opaque proprietary key.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tap_annex: Option<Vec<u8>>,
+    /// Map of MuSig2 aggregate public keys to the public keys of the participants.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
+    pub musig2_participant_pubkeys: BTreeMap<secp256k1::PublicKey, Vec<secp256k1::PublicKey>>,
+    /// Map of `<participant pubkey>|<aggregate pubkey>|<leafhash>` with the participant's MuSig2
+    /// public nonce. The leaf hash is omitted for a key path spend.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
+    pub musig2_pub_nonces: BTreeMap<Musig2Key, Musig2PubNonce>,
+    /// Map of `<participant pubkey>|<aggregate pubkey>|<leafhash>` with the participant's MuSig2
+    /// partial signature. The leaf hash is omitted for a key path spend.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
+    pub musig2_partial_sigs: BTreeMap<Musig2Key, Musig2PartialSig>,
+    /// Map of silent payment scan keys to the ECDH share of this input's private key.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
+    pub sp_ecdh_shares: BTreeMap<secp256k1::PublicKey, secp256k1::PublicKey>,
+    /// Map of silent payment scan keys to the DLEQ proof of the ECDH share for that scan key.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
+    pub sp_dleq_proofs: BTreeMap<secp256k1::PublicKey, DleqProof>,
     /// Proprietary key-value pairs for this input.
     #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq_byte_values"))]
     pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
@@ -127,6 +244,99 @@ pub struct Input {
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
 }
 
+/// Keys, signatures and raw values are formatted as hex and key sources as `[fingerprint/path]`.
+impl fmt::Debug for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Input")
+            .field("non_witness_utxo", &self.non_witness_utxo)
+            .field("witness_utxo", &self.witness_utxo)
+            .field(
+                "partial_sigs",
+                &Entries(self.partial_sigs.iter().map(|(pk, sig)| (Plain(pk), Plain(sig)))),
+            )
+            .field("sighash_type", &self.sighash_type.map(Plain))
+            .field("redeem_script", &self.redeem_script)
+            .field("witness_script", &self.witness_script)
+            .field(
+                "bip32_derivation",
+                &Entries(
+                    self.bip32_derivation.iter().map(|(pk, source)| (Plain(pk), Source(source))),
+                ),
+            )
+            .field("final_script_sig", &self.final_script_sig)
+            .field("final_script_witness", &self.final_script_witness)
+            .field("ripemd160_preimages", &Entries(preimages(&self.ripemd160_preimages)))
+            .field("sha256_preimages", &Entries(preimages(&self.sha256_preimages)))
+            .field("hash160_preimages", &Entries(preimages(&self.hash160_preimages)))
+            .field("hash256_preimages", &Entries(preimages(&self.hash256_preimages)))
+            .field("tap_key_sig", &self.tap_key_sig.as_ref().map(TapSig))
+            .field(
+                "tap_script_sigs",
+                &Entries(
+                    self.tap_script_sigs
+                        .iter()
+                        .map(|((pk, leaf_hash), sig)| ((Plain(pk), Plain(leaf_hash)), TapSig(sig))),
+                ),
+            )
+            .field(
+                "tap_scripts",
+                &Entries(self.tap_scripts.iter().map(|(control_block, (script, ver))| {
+                    (Control(control_block), (script, Plain(ver)))
+                })),
+            )
+            .field(
+                "tap_key_origins",
+                &Entries(self.tap_key_origins.iter().map(|(pk, (leaf_hashes, source))| {
+                    (Plain(pk), (leaf_hashes.iter().map(Plain).collect::<Vec<_>>(), Source(source)))
+                })),
+            )
+            .field("tap_internal_key", &self.tap_internal_key.map(Plain))
+            .field("tap_merkle_root", &self.tap_merkle_root.map(Plain))
+            .field("tap_annex", &self.tap_annex.as_deref().map(Hex))
+            .field(
+                "musig2_participant_pubkeys",
+                &Entries(self.musig2_participant_pubkeys.iter().map(
+                    |(aggregate, participants)| {
+                        (Plain(aggregate), participants.iter().map(Plain).collect::<Vec<_>>())
+                    },
+                )),
+            )
+            .field("musig2_pub_nonces", &Entries(musig2(&self.musig2_pub_nonces)))
+            .field("musig2_partial_sigs", &Entries(musig2(&self.musig2_partial_sigs)))
+            .field(
+                "sp_ecdh_shares",
+                &Entries(
+                    self.sp_ecdh_shares.iter().map(|(scan, share)| (Plain(scan), Plain(share))),
+                ),
+            )
+            .field(
+                "sp_dleq_proofs",
+                &Entries(self.sp_dleq_proofs.iter().map(|(scan, proof)| (Plain(scan), proof))),
+            )
+            .field("proprietary", &Proprietary(&self.proprietary))
+            .field("unknown", &Unknown(&self.unknown))
+            .finish()
+    }
+}
+
+/// Returns an iterator over `preimages` for `Debug` output.
+fn preimages<'a, H: fmt::Display>(
+    preimages: &'a BTreeMap<H, Vec<u8>>,
+) -> impl Iterator<Item = (Plain<&'a H>, Hex<'a>)> + Clone {
+    preimages.iter().map(|(hash, preimage)| (Plain(hash), Hex(preimage)))
+}
+
+/// Returns an iterator over MuSig2 values keyed by [`Musig2Key`] for `Debug` output.
+fn musig2<V>(values: &BTreeMap<Musig2Key, V>) -> impl Iterator<Item = (Musig2<'_>, &V)> + Clone {
+    values.iter().map(|(key, value)| (Musig2(key), value))
+}
+
+/// The key of the MuSig2 public nonce and partial signature maps.
+///
+/// The public key of the participant, the aggregate public key (before any BIP 32 or Taproot
+/// tweaks), and the hash of the leaf being signed or `None` for a key path spend.
+pub type Musig2Key = (secp256k1::PublicKey, secp256k1::PublicKey, Option<TapLeafHash>);
+
 /// A Signature hash type for the corresponding input.
 ///
 /// As of Taproot upgrade, the signature hash type can be either [`EcdsaSighashType`] or
@@ -214,6 +424,235 @@ impl std::error::Error for ParseSighashTypeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
 }
 
+/// The way a Taproot input is intended to be spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TapSpendPath {
+    /// Spend using a signature by the output key.
+    KeySpend,
+    /// Spend using the leaf script with this leaf hash.
+    ScriptSpend(TapLeafHash),
+}
+
+/// How an input spends its output, as classified by [`Input::spend_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SpendKind {
+    /// A pay-to-pubkey-hash output (P2PKH).
+    P2pkh,
+    /// A pay-to-script-hash output excluding wrapped segwit (P2SH).
+    P2sh,
+    /// A pay-to-witness-pubkey-hash output nested in a pay-to-script-hash.
+    P2shWpkh,
+    /// A pay-to-witness-pubkey-hash output (P2WPKH).
+    P2wpkh,
+    /// A pay-to-witness-script-hash output nested in a pay-to-script-hash.
+    P2shWsh,
+    /// A pay-to-witness-script-hash output (P2WSH).
+    P2wsh,
+    /// A Taproot output spent using the output key.
+    P2trKey,
+    /// A Taproot output spent using a leaf script.
+    P2trScript,
+    /// Any other output, or a P2SH output without its redeem script.
+    Unknown,
+}
+
+/// Error returned when the Taproot fields of an input are inconsistent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TapError {
+    /// The stored `tap_merkle_root` does not match the root computed from `tap_scripts`.
+    MerkleRootMismatch {
+        /// The merkle root stored in the input.
+        stored: TapNodeHash,
+        /// The merkle root computed from a leaf script and its control block.
+        computed: TapNodeHash,
+    },
+    /// A leaf in `tap_scripts` is not in the script tree.
+    LeafNotInTree {
+        /// The leaf hash of the missing leaf.
+        leaf_hash: TapLeafHash,
+    },
+    /// The leaf is not in `tap_scripts`.
+    UnknownLeaf {
+        /// The leaf hash of the unknown leaf.
+        leaf_hash: TapLeafHash,
+    },
+    /// The leaf script is not one of the supported script templates.
+    UnsupportedLeafScript {
+        /// The leaf hash of the unsupported leaf.
+        leaf_hash: TapLeafHash,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(TapError);
+
+impl fmt::Display for TapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TapError::*;
+
+        match *self {
+            MerkleRootMismatch { stored, computed } => write!(
+                f,
+                "stored taproot merkle root {} does not match computed root {}",
+                stored, computed
+            ),
+            LeafNotInTree { leaf_hash } =>
+                write!(f, "taproot leaf {} is not in the script tree", leaf_hash),
+            UnknownLeaf { leaf_hash } =>
+                write!(f, "taproot leaf {} is not in the input's tap scripts", leaf_hash),
+            UnsupportedLeafScript { leaf_hash } =>
+                write!(f, "the script of taproot leaf {} is not supported", leaf_hash),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use TapError::*;
+
+        match *self {
+            MerkleRootMismatch { .. }
+            | LeafNotInTree { .. }
+            | UnknownLeaf { .. }
+            | UnsupportedLeafScript { .. } => None,
+        }
+    }
+}
+
+/// Error returned when setting a script on an input that does not match the spent output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SetScriptError {
+    /// The script does not hash to the script pubkey of the spent output.
+    ScriptPubkeyMismatch,
+    /// The spent output is P2SH but the input has no redeem script.
+    MissingRedeemScript,
+}
+
+bitcoin_internals::impl_from_infallible!(SetScriptError);
+
+impl fmt::Display for SetScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SetScriptError::*;
+
+        match *self {
+            ScriptPubkeyMismatch =>
+                f.write_str("script does not match the script pubkey of the spent output"),
+            MissingRedeemScript => f.write_str("missing redeem script for a P2SH output"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SetScriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SetScriptError::*;
+
+        match *self {
+            ScriptPubkeyMismatch | MissingRedeemScript => None,
+        }
+    }
+}
+
+/// Returns true if `script` pushes `data`.
+fn pushes(script: &Script, data: &[u8]) -> bool {
+    script.instructions().any(|instruction| {
+        matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == data)
+    })
+}
+
+/// Error returned when adding a signature to an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InsertSigError {
+    /// The ECDSA signature does not have a low S value.
+    HighS,
+    /// The sighash type of the signature is not the sighash type of the input.
+    SighashTypeMismatch,
+    /// The key is not used by the input.
+    IrrelevantKey,
+    /// The leaf is not in the input's tap scripts.
+    UnknownLeaf(TapLeafHash),
+}
+
+bitcoin_internals::impl_from_infallible!(InsertSigError);
+
+impl fmt::Display for InsertSigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use InsertSigError::*;
+
+        match *self {
+            HighS => f.write_str("the signature does not have a low S value"),
+            SighashTypeMismatch =>
+                f.write_str("the sighash type of the signature is not the input's sighash type"),
+            IrrelevantKey => f.write_str("the key is not used by the input"),
+            UnknownLeaf(leaf_hash) =>
+                write!(f, "taproot leaf {} is not in the input's tap scripts", leaf_hash),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InsertSigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use InsertSigError::*;
+
+        match *self {
+            HighS | SighashTypeMismatch | IrrelevantKey | UnknownLeaf(_) => None,
+        }
+    }
+}
+
+/// Error returned when looking up the output spent by an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SpendUtxoError {
+    /// The input has neither a witness UTXO nor a non-witness UTXO.
+    MissingUtxo,
+    /// The non-witness UTXO is not the transaction of the spent outpoint.
+    TxidMismatch,
+    /// The non-witness UTXO does not have the spent output.
+    MissingOutput,
+    /// The spent output is P2SH but the input has no matching redeem script.
+    MissingRedeemScript,
+    /// The spent output is P2WSH but the input has no matching witness script.
+    MissingWitnessScript,
+    /// The spent output is a Taproot output, which has no script code.
+    Taproot,
+}
+
+bitcoin_internals::impl_from_infallible!(SpendUtxoError);
+
+impl fmt::Display for SpendUtxoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SpendUtxoError::*;
+
+        match *self {
+            MissingUtxo => f.write_str("the input has no UTXO"),
+            TxidMismatch =>
+                f.write_str("the non-witness UTXO is not the transaction of the spent outpoint"),
+            MissingOutput => f.write_str("the non-witness UTXO does not have the spent output"),
+            MissingRedeemScript => f.write_str("missing redeem script for a P2SH output"),
+            MissingWitnessScript => f.write_str("missing witness script for a P2WSH output"),
+            Taproot => f.write_str("taproot outputs have no script code"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SpendUtxoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SpendUtxoError::*;
+
+        match *self {
+            MissingUtxo | TxidMismatch | MissingOutput | MissingRedeemScript
+            | MissingWitnessScript | Taproot => None,
+        }
+    }
+}
+
 impl PsbtSighashType {
     /// Ambiguous `ALL` sighash type, may refer to either [`EcdsaSighashType::All`]
     /// or [`TapSighashType::All`].
@@ -284,7 +723,500 @@ impl Input {
             .unwrap_or(Ok(TapSighashType::Default))
     }
 
-    pub(super) fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), Error> {
+    /// Records that `key`, derived as described by `source`, is used in the leaf `leaf_hash`.
+    ///
+    /// Pass `None` for `leaf_hash` to record the key without a leaf, e.g. for the internal key.
+    /// If `key` already has an origin the leaf hash is added to its set of leaf hashes (unless
+    /// already present) and the existing key source is kept.
+    pub fn add_tap_key_origin(
+        &mut self,
+        key: XOnlyPublicKey,
+        leaf_hash: Option<TapLeafHash>,
+        source: KeySource,
+    ) {
+        super::add_tap_key_origin(&mut self.tap_key_origins, key, leaf_hash, source)
+    }
+
+    /// Sets the redeem script after checking that its P2SH script pubkey is `spk`.
+    ///
+    /// `spk` is the script pubkey of the output this input spends.
+    pub fn set_redeem_script_checked(
+        &mut self,
+        script: ScriptBuf,
+        spk: &Script,
+    ) -> Result<(), SetScriptError> {
+        if script.to_p2sh() != *spk {
+            return Err(SetScriptError::ScriptPubkeyMismatch);
+        }
+        self.redeem_script = Some(script);
+        Ok(())
+    }
+
+    /// Sets the witness script after checking that it hashes to `spk`.
+    ///
+    /// `spk` is the script pubkey of the output this input spends. For a native P2WSH output `spk`
+    /// must be the P2WSH of `script`, for P2SH-P2WSH the redeem script must already be set, be the
+    /// P2WSH of `script`, and hash to `spk`.
+    pub fn set_witness_script_checked(
+        &mut self,
+        script: ScriptBuf,
+        spk: &Script,
+    ) -> Result<(), SetScriptError> {
+        let p2wsh = script.to_p2wsh();
+        if spk.is_p2sh() {
+            match self.redeem_script {
+                Some(ref redeem_script) =>
+                    if *redeem_script != p2wsh || redeem_script.to_p2sh() != *spk {
+                        return Err(SetScriptError::ScriptPubkeyMismatch);
+                    },
+                None => return Err(SetScriptError::MissingRedeemScript),
+            }
+        } else if p2wsh != *spk {
+            return Err(SetScriptError::ScriptPubkeyMismatch);
+        }
+        self.witness_script = Some(script);
+        Ok(())
+    }
+
+    /// Checks that the stored `tap_merkle_root` agrees with the script tree in `tap_scripts`.
+    ///
+    /// For every leaf in `tap_scripts` the merkle root is recomputed from the leaf and the merkle
+    /// branch of its control block. Passes trivially if either field is absent.
+    pub fn validate_tap_merkle_root(&self) -> Result<(), TapError> {
+        let stored = match self.tap_merkle_root {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+
+        for (control_block, (script, ver)) in &self.tap_scripts {
+            let leaf_hash = TapLeafHash::from_script(script, *ver);
+            let computed = control_block
+                .merkle_branch
+                .iter()
+                .fold(TapNodeHash::from(leaf_hash), |acc, elem| {
+                    TapNodeHash::from_node_hashes(acc, *elem)
+                });
+            if computed != stored {
+                return Err(TapError::MerkleRootMismatch { stored, computed });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every leaf in `tap_scripts` is a leaf of `tap_tree`.
+    ///
+    /// Inputs do not carry the full script tree, `tap_tree` is the tree of the output being spent
+    /// as known to the caller e.g., from the PSBT output that created it.
+    pub fn validate_tap_scripts_in_tree(&self, tap_tree: &TapTree) -> Result<(), TapError> {
+        for (script, ver) in self.tap_scripts.values() {
+            if !tap_tree
+                .script_leaves()
+                .any(|leaf| leaf.script() == script && leaf.version() == *ver)
+            {
+                let leaf_hash = TapLeafHash::from_script(script, *ver);
+                return Err(TapError::LeafNotInTree { leaf_hash });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the maximum weight of the witness that spends this Taproot input using `path`.
+    ///
+    /// The weight includes the witness element count, the annex, and for script path spends the
+    /// leaf script and its control block, so the weight depends on the depth of the leaf in the script tree.
+    /// Signatures are assumed to be 65 bytes, i.e., to use a non-default sighash type. Only single
+    /// key and `multi_a` leaf scripts are supported.
+    pub fn tap_satisfaction_weight(&self, path: TapSpendPath) -> Result<Weight, TapError> {
+        const MAX_SIG_SIZE: usize = 65;
+
+        // (number of witness elements, size of the elements)
+        let (count, mut size) = match path {
+            TapSpendPath::KeySpend => (1, 1 + MAX_SIG_SIZE),
+            TapSpendPath::ScriptSpend(leaf_hash) => {
+                let (control_block, script) = self
+                    .tap_scripts
+                    .iter()
+                    .find(|(_, (script, ver))| TapLeafHash::from_script(script, *ver) == leaf_hash)
+                    .map(|(control_block, (script, _))| (control_block, script))
+                    .ok_or(TapError::UnknownLeaf { leaf_hash })?;
+
+                // (number of stack elements, size of the elements)
+                let (count, size) = if crate::script::tap_pk(script).is_some() {
+                    (1, 1 + MAX_SIG_SIZE)
+                } else if let Some((threshold, keys)) = crate::script::tap_multi_a(script) {
+                    let (sigs, empty) = (threshold, keys.len() - threshold);
+                    (keys.len(), sigs * (1 + MAX_SIG_SIZE) + empty)
+                } else {
+                    return Err(TapError::UnsupportedLeafScript { leaf_hash });
+                };
+
+                let script_size = VarInt(script.len() as u64).size() + script.len();
+                let control_block_size =
+                    VarInt(control_block.size() as u64).size() + control_block.size();
+                (count + 2, size + script_size + control_block_size)
+            }
+        };
+        let count = match self.tap_annex {
+            Some(ref annex) => {
+                size += VarInt(annex.len() as u64).size() + annex.len();
+                count + 1
+            }
+            None => count,
+        };
+        Ok(Weight::from_witness_data_size((VarInt(count as u64).size() + size) as u64))
+    }
+
+    /// Returns the ECDSA partial signature made by `pk`, if there is one.
+    ///
+    /// Signatures are decoded when the PSBT is deserialized, malformed signatures are rejected at
+    /// that point so a lookup cannot fail to decode.
+    pub fn partial_sig(&self, pk: &PublicKey) -> Option<ecdsa::Signature> {
+        self.partial_sigs.get(pk).copied()
+    }
+
+    /// Adds the ECDSA signature `sig` made by `pk` to the partial signatures, e.g. one made by an
+    /// external signer.
+    ///
+    /// The signature must have a low S value and the sighash type of this input, see
+    /// [`Input::ecdsa_hash_ty`]. `pk` must be in the BIP 32 derivations or be used, or have its
+    /// hash used, in the redeem script, witness script or witness UTXO. The signature itself is
+    /// not verified. Returns the signature `pk` had already made, if any.
+    pub fn insert_ecdsa_sig(
+        &mut self,
+        pk: PublicKey,
+        sig: ecdsa::Signature,
+    ) -> Result<Option<ecdsa::Signature>, InsertSigError> {
+        let mut normalized = sig.signature;
+        normalized.normalize_s();
+        if normalized != sig.signature {
+            return Err(InsertSigError::HighS);
+        }
+        if self.ecdsa_hash_ty() != Ok(sig.sighash_type) {
+            return Err(InsertSigError::SighashTypeMismatch);
+        }
+
+        let key = pk.to_bytes();
+        let hash = pk.pubkey_hash();
+        let scripts = [
+            self.redeem_script.as_deref(),
+            self.witness_script.as_deref(),
+            self.witness_utxo.as_ref().map(|utxo| utxo.script_pubkey.as_script()),
+        ];
+        let relevant = self.bip32_derivation.contains_key(&pk.inner)
+            || scripts
+                .iter()
+                .flatten()
+                .any(|script| pushes(script, &key) || pushes(script, hash.as_ref()));
+        if !relevant {
+            return Err(InsertSigError::IrrelevantKey);
+        }
+        Ok(self.partial_sigs.insert(pk, sig))
+    }
+
+    /// Adds the Schnorr signature `sig` made by `pk`, for a script path spend of the leaf
+    /// `leaf_hash` if one is given, otherwise for a key path spend.
+    ///
+    /// The signature must have the sighash type of this input, see [`Input::taproot_hash_ty`].
+    /// For a key path spend `pk` must be the internal key, the output key of the witness UTXO, or
+    /// have a key origin without leaves. For a script path spend the leaf must be in the tap
+    /// scripts and use `pk`. The signature itself is not verified. Returns the signature already
+    /// present, if any.
+    pub fn insert_schnorr_sig(
+        &mut self,
+        pk: XOnlyPublicKey,
+        leaf_hash: Option<TapLeafHash>,
+        sig: taproot::Signature,
+    ) -> Result<Option<taproot::Signature>, InsertSigError> {
+        if self.taproot_hash_ty() != Ok(sig.sighash_type) {
+            return Err(InsertSigError::SighashTypeMismatch);
+        }
+
+        match leaf_hash {
+            None => {
+                let output_key = self
+                    .witness_utxo
+                    .as_ref()
+                    .filter(|utxo| utxo.script_pubkey.is_p2tr())
+                    .map(|utxo| &utxo.script_pubkey.as_bytes()[2..]);
+                let relevant = self.tap_internal_key == Some(pk)
+                    || output_key == Some(&pk.serialize()[..])
+                    || self.tap_key_origins.get(&pk).map_or(false, |(leaves, _)| leaves.is_empty());
+                if !relevant {
+                    return Err(InsertSigError::IrrelevantKey);
+                }
+                Ok(self.tap_key_sig.replace(sig))
+            }
+            Some(leaf_hash) => {
+                let (script, _) = self
+                    .tap_scripts
+                    .values()
+                    .find(|(script, ver)| TapLeafHash::from_script(script, *ver) == leaf_hash)
+                    .ok_or(InsertSigError::UnknownLeaf(leaf_hash))?;
+                if !pushes(script, &pk.serialize()) {
+                    return Err(InsertSigError::IrrelevantKey);
+                }
+                Ok(self.tap_script_sigs.insert((pk, leaf_hash), sig))
+            }
+        }
+    }
+
+    /// Returns true if this input has been finalized i.e., it has a final scriptSig or a final
+    /// scriptWitness.
+    pub fn is_finalized(&self) -> bool {
+        self.final_script_sig.is_some() || self.final_script_witness.is_some()
+    }
+
+    /// Returns true if the data in this input is sufficient to satisfy `spk`, the script pubkey of
+    /// the output this input spends.
+    ///
+    /// This is a non-mutating predicate version of finalizing the input. With the `miniscript`
+    /// feature the scripts are satisfied by miniscript as `Psbt::finalize_input` does, using
+    /// the signatures and hash preimages of the input. There is no transaction to check against
+    /// so lock times are taken as satisfied.
+    ///
+    /// Without the `miniscript` feature only the standard script templates are understood: P2PK,
+    /// P2PKH, bare multisig, and their P2SH, P2WSH, and P2SH-P2WSH wrapped forms, P2WPKH (native
+    /// and wrapped), Taproot key path spends, and Taproot script path spends of single key and
+    /// `multi_a` leaves. Returns false for any other script.
+    pub fn is_satisfiable(&self, spk: &Script) -> bool {
+        if self.is_finalized() {
+            return true;
+        }
+
+        #[cfg(feature = "miniscript")]
+        return self.miniscript_satisfiable(spk);
+        #[cfg(not(feature = "miniscript"))]
+        return self.template_satisfiable(spk);
+    }
+
+    /// Returns true if `spk` is one of the templates understood by [`Input::is_satisfiable`] and
+    /// the data in this input satisfies it.
+    #[cfg(not(feature = "miniscript"))]
+    fn template_satisfiable(&self, spk: &Script) -> bool {
+        if spk.is_p2tr() {
+            return self.tap_key_sig.is_some()
+                || self
+                    .tap_scripts
+                    .values()
+                    .any(|(script, ver)| self.is_leaf_satisfiable(script, *ver));
+        }
+
+        let mut script = spk;
+        if spk.is_p2sh() {
+            match self.redeem_script {
+                Some(ref redeem_script) if redeem_script.to_p2sh() == *spk =>
+                    script = redeem_script,
+                _ => return false,
+            }
+        }
+
+        if script.is_p2wpkh() {
+            return self.partial_sigs.keys().any(|pk| {
+                pk.wpubkey_hash()
+                    .map(|hash| ScriptBuf::new_p2wpkh(&hash) == *script)
+                    .unwrap_or(false)
+            });
+        }
+
+        if script.is_p2wsh() {
+            match self.witness_script {
+                Some(ref witness_script) if witness_script.to_p2wsh() == *script =>
+                    script = witness_script,
+                _ => return false,
+            }
+        }
+
+        if let Some(pk) = script.p2pk_public_key() {
+            return self.partial_sigs.contains_key(&pk);
+        }
+
+        if script.is_p2pkh() {
+            return self
+                .partial_sigs
+                .keys()
+                .any(|pk| ScriptBuf::new_p2pkh(&pk.pubkey_hash()) == *script);
+        }
+
+        if let Some((threshold, keys)) = crate::script::multisig(script) {
+            return keys.iter().filter(|pk| self.partial_sigs.contains_key(pk)).count()
+                >= threshold;
+        }
+
+        false
+    }
+
+    /// Keeps only the cheapest satisfiable leaf in `tap_scripts` and the script signatures for it.
+    ///
+    /// Does nothing if no leaf is satisfiable. Returns true if any leaf was removed.
+    pub(crate) fn retain_cheapest_tap_leaf(&mut self) -> bool {
+        let cheapest = self
+            .tap_scripts
+            .values()
+            .filter(|(script, ver)| self.is_leaf_satisfiable(script, *ver))
+            .map(|(script, ver)| TapLeafHash::from_script(script, *ver))
+            .filter_map(|leaf_hash| {
+                self.tap_satisfaction_weight(TapSpendPath::ScriptSpend(leaf_hash))
+                    .ok()
+                    .map(|weight| (weight, leaf_hash))
+            })
+            .min();
+        let leaf_hash = match cheapest {
+            Some((_, leaf_hash)) => leaf_hash,
+            None => return false,
+        };
+
+        let len = self.tap_scripts.len();
+        self.tap_scripts
+            .retain(|_, (script, ver)| TapLeafHash::from_script(script, *ver) == leaf_hash);
+        self.tap_script_sigs.retain(|(_, hash), _| *hash == leaf_hash);
+        self.tap_scripts.len() != len
+    }
+
+    /// Returns true if the Taproot leaf `script` can be satisfied by this input's script signatures.
+    fn is_leaf_satisfiable(&self, script: &Script, ver: LeafVersion) -> bool {
+        if ver != LeafVersion::TapScript {
+            return false;
+        }
+        let leaf_hash = TapLeafHash::from_script(script, ver);
+
+        if let Some(pk) = crate::script::tap_pk(script) {
+            return self.tap_script_sigs.contains_key(&(pk, leaf_hash));
+        }
+
+        if let Some((threshold, keys)) = crate::script::tap_multi_a(script) {
+            return keys
+                .iter()
+                .filter(|pk| self.tap_script_sigs.contains_key(&(**pk, leaf_hash)))
+                .count()
+                >= threshold;
+        }
+
+        false
+    }
+
+    /// Returns true if this input spends a segwit output locked by `spk`.
+    ///
+    /// Wrapped segwit outputs (P2SH-P2WPKH and P2SH-P2WSH) are segwit even though `spk` is a
+    /// P2SH, these are detected using the `redeem_script` of this input.
+    pub fn is_segwit(&self, spk: &Script) -> bool {
+        super::is_segwit(spk, self.redeem_script.as_ref())
+    }
+
+    /// Returns how this input spends `spk`, the script pubkey of the output it spends.
+    ///
+    /// P2SH outputs are classified using the redeem script, or the last push of the final
+    /// scriptSig if the input is finalized. Taproot outputs are spent using a leaf script if the
+    /// input has script signatures or a final witness with more than the signature and annex, and
+    /// no key signature. Otherwise they are spent using the output key.
+    pub fn spend_kind(&self, spk: &Script) -> SpendKind {
+        if spk.is_p2pkh() {
+            return SpendKind::P2pkh;
+        }
+        if spk.is_p2wpkh() {
+            return SpendKind::P2wpkh;
+        }
+        if spk.is_p2wsh() {
+            return SpendKind::P2wsh;
+        }
+        if spk.is_p2tr() {
+            let witness_len = self.final_script_witness.as_ref().map_or(0, |witness| {
+                let annex = witness.len() > 1
+                    && witness
+                        .last()
+                        .map_or(false, |last| last.first() == Some(&TAPROOT_ANNEX_PREFIX));
+                witness.len() - usize::from(annex)
+            });
+            return if self.tap_key_sig.is_none()
+                && (!self.tap_script_sigs.is_empty() || witness_len > 1)
+            {
+                SpendKind::P2trScript
+            } else {
+                SpendKind::P2trKey
+            };
+        }
+        if spk.is_p2sh() {
+            let pushed = self.final_script_sig.as_ref().and_then(|script_sig| {
+                match script_sig.instructions().last() {
+                    Some(Ok(Instruction::PushBytes(bytes))) =>
+                        Some(Script::from_bytes(bytes.as_bytes())),
+                    _ => None,
+                }
+            });
+            let redeem_script = match self.redeem_script.as_deref().or(pushed) {
+                Some(redeem_script) if redeem_script.to_p2sh() == *spk => redeem_script,
+                _ => return SpendKind::Unknown,
+            };
+            return if redeem_script.is_p2wpkh() {
+                SpendKind::P2shWpkh
+            } else if redeem_script.is_p2wsh() {
+                SpendKind::P2shWsh
+            } else {
+                SpendKind::P2sh
+            };
+        }
+        SpendKind::Unknown
+    }
+
+    /// Returns the output spent by this input, which spends `outpoint`.
+    ///
+    /// The witness UTXO is used if present. Otherwise the output is taken from the non-witness
+    /// UTXO, after checking that it is the transaction `outpoint` refers to.
+    pub fn spend_utxo(&self, outpoint: OutPoint) -> Result<&TxOut, SpendUtxoError> {
+        if let Some(ref utxo) = self.witness_utxo {
+            return Ok(utxo);
+        }
+        let tx = self.non_witness_utxo.as_ref().ok_or(SpendUtxoError::MissingUtxo)?;
+        if tx.compute_txid() != outpoint.txid {
+            return Err(SpendUtxoError::TxidMismatch);
+        }
+        tx.output.get(outpoint.vout as usize).ok_or(SpendUtxoError::MissingOutput)
+    }
+
+    /// Returns the script pubkey of the output spent by this input, see [`Input::spend_utxo`].
+    pub fn script_pubkey(&self, outpoint: OutPoint) -> Result<&Script, SpendUtxoError> {
+        self.spend_utxo(outpoint).map(|utxo| utxo.script_pubkey.as_script())
+    }
+
+    /// Returns the script code signed by ECDSA signatures for this input, which spends
+    /// `outpoint`.
+    ///
+    /// For P2WPKH outputs, native or wrapped, this is the P2PKH script of the key hash, for P2WSH
+    /// outputs the witness script, for P2SH outputs the redeem script, and the script pubkey for
+    /// everything else. Taproot outputs have no script code.
+    pub fn script_code(&self, outpoint: OutPoint) -> Result<ScriptBuf, SpendUtxoError> {
+        let spk = self.script_pubkey(outpoint)?;
+        if spk.is_p2tr() {
+            return Err(SpendUtxoError::Taproot);
+        }
+        let script = if spk.is_p2sh() {
+            match self.redeem_script {
+                Some(ref redeem_script) if redeem_script.to_p2sh() == *spk => redeem_script,
+                _ => return Err(SpendUtxoError::MissingRedeemScript),
+            }
+        } else {
+            spk
+        };
+        if let Some(script_code) = script.p2wpkh_script_code() {
+            return Ok(script_code);
+        }
+        if script.is_p2wsh() {
+            return match self.witness_script {
+                Some(ref witness_script) if witness_script.to_p2wsh() == *script =>
+                    Ok(witness_script.clone()),
+                _ => Err(SpendUtxoError::MissingWitnessScript),
+            };
+        }
+        Ok(script.to_owned())
+    }
+
+    /// Returns true if the fields of the input show that it does not spend a Taproot output.
+    fn spends_non_taproot(&self) -> bool {
+        self.witness_utxo.as_ref().map_or(false, |utxo| !utxo.script_pubkey.is_p2tr())
+            || self.redeem_script.is_some()
+            || self.witness_script.is_some()
+    }
+
+    pub(crate) fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), Error> {
         let raw::Pair { key: raw_key, value: raw_value } = pair;
 
         match raw_key.type_value {
@@ -395,8 +1327,46 @@ impl Input {
                     self.tap_merkle_root <= <raw_key: _>|< raw_value: TapNodeHash>
                 }
             }
+            PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS => {
+                impl_psbt_insert_pair! {
+                    self.musig2_participant_pubkeys <= <raw_key: secp256k1::PublicKey>|<raw_value: Vec<secp256k1::PublicKey>>
+                }
+            }
+            PSBT_IN_MUSIG2_PUB_NONCE => {
+                impl_psbt_insert_pair! {
+                    self.musig2_pub_nonces <= <raw_key: Musig2Key>|<raw_value: Musig2PubNonce>
+                }
+            }
+            PSBT_IN_MUSIG2_PARTIAL_SIG => {
+                impl_psbt_insert_pair! {
+                    self.musig2_partial_sigs <= <raw_key: Musig2Key>|<raw_value: Musig2PartialSig>
+                }
+            }
+            PSBT_IN_SP_ECDH_SHARE => {
+                impl_psbt_insert_pair! {
+                    self.sp_ecdh_shares <= <raw_key: secp256k1::PublicKey>|<raw_value: secp256k1::PublicKey>
+                }
+            }
+            PSBT_IN_SP_DLEQ => {
+                impl_psbt_insert_pair! {
+                    self.sp_dleq_proofs <= <raw_key: secp256k1::PublicKey>|<raw_value: DleqProof>
+                }
+            }
             PSBT_IN_PROPRIETARY => {
                 let key = raw::ProprietaryKey::try_from(raw_key.clone())?;
+                if key == tap_annex_key() {
+                    if self.tap_annex.is_some() {
+                        return Err(Error::DuplicateKey(raw_key));
+                    }
+                    if raw_value.first() != Some(&TAPROOT_ANNEX_PREFIX) {
+                        return Err(Error::Taproot("annex does not start with 0x50"));
+                    }
+                    if self.spends_non_taproot() {
+                        return Err(Error::Taproot("annex on a non-Taproot input"));
+                    }
+                    self.tap_annex = Some(raw_value);
+                    return Ok(());
+                }
                 match self.proprietary.entry(key) {
                     btree_map::Entry::Vacant(empty_key) => {
                         empty_key.insert(raw_value);
@@ -415,8 +1385,80 @@ impl Input {
         Ok(())
     }
 
+    /// Returns true if every key-value pair of this input is also in `other`.
+    ///
+    /// Combining this input into `other` then changes nothing and can not conflict.
+    pub(crate) fn is_contained_in(&self, other: &Input) -> bool {
+        use super::{is_subfield as field, is_submap as map};
+
+        // Destructured so that a new field can not be forgotten.
+        let Input {
+            non_witness_utxo,
+            witness_utxo,
+            partial_sigs,
+            sighash_type,
+            redeem_script,
+            witness_script,
+            bip32_derivation,
+            final_script_sig,
+            final_script_witness,
+            ripemd160_preimages,
+            sha256_preimages,
+            hash160_preimages,
+            hash256_preimages,
+            tap_key_sig,
+            tap_script_sigs,
+            tap_scripts,
+            tap_key_origins,
+            tap_internal_key,
+            tap_merkle_root,
+            tap_annex,
+            musig2_participant_pubkeys,
+            musig2_pub_nonces,
+            musig2_partial_sigs,
+            sp_ecdh_shares,
+            sp_dleq_proofs,
+            proprietary,
+            unknown,
+        } = self;
+
+        field(witness_utxo, &other.witness_utxo)
+            && map(partial_sigs, &other.partial_sigs)
+            && map(bip32_derivation, &other.bip32_derivation)
+            && field(sighash_type, &other.sighash_type)
+            && field(redeem_script, &other.redeem_script)
+            && field(witness_script, &other.witness_script)
+            && field(final_script_sig, &other.final_script_sig)
+            && field(final_script_witness, &other.final_script_witness)
+            && map(ripemd160_preimages, &other.ripemd160_preimages)
+            && map(sha256_preimages, &other.sha256_preimages)
+            && map(hash160_preimages, &other.hash160_preimages)
+            && map(hash256_preimages, &other.hash256_preimages)
+            && field(tap_key_sig, &other.tap_key_sig)
+            && map(tap_script_sigs, &other.tap_script_sigs)
+            && map(tap_scripts, &other.tap_scripts)
+            && map(tap_key_origins, &other.tap_key_origins)
+            && field(tap_internal_key, &other.tap_internal_key)
+            && field(tap_merkle_root, &other.tap_merkle_root)
+            && field(tap_annex, &other.tap_annex)
+            && map(musig2_participant_pubkeys, &other.musig2_participant_pubkeys)
+            && map(musig2_pub_nonces, &other.musig2_pub_nonces)
+            && map(musig2_partial_sigs, &other.musig2_partial_sigs)
+            && map(sp_ecdh_shares, &other.sp_ecdh_shares)
+            && map(sp_dleq_proofs, &other.sp_dleq_proofs)
+            && map(proprietary, &other.proprietary)
+            && map(unknown, &other.unknown)
+            // Compared last, the transaction is the largest field.
+            && field(non_witness_utxo, &other.non_witness_utxo)
+    }
+
     /// Combines this [`Input`] with `other` `Input` (as described by BIP 174).
-    pub fn combine(&mut self, other: Self) {
+    pub fn combine(&mut self, mut other: Self) {
+        // Most inputs of the copies being combined have nothing this input does not have already.
+        if other.is_contained_in(self) {
+            return;
+        }
+
         combine!(non_witness_utxo, self, other);
 
         if let (&None, Some(witness_utxo)) = (&self.witness_utxo, other.witness_utxo) {
@@ -424,18 +1466,24 @@ impl Input {
             self.non_witness_utxo = None; // Clear out any non-witness UTXO when we set a witness one
         }
 
-        self.partial_sigs.extend(other.partial_sigs);
-        self.bip32_derivation.extend(other.bip32_derivation);
-        self.ripemd160_preimages.extend(other.ripemd160_preimages);
-        self.sha256_preimages.extend(other.sha256_preimages);
-        self.hash160_preimages.extend(other.hash160_preimages);
-        self.hash256_preimages.extend(other.hash256_preimages);
-        self.tap_script_sigs.extend(other.tap_script_sigs);
-        self.tap_scripts.extend(other.tap_scripts);
-        self.tap_key_origins.extend(other.tap_key_origins);
-        self.proprietary.extend(other.proprietary);
-        self.unknown.extend(other.unknown);
+        super::append(&mut self.partial_sigs, &mut other.partial_sigs);
+        super::append(&mut self.bip32_derivation, &mut other.bip32_derivation);
+        super::append(&mut self.ripemd160_preimages, &mut other.ripemd160_preimages);
+        super::append(&mut self.sha256_preimages, &mut other.sha256_preimages);
+        super::append(&mut self.hash160_preimages, &mut other.hash160_preimages);
+        super::append(&mut self.hash256_preimages, &mut other.hash256_preimages);
+        super::append(&mut self.tap_script_sigs, &mut other.tap_script_sigs);
+        super::append(&mut self.tap_scripts, &mut other.tap_scripts);
+        super::append(&mut self.tap_key_origins, &mut other.tap_key_origins);
+        super::append(&mut self.musig2_participant_pubkeys, &mut other.musig2_participant_pubkeys);
+        super::append(&mut self.musig2_pub_nonces, &mut other.musig2_pub_nonces);
+        super::append(&mut self.musig2_partial_sigs, &mut other.musig2_partial_sigs);
+        super::append(&mut self.sp_ecdh_shares, &mut other.sp_ecdh_shares);
+        super::append(&mut self.sp_dleq_proofs, &mut other.sp_dleq_proofs);
+        super::append(&mut self.proprietary, &mut other.proprietary);
+        super::append(&mut self.unknown, &mut other.unknown);
 
+        combine!(sighash_type, self, other);
         combine!(redeem_script, self, other);
         combine!(witness_script, self, other);
         combine!(final_script_sig, self, other);
@@ -443,6 +1491,7 @@ impl Input {
         combine!(tap_key_sig, self, other);
         combine!(tap_internal_key, self, other);
         combine!(tap_merkle_root, self, other);
+        combine!(tap_annex, self, other);
     }
 }
 
@@ -525,6 +1574,30 @@ impl Map for Input {
         impl_psbt_get_pair! {
             rv.push(self.tap_merkle_root, PSBT_IN_TAP_MERKLE_ROOT)
         }
+
+        impl_psbt_get_pair! {
+            rv.push_map(self.musig2_participant_pubkeys, PSBT_IN_MUSIG2_PARTICIPANT_PUBKEYS)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push_map(self.musig2_pub_nonces, PSBT_IN_MUSIG2_PUB_NONCE)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push_map(self.musig2_partial_sigs, PSBT_IN_MUSIG2_PARTIAL_SIG)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push_map(self.sp_ecdh_shares, PSBT_IN_SP_ECDH_SHARE)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push_map(self.sp_dleq_proofs, PSBT_IN_SP_DLEQ)
+        }
+        if let Some(ref annex) = self.tap_annex {
+            rv.push(raw::Pair { key: tap_annex_key().to_key(), value: annex.clone() });
+        }
+
         for (key, value) in self.proprietary.iter() {
             rv.push(raw::Pair { key: key.to_key(), value: value.clone() });
         }
@@ -537,7 +1610,7 @@ impl Map for Input {
     }
 }
 
-impl_psbtmap_ser_de_serialize!(Input);
+impl_psbtmap_ser_de_serialize!(Input, crate::MapLocation::Input(0));
 
 fn psbt_insert_hash_pair<H>(
     map: &mut BTreeMap<H, Vec<u8>>,
@@ -622,6 +1695,483 @@ mod test {
         assert_eq!(back.taproot_hash_ty(), Err(InvalidSighashTypeError(nonstd)));
     }
 
+    #[test]
+    fn is_segwit_wrapped_p2wpkh() {
+        let pk = "0339880dc92394b7355e3d0439fa283c31de7590812ea011c4245c0674a685e883"
+            .parse::<bitcoin::CompressedPublicKey>()
+            .unwrap();
+        let redeem_script = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash());
+        let spk = redeem_script.to_p2sh();
+
+        let mut input = Input::default();
+        assert!(!input.is_segwit(&spk)); // Can't tell without the redeem script.
+
+        input.redeem_script = Some(redeem_script.clone());
+        assert!(input.is_segwit(&spk));
+        assert!(input.is_segwit(&redeem_script)); // Native P2WPKH.
+        assert!(!input.is_segwit(&ScriptBuf::new_p2pkh(&pk.pubkey_hash())));
+    }
+
+    #[test]
+    fn spend_utxo_and_script_code() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::{absolute, transaction, Amount};
+
+        let pk = "0339880dc92394b7355e3d0439fa283c31de7590812ea011c4245c0674a685e883"
+            .parse::<bitcoin::CompressedPublicKey>()
+            .unwrap();
+        let redeem_script = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash());
+        let witness_script = ScriptBuf::new_p2pk(&pk.into());
+        let utxo = |script_pubkey| TxOut { value: Amount::from_sat(1_000), script_pubkey };
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![utxo(redeem_script.to_p2sh()), utxo(witness_script.to_p2wsh())],
+        };
+        let outpoint = OutPoint { txid: tx.compute_txid(), vout: 0 };
+
+        let mut input = Input::default();
+        assert_eq!(input.spend_utxo(outpoint), Err(SpendUtxoError::MissingUtxo));
+        input.non_witness_utxo = Some(tx.clone());
+        assert_eq!(input.spend_utxo(outpoint), Ok(&tx.output[0]));
+        let wrong_txid = OutPoint { txid: bitcoin::Txid::from_byte_array([1; 32]), vout: 0 };
+        assert_eq!(input.spend_utxo(wrong_txid), Err(SpendUtxoError::TxidMismatch));
+        let wrong_vout = OutPoint { vout: 2, ..outpoint };
+        assert_eq!(input.spend_utxo(wrong_vout), Err(SpendUtxoError::MissingOutput));
+
+        assert_eq!(input.script_code(outpoint), Err(SpendUtxoError::MissingRedeemScript));
+        input.redeem_script = Some(redeem_script);
+        assert_eq!(input.script_code(outpoint), Ok(ScriptBuf::new_p2pkh(&pk.pubkey_hash())));
+
+        let outpoint = OutPoint { vout: 1, ..outpoint };
+        assert_eq!(input.script_pubkey(outpoint), Ok(tx.output[1].script_pubkey.as_script()));
+        assert_eq!(input.script_code(outpoint), Err(SpendUtxoError::MissingWitnessScript));
+        input.witness_script = Some(witness_script.clone());
+        assert_eq!(input.script_code(outpoint), Ok(witness_script));
+
+        // The witness UTXO is preferred and needs no outpoint check.
+        input.witness_utxo =
+            Some(utxo(ScriptBuf::from_bytes([&[0x51, 0x20][..], &[2; 32]].concat())));
+        assert_eq!(input.script_code(wrong_txid), Err(SpendUtxoError::Taproot));
+    }
+
+    #[test]
+    fn spend_kind() {
+        let pk = "0339880dc92394b7355e3d0439fa283c31de7590812ea011c4245c0674a685e883"
+            .parse::<bitcoin::CompressedPublicKey>()
+            .unwrap();
+        let wpkh = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash());
+        let wsh = ScriptBuf::new_p2pk(&pk.into()).to_p2wsh();
+        let p2tr = ScriptBuf::from_bytes([&[0x51, 0x20][..], &[2; 32]].concat());
+
+        let mut input = Input::default();
+        let pkh = ScriptBuf::new_p2pkh(&pk.pubkey_hash());
+        assert_eq!(input.spend_kind(&pkh), SpendKind::P2pkh);
+        assert_eq!(input.spend_kind(&wpkh), SpendKind::P2wpkh);
+        assert_eq!(input.spend_kind(&wsh), SpendKind::P2wsh);
+        assert_eq!(input.spend_kind(&wpkh.to_p2sh()), SpendKind::Unknown);
+        assert_eq!(input.spend_kind(&p2tr), SpendKind::P2trKey);
+        assert_eq!(input.spend_kind(&ScriptBuf::new_op_return([1])), SpendKind::Unknown);
+
+        input.redeem_script = Some(wpkh.clone());
+        assert_eq!(input.spend_kind(&wpkh.to_p2sh()), SpendKind::P2shWpkh);
+        assert_eq!(input.spend_kind(&wsh.to_p2sh()), SpendKind::Unknown);
+        input.redeem_script = Some(wsh.clone());
+        assert_eq!(input.spend_kind(&wsh.to_p2sh()), SpendKind::P2shWsh);
+        input.redeem_script = None;
+        let script_sig = bitcoin::script::Builder::new()
+            .push_slice([1; 72])
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(pkh.as_bytes()).unwrap())
+            .into_script();
+        input.final_script_sig = Some(script_sig);
+        assert_eq!(input.spend_kind(&pkh.to_p2sh()), SpendKind::P2sh);
+
+        // A signature and an annex is a key spend, a script and control block too is not.
+        input.final_script_witness = Some(Witness::from_slice(&[&[1; 64][..], &[0x50]]));
+        assert_eq!(input.spend_kind(&p2tr), SpendKind::P2trKey);
+        input.final_script_witness =
+            Some(Witness::from_slice(&[&[1; 64][..], &[0x51], &[0xc0; 33]]));
+        assert_eq!(input.spend_kind(&p2tr), SpendKind::P2trScript);
+    }
+
+    #[test]
+    fn insert_sigs() {
+        use bitcoin::secp256k1::{Keypair, Message, Secp256k1, SecretKey};
+        use bitcoin::Amount;
+
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk));
+
+        let mut input = Input::default();
+        assert_eq!(input.insert_ecdsa_sig(pk, sig), Err(InsertSigError::IrrelevantKey));
+        input.witness_utxo = Some(TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+        });
+        assert_eq!(input.insert_ecdsa_sig(pk, sig), Ok(None));
+        assert_eq!(input.insert_ecdsa_sig(pk, sig), Ok(Some(sig)));
+
+        let none = ecdsa::Signature { sighash_type: EcdsaSighashType::None, ..sig };
+        assert_eq!(input.insert_ecdsa_sig(pk, none), Err(InsertSigError::SighashTypeMismatch));
+
+        // The same signature with S replaced by the curve order minus S.
+        let mut compact = sig.signature.serialize_compact();
+        let order = secp256k1::constants::CURVE_ORDER;
+        let mut borrow = 0;
+        for i in (32..64).rev() {
+            let diff = i16::from(order[i - 32]) - i16::from(compact[i]) - borrow;
+            borrow = i16::from(diff < 0);
+            compact[i] = (diff + 256 * borrow) as u8;
+        }
+        let high_s = ecdsa::Signature {
+            signature: secp256k1::ecdsa::Signature::from_compact(&compact).unwrap(),
+            ..sig
+        };
+        assert_eq!(input.insert_ecdsa_sig(pk, high_s), Err(InsertSigError::HighS));
+
+        let keypair = Keypair::from_secret_key(&secp, &sk);
+        let xonly = keypair.x_only_public_key().0;
+        let schnorr = taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            sighash_type: TapSighashType::Default,
+        };
+        let mut input = Input::default();
+        assert_eq!(
+            input.insert_schnorr_sig(xonly, None, schnorr),
+            Err(InsertSigError::IrrelevantKey)
+        );
+        input.tap_internal_key = Some(xonly);
+        assert_eq!(input.insert_schnorr_sig(xonly, None, schnorr), Ok(None));
+        assert_eq!(input.tap_key_sig, Some(schnorr));
+        let all = taproot::Signature { sighash_type: TapSighashType::All, ..schnorr };
+        assert_eq!(
+            input.insert_schnorr_sig(xonly, None, all),
+            Err(InsertSigError::SighashTypeMismatch)
+        );
+
+        let script = bitcoin::script::Builder::new()
+            .push_x_only_key(&xonly)
+            .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+        assert_eq!(
+            input.insert_schnorr_sig(xonly, Some(leaf_hash), schnorr),
+            Err(InsertSigError::UnknownLeaf(leaf_hash))
+        );
+        let control_block =
+            ControlBlock::decode(&[&[0xc0][..], &xonly.serialize()].concat()).unwrap();
+        input.tap_scripts.insert(control_block, (script, LeafVersion::TapScript));
+        assert_eq!(input.insert_schnorr_sig(xonly, Some(leaf_hash), schnorr), Ok(None));
+        let other = Keypair::from_secret_key(&secp, &SecretKey::from_slice(&[2; 32]).unwrap());
+        assert_eq!(
+            input.insert_schnorr_sig(other.x_only_public_key().0, Some(leaf_hash), schnorr),
+            Err(InsertSigError::IrrelevantKey)
+        );
+    }
+
+    #[test]
+    fn debug_formats_partial_sigs_as_hex() {
+        use bitcoin::hex::DisplayHex;
+        use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let sig =
+            ecdsa::Signature::sighash_all(secp.sign_ecdsa(&Message::from_digest([1; 32]), &sk));
+
+        let mut input = Input::default();
+        input.partial_sigs.insert(pk, sig);
+        input.unknown.insert(raw::Key { type_value: 0xe0, key_data: vec![0xab] }, vec![0xcd]);
+
+        let debug = format!("{:?}", input);
+        assert!(debug.contains(&format!("partial_sigs: {{{}: {}}}", pk, sig.to_vec().as_hex())));
+        assert!(debug.contains("unknown: {0xe0:ab: cd}"));
+        assert!(!debug.contains("key_data"));
+    }
+
+    #[test]
+    fn is_satisfiable_multisig() {
+        use bitcoin::opcodes::all::OP_CHECKMULTISIG;
+        use bitcoin::script::Builder;
+        use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+
+        let keys = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .map(|sk| (PublicKey::new(sk.public_key(&secp)), sk))
+            .collect::<Vec<_>>();
+        let witness_script = Builder::new()
+            .push_int(2)
+            .push_key(&keys[0].0)
+            .push_key(&keys[1].0)
+            .push_key(&keys[2].0)
+            .push_int(3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let spk = witness_script.to_p2wsh();
+
+        let mut input = Input { witness_script: Some(witness_script), ..Default::default() };
+        let sign = |input: &mut Input, (pk, sk): &(PublicKey, SecretKey)| {
+            let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, sk));
+            input.partial_sigs.insert(*pk, sig);
+        };
+
+        sign(&mut input, &keys[0]);
+        assert!(!input.is_satisfiable(&spk));
+
+        sign(&mut input, &keys[2]);
+        assert!(input.is_satisfiable(&spk));
+    }
+
+    #[test]
+    fn partial_sig() {
+        use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([1; 32]);
+
+        // Two of the three keys of a 2-of-3 multisig have signed.
+        let mut input = Input::default();
+        let keys = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .map(|sk| (PublicKey::new(sk.public_key(&secp)), sk))
+            .collect::<Vec<_>>();
+        for (pk, sk) in [&keys[0], &keys[2]] {
+            let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, sk));
+            input.partial_sigs.insert(*pk, sig);
+        }
+
+        let expected = input.partial_sigs[&keys[2].0];
+        assert_eq!(input.partial_sig(&keys[2].0), Some(expected));
+        assert_eq!(input.partial_sig(&keys[1].0), None);
+    }
+
+    #[test]
+    fn is_satisfiable_script_path_only() {
+        use bitcoin::opcodes::all::OP_CHECKSIG;
+        use bitcoin::script::Builder;
+        use bitcoin::secp256k1::{Keypair, Message, Secp256k1};
+        use bitcoin::taproot::TaprootBuilder;
+
+        // The BIP-341 NUMS point, an internal key with no known private key.
+        let nums = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0"
+            .parse::<XOnlyPublicKey>()
+            .unwrap();
+
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let (key, _) = keypair.x_only_public_key();
+
+        let script = Builder::new().push_x_only_key(&key).push_opcode(OP_CHECKSIG).into_script();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, script.clone())
+            .unwrap()
+            .finalize(&secp, nums)
+            .unwrap();
+        let spk = ScriptBuf::new_p2tr_tweaked(spend_info.output_key());
+        let leaf = (script.clone(), LeafVersion::TapScript);
+        let control_block = spend_info.control_block(&leaf).unwrap();
+
+        let mut input = Input { tap_internal_key: Some(nums), ..Default::default() };
+        input.tap_scripts.insert(control_block, leaf);
+        assert!(!input.is_satisfiable(&spk));
+
+        let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+        let sig = taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&Message::from_digest([1; 32]), &keypair),
+            sighash_type: TapSighashType::Default,
+        };
+        input.tap_script_sigs.insert((key, leaf_hash), sig);
+        assert!(input.tap_key_sig.is_none());
+        assert!(input.is_satisfiable(&spk));
+    }
+
+    #[test]
+    fn set_witness_script_checked() {
+        let witness_script = ScriptBuf::from_bytes(vec![0x51]);
+        let other = ScriptBuf::from_bytes(vec![0x52]);
+
+        let spk = witness_script.to_p2wsh();
+        let mut input = Input::default();
+        assert_eq!(
+            input.set_witness_script_checked(other.clone(), &spk),
+            Err(SetScriptError::ScriptPubkeyMismatch)
+        );
+        assert!(input.witness_script.is_none());
+        assert_eq!(input.set_witness_script_checked(witness_script.clone(), &spk), Ok(()));
+        assert_eq!(input.witness_script.as_ref(), Some(&witness_script));
+
+        // P2SH-P2WSH
+        let redeem_script = witness_script.to_p2wsh();
+        let spk = redeem_script.to_p2sh();
+        let mut input = Input::default();
+        assert_eq!(
+            input.set_witness_script_checked(witness_script.clone(), &spk),
+            Err(SetScriptError::MissingRedeemScript)
+        );
+        input.set_redeem_script_checked(redeem_script, &spk).unwrap();
+        assert_eq!(
+            input.set_witness_script_checked(other, &spk),
+            Err(SetScriptError::ScriptPubkeyMismatch)
+        );
+        assert_eq!(input.set_witness_script_checked(witness_script, &spk), Ok(()));
+    }
+
+    #[test]
+    fn validate_tap_merkle_root() {
+        use bitcoin::secp256k1::Secp256k1;
+        use bitcoin::taproot::TaprootBuilder;
+
+        let secp = Secp256k1::verification_only();
+        let internal_key = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0"
+            .parse::<XOnlyPublicKey>()
+            .unwrap();
+        let scripts = [ScriptBuf::from_bytes(vec![0x51]), ScriptBuf::from_bytes(vec![0x52])];
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, scripts[0].clone())
+            .unwrap()
+            .add_leaf(1, scripts[1].clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+
+        let mut input = Input { tap_merkle_root: spend_info.merkle_root(), ..Default::default() };
+        for script in scripts {
+            let leaf = (script, LeafVersion::TapScript);
+            input.tap_scripts.insert(spend_info.control_block(&leaf).unwrap(), leaf);
+        }
+        assert_eq!(input.validate_tap_merkle_root(), Ok(()));
+
+        let wrong = TapNodeHash::from_script(Script::new(), LeafVersion::TapScript);
+        input.tap_merkle_root = Some(wrong);
+        assert!(matches!(
+            input.validate_tap_merkle_root(),
+            Err(TapError::MerkleRootMismatch { stored, .. }) if stored == wrong
+        ));
+    }
+
+    #[test]
+    fn validate_tap_scripts_in_tree() {
+        use bitcoin::secp256k1::Secp256k1;
+        use bitcoin::taproot::TaprootBuilder;
+
+        let secp = Secp256k1::verification_only();
+        let internal_key = "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0"
+            .parse::<XOnlyPublicKey>()
+            .unwrap();
+        let scripts = [ScriptBuf::from_bytes(vec![0x51]), ScriptBuf::from_bytes(vec![0x52])];
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, scripts[0].clone())
+            .unwrap()
+            .add_leaf(1, scripts[1].clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+
+        let mut input = Input::default();
+        for script in &scripts {
+            let leaf = (script.clone(), LeafVersion::TapScript);
+            input.tap_scripts.insert(spend_info.control_block(&leaf).unwrap(), leaf);
+        }
+
+        let tap_tree = TapTree::try_from(
+            TaprootBuilder::new()
+                .add_leaf(1, scripts[0].clone())
+                .unwrap()
+                .add_leaf(1, scripts[1].clone())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(input.validate_tap_scripts_in_tree(&tap_tree), Ok(()));
+
+        let other_tree = TapTree::try_from(
+            TaprootBuilder::new()
+                .add_leaf(1, scripts[0].clone())
+                .unwrap()
+                .add_leaf(1, ScriptBuf::from_bytes(vec![0x53]))
+                .unwrap(),
+        )
+        .unwrap();
+        let leaf_hash = TapLeafHash::from_script(&scripts[1], LeafVersion::TapScript);
+        assert_eq!(
+            input.validate_tap_scripts_in_tree(&other_tree),
+            Err(TapError::LeafNotInTree { leaf_hash })
+        );
+    }
+
+    #[test]
+    fn tap_satisfaction_weight() {
+        use bitcoin::opcodes::all::OP_CHECKSIG;
+        use bitcoin::script::Builder;
+        use bitcoin::secp256k1::Secp256k1;
+        use bitcoin::taproot::TaprootBuilder;
+
+        let secp = Secp256k1::verification_only();
+        let key = "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d"
+            .parse::<XOnlyPublicKey>()
+            .unwrap();
+        let script = Builder::new().push_x_only_key(&key).push_opcode(OP_CHECKSIG).into_script();
+        let other = ScriptBuf::from_bytes(vec![0x51]);
+
+        // `script` is at depth 2.
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, other.clone())
+            .unwrap()
+            .add_leaf(2, other.clone())
+            .unwrap()
+            .add_leaf(2, script.clone())
+            .unwrap()
+            .finalize(&secp, key)
+            .unwrap();
+        let leaf = (script.clone(), LeafVersion::TapScript);
+        let mut input = Input::default();
+        input.tap_scripts.insert(spend_info.control_block(&leaf).unwrap(), leaf);
+
+        // Element count, signature with length prefix.
+        let key_spend = input.tap_satisfaction_weight(TapSpendPath::KeySpend).unwrap();
+        assert_eq!(key_spend, Weight::from_witness_data_size(1 + 1 + 65));
+
+        // Element count, signature, script, and control block each with length prefix.
+        let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+        let script_spend =
+            input.tap_satisfaction_weight(TapSpendPath::ScriptSpend(leaf_hash)).unwrap();
+        assert_eq!(script_spend, Weight::from_witness_data_size(1 + 66 + 35 + 1 + 33 + 2 * 32));
+
+        let unknown = TapLeafHash::from_script(&other, LeafVersion::TapScript);
+        assert_eq!(
+            input.tap_satisfaction_weight(TapSpendPath::ScriptSpend(unknown)),
+            Err(TapError::UnknownLeaf { leaf_hash: unknown })
+        );
+    }
+
+    #[test]
+    fn add_tap_key_origin_accumulates_leaf_hashes() {
+        use bitcoin::bip32::{DerivationPath, Fingerprint};
+
+        let key = "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d"
+            .parse::<XOnlyPublicKey>()
+            .unwrap();
+        let source = (Fingerprint::default(), DerivationPath::master());
+        let leaf_a = TapLeafHash::from_script(Script::new(), LeafVersion::TapScript);
+        let leaf_b = TapLeafHash::from_script(Script::from_bytes(&[0x51]), LeafVersion::TapScript);
+
+        let mut input = Input::default();
+        input.add_tap_key_origin(key, Some(leaf_a), source.clone());
+        input.add_tap_key_origin(key, Some(leaf_b), source.clone());
+        input.add_tap_key_origin(key, Some(leaf_a), source.clone());
+        input.add_tap_key_origin(key, None, source.clone());
+
+        assert_eq!(input.tap_key_origins.len(), 1);
+        assert_eq!(input.tap_key_origins[&key], (vec![leaf_a, leaf_b], source));
+    }
+
     #[test]
     fn psbt_sighash_const_all() {
         assert_eq!(PsbtSighashType::ALL.to_u32(), 0x01);
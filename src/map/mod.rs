@@ -1,19 +1,37 @@
 // SPDX-License-Identifier: CC0-1.0
 
+mod debug;
 mod global;
 mod input;
+mod musig;
 mod output;
+mod silent_payments;
+mod view;
+
+use core::mem;
+
+use bitcoin::bip32::KeySource;
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::taproot::TapLeafHash;
+use bitcoin::{Script, ScriptBuf};
 
 use crate::prelude::*;
-use crate::raw;
 use crate::serialize::Serialize;
+use crate::{raw, MapLocation, Psbt};
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
-    input::{Input, PsbtSighashType},
+    input::{
+        Input, InsertSigError, Musig2Key, PsbtSighashType, SetScriptError, SpendKind, SpendUtxoError, TapError,
+        TapSpendPath,
+    },
+    musig::{Musig2PartialSig, Musig2PubNonce},
     output::Output,
+    silent_payments::DleqProof,
+    view::{InputRef, OutputRef},
 };
+pub(crate) use self::global::conflicting_global_pairs;
 
 /// A trait that describes a PSBT key-value map.
 pub(super) trait Map {
@@ -27,12 +45,107 @@ pub(super) trait Map {
     /// Why is the separator here 0x00 instead of 0xff? The separator here is used to distinguish between each chunk of data.
     /// A separator of 0x00 would mean that the unserializer can read it as a key length of 0, which would never occur with
     /// actual keys. It can thus be used as a separator and allow for easier unserializer implementation.
+    ///
+    /// The pairs are written sorted by key type and then key data, so equal maps always serialize
+    /// to the same bytes.
     fn serialize_map(&self) -> Vec<u8> {
+        let mut pairs = Map::get_pairs(self);
+        pairs.sort_by(|a, b| a.key.cmp(&b.key));
         let mut buf = Vec::new();
-        for pair in Map::get_pairs(self) {
+        for pair in pairs {
             buf.extend(&pair.serialize());
         }
         buf.push(0x00_u8);
         buf
     }
 }
+
+/// Moves all entries of `theirs` into `ours`, the value in `theirs` is kept for keys in both.
+///
+/// Like [`BTreeMap::append`] but without rebuilding `ours`, combined maps usually have only a few
+/// entries that are not in `ours` already.
+pub(crate) fn append<K: Ord, V>(ours: &mut BTreeMap<K, V>, theirs: &mut BTreeMap<K, V>) {
+    if ours.is_empty() {
+        mem::swap(ours, theirs);
+        return;
+    }
+    for (key, value) in mem::take(theirs) {
+        ours.insert(key, value);
+    }
+}
+
+/// Returns true if every entry of `ours` is in `theirs` with the same value.
+pub(crate) fn is_submap<K: Ord, V: PartialEq>(
+    ours: &BTreeMap<K, V>,
+    theirs: &BTreeMap<K, V>,
+) -> bool {
+    ours.len() <= theirs.len() && ours.iter().all(|(key, value)| theirs.get(key) == Some(value))
+}
+
+/// Returns true if `ours` is not set or `theirs` has the same value.
+pub(crate) fn is_subfield<T: PartialEq>(ours: &Option<T>, theirs: &Option<T>) -> bool {
+    ours.is_none() || ours == theirs
+}
+
+/// Returns the keys that are in both `ours` and `theirs` with different values, along with the
+/// value in `ours` and the value in `theirs`.
+pub(crate) fn conflicting_pairs<M: Map>(ours: &M, theirs: &M) -> Vec<(raw::Key, Vec<u8>, Vec<u8>)> {
+    let ours =
+        ours.get_pairs().into_iter().map(|pair| (pair.key, pair.value)).collect::<BTreeMap<_, _>>();
+    theirs
+        .get_pairs()
+        .into_iter()
+        .filter_map(|pair| match ours.get(&pair.key) {
+            Some(value) if *value != pair.value => Some((pair.key, value.clone(), pair.value)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the name of the field with key type `type_value` in the map at `location`.
+pub(crate) fn field_name(location: MapLocation, type_value: u8) -> &'static str {
+    match location {
+        MapLocation::Global => global::field_name(type_value),
+        MapLocation::Input(_) => input::field_name(type_value),
+        MapLocation::Output(_) => output::field_name(type_value),
+    }
+}
+
+/// Returns the key type of the first field of the input or output at `location` that is not
+/// allowed given the script pubkey `spk` of the spent or created output.
+pub(crate) fn disallowed_field(psbt: &Psbt, location: MapLocation, spk: &Script) -> Option<u8> {
+    match location {
+        MapLocation::Global => None,
+        MapLocation::Input(index) => input::disallowed_field(&psbt.inputs[index], spk),
+        MapLocation::Output(index) => output::disallowed_field(&psbt.outputs[index], spk),
+    }
+}
+
+/// Returns true if `spk` is a witness program or a P2SH wrapping the witness program `redeem_script`.
+pub(super) fn is_segwit(spk: &Script, redeem_script: Option<&ScriptBuf>) -> bool {
+    if spk.is_witness_program() {
+        return true;
+    }
+    match redeem_script {
+        Some(redeem_script) if spk.is_p2sh() =>
+            redeem_script.is_witness_program() && redeem_script.to_p2sh() == *spk,
+        _ => false,
+    }
+}
+
+/// Records `key` with `source` in `origins`, adding `leaf_hash` to its leaf hashes if given.
+///
+/// Shared by [`Input::add_tap_key_origin`] and [`Output::add_tap_key_origin`].
+pub(crate) fn add_tap_key_origin(
+    origins: &mut BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>,
+    key: XOnlyPublicKey,
+    leaf_hash: Option<TapLeafHash>,
+    source: KeySource,
+) {
+    let (leaf_hashes, _) = origins.entry(key).or_insert_with(|| (vec![], source));
+    if let Some(leaf_hash) = leaf_hash {
+        if !leaf_hashes.contains(&leaf_hash) {
+            leaf_hashes.push(leaf_hash);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Values of the MuSig2 PSBT fields defined in BIP 373.
+
+/// A MuSig2 public nonce as defined in BIP 327.
+///
+/// The nonce is stored as its 66 byte serialization, it is not checked to be valid.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Musig2PubNonce([u8; 66]);
+
+impl Musig2PubNonce {
+    /// Creates a public nonce from its 66 byte serialization.
+    pub fn from_byte_array(bytes: [u8; 66]) -> Self { Musig2PubNonce(bytes) }
+
+    /// Returns the 66 byte serialization of this public nonce.
+    pub fn to_byte_array(self) -> [u8; 66] { self.0 }
+
+    /// Returns a reference to the 66 byte serialization of this public nonce.
+    pub fn as_byte_array(&self) -> &[u8; 66] { &self.0 }
+}
+
+/// A MuSig2 partial signature as defined in BIP 327.
+///
+/// The partial signature is stored as its 32 byte serialization, it is not checked to be valid.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Musig2PartialSig([u8; 32]);
+
+impl Musig2PartialSig {
+    /// Creates a partial signature from its 32 byte serialization.
+    pub fn from_byte_array(bytes: [u8; 32]) -> Self { Musig2PartialSig(bytes) }
+
+    /// Returns the 32 byte serialization of this partial signature.
+    pub fn to_byte_array(self) -> [u8; 32] { self.0 }
+
+    /// Returns a reference to the 32 byte serialization of this partial signature.
+    pub fn as_byte_array(&self) -> &[u8; 32] { &self.0 }
+}
+
+impl_psbt_byte_array_value!(Musig2PubNonce, 66);
+impl_psbt_byte_array_value!(Musig2PartialSig, 32);
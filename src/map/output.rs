@@ -1,10 +1,13 @@
 // SPDX-License-Identifier: CC0-1.0
 
+use core::fmt;
+
 use bitcoin::bip32::KeySource;
-use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::secp256k1::{self, XOnlyPublicKey};
 use bitcoin::taproot::{TapLeafHash, TapTree};
-use bitcoin::ScriptBuf;
+use bitcoin::{Script, ScriptBuf};
 
+use super::debug::{Entries, Plain, Proprietary, Source, Unknown};
 use super::Map;
 use crate::prelude::*;
 use crate::{raw, Error};
@@ -21,12 +24,51 @@ const PSBT_OUT_TAP_INTERNAL_KEY: u8 = 0x05;
 const PSBT_OUT_TAP_TREE: u8 = 0x06;
 /// Type: Taproot Key BIP 32 Derivation Path PSBT_OUT_TAP_BIP32_DERIVATION = 0x07
 const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
+/// Type: MuSig2 Participant Public Keys PSBT_OUT_MUSIG2_PARTICIPANT_PUBKEYS = 0x08
+const PSBT_OUT_MUSIG2_PARTICIPANT_PUBKEYS: u8 = 0x08;
+/// Type: Silent Payment v0 Info PSBT_OUT_SP_V0_INFO = 0x09
+const PSBT_OUT_SP_V0_INFO: u8 = 0x09;
+/// Type: Silent Payment v0 Label PSBT_OUT_SP_V0_LABEL = 0x0a
+const PSBT_OUT_SP_V0_LABEL: u8 = 0x0a;
 /// Type: Proprietary Use Type PSBT_IN_PROPRIETARY = 0xFC
 const PSBT_OUT_PROPRIETARY: u8 = 0xFC;
 
+/// Returns the name of the field with key type `type_value`, `"unknown"` for unknown key types.
+pub(super) fn field_name(type_value: u8) -> &'static str {
+    match type_value {
+        PSBT_OUT_REDEEM_SCRIPT => "redeem_script",
+        PSBT_OUT_WITNESS_SCRIPT => "witness_script",
+        PSBT_OUT_BIP32_DERIVATION => "bip32_derivation",
+        PSBT_OUT_TAP_INTERNAL_KEY => "tap_internal_key",
+        PSBT_OUT_TAP_TREE => "tap_tree",
+        PSBT_OUT_TAP_BIP32_DERIVATION => "tap_key_origins",
+        PSBT_OUT_MUSIG2_PARTICIPANT_PUBKEYS => "musig2_participant_pubkeys",
+        PSBT_OUT_SP_V0_INFO => "sp_v0_info",
+        PSBT_OUT_SP_V0_LABEL => "sp_v0_label",
+        PSBT_OUT_PROPRIETARY => "proprietary",
+        _ => "unknown",
+    }
+}
+
+/// Returns the key type of the first field of `output` that is not allowed in an output with
+/// script pubkey `spk`, i.e., a Taproot field in a non-Taproot output or a redeem or witness script
+/// in a Taproot output.
+pub(super) fn disallowed_field(output: &Output, spk: &Script) -> Option<u8> {
+    const TAPROOT_ONLY: &[u8] = &[
+        PSBT_OUT_TAP_INTERNAL_KEY,
+        PSBT_OUT_TAP_TREE,
+        PSBT_OUT_TAP_BIP32_DERIVATION,
+        PSBT_OUT_MUSIG2_PARTICIPANT_PUBKEYS,
+    ];
+    const NOT_TAPROOT: &[u8] = &[PSBT_OUT_REDEEM_SCRIPT, PSBT_OUT_WITNESS_SCRIPT];
+
+    let disallowed = if spk.is_p2tr() { NOT_TAPROOT } else { TAPROOT_ONLY };
+    output.get_pairs().into_iter().map(|pair| pair.key.type_value).find(|t| disallowed.contains(t))
+}
+
 /// A key-value map for an output of the corresponding index in the unsigned
 /// transaction.
-#[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
 pub struct Output {
@@ -45,6 +87,17 @@ pub struct Output {
     /// Map of tap root x only keys to origin info and leaf hashes contained in it.
     #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
     pub tap_key_origins: BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>,
+    /// Map of MuSig2 aggregate public keys to the public keys of the participants.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq"))]
+    pub musig2_participant_pubkeys: BTreeMap<secp256k1::PublicKey, Vec<secp256k1::PublicKey>>,
+    /// The silent payment address this output pays to, as its scan and spend keys.
+    ///
+    /// BIP 375 is specified for version 2 PSBTs where the output script can be omitted until it
+    /// is computed. Since a version 0 PSBT always has an output script in the unsigned
+    /// transaction, it is up to the creator to replace it once the script is known.
+    pub sp_v0_info: Option<(secp256k1::PublicKey, secp256k1::PublicKey)>,
+    /// The label applied to the spend key of the silent payment address.
+    pub sp_v0_label: Option<u32>,
     /// Proprietary key-value pairs for this output.
     #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq_byte_values"))]
     pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
@@ -53,8 +106,74 @@ pub struct Output {
     pub unknown: BTreeMap<raw::Key, Vec<u8>>,
 }
 
+/// Keys and raw values are formatted as hex and key sources as `[fingerprint/path]`.
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Output")
+            .field("redeem_script", &self.redeem_script)
+            .field("witness_script", &self.witness_script)
+            .field(
+                "bip32_derivation",
+                &Entries(
+                    self.bip32_derivation.iter().map(|(pk, source)| (Plain(pk), Source(source))),
+                ),
+            )
+            .field("tap_internal_key", &self.tap_internal_key.map(Plain))
+            .field("tap_tree", &self.tap_tree)
+            .field(
+                "tap_key_origins",
+                &Entries(self.tap_key_origins.iter().map(|(pk, (leaf_hashes, source))| {
+                    (Plain(pk), (leaf_hashes.iter().map(Plain).collect::<Vec<_>>(), Source(source)))
+                })),
+            )
+            .field(
+                "musig2_participant_pubkeys",
+                &Entries(self.musig2_participant_pubkeys.iter().map(
+                    |(aggregate, participants)| {
+                        (Plain(aggregate), participants.iter().map(Plain).collect::<Vec<_>>())
+                    },
+                )),
+            )
+            .field(
+                "sp_v0_info",
+                &self.sp_v0_info.as_ref().map(|(scan, spend)| (Plain(scan), Plain(spend))),
+            )
+            .field("sp_v0_label", &self.sp_v0_label)
+            .field("proprietary", &Proprietary(&self.proprietary))
+            .field("unknown", &Unknown(&self.unknown))
+            .finish()
+    }
+}
+
 impl Output {
-    pub(super) fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), Error> {
+    /// Returns true if this output pays to a segwit output locked by `spk` (the corresponding
+    /// unsigned transaction output's script).
+    ///
+    /// Wrapped segwit outputs (P2SH-P2WPKH and P2SH-P2WSH) are segwit even though `spk` is a
+    /// P2SH, these are detected using the `redeem_script` of this output.
+    pub fn is_segwit(&self, spk: &Script) -> bool {
+        super::is_segwit(spk, self.redeem_script.as_ref())
+    }
+
+    /// Returns true if this output is an `OP_RETURN` data carrier output, `spk` is the
+    /// corresponding unsigned transaction output's script.
+    pub fn is_op_return(&self, spk: &Script) -> bool { spk.is_op_return() }
+
+    /// Records that `key`, derived as described by `source`, is used in the leaf `leaf_hash`.
+    ///
+    /// Pass `None` for `leaf_hash` to record the key without a leaf, e.g. for the internal key.
+    /// If `key` already has an origin the leaf hash is added to its set of leaf hashes (unless
+    /// already present) and the existing key source is kept.
+    pub fn add_tap_key_origin(
+        &mut self,
+        key: XOnlyPublicKey,
+        leaf_hash: Option<TapLeafHash>,
+        source: KeySource,
+    ) {
+        super::add_tap_key_origin(&mut self.tap_key_origins, key, leaf_hash, source)
+    }
+
+    pub(crate) fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), Error> {
         let raw::Pair { key: raw_key, value: raw_value } = pair;
 
         match raw_key.type_value {
@@ -73,6 +192,21 @@ impl Output {
                     self.bip32_derivation <= <raw_key: bitcoin::secp256k1::PublicKey>|<raw_value: KeySource>
                 }
             }
+            PSBT_OUT_MUSIG2_PARTICIPANT_PUBKEYS => {
+                impl_psbt_insert_pair! {
+                    self.musig2_participant_pubkeys <= <raw_key: secp256k1::PublicKey>|<raw_value: Vec<secp256k1::PublicKey>>
+                }
+            }
+            PSBT_OUT_SP_V0_INFO => {
+                impl_psbt_insert_pair! {
+                    self.sp_v0_info <= <raw_key: _>|<raw_value: (secp256k1::PublicKey, secp256k1::PublicKey)>
+                }
+            }
+            PSBT_OUT_SP_V0_LABEL => {
+                impl_psbt_insert_pair! {
+                    self.sp_v0_label <= <raw_key: _>|<raw_value: u32>
+                }
+            }
             PSBT_OUT_PROPRIETARY => {
                 let key = raw::ProprietaryKey::try_from(raw_key.clone())?;
                 match self.proprietary.entry(key) {
@@ -108,17 +242,58 @@ impl Output {
         Ok(())
     }
 
+    /// Returns true if every key-value pair of this output is also in `other`.
+    ///
+    /// Combining this output into `other` then changes nothing and can not conflict.
+    pub(crate) fn is_contained_in(&self, other: &Output) -> bool {
+        use super::{is_subfield as field, is_submap as map};
+
+        // Destructured so that a new field can not be forgotten.
+        let Output {
+            redeem_script,
+            witness_script,
+            bip32_derivation,
+            tap_internal_key,
+            tap_tree,
+            tap_key_origins,
+            musig2_participant_pubkeys,
+            sp_v0_info,
+            sp_v0_label,
+            proprietary,
+            unknown,
+        } = self;
+
+        field(redeem_script, &other.redeem_script)
+            && field(witness_script, &other.witness_script)
+            && map(bip32_derivation, &other.bip32_derivation)
+            && field(tap_internal_key, &other.tap_internal_key)
+            && field(tap_tree, &other.tap_tree)
+            && map(tap_key_origins, &other.tap_key_origins)
+            && map(musig2_participant_pubkeys, &other.musig2_participant_pubkeys)
+            && field(sp_v0_info, &other.sp_v0_info)
+            && field(sp_v0_label, &other.sp_v0_label)
+            && map(proprietary, &other.proprietary)
+            && map(unknown, &other.unknown)
+    }
+
     /// Combines this [`Output`] with `other` `Output` (as described by BIP 174).
-    pub fn combine(&mut self, other: Self) {
-        self.bip32_derivation.extend(other.bip32_derivation);
-        self.proprietary.extend(other.proprietary);
-        self.unknown.extend(other.unknown);
-        self.tap_key_origins.extend(other.tap_key_origins);
+    pub fn combine(&mut self, mut other: Self) {
+        if other.is_contained_in(self) {
+            return;
+        }
+
+        super::append(&mut self.bip32_derivation, &mut other.bip32_derivation);
+        super::append(&mut self.proprietary, &mut other.proprietary);
+        super::append(&mut self.unknown, &mut other.unknown);
+        super::append(&mut self.tap_key_origins, &mut other.tap_key_origins);
+        super::append(&mut self.musig2_participant_pubkeys, &mut other.musig2_participant_pubkeys);
 
         combine!(redeem_script, self, other);
         combine!(witness_script, self, other);
         combine!(tap_internal_key, self, other);
         combine!(tap_tree, self, other);
+        combine!(sp_v0_info, self, other);
+        combine!(sp_v0_label, self, other);
     }
 }
 
@@ -150,6 +325,18 @@ impl Map for Output {
             rv.push_map(self.tap_key_origins, PSBT_OUT_TAP_BIP32_DERIVATION)
         }
 
+        impl_psbt_get_pair! {
+            rv.push_map(self.musig2_participant_pubkeys, PSBT_OUT_MUSIG2_PARTICIPANT_PUBKEYS)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push(self.sp_v0_info, PSBT_OUT_SP_V0_INFO)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push(self.sp_v0_label, PSBT_OUT_SP_V0_LABEL)
+        }
+
         for (key, value) in self.proprietary.iter() {
             rv.push(raw::Pair { key: key.to_key(), value: value.clone() });
         }
@@ -162,4 +349,4 @@ impl Map for Output {
     }
 }
 
-impl_psbtmap_ser_de_serialize!(Output);
+impl_psbtmap_ser_de_serialize!(Output, crate::MapLocation::Output(0));
@@ -0,0 +1,22 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Values of the silent payment PSBT fields defined in BIP 375.
+
+/// A BIP 374 DLEQ proof that an ECDH share was computed correctly.
+///
+/// The proof is stored as its 64 byte serialization, it is not checked to be valid.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DleqProof([u8; 64]);
+
+impl DleqProof {
+    /// Creates a DLEQ proof from its 64 byte serialization.
+    pub fn from_byte_array(bytes: [u8; 64]) -> Self { DleqProof(bytes) }
+
+    /// Returns the 64 byte serialization of this DLEQ proof.
+    pub fn to_byte_array(self) -> [u8; 64] { self.0 }
+
+    /// Returns a reference to the 64 byte serialization of this DLEQ proof.
+    pub fn as_byte_array(&self) -> &[u8; 64] { &self.0 }
+}
+
+impl_psbt_byte_array_value!(DleqProof, 64);
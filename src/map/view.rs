@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: CC0-1.0
+
+use bitcoin::{Amount, OutPoint, Script, Sequence, TxIn, TxOut};
+
+use crate::{Error, Input, Output, Psbt};
+
+/// A PSBT input along with the transaction input it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputRef<'a> {
+    index: usize,
+    input: &'a Input,
+    txin: &'a TxIn,
+}
+
+impl<'a> InputRef<'a> {
+    /// Returns the index of the input.
+    pub fn index(&self) -> usize { self.index }
+
+    /// Returns the PSBT input.
+    pub fn input(&self) -> &'a Input { self.input }
+
+    /// Returns the input of the unsigned transaction.
+    pub fn txin(&self) -> &'a TxIn { self.txin }
+
+    /// Returns the outpoint spent by the input.
+    pub fn previous_output(&self) -> OutPoint { self.txin.previous_output }
+
+    /// Returns the sequence number of the input.
+    pub fn sequence(&self) -> Sequence { self.txin.sequence }
+
+    /// Returns the output spent by the input, taken from the witness UTXO if present and the
+    /// non-witness UTXO otherwise.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::MissingUtxo`] if the input has neither UTXO, [`Error::PsbtUtxoOutOfbounds`] if the
+    /// non-witness UTXO does not have the spent output.
+    pub fn funding_utxo(&self) -> Result<&'a TxOut, Error> {
+        match (&self.input.witness_utxo, &self.input.non_witness_utxo) {
+            (Some(witness_utxo), _) => Ok(witness_utxo),
+            (None, Some(non_witness_utxo)) => {
+                let vout = self.txin.previous_output.vout as usize;
+                non_witness_utxo.output.get(vout).ok_or(Error::PsbtUtxoOutOfbounds)
+            }
+            (None, None) => Err(Error::MissingUtxo),
+        }
+    }
+}
+
+/// A PSBT output along with the transaction output it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputRef<'a> {
+    index: usize,
+    output: &'a Output,
+    txout: &'a TxOut,
+}
+
+impl<'a> OutputRef<'a> {
+    /// Returns the index of the output.
+    pub fn index(&self) -> usize { self.index }
+
+    /// Returns the PSBT output.
+    pub fn output(&self) -> &'a Output { self.output }
+
+    /// Returns the output of the unsigned transaction.
+    pub fn txout(&self) -> &'a TxOut { self.txout }
+
+    /// Returns the amount of the output.
+    pub fn value(&self) -> Amount { self.txout.value }
+
+    /// Returns the script pubkey of the output.
+    pub fn script_pubkey(&self) -> &'a Script { &self.txout.script_pubkey }
+}
+
+impl Psbt {
+    /// Returns an iterator over the inputs, each along with its transaction input.
+    ///
+    /// # Panics
+    ///
+    /// If the length of transaction inputs is not equal to the length of PSBT inputs.
+    pub fn input_pairs(&self) -> impl ExactSizeIterator<Item = InputRef<'_>> {
+        assert_eq!(self.inputs.len(), self.unsigned_tx.input.len());
+        self.inputs
+            .iter()
+            .zip(&self.unsigned_tx.input)
+            .enumerate()
+            .map(|(index, (input, txin))| InputRef { index, input, txin })
+    }
+
+    /// Returns an iterator over the outputs, each along with its transaction output.
+    ///
+    /// # Panics
+    ///
+    /// If the length of transaction outputs is not equal to the length of PSBT outputs.
+    pub fn output_pairs(&self) -> impl ExactSizeIterator<Item = OutputRef<'_>> {
+        assert_eq!(self.outputs.len(), self.unsigned_tx.output.len());
+        self.outputs
+            .iter()
+            .zip(&self.unsigned_tx.output)
+            .enumerate()
+            .map(|(index, (output, txout))| OutputRef { index, output, txout })
+    }
+
+    /// Returns the input at `index` along with its transaction input, `None` if out of bounds.
+    pub fn input_pair(&self, index: usize) -> Option<InputRef<'_>> {
+        match (self.inputs.get(index), self.unsigned_tx.input.get(index)) {
+            (Some(input), Some(txin)) => Some(InputRef { index, input, txin }),
+            _ => None,
+        }
+    }
+
+    /// Returns the output at `index` along with its transaction output, `None` if out of bounds.
+    pub fn output_pair(&self, index: usize) -> Option<OutputRef<'_>> {
+        match (self.outputs.get(index), self.unsigned_tx.output.get(index)) {
+            (Some(output), Some(txout)) => Some(OutputRef { index, output, txout }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, transaction, ScriptBuf, Transaction, Txid};
+
+    use super::*;
+
+    #[test]
+    fn input_and_output_pairs() {
+        let previous = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut::NULL, TxOut { value: Amount::from_sat(5), ..TxOut::NULL }],
+        };
+        let txins = [
+            TxIn {
+                previous_output: OutPoint::new(previous.compute_txid(), 1),
+                ..Default::default()
+            },
+            TxIn { previous_output: OutPoint::new(Txid::all_zeros(), 2), ..Default::default() },
+        ];
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: txins.to_vec(),
+            output: vec![TxOut { value: Amount::from_sat(3), script_pubkey: ScriptBuf::new() }],
+        })
+        .unwrap();
+        psbt.inputs[0].non_witness_utxo = Some(previous.clone());
+
+        let inputs = psbt.input_pairs().collect::<Vec<_>>();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[1].index(), 1);
+        assert_eq!(inputs[1].previous_output(), txins[1].previous_output);
+        assert_eq!(inputs[0].funding_utxo().unwrap(), &previous.output[1]);
+        assert!(matches!(inputs[1].funding_utxo(), Err(Error::MissingUtxo)));
+        assert_eq!(psbt.input_pair(1), Some(inputs[1]));
+        assert_eq!(psbt.input_pair(2), None);
+
+        let output = psbt.output_pair(0).unwrap();
+        assert_eq!(output.value(), Amount::from_sat(3));
+        assert_eq!(output.output(), &psbt.outputs[0]);
+        assert_eq!(psbt.output_pairs().map(|output| output.index()).collect::<Vec<_>>(), [0]);
+    }
+}
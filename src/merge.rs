@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Combining PSBTs with caller defined resolution of the cases BIP 174 leaves open.
+//!
+//! BIP 174 lets the combiner pick arbitrarily when two copies of a PSBT disagree. [`Psbt::combine`]
+//! lets the PSBT being combined in win on unknown and proprietary values, prefers a witness UTXO
+//! and requires equal unsigned transactions. Coordinators that need other choices pass a resolver
+//! to [`Psbt::combine_resolving`].
+
+use core::fmt;
+
+use crate::map::{self, field_name};
+use crate::prelude::*;
+use crate::{raw, CombineError, CombinePolicy, Input, MapLocation, Psbt};
+
+impl Psbt {
+    /// Combines this [`Psbt`] with `other` using `policy`, asking `resolve` how to settle each
+    /// [`MergeConflict`].
+    ///
+    /// `resolve` is called for every conflict before anything is merged, so this PSBT is unchanged
+    /// if it rejects one. All other fields are combined as in [`Psbt::combine_with_policy`].
+    ///
+    /// ```
+    /// # use psbt_v0::{CombinePolicy, Psbt, Resolution};
+    /// fn combine_keeping_ours(ours: &mut Psbt, theirs: Psbt) -> Result<(), psbt_v0::CombineError> {
+    ///     ours.combine_resolving(theirs, CombinePolicy::Bip174, |_| Resolution::PreferSelf)
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the unsigned transactions have different txids, the key sources of an xpub are
+    /// inconsistent, or `resolve` returns [`Resolution::Reject`].
+    pub fn combine_resolving<F>(
+        &mut self,
+        mut other: Psbt,
+        policy: CombinePolicy,
+        mut resolve: F,
+    ) -> Result<(), CombineError>
+    where
+        F: FnMut(&MergeConflict) -> Resolution,
+    {
+        self.validate_combine_source(&other)?;
+        let mut resolve = |conflict: MergeConflict| match resolve(&conflict) {
+            Resolution::Reject => Err(CombineError::Unresolved(Box::new(conflict))),
+            resolution => Ok(resolution),
+        };
+
+        let take_their_tx = self.unsigned_tx != other.unsigned_tx
+            && match resolve(MergeConflict::UnsignedTx)? {
+                Resolution::PreferOther => true,
+                _ => {
+                    other.unsigned_tx = self.unsigned_tx.clone();
+                    false
+                }
+            };
+
+        let mut take_their_utxos = vec![];
+        for (input_index, (ours, theirs)) in self.inputs.iter().zip(&mut other.inputs).enumerate() {
+            match (utxo_kind(ours), utxo_kind(theirs)) {
+                (Some(ours), Some(theirs)) if ours != theirs => {}
+                _ => continue,
+            }
+            match resolve(MergeConflict::Utxo { input_index })? {
+                Resolution::PreferOther => take_their_utxos.push(input_index),
+                _ => {
+                    theirs.witness_utxo = None;
+                    theirs.non_witness_utxo = None;
+                }
+            }
+        }
+
+        for (location, key, ours, theirs) in value_conflicts(self, &other) {
+            // The other PSBT's value is kept by the merge unless it is removed.
+            if let Resolution::PreferSelf =
+                resolve(MergeConflict::Value { location, key: key.clone(), ours, theirs })?
+            {
+                remove_value(&mut other, location, key);
+            }
+        }
+
+        if take_their_tx {
+            self.unsigned_tx = other.unsigned_tx.clone();
+        }
+        for index in take_their_utxos {
+            self.inputs[index].witness_utxo = None;
+            self.inputs[index].non_witness_utxo = None;
+        }
+        self.try_combine_with_policy(other, policy)
+    }
+}
+
+/// Returns `Some(true)` if `input` only has a witness UTXO, `Some(false)` if it only has a
+/// non-witness UTXO.
+fn utxo_kind(input: &Input) -> Option<bool> {
+    match (&input.witness_utxo, &input.non_witness_utxo) {
+        (Some(_), None) => Some(true),
+        (None, Some(_)) => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the unknown and proprietary keys with different values in `ours` and `theirs`.
+fn value_conflicts(ours: &Psbt, theirs: &Psbt) -> Vec<(MapLocation, raw::Key, Vec<u8>, Vec<u8>)> {
+    let global =
+        map::conflicting_pairs(ours, theirs).into_iter().map(|pair| (MapLocation::Global, pair));
+    let inputs = ours.inputs.iter().zip(&theirs.inputs).enumerate().flat_map(|(index, (a, b))| {
+        map::conflicting_pairs(a, b).into_iter().map(move |pair| (MapLocation::Input(index), pair))
+    });
+    let outputs =
+        ours.outputs.iter().zip(&theirs.outputs).enumerate().flat_map(|(index, (a, b))| {
+            map::conflicting_pairs(a, b)
+                .into_iter()
+                .map(move |pair| (MapLocation::Output(index), pair))
+        });
+
+    global
+        .chain(inputs)
+        .chain(outputs)
+        .filter(|(location, (key, _, _))| {
+            matches!(field_name(*location, key.type_value), "unknown" | "proprietary")
+        })
+        .map(|(location, (key, ours, theirs))| (location, key, ours, theirs))
+        .collect()
+}
+
+/// Removes the unknown or proprietary `key` from the map at `location`.
+fn remove_value(psbt: &mut Psbt, location: MapLocation, key: raw::Key) {
+    let (unknown, proprietary) = match location {
+        MapLocation::Global => (&mut psbt.unknown, &mut psbt.proprietary),
+        MapLocation::Input(index) => {
+            let input = &mut psbt.inputs[index];
+            (&mut input.unknown, &mut input.proprietary)
+        }
+        MapLocation::Output(index) => {
+            let output = &mut psbt.outputs[index];
+            (&mut output.unknown, &mut output.proprietary)
+        }
+    };
+    match raw::ProprietaryKey::try_from(key.clone()) {
+        Ok(key) => proprietary.remove(&key),
+        Err(_) => unknown.remove(&key),
+    };
+}
+
+/// How [`Psbt::combine_resolving`] settles a [`MergeConflict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Resolution {
+    /// Fail the combine with [`CombineError::Unresolved`].
+    Reject,
+    /// Keep the data of the PSBT being combined into.
+    PreferSelf,
+    /// Take the data of the PSBT being combined in.
+    PreferOther,
+}
+
+/// Two copies of a PSBT disagree in a way BIP 174 leaves to the combiner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MergeConflict {
+    /// An unknown or proprietary key has different values.
+    Value {
+        /// The map the key is in.
+        location: MapLocation,
+        /// The conflicting key, proprietary keys have key type `0xFC`.
+        key: raw::Key,
+        /// The value in the PSBT being combined into.
+        ours: Vec<u8>,
+        /// The value in the PSBT being combined in.
+        theirs: Vec<u8>,
+    },
+    /// One copy of the input only has a witness UTXO, the other only a non-witness UTXO.
+    Utxo {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The unsigned transactions have the same txid but are not equal, e.g. one has witnesses.
+    UnsignedTx,
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MergeConflict::*;
+
+        match *self {
+            Value { location, ref key, .. } => write!(
+                f,
+                "conflicting {} value for key {} in the {} map",
+                field_name(location, key.type_value),
+                key,
+                location
+            ),
+            Utxo { input_index } =>
+                write!(f, "input {} has a witness UTXO in one copy only", input_index),
+            UnsignedTx => f.write_str("the unsigned transactions differ in non-txid data"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{absolute, transaction, Amount, Transaction, TxIn, TxOut, Witness};
+
+    use super::*;
+
+    #[test]
+    fn combine_resolving() {
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(1_000),
+                script_pubkey: Default::default(),
+            }],
+        };
+        let mut ours = Psbt::from_unsigned_tx(tx.clone()).unwrap();
+        ours.inputs[0].non_witness_utxo = Some(tx.clone());
+        ours.outputs[0].unknown.insert(raw::Key::new(0xf0), vec![1]);
+
+        let mut theirs = ours.clone();
+        theirs.inputs[0].non_witness_utxo = None;
+        theirs.inputs[0].witness_utxo = Some(tx.output[0].clone());
+        theirs.outputs[0].unknown.insert(raw::Key::new(0xf0), vec![2]);
+        theirs.unsigned_tx.input[0].witness = Witness::from_slice(&[[0u8]]);
+
+        let mut conflicts = vec![];
+        let mut combined = ours.clone();
+        combined
+            .combine_resolving(theirs.clone(), CombinePolicy::Bip174, |conflict| {
+                conflicts.push(conflict.clone());
+                Resolution::PreferSelf
+            })
+            .unwrap();
+        assert_eq!(conflicts.len(), 3);
+        assert_eq!(conflicts[0], MergeConflict::UnsignedTx);
+        assert_eq!(conflicts[1], MergeConflict::Utxo { input_index: 0 });
+        assert!(matches!(
+            conflicts[2],
+            MergeConflict::Value { location: MapLocation::Output(0), .. }
+        ));
+        assert_eq!(combined, ours);
+
+        let mut combined = ours.clone();
+        combined
+            .combine_resolving(theirs.clone(), CombinePolicy::Bip174, |_| Resolution::PreferOther)
+            .unwrap();
+        assert_eq!(combined, theirs);
+
+        let mut combined = ours.clone();
+        let err = combined
+            .combine_resolving(theirs, CombinePolicy::Bip174, |conflict| match conflict {
+                MergeConflict::Value { .. } => Resolution::Reject,
+                _ => Resolution::PreferOther,
+            })
+            .unwrap_err();
+        assert!(matches!(err, CombineError::Unresolved(_)));
+        assert_eq!(combined, ours);
+    }
+}
@@ -0,0 +1,547 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Preparing MuSig2 signing sessions with the BIP 373 fields.
+//!
+//! Before any participant can make a partial signature every participant needs the public nonces
+//! of all the others. The PSBT carries these through rounds of combining: each participant
+//! generates a nonce pair, keeps the secret nonce and stores the public nonce in the input, the
+//! coordinator combines the PSBTs and, once every nonce is present, the nonces are aggregated.
+//!
+//! Key and nonce aggregation and nonce generation follow BIP 327. Creating partial signatures is
+//! left to a MuSig2 implementation.
+
+use core::fmt;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{
+    self, constants, PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification,
+};
+use bitcoin::TapLeafHash;
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::{IndexOutOfBoundsError, Input, Musig2Key, Musig2PubNonce, Psbt};
+
+impl Psbt {
+    /// Checks the MuSig2 participants and nonces of the input at `input_index`.
+    ///
+    /// - Every aggregate public key is the BIP 327 aggregate of its participants.
+    /// - Every aggregate public key is the Taproot internal key or in a leaf script of the input.
+    ///   Aggregate keys further derived with BIP 328, whose derivation is not checked here, are
+    ///   reported as unused.
+    /// - Every public nonce is from a participant of its aggregate public key and is valid.
+    pub fn validate_musig2_participants<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        input_index: usize,
+    ) -> Result<(), Musig2Error> {
+        let input = self.checked_input(input_index)?;
+        for (aggregate, participants) in &input.musig2_participant_pubkeys {
+            if aggregate_key(secp, participants)? != *aggregate {
+                return Err(Musig2Error::AggregateMismatch { aggregate: *aggregate });
+            }
+            if !is_used(input, aggregate) {
+                return Err(Musig2Error::AggregateNotUsed { aggregate: *aggregate });
+            }
+        }
+        for (&(participant, aggregate, _), nonce) in &input.musig2_pub_nonces {
+            participants(input, participant, aggregate)?;
+            nonce_points(nonce).ok_or(Musig2Error::InvalidNonce { participant })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the participants of `aggregate` that have not yet stored a public nonce in the
+    /// input at `input_index` for the leaf `leaf_hash`, or the key path if `None`.
+    pub fn missing_musig2_nonces(
+        &self,
+        input_index: usize,
+        aggregate: PublicKey,
+        leaf_hash: Option<TapLeafHash>,
+    ) -> Result<Vec<PublicKey>, Musig2Error> {
+        let input = self.checked_input(input_index)?;
+        let participants = input
+            .musig2_participant_pubkeys
+            .get(&aggregate)
+            .ok_or(Musig2Error::UnknownAggregate { aggregate })?;
+        let mut missing = participants
+            .iter()
+            .filter(|pk| !input.musig2_pub_nonces.contains_key(&(**pk, aggregate, leaf_hash)))
+            .copied()
+            .collect::<Vec<_>>();
+        missing.dedup();
+        Ok(missing)
+    }
+
+    /// Generates a nonce pair to sign the input at `input_index`, for the participant, aggregate
+    /// public key and leaf hash, `None` for the key path, of `key`.
+    ///
+    /// The public nonce is stored in the input, the secret nonce is returned and must be kept
+    /// until the participant signs. `rand` must be 32 fresh random bytes and is, following BIP
+    /// 327, hashed with the secret key of the participant if given. Reusing a nonce to sign a
+    /// different message leaks the secret key, which is why the secret nonce is not `Clone` and a
+    /// stored public nonce is never replaced.
+    pub fn generate_musig2_nonce<C: Signing>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        input_index: usize,
+        key: Musig2Key,
+        secret_key: Option<&SecretKey>,
+        rand: [u8; 32],
+    ) -> Result<Musig2SecNonce, Musig2Error> {
+        let (participant, aggregate, _) = key;
+        let input = self.checked_input(input_index)?;
+        participants(input, participant, aggregate)?;
+        if input.musig2_pub_nonces.contains_key(&key) {
+            return Err(Musig2Error::NonceExists { participant });
+        }
+
+        let rand = match secret_key {
+            Some(sk) => {
+                let aux = tagged_hash("MuSig/aux", &[&rand]);
+                let mut xored = sk.secret_bytes();
+                xored.iter_mut().zip(&aux).for_each(|(byte, aux)| *byte ^= aux);
+                xored
+            }
+            None => rand,
+        };
+        let aggregate_x = aggregate.x_only_public_key().0.serialize();
+        let nonce_key = |i: u8| {
+            let hash = tagged_hash(
+                "MuSig/nonce",
+                &[
+                    &rand,
+                    &[33],
+                    &participant.serialize(),
+                    &[32],
+                    &aggregate_x,
+                    // No message, and no extra input.
+                    &[0],
+                    &[0; 4],
+                    &[i],
+                ],
+            );
+            SecretKey::from_slice(&scalar_mod_n(hash).to_be_bytes())
+        };
+        let (k1, k2) = (nonce_key(0)?, nonce_key(1)?);
+
+        let mut pub_nonce = [0; 66];
+        pub_nonce[..33].copy_from_slice(&k1.public_key(secp).serialize());
+        pub_nonce[33..].copy_from_slice(&k2.public_key(secp).serialize());
+        self.inputs[input_index]
+            .musig2_pub_nonces
+            .insert(key, Musig2PubNonce::from_byte_array(pub_nonce));
+        Ok(Musig2SecNonce { k1, k2, participant })
+    }
+
+    /// Aggregates the public nonces of the participants of `aggregate` in the input at
+    /// `input_index`, for the leaf `leaf_hash` or the key path if `None`.
+    ///
+    /// Call this on the PSBT combined from those of every participant.
+    pub fn aggregate_musig2_nonces(
+        &self,
+        input_index: usize,
+        aggregate: PublicKey,
+        leaf_hash: Option<TapLeafHash>,
+    ) -> Result<Musig2AggNonce, Musig2Error> {
+        let input = self.checked_input(input_index)?;
+        let participants = input
+            .musig2_participant_pubkeys
+            .get(&aggregate)
+            .ok_or(Musig2Error::UnknownAggregate { aggregate })?;
+
+        let mut points = (vec![], vec![]);
+        for &participant in participants {
+            let nonce = input
+                .musig2_pub_nonces
+                .get(&(participant, aggregate, leaf_hash))
+                .ok_or(Musig2Error::MissingNonce { participant })?;
+            let (r1, r2) = nonce_points(nonce).ok_or(Musig2Error::InvalidNonce { participant })?;
+            points.0.push(r1);
+            points.1.push(r2);
+        }
+
+        let mut agg_nonce = [0; 66];
+        for (points, bytes) in [points.0, points.1].iter().zip(agg_nonce.chunks_mut(33)) {
+            let points = points.iter().collect::<Vec<_>>();
+            // The sum is encoded as 33 zero bytes if it is the point at infinity.
+            if let Ok(sum) = PublicKey::combine_keys(&points) {
+                bytes.copy_from_slice(&sum.serialize());
+            }
+        }
+        Ok(Musig2AggNonce(agg_nonce))
+    }
+}
+
+/// Returns the BIP 327 aggregate of the public keys `participants`, without tweaks.
+pub fn musig2_aggregate_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    participants: &[PublicKey],
+) -> Result<PublicKey, Musig2Error> {
+    aggregate_key(secp, participants)
+}
+
+/// Computes KeyAgg of BIP 327.
+fn aggregate_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    participants: &[PublicKey],
+) -> Result<PublicKey, Musig2Error> {
+    let serialized = participants.iter().map(PublicKey::serialize).collect::<Vec<_>>();
+    let list = tagged_hash("KeyAgg list", &serialized.iter().map(|pk| &pk[..]).collect::<Vec<_>>());
+    let second = participants.iter().find(|pk| Some(*pk) != participants.first());
+
+    let mut terms = vec![];
+    for (pk, serialized) in participants.iter().zip(&serialized) {
+        if Some(pk) == second {
+            terms.push(*pk);
+        } else {
+            let coefficient = tagged_hash("KeyAgg coefficient", &[&list, serialized]);
+            terms.push(pk.mul_tweak(secp, &scalar_mod_n(coefficient))?);
+        }
+    }
+    Ok(PublicKey::combine_keys(&terms.iter().collect::<Vec<_>>())?)
+}
+
+/// Returns the participants of `aggregate` in `input`, checking that `participant` is one.
+fn participants(
+    input: &Input,
+    participant: PublicKey,
+    aggregate: PublicKey,
+) -> Result<&[PublicKey], Musig2Error> {
+    let participants = input
+        .musig2_participant_pubkeys
+        .get(&aggregate)
+        .ok_or(Musig2Error::UnknownAggregate { aggregate })?;
+    if !participants.contains(&participant) {
+        return Err(Musig2Error::UnknownParticipant { participant });
+    }
+    Ok(participants)
+}
+
+/// Returns true if `aggregate` is the internal key of `input` or in one of its leaf scripts.
+fn is_used(input: &Input, aggregate: &PublicKey) -> bool {
+    let x_only = aggregate.x_only_public_key().0;
+    input.tap_internal_key == Some(x_only)
+        || input.tap_scripts.values().any(|(script, _)| {
+            script.instructions().any(|instruction| {
+                matches!(instruction, Ok(bitcoin::script::Instruction::PushBytes(push))
+                    if push.as_bytes() == x_only.serialize())
+            })
+        })
+}
+
+/// Returns the two points of a public nonce, or `None` if they are not valid.
+fn nonce_points(nonce: &Musig2PubNonce) -> Option<(PublicKey, PublicKey)> {
+    let bytes = nonce.as_byte_array();
+    let r1 = PublicKey::from_slice(&bytes[..33]).ok()?;
+    let r2 = PublicKey::from_slice(&bytes[33..]).ok()?;
+    Some((r1, r2))
+}
+
+/// Computes the BIP 340 tagged hash of the concatenation of `data`.
+pub(crate) fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Interprets `bytes` as a big endian integer modulo the curve order.
+fn scalar_mod_n(bytes: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(bytes).unwrap_or_else(|_| {
+        // The integer is less than twice the order, subtract it once.
+        let mut reduced = [0; 32];
+        let mut borrow = 0;
+        for i in (0..32).rev() {
+            let difference = i16::from(bytes[i]) - i16::from(constants::CURVE_ORDER[i]) - borrow;
+            reduced[i] = difference.rem_euclid(256) as u8;
+            borrow = i16::from(difference < 0);
+        }
+        Scalar::from_be_bytes(reduced).expect("reduced modulo the order")
+    })
+}
+
+/// The secret nonce of a MuSig2 participant, see [`Psbt::generate_musig2_nonce`].
+///
+/// Deliberately neither `Clone` nor `Copy`, a secret nonce must be used for one signature only.
+pub struct Musig2SecNonce {
+    k1: SecretKey,
+    k2: SecretKey,
+    participant: PublicKey,
+}
+
+impl Musig2SecNonce {
+    /// Returns the public key of the participant the nonce is for.
+    pub fn participant(&self) -> PublicKey { self.participant }
+
+    /// Returns the 97 byte BIP 327 serialization of the secret nonce, consuming it.
+    pub fn into_byte_array(self) -> [u8; 97] {
+        let mut bytes = [0; 97];
+        bytes[..32].copy_from_slice(&self.k1.secret_bytes());
+        bytes[32..64].copy_from_slice(&self.k2.secret_bytes());
+        bytes[64..].copy_from_slice(&self.participant.serialize());
+        bytes
+    }
+}
+
+impl fmt::Debug for Musig2SecNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Musig2SecNonce")
+            .field("participant", &self.participant)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The aggregate of the public nonces of the participants, see [`Psbt::aggregate_musig2_nonces`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Musig2AggNonce([u8; 66]);
+
+impl Musig2AggNonce {
+    /// Returns the 66 byte BIP 327 serialization of the aggregate nonce.
+    pub fn to_byte_array(self) -> [u8; 66] { self.0 }
+
+    /// Returns a reference to the 66 byte BIP 327 serialization of the aggregate nonce.
+    pub fn as_byte_array(&self) -> &[u8; 66] { &self.0 }
+}
+
+/// Error preparing a MuSig2 signing session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Musig2Error {
+    /// The input index is out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The input has no participants for the aggregate public key.
+    UnknownAggregate {
+        /// The aggregate public key.
+        aggregate: PublicKey,
+    },
+    /// The key is not a participant of the aggregate public key.
+    UnknownParticipant {
+        /// The public key of the participant.
+        participant: PublicKey,
+    },
+    /// The aggregate public key is not the aggregate of its participants.
+    AggregateMismatch {
+        /// The aggregate public key.
+        aggregate: PublicKey,
+    },
+    /// The aggregate public key is neither the internal key nor in a leaf script.
+    AggregateNotUsed {
+        /// The aggregate public key.
+        aggregate: PublicKey,
+    },
+    /// The participant already has a public nonce in the input.
+    NonceExists {
+        /// The public key of the participant.
+        participant: PublicKey,
+    },
+    /// The participant has no public nonce in the input.
+    MissingNonce {
+        /// The public key of the participant.
+        participant: PublicKey,
+    },
+    /// The public nonce of the participant is not two valid points.
+    InvalidNonce {
+        /// The public key of the participant.
+        participant: PublicKey,
+    },
+    /// Key aggregation or nonce generation failed.
+    Secp(secp256k1::Error),
+}
+
+bitcoin_internals::impl_from_infallible!(Musig2Error);
+
+impl fmt::Display for Musig2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Musig2Error::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            UnknownAggregate { aggregate } =>
+                write!(f, "no participants for the aggregate public key {}", aggregate),
+            UnknownParticipant { participant } =>
+                write!(f, "{} is not a participant of the aggregate public key", participant),
+            AggregateMismatch { aggregate } =>
+                write!(f, "{} is not the aggregate of its participants", aggregate),
+            AggregateNotUsed { aggregate } =>
+                write!(f, "the aggregate public key {} is not used by the input", aggregate),
+            NonceExists { participant } =>
+                write!(f, "participant {} already has a public nonce", participant),
+            MissingNonce { participant } =>
+                write!(f, "participant {} has no public nonce", participant),
+            InvalidNonce { participant } =>
+                write!(f, "the public nonce of participant {} is invalid", participant),
+            Secp(ref e) => write_err!(f, "MuSig2"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Musig2Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Musig2Error::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            Secp(ref e) => Some(e),
+            UnknownAggregate { .. }
+            | UnknownParticipant { .. }
+            | AggregateMismatch { .. }
+            | AggregateNotUsed { .. }
+            | NonceExists { .. }
+            | MissingNonce { .. }
+            | InvalidNonce { .. } => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for Musig2Error {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+impl From<secp256k1::Error> for Musig2Error {
+    fn from(e: secp256k1::Error) -> Self { Self::Secp(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hex::FromHex;
+    use bitcoin::{absolute, transaction, Transaction, TxIn};
+
+    use super::*;
+
+    fn pk(hex: &str) -> PublicKey { hex.parse().unwrap() }
+
+    fn nonce(hex: &str) -> Musig2PubNonce {
+        Musig2PubNonce::from_byte_array(<[u8; 66]>::from_hex(hex).unwrap())
+    }
+
+    #[test]
+    fn bip327_vectors() {
+        let secp = Secp256k1::verification_only();
+        let keys = [
+            pk("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            pk("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            pk("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+        ];
+        let x_only = |participants: &[PublicKey]| {
+            musig2_aggregate_key(&secp, participants).unwrap().x_only_public_key().0.to_string()
+        };
+        assert_eq!(
+            x_only(&keys),
+            "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"
+        );
+        assert_eq!(
+            x_only(&[keys[2], keys[1], keys[0]]),
+            "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"
+        );
+        assert_eq!(
+            x_only(&[keys[0], keys[0], keys[0]]),
+            "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"
+        );
+
+        let participants = [keys[0], keys[1]];
+        let aggregate = musig2_aggregate_key(&secp, &participants).unwrap();
+        let mut input = Input::default();
+        input.musig2_participant_pubkeys.insert(aggregate, participants.to_vec());
+        input.musig2_pub_nonces.insert(
+            (keys[0], aggregate, None),
+            nonce("020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E66603BA47FBC1834437B3212E89A84D8425E7BF12E0245D98262268EBDCB385D50641"),
+        );
+        input.musig2_pub_nonces.insert(
+            (keys[1], aggregate, None),
+            nonce("03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833"),
+        );
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        psbt.inputs[0] = input;
+        assert_eq!(
+            psbt.aggregate_musig2_nonces(0, aggregate, None).unwrap().to_byte_array(),
+            <[u8; 66]>::from_hex("035FE1873B4F2967F52FEA4A06AD5A8ECCBE9D0FD73068012C894E2E87CCB5804B024725377345BDE0E9C33AF3C43C0A29A9249F2F2956FA8CFEB55C8573D0262DC8").unwrap()
+        );
+    }
+
+    #[test]
+    fn nonce_exchange() {
+        let secp = Secp256k1::new();
+        let secret_keys =
+            [SecretKey::from_slice(&[1; 32]).unwrap(), SecretKey::from_slice(&[2; 32]).unwrap()];
+        let participants = secret_keys.iter().map(|sk| sk.public_key(&secp)).collect::<Vec<_>>();
+        let aggregate = musig2_aggregate_key(&secp, &participants).unwrap();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        psbt.inputs[0].musig2_participant_pubkeys.insert(aggregate, participants.clone());
+        assert_eq!(
+            psbt.validate_musig2_participants(&secp, 0),
+            Err(Musig2Error::AggregateNotUsed { aggregate })
+        );
+        psbt.inputs[0].tap_internal_key = Some(aggregate.x_only_public_key().0);
+        assert_eq!(psbt.validate_musig2_participants(&secp, 0), Ok(()));
+        assert_eq!(psbt.missing_musig2_nonces(0, aggregate, None), Ok(participants.clone()));
+
+        let mut first = psbt.clone();
+        let sec_nonce = first
+            .generate_musig2_nonce(
+                &secp,
+                0,
+                (participants[0], aggregate, None),
+                Some(&secret_keys[0]),
+                [7; 32],
+            )
+            .unwrap();
+        assert_eq!(sec_nonce.participant(), participants[0]);
+        assert!(matches!(
+            first.generate_musig2_nonce(
+                &secp,
+                0,
+                (participants[0], aggregate, None),
+                None,
+                [8; 32]
+            ),
+            Err(Musig2Error::NonceExists { .. })
+        ));
+        assert_eq!(
+            first.aggregate_musig2_nonces(0, aggregate, None),
+            Err(Musig2Error::MissingNonce { participant: participants[1] })
+        );
+
+        let mut second = psbt.clone();
+        second
+            .generate_musig2_nonce(&secp, 0, (participants[1], aggregate, None), None, [9; 32])
+            .unwrap();
+        let stranger = SecretKey::from_slice(&[3; 32]).unwrap().public_key(&secp);
+        assert_eq!(
+            second
+                .generate_musig2_nonce(&secp, 0, (stranger, aggregate, None), None, [9; 32])
+                .unwrap_err(),
+            Musig2Error::UnknownParticipant { participant: stranger }
+        );
+
+        first.combine(second).unwrap();
+        assert_eq!(first.missing_musig2_nonces(0, aggregate, None), Ok(vec![]));
+        assert_eq!(first.validate_musig2_participants(&secp, 0), Ok(()));
+        let agg_nonce = first.aggregate_musig2_nonces(0, aggregate, None).unwrap();
+        assert!(PublicKey::from_slice(&agg_nonce.as_byte_array()[..33]).is_ok());
+
+        first.inputs[0].musig2_participant_pubkeys.insert(aggregate, vec![participants[0]]);
+        assert_eq!(
+            first.validate_musig2_participants(&secp, 0),
+            Err(Musig2Error::AggregateMismatch { aggregate })
+        );
+    }
+}
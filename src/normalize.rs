@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bringing a PSBT into its canonical form.
+//!
+//! Maps are serialized with their keys sorted, so a PSBT's serialization only depends on its
+//! contents. A PSBT built or modified through its public fields can still hold the same field
+//! twice, once typed and once in an `unknown` map, or list Taproot leaf hashes in any order.
+
+use crate::map::Map;
+use crate::prelude::*;
+use crate::{raw, Error, Psbt};
+
+impl Psbt {
+    /// Brings this PSBT into its canonical form, so that PSBTs with the same fields serialize to
+    /// the same bytes.
+    ///
+    /// - Unknown fields of an input or output with a key type this crate knows are decoded into
+    ///   their typed field. Those that do not decode are kept as unknown fields.
+    /// - Unknown fields with the key of a typed field are removed, the typed field is kept.
+    /// - The leaf hashes of every Taproot key origin are sorted and deduplicated.
+    pub fn normalize(&mut self) {
+        let duplicates = duplicate_unknowns(self);
+        self.unknown.retain(|key, _| !duplicates.contains(key));
+
+        for input in &mut self.inputs {
+            let unknown = core::mem::take(&mut input.unknown);
+            let undecoded = decode_unknowns(unknown, |pair| input.insert_pair(pair));
+            input.unknown.extend(undecoded);
+            let duplicates = duplicate_unknowns(input);
+            input.unknown.retain(|key, _| !duplicates.contains(key));
+            for (leaf_hashes, _) in input.tap_key_origins.values_mut() {
+                leaf_hashes.sort();
+                leaf_hashes.dedup();
+            }
+        }
+        for output in &mut self.outputs {
+            let unknown = core::mem::take(&mut output.unknown);
+            let undecoded = decode_unknowns(unknown, |pair| output.insert_pair(pair));
+            output.unknown.extend(undecoded);
+            let duplicates = duplicate_unknowns(output);
+            output.unknown.retain(|key, _| !duplicates.contains(key));
+            for (leaf_hashes, _) in output.tap_key_origins.values_mut() {
+                leaf_hashes.sort();
+                leaf_hashes.dedup();
+            }
+        }
+    }
+
+    /// Returns this PSBT in its canonical form, see [`Psbt::normalize`].
+    pub fn normalized(mut self) -> Psbt {
+        self.normalize();
+        self
+    }
+}
+
+/// Decodes the fields of `unknown` using `insert_pair`, returning those that fail to decode.
+///
+/// Fields already present as a typed field are dropped.
+fn decode_unknowns<F>(
+    unknown: BTreeMap<raw::Key, Vec<u8>>,
+    mut insert_pair: F,
+) -> Vec<(raw::Key, Vec<u8>)>
+where
+    F: FnMut(raw::Pair) -> Result<(), Error>,
+{
+    let mut undecoded = vec![];
+    for (key, value) in unknown {
+        match insert_pair(raw::Pair { key: key.clone(), value: value.clone() }) {
+            Ok(()) | Err(Error::DuplicateKey(_)) => {}
+            Err(_) => undecoded.push((key, value)),
+        }
+    }
+    undecoded
+}
+
+/// Returns the keys of the unknown fields of `map` that another field has too.
+fn duplicate_unknowns<M: Map>(map: &M) -> BTreeSet<raw::Key> {
+    let mut seen = BTreeSet::new();
+    let mut duplicates = BTreeSet::new();
+    for pair in map.get_pairs() {
+        if !seen.insert(pair.key.clone()) {
+            duplicates.insert(pair.key);
+        }
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::{absolute, transaction, TapLeafHash, Transaction, TxIn};
+
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        let secp = Secp256k1::new();
+        let xonly = SecretKey::from_slice(&[1; 32]).unwrap().x_only_public_key(&secp).0;
+        let leaves =
+            vec![TapLeafHash::from_byte_array([2; 32]), TapLeafHash::from_byte_array([1; 32])];
+        psbt.inputs[0].tap_key_origins.insert(xonly, (leaves.clone(), Default::default()));
+        // A sighash type field stored as unknown, and a second copy of the unsigned transaction.
+        psbt.inputs[0].unknown.insert(raw::Key::new(0x03), vec![0x01, 0, 0, 0]);
+        psbt.inputs[0].unknown.insert(raw::Key::new(0x03).push([1]), vec![0x01, 0, 0, 0]);
+        psbt.unknown.insert(raw::Key::new(0x00), vec![1, 2, 3]);
+        psbt.unknown.insert(raw::Key::new(0xf0), vec![]);
+
+        let mut reordered = psbt.clone();
+        reordered.inputs[0].tap_key_origins.insert(
+            xonly,
+            (leaves.iter().rev().chain(&leaves).copied().collect(), Default::default()),
+        );
+
+        let normalized = psbt.normalized();
+        assert_eq!(normalized, reordered.normalized());
+        let input = &normalized.inputs[0];
+        assert_eq!(input.sighash_type, Some(crate::PsbtSighashType::ALL));
+        assert_eq!(input.unknown.keys().collect::<Vec<_>>(), [&raw::Key::new(0x03).push([1])]);
+        assert_eq!(input.tap_key_origins[&xonly].0, [leaves[1], leaves[0]]);
+        assert_eq!(normalized.unknown.keys().collect::<Vec<_>>(), [&raw::Key::new(0xf0)]);
+
+        // Serializing is stable across round trips, of PSBTs that deserialize.
+        let mut normalized = normalized;
+        normalized.inputs[0].unknown.clear();
+        let bytes = normalized.serialize();
+        assert_eq!(Psbt::deserialize(&bytes).unwrap().serialize(), bytes);
+        let mut keys = vec![];
+        let mut r = &bytes[5..];
+        while let Ok(pair) =
+            raw::Pair::decode(&mut r, &crate::serialize::DeserializeOptions::UNLIMITED)
+        {
+            keys.push(pair.key);
+        }
+        let mut sorted = keys.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(keys, sorted);
+    }
+}
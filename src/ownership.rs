@@ -0,0 +1,451 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Proofs that the party adding an input controls the output it spends.
+//!
+//! Collaborative transactions, e.g. a PayJoin proposal or a coinswap, take inputs from several
+//! parties. Before signing, each party wants to know the others' inputs are really theirs and not
+//! someone else's outputs. [`Psbt::prove_input_ownership`] attaches a BIP 322 simple signature for
+//! the spent output to the input, under the [`OwnershipProof`] proprietary field, and
+//! [`Psbt::verify_input_ownership`] checks them.
+//!
+//! The signed message is the consensus encoding of the spent outpoint followed by a challenge
+//! agreed on by the protocol, so a proof can not be replayed for another input or session. Only
+//! P2WPKH outputs and Taproot key path spends are supported.
+
+use core::fmt;
+
+use bitcoin::consensus::encode;
+use bitcoin::hashes::Hash;
+use bitcoin::key::TapTweak;
+use bitcoin::script::PushBytes;
+use bitcoin::secp256k1::{Keypair, Message, Secp256k1, Signing, Verification};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::{
+    absolute, ecdsa, opcodes, script, taproot, transaction, Amount, OutPoint, PublicKey, Script,
+    ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness, XOnlyPublicKey,
+};
+use bitcoin_internals::write_err;
+
+use crate::musig2::tagged_hash;
+use crate::prelude::*;
+use crate::{GetKey, IndexOutOfBoundsError, Input, KeyRequest, ProprietaryField, Psbt};
+
+/// A BIP 322 simple signature proving control of the output an input spends.
+///
+/// Stored in the input map under the proprietary key with prefix `"bip322"`, subtype `0x00` and no
+/// key data. The value is the consensus encoded witness of the BIP 322 `to_sign` transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipProof(pub Witness);
+
+impl ProprietaryField for OwnershipProof {
+    const PREFIX: &'static [u8] = b"bip322";
+    const SUBTYPE: u8 = 0x00;
+    type Error = encode::Error;
+
+    fn encode(&self) -> Vec<u8> { encode::serialize(&self.0) }
+    fn decode(_key: &[u8], value: &[u8]) -> Result<Self, Self::Error> {
+        encode::deserialize(value).map(OwnershipProof)
+    }
+}
+
+impl Psbt {
+    /// Signs an ownership proof for input `input_index` with a key from `k`, adding it to the
+    /// input's proprietary fields.
+    ///
+    /// The key is requested by the input's BIP 32 derivations, or for Taproot the origin of the
+    /// internal key, falling back to the public key itself.
+    ///
+    /// # Errors
+    ///
+    /// If the spent output is not known or not supported, or `k` has no key for it.
+    pub fn prove_input_ownership<C, K>(
+        &mut self,
+        input_index: usize,
+        challenge: &[u8],
+        k: &K,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), OwnershipError>
+    where
+        C: Signing + Verification,
+        K: GetKey,
+    {
+        let (input, spk) = self.ownership_input(input_index)?;
+        let message = message(&self.unsigned_tx.input[input_index].previous_output, challenge);
+        let to_sign = to_sign(&to_spend(&spk, &message));
+
+        let witness = if spk.is_p2wpkh() {
+            let sk = segwit_key(input, &spk, k, secp)
+                .ok_or(OwnershipError::MissingKey { input_index })?;
+            let sighash = SighashCache::new(&to_sign)
+                .p2wpkh_signature_hash(0, &spk, Amount::ZERO, EcdsaSighashType::All)
+                .expect("to_sign has one input spending a P2WPKH output");
+            let msg = Message::from_digest(sighash.to_byte_array());
+            let sig = ecdsa::Signature::sighash_all(secp.sign_ecdsa(&msg, &sk.inner));
+            Witness::p2wpkh(&sig, &sk.inner.public_key(secp))
+        } else {
+            let keypair = taproot_key(input, &spk, k, secp)
+                .ok_or(OwnershipError::MissingKey { input_index })?;
+            let prevout = to_spend_output(&spk);
+            let sighash = SighashCache::new(&to_sign)
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&[prevout]),
+                    TapSighashType::Default,
+                )
+                .expect("to_sign has one input and one prevout");
+            let msg = Message::from_digest(sighash.to_byte_array());
+            let signature = crate::sign_schnorr(&msg, &keypair, secp);
+            let sig = taproot::Signature { signature, sighash_type: TapSighashType::Default };
+            Witness::p2tr_key_spend(&sig)
+        };
+        self.inputs[input_index].insert_proprietary(&OwnershipProof(witness));
+        Ok(())
+    }
+
+    /// Checks every ownership proof in the inputs against `challenge`, returning the indices of
+    /// the inputs that have one.
+    ///
+    /// Inputs without a proof are not an error, the caller decides which inputs must be proven.
+    ///
+    /// # Errors
+    ///
+    /// On the first input with a proof that does not decode or does not sign for its spent output.
+    pub fn verify_input_ownership<C: Verification>(
+        &self,
+        challenge: &[u8],
+        secp: &Secp256k1<C>,
+    ) -> Result<Vec<usize>, OwnershipError> {
+        let mut proven = vec![];
+        for input_index in 0..self.inputs.len() {
+            let proof = match self.inputs[input_index].get_proprietary::<OwnershipProof>() {
+                Some(Ok(proof)) => proof,
+                Some(Err(_)) => return Err(OwnershipError::InvalidProof { input_index }),
+                None => continue,
+            };
+            let (_, spk) = self.ownership_input(input_index)?;
+            let message = message(&self.unsigned_tx.input[input_index].previous_output, challenge);
+            if !verify(&spk, &message, &proof.0, secp) {
+                return Err(OwnershipError::InvalidProof { input_index });
+            }
+            proven.push(input_index);
+        }
+        Ok(proven)
+    }
+
+    /// Returns input `input_index` and the script pubkey it spends, if it is supported.
+    fn ownership_input(&self, input_index: usize) -> Result<(&Input, ScriptBuf), OwnershipError> {
+        let input = self.checked_input(input_index)?;
+        let spk = self
+            .spend_utxo(input_index)
+            .map_err(|_| OwnershipError::MissingUtxo { input_index })?
+            .script_pubkey
+            .clone();
+        if !spk.is_p2wpkh() && !spk.is_p2tr() {
+            return Err(OwnershipError::UnsupportedScript { input_index });
+        }
+        Ok((input, spk))
+    }
+}
+
+/// Returns the message signed for the input spending `outpoint`.
+fn message(outpoint: &OutPoint, challenge: &[u8]) -> Vec<u8> {
+    let mut message = encode::serialize(outpoint);
+    message.extend_from_slice(challenge);
+    message
+}
+
+/// Returns the output of the BIP 322 `to_spend` transaction, locked by `spk`.
+fn to_spend_output(spk: &Script) -> TxOut {
+    TxOut { value: Amount::ZERO, script_pubkey: spk.into() }
+}
+
+/// Returns the BIP 322 `to_spend` transaction committing to `message`.
+fn to_spend(spk: &Script, message: &[u8]) -> Transaction {
+    let message_hash = tagged_hash("BIP0322-signed-message", &[message]);
+    let script_sig = script::Builder::new()
+        .push_opcode(opcodes::OP_0)
+        .push_slice(<&PushBytes>::from(&message_hash))
+        .into_script();
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![to_spend_output(spk)],
+    }
+}
+
+/// Returns the BIP 322 `to_sign` transaction spending `to_spend`, without a witness.
+fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint { txid: to_spend.compute_txid(), vout: 0 },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            // A bare `OP_RETURN`, not followed by an empty push.
+            script_pubkey: script::Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .into_script(),
+        }],
+    }
+}
+
+/// Returns the key from `k` whose hash is the P2WPKH program `spk`.
+fn segwit_key<C: Signing, K: GetKey>(
+    input: &Input,
+    spk: &Script,
+    k: &K,
+    secp: &Secp256k1<C>,
+) -> Option<bitcoin::PrivateKey> {
+    input.bip32_derivation.iter().find_map(|(pk, source)| {
+        let pk = PublicKey::new(*pk);
+        if ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().ok()?) != *spk {
+            return None;
+        }
+        k.get_key(&KeyRequest::Bip32(source.clone()), secp)
+            .ok()
+            .flatten()
+            .or_else(|| k.get_key(&KeyRequest::Pubkey(pk), secp).ok().flatten())
+    })
+}
+
+/// Returns the tweaked key pair from `k` for the key path spend of the Taproot output `spk`.
+fn taproot_key<C: Signing + Verification, K: GetKey>(
+    input: &Input,
+    spk: &Script,
+    k: &K,
+    secp: &Secp256k1<C>,
+) -> Option<Keypair> {
+    let internal_key = input.tap_internal_key?;
+    let sk = input
+        .tap_key_origins
+        .get(&internal_key)
+        .and_then(|(_, source)| k.get_key(&KeyRequest::Bip32(source.clone()), secp).ok())
+        .flatten()
+        .or_else(|| k.get_key(&KeyRequest::XOnlyPubkey(internal_key), secp).ok().flatten())?;
+    let keypair =
+        Keypair::from_secret_key(secp, &sk.inner).tap_tweak(secp, input.tap_merkle_root).to_inner();
+    if ScriptBuf::new_p2tr_tweaked(keypair.x_only_public_key().0.dangerous_assume_tweaked()) != *spk
+    {
+        return None;
+    }
+    Some(keypair)
+}
+
+/// Checks that `witness` spends the `to_spend` output locked by `spk` committing to `message`.
+fn verify<C: Verification>(
+    spk: &Script,
+    message: &[u8],
+    witness: &Witness,
+    secp: &Secp256k1<C>,
+) -> bool {
+    let to_sign = to_sign(&to_spend(spk, message));
+    let mut cache = SighashCache::new(&to_sign);
+    if spk.is_p2wpkh() {
+        if witness.len() != 2 {
+            return false;
+        }
+        let (sig, pk) = (&witness[0], &witness[1]);
+        let (sig, pk) = match (ecdsa::Signature::from_slice(sig), PublicKey::from_slice(pk)) {
+            (Ok(sig), Ok(pk)) => (sig, pk),
+            _ => return false,
+        };
+        if pk.wpubkey_hash().map(|hash| ScriptBuf::new_p2wpkh(&hash)).as_deref() != Ok(spk) {
+            return false;
+        }
+        let sighash = match cache.p2wpkh_signature_hash(0, spk, Amount::ZERO, sig.sighash_type) {
+            Ok(sighash) => sighash,
+            Err(_) => return false,
+        };
+        let msg = Message::from_digest(sighash.to_byte_array());
+        secp.verify_ecdsa(&msg, &sig.signature, &pk.inner).is_ok()
+    } else {
+        if witness.len() != 1 {
+            return false;
+        }
+        let sig = match taproot::Signature::from_slice(&witness[0]) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+        let output_key = match XOnlyPublicKey::from_slice(&spk.as_bytes()[2..]) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let prevouts = [to_spend_output(spk)];
+        let sighash = match cache.taproot_key_spend_signature_hash(
+            0,
+            &Prevouts::All(&prevouts),
+            sig.sighash_type,
+        ) {
+            Ok(sighash) => sighash,
+            Err(_) => return false,
+        };
+        let msg = Message::from_digest(sighash.to_byte_array());
+        secp.verify_schnorr(&sig.signature, &msg, &output_key).is_ok()
+    }
+}
+
+/// Error creating or verifying an ownership proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OwnershipError {
+    /// The input index is out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The output spent by the input is not known.
+    MissingUtxo {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The output spent by the input is neither P2WPKH nor Taproot.
+    UnsupportedScript {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// No key for the output spent by the input was found.
+    MissingKey {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The proof of the input does not decode or does not sign for the spent output.
+    InvalidProof {
+        /// The index of the input.
+        input_index: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(OwnershipError);
+
+impl fmt::Display for OwnershipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use OwnershipError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "input index out of bounds"; e),
+            MissingUtxo { input_index } =>
+                write!(f, "the output spent by input {} is not known", input_index),
+            UnsupportedScript { input_index } => write!(
+                f,
+                "ownership proofs are not supported for the output spent by input {}",
+                input_index
+            ),
+            MissingKey { input_index } =>
+                write!(f, "no key for the output spent by input {}", input_index),
+            InvalidProof { input_index } =>
+                write!(f, "the ownership proof of input {} is invalid", input_index),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OwnershipError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use OwnershipError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            MissingUtxo { .. }
+            | UnsupportedScript { .. }
+            | MissingKey { .. }
+            | InvalidProof { .. } => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for OwnershipError {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::hashes::Hash;
+    use bitcoin::{secp256k1, PrivateKey, Txid};
+
+    use super::*;
+
+    #[test]
+    fn bip322_transactions() {
+        // The test vectors of BIP 322 for the empty message.
+        let spk = ScriptBuf::from_hex("00142b05d564e6a7a33c087f16e0f730d1440123799d").unwrap();
+        let to_spend = to_spend(&spk, b"");
+        assert_eq!(
+            to_spend.compute_txid().to_string(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            to_sign(&to_spend).compute_txid().to_string(),
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"
+        );
+    }
+
+    #[test]
+    fn input_ownership() {
+        let secp = Secp256k1::new();
+        let sk = PrivateKey::new(
+            secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(),
+            bitcoin::Network::Bitcoin,
+        );
+        let pk = sk.public_key(&secp);
+        let keys = [(pk, sk)].into_iter().collect::<BTreeMap<_, _>>();
+        let xonly = XOnlyPublicKey::from(pk.inner);
+        let p2wpkh = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap());
+        let p2tr = ScriptBuf::new_p2tr(&secp, xonly, None);
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..3)
+                .map(|vout| TxIn {
+                    previous_output: OutPoint { txid: Txid::all_zeros(), vout },
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![],
+        })
+        .unwrap();
+        let utxo = |script_pubkey| Some(TxOut { value: Amount::from_sat(10_000), script_pubkey });
+        psbt.inputs[0].witness_utxo = utxo(p2wpkh);
+        psbt.inputs[0]
+            .bip32_derivation
+            .insert(pk.inner, (Fingerprint::default(), DerivationPath::master()));
+        psbt.inputs[1].witness_utxo = utxo(p2tr);
+        psbt.inputs[1].tap_internal_key = Some(xonly);
+        psbt.inputs[2].witness_utxo = utxo(ScriptBuf::new_p2pkh(&pk.pubkey_hash()));
+
+        psbt.prove_input_ownership(0, b"session", &keys, &secp).unwrap();
+        psbt.prove_input_ownership(1, b"session", &keys, &secp).unwrap();
+        assert_eq!(
+            psbt.prove_input_ownership(2, b"session", &keys, &secp),
+            Err(OwnershipError::UnsupportedScript { input_index: 2 })
+        );
+        assert_eq!(psbt.verify_input_ownership(b"session", &secp), Ok(vec![0, 1]));
+
+        // A proof for another session or another input is rejected.
+        assert_eq!(
+            psbt.verify_input_ownership(b"other", &secp),
+            Err(OwnershipError::InvalidProof { input_index: 0 })
+        );
+        psbt.unsigned_tx.input[0].previous_output.vout = 5;
+        assert_eq!(
+            psbt.verify_input_ownership(b"session", &secp),
+            Err(OwnershipError::InvalidProof { input_index: 0 })
+        );
+
+        let mut other = psbt.clone();
+        other.inputs[1].tap_internal_key = None;
+        assert_eq!(
+            other.prove_input_ownership(1, b"session", &keys, &secp),
+            Err(OwnershipError::MissingKey { input_index: 1 })
+        );
+    }
+}
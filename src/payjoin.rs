@@ -0,0 +1,461 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The PSBT checks of a PayJoin, as described in BIP 78.
+//!
+//! The sender sends a finalized original PSBT, the receiver answers with a proposal that adds its
+//! own inputs and may change its output. The receiver checks the original with
+//! [`Psbt::check_payjoin_original`] before building a proposal on it, the sender checks the proposal
+//! with [`Psbt::check_payjoin_proposal`] before signing it.
+
+use core::fmt;
+
+use bitcoin::{Amount, FeeRate, Script, Weight};
+use bitcoin_internals::write_err;
+
+use crate::{Error, EstimateWeightError, IndexOutOfBoundsError, Psbt};
+
+impl Psbt {
+    /// Checks this original PSBT before the receiver adds its inputs to it.
+    ///
+    /// Every input must be finalized and spend the same type of output, the PSBT must not reveal
+    /// the sender's key origins and the transaction must pay at least `min_fee_rate` for its
+    /// estimated weight.
+    ///
+    /// # Errors
+    ///
+    /// On the first check that fails.
+    pub fn check_payjoin_original(&self, min_fee_rate: FeeRate) -> Result<(), PayjoinError> {
+        if let Some(input_index) = self.inputs.iter().position(|input| !input.is_finalized()) {
+            return Err(PayjoinError::InputNotFinalized { input_index });
+        }
+        let first = self.payjoin_utxo_script(0)?;
+        for input_index in 1..self.inputs.len() {
+            if script_type(self.payjoin_utxo_script(input_index)?) != script_type(first) {
+                return Err(PayjoinError::MixedInputTypes { input_index });
+            }
+        }
+        if !self.xpub.is_empty()
+            || self.inputs.iter().any(|input| {
+                !input.bip32_derivation.is_empty() || !input.tap_key_origins.is_empty()
+            })
+            || self.has_output_key_origins()
+        {
+            return Err(PayjoinError::KeyOrigins);
+        }
+        check_fee_rate(self, min_fee_rate)
+    }
+
+    /// Checks the `proposal` the receiver made from this original PSBT.
+    ///
+    /// The proposal must keep the version, lock time, sender inputs and their sequence numbers,
+    /// leave the sender inputs unsigned and add only finalized inputs of the same type. Every
+    /// original output must still be present, outputs of the sender may only lose value to the
+    /// additional fee contribution `params` allows. The proposal must not reveal key origins and
+    /// must pay at least `params.min_fee_rate`.
+    ///
+    /// BIP 78 output substitution is not supported, the receiver's output keeps its script pubkey.
+    ///
+    /// # Errors
+    ///
+    /// On the first check that fails.
+    pub fn check_payjoin_proposal(
+        &self,
+        proposal: &Psbt,
+        params: &PayjoinParams,
+    ) -> Result<(), PayjoinError> {
+        self.check_output_index_is_within_bounds(params.receiver_output)?;
+        if let Some(index) = params.additional_fee_output_index {
+            self.check_output_index_is_within_bounds(index)?;
+        }
+        let (original_tx, proposed_tx) = (&self.unsigned_tx, &proposal.unsigned_tx);
+        if original_tx.version != proposed_tx.version
+            || original_tx.lock_time != proposed_tx.lock_time
+        {
+            return Err(PayjoinError::TransactionChanged);
+        }
+        if !proposal.xpub.is_empty() || proposal.has_output_key_origins() {
+            return Err(PayjoinError::KeyOrigins);
+        }
+
+        // The proposal with the sender's finalized inputs, to compute its fee and weight.
+        let mut completed = proposal.clone();
+        let mut seen = vec![false; original_tx.input.len()];
+        let sender_type = script_type(self.payjoin_utxo_script(0)?);
+        for (input_index, txin) in proposed_tx.input.iter().enumerate() {
+            let original = original_tx
+                .input
+                .iter()
+                .position(|original| original.previous_output == txin.previous_output);
+            match original {
+                Some(index) => {
+                    if txin.sequence != original_tx.input[index].sequence {
+                        return Err(PayjoinError::SequenceChanged { input_index });
+                    }
+                    if proposal.inputs[input_index].is_finalized() {
+                        return Err(PayjoinError::SenderInputFinalized { input_index });
+                    }
+                    seen[index] = true;
+                    completed.inputs[input_index] = self.inputs[index].clone();
+                }
+                None => {
+                    if txin.sequence != original_tx.input[0].sequence {
+                        return Err(PayjoinError::SequenceChanged { input_index });
+                    }
+                    if !proposal.inputs[input_index].is_finalized() {
+                        return Err(PayjoinError::InputNotFinalized { input_index });
+                    }
+                    if script_type(proposal.payjoin_utxo_script(input_index)?) != sender_type {
+                        return Err(PayjoinError::MixedInputTypes { input_index });
+                    }
+                }
+            }
+        }
+        if let Some(input_index) = seen.iter().position(|seen| !seen) {
+            return Err(PayjoinError::MissingSenderInput { input_index });
+        }
+
+        let mut used = vec![false; proposed_tx.output.len()];
+        for (output_index, txout) in original_tx.output.iter().enumerate() {
+            let position = proposed_tx.output.iter().zip(&used).position(|(proposed, used)| {
+                !used && proposed.script_pubkey == txout.script_pubkey
+            });
+            let position = match position {
+                Some(position) => position,
+                None => return Err(PayjoinError::MissingOutput { output_index }),
+            };
+            used[position] = true;
+
+            let value = proposed_tx.output[position].value;
+            if output_index == params.receiver_output || value >= txout.value {
+                continue;
+            }
+            if Some(output_index) != params.additional_fee_output_index {
+                return Err(PayjoinError::OutputDecreased { output_index });
+            }
+            let contribution = txout.value - value;
+            if contribution > params.max_additional_fee_contribution {
+                return Err(PayjoinError::FeeContributionExceeded {
+                    contribution,
+                    max: params.max_additional_fee_contribution,
+                });
+            }
+        }
+        check_fee_rate(&completed, params.min_fee_rate)
+    }
+
+    fn payjoin_utxo_script(&self, input_index: usize) -> Result<&Script, PayjoinError> {
+        match self.spend_utxo(input_index) {
+            Ok(utxo) => Ok(&utxo.script_pubkey),
+            Err(_) => Err(PayjoinError::MissingUtxo { input_index }),
+        }
+    }
+
+    fn has_output_key_origins(&self) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| !output.bip32_derivation.is_empty() || !output.tap_key_origins.is_empty())
+    }
+}
+
+/// Returns an error if `psbt` pays less than `min_fee_rate` for its estimated weight.
+fn check_fee_rate(psbt: &Psbt, min_fee_rate: FeeRate) -> Result<(), PayjoinError> {
+    let fee = psbt.fee().map_err(PayjoinError::Fee)?;
+    let weight = psbt.estimate_weight()?;
+    let fee_rate = fee / weight.max(Weight::from_wu(1));
+    if fee_rate < min_fee_rate {
+        return Err(PayjoinError::FeeRateTooLow { fee_rate, min: min_fee_rate });
+    }
+    Ok(())
+}
+
+/// Returns the kind of output `script_pubkey` is, other scripts are all of one kind.
+fn script_type(script_pubkey: &Script) -> u8 {
+    if script_pubkey.is_p2pkh() {
+        1
+    } else if script_pubkey.is_p2sh() {
+        2
+    } else if script_pubkey.is_p2wpkh() {
+        3
+    } else if script_pubkey.is_p2wsh() {
+        4
+    } else if script_pubkey.is_p2tr() {
+        5
+    } else {
+        0
+    }
+}
+
+/// The parameters the sender sent the receiver along with the original PSBT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayjoinParams {
+    /// The index of the original output paying the receiver.
+    pub receiver_output: usize,
+    /// The index of the original output that may pay for the receiver's inputs,
+    /// `additionalfeeoutputindex` in BIP 78.
+    pub additional_fee_output_index: Option<usize>,
+    /// The most the additional fee output may lose, `maxadditionalfeecontribution` in BIP 78.
+    pub max_additional_fee_contribution: Amount,
+    /// The lowest fee rate the proposal may pay, `minfeerate` in BIP 78.
+    pub min_fee_rate: FeeRate,
+}
+
+impl PayjoinParams {
+    /// Creates the parameters for a payment to `receiver_output` without an additional fee
+    /// contribution.
+    pub fn new(receiver_output: usize) -> Self {
+        PayjoinParams {
+            receiver_output,
+            additional_fee_output_index: None,
+            max_additional_fee_contribution: Amount::ZERO,
+            min_fee_rate: FeeRate::ZERO,
+        }
+    }
+}
+
+/// Error returned by the PayJoin checks, see [`Psbt::check_payjoin_original`] and
+/// [`Psbt::check_payjoin_proposal`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PayjoinError {
+    /// An output index in the parameters is out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// A sender input of the original or a receiver input of the proposal is not finalized.
+    InputNotFinalized {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// UTXO information for the input is not present or is invalid.
+    MissingUtxo {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The input spends a different type of output than the first sender input.
+    MixedInputTypes {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The PSBT contains xpubs or BIP 32 derivation paths.
+    KeyOrigins,
+    /// The fee of the PSBT can not be computed.
+    Fee(Error),
+    /// The weight of the transaction can not be estimated.
+    EstimateWeight(EstimateWeightError),
+    /// The transaction pays less than the minimum fee rate.
+    FeeRateTooLow {
+        /// The fee rate of the transaction.
+        fee_rate: FeeRate,
+        /// The minimum fee rate.
+        min: FeeRate,
+    },
+    /// The proposal changed the version or lock time of the transaction.
+    TransactionChanged,
+    /// The proposal does not spend an input of the original.
+    MissingSenderInput {
+        /// The index of the input in the original.
+        input_index: usize,
+    },
+    /// The input of the proposal has a different sequence number than the sender's inputs.
+    SequenceChanged {
+        /// The index of the input in the proposal.
+        input_index: usize,
+    },
+    /// The proposal finalized a sender input.
+    SenderInputFinalized {
+        /// The index of the input in the proposal.
+        input_index: usize,
+    },
+    /// The proposal has no output with the script pubkey of an original output.
+    MissingOutput {
+        /// The index of the output in the original.
+        output_index: usize,
+    },
+    /// The proposal decreased the value of a sender output.
+    OutputDecreased {
+        /// The index of the output in the original.
+        output_index: usize,
+    },
+    /// The additional fee output lost more than the sender allowed.
+    FeeContributionExceeded {
+        /// The value the output lost.
+        contribution: Amount,
+        /// The most the sender allowed.
+        max: Amount,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(PayjoinError);
+
+impl fmt::Display for PayjoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PayjoinError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "PayJoin output index out of bounds"; e),
+            InputNotFinalized { input_index } =>
+                write!(f, "input {} is not finalized", input_index),
+            MissingUtxo { input_index } =>
+                write!(f, "UTXO information is not available for input {}", input_index),
+            MixedInputTypes { input_index } =>
+                write!(f, "input {} spends a different type of output", input_index),
+            KeyOrigins => f.write_str("the PSBT reveals key origins"),
+            Fee(ref e) => write_err!(f, "can not compute the fee"; e),
+            EstimateWeight(ref e) => write_err!(f, "can not estimate the transaction weight"; e),
+            FeeRateTooLow { fee_rate, min } => write!(
+                f,
+                "the fee rate {} sat/kwu is below the minimum {} sat/kwu",
+                fee_rate.to_sat_per_kwu(),
+                min.to_sat_per_kwu()
+            ),
+            TransactionChanged => f.write_str("the proposal changed the version or lock time"),
+            MissingSenderInput { input_index } =>
+                write!(f, "the proposal does not spend original input {}", input_index),
+            SequenceChanged { input_index } =>
+                write!(f, "proposal input {} has a different sequence number", input_index),
+            SenderInputFinalized { input_index } =>
+                write!(f, "the proposal finalized sender input {}", input_index),
+            MissingOutput { output_index } =>
+                write!(f, "the proposal does not contain original output {}", output_index),
+            OutputDecreased { output_index } =>
+                write!(f, "the proposal decreased original output {}", output_index),
+            FeeContributionExceeded { contribution, max } => write!(
+                f,
+                "the additional fee contribution {} exceeds the maximum {}",
+                contribution, max
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PayjoinError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use PayjoinError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            Fee(ref e) => Some(e),
+            EstimateWeight(ref e) => Some(e),
+            InputNotFinalized { .. }
+            | MissingUtxo { .. }
+            | MixedInputTypes { .. }
+            | KeyOrigins
+            | FeeRateTooLow { .. }
+            | TransactionChanged
+            | MissingSenderInput { .. }
+            | SequenceChanged { .. }
+            | SenderInputFinalized { .. }
+            | MissingOutput { .. }
+            | OutputDecreased { .. }
+            | FeeContributionExceeded { .. } => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for PayjoinError {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+impl From<EstimateWeightError> for PayjoinError {
+    fn from(e: EstimateWeightError) -> Self { Self::EstimateWeight(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::hashes::Hash;
+    use bitcoin::{
+        absolute, secp256k1, transaction, NetworkKind, OutPoint, PrivateKey, ScriptBuf,
+        Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    use super::*;
+
+    #[test]
+    fn payjoin_checks() {
+        let secp = secp256k1::Secp256k1::new();
+        let pk = |i| {
+            let sk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+            PrivateKey::new(sk, NetworkKind::Test).public_key(&secp)
+        };
+        let p2wpkh = |i| ScriptBuf::new_p2wpkh(&pk(i).wpubkey_hash().unwrap());
+        let txin = |i| TxIn {
+            previous_output: OutPoint { txid: Txid::from_byte_array([i; 32]), vout: 0 },
+            ..Default::default()
+        };
+        let finalize = |psbt: &mut Psbt, index: usize, value: u64, key: u8| {
+            let input = &mut psbt.inputs[index];
+            input.witness_utxo =
+                Some(TxOut { value: Amount::from_sat(value), script_pubkey: p2wpkh(key) });
+            input.final_script_witness =
+                Some(Witness::from_slice(&[vec![0; 72], pk(key).to_bytes()]));
+        };
+
+        // The sender pays 5000 to output 0 and gets 4000 change in output 1.
+        let mut original = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![txin(1)],
+            output: vec![
+                TxOut { value: Amount::from_sat(5_000), script_pubkey: p2wpkh(2) },
+                TxOut { value: Amount::from_sat(4_000), script_pubkey: p2wpkh(1) },
+            ],
+        })
+        .unwrap();
+        finalize(&mut original, 0, 10_000, 1);
+        let min_fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
+        original.check_payjoin_original(min_fee_rate).unwrap();
+
+        let mut leaky = original.clone();
+        leaky.outputs[1]
+            .bip32_derivation
+            .insert(pk(1).inner, (Fingerprint::default(), DerivationPath::master()));
+        assert!(matches!(
+            leaky.check_payjoin_original(min_fee_rate),
+            Err(PayjoinError::KeyOrigins)
+        ));
+        assert!(matches!(
+            original.check_payjoin_original(FeeRate::from_sat_per_vb_unchecked(100)),
+            Err(PayjoinError::FeeRateTooLow { .. })
+        ));
+
+        // The receiver adds a 20_000 input to its output and takes 200 of the fee from the change.
+        let mut proposal = original.clone();
+        proposal.inputs[0] = Default::default();
+        proposal.unsigned_tx.input.insert(0, txin(3));
+        proposal.inputs.insert(0, Default::default());
+        finalize(&mut proposal, 0, 20_000, 3);
+        proposal.unsigned_tx.output[0].value = Amount::from_sat(25_000);
+        proposal.unsigned_tx.output[1].value = Amount::from_sat(3_800);
+
+        let mut params = PayjoinParams::new(0);
+        params.additional_fee_output_index = Some(1);
+        params.max_additional_fee_contribution = Amount::from_sat(300);
+        params.min_fee_rate = min_fee_rate;
+        original.check_payjoin_proposal(&proposal, &params).unwrap();
+
+        params.max_additional_fee_contribution = Amount::from_sat(100);
+        assert!(matches!(
+            original.check_payjoin_proposal(&proposal, &params),
+            Err(PayjoinError::FeeContributionExceeded { .. })
+        ));
+        params.additional_fee_output_index = None;
+        assert!(matches!(
+            original.check_payjoin_proposal(&proposal, &params),
+            Err(PayjoinError::OutputDecreased { output_index: 1 })
+        ));
+
+        let mut signed = proposal.clone();
+        finalize(&mut signed, 1, 10_000, 1);
+        assert!(matches!(
+            original.check_payjoin_proposal(&signed, &PayjoinParams::new(0)),
+            Err(PayjoinError::SenderInputFinalized { input_index: 1 })
+        ));
+        let mut dropped = proposal;
+        dropped.unsigned_tx.input.remove(1);
+        dropped.inputs.remove(1);
+        assert!(matches!(
+            original.check_payjoin_proposal(&dropped, &PayjoinParams::new(0)),
+            Err(PayjoinError::MissingSenderInput { input_index: 0 })
+        ));
+    }
+}
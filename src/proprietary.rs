@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Typed access to proprietary fields.
+//!
+//! BIP 174 reserves key type `0xFC` for proprietary use, the key data being a compact size
+//! prefixed identifier, a subtype and arbitrary key bytes. Implementing [`ProprietaryField`] for
+//! a type describes how it maps to one such entry, after which it can be read and written on any
+//! PSBT map with `get_proprietary` and `insert_proprietary`.
+
+use crate::prelude::*;
+use crate::raw::ProprietaryKey;
+use crate::{Input, Output, Psbt};
+
+/// A value stored in a proprietary (`0xFC`) PSBT field.
+///
+/// ```
+/// # use psbt_v0::{Input, ProprietaryField};
+/// /// A wallet specific note attached to an input.
+/// #[derive(Debug, PartialEq)]
+/// struct Note(Vec<u8>);
+///
+/// impl ProprietaryField for Note {
+///     const PREFIX: &'static [u8] = b"example";
+///     const SUBTYPE: u8 = 0x00;
+///     type Error = core::convert::Infallible;
+///
+///     fn encode(&self) -> Vec<u8> { self.0.clone() }
+///     fn decode(_key: &[u8], value: &[u8]) -> Result<Self, Self::Error> { Ok(Note(value.to_vec())) }
+/// }
+///
+/// let mut input = Input::default();
+/// input.insert_proprietary(&Note(b"hello".to_vec()));
+/// assert_eq!(input.get_proprietary::<Note>(), Some(Ok(Note(b"hello".to_vec()))));
+/// ```
+pub trait ProprietaryField: Sized {
+    /// The identifier prefix, used to avoid collisions between applications.
+    const PREFIX: &'static [u8];
+    /// The application defined subtype.
+    const SUBTYPE: u8;
+    /// Error returned when a stored value cannot be decoded.
+    type Error;
+
+    /// Returns the key bytes following the subtype, empty by default.
+    fn key_data(&self) -> Vec<u8> { Vec::new() }
+
+    /// Encodes the value of this field.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Decodes a field from the key bytes following the subtype and the value.
+    fn decode(key: &[u8], value: &[u8]) -> Result<Self, Self::Error>;
+
+    /// Returns the full proprietary key this field is stored under.
+    fn proprietary_key(&self) -> ProprietaryKey {
+        ProprietaryKey {
+            prefix: Self::PREFIX.to_vec(),
+            subtype: Self::SUBTYPE,
+            key: self.key_data(),
+        }
+    }
+
+    /// Returns true if `key` carries this field's prefix and subtype.
+    fn matches(key: &ProprietaryKey) -> bool {
+        key.prefix == Self::PREFIX && key.subtype == Self::SUBTYPE
+    }
+}
+
+fn get<T: ProprietaryField>(
+    map: &BTreeMap<ProprietaryKey, Vec<u8>>,
+) -> Option<Result<T, T::Error>> {
+    iter(map).next()
+}
+
+fn iter<T: ProprietaryField>(
+    map: &BTreeMap<ProprietaryKey, Vec<u8>>,
+) -> impl Iterator<Item = Result<T, T::Error>> + '_ {
+    map.iter().filter(|(key, _)| T::matches(key)).map(|(key, value)| T::decode(&key.key, value))
+}
+
+fn insert<T: ProprietaryField>(
+    map: &mut BTreeMap<ProprietaryKey, Vec<u8>>,
+    field: &T,
+) -> Option<Vec<u8>> {
+    map.insert(field.proprietary_key(), field.encode())
+}
+
+macro_rules! impl_proprietary_accessors {
+    ($map:ty) => {
+        impl $map {
+            /// Returns the first proprietary field of type `T`, if present.
+            ///
+            /// Fields sharing a prefix and subtype are ordered by their key bytes; use
+            /// `proprietary_fields` to read all of them.
+            pub fn get_proprietary<T: ProprietaryField>(&self) -> Option<Result<T, T::Error>> {
+                get(&self.proprietary)
+            }
+
+            /// Returns an iterator over all proprietary fields of type `T`.
+            pub fn proprietary_fields<T: ProprietaryField>(
+                &self,
+            ) -> impl Iterator<Item = Result<T, T::Error>> + '_ {
+                iter(&self.proprietary)
+            }
+
+            /// Inserts `field`, returning the raw value previously stored under the same key.
+            pub fn insert_proprietary<T: ProprietaryField>(
+                &mut self,
+                field: &T,
+            ) -> Option<Vec<u8>> {
+                insert(&mut self.proprietary, field)
+            }
+
+            /// Removes all proprietary fields of type `T`, returning how many were removed.
+            pub fn remove_proprietary<T: ProprietaryField>(&mut self) -> usize {
+                let before = self.proprietary.len();
+                self.proprietary.retain(|key, _| !T::matches(key));
+                before - self.proprietary.len()
+            }
+        }
+    };
+}
+impl_proprietary_accessors!(Psbt);
+impl_proprietary_accessors!(Input);
+impl_proprietary_accessors!(Output);
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::consensus::encode::{deserialize, serialize};
+
+    use super::*;
+
+    /// An amount assigned to a named account, keyed by the account number.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Account {
+        number: u32,
+        sats: u64,
+    }
+
+    impl ProprietaryField for Account {
+        const PREFIX: &'static [u8] = b"wallet";
+        const SUBTYPE: u8 = 0x01;
+        type Error = bitcoin::consensus::encode::Error;
+
+        fn key_data(&self) -> Vec<u8> { serialize(&self.number) }
+        fn encode(&self) -> Vec<u8> { serialize(&self.sats) }
+        fn decode(key: &[u8], value: &[u8]) -> Result<Self, Self::Error> {
+            Ok(Account { number: deserialize(key)?, sats: deserialize(value)? })
+        }
+    }
+
+    #[test]
+    fn typed_proprietary_fields() {
+        let mut input = Input::default();
+        // An unrelated field with the same prefix but another subtype.
+        input.proprietary.insert(
+            ProprietaryKey { prefix: b"wallet".to_vec(), subtype: 0x02, key: vec![] },
+            vec![0xff],
+        );
+        assert!(input.get_proprietary::<Account>().is_none());
+
+        assert_eq!(input.insert_proprietary(&Account { number: 2, sats: 20 }), None);
+        assert_eq!(input.insert_proprietary(&Account { number: 1, sats: 10 }), None);
+        assert_eq!(
+            input.insert_proprietary(&Account { number: 1, sats: 11 }),
+            Some(serialize(&10u64))
+        );
+
+        assert_eq!(
+            input.get_proprietary::<Account>().unwrap().unwrap(),
+            Account { number: 1, sats: 11 }
+        );
+        let all = input.proprietary_fields::<Account>().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(all, vec![Account { number: 1, sats: 11 }, Account { number: 2, sats: 20 }]);
+
+        // A malformed value surfaces the decoding error.
+        input.proprietary.insert(Account { number: 3, sats: 0 }.proprietary_key(), vec![0x00]);
+        assert!(input.proprietary_fields::<Account>().any(|field| field.is_err()));
+
+        assert_eq!(input.remove_proprietary::<Account>(), 3);
+        assert_eq!(input.proprietary.len(), 1);
+    }
+}
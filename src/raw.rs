@@ -6,7 +6,7 @@
 //! <https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki>.
 //!
 
-use core::fmt;
+use core::{cmp, fmt};
 
 use bitcoin::consensus::encode::{
     self, deserialize, serialize, Decodable, Encodable, ReadExt, VarInt, WriteExt, MAX_VEC_SIZE,
@@ -14,7 +14,7 @@ use bitcoin::consensus::encode::{
 use bitcoin::hex::DisplayHex as _;
 use bitcoin::io::{self, BufRead, Write};
 
-use super::serialize::{Deserialize, Serialize};
+use super::serialize::{Deserialize, DeserializeOptions, Serialize};
 use crate::prelude::*;
 use crate::Error;
 
@@ -34,7 +34,7 @@ pub struct Key {
 
 /// A PSBT key-value pair in its raw byte form.
 /// `<keypair> := <key> <value>`
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
 pub struct Pair {
@@ -76,7 +76,30 @@ impl fmt::Display for Key {
 }
 
 impl Key {
-    pub(crate) fn decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Error> {
+    /// Creates a key of type `type_value` with empty key data.
+    ///
+    /// Key data is appended with [`Key::push`] and [`Key::push_compact_size`]:
+    ///
+    /// ```
+    /// # use psbt_v0::raw::Key;
+    /// let key = Key::new(0xf0).push_compact_size(3).push(b"abc");
+    /// assert_eq!(key.key_data, b"\x03abc");
+    /// ```
+    pub fn new(type_value: u8) -> Self { Key { type_value, key_data: Vec::new() } }
+
+    /// Appends `data` to the key data.
+    pub fn push(mut self, data: impl AsRef<[u8]>) -> Self {
+        self.key_data.extend_from_slice(data.as_ref());
+        self
+    }
+
+    /// Appends `n` encoded as a compact size to the key data.
+    pub fn push_compact_size(mut self, n: u64) -> Self {
+        self.key_data.extend(serialize(&VarInt(n)));
+        self
+    }
+
+    pub(crate) fn decode<R: BufRead + ?Sized>(r: &mut R, max_len: usize) -> Result<Self, Error> {
         let VarInt(byte_size): VarInt = Decodable::consensus_decode(r)?;
 
         if byte_size == 0 {
@@ -85,10 +108,11 @@ impl Key {
 
         let key_byte_size: u64 = byte_size - 1;
 
-        if key_byte_size > MAX_VEC_SIZE as u64 {
+        let max = cmp::min(max_len, MAX_VEC_SIZE);
+        if key_byte_size > max as u64 {
             return Err(encode::Error::OversizedVectorAllocation {
                 requested: key_byte_size as usize,
-                max: MAX_VEC_SIZE,
+                max,
             }
             .into());
         }
@@ -134,13 +158,31 @@ impl Serialize for Pair {
 impl Deserialize for Pair {
     fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
         let mut decoder = bytes;
-        Pair::decode(&mut decoder)
+        Pair::decode(&mut decoder, &DeserializeOptions::UNLIMITED)
     }
 }
 
 impl Pair {
-    pub(crate) fn decode<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Error> {
-        Ok(Pair { key: Key::decode(r)?, value: Decodable::consensus_decode(r)? })
+    pub(crate) fn decode<R: BufRead + ?Sized>(
+        r: &mut R,
+        options: &DeserializeOptions,
+    ) -> Result<Self, Error> {
+        let key = Key::decode(r, options.max_key_len)?;
+
+        let VarInt(len): VarInt = Decodable::consensus_decode(r)?;
+        let max = cmp::min(options.max_value_len, MAX_VEC_SIZE);
+        if len > max as u64 {
+            return Err(
+                encode::Error::OversizedVectorAllocation { requested: len as usize, max }.into()
+            );
+        }
+        // Memory is only allocated as the value is read, not up front for the claimed length.
+        let mut value = Vec::new();
+        if r.read_to_limit(&mut value, len).map_err(encode::Error::Io)? != len as usize {
+            return Err(encode::Error::Io(io::ErrorKind::UnexpectedEof.into()).into());
+        }
+
+        Ok(Pair { key, value })
     }
 }
 
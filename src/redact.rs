@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Removing wallet data from a PSBT before sharing it.
+//!
+//! The key origins and global xpubs of a PSBT reveal the derivation structure of the wallet, and
+//! the scripts of its outputs which of them are change. Services that only look at part of a PSBT,
+//! e.g. one estimating its fee, do not need them. [`Psbt::redact`] removes them and returns what
+//! it removed, so they can be put back once the PSBT comes back.
+
+use core::mem;
+
+use crate::{CombineError, Input, Output, Psbt};
+
+/// The data [`Psbt::redact`] removes.
+///
+/// The [`Default`] removes the key origins, global xpubs and proprietary fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RedactOptions {
+    /// Remove the BIP 32 derivations and Taproot key origins of the inputs and outputs.
+    pub key_origins: bool,
+    /// Remove the global xpubs.
+    pub xpubs: bool,
+    /// Remove the proprietary fields of every map.
+    pub proprietary: bool,
+    /// Remove the unknown fields of every map.
+    pub unknown: bool,
+    /// Remove the redeem and witness scripts, Taproot internal keys and trees, and MuSig2
+    /// participants of the outputs.
+    pub output_scripts: bool,
+}
+
+impl RedactOptions {
+    /// Removes everything but what a fee estimator needs: the UTXOs and the scripts and
+    /// signatures of the inputs.
+    pub const FEE_ESTIMATION: Self = RedactOptions {
+        key_origins: true,
+        xpubs: true,
+        proprietary: true,
+        unknown: true,
+        output_scripts: true,
+    };
+}
+
+impl Default for RedactOptions {
+    fn default() -> Self {
+        RedactOptions {
+            key_origins: true,
+            xpubs: true,
+            proprietary: true,
+            unknown: false,
+            output_scripts: false,
+        }
+    }
+}
+
+impl Psbt {
+    /// Returns a copy of this PSBT without the data `options` selects, and that data.
+    ///
+    /// ```
+    /// # use psbt_v0::{PsbtBuilder, RedactOptions};
+    /// # let psbt = PsbtBuilder::new().input(Default::default()).build();
+    /// let (mut shared, redaction) = psbt.redact(RedactOptions::FEE_ESTIMATION);
+    /// // Send `shared` to the service, then restore the data it did not see.
+    /// redaction.restore(&mut shared)?;
+    /// assert_eq!(shared, psbt);
+    /// # Ok::<_, psbt_v0::CombineError>(())
+    /// ```
+    pub fn redact(&self, options: RedactOptions) -> (Psbt, Redaction) {
+        let mut redacted = self.clone();
+        let mut removed = Psbt {
+            unsigned_tx: self.unsigned_tx.clone(),
+            version: self.version,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: vec![Input::default(); self.inputs.len()],
+            outputs: vec![Output::default(); self.outputs.len()],
+        };
+
+        if options.xpubs {
+            removed.xpub = mem::take(&mut redacted.xpub);
+        }
+        if options.proprietary {
+            removed.proprietary = mem::take(&mut redacted.proprietary);
+        }
+        if options.unknown {
+            removed.unknown = mem::take(&mut redacted.unknown);
+        }
+        for (input, removed) in redacted.inputs.iter_mut().zip(&mut removed.inputs) {
+            if options.key_origins {
+                removed.bip32_derivation = mem::take(&mut input.bip32_derivation);
+                removed.tap_key_origins = mem::take(&mut input.tap_key_origins);
+            }
+            if options.proprietary {
+                removed.proprietary = mem::take(&mut input.proprietary);
+            }
+            if options.unknown {
+                removed.unknown = mem::take(&mut input.unknown);
+            }
+        }
+        for (output, removed) in redacted.outputs.iter_mut().zip(&mut removed.outputs) {
+            if options.key_origins {
+                removed.bip32_derivation = mem::take(&mut output.bip32_derivation);
+                removed.tap_key_origins = mem::take(&mut output.tap_key_origins);
+            }
+            if options.output_scripts {
+                removed.redeem_script = output.redeem_script.take();
+                removed.witness_script = output.witness_script.take();
+                removed.tap_internal_key = output.tap_internal_key.take();
+                removed.tap_tree = output.tap_tree.take();
+                removed.musig2_participant_pubkeys =
+                    mem::take(&mut output.musig2_participant_pubkeys);
+            }
+            if options.proprietary {
+                removed.proprietary = mem::take(&mut output.proprietary);
+            }
+            if options.unknown {
+                removed.unknown = mem::take(&mut output.unknown);
+            }
+        }
+        (redacted, Redaction { removed })
+    }
+}
+
+/// The data removed by [`Psbt::redact`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redaction {
+    removed: Psbt,
+}
+
+impl Redaction {
+    /// Returns the removed data, as a PSBT for the same transaction holding only that data.
+    pub fn removed(&self) -> &Psbt { &self.removed }
+
+    /// Puts the removed data back into `psbt`, a redacted PSBT or one derived from it.
+    ///
+    /// # Errors
+    ///
+    /// If `psbt` is for a different unsigned transaction or has different values for the removed
+    /// fields, see [`Psbt::combine_many`].
+    pub fn restore(self, psbt: &mut Psbt) -> Result<(), CombineError> {
+        psbt.combine_many(core::iter::once(self.removed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::{absolute, transaction, Amount, ScriptBuf, Transaction, TxIn, TxOut};
+
+    use super::*;
+    use crate::raw::ProprietaryKey;
+
+    #[test]
+    fn redact() {
+        let secp = Secp256k1::new();
+        let pk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap().public_key(&secp);
+        let origin =
+            (Fingerprint::from([1; 4]), "m/84'/0'/0'/1/3".parse::<DerivationPath>().unwrap());
+        let xpub = "xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL".parse::<Xpub>().unwrap();
+        let witness_script = ScriptBuf::from_bytes(vec![0x51]);
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(5_000),
+                script_pubkey: witness_script.to_p2wsh(),
+            }],
+        })
+        .unwrap();
+        psbt.xpub.insert(xpub, origin.clone());
+        let proprietary = ProprietaryKey { prefix: b"wallet".to_vec(), subtype: 0, key: vec![] };
+        psbt.proprietary.insert(proprietary.clone(), vec![1]);
+        psbt.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new() });
+        psbt.inputs[0].bip32_derivation.insert(pk, origin.clone());
+        psbt.inputs[0].proprietary.insert(proprietary, vec![2]);
+        psbt.outputs[0].bip32_derivation.insert(pk, origin);
+        psbt.outputs[0].witness_script = Some(witness_script);
+
+        let (redacted, redaction) = psbt.redact(RedactOptions::default());
+        assert!(redacted.xpub.is_empty() && redacted.proprietary.is_empty());
+        assert!(redacted.inputs[0].bip32_derivation.is_empty());
+        assert!(redacted.inputs[0].proprietary.is_empty());
+        assert!(redacted.outputs[0].bip32_derivation.is_empty());
+        assert!(redacted.outputs[0].witness_script.is_some());
+        assert_eq!(redacted.inputs[0].witness_utxo, psbt.inputs[0].witness_utxo);
+        assert!(redaction.removed().inputs[0].witness_utxo.is_none());
+
+        let (mut redacted, redaction) = psbt.redact(RedactOptions::FEE_ESTIMATION);
+        assert!(redacted.outputs[0].witness_script.is_none());
+        // The recipient may add data, e.g. a signature, before sending the PSBT back.
+        redacted.inputs[0].final_script_witness = Some(Default::default());
+        redaction.restore(&mut redacted).unwrap();
+        assert_eq!(redacted.outputs[0], psbt.outputs[0]);
+        assert_eq!(redacted.xpub, psbt.xpub);
+        assert_eq!(redacted.inputs[0].bip32_derivation, psbt.inputs[0].bip32_derivation);
+
+        let (redacted, redaction) = psbt.redact(RedactOptions::default());
+        let mut other = redacted.clone();
+        other.unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+        assert!(matches!(redaction.restore(&mut other), Err(CombineError::TxidMismatch { .. })));
+    }
+}
@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Reordering the inputs and outputs of a PSBT.
+//!
+//! The inputs and outputs of the unsigned transaction and the PSBT input and output maps are
+//! parallel vectors, reordering one without the other silently attaches fields to the wrong
+//! input or output. These helpers permute both together.
+
+use core::fmt;
+
+use bitcoin::hashes::Hash as _;
+#[cfg(feature = "rand")]
+use bitcoin::secp256k1::rand::{seq::SliceRandom, Rng};
+use bitcoin::{TxIn, TxOut};
+
+use crate::prelude::*;
+use crate::{Input, Output, Psbt};
+
+impl Psbt {
+    /// Sorts the inputs and outputs as described by BIP 69.
+    ///
+    /// Inputs are sorted by the txid of the spent output, compared in the byte order it is
+    /// displayed in, then by its index. Outputs are sorted by value, then by script pubkey.
+    ///
+    /// # Errors
+    ///
+    /// If an input is signed or finalized, reordering changes the transaction it signs. If the
+    /// number of input or output maps does not match the unsigned transaction.
+    pub fn sort_bip69(&mut self) -> Result<(), ReorderError> {
+        self.check_reorderable()?;
+        let mut inputs = self.take_inputs();
+        inputs.sort_by(|(a, _), (b, _)| {
+            let (a, b) = (a.previous_output, b.previous_output);
+            let (mut a_txid, mut b_txid) = (a.txid.to_byte_array(), b.txid.to_byte_array());
+            a_txid.reverse();
+            b_txid.reverse();
+            (a_txid, a.vout).cmp(&(b_txid, b.vout))
+        });
+        self.put_inputs(inputs);
+
+        let mut outputs = self.take_outputs();
+        outputs.sort_by(|(a, _), (b, _)| {
+            (a.value, a.script_pubkey.as_bytes()).cmp(&(b.value, b.script_pubkey.as_bytes()))
+        });
+        self.put_outputs(outputs);
+        Ok(())
+    }
+
+    /// Shuffles the inputs and outputs in a random order drawn from `rng`.
+    ///
+    /// # Errors
+    ///
+    /// If an input is signed or finalized, reordering changes the transaction it signs. If the
+    /// number of input or output maps does not match the unsigned transaction.
+    #[cfg(feature = "rand")]
+    pub fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<(), ReorderError> {
+        self.check_reorderable()?;
+        let mut inputs = self.take_inputs();
+        inputs.shuffle(rng);
+        self.put_inputs(inputs);
+
+        let mut outputs = self.take_outputs();
+        outputs.shuffle(rng);
+        self.put_outputs(outputs);
+        Ok(())
+    }
+
+    fn check_reorderable(&self) -> Result<(), ReorderError> {
+        if self.inputs.len() != self.unsigned_tx.input.len() {
+            return Err(ReorderError::InputCountMismatch {
+                txins: self.unsigned_tx.input.len(),
+                maps: self.inputs.len(),
+            });
+        }
+        if self.outputs.len() != self.unsigned_tx.output.len() {
+            return Err(ReorderError::OutputCountMismatch {
+                txouts: self.unsigned_tx.output.len(),
+                maps: self.outputs.len(),
+            });
+        }
+        for (index, input) in self.inputs.iter().enumerate() {
+            if !input.partial_sigs.is_empty()
+                || input.tap_key_sig.is_some()
+                || !input.tap_script_sigs.is_empty()
+                || !input.musig2_partial_sigs.is_empty()
+                || input.is_finalized()
+            {
+                return Err(ReorderError::Signed { index });
+            }
+        }
+        Ok(())
+    }
+
+    fn take_inputs(&mut self) -> Vec<(TxIn, Input)> {
+        let txins = core::mem::take(&mut self.unsigned_tx.input);
+        txins.into_iter().zip(core::mem::take(&mut self.inputs)).collect()
+    }
+
+    fn put_inputs(&mut self, inputs: Vec<(TxIn, Input)>) {
+        (self.unsigned_tx.input, self.inputs) = inputs.into_iter().unzip();
+    }
+
+    fn take_outputs(&mut self) -> Vec<(TxOut, Output)> {
+        let txouts = core::mem::take(&mut self.unsigned_tx.output);
+        txouts.into_iter().zip(core::mem::take(&mut self.outputs)).collect()
+    }
+
+    fn put_outputs(&mut self, outputs: Vec<(TxOut, Output)>) {
+        (self.unsigned_tx.output, self.outputs) = outputs.into_iter().unzip();
+    }
+}
+
+/// Error reordering the inputs and outputs of a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReorderError {
+    /// The input has signatures or is finalized.
+    Signed {
+        /// The index of the input.
+        index: usize,
+    },
+    /// The number of input maps does not match the number of unsigned transaction inputs.
+    InputCountMismatch {
+        /// The number of unsigned transaction inputs.
+        txins: usize,
+        /// The number of input maps.
+        maps: usize,
+    },
+    /// The number of output maps does not match the number of unsigned transaction outputs.
+    OutputCountMismatch {
+        /// The number of unsigned transaction outputs.
+        txouts: usize,
+        /// The number of output maps.
+        maps: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(ReorderError);
+
+impl fmt::Display for ReorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ReorderError::*;
+
+        match *self {
+            Signed { index } =>
+                write!(f, "input {} is signed, reordering would invalidate it", index),
+            InputCountMismatch { txins, maps } =>
+                write!(f, "the transaction has {} inputs but there are {} input maps", txins, maps),
+            OutputCountMismatch { txouts, maps } => write!(
+                f,
+                "the transaction has {} outputs but there are {} output maps",
+                txouts, maps
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReorderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ReorderError::*;
+
+        match *self {
+            Signed { .. } | InputCountMismatch { .. } | OutputCountMismatch { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{
+        absolute, transaction, Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    use super::*;
+
+    fn txin(first_byte: u8, vout: u32) -> TxIn {
+        // The last byte of the array is the first byte of the displayed txid.
+        let mut txid = [0; 32];
+        txid[31] = first_byte;
+        txid[0] = 0xff - first_byte;
+        TxIn {
+            previous_output: OutPoint { txid: Txid::from_byte_array(txid), vout },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sort_bip69() {
+        let txout = |sats, script: &[u8]| TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![txin(2, 0), txin(1, 1), txin(1, 0)],
+            output: vec![txout(2, &[0x51]), txout(1, &[0x52]), txout(1, &[0x51])],
+        })
+        .unwrap();
+        for (i, input) in psbt.inputs.iter_mut().enumerate() {
+            input.sha256_preimages.insert(Hash::hash(&[i as u8]), vec![i as u8]);
+        }
+        for (i, output) in psbt.outputs.iter_mut().enumerate() {
+            output.unknown.insert(crate::raw::Key::new(0xf0), vec![i as u8]);
+        }
+
+        psbt.sort_bip69().unwrap();
+        let outpoints =
+            psbt.unsigned_tx.input.iter().map(|txin| txin.previous_output).collect::<Vec<_>>();
+        assert_eq!(
+            outpoints,
+            vec![
+                txin(1, 0).previous_output,
+                txin(1, 1).previous_output,
+                txin(2, 0).previous_output
+            ]
+        );
+        // The maps moved with their inputs and outputs.
+        let preimages = psbt.inputs.iter().map(|input| input.sha256_preimages.values());
+        assert_eq!(
+            preimages.flatten().cloned().collect::<Vec<_>>(),
+            vec![vec![2], vec![1], vec![0]]
+        );
+        let values = psbt.outputs.iter().flat_map(|output| output.unknown.values());
+        assert_eq!(values.cloned().collect::<Vec<_>>(), vec![vec![2], vec![1], vec![0]]);
+        assert_eq!(psbt.unsigned_tx.output[0], txout(1, &[0x51]));
+
+        psbt.inputs[1].final_script_witness = Some(Witness::default());
+        assert_eq!(psbt.sort_bip69(), Err(ReorderError::Signed { index: 1 }));
+    }
+
+    #[test]
+    fn reorder_count_mismatch() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![txin(2, 0), txin(1, 0)],
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+
+        psbt.inputs.pop();
+        let expected = psbt.clone();
+        assert_eq!(psbt.sort_bip69(), Err(ReorderError::InputCountMismatch { txins: 2, maps: 1 }));
+        assert_eq!(psbt, expected);
+
+        psbt.inputs.push(Input::default());
+        psbt.outputs.push(Output::default());
+        assert_eq!(
+            psbt.sort_bip69(),
+            Err(ReorderError::OutputCountMismatch { txouts: 1, maps: 2 })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rand-std")]
+    fn shuffle() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..16).map(|i| txin(i, 0)).collect(),
+            output: vec![],
+        })
+        .unwrap();
+        for (i, input) in psbt.inputs.iter_mut().enumerate() {
+            input.unknown.insert(crate::raw::Key::new(0xf0), vec![i as u8]);
+        }
+
+        psbt.shuffle(&mut bitcoin::secp256k1::rand::thread_rng()).unwrap();
+        for (shuffled, input) in psbt.unsigned_tx.input.iter().zip(&psbt.inputs) {
+            let i = input.unknown.values().next().unwrap()[0];
+            assert_eq!(shuffled.previous_output, txin(i, 0).previous_output);
+        }
+    }
+}
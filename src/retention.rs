@@ -0,0 +1,147 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! What happens to unknown and proprietary fields as a PSBT goes through the roles.
+//!
+//! By default every role preserves them: signing does not touch them, combining takes the union
+//! and finalizing an input keeps them along with the UTXOs. Coordinators that use proprietary
+//! fields for their own bookkeeping may not want them to reach the parties a finalized PSBT is
+//! sent to. The `_with_retention` variants of the role methods apply a [`FieldRetention`] after
+//! the role is done, so the outcome does not depend on which code path ran.
+
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+
+#[cfg(feature = "miniscript")]
+use crate::prelude::*;
+#[cfg(feature = "miniscript")]
+use crate::FinalizeError;
+use crate::{Error, GetKey, Psbt, SigningErrors, SigningKeysMap};
+
+/// What to do with the unknown and proprietary fields of a PSBT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FieldRetention {
+    /// Keep them in every map, what the role methods do without a retention.
+    #[default]
+    Preserve,
+    /// Remove them from finalized inputs, and from the global map and the outputs once every
+    /// input is finalized.
+    DropAfterFinalize,
+    /// Remove them from every map.
+    DropAlways,
+}
+
+impl Psbt {
+    /// Removes the unknown and proprietary fields `retention` does not keep.
+    pub fn apply_field_retention(&mut self, retention: FieldRetention) {
+        let all = match retention {
+            FieldRetention::Preserve => return,
+            FieldRetention::DropAfterFinalize => false,
+            FieldRetention::DropAlways => true,
+        };
+        let mut finalized = true;
+        for input in &mut self.inputs {
+            if all || input.is_finalized() {
+                input.unknown.clear();
+                input.proprietary.clear();
+            } else {
+                finalized = false;
+            }
+        }
+        if all || finalized {
+            self.unknown.clear();
+            self.proprietary.clear();
+            for output in &mut self.outputs {
+                output.unknown.clear();
+                output.proprietary.clear();
+            }
+        }
+    }
+
+    /// Signs like [`Psbt::sign`] then applies `retention`, also if signing some inputs failed.
+    pub fn sign_with_retention<C, K>(
+        &mut self,
+        k: &K,
+        secp: &Secp256k1<C>,
+        retention: FieldRetention,
+    ) -> Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>
+    where
+        C: Signing + Verification,
+        K: GetKey,
+    {
+        let result = self.sign(k, secp);
+        self.apply_field_retention(retention);
+        result
+    }
+
+    /// Combines like [`Psbt::combine`] then applies `retention`.
+    pub fn combine_with_retention(
+        &mut self,
+        other: Self,
+        retention: FieldRetention,
+    ) -> Result<(), Error> {
+        self.combine(other)?;
+        self.apply_field_retention(retention);
+        Ok(())
+    }
+
+    /// Finalizes like [`Psbt::finalize_mut`] then applies `retention`, also if some inputs could
+    /// not be finalized.
+    #[cfg(feature = "miniscript")]
+    pub fn finalize_mut_with_retention(
+        &mut self,
+        retention: FieldRetention,
+    ) -> Result<(), BTreeMap<usize, FinalizeError>> {
+        let result = self.finalize_mut();
+        self.apply_field_retention(retention);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{absolute, transaction, ScriptBuf, Transaction, TxIn, TxOut};
+
+    use super::*;
+    use crate::raw::{self, ProprietaryKey};
+
+    #[test]
+    fn field_retention() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 2],
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        let unknown = raw::Key { type_value: 0x42, key_data: vec![] };
+        let proprietary = ProprietaryKey { prefix: b"coord".to_vec(), subtype: 0, key: vec![] };
+        psbt.unknown.insert(unknown.clone(), vec![1]);
+        psbt.outputs[0].proprietary.insert(proprietary.clone(), vec![1]);
+        for input in &mut psbt.inputs {
+            input.unknown.insert(unknown.clone(), vec![1]);
+            input.proprietary.insert(proprietary.clone(), vec![1]);
+        }
+        psbt.inputs[0].final_script_sig = Some(ScriptBuf::from_bytes(vec![0x51]));
+
+        let mut preserved = psbt.clone();
+        preserved.apply_field_retention(FieldRetention::Preserve);
+        assert_eq!(preserved, psbt);
+
+        let mut after_finalize = psbt.clone();
+        after_finalize.apply_field_retention(FieldRetention::DropAfterFinalize);
+        assert!(after_finalize.inputs[0].unknown.is_empty());
+        assert!(after_finalize.inputs[0].proprietary.is_empty());
+        assert_eq!(after_finalize.inputs[1], psbt.inputs[1]);
+        assert_eq!(after_finalize.unknown.len(), 1);
+
+        after_finalize.inputs[1].final_script_sig = Some(ScriptBuf::from_bytes(vec![0x51]));
+        after_finalize.apply_field_retention(FieldRetention::DropAfterFinalize);
+        assert!(after_finalize.unknown.is_empty());
+        assert!(after_finalize.outputs[0].proprietary.is_empty());
+
+        let mut combined = psbt.clone();
+        combined.combine_with_retention(psbt.clone(), FieldRetention::DropAlways).unwrap();
+        assert!(combined.unknown.is_empty());
+        assert!(combined.inputs.iter().all(|input| input.unknown.is_empty()));
+        assert!(combined.inputs.iter().all(|input| input.proprietary.is_empty()));
+    }
+}
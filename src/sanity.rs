@@ -0,0 +1,385 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Consistency checks a signer should run before signing.
+
+use core::fmt;
+
+use bitcoin::key::TapTweak;
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::{ScriptBuf, TapLeafHash, XOnlyPublicKey};
+use bitcoin_internals::write_err;
+
+use crate::{IndexOutOfBoundsError, Psbt, TapError};
+
+impl Psbt {
+    /// Checks that the data in the input at `input_index` is consistent.
+    ///
+    /// The checks are:
+    ///
+    /// - `non_witness_utxo` is the transaction spent by the input and, if both are present, agrees
+    ///   with `witness_utxo`.
+    /// - `redeem_script` hashes to the script pubkey and `witness_script` hashes to the witness
+    ///   program.
+    /// - For Taproot inputs, every control block in `tap_scripts` commits to its leaf in the output
+    ///   key, `tap_internal_key` and `tap_merkle_root` tweak to the output key, and
+    ///   `tap_merkle_root` agrees with `tap_scripts`.
+    /// - `sighash_type` is valid for the input's signing algorithm and every signature uses it.
+    ///
+    /// Fields that are absent are not checked, but the UTXO is required since without it none of
+    /// the scripts can be checked. Signers should run these checks to defend against PSBTs that
+    /// substitute a different UTXO, or scripts, than the one actually spent.
+    pub fn check_input<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        input_index: usize,
+    ) -> Result<(), CheckInputError> {
+        use CheckInputError::*;
+
+        let input = self.checked_input(input_index)?;
+        let spk = self.check_input_scripts(input_index)?.ok_or(MissingUtxo)?;
+
+        let taproot = spk.is_p2tr();
+        if taproot {
+            let output_key =
+                XOnlyPublicKey::from_slice(&spk.as_bytes()[2..]).map_err(|_| InvalidOutputKey)?;
+            for (control_block, (script, ver)) in &input.tap_scripts {
+                if !control_block.verify_taproot_commitment(secp, output_key, script) {
+                    let leaf_hash = TapLeafHash::from_script(script, *ver);
+                    return Err(ControlBlockMismatch { leaf_hash });
+                }
+            }
+            if let Some(internal_key) = input.tap_internal_key {
+                let (tweaked, _) = internal_key.tap_tweak(secp, input.tap_merkle_root);
+                if tweaked.to_inner() != output_key {
+                    return Err(InternalKeyMismatch);
+                }
+            }
+            input.validate_tap_merkle_root()?;
+        }
+
+        if let Some(sighash_type) = input.sighash_type {
+            let mismatch = if taproot {
+                let hash_ty = sighash_type.taproot_hash_ty().map_err(|_| InvalidSighashType)?;
+                input
+                    .tap_key_sig
+                    .iter()
+                    .chain(input.tap_script_sigs.values())
+                    .any(|sig| sig.sighash_type != hash_ty)
+            } else {
+                let hash_ty = sighash_type.ecdsa_hash_ty().map_err(|_| InvalidSighashType)?;
+                input.partial_sigs.values().any(|sig| sig.sighash_type != hash_ty)
+            };
+            if mismatch {
+                return Err(SighashTypeMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the UTXOs, redeem script and witness script of the input at `input_index`.
+    ///
+    /// Returns the script pubkey of the spent output, if it is known.
+    pub(crate) fn check_input_scripts(
+        &self,
+        input_index: usize,
+    ) -> Result<Option<&ScriptBuf>, CheckInputError> {
+        use CheckInputError::*;
+
+        let input = self.checked_input(input_index)?;
+        let prevout = self.unsigned_tx.input[input_index].previous_output;
+
+        let mut utxo = input.witness_utxo.as_ref();
+        if let Some(tx) = &input.non_witness_utxo {
+            if tx.compute_txid() != prevout.txid {
+                return Err(NonWitnessUtxoMismatch);
+            }
+            let txout = tx.output.get(prevout.vout as usize).ok_or(NonWitnessUtxoMismatch)?;
+            if utxo.map_or(false, |utxo| utxo != txout) {
+                return Err(WitnessUtxoMismatch);
+            }
+            utxo = Some(txout);
+        }
+        let spk = match utxo {
+            Some(utxo) => &utxo.script_pubkey,
+            None => return Ok(None),
+        };
+
+        let mut program = spk.as_script();
+        if let Some(redeem_script) = &input.redeem_script {
+            if redeem_script.to_p2sh() != *spk {
+                return Err(RedeemScriptMismatch);
+            }
+            program = redeem_script;
+        }
+        if let Some(witness_script) = &input.witness_script {
+            if witness_script.to_p2wsh() != *program {
+                return Err(WitnessScriptMismatch);
+            }
+        }
+
+        Ok(Some(spk))
+    }
+
+    /// Runs [`Psbt::check_input`] on every input, and checks that no two inputs spend the same
+    /// outpoint as [`Psbt::check_duplicate_inputs`] does.
+    pub fn sanity_check<C: Verification>(&self, secp: &Secp256k1<C>) -> Result<(), SanityError> {
+        let duplicates = self.check_duplicate_inputs().err().unwrap_or_default();
+        for input_index in 0..self.inputs.len() {
+            if duplicates.contains(&input_index) {
+                let error = CheckInputError::DuplicateOutpoint;
+                return Err(SanityError { input_index, error });
+            }
+            self.check_input(secp, input_index)
+                .map_err(|error| SanityError { input_index, error })?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`Psbt::check_input`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CheckInputError {
+    /// The input index is out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// Neither `witness_utxo` nor `non_witness_utxo` is present.
+    MissingUtxo,
+    /// `non_witness_utxo` is not the transaction spent by the input.
+    NonWitnessUtxoMismatch,
+    /// `witness_utxo` is not the output of `non_witness_utxo` spent by the input.
+    WitnessUtxoMismatch,
+    /// `redeem_script` does not hash to the script pubkey.
+    RedeemScriptMismatch,
+    /// `witness_script` does not hash to the witness program.
+    WitnessScriptMismatch,
+    /// The script pubkey of a Taproot output does not contain a valid key.
+    InvalidOutputKey,
+    /// A control block in `tap_scripts` does not commit to its leaf in the output key.
+    ControlBlockMismatch {
+        /// The leaf hash of the leaf.
+        leaf_hash: TapLeafHash,
+    },
+    /// `tap_internal_key` tweaked with `tap_merkle_root` is not the output key.
+    InternalKeyMismatch,
+    /// The Taproot fields are inconsistent.
+    Tap(TapError),
+    /// `sighash_type` is not valid for the input's signing algorithm.
+    InvalidSighashType,
+    /// A signature does not use the sighash type in `sighash_type`.
+    SighashTypeMismatch,
+    /// The input spends the same outpoint as an earlier input, only returned by
+    /// [`Psbt::sanity_check`].
+    DuplicateOutpoint,
+}
+
+bitcoin_internals::impl_from_infallible!(CheckInputError);
+
+impl fmt::Display for CheckInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CheckInputError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            MissingUtxo => f.write_str("the UTXO spent by the input is missing"),
+            NonWitnessUtxoMismatch =>
+                f.write_str("the non-witness UTXO is not the transaction spent by the input"),
+            WitnessUtxoMismatch =>
+                f.write_str("the witness UTXO does not match the non-witness UTXO"),
+            RedeemScriptMismatch =>
+                f.write_str("the redeem script does not hash to the script pubkey"),
+            WitnessScriptMismatch =>
+                f.write_str("the witness script does not hash to the witness program"),
+            InvalidOutputKey => f.write_str("the taproot output key is invalid"),
+            ControlBlockMismatch { leaf_hash } => write!(
+                f,
+                "the control block of leaf {} does not commit to the output key",
+                leaf_hash
+            ),
+            InternalKeyMismatch =>
+                f.write_str("the tweaked internal key does not match the output key"),
+            Tap(ref e) => write_err!(f, "taproot"; e),
+            InvalidSighashType =>
+                f.write_str("the sighash type is not valid for the input's signing algorithm"),
+            SighashTypeMismatch => f.write_str("a signature does not use the input's sighash type"),
+            DuplicateOutpoint => f.write_str("the outpoint is spent by an earlier input"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheckInputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use CheckInputError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            Tap(ref e) => Some(e),
+            MissingUtxo
+            | NonWitnessUtxoMismatch
+            | WitnessUtxoMismatch
+            | RedeemScriptMismatch
+            | WitnessScriptMismatch
+            | InvalidOutputKey
+            | ControlBlockMismatch { .. }
+            | InternalKeyMismatch
+            | InvalidSighashType
+            | SighashTypeMismatch
+            | DuplicateOutpoint => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for CheckInputError {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+impl From<TapError> for CheckInputError {
+    fn from(e: TapError) -> Self { Self::Tap(e) }
+}
+
+/// Error returned by [`Psbt::sanity_check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanityError {
+    /// The index of the first input that failed the checks.
+    pub input_index: usize,
+    /// The failed check.
+    pub error: CheckInputError,
+}
+
+impl fmt::Display for SanityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_err!(f, "input {} failed the sanity checks", self.input_index; self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SanityError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { Some(&self.error) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::taproot::{LeafVersion, TaprootBuilder};
+    use bitcoin::{
+        absolute, ecdsa, transaction, Amount, EcdsaSighashType, OutPoint, ScriptBuf, Transaction,
+        TxIn, TxOut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn check_input() {
+        let secp = Secp256k1::new();
+        let sk = bitcoin::secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(sk.public_key(&secp));
+        let xonly = XOnlyPublicKey::from(pk.inner);
+
+        let witness_script = ScriptBuf::new_p2pk(&pk);
+        let leaf = ScriptBuf::from_bytes(vec![0x51]);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, leaf.clone())
+            .unwrap()
+            .finalize(&secp, xonly)
+            .unwrap();
+        let prev_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(1_000),
+                    script_pubkey: ScriptBuf::new_p2sh(&witness_script.to_p2wsh().script_hash()),
+                },
+                TxOut {
+                    value: Amount::from_sat(2_000),
+                    script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+                },
+            ],
+        };
+        let txid = prev_tx.compute_txid();
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..2)
+                .map(|vout| TxIn { previous_output: OutPoint { txid, vout }, ..Default::default() })
+                .collect(),
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        assert_eq!(psbt.check_input(&secp, 0), Err(CheckInputError::MissingUtxo));
+
+        psbt.inputs[0].non_witness_utxo = Some(prev_tx.clone());
+        psbt.inputs[0].witness_utxo = Some(prev_tx.output[1].clone());
+        assert_eq!(psbt.check_input(&secp, 0), Err(CheckInputError::WitnessUtxoMismatch));
+        psbt.inputs[0].witness_utxo = Some(prev_tx.output[0].clone());
+        psbt.inputs[0].redeem_script = Some(witness_script.clone());
+        assert_eq!(psbt.check_input(&secp, 0), Err(CheckInputError::RedeemScriptMismatch));
+        psbt.inputs[0].redeem_script = Some(witness_script.to_p2wsh());
+        psbt.inputs[0].witness_script = Some(ScriptBuf::new());
+        assert_eq!(psbt.check_input(&secp, 0), Err(CheckInputError::WitnessScriptMismatch));
+        psbt.inputs[0].witness_script = Some(witness_script);
+
+        psbt.inputs[0].sighash_type = Some(EcdsaSighashType::Single.into());
+        let sig = ecdsa::Signature {
+            signature: secp.sign_ecdsa(&bitcoin::secp256k1::Message::from_digest([1; 32]), &sk),
+            sighash_type: EcdsaSighashType::All,
+        };
+        psbt.inputs[0].partial_sigs.insert(pk, sig);
+        assert_eq!(psbt.check_input(&secp, 0), Err(CheckInputError::SighashTypeMismatch));
+        psbt.inputs[0].sighash_type = Some(EcdsaSighashType::All.into());
+        assert_eq!(psbt.check_input(&secp, 0), Ok(()));
+
+        let leaf = (leaf, LeafVersion::TapScript);
+        let control_block = spend_info.control_block(&leaf).unwrap();
+        psbt.inputs[1].witness_utxo = Some(prev_tx.output[1].clone());
+        psbt.inputs[1].tap_internal_key = Some(xonly);
+        assert_eq!(psbt.check_input(&secp, 1), Err(CheckInputError::InternalKeyMismatch));
+        psbt.inputs[1].tap_merkle_root = spend_info.merkle_root();
+        let other = ScriptBuf::from_bytes(vec![0x52]);
+        psbt.inputs[1].tap_scripts.insert(control_block.clone(), (other.clone(), leaf.1));
+        let leaf_hash = TapLeafHash::from_script(&other, leaf.1);
+        assert_eq!(
+            psbt.check_input(&secp, 1),
+            Err(CheckInputError::ControlBlockMismatch { leaf_hash })
+        );
+        psbt.inputs[1].tap_scripts.insert(control_block, leaf);
+        assert_eq!(psbt.check_input(&secp, 1), Ok(()));
+        psbt.inputs[1].sighash_type = Some(EcdsaSighashType::SinglePlusAnyoneCanPay.into());
+        assert_eq!(psbt.check_input(&secp, 1), Ok(()));
+        psbt.inputs[1].sighash_type = Some(crate::PsbtSighashType::from_u32(0x04));
+        assert_eq!(psbt.check_input(&secp, 1), Err(CheckInputError::InvalidSighashType));
+        assert_eq!(
+            psbt.sanity_check(&secp),
+            Err(SanityError { input_index: 1, error: CheckInputError::InvalidSighashType })
+        );
+
+        // A non-witness UTXO that is not the spent transaction.
+        psbt.unsigned_tx.input[0].previous_output.txid = bitcoin::Txid::all_zeros();
+        assert_eq!(psbt.check_input(&secp, 0), Err(CheckInputError::NonWitnessUtxoMismatch));
+    }
+
+    #[test]
+    fn sanity_check_duplicate_inputs() {
+        let secp = Secp256k1::new();
+        let previous_output = OutPoint { txid: bitcoin::Txid::all_zeros(), vout: 0 };
+        let utxo = TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { previous_output, ..Default::default() }; 2],
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        for input in &mut psbt.inputs {
+            input.witness_utxo = Some(utxo.clone());
+        }
+        assert_eq!(
+            psbt.sanity_check(&secp),
+            Err(SanityError { input_index: 1, error: CheckInputError::DuplicateOutpoint })
+        );
+
+        psbt.unsigned_tx.input[1].previous_output.vout = 1;
+        assert_eq!(psbt.sanity_check(&secp), Ok(()));
+    }
+}
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Recognition of standard script templates.
+//!
+//! Just enough script parsing to reason about the common single key and multisig scripts without
+//! requiring a full script interpreter.
+
+use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+use bitcoin::opcodes::{Class, ClassifyContext};
+use bitcoin::script::{self, Instruction};
+use bitcoin::{PublicKey, Script, XOnlyPublicKey};
+
+use crate::prelude::*;
+
+/// Parses a bare multisig script `<m> <pk_1> ... <pk_n> <n> OP_CHECKMULTISIG`.
+///
+/// Returns the threshold `m` and the public keys.
+pub(crate) fn multisig(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let mut instructions = script.instructions();
+
+    let threshold = match instructions.next()? {
+        Ok(Instruction::Op(op)) => pushnum(op)?,
+        _ => return None,
+    };
+
+    let mut keys = vec![];
+    let num_keys = loop {
+        match instructions.next()? {
+            Ok(Instruction::PushBytes(bytes)) =>
+                keys.push(PublicKey::from_slice(bytes.as_bytes()).ok()?),
+            Ok(Instruction::Op(op)) => break pushnum(op)?,
+            Err(_) => return None,
+        }
+    };
+
+    match instructions.next()? {
+        Ok(Instruction::Op(op)) if op == OP_CHECKMULTISIG => {}
+        _ => return None,
+    }
+
+    if instructions.next().is_some() || num_keys != keys.len() || threshold > num_keys {
+        return None;
+    }
+    Some((threshold, keys))
+}
+
+/// Parses a tapscript single key script `<xonly> OP_CHECKSIG`.
+pub(crate) fn tap_pk(script: &Script) -> Option<XOnlyPublicKey> {
+    let mut instructions = script.instructions();
+
+    let key = match instructions.next()? {
+        Ok(Instruction::PushBytes(bytes)) => XOnlyPublicKey::from_slice(bytes.as_bytes()).ok()?,
+        _ => return None,
+    };
+    match instructions.next()? {
+        Ok(Instruction::Op(op)) if op == OP_CHECKSIG => {}
+        _ => return None,
+    }
+
+    if instructions.next().is_some() {
+        return None;
+    }
+    Some(key)
+}
+
+/// Parses a tapscript multisig script (as produced by miniscript `multi_a`):
+///
+/// `<xonly_1> OP_CHECKSIG <xonly_2> OP_CHECKSIGADD ... <xonly_n> OP_CHECKSIGADD <m> OP_NUMEQUAL`
+///
+/// Returns the threshold `m` and the x-only public keys.
+pub(crate) fn tap_multi_a(script: &Script) -> Option<(usize, Vec<XOnlyPublicKey>)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    if instructions.len() < 4 || instructions.len() % 2 != 0 {
+        return None;
+    }
+
+    let (keys, tail) = instructions.split_at(instructions.len() - 2);
+
+    let mut pks = vec![];
+    for (i, pair) in keys.chunks(2).enumerate() {
+        let expected = if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+        match pair {
+            [Instruction::PushBytes(bytes), Instruction::Op(op)] if *op == expected =>
+                pks.push(XOnlyPublicKey::from_slice(bytes.as_bytes()).ok()?),
+            _ => return None,
+        }
+    }
+
+    let threshold = match tail {
+        [threshold, Instruction::Op(op)] if *op == OP_NUMEQUAL => script_num(threshold)?,
+        _ => return None,
+    };
+
+    if threshold == 0 || threshold > pks.len() {
+        return None;
+    }
+    Some((threshold, pks))
+}
+
+/// Returns the value of a small number push (`OP_1` through `OP_16`).
+fn pushnum(op: bitcoin::Opcode) -> Option<usize> {
+    match op.classify(ClassifyContext::Legacy) {
+        Class::PushNum(n) if n >= 1 => Some(n as usize),
+        _ => None,
+    }
+}
+
+/// Returns the non-negative number pushed by `instruction`.
+fn script_num(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Op(op) => pushnum(*op),
+        Instruction::PushBytes(bytes) => {
+            let n = script::read_scriptint(bytes.as_bytes()).ok()?;
+            usize::try_from(n).ok()
+        }
+    }
+}
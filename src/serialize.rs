@@ -17,9 +17,10 @@ use bitcoin::taproot::{
 };
 use bitcoin::{ecdsa, io, taproot, PublicKey, ScriptBuf, Transaction, TxOut, VarInt, Witness};
 
-use super::map::{Input, Map, Output, PsbtSighashType};
+use super::map::{Map, PsbtSighashType};
 use crate::prelude::*;
-use crate::{Error, Psbt};
+use crate::stream::{write_all, MAGIC_BYTES, PSBT_SERPARATOR};
+use crate::{DecodeError, Error, Psbt, PsbtReader};
 
 /// A trait for serializing a value as raw data for insertion into PSBT
 /// key-value maps.
@@ -46,26 +47,19 @@ impl Psbt {
     }
 
     /// Serialize the PSBT into a writer.
+    ///
+    /// All input and output maps are written, even if their number does not match the unsigned
+    /// transaction. Use [`PsbtWriter`](crate::PsbtWriter) to write the input and output maps one at a time instead.
     pub fn serialize_to_writer(&self, w: &mut impl Write) -> io::Result<usize> {
-        let mut written_len = 0;
-
-        fn write_all(w: &mut impl Write, data: &[u8]) -> io::Result<usize> {
-            w.write_all(data).map(|_| data.len())
-        }
-
-        // magic
-        written_len += write_all(w, b"psbt")?;
-        // separator
-        written_len += write_all(w, &[0xff])?;
-
+        let mut written_len = write_all(w, MAGIC_BYTES)?;
+        written_len += write_all(w, &[PSBT_SERPARATOR])?;
         written_len += write_all(w, &self.serialize_map())?;
 
-        for i in &self.inputs {
-            written_len += write_all(w, &i.serialize_map())?;
+        for input in &self.inputs {
+            written_len += write_all(w, &input.serialize_map())?;
         }
-
-        for i in &self.outputs {
-            written_len += write_all(w, &i.serialize_map())?;
+        for output in &self.outputs {
+            written_len += write_all(w, &output.serialize_map())?;
         }
 
         Ok(written_len)
@@ -77,50 +71,155 @@ impl Psbt {
     }
 
     /// Deserialize a value from raw binary data read from a `BufRead` object.
+    ///
+    /// Use [`PsbtReader`] to read the input and output maps one at a time instead.
     pub fn deserialize_from_reader<R: io::BufRead>(r: &mut R) -> Result<Self, Error> {
-        const MAGIC_BYTES: &[u8] = b"psbt";
+        PsbtReader::new(r)?.into_psbt()
+    }
 
-        let magic: [u8; 4] = Decodable::consensus_decode(r)?;
-        if magic != MAGIC_BYTES {
-            return Err(Error::InvalidMagic);
-        }
+    /// Deserialize a value from raw binary data, enforcing the limits in `options`.
+    pub fn deserialize_with_options(
+        mut bytes: &[u8],
+        options: &DeserializeOptions,
+    ) -> Result<Self, DecodeError> {
+        Self::deserialize_from_reader_with_options(&mut bytes, options)
+    }
 
-        const PSBT_SERPARATOR: u8 = 0xff_u8;
-        let separator: u8 = Decodable::consensus_decode(r)?;
-        if separator != PSBT_SERPARATOR {
-            return Err(Error::InvalidSeparator);
+    /// Deserialize a value from raw binary data read from a `BufRead` object, enforcing the
+    /// limits in `options`.
+    ///
+    /// Decoding stops as soon as a limit is exceeded, before memory is allocated for the data
+    /// beyond it.
+    ///
+    /// # Errors
+    ///
+    /// [`DecodeError::TooLarge`] if the PSBT exceeds [`DeserializeOptions::max_size`], and
+    /// [`Error::TooManyInputs`] or [`Error::TooManyOutputs`] if it exceeds the corresponding
+    /// limit. A key or value longer than allowed is reported as
+    /// [`encode::Error::OversizedVectorAllocation`].
+    pub fn deserialize_from_reader_with_options<R: io::BufRead>(
+        r: &mut R,
+        options: &DeserializeOptions,
+    ) -> Result<Self, DecodeError> {
+        let mut limited = LimitedReader { inner: r, remaining: options.max_size, exceeded: false };
+        match PsbtReader::with_options(&mut limited, *options).and_then(PsbtReader::into_psbt) {
+            Err(_) if limited.exceeded => Err(DecodeError::TooLarge { max: options.max_size }),
+            res => Ok(res?),
         }
+    }
 
-        let mut global = Psbt::decode_global(r)?;
-        global.unsigned_tx_checks()?;
-
-        let inputs: Vec<Input> = {
-            let inputs_len: usize = (global.unsigned_tx.input).len();
-
-            let mut inputs: Vec<Input> = Vec::with_capacity(inputs_len);
-
-            for _ in 0..inputs_len {
-                inputs.push(Input::decode(r)?);
-            }
+    /// Deserialize a value from raw binary data read from a `BufRead` object, reading at most
+    /// `max_bytes` bytes.
+    ///
+    /// Decoding stops as soon as the budget is exhausted, the rest of the stream is neither read
+    /// nor allocated for. Useful for services that accept PSBTs from untrusted peers.
+    ///
+    /// # Errors
+    ///
+    /// [`DecodeError::TooLarge`] if decoding the PSBT requires reading more than `max_bytes`
+    /// bytes.
+    pub fn from_reader_limited<R: io::BufRead>(
+        r: &mut R,
+        max_bytes: u64,
+    ) -> Result<Self, DecodeError> {
+        let mut limited = LimitedReader { inner: r, remaining: max_bytes, exceeded: false };
+        match Self::deserialize_from_reader(&mut limited) {
+            Err(_) if limited.exceeded => Err(DecodeError::TooLarge { max: max_bytes }),
+            res => Ok(res?),
+        }
+    }
+}
 
-            inputs
-        };
+/// Limits enforced while deserializing a PSBT from an untrusted source.
+///
+/// The [`Default`] limits match what Bitcoin Core accepts: a PSBT of at most 100 MB and keys and
+/// values no longer than the consensus limit for vectors, with no limit on the number of inputs
+/// and outputs other than what fits in the size limit.
+///
+/// ```
+/// # use psbt_v0::serialize::DeserializeOptions;
+/// let options = DeserializeOptions { max_size: 1_000_000, max_inputs: 100, ..Default::default() };
+/// # let _ = options;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeserializeOptions {
+    /// The maximum number of bytes of the serialized PSBT.
+    pub max_size: u64,
+    /// The maximum number of inputs of the unsigned transaction.
+    pub max_inputs: usize,
+    /// The maximum number of outputs of the unsigned transaction.
+    pub max_outputs: usize,
+    /// The maximum length of a key, excluding the key type.
+    pub max_key_len: usize,
+    /// The maximum length of a value.
+    pub max_value_len: usize,
+}
+
+impl DeserializeOptions {
+    /// The limits of [`Psbt::deserialize`], only those of consensus decoding.
+    pub(crate) const UNLIMITED: Self = DeserializeOptions {
+        max_size: u64::MAX,
+        max_inputs: usize::MAX,
+        max_outputs: usize::MAX,
+        max_key_len: encode::MAX_VEC_SIZE,
+        max_value_len: encode::MAX_VEC_SIZE,
+    };
+}
+
+impl Default for DeserializeOptions {
+    fn default() -> Self {
+        // Bitcoin Core's `MAX_FILE_SIZE_PSBT`.
+        DeserializeOptions { max_size: 100_000_000, ..DeserializeOptions::UNLIMITED }
+    }
+}
 
-        let outputs: Vec<Output> = {
-            let outputs_len: usize = (global.unsigned_tx.output).len();
+/// Reader adapter that reads at most `remaining` bytes and records any attempt to read more.
+struct LimitedReader<'a, R: io::BufRead> {
+    inner: &'a mut R,
+    remaining: u64,
+    exceeded: bool,
+}
 
-            let mut outputs: Vec<Output> = Vec::with_capacity(outputs_len);
+impl<'a, R: io::BufRead> LimitedReader<'a, R> {
+    /// Records whether the budget is exhausted while the inner reader has more bytes.
+    ///
+    /// Reading at the end of the inner reader gets nothing with or without the budget, so probing
+    /// for more data after reading exactly the budget does not exceed it.
+    fn check_exceeded(&mut self) -> io::Result<()> {
+        if !self.inner.fill_buf()?.is_empty() {
+            self.exceeded = true;
+        }
+        Ok(())
+    }
+}
 
-            for _ in 0..outputs_len {
-                outputs.push(Output::decode(r)?);
-            }
+impl<'a, R: io::BufRead> io::Read for LimitedReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 && !buf.is_empty() {
+            self.check_exceeded()?;
+            return Ok(0);
+        }
+        let len = core::cmp::min(buf.len() as u64, self.remaining) as usize;
+        let read = self.inner.read(&mut buf[..len])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
 
-            outputs
-        };
+impl<'a, R: io::BufRead> io::BufRead for LimitedReader<'a, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.remaining == 0 {
+            self.check_exceeded()?;
+            return Ok(&[]);
+        }
+        let buf = self.inner.fill_buf()?;
+        let cap = core::cmp::min(buf.len() as u64, self.remaining) as usize;
+        Ok(&buf[..cap])
+    }
 
-        global.inputs = inputs;
-        global.outputs = outputs;
-        Ok(global)
+    fn consume(&mut self, amount: usize) {
+        self.remaining -= amount as u64;
+        self.inner.consume(amount);
     }
 }
 impl_psbt_de_serialize!(Transaction);
@@ -259,6 +358,9 @@ impl Serialize for XOnlyPublicKey {
 
 impl Deserialize for XOnlyPublicKey {
     fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 32 {
+            return Err(Error::InvalidLength { expected: 32, found: bytes.len() });
+        }
         XOnlyPublicKey::from_slice(bytes).map_err(|_| Error::InvalidXOnlyPublicKey)
     }
 }
@@ -274,8 +376,7 @@ impl Deserialize for taproot::Signature {
         taproot::Signature::from_slice(bytes).map_err(|e| match e {
             SighashType(err) => Error::NonStandardSighashType(err.0),
             InvalidSignatureSize(_) => Error::InvalidTaprootSignature(e),
-            Secp256k1(..) => Error::InvalidTaprootSignature(e),
-            _ => todo!("handle this properly"),
+            _ => Error::InvalidTaprootSignature(e),
         })
     }
 }
@@ -292,8 +393,8 @@ impl Serialize for (XOnlyPublicKey, TapLeafHash) {
 
 impl Deserialize for (XOnlyPublicKey, TapLeafHash) {
     fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
-        if bytes.len() < 32 {
-            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        if bytes.len() != 64 {
+            return Err(Error::InvalidLength { expected: 64, found: bytes.len() });
         }
         let a: XOnlyPublicKey = Deserialize::deserialize(&bytes[..32])?;
         let b: TapLeafHash = Deserialize::deserialize(&bytes[32..])?;
@@ -395,12 +496,86 @@ impl Deserialize for TapTree {
     }
 }
 
+// MuSig2 participant public keys
+impl Serialize for Vec<secp256k1::PublicKey> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(33 * self.len());
+        for pk in self {
+            buf.extend(pk.serialize());
+        }
+        buf
+    }
+}
+
+impl Deserialize for Vec<secp256k1::PublicKey> {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        bytes.chunks(33).map(secp256k1::PublicKey::deserialize).collect()
+    }
+}
+
+// MuSig2 participant, aggregate key, and optional leaf hash
+impl Serialize for (secp256k1::PublicKey, secp256k1::PublicKey, Option<TapLeafHash>) {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(33 + 33 + 32);
+        buf.extend(self.0.serialize());
+        buf.extend(self.1.serialize());
+        if let Some(leaf_hash) = self.2 {
+            buf.extend(leaf_hash.as_byte_array());
+        }
+        buf
+    }
+}
+
+impl Deserialize for (secp256k1::PublicKey, secp256k1::PublicKey, Option<TapLeafHash>) {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 66 && bytes.len() != 98 {
+            return Err(Error::Musig2("invalid participant and aggregate key length"));
+        }
+        let participant = secp256k1::PublicKey::deserialize(&bytes[..33])?;
+        let aggregate = secp256k1::PublicKey::deserialize(&bytes[33..66])?;
+        let leaf_hash =
+            if bytes.len() == 98 { Some(TapLeafHash::deserialize(&bytes[66..])?) } else { None };
+        Ok((participant, aggregate, leaf_hash))
+    }
+}
+
+// Silent payment scan and spend keys
+impl Serialize for (secp256k1::PublicKey, secp256k1::PublicKey) {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(33 + 33);
+        buf.extend(self.0.serialize());
+        buf.extend(self.1.serialize());
+        buf
+    }
+}
+
+impl Deserialize for (secp256k1::PublicKey, secp256k1::PublicKey) {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != 66 {
+            return Err(Error::InvalidLength { expected: 66, found: bytes.len() });
+        }
+        let scan = secp256k1::PublicKey::deserialize(&bytes[..33])?;
+        let spend = secp256k1::PublicKey::deserialize(&bytes[33..])?;
+        Ok((scan, spend))
+    }
+}
+
+// Silent payment label
+impl Serialize for u32 {
+    fn serialize(&self) -> Vec<u8> { serialize(self) }
+}
+
+impl Deserialize for u32 {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> { Ok(encode::deserialize(bytes)?) }
+}
+
 // Helper function to compute key source len
 fn key_source_len(key_source: &KeySource) -> usize { 4 + 4 * (key_source.1).as_ref().len() }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::map::{Input, Map, Output};
 
     // Composes tree matching a given depth map, filled with dumb script leafs,
     // each of which consists of a single push-int op code, with int value
@@ -449,6 +624,74 @@ mod tests {
         assert_eq!(tree, tree_prime);
     }
 
+    #[test]
+    fn musig2_roundtrip() {
+        use crate::{Musig2PartialSig, Musig2PubNonce};
+
+        let secp = secp256k1::Secp256k1::new();
+        let pk = |byte| secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap().public_key(&secp);
+        let leaf_hash = TapLeafHash::from_byte_array([7; 32]);
+
+        let key = (pk(1), pk(3), Some(leaf_hash));
+        let bytes = key.serialize();
+        assert_eq!(bytes.len(), 98);
+        assert_eq!(<(_, _, _)>::deserialize(&bytes).unwrap(), key);
+        let key_path = (pk(1), pk(3), None);
+        assert_eq!(key_path.serialize(), bytes[..66]);
+        assert!(<(secp256k1::PublicKey, secp256k1::PublicKey, Option<TapLeafHash>)>::deserialize(
+            &bytes[..70]
+        )
+        .is_err());
+
+        let participants = vec![pk(1), pk(2)];
+        assert_eq!(
+            Vec::<secp256k1::PublicKey>::deserialize(&participants.serialize()).unwrap(),
+            participants
+        );
+
+        let nonce = Musig2PubNonce::from_byte_array([2; 66]);
+        assert_eq!(Musig2PubNonce::deserialize(&nonce.serialize()).unwrap(), nonce);
+        assert!(Musig2PubNonce::deserialize(&[2; 65]).is_err());
+        assert!(Musig2PartialSig::deserialize(&[2; 33]).is_err());
+
+        let mut input = Input::default();
+        input.musig2_participant_pubkeys.insert(pk(3), participants);
+        input.musig2_pub_nonces.insert(key, nonce);
+        input.musig2_partial_sigs.insert(key_path, Musig2PartialSig::from_byte_array([4; 32]));
+        let types = input.get_pairs().iter().map(|pair| pair.key.type_value).collect::<Vec<_>>();
+        assert_eq!(types, vec![0x1a, 0x1b, 0x1c]);
+        assert_eq!(Input::deserialize(&input.serialize_map()).unwrap(), input);
+    }
+
+    #[test]
+    fn silent_payments_roundtrip() {
+        use crate::DleqProof;
+
+        let secp = secp256k1::Secp256k1::new();
+        let pk = |byte| secp256k1::SecretKey::from_slice(&[byte; 32]).unwrap().public_key(&secp);
+
+        let mut input = Input::default();
+        input.sp_ecdh_shares.insert(pk(1), pk(2));
+        input.sp_dleq_proofs.insert(pk(1), DleqProof::from_byte_array([3; 64]));
+        let types = input.get_pairs().iter().map(|pair| pair.key.type_value).collect::<Vec<_>>();
+        assert_eq!(types, vec![0x1d, 0x1e]);
+        assert_eq!(Input::deserialize(&input.serialize_map()).unwrap(), input);
+
+        let output =
+            Output { sp_v0_info: Some((pk(1), pk(4))), sp_v0_label: Some(7), ..Default::default() };
+        let pairs = output.get_pairs();
+        assert_eq!(
+            pairs.iter().map(|pair| pair.key.type_value).collect::<Vec<_>>(),
+            vec![0x09, 0x0a]
+        );
+        assert_eq!(pairs[1].value, vec![7, 0, 0, 0]);
+        assert_eq!(Output::deserialize(&output.serialize_map()).unwrap(), output);
+
+        assert!(DleqProof::deserialize(&[3; 63]).is_err());
+        assert!(<(secp256k1::PublicKey, secp256k1::PublicKey)>::deserialize(&pairs[0].value[1..])
+            .is_err());
+    }
+
     #[test]
     fn can_deserialize_non_standard_psbt_sighash_type() {
         let non_standard_sighash = [222u8, 0u8, 0u8, 0u8]; // 32 byte value.
@@ -456,6 +699,137 @@ mod tests {
         assert!(sighash.is_ok())
     }
 
+    #[test]
+    fn from_reader_limited() {
+        use bitcoin::absolute;
+
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0]
+            .unknown
+            .insert(crate::raw::Key { type_value: 0xf0, key_data: vec![] }, vec![0xab; 10_000]);
+        let bytes = psbt.serialize();
+
+        let mut reader = io::Cursor::new(&bytes);
+        let err = Psbt::from_reader_limited(&mut reader, 1_000).unwrap_err();
+        assert!(matches!(err, DecodeError::TooLarge { max: 1_000 }));
+        assert!(reader.position() <= 1_000);
+    }
+
+    #[test]
+    fn from_reader_limited_boundary() {
+        use bitcoin::absolute;
+
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        };
+        let psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        let bytes = psbt.serialize();
+        let len = bytes.len() as u64;
+
+        let mut reader = io::Cursor::new(&bytes);
+        assert_eq!(Psbt::from_reader_limited(&mut reader, len).unwrap(), psbt);
+        let mut reader = io::Cursor::new(&bytes);
+        let err = Psbt::from_reader_limited(&mut reader, len - 1).unwrap_err();
+        assert!(matches!(err, DecodeError::TooLarge { max } if max == len - 1));
+
+        // A truncated PSBT of exactly the budget is invalid, not too large.
+        let truncated = &bytes[..bytes.len() - 1];
+        let mut reader = io::Cursor::new(truncated);
+        let err = Psbt::from_reader_limited(&mut reader, len - 1).unwrap_err();
+        assert!(matches!(err, DecodeError::Decode(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn deserialize_with_options() {
+        use bitcoin::absolute;
+
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        let global_len = psbt.serialize().len() - 1;
+        psbt.inputs[0]
+            .unknown
+            .insert(crate::raw::Key { type_value: 0xf0, key_data: vec![] }, vec![0xab; 10_000]);
+        let bytes = psbt.serialize();
+
+        let options = DeserializeOptions::default();
+        assert_eq!(Psbt::deserialize_with_options(&bytes, &options).unwrap(), psbt);
+
+        let options = DeserializeOptions { max_value_len: 1_000, ..Default::default() };
+        assert!(matches!(
+            Psbt::deserialize_with_options(&bytes, &options),
+            Err(DecodeError::Decode(Error::ConsensusEncoding(
+                encode::Error::OversizedVectorAllocation { .. }
+            )))
+        ));
+        let options = DeserializeOptions { max_inputs: 0, ..Default::default() };
+        assert!(matches!(
+            Psbt::deserialize_with_options(&bytes, &options),
+            Err(DecodeError::Decode(Error::TooManyInputs { max: 0 }))
+        ));
+        let options = DeserializeOptions { max_size: 1_000, ..Default::default() };
+        assert!(matches!(
+            Psbt::deserialize_with_options(&bytes, &options),
+            Err(DecodeError::TooLarge { max: 1_000 })
+        ));
+
+        // An input value claiming to be 4 GB long, rejected before anything is allocated.
+        let mut crafted = bytes[..global_len].to_vec();
+        crafted.extend([0x01, 0xf0, 0xfe, 0xff, 0xff, 0xff, 0xff]);
+        assert!(matches!(
+            Psbt::deserialize_with_options(&crafted, &DeserializeOptions::default()),
+            Err(DecodeError::Decode(Error::ConsensusEncoding(
+                encode::Error::OversizedVectorAllocation { .. }
+            )))
+        ));
+    }
+
+    #[test]
+    fn deserialize_pair_error() {
+        use bitcoin::absolute;
+
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default(); 3],
+            output: vec![],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        // A `tap_internal_key` one byte too long in the last input.
+        let key = crate::raw::Key { type_value: 0x17, key_data: vec![] };
+        psbt.inputs[2].unknown.insert(key.clone(), vec![0x02; 33]);
+
+        let err = match Psbt::deserialize(&psbt.serialize()) {
+            Err(Error::Pair(e)) => e,
+            res => panic!("unexpected result: {:?}", res),
+        };
+        assert_eq!(err.location, crate::MapLocation::Input(2));
+        assert_eq!(err.key, key);
+        assert_eq!(err.field(), "tap_internal_key");
+        assert!(matches!(err.error, Error::InvalidLength { expected: 32, found: 33 }));
+        assert!(err
+            .to_string()
+            .starts_with("invalid tap_internal_key (key type 0x17) in the input 2 map"));
+        #[cfg(feature = "std")]
+        {
+            let source = std::error::Error::source(&*err).unwrap();
+            assert_eq!(source.to_string(), "invalid length 33, expected 32 bytes");
+        }
+    }
+
     #[test]
     #[should_panic(expected = "InvalidMagic")]
     fn invalid_vector_1() {
@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Coordinating the signers of a multi-party PSBT.
+//!
+//! The coordinator of an n-of-m wallet sends the PSBT to each cosigner in turn and combines what
+//! comes back. A [`SigningSession`] does the bookkeeping: which cosigners, identified by their
+//! master fingerprint, have signed which inputs, whether a round made no progress, and what to
+//! send the next cosigner.
+
+use core::fmt;
+
+use bitcoin::bip32::Fingerprint;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::{CombineError, CombinePolicy, Input, Psbt, SignerKey};
+
+/// The state of a PSBT being signed by several parties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningSession {
+    /// All the PSBTs received so far combined.
+    psbt: Psbt,
+    /// The inputs each fingerprint has signatures in.
+    signed: BTreeMap<Fingerprint, BTreeSet<usize>>,
+    /// The hashes of the serializations of the PSBTs received.
+    received: BTreeSet<sha256::Hash>,
+}
+
+impl SigningSession {
+    /// Starts a session for `psbt`, crediting the signatures it already has.
+    pub fn new(psbt: Psbt) -> Self {
+        let mut signed = BTreeMap::<_, BTreeSet<usize>>::new();
+        for (fingerprint, index) in signers(&psbt) {
+            signed.entry(fingerprint).or_default().insert(index);
+        }
+        let received = [sha256::Hash::hash(&psbt.serialize())].into_iter().collect();
+        SigningSession { psbt, signed, received }
+    }
+
+    /// Returns the PSBT with everything received so far.
+    pub fn psbt(&self) -> &Psbt { &self.psbt }
+
+    /// Returns the PSBT with everything received so far, consuming the session.
+    pub fn into_psbt(self) -> Psbt { self.psbt }
+
+    /// Returns the inputs each fingerprint has signed.
+    ///
+    /// A signature is credited to the fingerprint of its key's origin, signatures by keys
+    /// without an origin are not listed.
+    pub fn signed(&self) -> &BTreeMap<Fingerprint, BTreeSet<usize>> { &self.signed }
+
+    /// Returns true if `fingerprint` has signed at least one input.
+    pub fn has_signed(&self, fingerprint: Fingerprint) -> bool {
+        self.signed.contains_key(&fingerprint)
+    }
+
+    /// Combines a PSBT returned by a signer into the session.
+    ///
+    /// Returns the fingerprints that contributed new signatures.
+    ///
+    /// # Errors
+    ///
+    /// - [`SessionError::Stalled`] if `psbt` was received before or has no new signatures, the
+    ///   signer did not do anything. Its other fields are still combined in the latter case.
+    /// - [`SessionError::Combine`] if `psbt` is for another transaction or can not be combined.
+    pub fn receive(&mut self, psbt: Psbt) -> Result<BTreeSet<Fingerprint>, SessionError> {
+        self.psbt.validate_combine_source(&psbt)?;
+        if !self.received.insert(sha256::Hash::hash(&psbt.serialize())) {
+            return Err(SessionError::Stalled);
+        }
+
+        let before = signature_count(&self.psbt);
+        self.psbt.try_combine_with_policy(psbt, CombinePolicy::Bip174)?;
+        if signature_count(&self.psbt) == before {
+            return Err(SessionError::Stalled);
+        }
+
+        let mut new = BTreeSet::new();
+        for (fingerprint, index) in signers(&self.psbt) {
+            if self.signed.entry(fingerprint).or_default().insert(index) {
+                new.insert(fingerprint);
+            }
+        }
+        Ok(new)
+    }
+
+    /// Returns the fingerprints of the keys that can still provide a missing signature.
+    ///
+    /// See [`Psbt::remaining_signers`], keys without an origin are not listed.
+    pub fn pending_signers(&self) -> BTreeSet<Fingerprint> {
+        let mut pending = BTreeSet::new();
+        for (input, needed) in self.psbt.inputs.iter().zip(self.psbt.remaining_signers()) {
+            let needed = match needed {
+                Some(needed) if needed.missing > 0 => needed,
+                _ => continue,
+            };
+            pending.extend(needed.signers.iter().filter_map(|key| origin(input, *key)));
+        }
+        pending
+    }
+
+    /// Returns true if no input is missing signatures, as far as [`Psbt::remaining_signers`]
+    /// can tell.
+    pub fn is_complete(&self) -> bool {
+        self.psbt
+            .remaining_signers()
+            .iter()
+            .all(|needed| needed.as_ref().map_or(false, |n| n.missing == 0))
+    }
+
+    /// Returns the PSBT to send to the signer with `fingerprint`.
+    ///
+    /// The input key origins of other signers and all signatures are removed from it, the
+    /// session has them already. Outputs are left alone so the signer can recognize change
+    /// belonging to the whole wallet.
+    pub fn psbt_for(&self, fingerprint: Fingerprint) -> Psbt {
+        let mut psbt = self.psbt.clone();
+        for input in &mut psbt.inputs {
+            input.bip32_derivation.retain(|_, (fp, _)| *fp == fingerprint);
+            input.tap_key_origins.retain(|_, (_, (fp, _))| *fp == fingerprint);
+            input.partial_sigs.clear();
+            input.tap_key_sig = None;
+            input.tap_script_sigs.clear();
+            input.musig2_partial_sigs.clear();
+        }
+        psbt
+    }
+}
+
+/// Returns the fingerprint and input index of every signature of `psbt` with a key origin.
+fn signers(psbt: &Psbt) -> impl Iterator<Item = (Fingerprint, usize)> + '_ {
+    psbt.inputs.iter().enumerate().flat_map(|(index, input)| {
+        let ecdsa = input.partial_sigs.keys().map(|pk| SignerKey::Ecdsa(*pk));
+        let key_spend =
+            input.tap_key_sig.and(input.tap_internal_key).map(SignerKey::XOnly).into_iter();
+        let script_spend = input.tap_script_sigs.keys().map(|(xonly, _)| SignerKey::XOnly(*xonly));
+        ecdsa
+            .chain(key_spend)
+            .chain(script_spend)
+            .filter_map(move |key| origin(input, key))
+            .map(move |fingerprint| (fingerprint, index))
+    })
+}
+
+/// Returns the fingerprint of the origin of `key` in `input`.
+fn origin(input: &Input, key: SignerKey) -> Option<Fingerprint> {
+    match key {
+        SignerKey::Ecdsa(pk) => input.bip32_derivation.get(&pk.inner).map(|(fp, _)| *fp),
+        SignerKey::XOnly(xonly) => input.tap_key_origins.get(&xonly).map(|(_, (fp, _))| *fp),
+    }
+}
+
+/// Returns the number of signatures of `psbt`.
+fn signature_count(psbt: &Psbt) -> usize {
+    psbt.inputs
+        .iter()
+        .map(|input| {
+            input.partial_sigs.len()
+                + usize::from(input.tap_key_sig.is_some())
+                + input.tap_script_sigs.len()
+                + input.musig2_partial_sigs.len()
+                + usize::from(input.is_finalized())
+        })
+        .sum()
+}
+
+/// Error receiving a PSBT in a [`SigningSession`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionError {
+    /// The PSBT can not be combined with the session's.
+    Combine(CombineError),
+    /// The PSBT was received before or has no new signatures.
+    Stalled,
+}
+
+bitcoin_internals::impl_from_infallible!(SessionError);
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SessionError::*;
+
+        match *self {
+            Combine(ref e) => write_err!(f, "the PSBT can not be combined into the session"; e),
+            Stalled => f.write_str("the PSBT adds no signatures to the session"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SessionError::*;
+
+        match *self {
+            Combine(ref e) => Some(e),
+            Stalled => None,
+        }
+    }
+}
+
+impl From<CombineError> for SessionError {
+    fn from(e: CombineError) -> Self { Self::Combine(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Xpriv};
+    use bitcoin::script::Builder;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::{
+        absolute, opcodes, transaction, Amount, Network, PublicKey, Transaction, TxIn, TxOut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn signing_session() {
+        let secp = Secp256k1::new();
+        let masters = (1..=3u8)
+            .map(|i| Xpriv::new_master(Network::Testnet, &[i; 32]).unwrap())
+            .collect::<Vec<_>>();
+        let path = "m/48'/1'/0'/2'/0/0".parse::<DerivationPath>().unwrap();
+        let keys = masters
+            .iter()
+            .map(|master| master.derive_priv(&secp, &path).unwrap().private_key.public_key(&secp))
+            .collect::<Vec<_>>();
+        let fingerprints =
+            masters.iter().map(|master| master.fingerprint(&secp)).collect::<Vec<_>>();
+        let witness_script = keys
+            .iter()
+            .fold(Builder::new().push_int(2), |builder, pk| builder.push_key(&PublicKey::new(*pk)))
+            .push_int(3)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        let input = &mut psbt.inputs[0];
+        input.witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: witness_script.to_p2wsh(),
+        });
+        input.witness_script = Some(witness_script);
+        for (pk, fingerprint) in keys.iter().zip(&fingerprints) {
+            input.bip32_derivation.insert(*pk, (*fingerprint, path.clone()));
+        }
+
+        let mut session = SigningSession::new(psbt);
+        assert!(!session.is_complete());
+        assert_eq!(session.pending_signers(), fingerprints.iter().copied().collect());
+
+        let mut forwarded = session.psbt_for(fingerprints[0]);
+        assert_eq!(forwarded.inputs[0].bip32_derivation.len(), 1);
+        assert_eq!(session.receive(forwarded.clone()), Err(SessionError::Stalled));
+        forwarded.sign(&masters[0], &secp).unwrap();
+        assert_eq!(session.receive(forwarded.clone()), Ok([fingerprints[0]].into_iter().collect()));
+        assert_eq!(session.receive(forwarded), Err(SessionError::Stalled));
+        assert!(session.has_signed(fingerprints[0]));
+        assert_eq!(session.pending_signers(), fingerprints[1..].iter().copied().collect());
+
+        let mut forwarded = session.psbt_for(fingerprints[2]);
+        assert!(forwarded.inputs[0].partial_sigs.is_empty());
+        forwarded.sign(&masters[2], &secp).unwrap();
+        session.receive(forwarded).unwrap();
+        assert!(session.is_complete());
+        assert_eq!(session.signed().len(), 2);
+        assert_eq!(session.psbt().inputs[0].partial_sigs.len(), 2);
+
+        let other = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::ONE,
+            ..session.psbt().unsigned_tx.clone()
+        })
+        .unwrap();
+        assert!(matches!(session.receive(other), Err(SessionError::Combine(_))));
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Splitting a PSBT into one PSBT per input, and joining them back.
+//!
+//! Threshold signing services send each input to the device holding its keys. A shard is a copy
+//! of the PSBT with the data of the other inputs removed, so a device only sees the scripts, key
+//! origins and signatures of the input it signs. [`Psbt::join_shards`] combines the signed shards
+//! and fails if any of them is for a different transaction or disagrees with another.
+
+use crate::prelude::*;
+use crate::{CombineError, Input, Psbt};
+
+impl Psbt {
+    /// Returns one PSBT per input, each with the data of only that input.
+    ///
+    /// The other inputs keep their UTXOs, which Taproot signatures commit to and the fee is
+    /// computed from. The global map and the outputs are kept as they are. Joining the shards
+    /// gives back this PSBT.
+    pub fn split_inputs(&self) -> Vec<Psbt> {
+        (0..self.inputs.len())
+            .map(|index| {
+                let inputs = self
+                    .inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, input)| {
+                        if i == index {
+                            input.clone()
+                        } else {
+                            Input {
+                                non_witness_utxo: input.non_witness_utxo.clone(),
+                                witness_utxo: input.witness_utxo.clone(),
+                                ..Default::default()
+                            }
+                        }
+                    })
+                    .collect();
+                Psbt {
+                    unsigned_tx: self.unsigned_tx.clone(),
+                    version: self.version,
+                    xpub: self.xpub.clone(),
+                    proprietary: self.proprietary.clone(),
+                    unknown: self.unknown.clone(),
+                    inputs,
+                    outputs: self.outputs.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Joins the shards returned by [`Psbt::split_inputs`], e.g. after each was signed.
+    ///
+    /// The shards may be in any order, they are combined as by [`Psbt::combine_many`].
+    ///
+    /// # Errors
+    ///
+    /// If `shards` is empty, if a shard is for a different unsigned transaction than the first or
+    /// if two shards have different values for the same key.
+    pub fn join_shards<I>(shards: I) -> Result<Psbt, CombineError>
+    where
+        I: IntoIterator<Item = Psbt>,
+    {
+        let mut shards = shards.into_iter();
+        let mut psbt = shards.next().ok_or(CombineError::NoPsbts)?;
+        psbt.combine_many(shards)?;
+        Ok(psbt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute;
+
+    use super::*;
+    use crate::test_utils::{dummy_sig, psbt_spending, ScriptType};
+
+    fn psbt() -> Psbt {
+        psbt_spending(&[ScriptType::P2wpkh, ScriptType::P2wsh, ScriptType::P2trKey])
+    }
+
+    #[test]
+    fn split_inputs() {
+        let psbt = psbt();
+        let shards = psbt.split_inputs();
+        assert_eq!(shards.len(), 3);
+        for (index, shard) in shards.iter().enumerate() {
+            assert_eq!(shard.unsigned_tx, psbt.unsigned_tx);
+            assert_eq!(shard.outputs, psbt.outputs);
+            assert_eq!(shard.inputs[index], psbt.inputs[index]);
+            for (i, input) in shard.inputs.iter().enumerate().filter(|(i, _)| *i != index) {
+                let utxo_only = Input {
+                    witness_utxo: psbt.inputs[i].witness_utxo.clone(),
+                    ..Default::default()
+                };
+                assert_eq!(*input, utxo_only);
+            }
+        }
+    }
+
+    #[test]
+    fn join_shards() {
+        let mut psbt = psbt();
+        assert_eq!(Psbt::join_shards(psbt.split_inputs()), Ok(psbt.clone()));
+
+        // Each device signs its own input, the shards come back in any order.
+        let mut shards = psbt.split_inputs();
+        for (index, shard) in shards.iter_mut().enumerate().take(2) {
+            let pk = bitcoin::PublicKey::new(
+                *shard.inputs[index].bip32_derivation.keys().next().unwrap(),
+            );
+            shard.inputs[index].partial_sigs.insert(pk, dummy_sig());
+            psbt.inputs[index].partial_sigs.insert(pk, dummy_sig());
+        }
+        shards.reverse();
+        assert_eq!(Psbt::join_shards(shards), Ok(psbt));
+    }
+
+    #[test]
+    fn join_shards_of_different_transactions() {
+        let mut shards = psbt().split_inputs();
+        shards[0].unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+        assert!(matches!(Psbt::join_shards(shards), Err(CombineError::TxidMismatch { .. })));
+    }
+
+    #[test]
+    fn join_no_shards() {
+        assert_eq!(Psbt::join_shards(vec![]), Err(CombineError::NoPsbts));
+    }
+}
@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Restricting the sighash types a signer accepts.
+//!
+//! A PSBT chooses the sighash type of each input, a signer that signs whatever it is given can be
+//! tricked into signing with `SIGHASH_NONE` or `SIGHASH_SINGLE`, which let others change the
+//! outputs. A [`SighashPolicy`] lists the types the signer accepts.
+
+use core::fmt;
+
+use bitcoin::secp256k1::{Secp256k1, Signing, Verification};
+use bitcoin::TapSighashType;
+
+use crate::prelude::*;
+use crate::{
+    GetKey, InputSigs, Psbt, PsbtSighashType, SigningAlgorithm, SigningErrors, SigningKeysMap,
+    VerifySigError,
+};
+
+/// The sighash types a signer accepts.
+///
+/// Non-standard sighash types are never allowed.
+///
+/// ```
+/// # use psbt_v0::bitcoin::sighash::EcdsaSighashType;
+/// # use psbt_v0::{PsbtSighashType, SighashPolicy};
+/// let policy = SighashPolicy::ALL_ONLY;
+/// assert!(policy.allows(EcdsaSighashType::All.into()));
+/// assert!(!policy.allows(EcdsaSighashType::Single.into()));
+///
+/// let policy = policy.allow(EcdsaSighashType::AllPlusAnyoneCanPay);
+/// assert!(policy.allows(PsbtSighashType::from_u32(0x81)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SighashPolicy {
+    /// One bit per standard sighash type, see [`standard_index`].
+    allowed: u8,
+}
+
+impl SighashPolicy {
+    /// Allows no sighash type.
+    pub const NONE: SighashPolicy = SighashPolicy { allowed: 0 };
+
+    /// Allows `SIGHASH_DEFAULT` and `SIGHASH_ALL`, the sighash types that commit to the whole
+    /// transaction.
+    pub const ALL_ONLY: SighashPolicy = SighashPolicy { allowed: 0b11 };
+
+    /// Allows every standard sighash type.
+    pub const STANDARD: SighashPolicy = SighashPolicy { allowed: 0x7f };
+
+    /// Returns this policy with `sighash_type` allowed too.
+    pub fn allow(mut self, sighash_type: impl Into<PsbtSighashType>) -> Self {
+        if let Some(index) = standard_index(sighash_type.into()) {
+            self.allowed |= 1 << index;
+        }
+        self
+    }
+
+    /// Returns true if this policy allows `sighash_type`.
+    pub fn allows(&self, sighash_type: PsbtSighashType) -> bool {
+        standard_index(sighash_type).map_or(false, |index| self.allowed & (1 << index) != 0)
+    }
+
+    /// Returns the sighash type input `input_index` of `psbt` would be signed with if this policy
+    /// does not allow it.
+    ///
+    /// Without a sighash type field ECDSA inputs are signed with `SIGHASH_ALL` and Taproot inputs
+    /// with `SIGHASH_DEFAULT`. Inputs whose signing algorithm is unknown are left to the signer to
+    /// reject.
+    pub(crate) fn disallowed_input(
+        &self,
+        psbt: &Psbt,
+        input_index: usize,
+    ) -> Option<PsbtSighashType> {
+        let sighash_type = match psbt.inputs.get(input_index)?.sighash_type {
+            Some(sighash_type) => sighash_type,
+            None => match psbt.signing_algorithm(input_index).ok()? {
+                SigningAlgorithm::Ecdsa => PsbtSighashType::ALL,
+                SigningAlgorithm::Schnorr => TapSighashType::Default.into(),
+            },
+        };
+        Some(sighash_type).filter(|ty| !self.allows(*ty))
+    }
+}
+
+/// Returns the position of `sighash_type` among the standard sighash types.
+fn standard_index(sighash_type: PsbtSighashType) -> Option<u8> {
+    match sighash_type.to_u32() {
+        0x00 => Some(0),
+        0x01 => Some(1),
+        0x02 => Some(2),
+        0x03 => Some(3),
+        0x81 => Some(4),
+        0x82 => Some(5),
+        0x83 => Some(6),
+        _ => None,
+    }
+}
+
+impl Psbt {
+    /// Checks the sighash type field and the signatures of every input against `policy`.
+    ///
+    /// MuSig2 partial signatures do not record their sighash type and are not checked.
+    ///
+    /// # Errors
+    ///
+    /// On the first input whose sighash type field or one of whose signatures is not allowed.
+    pub fn enforce_sighash_policy(&self, policy: &SighashPolicy) -> Result<(), SighashPolicyError> {
+        for (input_index, input) in self.inputs.iter().enumerate() {
+            if let Some(sighash_type) = input.sighash_type.filter(|ty| !policy.allows(*ty)) {
+                return Err(SighashPolicyError::InputSighashType { input_index, sighash_type });
+            }
+
+            let ecdsa = input.partial_sigs.values().map(|sig| sig.sighash_type.into());
+            let taproot = input
+                .tap_key_sig
+                .iter()
+                .chain(input.tap_script_sigs.values())
+                .map(|sig| sig.sighash_type.into());
+            if let Some(sighash_type) = ecdsa.chain(taproot).find(|ty| !policy.allows(*ty)) {
+                return Err(SighashPolicyError::Signature { input_index, sighash_type });
+            }
+        }
+        Ok(())
+    }
+
+    /// Signs the PSBT like [`Psbt::sign`], refusing to sign inputs whose sighash type `policy`
+    /// does not allow.
+    ///
+    /// Inputs that are refused are reported with [`SignError::DisallowedSighashType`], the others
+    /// are signed.
+    ///
+    /// [`SignError::DisallowedSighashType`]: crate::SignError::DisallowedSighashType
+    pub fn sign_with_policy<C, K>(
+        &mut self,
+        k: &K,
+        secp: &Secp256k1<C>,
+        policy: &SighashPolicy,
+    ) -> Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>
+    where
+        C: Signing + Verification,
+        K: GetKey,
+    {
+        self.sign_inputs(0..self.inputs.len(), k, secp, Some(policy), None)
+    }
+
+    /// Verifies the signatures like [`Psbt::verify_sigs`], reporting signatures whose sighash
+    /// type `policy` does not allow with [`VerifySigError::DisallowedSighashType`].
+    pub fn verify_sigs_with_policy<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        policy: &SighashPolicy,
+    ) -> Vec<InputSigs> {
+        let mut results = self.verify_sigs(secp);
+        let check = |sighash_type: PsbtSighashType, result: &mut Result<(), VerifySigError>| {
+            if !policy.allows(sighash_type) {
+                *result = Err(VerifySigError::DisallowedSighashType(sighash_type));
+            }
+        };
+
+        for (input, result) in self.inputs.iter().zip(&mut results) {
+            for (pubkey, sig) in &input.partial_sigs {
+                if let Some(verified) = result.partial_sigs.get_mut(pubkey) {
+                    check(sig.sighash_type.into(), verified);
+                }
+            }
+            if let (Some(sig), Some(verified)) = (&input.tap_key_sig, &mut result.tap_key_sig) {
+                check(sig.sighash_type.into(), verified);
+            }
+            for (key, sig) in &input.tap_script_sigs {
+                if let Some(verified) = result.tap_script_sigs.get_mut(key) {
+                    check(sig.sighash_type.into(), verified);
+                }
+            }
+        }
+        results
+    }
+}
+
+/// Error returned by [`Psbt::enforce_sighash_policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SighashPolicyError {
+    /// The sighash type field of the input is not allowed.
+    InputSighashType {
+        /// The index of the input.
+        input_index: usize,
+        /// The sighash type of the input.
+        sighash_type: PsbtSighashType,
+    },
+    /// A signature of the input uses a sighash type that is not allowed.
+    Signature {
+        /// The index of the input.
+        input_index: usize,
+        /// The sighash type of the signature.
+        sighash_type: PsbtSighashType,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(SighashPolicyError);
+
+impl fmt::Display for SighashPolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use SighashPolicyError::*;
+
+        match *self {
+            InputSighashType { input_index, sighash_type } => write!(
+                f,
+                "input {} has sighash type {}, which the policy does not allow",
+                input_index, sighash_type
+            ),
+            Signature { input_index, sighash_type } => write!(
+                f,
+                "input {} has a signature with sighash type {}, which the policy does not allow",
+                input_index, sighash_type
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SighashPolicyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use SighashPolicyError::*;
+
+        match *self {
+            InputSighashType { .. } | Signature { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint, KeySource};
+    use bitcoin::sighash::EcdsaSighashType;
+    use bitcoin::{
+        absolute, secp256k1, transaction, Amount, NetworkKind, PrivateKey, ScriptBuf, Transaction,
+        TxIn, TxOut,
+    };
+
+    use super::*;
+    use crate::SignError;
+
+    #[test]
+    fn sighash_policy() {
+        let secp = Secp256k1::new();
+        let sk =
+            PrivateKey::new(secp256k1::SecretKey::from_slice(&[1; 32]).unwrap(), NetworkKind::Test);
+        let pk = sk.public_key(&secp);
+        let utxo = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap()),
+        };
+        let source: KeySource = (Fingerprint::default(), DerivationPath::master());
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        for input in &mut psbt.inputs {
+            input.witness_utxo = Some(utxo.clone());
+            input.bip32_derivation.insert(pk.inner, source.clone());
+        }
+        psbt.inputs[1].sighash_type = Some(EcdsaSighashType::Single.into());
+
+        let policy = SighashPolicy::ALL_ONLY;
+        assert_eq!(
+            psbt.enforce_sighash_policy(&policy),
+            Err(SighashPolicyError::InputSighashType {
+                input_index: 1,
+                sighash_type: EcdsaSighashType::Single.into()
+            })
+        );
+
+        let keys = BTreeMap::from([(pk, sk)]);
+        let (used, errors) = psbt.sign_with_policy(&keys, &secp, &policy).unwrap_err();
+        assert!(used.contains_key(&0));
+        assert_eq!(errors[&1], SignError::DisallowedSighashType(EcdsaSighashType::Single.into()));
+        assert!(psbt.inputs[1].partial_sigs.is_empty());
+
+        // A signature made under a laxer policy is caught by verification.
+        psbt.sign(&keys, &secp).unwrap();
+        psbt.inputs[1].sighash_type = None;
+        assert!(matches!(
+            psbt.enforce_sighash_policy(&policy),
+            Err(SighashPolicyError::Signature { input_index: 1, .. })
+        ));
+        let results = psbt.verify_sigs_with_policy(&secp, &policy);
+        assert!(results[0].is_valid());
+        assert_eq!(
+            results[1].partial_sigs[&pk],
+            Err(VerifySigError::DisallowedSighashType(EcdsaSighashType::Single.into()))
+        );
+        assert!(psbt.verify_sigs(&secp).iter().all(InputSigs::is_valid));
+        assert!(psbt.enforce_sighash_policy(&SighashPolicy::STANDARD).is_ok());
+    }
+}
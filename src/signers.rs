@@ -0,0 +1,326 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Counting the signatures an input still needs and the keys that can provide them.
+//!
+//! Single key, `OP_CHECKMULTISIG` and Taproot `multi_a` scripts are understood without
+//! miniscript. With the `miniscript` feature any miniscript witness or redeem script is.
+
+use bitcoin::{PublicKey, Script, TapLeafHash, XOnlyPublicKey};
+
+use crate::prelude::*;
+use crate::{script, Input, Psbt};
+
+impl Psbt {
+    /// Returns the signatures each input still needs, see [`Input::signatures_needed`].
+    ///
+    /// Finalized inputs need no signatures. `None` is returned for inputs whose UTXO is missing.
+    pub fn remaining_signers(&self) -> Vec<Option<SignaturesNeeded>> {
+        (0..self.inputs.len())
+            .map(|index| {
+                let input = &self.inputs[index];
+                if input.is_finalized() {
+                    return Some(SignaturesNeeded::none());
+                }
+                let utxo = self.spend_utxo(index).ok()?;
+                input.signatures_needed(&utxo.script_pubkey)
+            })
+            .collect()
+    }
+}
+
+impl Input {
+    /// Returns how many more signatures this input, spending `spk`, needs and which keys have not
+    /// signed yet.
+    ///
+    /// Signatures are counted, not verified. For Taproot the spend path needing the fewest
+    /// signatures is reported, the key path if it ties. Returns `None` if the script, or the
+    /// redeem or witness script it commits to, is missing or not understood.
+    pub fn signatures_needed(&self, spk: &Script) -> Option<SignaturesNeeded> {
+        if spk.is_p2tr() {
+            return self.tap_signatures_needed();
+        }
+
+        let mut script = spk;
+        let mut segwit = spk.is_witness_program();
+        if script.is_p2sh() {
+            script = self.redeem_script.as_ref()?;
+            segwit = script.is_witness_program();
+        }
+        if script.is_p2wsh() {
+            script = self.witness_script.as_ref()?;
+        }
+
+        let signed = |pk: &PublicKey| self.partial_sigs.contains_key(pk);
+        if script.is_p2pkh() || script.is_p2wpkh() {
+            if !self.partial_sigs.is_empty() {
+                return Some(SignaturesNeeded::none());
+            }
+            let hash = &script.as_bytes()[if script.is_p2pkh() { 3 } else { 2 }..][..20];
+            let signers = self
+                .bip32_derivation
+                .keys()
+                .map(|pk| PublicKey::new(*pk))
+                .filter(|pk| pk.pubkey_hash()[..] == *hash)
+                .map(SignerKey::Ecdsa)
+                .collect();
+            return Some(SignaturesNeeded { missing: 1, signers });
+        }
+        if script.is_p2pk() {
+            let pk = PublicKey::from_slice(&script.as_bytes()[1..script.len() - 1]).ok()?;
+            return Some(if signed(&pk) {
+                SignaturesNeeded::none()
+            } else {
+                SignaturesNeeded { missing: 1, signers: vec![SignerKey::Ecdsa(pk)] }
+            });
+        }
+        if let Some((threshold, keys)) = script::multisig(script) {
+            let missing = threshold.saturating_sub(keys.iter().filter(|pk| signed(pk)).count());
+            let signers = keys.into_iter().filter(|pk| !signed(pk)).map(SignerKey::Ecdsa);
+            return Some(SignaturesNeeded { missing, signers: signers.collect() });
+        }
+        miniscript_signatures_needed(script, segwit, &signed)
+    }
+
+    fn tap_signatures_needed(&self) -> Option<SignaturesNeeded> {
+        if self.tap_key_sig.is_some() {
+            return Some(SignaturesNeeded::none());
+        }
+        let key_path = self.tap_internal_key.map(|internal_key| SignaturesNeeded {
+            missing: 1,
+            signers: vec![SignerKey::XOnly(internal_key)],
+        });
+
+        let script_paths = self.tap_scripts.values().filter_map(|(script, version)| {
+            let leaf_hash = TapLeafHash::from_script(script, *version);
+            let (threshold, keys) = script::tap_pk(script)
+                .map(|pk| (1, vec![pk]))
+                .or_else(|| script::tap_multi_a(script))?;
+            let signed = |pk: &XOnlyPublicKey| self.tap_script_sigs.contains_key(&(*pk, leaf_hash));
+            let missing = threshold.saturating_sub(keys.iter().filter(|pk| signed(pk)).count());
+            let signers = keys.into_iter().filter(|pk| !signed(pk)).map(SignerKey::XOnly);
+            Some(SignaturesNeeded { missing, signers: signers.collect() })
+        });
+
+        // `min_by_key` returns the first minimum, i.e. the key path on ties.
+        key_path.into_iter().chain(script_paths).min_by_key(|needed| needed.missing)
+    }
+}
+
+#[cfg(feature = "miniscript")]
+fn miniscript_signatures_needed(
+    script: &Script,
+    segwit: bool,
+    signed: &dyn Fn(&PublicKey) -> bool,
+) -> Option<SignaturesNeeded> {
+    use miniscript::{Legacy, Miniscript, ScriptContext, Segwitv0, Terminal};
+
+    /// Returns the fewest signatures not yet made that satisfy `ms`.
+    fn needed<Ctx: ScriptContext>(
+        ms: &Miniscript<PublicKey, Ctx>,
+        signed: &dyn Fn(&PublicKey) -> bool,
+    ) -> Option<usize> {
+        use Terminal::*;
+
+        let min = |a: Option<usize>, b: Option<usize>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match ms.node {
+            True | After(_) | Older(_) | Sha256(_) | Hash256(_) | Ripemd160(_) | Hash160(_) =>
+                Some(0),
+            False | RawPkH(_) => None,
+            PkK(ref pk) | PkH(ref pk) => Some(usize::from(!signed(pk))),
+            Alt(ref ms) | Swap(ref ms) | Check(ref ms) | DupIf(ref ms) | Verify(ref ms)
+            | NonZero(ref ms) | ZeroNotEqual(ref ms) => needed(ms, signed),
+            AndV(ref a, ref b) | AndB(ref a, ref b) =>
+                Some(needed(a, signed)? + needed(b, signed)?),
+            OrB(ref a, ref b) | OrD(ref a, ref b) | OrC(ref a, ref b) | OrI(ref a, ref b) =>
+                min(needed(a, signed), needed(b, signed)),
+            AndOr(ref a, ref b, ref c) => {
+                let both = needed(a, signed).and_then(|a| Some(a + needed(b, signed)?));
+                min(both, needed(c, signed))
+            }
+            Thresh(ref thresh) => {
+                let mut costs =
+                    thresh.iter().filter_map(|ms| needed(ms, signed)).collect::<Vec<_>>();
+                if costs.len() < thresh.k() {
+                    return None;
+                }
+                costs.sort_unstable();
+                Some(costs[..thresh.k()].iter().sum())
+            }
+            Multi(ref thresh) =>
+                Some(thresh.k().saturating_sub(thresh.iter().filter(|pk| signed(pk)).count())),
+            MultiA(ref thresh) =>
+                Some(thresh.k().saturating_sub(thresh.iter().filter(|pk| signed(pk)).count())),
+        }
+    }
+
+    fn signatures_needed<Ctx: ScriptContext<Key = PublicKey>>(
+        script: &Script,
+        signed: &dyn Fn(&PublicKey) -> bool,
+    ) -> Option<SignaturesNeeded> {
+        let ms = Miniscript::<PublicKey, Ctx>::parse_insane(script).ok()?;
+        let missing = needed(&ms, signed)?;
+        // In script order, a key may appear in several branches.
+        let mut signers = vec![];
+        for pk in ms.iter_pk().filter(|pk| !signed(pk)).map(SignerKey::Ecdsa) {
+            if !signers.contains(&pk) {
+                signers.push(pk);
+            }
+        }
+        Some(SignaturesNeeded { missing, signers })
+    }
+
+    if segwit {
+        signatures_needed::<Segwitv0>(script, signed)
+    } else {
+        signatures_needed::<Legacy>(script, signed)
+    }
+}
+
+#[cfg(not(feature = "miniscript"))]
+fn miniscript_signatures_needed(
+    _: &Script,
+    _: bool,
+    _: &dyn Fn(&PublicKey) -> bool,
+) -> Option<SignaturesNeeded> {
+    None
+}
+
+/// The signatures an input still needs, returned by [`Input::signatures_needed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignaturesNeeded {
+    /// The number of signatures still needed.
+    pub missing: usize,
+    /// The keys of the script that have not signed, any `missing` of them can complete it.
+    ///
+    /// For scripts with other conditions than signatures, not every combination may satisfy
+    /// them.
+    pub signers: Vec<SignerKey>,
+}
+
+impl SignaturesNeeded {
+    /// Returns the value of an input that needs no more signatures.
+    fn none() -> Self { SignaturesNeeded { missing: 0, signers: vec![] } }
+}
+
+/// A key that can sign an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SignerKey {
+    /// A key signing with ECDSA.
+    Ecdsa(PublicKey),
+    /// A key signing a Taproot key path or script path spend.
+    XOnly(XOnlyPublicKey),
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+    use bitcoin::script::Builder;
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::taproot::LeafVersion;
+    use bitcoin::{
+        absolute, ecdsa, taproot, transaction, Amount, ScriptBuf, Transaction, TxIn, TxOut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn signatures_needed() {
+        let secp = Secp256k1::new();
+        let keys = (1..=3u8)
+            .map(|i| {
+                let sk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+                PublicKey::new(sk.public_key(&secp))
+            })
+            .collect::<Vec<_>>();
+        let sig =
+            ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+                .unwrap();
+
+        let witness_script = Builder::new()
+            .push_int(2)
+            .push_key(&keys[0])
+            .push_key(&keys[1])
+            .push_key(&keys[2])
+            .push_int(3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: witness_script.to_p2wsh(),
+        });
+        psbt.inputs[0].witness_script = Some(witness_script);
+        psbt.inputs[0].partial_sigs.insert(keys[1], sig);
+
+        // A Taproot input with a 2-of-2 leaf that has one signature and a single key leaf.
+        let xonly = keys.iter().map(|pk| pk.inner.x_only_public_key().0).collect::<Vec<_>>();
+        let multi_a = Builder::new()
+            .push_x_only_key(&xonly[0])
+            .push_opcode(OP_CHECKSIG)
+            .push_x_only_key(&xonly[1])
+            .push_opcode(OP_CHECKSIGADD)
+            .push_int(2)
+            .push_opcode(OP_NUMEQUAL)
+            .into_script();
+        let leaf_hash = TapLeafHash::from_script(&multi_a, LeafVersion::TapScript);
+        let tap = &mut psbt.inputs[1];
+        tap.witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, xonly[2], None),
+        });
+        let control_block =
+            taproot::ControlBlock::decode(&[&[0xc0][..], &xonly[2].serialize()].concat()).unwrap();
+        tap.tap_scripts.insert(control_block, (multi_a, LeafVersion::TapScript));
+        let tap_sig = taproot::Signature::from_slice(&[1; 64]).unwrap();
+        tap.tap_script_sigs.insert((xonly[0], leaf_hash), tap_sig);
+
+        let needed = psbt.remaining_signers();
+        assert_eq!(
+            needed[0],
+            Some(SignaturesNeeded {
+                missing: 1,
+                signers: vec![SignerKey::Ecdsa(keys[0]), SignerKey::Ecdsa(keys[2])]
+            })
+        );
+        assert_eq!(
+            needed[1],
+            Some(SignaturesNeeded { missing: 1, signers: vec![SignerKey::XOnly(xonly[1])] })
+        );
+        assert_eq!(needed[2], None);
+
+        psbt.inputs[0].partial_sigs.insert(keys[2], sig);
+        assert_eq!(psbt.remaining_signers()[0].as_ref().unwrap().missing, 0);
+
+        #[cfg(feature = "miniscript")]
+        {
+            use miniscript::{Miniscript, Segwitv0};
+
+            let policy = format!("and_v(v:pk({}),or_d(pk({}),pk({})))", keys[0], keys[1], keys[2]);
+            let witness_script =
+                Miniscript::<PublicKey, Segwitv0>::from_str_insane(&policy).unwrap().encode();
+            let input = &mut psbt.inputs[2];
+            input.witness_utxo = Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: witness_script.to_p2wsh(),
+            });
+            input.witness_script = Some(witness_script);
+            input.partial_sigs.insert(keys[2], sig);
+            assert_eq!(
+                psbt.remaining_signers()[2],
+                Some(SignaturesNeeded {
+                    missing: 1,
+                    signers: vec![SignerKey::Ecdsa(keys[0]), SignerKey::Ecdsa(keys[1])]
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Accounting for the serialized size of a PSBT, field by field.
+//!
+//! Transports with payload limits, such as QR codes and NFC, have to decide what to prune before
+//! sending a PSBT, e.g. the non-witness UTXOs of segwit inputs or the global xpubs.
+//! [`Psbt::size_report`] tells how many bytes each field takes.
+
+use bitcoin::VarInt;
+
+use crate::map::{self, Map};
+use crate::prelude::*;
+use crate::stream::MAGIC_BYTES;
+use crate::{raw, MapLocation, Psbt};
+
+impl Psbt {
+    /// Returns the length of [`Psbt::serialize`] without building the serialized PSBT.
+    ///
+    /// The fields are still encoded one at a time to measure them.
+    pub fn serialized_size(&self) -> usize {
+        let maps = core::iter::once(self.get_pairs())
+            .chain(self.inputs.iter().map(Map::get_pairs))
+            .chain(self.outputs.iter().map(Map::get_pairs));
+        // The separator after the magic bytes, and the one ending each map.
+        MAGIC_BYTES.len()
+            + 1
+            + maps.map(|pairs| pairs.iter().map(pair_size).sum::<usize>() + 1).sum::<usize>()
+    }
+
+    /// Returns the serialized size of every field of this PSBT, see [`SizeReport`].
+    pub fn size_report(&self) -> SizeReport {
+        SizeReport {
+            global: MapSize::new(MapLocation::Global, self.get_pairs()),
+            inputs: self
+                .inputs
+                .iter()
+                .enumerate()
+                .map(|(index, input)| MapSize::new(MapLocation::Input(index), input.get_pairs()))
+                .collect(),
+            outputs: self
+                .outputs
+                .iter()
+                .enumerate()
+                .map(|(index, output)| MapSize::new(MapLocation::Output(index), output.get_pairs()))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the serialized size of `pair`.
+fn pair_size(pair: &raw::Pair) -> usize {
+    let key_len = 1 + pair.key.key_data.len();
+    let value_len = pair.value.len();
+    VarInt::from(key_len).size() + key_len + VarInt::from(value_len).size() + value_len
+}
+
+/// The serialized size of each field of a PSBT, returned by [`Psbt::size_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// The sizes of the fields of the global map, including the unsigned transaction.
+    pub global: MapSize,
+    /// The sizes of the fields of each input map, in order.
+    pub inputs: Vec<MapSize>,
+    /// The sizes of the fields of each output map, in order.
+    pub outputs: Vec<MapSize>,
+}
+
+impl SizeReport {
+    /// Returns the serialized size of the PSBT, the same as [`Psbt::serialized_size`].
+    pub fn total(&self) -> usize {
+        MAGIC_BYTES.len() + 1 + self.maps().map(MapSize::total).sum::<usize>()
+    }
+
+    /// Returns the serialized size of all the fields named `field` in any map, e.g.
+    /// `non_witness_utxo`.
+    ///
+    /// This is how many bytes removing those fields saves.
+    pub fn field_total(&self, field: &str) -> usize {
+        self.maps()
+            .flat_map(|map| &map.fields)
+            .filter(|size| size.field == field)
+            .map(|size| size.size)
+            .sum()
+    }
+
+    fn maps(&self) -> impl Iterator<Item = &MapSize> {
+        core::iter::once(&self.global).chain(&self.inputs).chain(&self.outputs)
+    }
+}
+
+/// The serialized size of the fields of a single map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapSize {
+    /// The location of the map.
+    pub location: MapLocation,
+    /// The size of each field present in the map, in key type order.
+    pub fields: Vec<FieldSize>,
+}
+
+impl MapSize {
+    fn new(location: MapLocation, pairs: Vec<raw::Pair>) -> Self {
+        let mut fields = BTreeMap::<u8, FieldSize>::new();
+        for pair in pairs {
+            let type_value = pair.key.type_value;
+            let field = fields.entry(type_value).or_insert_with(|| FieldSize {
+                field: map::field_name(location, type_value),
+                type_value,
+                count: 0,
+                size: 0,
+            });
+            field.count += 1;
+            field.size += pair_size(&pair);
+        }
+        MapSize { location, fields: fields.into_values().collect() }
+    }
+
+    /// Returns the serialized size of the map, including the separator ending it.
+    pub fn total(&self) -> usize { self.fields.iter().map(|field| field.size).sum::<usize>() + 1 }
+}
+
+/// The serialized size of the key-value pairs of one key type in a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSize {
+    /// The name of the field, e.g. `partial_sigs`.
+    pub field: &'static str,
+    /// The key type of the field.
+    pub type_value: u8,
+    /// The number of key-value pairs of the field, more than one for fields keyed by e.g. a
+    /// public key.
+    pub count: usize,
+    /// The serialized size of the key-value pairs.
+    pub size: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::{DerivationPath, Fingerprint};
+    use bitcoin::secp256k1::{self, Secp256k1};
+    use bitcoin::{absolute, transaction, Amount, ScriptBuf, Transaction, TxIn, TxOut};
+
+    use super::*;
+
+    #[test]
+    fn size_report() {
+        let secp = Secp256k1::new();
+        let prev = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 2],
+            output: vec![TxOut::NULL],
+        })
+        .unwrap();
+        for input in &mut psbt.inputs {
+            input.non_witness_utxo = Some(prev.clone());
+            input.witness_utxo = Some(prev.output[0].clone());
+        }
+        for i in 1..=2u8 {
+            let pk = secp256k1::SecretKey::from_slice(&[i; 32]).unwrap().public_key(&secp);
+            psbt.inputs[0]
+                .bip32_derivation
+                .insert(pk, (Fingerprint::default(), DerivationPath::master()));
+        }
+
+        let report = psbt.size_report();
+        assert_eq!(psbt.serialized_size(), psbt.serialize().len());
+        assert_eq!(report.total(), psbt.serialize().len());
+
+        let derivations =
+            report.inputs[0].fields.iter().find(|f| f.field == "bip32_derivation").unwrap();
+        assert_eq!((derivations.count, derivations.size), (2, 2 * (1 + 34 + 1 + 4)));
+        let saved = report.field_total("non_witness_utxo");
+        assert_eq!(saved, 2 * (1 + 1 + 1 + bitcoin::consensus::serialize(&prev).len()));
+        for input in &mut psbt.inputs {
+            input.non_witness_utxo = None;
+        }
+        assert_eq!(psbt.serialized_size(), report.total() - saved);
+    }
+}
@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Checking the unsigned transaction against the relay policy of the network.
+//!
+//! Nodes only relay and mine transactions that are standard, a stricter set of rules than
+//! consensus. A transaction violating them is valid yet will never reach a miner, which is best
+//! found out before collecting signatures from every cosigner.
+
+use core::fmt;
+
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::{absolute, opcodes, transaction, Amount, FeeRate, Script, Weight, WitnessVersion};
+
+use crate::prelude::*;
+use crate::Psbt;
+
+/// The limits of a transaction relay policy, see [`Psbt::check_standardness`].
+///
+/// The [`Default`] policy is that of Bitcoin Core 29.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardnessPolicy {
+    /// The fee rate used to compute the dust threshold of outputs (`-dustrelayfee`).
+    pub dust_relay_fee: FeeRate,
+    /// The minimum fee rate of transactions relayed (`-minrelaytxfee`).
+    pub min_relay_fee: FeeRate,
+    /// The maximum weight of a standard transaction.
+    pub max_weight: Weight,
+    /// The highest standard transaction version.
+    pub max_version: transaction::Version,
+    /// The maximum size of an `OP_RETURN` script pubkey (`-datacarriersize`).
+    pub max_op_return_size: usize,
+    /// The maximum number of `OP_RETURN` outputs.
+    pub max_op_return_outputs: usize,
+    /// Whether bare multisig outputs, of at most three keys, are standard (`-permitbaremultisig`).
+    pub permit_bare_multisig: bool,
+    /// The height of the chain tip, to check that a height based lock time is final in the next
+    /// block. Not checked if `None`.
+    pub tip_height: Option<absolute::Height>,
+}
+
+impl Default for StandardnessPolicy {
+    fn default() -> Self {
+        StandardnessPolicy {
+            dust_relay_fee: FeeRate::DUST,
+            min_relay_fee: FeeRate::BROADCAST_MIN,
+            max_weight: Weight::from_wu(400_000),
+            max_version: transaction::Version::non_standard(3),
+            max_op_return_size: Psbt::MAX_STANDARD_OP_RETURN_SIZE,
+            max_op_return_outputs: Psbt::MAX_STANDARD_OP_RETURNS,
+            permit_bare_multisig: true,
+            tip_height: None,
+        }
+    }
+}
+
+impl Psbt {
+    /// Checks whether the transaction will be standard once finalized, returning every issue
+    /// found.
+    ///
+    /// The checks are:
+    ///
+    /// - The transaction version is between 1 and [`StandardnessPolicy::max_version`].
+    /// - The lock time is final in the block after [`StandardnessPolicy::tip_height`].
+    /// - Every script pubkey is of a standard type, and `OP_RETURN` outputs are within the size
+    ///   and count limits.
+    /// - No output is dust at [`StandardnessPolicy::dust_relay_fee`].
+    /// - The [estimated](Psbt::estimate_weight) weight and fee rate are within the policy limits.
+    ///   If the weight can not be estimated or the fee not computed, the fee rate is reported as
+    ///   [`StandardnessIssue::UnknownFeeRate`].
+    pub fn check_standardness(&self, policy: &StandardnessPolicy) -> Vec<StandardnessIssue> {
+        let mut issues = vec![];
+        let tx = &self.unsigned_tx;
+
+        if tx.version < transaction::Version::ONE || tx.version > policy.max_version {
+            issues.push(StandardnessIssue::Version(tx.version));
+        }
+        if let (absolute::LockTime::Blocks(height), Some(tip)) = (tx.lock_time, policy.tip_height) {
+            let enforced = tx.input.iter().any(|txin| txin.sequence.enables_absolute_lock_time());
+            if enforced && height > tip {
+                issues.push(StandardnessIssue::NonFinal { lock_time: tx.lock_time });
+            }
+        }
+
+        let mut op_returns = 0;
+        for (output_index, txout) in tx.output.iter().enumerate() {
+            let spk = &txout.script_pubkey;
+            if spk.is_op_return() {
+                op_returns += 1;
+                if spk.len() > policy.max_op_return_size {
+                    issues.push(StandardnessIssue::OpReturnTooLarge {
+                        output_index,
+                        size: spk.len(),
+                    });
+                }
+            }
+            if !is_standard_script(spk, policy) {
+                issues.push(StandardnessIssue::NonStandardScript { output_index });
+                continue;
+            }
+            let threshold = spk.minimal_non_dust_custom(policy.dust_relay_fee);
+            if txout.value < threshold {
+                issues.push(StandardnessIssue::Dust {
+                    output_index,
+                    value: txout.value,
+                    threshold,
+                });
+            }
+        }
+        if op_returns > policy.max_op_return_outputs {
+            issues.push(StandardnessIssue::TooManyOpReturns { count: op_returns });
+        }
+
+        match (self.estimate_weight(), self.fee()) {
+            (Ok(weight), Ok(fee)) => {
+                if weight > policy.max_weight {
+                    issues.push(StandardnessIssue::TooHeavy { weight });
+                }
+                let fee_rate = FeeRate::from_sat_per_kwu(
+                    fee.to_sat().saturating_mul(1000) / weight.to_wu().max(1),
+                );
+                let min_fee = policy.min_relay_fee.fee_vb(weight.to_vbytes_ceil());
+                if min_fee.map_or(true, |min_fee| fee < min_fee) {
+                    issues.push(StandardnessIssue::FeeRateTooLow { fee_rate });
+                }
+            }
+            (Ok(weight), Err(_)) => {
+                if weight > policy.max_weight {
+                    issues.push(StandardnessIssue::TooHeavy { weight });
+                }
+                issues.push(StandardnessIssue::UnknownFeeRate);
+            }
+            (Err(_), _) => issues.push(StandardnessIssue::UnknownFeeRate),
+        }
+        issues
+    }
+}
+
+/// Returns true if `spk` is a standard script pubkey under `policy`.
+///
+/// `OP_RETURN` outputs are standard if only data is pushed, their size is checked separately.
+fn is_standard_script(spk: &Script, policy: &StandardnessPolicy) -> bool {
+    if spk.is_op_return() {
+        return spk.instructions().skip(1).all(|instruction| match instruction {
+            Ok(Instruction::PushBytes(_)) => true,
+            Ok(Instruction::Op(op)) => op.to_u8() <= opcodes::all::OP_PUSHNUM_16.to_u8(),
+            Err(_) => false,
+        });
+    }
+    if spk.is_multisig() {
+        let keys =
+            spk.instructions().filter(|i| matches!(i, Ok(Instruction::PushBytes(_)))).count();
+        return policy.permit_bare_multisig && keys <= 3;
+    }
+    match spk.witness_version() {
+        // Version 0 programs are P2WPKH or P2WSH, other lengths can not be spent.
+        Some(WitnessVersion::V0) => spk.is_p2wpkh() || spk.is_p2wsh(),
+        Some(_) => true,
+        None => spk.is_p2pkh() || spk.is_p2sh() || spk.is_p2pk(),
+    }
+}
+
+/// A reason the transaction of a PSBT will not be relayed, see [`Psbt::check_standardness`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StandardnessIssue {
+    /// The transaction version is not standard.
+    Version(transaction::Version),
+    /// The lock time will not be final in the next block.
+    NonFinal {
+        /// The lock time of the transaction.
+        lock_time: absolute::LockTime,
+    },
+    /// The script pubkey of an output is not of a standard type.
+    NonStandardScript {
+        /// The index of the output.
+        output_index: usize,
+    },
+    /// An `OP_RETURN` output is larger than the policy allows.
+    OpReturnTooLarge {
+        /// The index of the output.
+        output_index: usize,
+        /// The size of the script pubkey.
+        size: usize,
+    },
+    /// There are more `OP_RETURN` outputs than the policy allows.
+    TooManyOpReturns {
+        /// The number of `OP_RETURN` outputs.
+        count: usize,
+    },
+    /// The value of an output is below the dust threshold.
+    Dust {
+        /// The index of the output.
+        output_index: usize,
+        /// The value of the output.
+        value: Amount,
+        /// The smallest value that is not dust.
+        threshold: Amount,
+    },
+    /// The estimated weight of the final transaction is above the maximum.
+    TooHeavy {
+        /// The estimated weight.
+        weight: Weight,
+    },
+    /// The estimated fee rate of the final transaction is below the minimum relay fee rate.
+    FeeRateTooLow {
+        /// The estimated fee rate.
+        fee_rate: FeeRate,
+    },
+    /// The fee or weight could not be computed, so the fee rate is unknown.
+    UnknownFeeRate,
+}
+
+impl fmt::Display for StandardnessIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use StandardnessIssue::*;
+
+        match *self {
+            Version(version) => write!(f, "transaction version {} is not standard", version),
+            NonFinal { lock_time } =>
+                write!(f, "lock time {} is not final in the next block", lock_time),
+            NonStandardScript { output_index } =>
+                write!(f, "the script pubkey of output {} is not standard", output_index),
+            OpReturnTooLarge { output_index, size } =>
+                write!(f, "OP_RETURN output {} is too large ({} bytes)", output_index, size),
+            TooManyOpReturns { count } =>
+                write!(f, "the transaction has too many OP_RETURN outputs ({})", count),
+            Dust { output_index, value, threshold } => write!(
+                f,
+                "output {} of {} is below the dust threshold of {}",
+                output_index, value, threshold
+            ),
+            TooHeavy { weight } =>
+                write!(f, "the estimated weight {} is above the standard maximum", weight),
+            FeeRateTooLow { fee_rate } => write!(
+                f,
+                "the estimated fee rate of {} sat/vB is below the minimum relay fee rate",
+                fee_rate.to_sat_per_vb_floor()
+            ),
+            UnknownFeeRate => f.write_str("the fee rate of the transaction is unknown"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StandardnessIssue {}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::script::Builder;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, WPubkeyHash, Witness};
+
+    use super::*;
+
+    #[test]
+    fn check_standardness() {
+        let wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let op_return = |size: usize| {
+            Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .push_slice(<&bitcoin::script::PushBytes>::try_from(&vec![0; size][..]).unwrap())
+                .into_script()
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::from_height(800_000).unwrap(),
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![
+                TxOut { value: Amount::from_sat(10_000), script_pubkey: wpkh.clone() },
+                TxOut { value: Amount::ZERO, script_pubkey: op_return(20) },
+            ],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(11_000), script_pubkey: wpkh.clone() });
+        psbt.inputs[0].final_script_witness =
+            Some(Witness::from_slice(&[vec![0; 72], vec![2; 33]]));
+
+        let policy = StandardnessPolicy {
+            tip_height: Some(absolute::Height::from_consensus(800_000).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(psbt.check_standardness(&policy), []);
+
+        let tx = &mut psbt.unsigned_tx;
+        tx.version = transaction::Version::non_standard(4);
+        tx.lock_time = absolute::LockTime::from_height(800_001).unwrap();
+        tx.output[0].value = Amount::from_sat(293);
+        tx.output.push(TxOut { value: Amount::ZERO, script_pubkey: op_return(81) });
+        tx.output
+            .push(TxOut { value: Amount::ONE_SAT, script_pubkey: ScriptBuf::from(vec![0x51]) });
+        psbt.outputs.resize(4, Default::default());
+        assert_eq!(
+            psbt.check_standardness(&policy),
+            [
+                StandardnessIssue::Version(transaction::Version::non_standard(4)),
+                StandardnessIssue::NonFinal {
+                    lock_time: absolute::LockTime::from_height(800_001).unwrap()
+                },
+                StandardnessIssue::Dust {
+                    output_index: 0,
+                    value: Amount::from_sat(293),
+                    threshold: Amount::from_sat(294)
+                },
+                StandardnessIssue::OpReturnTooLarge { output_index: 2, size: 84 },
+                StandardnessIssue::NonStandardScript { output_index: 3 },
+                StandardnessIssue::TooManyOpReturns { count: 2 },
+            ]
+        );
+
+        psbt.unsigned_tx.output.truncate(1);
+        psbt.outputs.truncate(1);
+        psbt.unsigned_tx.output[0].value = Amount::from_sat(10_950);
+        assert!(matches!(
+            psbt.check_standardness(&StandardnessPolicy::default())[..],
+            [StandardnessIssue::Version(_), StandardnessIssue::FeeRateTooLow { .. }]
+        ));
+        psbt.inputs[0].witness_utxo = None;
+        assert_eq!(
+            psbt.check_standardness(&StandardnessPolicy::default()).last(),
+            Some(&StandardnessIssue::UnknownFeeRate)
+        );
+    }
+}
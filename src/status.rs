@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Introspection of how far a PSBT has progressed through the BIP 174 roles.
+
+use core::fmt;
+
+use bitcoin::Script;
+
+use crate::prelude::*;
+use crate::{script, Input, Psbt};
+
+impl Psbt {
+    /// Returns the signing state of every input, see [`InputStatus`].
+    ///
+    /// Signatures are counted, not verified. Scripts are only understood as far as
+    /// [`Input::is_satisfiable`] understands them, the number of required signatures is reported
+    /// for single key and multisig scripts.
+    pub fn role_status(&self) -> PsbtStatus {
+        let inputs = self
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                if input.is_finalized() {
+                    return InputStatus::Finalized;
+                }
+                match self.spend_utxo(index) {
+                    Ok(utxo) => input.status(&utxo.script_pubkey),
+                    Err(_) => InputStatus::MissingData(vec![MissingField::Utxo]),
+                }
+            })
+            .collect();
+        PsbtStatus { inputs }
+    }
+}
+
+impl Input {
+    /// Returns the signing state of a non-finalized input spending `spk`.
+    fn status(&self, spk: &Script) -> InputStatus {
+        let mut missing = vec![];
+        let mut script = spk;
+        if spk.is_p2sh() {
+            match self.redeem_script {
+                Some(ref redeem_script) => script = redeem_script,
+                None => missing.push(MissingField::RedeemScript),
+            }
+        }
+        if script.is_p2wsh() && self.witness_script.is_none() {
+            missing.push(MissingField::WitnessScript);
+        }
+        if !missing.is_empty() {
+            return InputStatus::MissingData(missing);
+        }
+
+        let signatures = self.partial_sigs.len()
+            + self.tap_script_sigs.len()
+            + usize::from(self.tap_key_sig.is_some());
+        if self.is_satisfiable(spk) {
+            InputStatus::Signed { signatures }
+        } else if signatures == 0 {
+            InputStatus::ReadyToSign
+        } else {
+            InputStatus::PartiallySigned { signatures, required: self.required_signatures(spk) }
+        }
+    }
+
+    /// Returns the smallest number of signatures that satisfies `spk`, if it is understood.
+    fn required_signatures(&self, spk: &Script) -> Option<usize> {
+        if spk.is_p2tr() {
+            return self
+                .tap_scripts
+                .values()
+                .filter_map(|(script, _)| {
+                    script::tap_pk(script)
+                        .map(|_| 1)
+                        .or_else(|| script::tap_multi_a(script).map(|(threshold, _)| threshold))
+                })
+                .min();
+        }
+
+        let mut script = spk;
+        if let (true, Some(redeem_script)) = (script.is_p2sh(), &self.redeem_script) {
+            script = redeem_script;
+        }
+        if let (true, Some(witness_script)) = (script.is_p2wsh(), &self.witness_script) {
+            script = witness_script;
+        }
+
+        if script.is_p2pkh() || script.is_p2wpkh() || script.is_p2pk() {
+            Some(1)
+        } else {
+            script::multisig(script).map(|(threshold, _)| threshold)
+        }
+    }
+}
+
+/// The signing state of each input of a PSBT, returned by [`Psbt::role_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtStatus {
+    /// The state of each input, in order.
+    pub inputs: Vec<InputStatus>,
+}
+
+impl PsbtStatus {
+    /// Returns true if every input is finalized, i.e. the transaction can be extracted.
+    pub fn is_finalized(&self) -> bool {
+        self.inputs.iter().all(|status| *status == InputStatus::Finalized)
+    }
+
+    /// Returns true if every input is signed or finalized, i.e. the PSBT can be finalized.
+    pub fn is_signed(&self) -> bool {
+        self.inputs
+            .iter()
+            .all(|status| matches!(status, InputStatus::Signed { .. } | InputStatus::Finalized))
+    }
+
+    /// Returns the indices of the inputs that lack data a signer needs.
+    pub fn missing_data(&self) -> impl Iterator<Item = usize> + '_ {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, status)| matches!(status, InputStatus::MissingData(_)))
+            .map(|(index, _)| index)
+    }
+}
+
+/// The signing state of a single input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InputStatus {
+    /// The input lacks data a signer needs.
+    MissingData(Vec<MissingField>),
+    /// The input has everything a signer needs and no signatures yet.
+    ReadyToSign,
+    /// The input has some signatures, but not enough to satisfy the spent script.
+    PartiallySigned {
+        /// The number of signatures present.
+        signatures: usize,
+        /// The number of signatures required, if the script is understood.
+        required: Option<usize>,
+    },
+    /// The input has enough signatures and can be finalized.
+    Signed {
+        /// The number of signatures present.
+        signatures: usize,
+    },
+    /// The input is finalized.
+    Finalized,
+}
+
+impl InputStatus {
+    /// Returns the number of signatures still needed, if it is known.
+    pub fn missing_signatures(&self) -> Option<usize> {
+        match *self {
+            InputStatus::PartiallySigned { signatures, required } =>
+                required.map(|required| required.saturating_sub(signatures)),
+            InputStatus::Signed { .. } | InputStatus::Finalized => Some(0),
+            InputStatus::MissingData(_) | InputStatus::ReadyToSign => None,
+        }
+    }
+}
+
+impl fmt::Display for InputStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use InputStatus::*;
+
+        match *self {
+            MissingData(ref missing) => {
+                f.write_str("missing ")?;
+                for (i, field) in missing.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    fmt::Display::fmt(field, f)?;
+                }
+                Ok(())
+            }
+            ReadyToSign => f.write_str("ready to sign"),
+            PartiallySigned { signatures, required: Some(required) } =>
+                write!(f, "partially signed ({} of {} signatures)", signatures, required),
+            PartiallySigned { signatures, required: None } =>
+                write!(f, "partially signed ({} signatures)", signatures),
+            Signed { .. } => f.write_str("signed"),
+            Finalized => f.write_str("finalized"),
+        }
+    }
+}
+
+/// Data an input needs before it can be signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MissingField {
+    /// Neither the witness UTXO nor the non-witness UTXO.
+    Utxo,
+    /// The redeem script of a P2SH output.
+    RedeemScript,
+    /// The witness script of a P2WSH output.
+    WitnessScript,
+}
+
+impl fmt::Display for MissingField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use MissingField::*;
+
+        match *self {
+            Utxo => f.write_str("utxo"),
+            RedeemScript => f.write_str("redeem script"),
+            WitnessScript => f.write_str("witness script"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::Witness;
+
+    use super::*;
+    use crate::test_utils::{dummy_sig, Fixture, ScriptType};
+
+    #[test]
+    fn ready_to_sign() {
+        for script_type in ScriptType::ALL {
+            let status = Fixture::new(script_type).psbt.role_status();
+            assert_eq!(status.inputs, vec![InputStatus::ReadyToSign], "{:?}", script_type);
+            assert_eq!(status.inputs[0].missing_signatures(), None);
+        }
+    }
+
+    #[test]
+    fn missing_data() {
+        let mut psbt = Fixture::new(ScriptType::P2wsh).psbt;
+        psbt.inputs[0].witness_script = None;
+        let status = psbt.role_status();
+        assert_eq!(status.inputs[0], InputStatus::MissingData(vec![MissingField::WitnessScript]));
+        assert_eq!(status.missing_data().collect::<Vec<_>>(), vec![0]);
+
+        let mut psbt = Fixture::new(ScriptType::P2shMultisig).psbt;
+        psbt.inputs[0].redeem_script = None;
+        assert_eq!(
+            psbt.role_status().inputs[0],
+            InputStatus::MissingData(vec![MissingField::RedeemScript])
+        );
+
+        let mut psbt = Fixture::new(ScriptType::P2wpkh).psbt;
+        psbt.inputs[0].witness_utxo = None;
+        let status = psbt.role_status();
+        assert_eq!(status.inputs[0], InputStatus::MissingData(vec![MissingField::Utxo]));
+        assert_eq!(status.inputs[0].to_string(), "missing utxo");
+    }
+
+    #[test]
+    fn partially_signed() {
+        let mut fixture = Fixture::new(ScriptType::P2wsh);
+        let pk = fixture.public_key(0);
+        fixture.psbt.inputs[0].partial_sigs.insert(pk, dummy_sig());
+
+        let status = fixture.psbt.role_status();
+        assert_eq!(
+            status.inputs[0],
+            InputStatus::PartiallySigned { signatures: 1, required: Some(2) }
+        );
+        assert_eq!(status.inputs[0].missing_signatures(), Some(1));
+        assert_eq!(status.inputs[0].to_string(), "partially signed (1 of 2 signatures)");
+        assert!(!status.is_signed());
+    }
+
+    #[test]
+    fn signed() {
+        let mut fixture = Fixture::new(ScriptType::P2wsh);
+        for index in [0, 2] {
+            let pk = fixture.public_key(index);
+            fixture.psbt.inputs[0].partial_sigs.insert(pk, dummy_sig());
+        }
+
+        let status = fixture.psbt.role_status();
+        assert_eq!(status.inputs[0], InputStatus::Signed { signatures: 2 });
+        assert_eq!(status.inputs[0].missing_signatures(), Some(0));
+        assert!(status.is_signed());
+        assert!(!status.is_finalized());
+    }
+
+    #[test]
+    fn finalized() {
+        let mut psbt = Fixture::new(ScriptType::P2wpkh).psbt;
+        psbt.inputs[0].final_script_witness = Some(Witness::default());
+
+        let status = psbt.role_status();
+        assert_eq!(status.inputs[0], InputStatus::Finalized);
+        assert!(status.is_signed());
+        assert!(status.is_finalized());
+    }
+}
@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Incremental PSBT (de)serialization.
+//!
+//! PSBTs with many inputs, each carrying a full `non_witness_utxo`, can be tens of megabytes. The
+//! reader and writer in this module process such a PSBT one key-value map at a time so that only
+//! the map currently being processed needs to be held in memory.
+
+use bitcoin::consensus::encode::Decodable;
+use bitcoin::io::{self, BufRead, Write};
+use bitcoin::Transaction;
+
+use crate::map::Map;
+use crate::prelude::*;
+use crate::serialize::DeserializeOptions;
+use crate::{Error, Input, MapLocation, Output, Psbt};
+
+pub(crate) const MAGIC_BYTES: &[u8] = b"psbt";
+pub(crate) const PSBT_SERPARATOR: u8 = 0xff_u8;
+
+/// Reads a serialized PSBT one map at a time.
+///
+/// The global map is read by [`PsbtReader::new`], the input and output maps are then read on
+/// demand in the order they are serialized.
+///
+/// ```
+/// # use psbt_v0::{PsbtBuilder, PsbtReader};
+/// # let bytes = PsbtBuilder::new().input(Default::default()).build().serialize();
+/// let mut slice = &bytes[..];
+/// let mut reader = PsbtReader::new(&mut slice)?;
+/// while let Some(input) = reader.read_input()? {
+///     // Process the input, then drop it before reading the next one.
+/// #   let _ = input;
+/// }
+/// # Ok::<_, psbt_v0::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct PsbtReader<'a, R: BufRead + ?Sized> {
+    reader: &'a mut R,
+    global: Psbt,
+    options: DeserializeOptions,
+    inputs_read: usize,
+    outputs_read: usize,
+}
+
+impl<'a, R: BufRead + ?Sized> PsbtReader<'a, R> {
+    /// Reads the magic bytes and the global map from `reader`.
+    pub fn new(reader: &'a mut R) -> Result<Self, Error> {
+        Self::with_options(reader, DeserializeOptions::UNLIMITED)
+    }
+
+    /// Reads the magic bytes and the global map from `reader`, enforcing the limits in `options`.
+    ///
+    /// The total size limit, [`DeserializeOptions::max_size`], is not enforced by the reader, see
+    /// [`Psbt::deserialize_from_reader_with_options`].
+    pub fn with_options(reader: &'a mut R, options: DeserializeOptions) -> Result<Self, Error> {
+        let magic: [u8; 4] = Decodable::consensus_decode(reader)?;
+        if magic != MAGIC_BYTES {
+            return Err(Error::InvalidMagic);
+        }
+
+        let separator: u8 = Decodable::consensus_decode(reader)?;
+        if separator != PSBT_SERPARATOR {
+            return Err(Error::InvalidSeparator);
+        }
+
+        let global = Psbt::decode_global(reader, &options)?;
+        global.unsigned_tx_checks()?;
+        if global.unsigned_tx.input.len() > options.max_inputs {
+            return Err(Error::TooManyInputs { max: options.max_inputs });
+        }
+        if global.unsigned_tx.output.len() > options.max_outputs {
+            return Err(Error::TooManyOutputs { max: options.max_outputs });
+        }
+
+        Ok(PsbtReader { reader, global, options, inputs_read: 0, outputs_read: 0 })
+    }
+
+    /// Returns the unsigned transaction.
+    pub fn unsigned_tx(&self) -> &Transaction { &self.global.unsigned_tx }
+
+    /// Returns the global map as a PSBT with no input or output maps.
+    pub fn global(&self) -> &Psbt { &self.global }
+
+    /// Reads the next input map, returns `None` once all input maps have been read.
+    pub fn read_input(&mut self) -> Result<Option<Input>, Error> {
+        if self.inputs_read == self.global.unsigned_tx.input.len() {
+            return Ok(None);
+        }
+        let input =
+            Input::decode(self.reader, MapLocation::Input(self.inputs_read), &self.options)?;
+        self.inputs_read += 1;
+        Ok(Some(input))
+    }
+
+    /// Reads the next output map, returns `None` once all output maps have been read.
+    ///
+    /// Any input maps not read yet are skipped.
+    pub fn read_output(&mut self) -> Result<Option<Output>, Error> {
+        while self.read_input()?.is_some() {}
+
+        if self.outputs_read == self.global.unsigned_tx.output.len() {
+            return Ok(None);
+        }
+        let output =
+            Output::decode(self.reader, MapLocation::Output(self.outputs_read), &self.options)?;
+        self.outputs_read += 1;
+        Ok(Some(output))
+    }
+
+    /// Reads all maps, returning the complete PSBT.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::PartiallyRead`] if a map was already returned by [`PsbtReader::read_input`] or
+    /// [`PsbtReader::read_output`], or any error decoding the maps.
+    pub fn into_psbt(mut self) -> Result<Psbt, Error> {
+        if self.inputs_read > 0 || self.outputs_read > 0 {
+            return Err(Error::PartiallyRead);
+        }
+
+        let mut inputs = Vec::with_capacity(self.global.unsigned_tx.input.len());
+        while let Some(input) = self.read_input()? {
+            inputs.push(input);
+        }
+        let mut outputs = Vec::with_capacity(self.global.unsigned_tx.output.len());
+        while let Some(output) = self.read_output()? {
+            outputs.push(output);
+        }
+
+        let mut psbt = self.global;
+        psbt.inputs = inputs;
+        psbt.outputs = outputs;
+        Ok(psbt)
+    }
+
+    /// Returns the underlying reader, positioned after the last map read.
+    pub fn into_inner(self) -> &'a mut R { self.reader }
+}
+
+/// Writes a serialized PSBT one map at a time.
+///
+/// The global map is written by [`PsbtWriter::new`], then exactly one input map must be written
+/// for every input of the unsigned transaction followed by one output map for every output.
+#[derive(Debug)]
+pub struct PsbtWriter<'a, W: Write + ?Sized> {
+    writer: &'a mut W,
+    inputs: usize,
+    outputs: usize,
+    inputs_written: usize,
+    outputs_written: usize,
+    written_len: usize,
+}
+
+impl<'a, W: Write + ?Sized> PsbtWriter<'a, W> {
+    /// Writes the magic bytes and the global map of `psbt` to `writer`.
+    ///
+    /// The input and output maps of `psbt` are ignored, so they may be empty.
+    pub fn new(writer: &'a mut W, psbt: &Psbt) -> io::Result<Self> {
+        let mut written_len = write_all(writer, MAGIC_BYTES)?;
+        written_len += write_all(writer, &[PSBT_SERPARATOR])?;
+        written_len += write_all(writer, &psbt.serialize_map())?;
+
+        Ok(PsbtWriter {
+            writer,
+            inputs: psbt.unsigned_tx.input.len(),
+            outputs: psbt.unsigned_tx.output.len(),
+            inputs_written: 0,
+            outputs_written: 0,
+            written_len,
+        })
+    }
+
+    /// Writes the next input map.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind [`io::ErrorKind::InvalidInput`] if all input maps have already been
+    /// written, or any error from the underlying writer.
+    pub fn write_input(&mut self, input: &Input) -> io::Result<()> {
+        if self.inputs_written == self.inputs {
+            return Err(invalid_input("all input maps have been written"));
+        }
+        self.written_len += write_all(self.writer, &input.serialize_map())?;
+        self.inputs_written += 1;
+        Ok(())
+    }
+
+    /// Writes the next output map.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind [`io::ErrorKind::InvalidInput`] if an input map has not been written yet
+    /// or all output maps have already been written, or any error from the underlying writer.
+    pub fn write_output(&mut self, output: &Output) -> io::Result<()> {
+        if self.inputs_written != self.inputs {
+            return Err(invalid_input("input maps must be written before outputs"));
+        }
+        if self.outputs_written == self.outputs {
+            return Err(invalid_input("all output maps have been written"));
+        }
+        self.written_len += write_all(self.writer, &output.serialize_map())?;
+        self.outputs_written += 1;
+        Ok(())
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn written_len(&self) -> usize { self.written_len }
+
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// An error of kind [`io::ErrorKind::InvalidInput`] if not all input and output maps have
+    /// been written.
+    pub fn finish(self) -> io::Result<usize> {
+        if self.inputs_written != self.inputs {
+            return Err(invalid_input("not all input maps have been written"));
+        }
+        if self.outputs_written != self.outputs {
+            return Err(invalid_input("not all output maps have been written"));
+        }
+        Ok(self.written_len)
+    }
+}
+
+fn invalid_input(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub(crate) fn write_all<W: Write + ?Sized>(w: &mut W, data: &[u8]) -> io::Result<usize> {
+    w.write_all(data).map(|_| data.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{Amount, OutPoint, ScriptBuf, TxOut};
+
+    use super::*;
+    use crate::PsbtBuilder;
+
+    #[test]
+    fn stream_roundtrip() {
+        let txout = |sat| TxOut { value: Amount::from_sat(sat), script_pubkey: ScriptBuf::new() };
+        let mut psbt = PsbtBuilder::new()
+            .input(OutPoint::null())
+            .input(OutPoint { vout: 1, ..OutPoint::null() })
+            .output(txout(1_000))
+            .build();
+        psbt.inputs[0].witness_utxo = Some(txout(2_000));
+        psbt.inputs[1].witness_utxo = Some(txout(3_000));
+        psbt.outputs[0].redeem_script = Some(ScriptBuf::from_bytes(vec![0x51]));
+        let bytes = psbt.serialize();
+
+        let mut buf = Vec::new();
+        let mut writer = PsbtWriter::new(&mut buf, &psbt).unwrap();
+        for input in &psbt.inputs {
+            writer.write_input(input).unwrap();
+        }
+        writer.write_output(&psbt.outputs[0]).unwrap();
+        assert_eq!(writer.finish().unwrap(), bytes.len());
+        assert_eq!(buf, bytes);
+
+        let mut slice = &bytes[..];
+        let mut reader = PsbtReader::new(&mut slice).unwrap();
+        assert_eq!(reader.unsigned_tx(), &psbt.unsigned_tx);
+        assert_eq!(reader.read_input().unwrap().as_ref(), Some(&psbt.inputs[0]));
+        // Reading an output skips the remaining inputs.
+        assert_eq!(reader.read_output().unwrap().as_ref(), Some(&psbt.outputs[0]));
+        assert_eq!(reader.read_input().unwrap(), None);
+        assert_eq!(reader.read_output().unwrap(), None);
+        assert!(reader.into_inner().is_empty());
+
+        let mut slice = &bytes[..];
+        let reader = PsbtReader::new(&mut slice).unwrap();
+        assert_eq!(reader.into_psbt().unwrap(), psbt);
+
+        let mut slice = &bytes[..];
+        let mut reader = PsbtReader::new(&mut slice).unwrap();
+        reader.read_input().unwrap();
+        assert!(matches!(reader.into_psbt(), Err(Error::PartiallyRead)));
+
+        assert!(matches!(PsbtReader::new(&mut &bytes[1..]), Err(Error::InvalidMagic)));
+    }
+
+    #[test]
+    fn stream_writer_order() {
+        let psbt = PsbtBuilder::new().input(OutPoint::null()).output(TxOut::NULL).build();
+        let mut buf = Vec::new();
+        let mut writer = PsbtWriter::new(&mut buf, &psbt).unwrap();
+        let err = writer.write_output(&psbt.outputs[0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        writer.write_input(&psbt.inputs[0]).unwrap();
+        assert_eq!(
+            writer.write_input(&psbt.inputs[0]).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(writer.finish().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn serialize_mismatched_map_counts() {
+        let mut psbt = PsbtBuilder::new().input(OutPoint::null()).output(TxOut::NULL).build();
+        psbt.inputs.push(Input::default());
+        psbt.outputs.clear();
+
+        // Serializing does not check the number of maps.
+        let bytes = psbt.serialize();
+        assert_eq!(psbt.serialize_to_writer(&mut Vec::new()).unwrap(), bytes.len());
+        assert_ne!(Psbt::deserialize(&bytes).ok(), Some(psbt));
+    }
+}
@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Strict deserialization rejecting PSBTs that violate BIP 174 or BIP 371.
+
+use core::fmt;
+
+use bitcoin_internals::write_err;
+
+use crate::{map, CheckInputError, Error, MapLocation, Psbt};
+
+impl Psbt {
+    /// Deserializes a PSBT, rejecting any that violate the MUST clauses of BIP 174 and BIP 371.
+    ///
+    /// [`Psbt::deserialize`] already rejects duplicate keys, keys and values of the wrong length,
+    /// and unsigned transactions with scriptSigs or witnesses. In addition this rejects:
+    ///
+    /// - Data following the PSBT.
+    /// - A `non_witness_utxo` that is not the transaction spent by the input, or that disagrees
+    ///   with `witness_utxo`.
+    /// - Redeem and witness scripts that do not hash to the script pubkey or witness program.
+    /// - Fields not allowed for the type of the spent or created output: Taproot fields in
+    ///   non-Taproot inputs and outputs, and ECDSA signatures, redeem scripts, and witness scripts
+    ///   in Taproot ones.
+    ///
+    /// Inputs without UTXO information are only checked against the rules that do not need it.
+    pub fn deserialize_strict(bytes: &[u8]) -> Result<Self, StrictError> {
+        let mut reader = bytes;
+        let psbt = Psbt::deserialize_from_reader(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(StrictError::TrailingData { len: reader.len() });
+        }
+
+        for input_index in 0..psbt.inputs.len() {
+            let spk = psbt
+                .check_input_scripts(input_index)
+                .map_err(|error| StrictError::Input { input_index, error })?;
+            if let Some(spk) = spk {
+                psbt.check_allowed_fields(MapLocation::Input(input_index), spk)?;
+            }
+        }
+        for (output_index, txout) in psbt.unsigned_tx.output.iter().enumerate() {
+            psbt.check_allowed_fields(MapLocation::Output(output_index), &txout.script_pubkey)?;
+        }
+        Ok(psbt)
+    }
+
+    fn check_allowed_fields(
+        &self,
+        location: MapLocation,
+        spk: &bitcoin::Script,
+    ) -> Result<(), StrictError> {
+        match map::disallowed_field(self, location, spk) {
+            Some(type_value) => Err(StrictError::DisallowedField { location, type_value }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error returned by [`Psbt::deserialize_strict`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StrictError {
+    /// The PSBT could not be deserialized.
+    Parse(Error),
+    /// The PSBT is followed by more data.
+    TrailingData {
+        /// The number of bytes following the PSBT.
+        len: usize,
+    },
+    /// The UTXO or scripts of an input are inconsistent.
+    Input {
+        /// The index of the input.
+        input_index: usize,
+        /// The inconsistency.
+        error: CheckInputError,
+    },
+    /// A map contains a field not allowed for the type of the spent or created output.
+    DisallowedField {
+        /// The map containing the field.
+        location: MapLocation,
+        /// The key type of the field.
+        type_value: u8,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(StrictError);
+
+impl StrictError {
+    /// Returns the name of the disallowed field, if this is a [`StrictError::DisallowedField`].
+    pub fn field(&self) -> Option<&'static str> {
+        match *self {
+            StrictError::DisallowedField { location, type_value } =>
+                Some(map::field_name(location, type_value)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StrictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use StrictError::*;
+
+        match *self {
+            Parse(ref e) => write_err!(f, "failed to deserialize the PSBT"; e),
+            TrailingData { len } => write!(f, "the PSBT is followed by {} bytes of data", len),
+            Input { input_index, ref error } =>
+                write_err!(f, "input {} is inconsistent", input_index; error),
+            DisallowedField { location, type_value } => write!(
+                f,
+                "the {} field is not allowed in {}",
+                map::field_name(location, type_value),
+                location
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StrictError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use StrictError::*;
+
+        match *self {
+            Parse(ref e) => Some(e),
+            Input { ref error, .. } => Some(error),
+            TrailingData { .. } | DisallowedField { .. } => None,
+        }
+    }
+}
+
+impl From<Error> for StrictError {
+    fn from(e: Error) -> Self { Self::Parse(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bitcoin::{
+        absolute, transaction, Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut,
+        XOnlyPublicKey,
+    };
+
+    use super::*;
+
+    #[test]
+    fn deserialize_strict() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = bitcoin::PublicKey::new(sk.public_key(&secp));
+        let xonly = XOnlyPublicKey::from(pk.inner);
+
+        let p2tr = ScriptBuf::new_p2tr(&secp, xonly, None);
+        let p2wpkh = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap());
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::null(), ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(1_000), script_pubkey: p2wpkh.clone() }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo =
+            Some(TxOut { value: Amount::from_sat(2_000), script_pubkey: p2tr });
+        psbt.inputs[0].tap_internal_key = Some(xonly);
+        assert_eq!(Psbt::deserialize_strict(&psbt.serialize()).unwrap(), psbt);
+
+        let mut bytes = psbt.serialize();
+        bytes.push(0x00);
+        assert!(Psbt::deserialize(&bytes).is_ok());
+        assert!(matches!(
+            Psbt::deserialize_strict(&bytes),
+            Err(StrictError::TrailingData { len: 1 })
+        ));
+
+        let mut invalid = psbt.clone();
+        invalid.outputs[0].tap_internal_key = Some(xonly);
+        let err = Psbt::deserialize_strict(&invalid.serialize()).unwrap_err();
+        assert!(matches!(
+            err,
+            StrictError::DisallowedField { location: MapLocation::Output(0), type_value: 0x05 }
+        ));
+        assert_eq!(err.field(), Some("tap_internal_key"));
+
+        let mut invalid = psbt.clone();
+        invalid.inputs[0].witness_script = Some(ScriptBuf::new());
+        assert!(matches!(
+            Psbt::deserialize_strict(&invalid.serialize()),
+            Err(StrictError::Input {
+                input_index: 0,
+                error: CheckInputError::WitnessScriptMismatch
+            })
+        ));
+
+        let mut invalid = psbt;
+        invalid.inputs[0].redeem_script = Some(p2wpkh);
+        assert!(matches!(
+            Psbt::deserialize_strict(&invalid.serialize()),
+            Err(StrictError::Input {
+                input_index: 0,
+                error: CheckInputError::RedeemScriptMismatch
+            })
+        ));
+    }
+}
@@ -0,0 +1,449 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Ready-made PSBTs for testing signers, finalizers and other code built on this crate.
+//!
+//! A [`Fixture`] is a PSBT spending one output of a known [`ScriptType`], with everything needed
+//! to sign it, and the master key it is signed with. Fixtures are deterministic, the same script
+//! type and network always give the same PSBT. The PSBTs of the BIP 174 and BIP 371 test vectors
+//! are in [`BIP174_VALID`], [`BIP174_INVALID`], [`BIP371_VALID`] and [`BIP371_INVALID`].
+//!
+//! ```
+//! use psbt_v0::bitcoin::secp256k1::Secp256k1;
+//! use psbt_v0::bitcoin::NetworkKind;
+//! use psbt_v0::test_utils::{Fixture, ScriptType};
+//!
+//! let secp = Secp256k1::new();
+//! let mut fixture = Fixture::builder(ScriptType::P2wpkh).network(NetworkKind::Main).build();
+//! fixture.psbt.sign(&fixture.xpriv, &secp).unwrap();
+//! assert_eq!(fixture.psbt.inputs[0].partial_sigs.len(), 1);
+//! ```
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, KeySource, Xpriv};
+use bitcoin::hex::FromHex;
+use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{All, Secp256k1};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TaprootBuilder};
+use bitcoin::{
+    absolute, transaction, Amount, NetworkKind, OutPoint, PublicKey, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, XOnlyPublicKey,
+};
+
+use crate::prelude::*;
+use crate::{Error, Input, Psbt};
+
+/// The seed of the master key of every fixture.
+const SEED: [u8; 32] = [1; 32];
+
+/// The script types [`Fixture`] builds PSBTs for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptType {
+    /// Pay to public key hash.
+    P2pkh,
+    /// A 2-of-3 `OP_CHECKMULTISIG` in P2SH.
+    P2shMultisig,
+    /// Pay to witness public key hash.
+    P2wpkh,
+    /// A 2-of-3 `OP_CHECKMULTISIG` in P2WSH.
+    P2wsh,
+    /// A Taproot key path spend.
+    P2trKey,
+    /// A Taproot script path spend of a single `<key> OP_CHECKSIG` leaf.
+    P2trScript,
+}
+
+impl ScriptType {
+    /// All script types.
+    pub const ALL: [ScriptType; 6] = [
+        ScriptType::P2pkh,
+        ScriptType::P2shMultisig,
+        ScriptType::P2wpkh,
+        ScriptType::P2wsh,
+        ScriptType::P2trKey,
+        ScriptType::P2trScript,
+    ];
+}
+
+/// A PSBT with one input of a known script type, and the key that signs it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// The PSBT, its input has the UTXO, scripts and key origins needed to sign it.
+    pub psbt: Psbt,
+    /// The master key the keys of the input are derived from, at `m/0/i`.
+    pub xpriv: Xpriv,
+}
+
+impl Fixture {
+    /// Returns the fixture for `script_type` on test networks.
+    pub fn new(script_type: ScriptType) -> Self { Fixture::builder(script_type).build() }
+
+    /// Returns a builder for the fixture for `script_type`.
+    pub fn builder(script_type: ScriptType) -> FixtureBuilder {
+        FixtureBuilder {
+            script_type,
+            network: NetworkKind::Test,
+            amount: Amount::from_sat(100_000),
+            fee: Amount::from_sat(1_000),
+        }
+    }
+}
+
+/// Builds a [`Fixture`], see [`Fixture::builder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureBuilder {
+    script_type: ScriptType,
+    network: NetworkKind,
+    amount: Amount,
+    fee: Amount,
+}
+
+impl FixtureBuilder {
+    /// Sets the network of the master key, test networks by default.
+    pub fn network(mut self, network: NetworkKind) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Sets the value of the spent output, 100,000 satoshis by default.
+    pub fn amount(mut self, amount: Amount) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    /// Sets the fee, 1,000 satoshis by default. The rest of the amount goes to the one output.
+    pub fn fee(mut self, fee: Amount) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Builds the fixture.
+    pub fn build(self) -> Fixture {
+        let secp = Secp256k1::new();
+        let xpriv = Xpriv::new_master(self.network, &SEED).expect("32 bytes is a valid seed");
+        let keys = (0..4).map(|index| key(&xpriv, index, &secp)).collect::<Vec<_>>();
+        let xonly = |index: usize| XOnlyPublicKey::from(keys[index].0.inner);
+
+        let mut input = Input::default();
+        let script_pubkey = match self.script_type {
+            ScriptType::P2pkh => {
+                input.bip32_derivation.insert(keys[0].0.inner, keys[0].1.clone());
+                ScriptBuf::new_p2pkh(&keys[0].0.pubkey_hash())
+            }
+            ScriptType::P2shMultisig | ScriptType::P2wsh => {
+                let mut builder = Builder::new().push_int(2);
+                for (pk, origin) in &keys[..3] {
+                    builder = builder.push_key(pk);
+                    input.bip32_derivation.insert(pk.inner, origin.clone());
+                }
+                let script = builder.push_int(3).push_opcode(OP_CHECKMULTISIG).into_script();
+                if self.script_type == ScriptType::P2wsh {
+                    input.witness_script = Some(script.clone());
+                    script.to_p2wsh()
+                } else {
+                    input.redeem_script = Some(script.clone());
+                    script.to_p2sh()
+                }
+            }
+            ScriptType::P2wpkh => {
+                input.bip32_derivation.insert(keys[0].0.inner, keys[0].1.clone());
+                ScriptBuf::new_p2wpkh(
+                    &keys[0].0.wpubkey_hash().expect("derived keys are compressed"),
+                )
+            }
+            ScriptType::P2trKey => {
+                input.tap_internal_key = Some(xonly(0));
+                input.tap_key_origins.insert(xonly(0), (vec![], keys[0].1.clone()));
+                ScriptBuf::new_p2tr(&secp, xonly(0), None)
+            }
+            ScriptType::P2trScript => {
+                let leaf = Builder::new()
+                    .push_x_only_key(&xonly(1))
+                    .push_opcode(OP_CHECKSIG)
+                    .into_script();
+                let spend_info = TaprootBuilder::new()
+                    .add_leaf(0, leaf.clone())
+                    .expect("a single leaf at depth 0 is valid")
+                    .finalize(&secp, xonly(0))
+                    .expect("the tree is complete");
+                let control_block = spend_info
+                    .control_block(&(leaf.clone(), LeafVersion::TapScript))
+                    .expect("the leaf is in the tree");
+                let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+                // The internal key has no origin, so only the script path is signed.
+                input.tap_internal_key = Some(xonly(0));
+                input.tap_merkle_root = spend_info.merkle_root();
+                input.tap_scripts.insert(control_block, (leaf, LeafVersion::TapScript));
+                input.tap_key_origins.insert(xonly(1), (vec![leaf_hash], keys[1].1.clone()));
+                ScriptBuf::new_p2tr_tweaked(spend_info.output_key())
+            }
+        };
+
+        let prev_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut { value: self.amount, script_pubkey }],
+        };
+        let previous_output = OutPoint { txid: prev_tx.compute_txid(), vout: 0 };
+        match self.script_type {
+            ScriptType::P2pkh | ScriptType::P2shMultisig => input.non_witness_utxo = Some(prev_tx),
+            _ => input.witness_utxo = Some(prev_tx.output[0].clone()),
+        }
+
+        let recipient = keys[3].0.wpubkey_hash().expect("derived keys are compressed");
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: self.amount.checked_sub(self.fee).unwrap_or(Amount::ZERO),
+                script_pubkey: ScriptBuf::new_p2wpkh(&recipient),
+            }],
+        })
+        .expect("the unsigned transaction has no scriptSigs or witnesses");
+        psbt.inputs[0] = input;
+        Fixture { psbt, xpriv }
+    }
+}
+
+/// Returns the key at `m/0/index` of `xpriv` and its origin.
+fn key(xpriv: &Xpriv, index: u32, secp: &Secp256k1<All>) -> (PublicKey, KeySource) {
+    let path = DerivationPath::from(vec![ChildNumber::from(0), ChildNumber::from(index)]);
+    let sk = xpriv.derive_priv(secp, &path).expect("the path is not too deep").to_priv();
+    (sk.public_key(secp), (xpriv.fingerprint(secp), path))
+}
+
+/// A PSBT of the BIP test vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TestVector {
+    /// What the vector is for, as described by the BIP.
+    pub description: &'static str,
+    /// The hex encoded PSBT.
+    pub hex: &'static str,
+}
+
+impl TestVector {
+    /// Parses the PSBT, only valid vectors parse.
+    pub fn psbt(&self) -> Result<Psbt, Error> {
+        let bytes = Vec::<u8>::from_hex(self.hex).expect("the vectors are valid hex");
+        Psbt::deserialize(&bytes)
+    }
+}
+
+/// The valid PSBTs of the BIP 174 test vectors.
+pub const BIP174_VALID: &[TestVector] = &[
+    TestVector {
+        description: "One P2PKH input, the outputs are empty.",
+        hex: "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab300000000000000",
+    },
+    TestVector {
+        description: "One P2PKH input and one P2SH-P2WPKH input, the first one finalized, the outputs are empty.",
+        hex: "70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac000000000001076a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682dc86e2d73fa882920001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000",
+    },
+    TestVector {
+        description: "One P2PKH input with a sighash type, the outputs are empty.",
+        hex: "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000000001030401000000000000",
+    },
+    TestVector {
+        description: "One P2PKH input and one P2SH-P2WPKH input, the outputs have BIP 32 derivations.",
+        hex: "70736274ff0100a00200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40000000000feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac00000000000100df0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf6000000006a473044022070b2245123e6bf474d60c5b50c043d4c691a5d2435f09a34a7662a9dc251790a022001329ca9dacf280bdf30740ec0390422422c81cb45839457aeb76fc12edd95b3012102657d118d3357b8e0f4c2cd46db7b39f6d9c38d9a70abcb9b2de5dc8dbfe4ce31feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e13000001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb8230800220202ead596687ca806043edc3de116cdf29d5e9257c196cd055cf698c8d02bf24e9910b4a6ba670000008000000080020000800022020394f62be9df19952c5587768aeb7698061ad2c4a25c894f47d8c162b4d7213d0510b4a6ba6700000080010000800200008000",
+    },
+    TestVector {
+        description: "One P2SH-P2WSH multisig input with a partial signature, its scripts and BIP 32 derivations.",
+        hex: "70736274ff0100550200000001279a2323a5dfb51fc45f220fa58b0fc13e1e3342792a85d7e36cd6333b5cbc390000000000ffffffff01a05aea0b000000001976a914ffe9c0061097cc3b636f2cb0460fa4fc427d2b4588ac0000000000010120955eea0b0000000017a9146345200f68d189e1adc0df1c4d16ea8f14c0dbeb87220203b1341ccba7683b6af4f1238cd6e97e7167d569fac47f1e48d47541844355bd4646304302200424b58effaaa694e1559ea5c93bbfd4a89064224055cdf070b6771469442d07021f5c8eb0fea6516d60b8acb33ad64ede60e8785bfb3aa94b99bdf86151db9a9a010104220020771fd18ad459666dd49f3d564e3dbc42f4c84774e360ada16816a8ed488d5681010547522103b1341ccba7683b6af4f1238cd6e97e7167d569fac47f1e48d47541844355bd462103de55d1e1dac805e3f8a58c1fbf9b94c02f3dbaafe127fefca4995f26f82083bd52ae220603b1341ccba7683b6af4f1238cd6e97e7167d569fac47f1e48d47541844355bd4610b4a6ba67000000800000008004000080220603de55d1e1dac805e3f8a58c1fbf9b94c02f3dbaafe127fefca4995f26f82083bd10b4a6ba670000008000000080050000800000",
+    },
+    TestVector {
+        description: "Unknown fields in the global map.",
+        hex: "70736274ff01003f0200000001ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff0000000000ffffffff010000000000000000036a010000000000000a0f0102030405060708090f0102030405060708090a0b0c0d0e0f0000",
+    },
+];
+
+/// The invalid PSBTs of the BIP 174 test vectors.
+pub const BIP174_INVALID: &[TestVector] = &[
+    TestVector {
+        description: "A network transaction, not a PSBT.",
+        hex: "0200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf6000000006a473044022070b2245123e6bf474d60c5b50c043d4c691a5d2435f09a34a7662a9dc251790a022001329ca9dacf280bdf30740ec0390422422c81cb45839457aeb76fc12edd95b3012102657d118d3357b8e0f4c2cd46db7b39f6d9c38d9a70abcb9b2de5dc8dbfe4ce31feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300",
+    },
+    TestVector {
+        description: "The output maps are missing.",
+        hex: "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000000000",
+    },
+    TestVector {
+        description: "The unsigned transaction has scriptSigs.",
+        hex: "70736274ff0100fd0a010200000002ab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be4000000006a47304402204759661797c01b036b25928948686218347d89864b719e1f7fcf57d1e511658702205309eabf56aa4d8891ffd111fdf1336f3a29da866d7f8486d75546ceedaf93190121035cdc61fc7ba971c0b501a646a2a83b102cb43881217ca682dc86e2d73fa88292feffffffab0949a08c5af7c49b8212f417e2f15ab3f5c33dcf153821a8139f877a5b7be40100000000feffffff02603bea0b000000001976a914768a40bbd740cbe81d988e71de2a4d5c71396b1d88ac8e240000000000001976a9146f4620b553fa095e721b9ee0efe9fa039cca459788ac00000000000001012000e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787010416001485d13537f2e265405a34dbafa9e3dda01fb82308000000",
+    },
+    TestVector {
+        description: "There is no unsigned transaction.",
+        hex: "70736274ff000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000000000",
+    },
+    TestVector {
+        description: "The unsigned transaction is set twice.",
+        hex: "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab30000000001003f0200000001ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff0000000000ffffffff010000000000000000036a010000000000000000",
+    },
+];
+
+/// The valid PSBTs of the BIP 371 test vectors.
+pub const BIP371_VALID: &[TestVector] = &[
+    TestVector {
+        description: "A key path input with its internal key and key origin.",
+        hex: "70736274ff010052020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff0148e6052a01000000160014768e1eeb4cf420866033f80aceff0f9720744969000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a07572116fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000800100000000000000011720fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa232002202036b772a6db74d8753c98a827958de6c78ab3312109f37d3e0304484242ece73d818772b2da7540000800100008000000080000000000000000000",
+    },
+    TestVector {
+        description: "A key path input with a key path signature.",
+        hex: "70736274ff010052020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff0148e6052a01000000160014768e1eeb4cf420866033f80aceff0f9720744969000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757011340bb53ec917bad9d906af1ba87181c48b86ace5aae2b53605a725ca74625631476fc6f5baedaf4f2ee0f477f36f58f3970d5b8273b7e497b97af2e3f125c97af342116fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000800100000000000000011720fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa232002202036b772a6db74d8753c98a827958de6c78ab3312109f37d3e0304484242ece73d818772b2da7540000800100008000000080000000000000000000",
+    },
+    TestVector {
+        description: "An output with its internal key and key origin.",
+        hex: "70736274ff01005e020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff0148e6052a0100000022512083698e458c6664e1595d75da2597de1e22ee97d798e706c4c0a4b5a9823cd743000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a07572116fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000800100000000000000011720fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa232000105201124da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e67121071124da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e6711900772b2da7560000800100008000000080000000000500000000",
+    },
+    TestVector {
+        description: "A script path input with its internal key, merkle root, leaf scripts and key origins.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a0100000022512083698e458c6664e1595d75da2597de1e22ee97d798e706c4c0a4b5a9823cd743000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6926215c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f823202cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc04215c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac097c6e6fea5ff714ff5724499990810e406e98aa10f5bf7e5f6784bc1d0a9a6ce23204320b0bf16f011b53ea7be615924aa7f27e5d29ad20ea1155d848676c3bad1b2acc06215c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b09115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f82320fa0f7a3cef3b1d0c0a6ce7d26e17ada0b2e5c92d19efad48b41859cb8a451ca9acc021162cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d23901cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b09772b2da7560000800100008002000080000000000000000021164320b0bf16f011b53ea7be615924aa7f27e5d29ad20ea1155d848676c3bad1b23901115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f8772b2da75600008001000080010000800000000000000000211650929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac005007c461e5d2116fa0f7a3cef3b1d0c0a6ce7d26e17ada0b2e5c92d19efad48b41859cb8a451ca939016f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970772b2da7560000800100008003000080000000000000000001172050929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0011820f0362e2f75a6f420a5bde3eb221d96ae6720cf25f81890c95b1d775acb515e65000105201124da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e67121071124da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e6711900772b2da7560000800100008000000080000000000500000000",
+    },
+    TestVector {
+        description: "An output with its internal key, key origins and tap tree.",
+        hex: "70736274ff01005e020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff0148e6052a010000002251200a8cbdc86de1ce1c0f9caeb22d6df7ced3683fe423e05d1e402a879341d6f6f5000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a07572116fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000800100000000000000011720fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2320001052050929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac001066f02c02220736e572900fe1252589a2143c8f3c79f71a0412d2353af755e9701c782694a02ac02c02220631c5f3b5832b8fbdebfb19704ceeb323c21f40f7a24f43d68ef0cc26b125969ac01c0222044faa49a0338de488c8dfffecdfb6f329f380bd566ef20c8df6d813eab1c4273ac210744faa49a0338de488c8dfffecdfb6f329f380bd566ef20c8df6d813eab1c42733901f06b798b92a10ed9a9d0bbfd3af173a53b1617da3a4159ca008216cd856b2e0e772b2da75600008001000080010000800000000003000000210750929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac005007c461e5d2107631c5f3b5832b8fbdebfb19704ceeb323c21f40f7a24f43d68ef0cc26b125969390118ace409889785e0ea70ceebb8e1ca892a7a78eaede0f2e296cf435961a8f4ca772b2da756000080010000800200008000000000030000002107736e572900fe1252589a2143c8f3c79f71a0412d2353af755e9701c782694a02390129a5b4915090162d759afd3fe0f93fa3326056d0b4088cb933cae7826cb8d82c772b2da7560000800100008003000080000000000300000000",
+    },
+    TestVector {
+        description: "A script path input with a script path signature.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a0100000022512083698e458c6664e1595d75da2597de1e22ee97d798e706c4c0a4b5a9823cd743000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b69241142cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b0940bf818d9757d6ffeb538ba057fb4c1fc4e0f5ef186e765beb564791e02af5fd3d5e2551d4e34e33d86f276b82c99c79aed3f0395a081efcd2cc2c65dd7e693d7941144320b0bf16f011b53ea7be615924aa7f27e5d29ad20ea1155d848676c3bad1b2115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f840e1f1ab6fabfa26b236f21833719dc1d428ab768d80f91f9988d8abef47bfb863bb1f2a529f768c15f00ce34ec283cdc07e88f8428be28f6ef64043c32911811a4114fa0f7a3cef3b1d0c0a6ce7d26e17ada0b2e5c92d19efad48b41859cb8a451ca96f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae97040ec1f0379206461c83342285423326708ab031f0da4a253ee45aafa5b8c92034d8b605490f8cd13e00f989989b97e215faa36f12dee3693d2daccf3781c1757f66215c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f823202cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc04215c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac097c6e6fea5ff714ff5724499990810e406e98aa10f5bf7e5f6784bc1d0a9a6ce23204320b0bf16f011b53ea7be615924aa7f27e5d29ad20ea1155d848676c3bad1b2acc06215c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b09115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f82320fa0f7a3cef3b1d0c0a6ce7d26e17ada0b2e5c92d19efad48b41859cb8a451ca9acc021162cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d23901cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b09772b2da7560000800100008002000080000000000000000021164320b0bf16f011b53ea7be615924aa7f27e5d29ad20ea1155d848676c3bad1b23901115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f8772b2da75600008001000080010000800000000000000000211650929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac005007c461e5d2116fa0f7a3cef3b1d0c0a6ce7d26e17ada0b2e5c92d19efad48b41859cb8a451ca939016f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970772b2da7560000800100008003000080000000000000000001172050929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0011820f0362e2f75a6f420a5bde3eb221d96ae6720cf25f81890c95b1d775acb515e65000105201124da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e67121071124da7aec92ccd06c954562647f437b138b95721a84be2bf2276bbddab3e6711900772b2da7560000800100008000000080000000000500000000",
+    },
+];
+
+/// The invalid PSBTs of the BIP 371 test vectors.
+pub const BIP371_INVALID: &[TestVector] = &[
+    TestVector {
+        description: "An input internal key of 33 bytes.",
+        hex: "70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a075701172102fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa232000000",
+    },
+    TestVector {
+        description: "An input key path signature of 66 bytes.",
+        hex: "70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757011342173bb3d36c074afb716fec6307a069a2e450b995f3c82785945ab8df0e24260dcd703b0cbf34de399184a9481ac2b3586db6601f026a77f7e4938481bc34751701aa000000",
+    },
+    TestVector {
+        description: "An input Taproot key origin with a 33 byte key.",
+        hex: "70736274ff010071020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02787c01000000000016001483a7e34bd99ff03a4962ef8a1a101bb295461ece606b042a010000001600147ac369df1b20e033d6116623957b0ac49f3c52e8000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757221602fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da75600008001000080000000800100000000000000000000",
+    },
+    TestVector {
+        description: "An output internal key of 33 bytes.",
+        hex: "70736274ff01007d020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02887b0100000000001600142382871c7e8421a00093f754d91281e675874b9f606b042a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757000001052102fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa23200",
+    },
+    TestVector {
+        description: "An output Taproot key origin with a 33 byte key.",
+        hex: "70736274ff01007d020000000127744ababf3027fe0d6cf23a96eee2efb188ef52301954585883e69b6624b2420000000000ffffffff02887b0100000000001600142382871c7e8421a00093f754d91281e675874b9f606b042a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a0757000000000001012b00f2052a010000002251205a2c2cf5b52cf31f83ad2e8da63ff03183ecd8f609c7510ae8a48e03910a07570000220702fe349064c98d6e2a853fa3c9b12bd8b304a19c195c60efa7ee2393046d3fa2321900772b2da7560000800100008000000080010000000000000000",
+    },
+    TestVector {
+        description: "An input script path signature with a 65 byte key.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6924214022cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b094089756aa3739ccc689ec0fcf3a360be32cc0b59b16e93a1e8bb4605726b2ca7a3ff706c4176649632b2cc68e1f912b8a578e3719ce7710885c7a966f49bcd43cb0000",
+    },
+    TestVector {
+        description: "An input script path signature of 66 bytes.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b69241142cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b094289756aa3739ccc689ec0fcf3a360be32cc0b59b16e93a1e8bb4605726b2ca7a3ff706c4176649632b2cc68e1f912b8a578e3719ce7710885c7a966f49bcd43cb01010000",
+    },
+    TestVector {
+        description: "An input script path signature of 57 bytes.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b69241142cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2cd970e15f53fc0c82f950fd560ffa919b76172be017368a89913af074f400b093989756aa3739ccc689ec0fcf3a360be32cc0b59b16e93a1e8bb4605726b2ca7a3ff706c4176649632b2cc68e1f912b8a578e3719ce7710885c7a966f49bcd43cb0000",
+    },
+    TestVector {
+        description: "An input leaf script with a control block that is too long.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6926315c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e1f80023202cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc00000",
+    },
+    TestVector {
+        description: "An input leaf script with a control block that is too short.",
+        hex: "70736274ff01005e02000000019bd48765230bf9a72e662001f972556e54f0c6f97feb56bcb5600d817f6995260100000000ffffffff0148e6052a01000000225120030da4fce4f7db28c2cb2951631e003713856597fe963882cb500e68112cca63000000000001012b00f2052a01000000225120c2247efbfd92ac47f6f40b8d42d169175a19fa9fa10e4a25d7f35eb4dd85b6926115c150929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac06f7d62059e9497a1a4a267569d9876da60101aff38e3529b9b939ce7f91ae970115f2e490af7cc45c4f78511f36057ce5c5a5c56325a29fb44dfc203f356e123202cb13ac68248de806aa6a3659cf3c03eb6821d09c8114a4e868febde865bb6d2acc00000",
+    },
+];
+
+#[cfg(test)]
+impl Fixture {
+    /// Returns the key at `m/0/index`. The keys of the input are at 0 to 2, the recipient's at 3.
+    pub(crate) fn public_key(&self, index: u32) -> PublicKey {
+        key(&self.xpriv, index, &Secp256k1::new()).0
+    }
+}
+
+/// Returns a PSBT with one input of each of `script_types`, as in its [`Fixture`], and the
+/// output of the first fixture.
+#[cfg(test)]
+pub(crate) fn psbt_spending(script_types: &[ScriptType]) -> Psbt {
+    let mut fixtures = script_types.iter().enumerate().map(|(index, script_type)| {
+        // Different amounts give different previous transactions, so outpoints are unique.
+        Fixture::builder(*script_type).amount(Amount::from_sat(100_000 + index as u64)).build()
+    });
+    let mut psbt = fixtures.next().expect("at least one script type").psbt;
+    for fixture in fixtures {
+        let txin = fixture.psbt.unsigned_tx.input[0].clone();
+        psbt.push_input(txin, fixture.psbt.inputs[0].clone()).expect("the txin is unsigned");
+    }
+    psbt
+}
+
+/// Returns a signature with `SIGHASH_ALL`, for tests that count signatures but do not verify them.
+#[cfg(test)]
+pub(crate) fn dummy_sig() -> bitcoin::ecdsa::Signature {
+    bitcoin::ecdsa::Signature::from_slice(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01])
+        .expect("valid DER with a sighash type")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures() {
+        let secp = Secp256k1::new();
+        for network in [NetworkKind::Main, NetworkKind::Test] {
+            for script_type in ScriptType::ALL {
+                let fixture = Fixture::builder(script_type).network(network).build();
+                assert_eq!(fixture.xpriv.network, network);
+                assert_eq!(fixture.psbt.fee().unwrap(), Amount::from_sat(1_000));
+
+                let mut psbt = fixture.psbt.clone();
+                psbt.sign(&fixture.xpriv, &secp).unwrap();
+                let input = &psbt.inputs[0];
+                match script_type {
+                    ScriptType::P2pkh | ScriptType::P2wpkh =>
+                        assert_eq!(input.partial_sigs.len(), 1),
+                    ScriptType::P2shMultisig | ScriptType::P2wsh =>
+                        assert_eq!(input.partial_sigs.len(), 3),
+                    ScriptType::P2trKey => assert!(input.tap_key_sig.is_some()),
+                    ScriptType::P2trScript => {
+                        assert!(input.tap_key_sig.is_none());
+                        assert_eq!(input.tap_script_sigs.len(), 1);
+                    }
+                }
+                #[cfg(feature = "miniscript")]
+                {
+                    psbt.finalize_mut().unwrap();
+                    assert!(psbt.is_complete());
+                }
+            }
+        }
+        assert_eq!(Fixture::new(ScriptType::P2trScript), Fixture::new(ScriptType::P2trScript));
+        assert_ne!(
+            Fixture::new(ScriptType::P2wpkh).xpriv,
+            Fixture::builder(ScriptType::P2wpkh).network(NetworkKind::Main).build().xpriv
+        );
+    }
+
+    #[test]
+    fn test_vectors() {
+        for vector in BIP174_VALID.iter().chain(BIP371_VALID) {
+            // Leaves of a tap tree at the same depth may be serialized in a different order.
+            let psbt = vector.psbt().unwrap();
+            assert_eq!(
+                Psbt::deserialize(&psbt.serialize()).unwrap(),
+                psbt,
+                "{}",
+                vector.description
+            );
+        }
+        for vector in BIP174_INVALID.iter().chain(BIP371_INVALID) {
+            assert!(vector.psbt().is_err(), "{}", vector.description);
+        }
+    }
+}
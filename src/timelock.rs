@@ -0,0 +1,269 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Checking the lock time and sequence numbers against the timelocks of spending plans.
+//!
+//! A miniscript [`Plan`] spending an `after` or `older` branch only satisfies the script if the
+//! transaction's lock time or the input's sequence number allows it. Nothing in the PSBT records
+//! this, a transaction with the wrong values is only rejected when broadcast.
+
+use core::fmt;
+
+use bitcoin::{absolute, relative, transaction, Sequence};
+use bitcoin_internals::write_err;
+use miniscript::plan::Plan;
+
+use crate::prelude::*;
+use crate::{IndexOutOfBoundsError, Psbt};
+
+impl Psbt {
+    /// Returns the lock time needed to spend the inputs with the plans in `plans`, keyed by input
+    /// index, or `None` if no plan uses an absolute timelock.
+    ///
+    /// # Errors
+    ///
+    /// If a plan is for an input that does not exist, or the plans use both time and height based
+    /// lock times.
+    pub fn required_locktime(
+        &self,
+        plans: &BTreeMap<usize, Plan>,
+    ) -> Result<Option<absolute::LockTime>, TimelockError> {
+        self.check_plan_indices(plans)?;
+        let mut required: Option<absolute::LockTime> = None;
+        for plan in plans.values() {
+            let lock_time = match plan.absolute_timelock {
+                Some(lock_time) => lock_time,
+                None => continue,
+            };
+            required = match required {
+                None => Some(lock_time),
+                Some(max) if !max.is_same_unit(lock_time) =>
+                    return Err(TimelockError::MixedLockTimeUnits),
+                Some(max) if lock_time.to_consensus_u32() > max.to_consensus_u32() =>
+                    Some(lock_time),
+                Some(max) => Some(max),
+            };
+        }
+        Ok(required)
+    }
+
+    /// Returns the sequence numbers needed to spend the inputs with the plans in `plans`, keyed
+    /// by input index.
+    ///
+    /// Only inputs whose plan uses a relative timelock need a particular sequence number.
+    pub fn required_sequences(
+        &self,
+        plans: &BTreeMap<usize, Plan>,
+    ) -> Result<BTreeMap<usize, Sequence>, IndexOutOfBoundsError> {
+        self.check_plan_indices(plans)?;
+        Ok(plans
+            .iter()
+            .filter_map(|(&index, plan)| plan.relative_timelock.map(|lock| (index, lock)))
+            .map(|(index, lock)| (index, lock.to_sequence()))
+            .collect())
+    }
+
+    /// Checks that the unsigned transaction satisfies the timelocks of the plans in `plans`,
+    /// keyed by input index.
+    ///
+    /// The lock time must be at least the [required lock time](Psbt::required_locktime), with the
+    /// same unit, and enabled by the sequence number of every input with an absolute timelock.
+    /// Inputs with a relative timelock need a sequence number at least as long, with the same
+    /// unit, in a version 2 transaction.
+    pub fn check_timelocks(&self, plans: &BTreeMap<usize, Plan>) -> Result<(), TimelockError> {
+        let lock_time = self.unsigned_tx.lock_time;
+        if let Some(required) = self.required_locktime(plans)? {
+            if !required.is_implied_by(lock_time) {
+                return Err(TimelockError::LockTimeNotSatisfied { required, lock_time });
+            }
+        }
+
+        for (&input_index, plan) in plans {
+            let sequence = self.unsigned_tx.input[input_index].sequence;
+            if plan.absolute_timelock.is_some() && !sequence.enables_absolute_lock_time() {
+                return Err(TimelockError::LockTimeDisabled { input_index });
+            }
+            let required = match plan.relative_timelock {
+                Some(required) => required,
+                None => continue,
+            };
+            if self.unsigned_tx.version < transaction::Version::TWO {
+                return Err(TimelockError::TxVersion);
+            }
+            if !required.is_implied_by_sequence(sequence) {
+                return Err(TimelockError::SequenceNotSatisfied {
+                    input_index,
+                    required,
+                    sequence,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every plan in `plans` is for an existing input.
+    fn check_plan_indices(
+        &self,
+        plans: &BTreeMap<usize, Plan>,
+    ) -> Result<(), IndexOutOfBoundsError> {
+        let length = self.inputs.len().min(self.unsigned_tx.input.len());
+        match plans.range(length..).next() {
+            Some((&index, _)) => Err(IndexOutOfBoundsError::Inputs { index, length }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error checking the timelocks of spending plans.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimelockError {
+    /// A plan is for an input that does not exist.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The plans use both time and height based absolute timelocks.
+    MixedLockTimeUnits,
+    /// The lock time of the transaction does not satisfy the plans.
+    LockTimeNotSatisfied {
+        /// The lock time required by the plans.
+        required: absolute::LockTime,
+        /// The lock time of the transaction.
+        lock_time: absolute::LockTime,
+    },
+    /// The sequence number of an input with an absolute timelock disables the lock time.
+    LockTimeDisabled {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The sequence number of the input does not satisfy its relative timelock.
+    SequenceNotSatisfied {
+        /// The index of the input.
+        input_index: usize,
+        /// The relative timelock required by the plan.
+        required: relative::LockTime,
+        /// The sequence number of the input.
+        sequence: Sequence,
+    },
+    /// Relative timelocks are only enforced in version 2 transactions.
+    TxVersion,
+}
+
+bitcoin_internals::impl_from_infallible!(TimelockError);
+
+impl fmt::Display for TimelockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TimelockError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "plan for a missing input"; e),
+            MixedLockTimeUnits =>
+                f.write_str("the plans use both time and height based absolute timelocks"),
+            LockTimeNotSatisfied { required, lock_time } => write!(
+                f,
+                "lock time {} does not satisfy the required lock time {}",
+                lock_time, required
+            ),
+            LockTimeDisabled { input_index } =>
+                write!(f, "the sequence number of input {} disables the lock time", input_index),
+            SequenceNotSatisfied { input_index, required, sequence } => write!(
+                f,
+                "sequence {:#010x} of input {} does not satisfy the relative timelock {}",
+                sequence.to_consensus_u32(),
+                input_index,
+                required
+            ),
+            TxVersion => f.write_str("relative timelocks need a version 2 transaction"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TimelockError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use TimelockError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            MixedLockTimeUnits
+            | LockTimeNotSatisfied { .. }
+            | LockTimeDisabled { .. }
+            | SequenceNotSatisfied { .. }
+            | TxVersion => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for TimelockError {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use bitcoin::{Transaction, TxIn};
+    use miniscript::descriptor::{DefiniteDescriptorKey, DescriptorPublicKey};
+    use miniscript::plan::Assets;
+    use miniscript::Descriptor;
+
+    use super::*;
+
+    const KEY: &str = "02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c";
+
+    fn plan(policy: &str, assets: Assets) -> Plan {
+        let desc = format!("wsh(and_v(v:pk({}),{}))", KEY, policy);
+        let desc = Descriptor::<DefiniteDescriptorKey>::from_str(&desc).unwrap();
+        desc.plan(&assets.add(DescriptorPublicKey::from_str(KEY).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn timelocks() {
+        let after =
+            plan("after(800000)", Assets::new().after(absolute::LockTime::from_consensus(800_000)));
+        let older = plan("older(144)", Assets::new().older(relative::LockTime::from_height(144)));
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::ONE,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+
+        let plans = [(0, after.clone()), (1, older)].into_iter().collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            psbt.required_locktime(&plans),
+            Ok(Some(absolute::LockTime::from_consensus(800_000)))
+        );
+        let sequences = psbt.required_sequences(&plans).unwrap();
+        assert_eq!(sequences.into_iter().collect::<Vec<_>>(), [(1, Sequence::from_height(144))]);
+        assert_eq!(
+            psbt.check_timelocks(&plans),
+            Err(TimelockError::LockTimeNotSatisfied {
+                required: absolute::LockTime::from_consensus(800_000),
+                lock_time: absolute::LockTime::ZERO
+            })
+        );
+
+        psbt.unsigned_tx.lock_time = absolute::LockTime::from_consensus(800_001);
+        assert_eq!(
+            psbt.check_timelocks(&plans),
+            Err(TimelockError::LockTimeDisabled { input_index: 0 })
+        );
+        psbt.unsigned_tx.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        assert_eq!(psbt.check_timelocks(&plans), Err(TimelockError::TxVersion));
+        psbt.unsigned_tx.version = transaction::Version::TWO;
+        assert!(matches!(
+            psbt.check_timelocks(&plans),
+            Err(TimelockError::SequenceNotSatisfied { input_index: 1, .. })
+        ));
+        psbt.unsigned_tx.input[1].sequence = Sequence::from_height(150);
+        assert_eq!(psbt.check_timelocks(&plans), Ok(()));
+
+        let time = plan(
+            "after(1700000000)",
+            Assets::new().after(absolute::LockTime::from_consensus(1_700_000_000)),
+        );
+        let mixed = [(0, after), (1, time)].into_iter().collect::<BTreeMap<_, _>>();
+        assert_eq!(psbt.required_locktime(&mixed), Err(TimelockError::MixedLockTimeUnits));
+        let missing = [(2, plans[&1].clone())].into_iter().collect::<BTreeMap<_, _>>();
+        assert!(psbt.required_sequences(&missing).is_err());
+    }
+}
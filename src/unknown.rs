@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Access to unknown fields by key type and key data.
+//!
+//! Fields whose key type this crate does not know are kept in the `unknown` map of the PSBT,
+//! input or output they belong to and serialized unchanged. Together with [`raw::Key::new`] these
+//! helpers let new BIPs be prototyped without changing the serializer.
+
+use core::fmt;
+
+use crate::map::field_name;
+use crate::prelude::*;
+use crate::{raw, Input, MapLocation, Output, Psbt};
+
+fn get<'a>(
+    map: &'a BTreeMap<raw::Key, Vec<u8>>,
+    type_value: u8,
+    key_data: &[u8],
+) -> Option<&'a [u8]> {
+    // Avoids allocating a key for the lookup, keys of one type are ordered by their key data.
+    iter(map, type_value).find(|(data, _)| *data == key_data).map(|(_, value)| value)
+}
+
+fn iter(map: &BTreeMap<raw::Key, Vec<u8>>, type_value: u8) -> impl Iterator<Item = (&[u8], &[u8])> {
+    map.range(raw::Key::new(type_value)..)
+        .take_while(move |(key, _)| key.type_value == type_value)
+        .map(|(key, value)| (key.key_data.as_slice(), value.as_slice()))
+}
+
+fn insert(
+    map: &mut BTreeMap<raw::Key, Vec<u8>>,
+    location: MapLocation,
+    key: raw::Key,
+    value: Vec<u8>,
+) -> Result<Option<Vec<u8>>, KnownKeyError> {
+    match field_name(location, key.type_value) {
+        "unknown" => Ok(map.insert(key, value)),
+        field => Err(KnownKeyError { type_value: key.type_value, field }),
+    }
+}
+
+macro_rules! impl_unknown_accessors {
+    ($map:ty, $location:expr) => {
+        impl $map {
+            /// Returns the value of the unknown field with key type `type_value` and key data
+            /// `key_data`, if present.
+            pub fn get_unknown(&self, type_value: u8, key_data: &[u8]) -> Option<&[u8]> {
+                get(&self.unknown, type_value, key_data)
+            }
+
+            /// Returns an iterator over the key data and values of the unknown fields with key
+            /// type `type_value`, ordered by key data as they are serialized.
+            pub fn unknown_fields(&self, type_value: u8) -> impl Iterator<Item = (&[u8], &[u8])> {
+                iter(&self.unknown, type_value)
+            }
+
+            /// Inserts an unknown field, returning the value previously stored under `key`.
+            ///
+            /// # Errors
+            ///
+            /// If the key type is one this crate parses, the field would not be read back as
+            /// unknown.
+            pub fn insert_unknown(
+                &mut self,
+                key: raw::Key,
+                value: Vec<u8>,
+            ) -> Result<Option<Vec<u8>>, KnownKeyError> {
+                insert(&mut self.unknown, $location, key, value)
+            }
+
+            /// Removes the unknown field with key type `type_value` and key data `key_data`,
+            /// returning its value.
+            pub fn remove_unknown(&mut self, type_value: u8, key_data: &[u8]) -> Option<Vec<u8>> {
+                self.unknown.remove(&raw::Key::new(type_value).push(key_data))
+            }
+        }
+    };
+}
+impl_unknown_accessors!(Psbt, MapLocation::Global);
+impl_unknown_accessors!(Input, MapLocation::Input(0));
+impl_unknown_accessors!(Output, MapLocation::Output(0));
+
+/// Error inserting an unknown field whose key type is known, see [`Psbt::insert_unknown`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownKeyError {
+    type_value: u8,
+    field: &'static str,
+}
+
+impl KnownKeyError {
+    /// Returns the key type of the rejected key.
+    pub fn type_value(&self) -> u8 { self.type_value }
+
+    /// Returns the name of the field the key type is parsed as.
+    pub fn field(&self) -> &'static str { self.field }
+}
+
+impl fmt::Display for KnownKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key type {:#04x} is the known field {}", self.type_value, self.field)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KnownKeyError {}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{absolute, transaction, Transaction};
+
+    use super::*;
+
+    #[test]
+    fn unknown_fields() {
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        })
+        .unwrap();
+
+        let key = raw::Key::new(0x10).push_compact_size(2).push([0xbb, 0xaa]);
+        assert_eq!(psbt.insert_unknown(key.clone(), vec![2]), Ok(None));
+        assert_eq!(psbt.insert_unknown(raw::Key::new(0x10).push([0x01]), vec![1]), Ok(None));
+        assert_eq!(psbt.insert_unknown(raw::Key::new(0x11), vec![3]), Ok(None));
+        assert_eq!(psbt.insert_unknown(key, vec![4]), Ok(Some(vec![2])));
+
+        let err = psbt.insert_unknown(raw::Key::new(0x01), vec![]).unwrap_err();
+        assert_eq!(err.field(), "xpub");
+        assert!(Input::default().insert_unknown(raw::Key::new(0x06), vec![]).is_err());
+        assert!(Output::default().insert_unknown(raw::Key::new(0x03), vec![]).is_ok());
+
+        let psbt = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(psbt.get_unknown(0x10, &[0x02, 0xbb, 0xaa]), Some(&[4][..]));
+        assert_eq!(psbt.get_unknown(0x10, &[0x02]), None);
+        let fields = psbt.unknown_fields(0x10).collect::<Vec<_>>();
+        assert_eq!(fields, vec![(&[0x01][..], &[1][..]), (&[0x02, 0xbb, 0xaa][..], &[4][..])]);
+
+        let mut psbt = psbt;
+        assert_eq!(psbt.remove_unknown(0x11, &[]), Some(vec![3]));
+        assert_eq!(psbt.unknown_fields(0x11).count(), 0);
+    }
+}
@@ -0,0 +1,1075 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The BIP 174 Updater role.
+//!
+//! Inputs and outputs are updated from a descriptor by deriving it at a particular index and
+//! filling in the scripts and key origins needed to later sign for, or verify, the output.
+
+use core::fmt;
+use core::ops::Range;
+
+use bitcoin::bip32::KeySource;
+use bitcoin::secp256k1::{self, Secp256k1};
+use bitcoin::taproot::{
+    ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TapTree, TaprootBuilder,
+};
+use bitcoin::{
+    OutPoint, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, WitnessVersion, XOnlyPublicKey,
+};
+use bitcoin_internals::write_err;
+use miniscript::descriptor::{
+    ConversionError, DefiniteDescriptorKey, DescriptorPublicKey, ShInner,
+};
+use miniscript::miniscript::satisfy::{Placeholder, SchnorrSigType};
+use miniscript::plan::{AssetProvider, Plan};
+use miniscript::{Descriptor, ForEachKey};
+
+use crate::prelude::*;
+use crate::{IndexOutOfBoundsError, Input, Output, Psbt, UtxoPolicy};
+
+impl Psbt {
+    /// Updates the input at `input_index` from `descriptor` derived at `derivation_index`.
+    ///
+    /// Sets the redeem and witness scripts and the BIP 32 derivations, or for Taproot
+    /// descriptors the internal key, merkle root, leaf scripts, and Taproot key origins. Fields
+    /// the descriptor does not describe are left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// If the input has no UTXO, or the derived descriptor does not match the script pubkey of
+    /// the UTXO. The input is not modified on error.
+    pub fn update_input_with_descriptor(
+        &mut self,
+        input_index: usize,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+    ) -> Result<(), UpdateError> {
+        self.check_index_is_within_bounds(input_index)?;
+        let derived = Derived::new(descriptor, derivation_index, true)?;
+        self.check_input_spk(input_index, &derived)?;
+        derived.update_input(&mut self.inputs[input_index]);
+        Ok(())
+    }
+
+    /// Updates the output at `output_index` from `descriptor` derived at `derivation_index`.
+    ///
+    /// Sets the redeem and witness scripts and the BIP 32 derivations, or for Taproot
+    /// descriptors the internal key, Taproot tree, and Taproot key origins. Fields the
+    /// descriptor does not describe are left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// If the derived descriptor does not match the script pubkey of the unsigned transaction
+    /// output. The output is not modified on error.
+    pub fn update_output_with_descriptor(
+        &mut self,
+        output_index: usize,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+    ) -> Result<(), UpdateError> {
+        self.check_output_index_is_within_bounds(output_index)?;
+        let derived = Derived::new(descriptor, derivation_index, true)?;
+        self.check_output_spk(output_index, &derived)?;
+
+        derived.update_output(&mut self.outputs[output_index]);
+        Ok(())
+    }
+
+    /// Appends an input spending `utxo` at `previous_output`, updated from `descriptor` derived
+    /// at `derivation_index`.
+    ///
+    /// The UTXO fields are chosen from the descriptor type: SegWit inputs get a witness UTXO and
+    /// legacy inputs the non-witness UTXO `previous_tx`, which they require. With
+    /// [`UtxoPolicy::IncludeNonWitness`] SegWit v0 inputs get the non-witness UTXO too, as
+    /// hardware signers guarding against the fee attack on SegWit v0 sighashes require. Taproot
+    /// sighashes commit to the amounts of all inputs, so Taproot inputs never need it. The input
+    /// is then updated as by [`Psbt::update_input_with_descriptor`]. The sequence number is
+    /// [`Sequence::ENABLE_RBF_NO_LOCKTIME`], see [`Psbt::set_input_sequence`] to change it.
+    ///
+    /// Returns the index of the new input.
+    ///
+    /// # Errors
+    ///
+    /// If the non-witness UTXO is required but not given, does not match `previous_output` and
+    /// `utxo`, or the derived descriptor does not match the script pubkey of `utxo`. The PSBT is
+    /// not modified on error.
+    pub fn add_input_from_utxo(
+        &mut self,
+        previous_output: OutPoint,
+        utxo: TxOut,
+        previous_tx: Option<Transaction>,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+        policy: UtxoPolicy,
+    ) -> Result<usize, UpdateError> {
+        if let Some(ref tx) = previous_tx {
+            let vout = previous_output.vout as usize;
+            if tx.compute_txid() != previous_output.txid || tx.output.get(vout) != Some(&utxo) {
+                return Err(UpdateError::PreviousTxMismatch);
+            }
+        }
+
+        let mut input = Input::default();
+        match descriptor.desc_type().segwit_version() {
+            Some(WitnessVersion::V0) => {
+                if policy == UtxoPolicy::IncludeNonWitness {
+                    input.non_witness_utxo =
+                        Some(previous_tx.ok_or(UpdateError::MissingPreviousTx)?);
+                }
+                input.witness_utxo = Some(utxo);
+            }
+            Some(_) => input.witness_utxo = Some(utxo),
+            None =>
+                input.non_witness_utxo = Some(previous_tx.ok_or(UpdateError::MissingPreviousTx)?),
+        }
+
+        let input_index = self.inputs.len();
+        let txin = TxIn {
+            previous_output,
+            sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+            ..Default::default()
+        };
+        self.push_input(txin, input).expect("scriptSig and witness are empty");
+        if let Err(e) = self.update_input_with_descriptor(input_index, descriptor, derivation_index)
+        {
+            self.unsigned_tx.input.pop();
+            self.inputs.pop();
+            return Err(e);
+        }
+        Ok(input_index)
+    }
+}
+
+impl Psbt {
+    /// Updates the input at `input_index` like [`Psbt::update_input_with_descriptor`], for a
+    /// Taproot key path spend.
+    ///
+    /// Only the internal key, its origin and the merkle root are set. The leaf scripts, their
+    /// control blocks and the key origins of their keys, which a key path spend does not need,
+    /// are not computed. Other descriptors are updated as by
+    /// [`Psbt::update_input_with_descriptor`].
+    pub fn update_input_for_key_spend(
+        &mut self,
+        input_index: usize,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+    ) -> Result<(), UpdateError> {
+        self.check_index_is_within_bounds(input_index)?;
+        let derived = Derived::new(descriptor, derivation_index, false)?;
+        self.check_input_spk(input_index, &derived)?;
+        derived.update_input(&mut self.inputs[input_index]);
+        Ok(())
+    }
+
+    /// Updates the input at `input_index` like [`Psbt::update_input_with_descriptor`], reusing
+    /// the derivations in `cache`.
+    ///
+    /// Deriving a descriptor, and for Taproot building its tree, is the expensive part of an
+    /// update. Wallets updating many inputs and outputs from the same descriptors, possibly at
+    /// the same index, should share a cache between the updates.
+    pub fn update_input_with_descriptor_cached(
+        &mut self,
+        input_index: usize,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+        cache: &mut DescriptorCache,
+    ) -> Result<(), UpdateError> {
+        self.check_index_is_within_bounds(input_index)?;
+        let derived = cache.derive(descriptor, derivation_index)?;
+        self.check_input_spk(input_index, derived)?;
+        derived.update_input(&mut self.inputs[input_index]);
+        Ok(())
+    }
+
+    /// Updates the output at `output_index` like [`Psbt::update_output_with_descriptor`],
+    /// reusing the derivations in `cache`.
+    pub fn update_output_with_descriptor_cached(
+        &mut self,
+        output_index: usize,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+        cache: &mut DescriptorCache,
+    ) -> Result<(), UpdateError> {
+        self.check_output_index_is_within_bounds(output_index)?;
+        let derived = cache.derive(descriptor, derivation_index)?;
+        self.check_output_spk(output_index, derived)?;
+        derived.update_output(&mut self.outputs[output_index]);
+        Ok(())
+    }
+
+    /// Checks that the input at `input_index`, which exists, spends the output of `derived`.
+    fn check_input_spk(&self, input_index: usize, derived: &Derived) -> Result<(), UpdateError> {
+        match self.spend_utxo(input_index) {
+            Ok(utxo) if utxo.script_pubkey == derived.script_pubkey => Ok(()),
+            Ok(_) => Err(UpdateError::ScriptPubkeyMismatch),
+            Err(_) => Err(UpdateError::MissingUtxo),
+        }
+    }
+
+    /// Checks that the output at `output_index`, which exists, is the output of `derived`.
+    fn check_output_spk(&self, output_index: usize, derived: &Derived) -> Result<(), UpdateError> {
+        if self.unsigned_tx.output[output_index].script_pubkey != derived.script_pubkey {
+            return Err(UpdateError::ScriptPubkeyMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Descriptors derived at an index, shared between updates from the same descriptors.
+///
+/// See [`Psbt::update_input_with_descriptor_cached`].
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorCache {
+    derived: BTreeMap<Descriptor<DescriptorPublicKey>, BTreeMap<u32, Derived>>,
+}
+
+impl DescriptorCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self { DescriptorCache::default() }
+
+    /// Returns the number of derived descriptors in the cache.
+    pub fn len(&self) -> usize { self.derived.values().map(BTreeMap::len).sum() }
+
+    /// Returns true if the cache is empty.
+    pub fn is_empty(&self) -> bool { self.derived.is_empty() }
+
+    /// Removes every derived descriptor from the cache.
+    pub fn clear(&mut self) { self.derived.clear() }
+
+    /// Returns `descriptor` derived at `derivation_index`, deriving it if it is not cached.
+    fn derive(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+    ) -> Result<&Derived, ConversionError> {
+        if !self
+            .derived
+            .get(descriptor)
+            .map_or(false, |derived| derived.contains_key(&derivation_index))
+        {
+            let derived = Derived::new(descriptor, derivation_index, true)?;
+            self.derived.entry(descriptor.clone()).or_default().insert(derivation_index, derived);
+        }
+        Ok(&self.derived[descriptor][&derivation_index])
+    }
+}
+
+impl Psbt {
+    /// Updates every input spending an output of `descriptor` and returns the plans to spend them
+    /// with `assets`, keyed by input index.
+    ///
+    /// `index_resolver` is called with the script pubkey of the UTXO of each input and returns
+    /// the index `descriptor` might derive it at, usually looked up in the wallet's script pubkey
+    /// index. Inputs without a UTXO, without an index or whose script pubkey the descriptor does
+    /// not derive at that index are skipped. Inputs with a plan that spends the Taproot key path
+    /// are updated as by [`Psbt::update_input_for_key_spend`], the others as by
+    /// [`Psbt::update_input_with_descriptor`].
+    ///
+    /// The plans are what [`Psbt::check_timelocks`] needs.
+    ///
+    /// # Errors
+    ///
+    /// If the descriptor can not be derived at a resolved index, or `assets` can not satisfy a
+    /// derived descriptor. No input is modified on error.
+    pub fn plan_and_update<P, F>(
+        &mut self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        assets: &P,
+        mut index_resolver: F,
+    ) -> Result<BTreeMap<usize, Plan>, UpdateError>
+    where
+        P: AssetProvider<DefiniteDescriptorKey>,
+        F: FnMut(&Script) -> Option<u32>,
+    {
+        let mut updates = vec![];
+        for input_index in 0..self.inputs.len().min(self.unsigned_tx.input.len()) {
+            let spk = match self.spend_utxo(input_index) {
+                Ok(utxo) => &utxo.script_pubkey,
+                Err(_) => continue,
+            };
+            let derivation_index = match index_resolver(spk) {
+                Some(derivation_index) => derivation_index,
+                None => continue,
+            };
+            let definite = descriptor.at_derivation_index(derivation_index)?;
+            let derived = Derived::from_definite(&definite, false)?;
+            if derived.script_pubkey != *spk {
+                continue;
+            }
+
+            let plan = definite
+                .clone()
+                .plan(assets)
+                .map_err(|_| UpdateError::Unsatisfiable { input_index })?;
+            let key_spend = plan.witness_template().iter().any(|placeholder| {
+                matches!(
+                    placeholder,
+                    Placeholder::SchnorrSigPk(_, SchnorrSigType::KeySpend { .. }, _)
+                )
+            });
+            let derived =
+                if key_spend { derived } else { Derived::from_definite(&definite, true)? };
+            updates.push((input_index, derived, plan));
+        }
+
+        let mut plans = BTreeMap::new();
+        for (input_index, derived, plan) in updates {
+            derived.update_input(&mut self.inputs[input_index]);
+            plans.insert(input_index, plan);
+        }
+        Ok(plans)
+    }
+}
+
+impl Psbt {
+    /// Returns, for each output, the index at which `descriptor` derives it, if it is change.
+    ///
+    /// An output is change if `descriptor` derived at an index in `range` produces its script
+    /// pubkey and every field of the output agrees with the derived descriptor, see
+    /// [`Output::is_mine`]. `range` is the gap limit the wallet is willing to search.
+    pub fn verify_change(
+        &self,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        range: Range<u32>,
+    ) -> Result<Vec<Option<u32>>, UpdateError> {
+        let derived = range
+            .map(|index| Ok((index, Derived::new(descriptor, index, true)?)))
+            .collect::<Result<Vec<_>, ConversionError>>()?;
+
+        Ok(self
+            .outputs
+            .iter()
+            .zip(&self.unsigned_tx.output)
+            .map(|(output, txout)| {
+                derived
+                    .iter()
+                    .find(|(_, derived)| derived.script_pubkey == txout.script_pubkey)
+                    .filter(|(_, derived)| derived.check_output(output).is_ok())
+                    .map(|(index, _)| *index)
+            })
+            .collect())
+    }
+}
+
+impl Output {
+    /// Checks that this output, with script pubkey `script_pubkey`, derives from `descriptor`.
+    ///
+    /// `descriptor` is derived at each index in `range` until it produces `script_pubkey`. The
+    /// redeem and witness scripts, BIP 32 derivations, Taproot internal key, Taproot tree, and
+    /// Taproot key origins present in the output must then agree with the derived descriptor.
+    /// This is the check signers make before hiding an output from the user as change.
+    ///
+    /// Returns the derivation index of the output.
+    pub fn is_mine(
+        &self,
+        script_pubkey: &Script,
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        range: Range<u32>,
+    ) -> Result<u32, ChangeError> {
+        for index in range {
+            let derived = Derived::new(descriptor, index, true)?;
+            if derived.script_pubkey == *script_pubkey {
+                derived.check_output(self)?;
+                return Ok(index);
+            }
+        }
+        Err(ChangeError::NotDerived)
+    }
+}
+
+/// The PSBT fields described by a descriptor derived at a particular index.
+#[derive(Debug, Clone)]
+struct Derived {
+    script_pubkey: ScriptBuf,
+    redeem_script: Option<ScriptBuf>,
+    witness_script: Option<ScriptBuf>,
+    bip32_derivation: BTreeMap<secp256k1::PublicKey, KeySource>,
+    tap_internal_key: Option<XOnlyPublicKey>,
+    tap_merkle_root: Option<TapNodeHash>,
+    tap_scripts: BTreeMap<ControlBlock, (ScriptBuf, LeafVersion)>,
+    tap_key_origins: BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>,
+    tap_tree: Option<TapTree>,
+}
+
+impl Derived {
+    /// Derives `descriptor` at `derivation_index`, with the Taproot leaves only if `leaves`.
+    fn new(
+        descriptor: &Descriptor<DescriptorPublicKey>,
+        derivation_index: u32,
+        leaves: bool,
+    ) -> Result<Self, ConversionError> {
+        Derived::from_definite(&descriptor.at_derivation_index(derivation_index)?, leaves)
+    }
+
+    /// Derives the keys of `definite`, with the Taproot leaves only if `leaves`.
+    fn from_definite(
+        definite: &Descriptor<DefiniteDescriptorKey>,
+        leaves: bool,
+    ) -> Result<Self, ConversionError> {
+        let secp = Secp256k1::verification_only();
+        let derived = definite.derived_descriptor(&secp)?;
+
+        let mut fields = Derived {
+            script_pubkey: derived.script_pubkey(),
+            redeem_script: None,
+            witness_script: None,
+            bip32_derivation: BTreeMap::new(),
+            tap_internal_key: None,
+            tap_merkle_root: None,
+            tap_scripts: BTreeMap::new(),
+            tap_key_origins: BTreeMap::new(),
+            tap_tree: None,
+        };
+
+        let (tr_derived, tr_definite) = match (&derived, definite) {
+            (Descriptor::Tr(tr_derived), Descriptor::Tr(tr_definite)) => (tr_derived, tr_definite),
+            _ => {
+                let mut result = Ok(());
+                definite.for_each_key(|key| {
+                    result = key_source(key).and_then(|source| {
+                        let pk = key.derive_public_key(&secp)?;
+                        fields.bip32_derivation.insert(pk.inner, source);
+                        Ok(())
+                    });
+                    result.is_ok()
+                });
+                result?;
+
+                match derived {
+                    Descriptor::Sh(ref sh) => match sh.as_inner() {
+                        ShInner::Wsh(wsh) => {
+                            fields.witness_script = Some(wsh.inner_script());
+                            fields.redeem_script = Some(wsh.inner_script().to_p2wsh());
+                        }
+                        ShInner::Wpkh(_) | ShInner::SortedMulti(_) | ShInner::Ms(_) =>
+                            fields.redeem_script = Some(sh.inner_script()),
+                    },
+                    Descriptor::Wsh(ref wsh) => fields.witness_script = Some(wsh.inner_script()),
+                    _ => {}
+                }
+                return Ok(fields);
+            }
+        };
+
+        let internal_key = tr_derived.internal_key().inner.x_only_public_key().0;
+        fields.tap_internal_key = Some(internal_key);
+        fields
+            .tap_key_origins
+            .insert(internal_key, (vec![], key_source(tr_definite.internal_key())?));
+        if tr_derived.tap_tree().is_none() {
+            // No merkle root, and nothing else to derive.
+            return Ok(fields);
+        }
+        if !leaves {
+            let builder =
+                tr_derived.iter_scripts().fold(TaprootBuilder::new(), |builder, (depth, ms)| {
+                    builder.add_leaf(depth, ms.encode()).expect("leaves are in DFS order")
+                });
+            let tap_tree = TapTree::try_from(builder).expect("the tree is complete");
+            fields.tap_merkle_root = Some(tap_tree.root_hash());
+            fields.tap_tree = Some(tap_tree);
+            return Ok(fields);
+        }
+
+        let spend_info = tr_derived.spend_info();
+        fields.tap_merkle_root = spend_info.merkle_root();
+
+        let mut builder = TaprootBuilder::new();
+        for ((depth, ms_derived), (_, ms_definite)) in
+            tr_derived.iter_scripts().zip(tr_definite.iter_scripts())
+        {
+            let script = ms_derived.encode();
+            let leaf_hash = TapLeafHash::from_script(&script, LeafVersion::TapScript);
+            let control_block = spend_info
+                .control_block(&(script.clone(), LeafVersion::TapScript))
+                .expect("every leaf of the tree has a control block");
+            builder = builder.add_leaf(depth, script.clone()).expect("leaves are in DFS order");
+            fields.tap_scripts.insert(control_block, (script, LeafVersion::TapScript));
+
+            for (pk, key) in ms_derived.iter_pk().zip(ms_definite.iter_pk()) {
+                let source = key_source(&key)?;
+                crate::map::add_tap_key_origin(
+                    &mut fields.tap_key_origins,
+                    pk.inner.x_only_public_key().0,
+                    Some(leaf_hash),
+                    source,
+                );
+            }
+        }
+        fields.tap_tree = Some(TapTree::try_from(builder).expect("the tree is complete"));
+        Ok(fields)
+    }
+
+    /// Sets the fields of `input` the derived descriptor describes.
+    fn update_input(&self, input: &mut Input) {
+        if self.redeem_script.is_some() {
+            input.redeem_script = self.redeem_script.clone();
+        }
+        if self.witness_script.is_some() {
+            input.witness_script = self.witness_script.clone();
+        }
+        input
+            .bip32_derivation
+            .extend(self.bip32_derivation.iter().map(|(pk, source)| (*pk, source.clone())));
+        if self.tap_internal_key.is_some() {
+            input.tap_internal_key = self.tap_internal_key;
+            input.tap_merkle_root = self.tap_merkle_root;
+        }
+        input
+            .tap_scripts
+            .extend(self.tap_scripts.iter().map(|(cb, leaf)| (cb.clone(), leaf.clone())));
+        for (key, (leaf_hashes, source)) in &self.tap_key_origins {
+            input.add_tap_key_origin(*key, None, source.clone());
+            for leaf_hash in leaf_hashes {
+                input.add_tap_key_origin(*key, Some(*leaf_hash), source.clone());
+            }
+        }
+    }
+
+    /// Sets the fields of `output` the derived descriptor describes.
+    fn update_output(&self, output: &mut Output) {
+        if self.redeem_script.is_some() {
+            output.redeem_script = self.redeem_script.clone();
+        }
+        if self.witness_script.is_some() {
+            output.witness_script = self.witness_script.clone();
+        }
+        output
+            .bip32_derivation
+            .extend(self.bip32_derivation.iter().map(|(pk, source)| (*pk, source.clone())));
+        if self.tap_internal_key.is_some() {
+            output.tap_internal_key = self.tap_internal_key;
+            output.tap_tree = self.tap_tree.clone();
+        }
+        for (key, (leaf_hashes, source)) in &self.tap_key_origins {
+            output.add_tap_key_origin(*key, None, source.clone());
+            for leaf_hash in leaf_hashes {
+                output.add_tap_key_origin(*key, Some(*leaf_hash), source.clone());
+            }
+        }
+    }
+}
+
+impl Derived {
+    /// Checks that the fields present in `output` agree with the derived descriptor.
+    fn check_output(&self, output: &Output) -> Result<(), ChangeError> {
+        use ChangeError::FieldMismatch;
+
+        if output.redeem_script.is_some() && output.redeem_script != self.redeem_script {
+            return Err(FieldMismatch { field: "redeem_script" });
+        }
+        if output.witness_script.is_some() && output.witness_script != self.witness_script {
+            return Err(FieldMismatch { field: "witness_script" });
+        }
+        if output
+            .bip32_derivation
+            .iter()
+            .any(|(pk, source)| self.bip32_derivation.get(pk) != Some(source))
+        {
+            return Err(FieldMismatch { field: "bip32_derivation" });
+        }
+        if output.tap_internal_key.is_some() && output.tap_internal_key != self.tap_internal_key {
+            return Err(FieldMismatch { field: "tap_internal_key" });
+        }
+        if output.tap_tree.is_some() && output.tap_tree != self.tap_tree {
+            return Err(FieldMismatch { field: "tap_tree" });
+        }
+        let origins_agree = output.tap_key_origins.iter().all(|(pk, (leaf_hashes, source))| {
+            match self.tap_key_origins.get(pk) {
+                Some((derived_hashes, derived_source)) =>
+                    source == derived_source
+                        && leaf_hashes.iter().all(|hash| derived_hashes.contains(hash)),
+                None => false,
+            }
+        });
+        if !origins_agree {
+            return Err(FieldMismatch { field: "tap_key_origins" });
+        }
+        Ok(())
+    }
+}
+
+/// Returns the origin of `key` as recorded in the descriptor.
+fn key_source(key: &DefiniteDescriptorKey) -> Result<KeySource, ConversionError> {
+    let path = key.full_derivation_path().ok_or(ConversionError::MultiKey)?;
+    Ok((key.master_fingerprint(), path))
+}
+
+/// Error updating a PSBT input or output from a descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpdateError {
+    /// The input or output index is out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// The input has neither a witness nor a non-witness UTXO.
+    MissingUtxo,
+    /// The derived descriptor does not match the script pubkey of the input or output.
+    ScriptPubkeyMismatch,
+    /// The non-witness UTXO is required but was not given.
+    MissingPreviousTx,
+    /// The non-witness UTXO does not contain the spent output.
+    PreviousTxMismatch,
+    /// The descriptor could not be derived at the given index.
+    Derivation(ConversionError),
+    /// The assets can not satisfy the descriptor the input spends.
+    Unsatisfiable {
+        /// The index of the input.
+        input_index: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(UpdateError);
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use UpdateError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            MissingUtxo => f.write_str("input has neither a witness nor a non-witness UTXO"),
+            ScriptPubkeyMismatch =>
+                f.write_str("derived descriptor does not match the script pubkey"),
+            MissingPreviousTx => f.write_str("non-witness UTXO is required but missing"),
+            PreviousTxMismatch => f.write_str("non-witness UTXO does not contain the spent output"),
+            Derivation(ref e) => write_err!(f, "failed to derive descriptor"; e),
+            Unsatisfiable { input_index } =>
+                write!(f, "the assets can not satisfy the descriptor of input {}", input_index),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UpdateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use UpdateError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            Derivation(ref e) => Some(e),
+            MissingUtxo
+            | ScriptPubkeyMismatch
+            | MissingPreviousTx
+            | PreviousTxMismatch
+            | Unsatisfiable { .. } => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for UpdateError {
+    fn from(e: IndexOutOfBoundsError) -> Self { Self::IndexOutOfBounds(e) }
+}
+
+impl From<ConversionError> for UpdateError {
+    fn from(e: ConversionError) -> Self { Self::Derivation(e) }
+}
+
+/// Error checking that an output is change, see [`Output::is_mine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChangeError {
+    /// No index in the range derives the script pubkey of the output.
+    NotDerived,
+    /// A field of the output does not agree with the derived descriptor.
+    FieldMismatch {
+        /// The name of the field.
+        field: &'static str,
+    },
+    /// The descriptor could not be derived.
+    Derivation(ConversionError),
+}
+
+bitcoin_internals::impl_from_infallible!(ChangeError);
+
+impl fmt::Display for ChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ChangeError::*;
+
+        match *self {
+            NotDerived => f.write_str("the descriptor does not derive the script pubkey"),
+            FieldMismatch { field } =>
+                write!(f, "output field {} does not agree with the descriptor", field),
+            Derivation(ref e) => write_err!(f, "failed to derive descriptor"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChangeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ChangeError::*;
+
+        match *self {
+            Derivation(ref e) => Some(e),
+            NotDerived | FieldMismatch { .. } => None,
+        }
+    }
+}
+
+impl From<ConversionError> for ChangeError {
+    fn from(e: ConversionError) -> Self { Self::Derivation(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{transaction, Amount, Txid};
+    use miniscript::plan::Assets;
+
+    use super::*;
+
+    const XPUB: &str = "[d34db33f/84'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL";
+
+    fn psbt_paying_to(descriptor: &Descriptor<DescriptorPublicKey>, index: u32) -> Psbt {
+        let spk = descriptor.at_derivation_index(index).unwrap().script_pubkey();
+        let txout = TxOut { value: Amount::from_sat(10_000), script_pubkey: spk };
+        let mut psbt = Psbt::with_capacity(1, 1);
+        let txin = TxIn {
+            previous_output: OutPoint { txid: Txid::all_zeros(), vout: 0 },
+            sequence: Sequence::MAX,
+            ..Default::default()
+        };
+        psbt.push_input(txin, Input { witness_utxo: Some(txout.clone()), ..Default::default() })
+            .unwrap();
+        psbt.push_output(txout, Output::default());
+        psbt.unsigned_tx.version = transaction::Version::TWO;
+        psbt
+    }
+
+    #[test]
+    fn update_with_descriptor() {
+        let secp = Secp256k1::verification_only();
+
+        let wsh = format!("wsh(multi(1,{}/0/*,{}/1/*))", XPUB, XPUB);
+        let descriptor = wsh.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt_paying_to(&descriptor, 7);
+        psbt.update_input_with_descriptor(0, &descriptor, 7).unwrap();
+        psbt.update_output_with_descriptor(0, &descriptor, 7).unwrap();
+
+        let derived = descriptor.derived_descriptor(&secp, 7).unwrap();
+        let input = &psbt.inputs[0];
+        assert_eq!(input.witness_script, Some(derived.explicit_script().unwrap()));
+        assert_eq!(input.redeem_script, None);
+        assert_eq!(input.bip32_derivation.len(), 2);
+        assert!(input
+            .bip32_derivation
+            .values()
+            .any(|(fp, path)| fp.to_string() == "d34db33f" && path.to_string() == "84'/0'/0'/1/7"));
+        assert_eq!(psbt.outputs[0].bip32_derivation, input.bip32_derivation);
+        assert_eq!(psbt.outputs[0].witness_script, input.witness_script);
+
+        assert_eq!(
+            psbt.update_input_with_descriptor(0, &descriptor, 8),
+            Err(UpdateError::ScriptPubkeyMismatch)
+        );
+        assert!(matches!(
+            psbt.update_output_with_descriptor(1, &descriptor, 7),
+            Err(UpdateError::IndexOutOfBounds(IndexOutOfBoundsError::Outputs { .. }))
+        ));
+
+        let tr = format!("tr({}/0/*,pk({}/1/*))", XPUB, XPUB);
+        let descriptor = tr.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt_paying_to(&descriptor, 3);
+        psbt.update_input_with_descriptor(0, &descriptor, 3).unwrap();
+        psbt.update_output_with_descriptor(0, &descriptor, 3).unwrap();
+
+        let input = &psbt.inputs[0];
+        let output = &psbt.outputs[0];
+        assert!(input.tap_internal_key.is_some());
+        assert!(input.tap_merkle_root.is_some());
+        assert_eq!(input.tap_scripts.len(), 1);
+        assert_eq!(input.tap_key_origins.len(), 2);
+        let (_, (script, _)) = input.tap_scripts.iter().next().unwrap();
+        let leaf_hash = TapLeafHash::from_script(script, LeafVersion::TapScript);
+        assert!(input.tap_key_origins.values().any(|(hashes, _)| hashes == &vec![leaf_hash]));
+        assert!(input.tap_key_origins[&input.tap_internal_key.unwrap()].0.is_empty());
+        assert_eq!(output.tap_internal_key, input.tap_internal_key);
+        assert_eq!(output.tap_key_origins, input.tap_key_origins);
+        assert_eq!(output.tap_tree.as_ref().map(|t| t.root_hash()), input.tap_merkle_root);
+    }
+
+    #[test]
+    fn update_output_tap_tree() {
+        let secp = Secp256k1::verification_only();
+
+        let tr =
+            format!("tr({}/0/*,{{pk({}/1/*),{{pk({}/2/*),pk({}/3/*)}}}})", XPUB, XPUB, XPUB, XPUB);
+        let descriptor = tr.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt_paying_to(&descriptor, 5);
+        psbt.update_output_with_descriptor(0, &descriptor, 5).unwrap();
+
+        let output = &psbt.outputs[0];
+        let tap_tree = output.tap_tree.as_ref().unwrap();
+        let depths =
+            tap_tree.script_leaves().map(|leaf| leaf.merkle_branch().len()).collect::<Vec<_>>();
+        assert_eq!(depths, vec![1, 2, 2]);
+
+        // The tree and internal key alone let a signer check that the output is the change.
+        let spk = ScriptBuf::new_p2tr(
+            &secp,
+            output.tap_internal_key.unwrap(),
+            Some(tap_tree.root_hash()),
+        );
+        assert_eq!(psbt.unsigned_tx.output[0].script_pubkey, spk);
+        assert_eq!(output.tap_key_origins.len(), 4);
+    }
+
+    #[test]
+    fn update_for_key_spend_and_cached() {
+        let tr =
+            format!("tr({}/0/*,{{pk({}/1/*),{{pk({}/2/*),pk({}/3/*)}}}})", XPUB, XPUB, XPUB, XPUB);
+        let descriptor = tr.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut full = psbt_paying_to(&descriptor, 2);
+        full.update_input_with_descriptor(0, &descriptor, 2).unwrap();
+
+        let mut key_spend = psbt_paying_to(&descriptor, 2);
+        key_spend.update_input_for_key_spend(0, &descriptor, 2).unwrap();
+        let (input, full_input) = (&key_spend.inputs[0], &full.inputs[0]);
+        assert_eq!(input.tap_internal_key, full_input.tap_internal_key);
+        assert_eq!(input.tap_merkle_root, full_input.tap_merkle_root);
+        assert!(input.tap_scripts.is_empty());
+        assert_eq!(input.tap_key_origins.len(), 1);
+        assert_eq!(
+            key_spend.update_input_for_key_spend(0, &descriptor, 3),
+            Err(UpdateError::ScriptPubkeyMismatch)
+        );
+
+        // Without a tree there is no merkle root.
+        let key_only =
+            format!("tr({}/0/*)", XPUB).parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt_paying_to(&key_only, 2);
+        psbt.update_input_with_descriptor(0, &key_only, 2).unwrap();
+        psbt.update_output_with_descriptor(0, &key_only, 2).unwrap();
+        assert!(psbt.inputs[0].tap_internal_key.is_some());
+        assert_eq!(psbt.inputs[0].tap_merkle_root, None);
+        assert_eq!(psbt.outputs[0].tap_tree, None);
+
+        let mut cache = DescriptorCache::new();
+        let mut cached = psbt_paying_to(&descriptor, 2);
+        cached.update_input_with_descriptor_cached(0, &descriptor, 2, &mut cache).unwrap();
+        cached.update_output_with_descriptor_cached(0, &descriptor, 2, &mut cache).unwrap();
+        assert_eq!(cached.inputs, full.inputs);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(
+            cached.update_input_with_descriptor_cached(0, &descriptor, 3, &mut cache),
+            Err(UpdateError::ScriptPubkeyMismatch)
+        );
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn plan_and_update() {
+        let key = format!("{}/0/*", XPUB).parse::<DescriptorPublicKey>().unwrap();
+        let wsh = format!("wsh(multi(1,{}/0/*,{}/1/*))", XPUB, XPUB);
+        let wsh = wsh.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let spks = (0..10)
+            .map(|index| (wsh.at_derivation_index(index).unwrap().script_pubkey(), index))
+            .collect::<BTreeMap<_, _>>();
+        let resolver = |spk: &Script| spks.get(spk).copied();
+
+        let mut psbt = psbt_paying_to(&wsh, 3);
+        let other = psbt_paying_to(&wsh, 20);
+        psbt.push_input(other.unsigned_tx.input[0].clone(), other.inputs[0].clone()).unwrap();
+        let spent = psbt_paying_to(&wsh, 5);
+        psbt.push_input(spent.unsigned_tx.input[0].clone(), spent.inputs[0].clone()).unwrap();
+        psbt.push_input(TxIn::default(), Input::default()).unwrap();
+
+        let unsatisfiable = psbt.plan_and_update(&wsh, &Assets::new(), resolver);
+        assert!(matches!(unsatisfiable, Err(UpdateError::Unsatisfiable { input_index: 0 })));
+        assert!(psbt.inputs[0].witness_script.is_none());
+
+        let plans = psbt.plan_and_update(&wsh, &Assets::new().add(key.clone()), resolver).unwrap();
+        assert_eq!(plans.keys().copied().collect::<Vec<_>>(), [0, 2]);
+        let mut expected = psbt_paying_to(&wsh, 5);
+        expected.update_input_with_descriptor(0, &wsh, 5).unwrap();
+        assert_eq!(psbt.inputs[2], expected.inputs[0]);
+        assert!(psbt.inputs[1].witness_script.is_none());
+        assert_eq!(psbt.check_timelocks(&plans), Ok(()));
+
+        // A key path plan does not need the leaves.
+        let tr = format!("tr({}/0/*,pk({}/1/*))", XPUB, XPUB);
+        let tr = tr.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt_paying_to(&tr, 1);
+        let plans = psbt.plan_and_update(&tr, &Assets::new().add(key), |_| Some(1)).unwrap();
+        assert_eq!(plans.len(), 1);
+        assert!(psbt.inputs[0].tap_merkle_root.is_some());
+        assert!(psbt.inputs[0].tap_scripts.is_empty());
+    }
+
+    #[test]
+    fn verify_change() {
+        let wpkh = format!("wpkh({}/1/*)", XPUB);
+        let descriptor = wpkh.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt_paying_to(&descriptor, 12);
+        psbt.update_output_with_descriptor(0, &descriptor, 12).unwrap();
+
+        let spk = psbt.unsigned_tx.output[0].script_pubkey.clone();
+        assert_eq!(psbt.outputs[0].is_mine(&spk, &descriptor, 0..20), Ok(12));
+        assert_eq!(psbt.outputs[0].is_mine(&spk, &descriptor, 0..10), Err(ChangeError::NotDerived));
+        assert_eq!(psbt.verify_change(&descriptor, 0..20), Ok(vec![Some(12)]));
+
+        // A derivation path pointing elsewhere would mislead a hardware signer.
+        let (_, path) = psbt.outputs[0].bip32_derivation.values_mut().next().unwrap();
+        *path = "84'/0'/0'/1/13".parse().unwrap();
+        assert_eq!(
+            psbt.outputs[0].is_mine(&spk, &descriptor, 0..20),
+            Err(ChangeError::FieldMismatch { field: "bip32_derivation" })
+        );
+        assert_eq!(psbt.verify_change(&descriptor, 0..20), Ok(vec![None]));
+    }
+
+    #[test]
+    fn add_input_from_utxo() {
+        let wpkh = format!("wpkh({}/0/*)", XPUB);
+        let wpkh = wpkh.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let pkh = format!("pkh({}/0/*)", XPUB);
+        let pkh = pkh.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let pay_to = |descriptor: &Descriptor<DescriptorPublicKey>| {
+            let spk = descriptor.at_derivation_index(4).unwrap().script_pubkey();
+            let utxo = TxOut { value: Amount::from_sat(10_000), script_pubkey: spk };
+            let tx = Transaction {
+                version: transaction::Version::TWO,
+                lock_time: bitcoin::absolute::LockTime::ZERO,
+                input: vec![TxIn::default()],
+                output: vec![utxo.clone()],
+            };
+            (OutPoint { txid: tx.compute_txid(), vout: 0 }, utxo, tx)
+        };
+        let mut psbt = Psbt::with_capacity(0, 0);
+
+        let (outpoint, utxo, tx) = pay_to(&wpkh);
+        let index = psbt
+            .add_input_from_utxo(outpoint, utxo.clone(), None, &wpkh, 4, UtxoPolicy::WitnessOnly)
+            .unwrap();
+        assert_eq!(psbt.inputs[index].witness_utxo, Some(utxo.clone()));
+        assert_eq!(psbt.inputs[index].non_witness_utxo, None);
+        assert_eq!(psbt.inputs[index].bip32_derivation.len(), 1);
+        assert_eq!(
+            psbt.add_input_from_utxo(
+                outpoint,
+                utxo.clone(),
+                None,
+                &wpkh,
+                4,
+                UtxoPolicy::IncludeNonWitness
+            ),
+            Err(UpdateError::MissingPreviousTx)
+        );
+        let index = psbt
+            .add_input_from_utxo(
+                outpoint,
+                utxo.clone(),
+                Some(tx.clone()),
+                &wpkh,
+                4,
+                UtxoPolicy::IncludeNonWitness,
+            )
+            .unwrap();
+        assert_eq!(psbt.inputs[index].non_witness_utxo, Some(tx));
+
+        let (outpoint, utxo, tx) = pay_to(&pkh);
+        assert_eq!(
+            psbt.add_input_from_utxo(
+                outpoint,
+                utxo.clone(),
+                None,
+                &pkh,
+                4,
+                UtxoPolicy::WitnessOnly
+            ),
+            Err(UpdateError::MissingPreviousTx)
+        );
+        assert_eq!(
+            psbt.add_input_from_utxo(
+                outpoint,
+                utxo.clone(),
+                Some(tx.clone()),
+                &pkh,
+                5,
+                UtxoPolicy::WitnessOnly
+            ),
+            Err(UpdateError::ScriptPubkeyMismatch)
+        );
+        let mismatched = OutPoint { vout: 1, ..outpoint };
+        assert_eq!(
+            psbt.add_input_from_utxo(
+                mismatched,
+                utxo.clone(),
+                Some(tx.clone()),
+                &pkh,
+                4,
+                UtxoPolicy::WitnessOnly
+            ),
+            Err(UpdateError::PreviousTxMismatch)
+        );
+        assert_eq!(psbt.inputs.len(), 2);
+        assert_eq!(psbt.unsigned_tx.input.len(), 2);
+
+        let index = psbt
+            .add_input_from_utxo(outpoint, utxo, Some(tx.clone()), &pkh, 4, UtxoPolicy::WitnessOnly)
+            .unwrap();
+        assert_eq!(psbt.inputs[index].witness_utxo, None);
+        assert_eq!(psbt.inputs[index].non_witness_utxo, Some(tx));
+        assert_eq!(psbt.unsigned_tx.input[index].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+    }
+}
+
+#[cfg(bench)]
+mod benches {
+    use test::{black_box, Bencher};
+
+    use super::*;
+
+    const TR: &str = "tr([d34db33f/86'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*,{pk(02e96fe52ef0e22d2f131dd425ce1893073a3c6ad20e8cac36726393dfb4856a4c),{pk(03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd),pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)}})";
+
+    /// A PSBT with 50 inputs all spending the descriptor at index 0.
+    fn psbt(descriptor: &Descriptor<DescriptorPublicKey>) -> Psbt {
+        let spk = descriptor.at_derivation_index(0).unwrap().script_pubkey();
+        let utxo = TxOut { value: bitcoin::Amount::from_sat(10_000), script_pubkey: spk };
+        let mut psbt = Psbt::with_capacity(50, 0);
+        for _ in 0..50 {
+            let input = Input { witness_utxo: Some(utxo.clone()), ..Default::default() };
+            psbt.push_input(TxIn::default(), input).unwrap();
+        }
+        psbt
+    }
+
+    #[bench]
+    fn update_inputs(bh: &mut Bencher) {
+        let descriptor = TR.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt(&descriptor);
+        bh.iter(|| {
+            for index in 0..psbt.inputs.len() {
+                psbt.update_input_with_descriptor(index, &descriptor, 0).unwrap();
+            }
+            black_box(&psbt);
+        });
+    }
+
+    #[bench]
+    fn update_inputs_for_key_spend(bh: &mut Bencher) {
+        let descriptor = TR.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt(&descriptor);
+        bh.iter(|| {
+            for index in 0..psbt.inputs.len() {
+                psbt.update_input_for_key_spend(index, &descriptor, 0).unwrap();
+            }
+            black_box(&psbt);
+        });
+    }
+
+    #[bench]
+    fn update_inputs_cached(bh: &mut Bencher) {
+        let descriptor = TR.parse::<Descriptor<DescriptorPublicKey>>().unwrap();
+        let mut psbt = psbt(&descriptor);
+        bh.iter(|| {
+            let mut cache = DescriptorCache::new();
+            for index in 0..psbt.inputs.len() {
+                psbt.update_input_with_descriptor_cached(index, &descriptor, 0, &mut cache)
+                    .unwrap();
+            }
+            black_box(&psbt);
+        });
+    }
+}
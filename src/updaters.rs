@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! A common interface for the backends that update a PSBT.
+//!
+//! Watch-only services usually know different things about a PSBT from different places: the
+//! UTXOs from a chain index, the scripts and key origins from the wallet's descriptors. Each
+//! source is an [`Updater`], and a list of updaters is itself an updater that runs them in order,
+//! so a server can create a PSBT, run it through its updaters and hand it to the signers.
+
+use core::fmt;
+#[cfg(feature = "miniscript")]
+use core::ops::Range;
+
+#[cfg(feature = "miniscript")]
+use bitcoin::ScriptBuf;
+use bitcoin::{OutPoint, TxOut};
+#[cfg(feature = "miniscript")]
+use bitcoin_internals::write_err;
+#[cfg(feature = "miniscript")]
+use miniscript::descriptor::{Descriptor, DescriptorPublicKey};
+
+use crate::prelude::*;
+use crate::Psbt;
+#[cfg(feature = "miniscript")]
+use crate::{DescriptorCache, UpdateError};
+
+/// A backend that adds what it knows about the inputs and outputs to a PSBT.
+///
+/// The trait is object safe, errors from the backend are reported as strings.
+pub trait Updater {
+    /// Updates `psbt` with what this backend knows.
+    ///
+    /// # Errors
+    ///
+    /// If the backend fails. The PSBT may have been partially updated.
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError>;
+}
+
+impl<U: Updater + ?Sized> Updater for &U {
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError> {
+        (**self).update(psbt)
+    }
+}
+
+impl<U: Updater + ?Sized> Updater for Box<U> {
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError> {
+        (**self).update(psbt)
+    }
+}
+
+/// Runs the updaters in order, stopping at the first that fails.
+impl<U: Updater> Updater for [U] {
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError> {
+        let mut report = UpdateReport::default();
+        for updater in self {
+            report.extend(updater.update(psbt)?);
+        }
+        Ok(report)
+    }
+}
+
+/// Runs the updaters in order, stopping at the first that fails.
+impl<U: Updater> Updater for Vec<U> {
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError> {
+        self[..].update(psbt)
+    }
+}
+
+/// The inputs and outputs an [`Updater`] changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateReport {
+    /// The indices of the inputs that were changed, in order.
+    pub updated_inputs: Vec<usize>,
+    /// The indices of the outputs that were changed, in order.
+    pub updated_outputs: Vec<usize>,
+}
+
+impl UpdateReport {
+    /// Returns true if nothing was changed.
+    pub fn is_empty(&self) -> bool {
+        self.updated_inputs.is_empty() && self.updated_outputs.is_empty()
+    }
+
+    /// Adds the inputs and outputs changed according to `other`.
+    pub fn extend(&mut self, other: UpdateReport) {
+        for (ours, theirs) in [
+            (&mut self.updated_inputs, other.updated_inputs),
+            (&mut self.updated_outputs, other.updated_outputs),
+        ] {
+            ours.extend(theirs);
+            ours.sort_unstable();
+            ours.dedup();
+        }
+    }
+}
+
+/// Sets the witness UTXO of inputs missing their UTXO, looking up the spent outputs with an
+/// oracle, e.g. a chain index or Bitcoin Core's `gettxout`.
+///
+/// Only native SegWit outputs, and P2SH outputs of inputs with a SegWit redeem script, are set.
+/// Legacy inputs need the whole previous transaction, see [`Psbt::populate_utxos`].
+pub struct ChainUpdater<F>(pub F);
+
+impl<F> Updater for ChainUpdater<F>
+where
+    F: Fn(OutPoint) -> Option<TxOut>,
+{
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError> {
+        let mut report = UpdateReport::default();
+        for (index, (input, txin)) in
+            psbt.inputs.iter_mut().zip(&psbt.unsigned_tx.input).enumerate()
+        {
+            if input.witness_utxo.is_some() || input.non_witness_utxo.is_some() {
+                continue;
+            }
+            let utxo = match (self.0)(txin.previous_output) {
+                Some(utxo) => utxo,
+                None => continue,
+            };
+            let spk = &utxo.script_pubkey;
+            let segwit = spk.is_witness_program()
+                || (spk.is_p2sh()
+                    && input.redeem_script.as_ref().map_or(false, |script| {
+                        script.is_witness_program() && script.to_p2sh() == *spk
+                    }));
+            if segwit {
+                input.witness_utxo = Some(utxo);
+                report.updated_inputs.push(index);
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Updates the inputs and outputs derived by a set of wallet descriptors.
+///
+/// Each descriptor is derived at every index in a range, the wallet's gap limit. An input whose
+/// UTXO, or an output whose script pubkey, one of the derived descriptors produces is updated as
+/// by [`Psbt::update_input_with_descriptor`] and [`Psbt::update_output_with_descriptor`]. Inputs
+/// need their UTXO, so a [`ChainUpdater`] should run first.
+#[cfg(feature = "miniscript")]
+#[derive(Debug, Clone)]
+pub struct DescriptorUpdater {
+    descriptors: Vec<Descriptor<DescriptorPublicKey>>,
+    range: Range<u32>,
+}
+
+#[cfg(feature = "miniscript")]
+impl DescriptorUpdater {
+    /// Creates an updater deriving `descriptors` at the indices in `range`.
+    ///
+    /// Multipath descriptors must be split into single path descriptors first.
+    pub fn new(descriptors: Vec<Descriptor<DescriptorPublicKey>>, range: Range<u32>) -> Self {
+        DescriptorUpdater { descriptors, range }
+    }
+
+    /// Returns the descriptor and index deriving each script pubkey.
+    fn derive_all(&self) -> Result<BTreeMap<ScriptBuf, (usize, u32)>, UpdateError> {
+        let mut derived = BTreeMap::new();
+        for (descriptor_index, descriptor) in self.descriptors.iter().enumerate() {
+            for index in self.range.clone() {
+                let spk = descriptor.at_derivation_index(index)?.script_pubkey();
+                derived.entry(spk).or_insert((descriptor_index, index));
+            }
+        }
+        Ok(derived)
+    }
+}
+
+#[cfg(feature = "miniscript")]
+impl Updater for DescriptorUpdater {
+    fn update(&self, psbt: &mut Psbt) -> Result<UpdateReport, UpdaterError> {
+        let derived = self.derive_all()?;
+        let mut cache = DescriptorCache::new();
+        let mut report = UpdateReport::default();
+        for index in 0..psbt.inputs.len() {
+            let spk = match psbt.spend_utxo(index) {
+                Ok(utxo) => &utxo.script_pubkey,
+                Err(_) => continue,
+            };
+            if let Some(&(descriptor_index, derivation_index)) = derived.get(spk) {
+                let before = psbt.inputs[index].clone();
+                let descriptor = &self.descriptors[descriptor_index];
+                psbt.update_input_with_descriptor_cached(
+                    index,
+                    descriptor,
+                    derivation_index,
+                    &mut cache,
+                )?;
+                if psbt.inputs[index] != before {
+                    report.updated_inputs.push(index);
+                }
+            }
+        }
+        for index in 0..psbt.outputs.len() {
+            let spk = &psbt.unsigned_tx.output[index].script_pubkey;
+            if let Some(&(descriptor_index, derivation_index)) = derived.get(spk) {
+                let before = psbt.outputs[index].clone();
+                let descriptor = &self.descriptors[descriptor_index];
+                psbt.update_output_with_descriptor_cached(
+                    index,
+                    descriptor,
+                    derivation_index,
+                    &mut cache,
+                )?;
+                if psbt.outputs[index] != before {
+                    report.updated_outputs.push(index);
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Error returned by an [`Updater`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpdaterError {
+    /// The backend failed.
+    Backend(String),
+    /// Updating from a descriptor failed.
+    #[cfg(feature = "miniscript")]
+    Descriptor(UpdateError),
+}
+
+bitcoin_internals::impl_from_infallible!(UpdaterError);
+
+impl fmt::Display for UpdaterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use UpdaterError::*;
+
+        match *self {
+            Backend(ref msg) => write!(f, "updater failed: {}", msg),
+            #[cfg(feature = "miniscript")]
+            Descriptor(ref e) => write_err!(f, "failed to update from a descriptor"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UpdaterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use UpdaterError::*;
+
+        match *self {
+            Backend(_) => None,
+            #[cfg(feature = "miniscript")]
+            Descriptor(ref e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "miniscript")]
+impl From<UpdateError> for UpdaterError {
+    fn from(e: UpdateError) -> Self { Self::Descriptor(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, transaction, Amount, ScriptBuf, Transaction, TxIn, Txid};
+
+    use super::*;
+
+    fn outpoint(vout: u32) -> OutPoint { OutPoint { txid: Txid::all_zeros(), vout } }
+
+    #[test]
+    fn update_pipeline() {
+        #[cfg(feature = "miniscript")]
+        let descriptor = "wpkh([d34db33f/84'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/0/*)"
+            .parse::<Descriptor<DescriptorPublicKey>>()
+            .unwrap();
+        #[cfg(feature = "miniscript")]
+        let ours = descriptor.at_derivation_index(5).unwrap().script_pubkey();
+        #[cfg(not(feature = "miniscript"))]
+        let ours = ScriptBuf::new_p2wsh(&bitcoin::WScriptHash::all_zeros());
+        let legacy = ScriptBuf::new_p2pkh(&bitcoin::PubkeyHash::all_zeros());
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..3)
+                .map(|vout| TxIn { previous_output: outpoint(vout), ..Default::default() })
+                .collect(),
+            output: vec![TxOut { value: Amount::from_sat(5_000), script_pubkey: ours.clone() }],
+        })
+        .unwrap();
+        let chain = ChainUpdater(|outpoint: OutPoint| match outpoint.vout {
+            0 => Some(TxOut { value: Amount::from_sat(10_000), script_pubkey: ours.clone() }),
+            1 => Some(TxOut { value: Amount::from_sat(10_000), script_pubkey: legacy.clone() }),
+            _ => None,
+        });
+        let unknown = ChainUpdater(|_: OutPoint| None);
+
+        let updaters: Vec<Box<dyn Updater>> = vec![
+            Box::new(chain),
+            Box::new(unknown),
+            #[cfg(feature = "miniscript")]
+            Box::new(DescriptorUpdater::new(vec![descriptor], 0..10)),
+        ];
+        let report = updaters.update(&mut psbt).unwrap();
+        assert_eq!(report.updated_inputs, vec![0]);
+        assert_eq!(psbt.inputs[0].witness_utxo.as_ref().unwrap().script_pubkey, ours);
+        assert_eq!(psbt.inputs[1].witness_utxo, None);
+        #[cfg(feature = "miniscript")]
+        {
+            assert_eq!(report.updated_outputs, vec![0]);
+            assert_eq!(psbt.inputs[0].bip32_derivation.len(), 1);
+            assert_eq!(psbt.outputs[0].bip32_derivation, psbt.inputs[0].bip32_derivation);
+        }
+
+        // Nothing is left to update.
+        assert!(updaters.update(&mut psbt).unwrap().is_empty());
+    }
+}
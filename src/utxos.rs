@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Adding and removing the previous transactions of inputs.
+//!
+//! A non-witness UTXO holds the whole transaction being spent, often most of the size of a PSBT.
+//! SegWit inputs can do with the witness UTXO alone, except for signers guarding against the fee
+//! attack on SegWit v0 sighashes. Legacy inputs can not be signed without it.
+
+use core::fmt;
+
+use bitcoin::{Script, Transaction, Txid};
+
+use crate::prelude::*;
+use crate::{Error, Input, Psbt, PsbtReader, PsbtWriter};
+
+/// Which UTXO fields SegWit v0 inputs carry.
+///
+/// Taproot sighashes commit to the amounts of all inputs, Taproot inputs never need the
+/// non-witness UTXO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UtxoPolicy {
+    /// Only the witness UTXO, as BIP 174 requires.
+    WitnessOnly,
+    /// The witness UTXO and the non-witness UTXO, as many hardware signers require.
+    IncludeNonWitness,
+}
+
+impl Psbt {
+    /// Removes the non-witness UTXOs that SegWit inputs do not need under `policy`.
+    ///
+    /// A missing witness UTXO is first taken from the non-witness UTXO. The non-witness UTXO is
+    /// removed from Taproot inputs, and from SegWit v0 inputs with [`UtxoPolicy::WitnessOnly`].
+    /// Legacy inputs and inputs whose non-witness UTXO does not contain the spent output are
+    /// left alone.
+    pub fn prune_non_witness_utxos(&mut self, policy: UtxoPolicy) {
+        for (input, txin) in self.inputs.iter_mut().zip(&self.unsigned_tx.input) {
+            let vout = txin.previous_output.vout as usize;
+            let spent = match (&input.witness_utxo, &input.non_witness_utxo) {
+                (_, None) => continue,
+                (Some(utxo), Some(_)) => utxo.clone(),
+                (None, Some(tx)) => match tx.output.get(vout) {
+                    Some(utxo) => utxo.clone(),
+                    None => continue,
+                },
+            };
+
+            let prune = if spent.script_pubkey.is_p2tr() {
+                true
+            } else if is_segwit(input, &spent.script_pubkey) {
+                policy == UtxoPolicy::WitnessOnly
+            } else {
+                continue;
+            };
+            input.witness_utxo = Some(spent);
+            if prune {
+                input.non_witness_utxo = None;
+            }
+        }
+    }
+
+    /// Adds the non-witness UTXO to inputs missing one, looking up previous transactions with
+    /// `resolver`.
+    ///
+    /// Taproot inputs with a witness UTXO are skipped, they never need the non-witness UTXO. A
+    /// missing witness UTXO of a SegWit input is set from the previous transaction too. `resolver`
+    /// is called once per txid, e.g. backed by an Electrum server or Bitcoin Core's
+    /// `getrawtransaction`.
+    ///
+    /// Returns the indices of the inputs whose previous transaction `resolver` did not find.
+    ///
+    /// # Errors
+    ///
+    /// If a transaction returned by `resolver` is not the one being spent. The inputs before it
+    /// have been updated.
+    pub fn populate_utxos<F>(&mut self, mut resolver: F) -> Result<Vec<usize>, PopulateUtxosError>
+    where
+        F: FnMut(Txid) -> Option<Transaction>,
+    {
+        let mut resolved = BTreeMap::new();
+        let mut not_found = vec![];
+        for (input_index, (input, txin)) in
+            self.inputs.iter_mut().zip(&self.unsigned_tx.input).enumerate()
+        {
+            let taproot =
+                input.witness_utxo.as_ref().map_or(false, |utxo| utxo.script_pubkey.is_p2tr());
+            if input.non_witness_utxo.is_some() || taproot {
+                continue;
+            }
+
+            let txid = txin.previous_output.txid;
+            let tx = match resolved.entry(txid).or_insert_with(|| resolver(txid)) {
+                Some(tx) => tx.clone(),
+                None => {
+                    not_found.push(input_index);
+                    continue;
+                }
+            };
+            if tx.compute_txid() != txid {
+                return Err(PopulateUtxosError::TxidMismatch { input_index });
+            }
+            let spent = match tx.output.get(txin.previous_output.vout as usize) {
+                Some(spent) => spent.clone(),
+                None => return Err(PopulateUtxosError::MissingOutput { input_index }),
+            };
+            if input.witness_utxo.as_ref().map_or(false, |utxo| *utxo != spent) {
+                return Err(PopulateUtxosError::WitnessUtxoMismatch { input_index });
+            }
+
+            if input.witness_utxo.is_none() && is_segwit(input, &spent.script_pubkey) {
+                input.witness_utxo = Some(spent);
+            }
+            input.non_witness_utxo = Some(tx);
+        }
+        Ok(not_found)
+    }
+}
+
+/// A PSBT holding each non-witness UTXO once, however many of its inputs spend from it.
+///
+/// Every input of a [`Psbt`] carries its own copy of the previous transaction, so a large
+/// transaction spent by many inputs is held in memory many times. Only copies equal to each other
+/// are shared, the serialization is unchanged.
+///
+/// ```
+/// # use psbt_v0::{CompactPsbt, PsbtBuilder};
+/// # let bytes = PsbtBuilder::new().input(Default::default()).build().serialize();
+/// let compact = CompactPsbt::deserialize(&bytes)?;
+/// assert_eq!(compact.serialize(), bytes);
+/// let psbt = compact.into_psbt();
+/// # let _ = psbt;
+/// # Ok::<_, psbt_v0::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactPsbt {
+    /// The PSBT, without the non-witness UTXOs held in `non_witness_utxos`.
+    psbt: Psbt,
+    /// Whether the non-witness UTXO of each input is held in `non_witness_utxos`.
+    shared: Vec<bool>,
+    /// The shared non-witness UTXOs by the txid the inputs spend from.
+    non_witness_utxos: BTreeMap<Txid, Transaction>,
+}
+
+impl Psbt {
+    /// Makes the inputs spending outputs of the same transaction share its non-witness UTXO.
+    pub fn compact(mut self) -> CompactPsbt {
+        let num_inputs = self.inputs.len();
+        let inputs = core::mem::replace(&mut self.inputs, Vec::with_capacity(num_inputs));
+        let mut compact = CompactPsbt {
+            psbt: self,
+            shared: Vec::with_capacity(num_inputs),
+            non_witness_utxos: BTreeMap::new(),
+        };
+        for input in inputs {
+            compact.push_input(input);
+        }
+        compact
+    }
+}
+
+impl CompactPsbt {
+    /// Deserializes a PSBT, sharing each non-witness UTXO between inputs as they are decoded.
+    ///
+    /// Unlike [`Psbt::deserialize`], equal copies of a previous transaction are never held in
+    /// memory at the same time.
+    pub fn deserialize(mut bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = PsbtReader::new(&mut bytes)?;
+        let num_inputs = reader.unsigned_tx().input.len();
+        let mut compact = CompactPsbt {
+            psbt: reader.global().clone(),
+            shared: Vec::with_capacity(num_inputs),
+            non_witness_utxos: BTreeMap::new(),
+        };
+        compact.psbt.inputs.reserve(num_inputs);
+        while let Some(input) = reader.read_input()? {
+            compact.push_input(input);
+        }
+        while let Some(output) = reader.read_output()? {
+            compact.psbt.outputs.push(output);
+        }
+        Ok(compact)
+    }
+
+    /// Serializes the PSBT, as [`Psbt::serialize`] does for [`CompactPsbt::into_psbt`].
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut writer = PsbtWriter::new(&mut buf, &self.psbt).expect("writing to a vec");
+        for (input_index, input) in self.psbt.inputs.iter().enumerate() {
+            match self.non_witness_utxo(input_index) {
+                Some(tx) if self.shared[input_index] => {
+                    let input = Input { non_witness_utxo: Some(tx.clone()), ..input.clone() };
+                    writer.write_input(&input)
+                }
+                _ => writer.write_input(input),
+            }
+            .expect("writing to a vec");
+        }
+        for output in &self.psbt.outputs {
+            writer.write_output(output).expect("writing to a vec");
+        }
+        writer.finish().expect("all maps written");
+        buf
+    }
+
+    /// Returns the PSBT without the non-witness UTXOs of its inputs that share one.
+    ///
+    /// Use [`CompactPsbt::non_witness_utxo`] to get the non-witness UTXO of an input.
+    pub fn psbt(&self) -> &Psbt { &self.psbt }
+
+    /// Returns the non-witness UTXO of the input at `input_index`, if any.
+    pub fn non_witness_utxo(&self, input_index: usize) -> Option<&Transaction> {
+        if *self.shared.get(input_index)? {
+            let txid = self.psbt.unsigned_tx.input[input_index].previous_output.txid;
+            self.non_witness_utxos.get(&txid)
+        } else {
+            self.psbt.inputs[input_index].non_witness_utxo.as_ref()
+        }
+    }
+
+    /// Returns the number of distinct non-witness UTXOs held for the inputs.
+    pub fn num_shared_non_witness_utxos(&self) -> usize { self.non_witness_utxos.len() }
+
+    /// Returns the PSBT, giving every input its own copy of its non-witness UTXO again.
+    pub fn into_psbt(self) -> Psbt {
+        let CompactPsbt { mut psbt, shared, non_witness_utxos } = self;
+        for ((input, txin), shared) in
+            psbt.inputs.iter_mut().zip(&psbt.unsigned_tx.input).zip(shared)
+        {
+            if shared {
+                input.non_witness_utxo = non_witness_utxos.get(&txin.previous_output.txid).cloned();
+            }
+        }
+        psbt
+    }
+
+    /// Appends `input`, moving its non-witness UTXO into `non_witness_utxos` unless a different
+    /// transaction is held for the txid it spends from.
+    fn push_input(&mut self, mut input: Input) {
+        let input_index = self.psbt.inputs.len();
+        let txid = self.psbt.unsigned_tx.input[input_index].previous_output.txid;
+        let shared = match input.non_witness_utxo.take() {
+            None => false,
+            Some(tx) => match self.non_witness_utxos.entry(txid) {
+                btree_map::Entry::Vacant(entry) => {
+                    entry.insert(tx);
+                    true
+                }
+                btree_map::Entry::Occupied(entry) if *entry.get() == tx => true,
+                btree_map::Entry::Occupied(_) => {
+                    input.non_witness_utxo = Some(tx);
+                    false
+                }
+            },
+        };
+        self.psbt.inputs.push(input);
+        self.shared.push(shared);
+    }
+}
+
+/// Returns true if `input`, spending `spk`, is a native or nested SegWit input.
+fn is_segwit(input: &Input, spk: &Script) -> bool {
+    spk.is_witness_program()
+        || (spk.is_p2sh()
+            && input.redeem_script.as_ref().map_or(false, |script| script.is_witness_program()))
+}
+
+/// Error returned by [`Psbt::populate_utxos`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PopulateUtxosError {
+    /// The previous transaction found for the input does not have the txid it spends.
+    TxidMismatch {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The previous transaction found for the input does not have the output it spends.
+    MissingOutput {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The spent output of the previous transaction is not the input's witness UTXO.
+    WitnessUtxoMismatch {
+        /// The index of the input.
+        input_index: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(PopulateUtxosError);
+
+impl fmt::Display for PopulateUtxosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PopulateUtxosError::*;
+
+        match *self {
+            TxidMismatch { input_index } =>
+                write!(f, "the previous transaction of input {} has the wrong txid", input_index),
+            MissingOutput { input_index } => write!(
+                f,
+                "the previous transaction of input {} does not have the spent output",
+                input_index
+            ),
+            WitnessUtxoMismatch { input_index } => write!(
+                f,
+                "the previous transaction of input {} does not match its witness UTXO",
+                input_index
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PopulateUtxosError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use PopulateUtxosError::*;
+
+        match *self {
+            TxidMismatch { .. } | MissingOutput { .. } | WitnessUtxoMismatch { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::{
+        absolute, transaction, Amount, OutPoint, ScriptBuf, TxIn, TxOut, WPubkeyHash, WScriptHash,
+    };
+
+    use super::*;
+
+    #[test]
+    fn prune_and_populate_utxos() {
+        let scripts = [
+            ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+            ScriptBuf::from_bytes([&[0x51, 0x20][..], &[2; 32]].concat()),
+            ScriptBuf::new_p2wsh(&WScriptHash::all_zeros()).to_p2sh(),
+        ];
+        let previous = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: scripts
+                .iter()
+                .map(|script| TxOut {
+                    value: Amount::from_sat(1_000),
+                    script_pubkey: script.clone(),
+                })
+                .collect(),
+        };
+        let txid = previous.compute_txid();
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..3)
+                .map(|vout| TxIn { previous_output: OutPoint { txid, vout }, ..Default::default() })
+                .collect(),
+            output: vec![],
+        })
+        .unwrap();
+        // The P2SH input is a legacy input without a redeem script.
+        psbt.inputs[1].witness_utxo = Some(previous.output[1].clone());
+
+        let mut calls = 0;
+        let not_found = psbt
+            .populate_utxos(|id| {
+                calls += 1;
+                Some(previous.clone()).filter(|_| id == txid)
+            })
+            .unwrap();
+        assert!(not_found.is_empty());
+        assert_eq!(calls, 1);
+        assert_eq!(psbt.inputs[0].witness_utxo, Some(previous.output[0].clone()));
+        assert!(psbt.inputs[1].non_witness_utxo.is_none());
+        assert!(psbt.inputs[2].witness_utxo.is_none());
+        assert!(psbt.inputs[2].non_witness_utxo.is_some());
+
+        let mut pruned = psbt.clone();
+        pruned.prune_non_witness_utxos(UtxoPolicy::IncludeNonWitness);
+        assert_eq!(pruned, psbt);
+        pruned.prune_non_witness_utxos(UtxoPolicy::WitnessOnly);
+        assert!(pruned.inputs[0].non_witness_utxo.is_none());
+        assert!(pruned.inputs[2].non_witness_utxo.is_some());
+
+        let mut psbt = pruned;
+        psbt.inputs[2].non_witness_utxo = None;
+        assert_eq!(psbt.populate_utxos(|_| None), Ok(vec![0, 2]));
+        let mut wrong = previous.clone();
+        wrong.output[0].value = Amount::from_sat(1);
+        assert_eq!(
+            psbt.populate_utxos(|_| Some(wrong.clone())),
+            Err(PopulateUtxosError::TxidMismatch { input_index: 0 })
+        );
+    }
+
+    #[test]
+    fn compact_psbt() {
+        let previous = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); 100],
+            output: vec![TxOut::NULL; 10],
+        };
+        let txid = previous.compute_txid();
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..10)
+                .map(|vout| TxIn { previous_output: OutPoint { txid, vout }, ..Default::default() })
+                .collect(),
+            output: vec![],
+        })
+        .unwrap();
+        for input in &mut psbt.inputs[..9] {
+            input.non_witness_utxo = Some(previous.clone());
+        }
+
+        // The nine inputs hold one copy of the transaction instead of nine.
+        let bytes = psbt.serialize();
+        let compact = CompactPsbt::deserialize(&bytes).unwrap();
+        assert_eq!(compact.num_shared_non_witness_utxos(), 1);
+        assert!(compact.psbt().inputs.iter().all(|input| input.non_witness_utxo.is_none()));
+        assert_eq!(compact.non_witness_utxo(0), Some(&previous));
+        assert_eq!(compact.non_witness_utxo(9), None);
+        assert_eq!(compact.non_witness_utxo(10), None);
+        assert_eq!(compact.serialize(), bytes);
+        assert_eq!(compact, psbt.clone().compact());
+        assert_eq!(compact.into_psbt(), psbt);
+
+        // A copy that differs is not shared.
+        let mut other = previous;
+        other.lock_time = absolute::LockTime::from_consensus(1);
+        psbt.inputs[8].non_witness_utxo = Some(other.clone());
+        let compact = psbt.clone().compact();
+        assert_eq!(compact.num_shared_non_witness_utxos(), 1);
+        assert_eq!(compact.psbt().inputs[8].non_witness_utxo, Some(other));
+        assert_eq!(compact.serialize(), psbt.serialize());
+        assert_eq!(compact.into_psbt(), psbt);
+    }
+}
@@ -0,0 +1,1186 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! PSBTs of version 2, as described in BIP 370.
+//!
+//! A version 2 PSBT has no unsigned transaction, the transaction fields are spread over the
+//! global, input and output maps. [`PsbtV2`] holds such a PSBT and converts to and from a
+//! version 0 [`Psbt`], [`VersionedPsbt`] holds a PSBT of either version. When every field needed
+//! to build the transaction is present [`Psbt::deserialize_any_version`] converts a version 2
+//! PSBT to version 0 directly, all other fields are decoded as in version 0.
+
+use core::fmt;
+
+use bitcoin::bip32::{KeySource, Xpub};
+use bitcoin::consensus::encode::{self, Decodable, VarInt};
+use bitcoin::{
+    absolute, transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+};
+use bitcoin_internals::write_err;
+
+use crate::map::Map;
+use crate::prelude::*;
+use crate::serialize::{DeserializeOptions, Serialize};
+use crate::stream::{MAGIC_BYTES, PSBT_SERPARATOR};
+use crate::{raw, Error, Input, MapLocation, Output, Psbt};
+
+/// Type: Unsigned Transaction PSBT_GLOBAL_UNSIGNED_TX = 0x00
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+/// Type: Transaction Version PSBT_GLOBAL_TX_VERSION = 0x02
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+/// Type: Fallback Locktime PSBT_GLOBAL_FALLBACK_LOCKTIME = 0x03
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+/// Type: Input Count PSBT_GLOBAL_INPUT_COUNT = 0x04
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+/// Type: Output Count PSBT_GLOBAL_OUTPUT_COUNT = 0x05
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+/// Type: Transaction Modifiable Flags PSBT_GLOBAL_TX_MODIFIABLE = 0x06
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
+/// Type: Version Number PSBT_GLOBAL_VERSION = 0xFB
+const PSBT_GLOBAL_VERSION: u8 = 0xFB;
+/// Type: Previous TXID PSBT_IN_PREVIOUS_TXID = 0x0e
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+/// Type: Spent Output Index PSBT_IN_OUTPUT_INDEX = 0x0f
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+/// Type: Sequence Number PSBT_IN_SEQUENCE = 0x10
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+/// Type: Required Time-based Locktime PSBT_IN_REQUIRED_TIME_LOCKTIME = 0x11
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+/// Type: Required Height-based Locktime PSBT_IN_REQUIRED_HEIGHT_LOCKTIME = 0x12
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
+/// Type: Output Amount PSBT_OUT_AMOUNT = 0x03
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+/// Type: Output Script PSBT_OUT_SCRIPT = 0x04
+const PSBT_OUT_SCRIPT: u8 = 0x04;
+
+impl Psbt {
+    /// Deserializes a PSBT of version 0 or version 2.
+    ///
+    /// A version 2 PSBT is converted to version 0, its lock time determined from the required
+    /// lock times of the inputs as BIP 370 describes. The transaction modifiable flags are not
+    /// kept, a version 0 PSBT can not be modified.
+    ///
+    /// # Errors
+    ///
+    /// [`DecodeAnyError::UnsupportedVersion`] with the decoded global fields if a version 2 PSBT
+    /// lacks the fields needed to build the transaction. [`DecodeAnyError::Decode`] if the PSBT
+    /// is invalid or of another version.
+    pub fn deserialize_any_version(bytes: &[u8]) -> Result<Psbt, DecodeAnyError> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC_BYTES || bytes[4] != PSBT_SERPARATOR {
+            return Ok(Psbt::deserialize(bytes)?);
+        }
+        let mut r = &bytes[5..];
+        let mut global = read_map(&mut r)?;
+        let version = match take::<u32>(&mut global, PSBT_GLOBAL_VERSION)? {
+            Some(2) => 2,
+            _ => return Ok(Psbt::deserialize(bytes)?),
+        };
+        if global.iter().any(|pair| pair.key.type_value == PSBT_GLOBAL_UNSIGNED_TX) {
+            return Err(Error::Version("version 2 PSBTs have no unsigned transaction").into());
+        }
+
+        let tx_version = take(&mut global, PSBT_GLOBAL_TX_VERSION)?;
+        let input_count = take::<VarInt>(&mut global, PSBT_GLOBAL_INPUT_COUNT)?;
+        let output_count = take::<VarInt>(&mut global, PSBT_GLOBAL_OUTPUT_COUNT)?;
+        let (tx_version, input_count, output_count) = match (tx_version, input_count, output_count)
+        {
+            (Some(tx_version), Some(VarInt(inputs)), Some(VarInt(outputs))) =>
+                (tx_version, inputs as usize, outputs as usize),
+            _ =>
+                return Err(Error::Version(
+                    "version 2 PSBT without version or input and output counts",
+                )
+                .into()),
+        };
+        let fallback_lock_time = take(&mut global, PSBT_GLOBAL_FALLBACK_LOCKTIME)?;
+        let tx_modifiable = take::<u8>(&mut global, PSBT_GLOBAL_TX_MODIFIABLE)?.unwrap_or(0);
+
+        let mut inputs = vec![];
+        for _ in 0..input_count {
+            inputs.push(read_map(&mut r)?);
+        }
+        let mut outputs = vec![];
+        for _ in 0..output_count {
+            outputs.push(read_map(&mut r)?);
+        }
+
+        let mut txins = vec![];
+        let mut required = vec![];
+        for (input_index, map) in inputs.iter_mut().enumerate() {
+            let txid = take::<Txid>(map, PSBT_IN_PREVIOUS_TXID)?;
+            let vout = take::<u32>(map, PSBT_IN_OUTPUT_INDEX)?;
+            let sequence = take::<Sequence>(map, PSBT_IN_SEQUENCE)?.unwrap_or(Sequence::MAX);
+            let time = take::<u32>(map, PSBT_IN_REQUIRED_TIME_LOCKTIME)?;
+            let height = take::<u32>(map, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)?;
+            if time.map_or(false, |time| time < absolute::LOCK_TIME_THRESHOLD)
+                || height.map_or(false, |height| height >= absolute::LOCK_TIME_THRESHOLD)
+            {
+                return Err(Error::Version("invalid required lock time").into());
+            }
+            required.push((time, height));
+            txins.push(match (txid, vout) {
+                (Some(txid), Some(vout)) => Ok(TxIn {
+                    previous_output: OutPoint { txid, vout },
+                    sequence,
+                    ..Default::default()
+                }),
+                (None, _) => Err(ConvertV2Error::MissingPreviousTxid { input_index }),
+                (Some(_), None) => Err(ConvertV2Error::MissingOutputIndex { input_index }),
+            });
+        }
+        let mut txouts = vec![];
+        for (output_index, map) in outputs.iter_mut().enumerate() {
+            let amount = take::<i64>(map, PSBT_OUT_AMOUNT)?;
+            let script = take_raw(map, PSBT_OUT_SCRIPT)?.map(ScriptBuf::from_bytes);
+            txouts.push(match (amount, script) {
+                (Some(amount), Some(script_pubkey)) => match u64::try_from(amount) {
+                    Ok(amount) => Ok(TxOut { value: Amount::from_sat(amount), script_pubkey }),
+                    Err(_) => return Err(Error::Version("negative output amount").into()),
+                },
+                (None, _) => Err(ConvertV2Error::MissingAmount { output_index }),
+                (Some(_), None) => Err(ConvertV2Error::MissingScript { output_index }),
+            });
+        }
+
+        let tx = txins.into_iter().collect::<Result<Vec<_>, _>>().and_then(|input| {
+            let output = txouts.into_iter().collect::<Result<Vec<_>, _>>()?;
+            let lock_time = lock_time(&required, fallback_lock_time)?;
+            Ok(Transaction { version: tx_version, lock_time, input, output })
+        });
+        let tx = match tx {
+            Ok(tx) => tx,
+            Err(reason) => {
+                let decoded = decode_global(
+                    &global,
+                    Transaction {
+                        version: tx_version,
+                        lock_time: absolute::LockTime::ZERO,
+                        // Transactions without inputs do not round trip.
+                        input: vec![TxIn::default()],
+                        output: vec![],
+                    },
+                )?;
+                let globals = GlobalsV2 {
+                    version,
+                    tx_version,
+                    fallback_lock_time,
+                    input_count,
+                    output_count,
+                    tx_modifiable,
+                    xpub: decoded.xpub,
+                    proprietary: decoded.proprietary,
+                    unknown: decoded.unknown,
+                };
+                return Err(DecodeAnyError::UnsupportedVersion {
+                    globals: Box::new(globals),
+                    reason,
+                });
+            }
+        };
+
+        // The remaining fields are decoded by the version 0 decoder.
+        let mut v0 = MAGIC_BYTES.to_vec();
+        v0.push(PSBT_SERPARATOR);
+        write_map(&mut v0, &global, Some(&tx));
+        for map in inputs.iter().chain(&outputs) {
+            write_map(&mut v0, map, None);
+        }
+        Ok(Psbt::deserialize(&v0)?)
+    }
+}
+
+/// A version 2 PSBT, as described in BIP 370.
+///
+/// Unlike a version 0 [`Psbt`] there is no unsigned transaction, every input and output carries
+/// its own part of the transaction. Inputs and outputs can be added while the modifiable flags
+/// allow it, the transaction is only fixed when the PSBT is converted to version 0 with
+/// [`PsbtV2::into_v0`].
+///
+/// ```
+/// # use psbt_v0::bitcoin::{transaction, Amount, OutPoint, ScriptBuf};
+/// # use psbt_v0::{InputV2, OutputV2, PsbtV2};
+/// let mut psbt = PsbtV2::new(transaction::Version::TWO);
+/// psbt.add_input(InputV2::new(OutPoint::null())).expect("inputs are modifiable");
+/// psbt.add_output(OutputV2::new(Amount::from_sat(1_000), ScriptBuf::new()))
+///     .expect("outputs are modifiable");
+/// let psbt = psbt.into_v0().expect("no lock time conflict");
+/// assert_eq!(psbt.unsigned_tx.input.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsbtV2 {
+    /// The version of the transaction.
+    pub tx_version: transaction::Version,
+    /// The lock time used if no input requires one.
+    pub fallback_lock_time: Option<absolute::LockTime>,
+    /// The transaction modifiable flags, see [`PsbtV2::INPUTS_MODIFIABLE`] and
+    /// [`PsbtV2::OUTPUTS_MODIFIABLE`].
+    pub tx_modifiable: u8,
+    /// The global xpubs.
+    pub xpub: BTreeMap<Xpub, KeySource>,
+    /// Global proprietary key-value pairs.
+    pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
+    /// Unknown global key-value pairs.
+    pub unknown: BTreeMap<raw::Key, Vec<u8>>,
+    /// The inputs.
+    pub inputs: Vec<InputV2>,
+    /// The outputs.
+    pub outputs: Vec<OutputV2>,
+}
+
+impl PsbtV2 {
+    /// The flag allowing inputs to be added or removed.
+    pub const INPUTS_MODIFIABLE: u8 = 1 << 0;
+    /// The flag allowing outputs to be added or removed.
+    pub const OUTPUTS_MODIFIABLE: u8 = 1 << 1;
+    /// The flag recording that a signature with `SIGHASH_SINGLE` has been added.
+    pub const HAS_SIGHASH_SINGLE: u8 = 1 << 2;
+
+    /// Creates an empty PSBT for a transaction of version `tx_version`, with modifiable inputs
+    /// and outputs.
+    pub fn new(tx_version: transaction::Version) -> Self {
+        PsbtV2 {
+            tx_version,
+            fallback_lock_time: None,
+            tx_modifiable: Self::INPUTS_MODIFIABLE | Self::OUTPUTS_MODIFIABLE,
+            xpub: BTreeMap::new(),
+            proprietary: BTreeMap::new(),
+            unknown: BTreeMap::new(),
+            inputs: vec![],
+            outputs: vec![],
+        }
+    }
+
+    /// Converts the version 0 `psbt`, the transaction is not modifiable.
+    ///
+    /// # Errors
+    ///
+    /// If the number of input or output maps does not match the unsigned transaction.
+    pub fn from_v0(psbt: Psbt) -> Result<Self, ConvertV0Error> {
+        let tx = psbt.unsigned_tx;
+        if psbt.inputs.len() != tx.input.len() {
+            return Err(ConvertV0Error::InputCountMismatch {
+                txins: tx.input.len(),
+                maps: psbt.inputs.len(),
+            });
+        }
+        if psbt.outputs.len() != tx.output.len() {
+            return Err(ConvertV0Error::OutputCountMismatch {
+                txouts: tx.output.len(),
+                maps: psbt.outputs.len(),
+            });
+        }
+        let inputs = tx
+            .input
+            .into_iter()
+            .zip(psbt.inputs)
+            .map(|(txin, map)| InputV2 {
+                previous_output: txin.previous_output,
+                sequence: Some(txin.sequence).filter(|sequence| *sequence != Sequence::MAX),
+                required_time_lock_time: None,
+                required_height_lock_time: None,
+                map,
+            })
+            .collect();
+        let outputs = tx
+            .output
+            .into_iter()
+            .zip(psbt.outputs)
+            .map(|(txout, map)| OutputV2 {
+                amount: txout.value,
+                script_pubkey: txout.script_pubkey,
+                map,
+            })
+            .collect();
+        Ok(PsbtV2 {
+            tx_version: tx.version,
+            fallback_lock_time: Some(tx.lock_time)
+                .filter(|lock_time| *lock_time != absolute::LockTime::ZERO),
+            tx_modifiable: 0,
+            xpub: psbt.xpub,
+            proprietary: psbt.proprietary,
+            unknown: psbt.unknown,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Converts this PSBT to version 0, its lock time determined from the required lock times of
+    /// the inputs as BIP 370 describes.
+    ///
+    /// # Errors
+    ///
+    /// [`ConvertV2Error::LockTimeConflict`] if some inputs require a time based lock time and
+    /// others a height based one.
+    pub fn into_v0(self) -> Result<Psbt, ConvertV2Error> {
+        let lock_time = self.lock_time()?;
+        let (input, inputs) = self
+            .inputs
+            .into_iter()
+            .map(|input| {
+                let txin = TxIn {
+                    previous_output: input.previous_output,
+                    sequence: input.sequence.unwrap_or(Sequence::MAX),
+                    ..Default::default()
+                };
+                (txin, input.map)
+            })
+            .unzip();
+        let (output, outputs) = self
+            .outputs
+            .into_iter()
+            .map(|output| {
+                let txout = TxOut { value: output.amount, script_pubkey: output.script_pubkey };
+                (txout, output.map)
+            })
+            .unzip();
+        Ok(Psbt {
+            unsigned_tx: Transaction { version: self.tx_version, lock_time, input, output },
+            version: 0,
+            xpub: self.xpub,
+            proprietary: self.proprietary,
+            unknown: self.unknown,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Returns the lock time of the transaction as BIP 370 describes.
+    ///
+    /// # Errors
+    ///
+    /// [`ConvertV2Error::LockTimeConflict`] if some inputs require a time based lock time and
+    /// others a height based one.
+    pub fn lock_time(&self) -> Result<absolute::LockTime, ConvertV2Error> {
+        let required = self.inputs.iter().map(InputV2::required_lock_times).collect::<Vec<_>>();
+        lock_time(&required, self.fallback_lock_time)
+    }
+
+    /// Adds `input` to the end of the inputs.
+    ///
+    /// # Errors
+    ///
+    /// If the inputs are not modifiable or the lock time `input` requires conflicts with the
+    /// other inputs. The PSBT is not modified on error.
+    pub fn add_input(&mut self, input: InputV2) -> Result<(), ModifyError> {
+        if self.tx_modifiable & Self::INPUTS_MODIFIABLE == 0 {
+            return Err(ModifyError::InputsNotModifiable);
+        }
+        let mut required = self.inputs.iter().map(InputV2::required_lock_times).collect::<Vec<_>>();
+        required.push(input.required_lock_times());
+        lock_time(&required, self.fallback_lock_time).map_err(|_| ModifyError::LockTimeConflict)?;
+        self.inputs.push(input);
+        Ok(())
+    }
+
+    /// Adds `output` to the end of the outputs.
+    ///
+    /// # Errors
+    ///
+    /// If the outputs are not modifiable.
+    pub fn add_output(&mut self, output: OutputV2) -> Result<(), ModifyError> {
+        if self.tx_modifiable & Self::OUTPUTS_MODIFIABLE == 0 {
+            return Err(ModifyError::OutputsNotModifiable);
+        }
+        self.outputs.push(output);
+        Ok(())
+    }
+
+    /// Serializes this PSBT as a version 2 PSBT.
+    pub fn serialize(&self) -> Vec<u8> {
+        let global = Psbt {
+            unsigned_tx: Transaction {
+                version: self.tx_version,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            },
+            version: 0,
+            xpub: self.xpub.clone(),
+            proprietary: self.proprietary.clone(),
+            unknown: self.unknown.clone(),
+            inputs: vec![],
+            outputs: vec![],
+        };
+        // The unsigned transaction is replaced by the version 2 fields.
+        let mut pairs = global.get_pairs();
+        pairs.retain(|pair| pair.key.type_value != PSBT_GLOBAL_UNSIGNED_TX);
+        pairs.push(pair(PSBT_GLOBAL_VERSION, encode::serialize(&2u32)));
+        pairs.push(pair(PSBT_GLOBAL_TX_VERSION, encode::serialize(&self.tx_version)));
+        if let Some(lock_time) = self.fallback_lock_time {
+            pairs.push(pair(PSBT_GLOBAL_FALLBACK_LOCKTIME, encode::serialize(&lock_time)));
+        }
+        pairs.push(pair(
+            PSBT_GLOBAL_INPUT_COUNT,
+            encode::serialize(&VarInt::from(self.inputs.len())),
+        ));
+        pairs.push(pair(
+            PSBT_GLOBAL_OUTPUT_COUNT,
+            encode::serialize(&VarInt::from(self.outputs.len())),
+        ));
+        if self.tx_modifiable != 0 {
+            pairs.push(pair(PSBT_GLOBAL_TX_MODIFIABLE, vec![self.tx_modifiable]));
+        }
+
+        let mut buf = MAGIC_BYTES.to_vec();
+        buf.push(PSBT_SERPARATOR);
+        write_sorted_map(&mut buf, pairs);
+        for input in &self.inputs {
+            write_sorted_map(&mut buf, input.pairs());
+        }
+        for output in &self.outputs {
+            write_sorted_map(&mut buf, output.pairs());
+        }
+        buf
+    }
+
+    /// Deserializes a version 2 PSBT.
+    ///
+    /// # Errors
+    ///
+    /// If the PSBT is invalid, of another version or lacks a field BIP 370 requires.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC_BYTES {
+            return Err(Error::InvalidMagic);
+        }
+        if bytes[4] != PSBT_SERPARATOR {
+            return Err(Error::InvalidSeparator);
+        }
+        let mut r = &bytes[5..];
+        let mut global = read_map(&mut r)?;
+        if take::<u32>(&mut global, PSBT_GLOBAL_VERSION)? != Some(2) {
+            return Err(Error::Version("not a version 2 PSBT"));
+        }
+        if global.iter().any(|pair| pair.key.type_value == PSBT_GLOBAL_UNSIGNED_TX) {
+            return Err(Error::Version("version 2 PSBTs have no unsigned transaction"));
+        }
+        let tx_version = take(&mut global, PSBT_GLOBAL_TX_VERSION)?;
+        let input_count = take::<VarInt>(&mut global, PSBT_GLOBAL_INPUT_COUNT)?;
+        let output_count = take::<VarInt>(&mut global, PSBT_GLOBAL_OUTPUT_COUNT)?;
+        let (tx_version, input_count, output_count) = match (tx_version, input_count, output_count)
+        {
+            (Some(tx_version), Some(VarInt(inputs)), Some(VarInt(outputs))) =>
+                (tx_version, inputs, outputs),
+            _ =>
+                return Err(Error::Version(
+                    "version 2 PSBT without version or input and output counts",
+                )),
+        };
+        let fallback_lock_time = take(&mut global, PSBT_GLOBAL_FALLBACK_LOCKTIME)?;
+        let tx_modifiable = take::<u8>(&mut global, PSBT_GLOBAL_TX_MODIFIABLE)?.unwrap_or(0);
+        let decoded = decode_global(
+            &global,
+            Transaction {
+                version: tx_version,
+                lock_time: absolute::LockTime::ZERO,
+                // Transactions without inputs do not round trip.
+                input: vec![TxIn::default()],
+                output: vec![],
+            },
+        )?;
+
+        let mut inputs = vec![];
+        for index in 0..input_count {
+            inputs.push(InputV2::decode(&mut r, index as usize)?);
+        }
+        let mut outputs = vec![];
+        for index in 0..output_count {
+            outputs.push(OutputV2::decode(&mut r, index as usize)?);
+        }
+        Ok(PsbtV2 {
+            tx_version,
+            fallback_lock_time,
+            tx_modifiable,
+            xpub: decoded.xpub,
+            proprietary: decoded.proprietary,
+            unknown: decoded.unknown,
+            inputs,
+            outputs,
+        })
+    }
+}
+
+impl TryFrom<Psbt> for PsbtV2 {
+    type Error = ConvertV0Error;
+
+    fn try_from(psbt: Psbt) -> Result<Self, Self::Error> { PsbtV2::from_v0(psbt) }
+}
+
+impl TryFrom<PsbtV2> for Psbt {
+    type Error = ConvertV2Error;
+
+    fn try_from(psbt: PsbtV2) -> Result<Self, Self::Error> { psbt.into_v0() }
+}
+
+/// An input of a version 2 PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputV2 {
+    /// The output this input spends.
+    pub previous_output: OutPoint,
+    /// The sequence number, `0xffffffff` if `None`.
+    pub sequence: Option<Sequence>,
+    /// The minimum time based lock time this input requires.
+    pub required_time_lock_time: Option<absolute::Time>,
+    /// The minimum height based lock time this input requires.
+    pub required_height_lock_time: Option<absolute::Height>,
+    /// The fields this input has in common with a version 0 input.
+    pub map: Input,
+}
+
+impl InputV2 {
+    /// Creates an input spending `previous_output` with no other fields.
+    pub fn new(previous_output: OutPoint) -> Self {
+        InputV2 {
+            previous_output,
+            sequence: None,
+            required_time_lock_time: None,
+            required_height_lock_time: None,
+            map: Input::default(),
+        }
+    }
+
+    fn required_lock_times(&self) -> (Option<u32>, Option<u32>) {
+        (
+            self.required_time_lock_time.map(absolute::Time::to_consensus_u32),
+            self.required_height_lock_time.map(absolute::Height::to_consensus_u32),
+        )
+    }
+
+    fn pairs(&self) -> Vec<raw::Pair> {
+        let mut pairs = self.map.get_pairs();
+        pairs.push(pair(PSBT_IN_PREVIOUS_TXID, encode::serialize(&self.previous_output.txid)));
+        pairs.push(pair(PSBT_IN_OUTPUT_INDEX, encode::serialize(&self.previous_output.vout)));
+        if let Some(sequence) = self.sequence {
+            pairs.push(pair(PSBT_IN_SEQUENCE, encode::serialize(&sequence)));
+        }
+        if let Some(time) = self.required_time_lock_time {
+            pairs.push(pair(
+                PSBT_IN_REQUIRED_TIME_LOCKTIME,
+                encode::serialize(&time.to_consensus_u32()),
+            ));
+        }
+        if let Some(height) = self.required_height_lock_time {
+            pairs.push(pair(
+                PSBT_IN_REQUIRED_HEIGHT_LOCKTIME,
+                encode::serialize(&height.to_consensus_u32()),
+            ));
+        }
+        pairs
+    }
+
+    fn decode(r: &mut &[u8], index: usize) -> Result<Self, Error> {
+        let mut map = read_map(r)?;
+        let txid = take::<Txid>(&mut map, PSBT_IN_PREVIOUS_TXID)?;
+        let vout = take::<u32>(&mut map, PSBT_IN_OUTPUT_INDEX)?;
+        let sequence = take::<Sequence>(&mut map, PSBT_IN_SEQUENCE)?;
+        let time = take::<u32>(&mut map, PSBT_IN_REQUIRED_TIME_LOCKTIME)?
+            .map(absolute::Time::from_consensus)
+            .transpose()
+            .map_err(|_| Error::Version("invalid required lock time"))?;
+        let height = take::<u32>(&mut map, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)?
+            .map(absolute::Height::from_consensus)
+            .transpose()
+            .map_err(|_| Error::Version("invalid required lock time"))?;
+        let previous_output = match (txid, vout) {
+            (Some(txid), Some(vout)) => OutPoint { txid, vout },
+            _ => return Err(Error::Version("version 2 PSBT input without previous output")),
+        };
+
+        let mut buf = vec![];
+        write_map(&mut buf, &map, None);
+        let location = MapLocation::Input(index);
+        let map = Input::decode(&mut buf.as_slice(), location, &DeserializeOptions::UNLIMITED)?;
+        Ok(InputV2 {
+            previous_output,
+            sequence,
+            required_time_lock_time: time,
+            required_height_lock_time: height,
+            map,
+        })
+    }
+}
+
+/// An output of a version 2 PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputV2 {
+    /// The amount of the output.
+    pub amount: Amount,
+    /// The script pubkey of the output.
+    pub script_pubkey: ScriptBuf,
+    /// The fields this output has in common with a version 0 output.
+    pub map: Output,
+}
+
+impl OutputV2 {
+    /// Creates an output paying `amount` to `script_pubkey` with no other fields.
+    pub fn new(amount: Amount, script_pubkey: ScriptBuf) -> Self {
+        OutputV2 { amount, script_pubkey, map: Output::default() }
+    }
+
+    fn pairs(&self) -> Vec<raw::Pair> {
+        let mut pairs = self.map.get_pairs();
+        pairs.push(pair(PSBT_OUT_AMOUNT, encode::serialize(&self.amount)));
+        pairs.push(pair(PSBT_OUT_SCRIPT, self.script_pubkey.to_bytes()));
+        pairs
+    }
+
+    fn decode(r: &mut &[u8], index: usize) -> Result<Self, Error> {
+        let mut map = read_map(r)?;
+        let amount = take::<i64>(&mut map, PSBT_OUT_AMOUNT)?;
+        let script = take_raw(&mut map, PSBT_OUT_SCRIPT)?.map(ScriptBuf::from_bytes);
+        let (amount, script_pubkey) = match (amount, script) {
+            (Some(amount), Some(script_pubkey)) => match u64::try_from(amount) {
+                Ok(amount) => (Amount::from_sat(amount), script_pubkey),
+                Err(_) => return Err(Error::Version("negative output amount")),
+            },
+            _ => return Err(Error::Version("version 2 PSBT output without amount or script")),
+        };
+
+        let mut buf = vec![];
+        write_map(&mut buf, &map, None);
+        let location = MapLocation::Output(index);
+        let map = Output::decode(&mut buf.as_slice(), location, &DeserializeOptions::UNLIMITED)?;
+        Ok(OutputV2 { amount, script_pubkey, map })
+    }
+}
+
+/// A PSBT of either version 0 or version 2.
+///
+/// Lets callers accept both versions without converting a version 2 PSBT, keeping its inputs
+/// and outputs modifiable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionedPsbt {
+    /// A version 0 PSBT.
+    V0(Psbt),
+    /// A version 2 PSBT.
+    V2(PsbtV2),
+}
+
+impl VersionedPsbt {
+    /// Deserializes a PSBT of version 0 or version 2.
+    ///
+    /// # Errors
+    ///
+    /// If the PSBT is invalid or of another version.
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let mut r = bytes.get(5..).unwrap_or_default();
+        let version = match read_map(&mut r) {
+            Ok(mut global) => take::<u32>(&mut global, PSBT_GLOBAL_VERSION).ok().flatten(),
+            Err(_) => None,
+        };
+        match version {
+            Some(2) => Ok(VersionedPsbt::V2(PsbtV2::deserialize(bytes)?)),
+            _ => Ok(VersionedPsbt::V0(Psbt::deserialize(bytes)?)),
+        }
+    }
+
+    /// Serializes the PSBT in its version.
+    pub fn serialize(&self) -> Vec<u8> {
+        match *self {
+            VersionedPsbt::V0(ref psbt) => psbt.serialize(),
+            VersionedPsbt::V2(ref psbt) => psbt.serialize(),
+        }
+    }
+
+    /// Returns the PSBT as version 0, see [`PsbtV2::into_v0`].
+    ///
+    /// # Errors
+    ///
+    /// If a version 2 PSBT can not be converted.
+    pub fn into_v0(self) -> Result<Psbt, ConvertV2Error> {
+        match self {
+            VersionedPsbt::V0(psbt) => Ok(psbt),
+            VersionedPsbt::V2(psbt) => psbt.into_v0(),
+        }
+    }
+
+    /// Returns the PSBT as version 2, see [`PsbtV2::from_v0`].
+    ///
+    /// # Errors
+    ///
+    /// If a version 0 PSBT can not be converted.
+    pub fn into_v2(self) -> Result<PsbtV2, ConvertV0Error> {
+        match self {
+            VersionedPsbt::V0(psbt) => PsbtV2::from_v0(psbt),
+            VersionedPsbt::V2(psbt) => Ok(psbt),
+        }
+    }
+}
+
+impl From<Psbt> for VersionedPsbt {
+    fn from(psbt: Psbt) -> Self { VersionedPsbt::V0(psbt) }
+}
+
+impl From<PsbtV2> for VersionedPsbt {
+    fn from(psbt: PsbtV2) -> Self { VersionedPsbt::V2(psbt) }
+}
+
+/// Returns the lock time of a version 2 PSBT whose inputs require the (time, height) lock times
+/// in `required`.
+fn lock_time(
+    required: &[(Option<u32>, Option<u32>)],
+    fallback: Option<absolute::LockTime>,
+) -> Result<absolute::LockTime, ConvertV2Error> {
+    let required = required.iter().filter(|(time, height)| time.is_some() || height.is_some());
+    if required.clone().next().is_none() {
+        return Ok(fallback.unwrap_or(absolute::LockTime::ZERO));
+    }
+    // Height based lock times are preferred when every input allows both.
+    if let Some(heights) = required.clone().map(|(_, height)| *height).collect::<Option<Vec<_>>>() {
+        let height = heights.into_iter().max().expect("at least one input requires a lock time");
+        return Ok(absolute::LockTime::from_consensus(height));
+    }
+    if let Some(times) = required.map(|(time, _)| *time).collect::<Option<Vec<_>>>() {
+        let time = times.into_iter().max().expect("at least one input requires a lock time");
+        return Ok(absolute::LockTime::from_consensus(time));
+    }
+    Err(ConvertV2Error::LockTimeConflict)
+}
+
+/// Reads the pairs of a map up to its separator.
+fn read_map(r: &mut &[u8]) -> Result<Vec<raw::Pair>, Error> {
+    let mut pairs = vec![];
+    loop {
+        match raw::Pair::decode(r, &DeserializeOptions::UNLIMITED) {
+            Ok(pair) => pairs.push(pair),
+            Err(Error::NoMorePairs) => return Ok(pairs),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Writes `map`, preceded by the unsigned transaction `tx` if given, and its separator.
+fn write_map(buf: &mut Vec<u8>, map: &[raw::Pair], tx: Option<&Transaction>) {
+    if let Some(tx) = tx {
+        let key = raw::Key::new(PSBT_GLOBAL_UNSIGNED_TX);
+        buf.extend(raw::Pair { key, value: encode::serialize(tx) }.serialize());
+    }
+    for pair in map {
+        buf.extend(pair.serialize());
+    }
+    buf.push(0x00);
+}
+
+/// Writes the pairs of `map`, sorted by key, and its separator.
+fn write_sorted_map(buf: &mut Vec<u8>, mut map: Vec<raw::Pair>) {
+    map.sort_by(|a, b| a.key.cmp(&b.key));
+    write_map(buf, &map, None);
+}
+
+fn pair(type_value: u8, value: impl Into<Vec<u8>>) -> raw::Pair {
+    raw::Pair { key: raw::Key::new(type_value), value: value.into() }
+}
+
+/// Decodes the global fields of `map` known to version 0, with `tx` as unsigned transaction.
+fn decode_global(map: &[raw::Pair], tx: Transaction) -> Result<Psbt, Error> {
+    let mut buf = vec![];
+    write_map(&mut buf, map, Some(&tx));
+    Psbt::decode_global(&mut buf.as_slice(), &DeserializeOptions::UNLIMITED)
+}
+
+/// Removes the field with key type `type_value` from `map` and decodes its value.
+fn take<T: Decodable>(map: &mut Vec<raw::Pair>, type_value: u8) -> Result<Option<T>, Error> {
+    match take_raw(map, type_value)? {
+        Some(value) => Ok(Some(encode::deserialize(&value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Removes the field with key type `type_value` from `map` and returns its value.
+fn take_raw(map: &mut Vec<raw::Pair>, type_value: u8) -> Result<Option<Vec<u8>>, Error> {
+    let mut value = None;
+    for pair in core::mem::take(map) {
+        if pair.key.type_value != type_value {
+            map.push(pair);
+        } else if !pair.key.key_data.is_empty() {
+            return Err(Error::InvalidKey(pair.key));
+        } else if value.is_some() {
+            return Err(Error::DuplicateKey(pair.key));
+        } else {
+            value = Some(pair.value);
+        }
+    }
+    Ok(value)
+}
+
+/// The global fields of a version 2 PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalsV2 {
+    /// The PSBT version, 2.
+    pub version: u32,
+    /// The version of the transaction.
+    pub tx_version: transaction::Version,
+    /// The lock time used if no input requires one.
+    pub fallback_lock_time: Option<absolute::LockTime>,
+    /// The number of inputs.
+    pub input_count: usize,
+    /// The number of outputs.
+    pub output_count: usize,
+    /// The transaction modifiable flags.
+    pub tx_modifiable: u8,
+    /// The global xpubs.
+    pub xpub: BTreeMap<Xpub, KeySource>,
+    /// Global proprietary key-value pairs.
+    pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
+    /// Unknown global key-value pairs.
+    pub unknown: BTreeMap<raw::Key, Vec<u8>>,
+}
+
+/// Error returned by [`Psbt::deserialize_any_version`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecodeAnyError {
+    /// The PSBT is invalid or of a version other than 0 and 2.
+    Decode(Error),
+    /// The version 2 PSBT can not be converted to version 0.
+    UnsupportedVersion {
+        /// The global fields of the PSBT.
+        globals: Box<GlobalsV2>,
+        /// Why the PSBT can not be converted.
+        reason: ConvertV2Error,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(DecodeAnyError);
+
+impl fmt::Display for DecodeAnyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DecodeAnyError::*;
+
+        match *self {
+            Decode(ref e) => write_err!(f, "can not decode the PSBT"; e),
+            UnsupportedVersion { ref globals, ref reason } => write_err!(
+                f,
+                "the version {} PSBT can not be converted to version 0",
+                globals.version;
+                reason
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeAnyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use DecodeAnyError::*;
+
+        match *self {
+            Decode(ref e) => Some(e),
+            UnsupportedVersion { ref reason, .. } => Some(reason),
+        }
+    }
+}
+
+impl From<Error> for DecodeAnyError {
+    fn from(e: Error) -> Self { Self::Decode(e) }
+}
+
+/// The reason a version 2 PSBT can not be converted to version 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConvertV2Error {
+    /// The input has no previous txid.
+    MissingPreviousTxid {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The input has no spent output index.
+    MissingOutputIndex {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The output has no amount.
+    MissingAmount {
+        /// The index of the output.
+        output_index: usize,
+    },
+    /// The output has no script.
+    MissingScript {
+        /// The index of the output.
+        output_index: usize,
+    },
+    /// Some inputs require a time based lock time and others a height based one.
+    LockTimeConflict,
+}
+
+bitcoin_internals::impl_from_infallible!(ConvertV2Error);
+
+impl fmt::Display for ConvertV2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConvertV2Error::*;
+
+        match *self {
+            MissingPreviousTxid { input_index } => {
+                write!(f, "input {} has no previous txid", input_index)
+            }
+            MissingOutputIndex { input_index } => {
+                write!(f, "input {} has no spent output index", input_index)
+            }
+            MissingAmount { output_index } => write!(f, "output {} has no amount", output_index),
+            MissingScript { output_index } => write!(f, "output {} has no script", output_index),
+            LockTimeConflict =>
+                f.write_str("the inputs require both time and height based lock times"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConvertV2Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ConvertV2Error::*;
+
+        match *self {
+            MissingPreviousTxid { .. }
+            | MissingOutputIndex { .. }
+            | MissingAmount { .. }
+            | MissingScript { .. }
+            | LockTimeConflict => None,
+        }
+    }
+}
+
+/// The reason a version 0 PSBT can not be converted to version 2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConvertV0Error {
+    /// The number of input maps does not match the number of unsigned transaction inputs.
+    InputCountMismatch {
+        /// The number of unsigned transaction inputs.
+        txins: usize,
+        /// The number of input maps.
+        maps: usize,
+    },
+    /// The number of output maps does not match the number of unsigned transaction outputs.
+    OutputCountMismatch {
+        /// The number of unsigned transaction outputs.
+        txouts: usize,
+        /// The number of output maps.
+        maps: usize,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(ConvertV0Error);
+
+impl fmt::Display for ConvertV0Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ConvertV0Error::*;
+
+        match *self {
+            InputCountMismatch { txins, maps } =>
+                write!(f, "the transaction has {} inputs but there are {} input maps", txins, maps),
+            OutputCountMismatch { txouts, maps } => write!(
+                f,
+                "the transaction has {} outputs but there are {} output maps",
+                txouts, maps
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConvertV0Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ConvertV0Error::*;
+
+        match *self {
+            InputCountMismatch { .. } | OutputCountMismatch { .. } => None,
+        }
+    }
+}
+
+/// Error adding an input or output to a [`PsbtV2`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ModifyError {
+    /// The modifiable flags do not allow adding inputs.
+    InputsNotModifiable,
+    /// The modifiable flags do not allow adding outputs.
+    OutputsNotModifiable,
+    /// The input requires a lock time that conflicts with the other inputs.
+    LockTimeConflict,
+}
+
+bitcoin_internals::impl_from_infallible!(ModifyError);
+
+impl fmt::Display for ModifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ModifyError::*;
+
+        match *self {
+            InputsNotModifiable => f.write_str("the inputs of the PSBT are not modifiable"),
+            OutputsNotModifiable => f.write_str("the outputs of the PSBT are not modifiable"),
+            LockTimeConflict =>
+                f.write_str("the input requires a lock time that conflicts with the other inputs"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ModifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ModifyError::*;
+
+        match *self {
+            InputsNotModifiable | OutputsNotModifiable | LockTimeConflict => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::WPubkeyHash;
+
+    use super::*;
+
+    fn serialize_v2(
+        global: &[raw::Pair],
+        inputs: &[Vec<raw::Pair>],
+        outputs: &[Vec<raw::Pair>],
+    ) -> Vec<u8> {
+        let mut buf = b"psbt\xff".to_vec();
+        write_map(&mut buf, global, None);
+        for map in inputs.iter().chain(outputs) {
+            write_map(&mut buf, map, None);
+        }
+        buf
+    }
+
+    #[test]
+    fn deserialize_any_version() {
+        let utxo = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+        let txid = Txid::from_byte_array([1; 32]);
+        let global = vec![
+            pair(PSBT_GLOBAL_VERSION, 2u32.to_le_bytes()),
+            pair(PSBT_GLOBAL_TX_VERSION, 2i32.to_le_bytes()),
+            pair(PSBT_GLOBAL_INPUT_COUNT, [2]),
+            pair(PSBT_GLOBAL_OUTPUT_COUNT, [1]),
+            pair(PSBT_GLOBAL_TX_MODIFIABLE, [0]),
+            pair(0xf0, [7]),
+        ];
+        let input = |vout: u32, height: u32| {
+            vec![
+                pair(0x01, encode::serialize(&utxo)),
+                pair(PSBT_IN_PREVIOUS_TXID, encode::serialize(&txid)),
+                pair(PSBT_IN_OUTPUT_INDEX, vout.to_le_bytes()),
+                pair(PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, height.to_le_bytes()),
+            ]
+        };
+        let inputs = vec![input(0, 800_000), input(1, 800_001)];
+        let mut outputs = vec![vec![
+            pair(PSBT_OUT_AMOUNT, 9_000i64.to_le_bytes()),
+            pair(PSBT_OUT_SCRIPT, utxo.script_pubkey.to_bytes()),
+        ]];
+
+        let psbt =
+            Psbt::deserialize_any_version(&serialize_v2(&global, &inputs, &outputs)).unwrap();
+        assert_eq!(psbt.version, 0);
+        assert_eq!(psbt.unsigned_tx.version, transaction::Version::TWO);
+        assert_eq!(psbt.unsigned_tx.lock_time, absolute::LockTime::from_consensus(800_001));
+        assert_eq!(psbt.unsigned_tx.input[1].previous_output, OutPoint { txid, vout: 1 });
+        assert_eq!(psbt.unsigned_tx.input[1].sequence, Sequence::MAX);
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(9_000));
+        assert_eq!(psbt.inputs[0].witness_utxo, Some(utxo));
+        assert_eq!(psbt.unknown[&raw::Key::new(0xf0)], vec![7]);
+
+        // Version 0 PSBTs decode as usual.
+        let v0 = psbt.serialize();
+        assert_eq!(Psbt::deserialize_any_version(&v0).unwrap(), psbt);
+
+        outputs[0].pop();
+        match Psbt::deserialize_any_version(&serialize_v2(&global, &inputs, &outputs)) {
+            Err(DecodeAnyError::UnsupportedVersion { globals, reason }) => {
+                assert_eq!(reason, ConvertV2Error::MissingScript { output_index: 0 });
+                assert_eq!((globals.input_count, globals.output_count), (2, 1));
+                assert_eq!(globals.unknown[&raw::Key::new(0xf0)], vec![7]);
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+
+        let required = [(Some(500_000_000), None), (None, Some(800_000))];
+        assert_eq!(lock_time(&required, None), Err(ConvertV2Error::LockTimeConflict));
+        let required = [(Some(500_000_000), Some(1)), (Some(600_000_000), None)];
+        assert_eq!(lock_time(&required, None), Ok(absolute::LockTime::from_consensus(600_000_000)));
+    }
+
+    #[test]
+    fn psbt_v2_roundtrip() {
+        let utxo = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+        let txin = |vout, sequence| TxIn {
+            previous_output: OutPoint { txid: Txid::from_byte_array([1; 32]), vout },
+            sequence,
+            ..Default::default()
+        };
+        let mut v0 = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::from_consensus(800_000),
+            input: vec![txin(0, Sequence::MAX), txin(1, Sequence::ENABLE_RBF_NO_LOCKTIME)],
+            output: vec![TxOut { value: Amount::from_sat(9_000), ..utxo.clone() }],
+        })
+        .unwrap();
+        v0.inputs[1].witness_utxo = Some(utxo);
+        v0.outputs[0].unknown.insert(raw::Key::new(0xf0), vec![7]);
+        v0.unknown.insert(raw::Key::new(0xf0), vec![8]);
+
+        let v2 = PsbtV2::from_v0(v0.clone()).unwrap();
+        assert_eq!(v2.inputs[0].sequence, None);
+        assert_eq!(v2.fallback_lock_time, Some(v0.unsigned_tx.lock_time));
+        assert_eq!(v2.tx_modifiable, 0);
+
+        let bytes = v2.serialize();
+        assert_eq!(PsbtV2::deserialize(&bytes).unwrap(), v2);
+        assert_eq!(Psbt::deserialize_any_version(&bytes).unwrap(), v0);
+        assert_eq!(VersionedPsbt::deserialize(&bytes).unwrap(), VersionedPsbt::V2(v2.clone()));
+        assert_eq!(
+            VersionedPsbt::deserialize(&v0.serialize()).unwrap(),
+            VersionedPsbt::V0(v0.clone())
+        );
+        assert!(matches!(PsbtV2::deserialize(&v0.serialize()), Err(Error::Version(_))));
+        assert_eq!(v2.into_v0().unwrap(), v0);
+
+        v0.inputs.pop();
+        assert_eq!(
+            PsbtV2::from_v0(v0),
+            Err(ConvertV0Error::InputCountMismatch { txins: 2, maps: 1 })
+        );
+    }
+
+    #[test]
+    fn psbt_v2_modify() {
+        let mut psbt = PsbtV2::new(transaction::Version::TWO);
+        psbt.fallback_lock_time = Some(absolute::LockTime::from_consensus(100));
+
+        let mut input = InputV2::new(OutPoint::null());
+        input.required_height_lock_time = Some(absolute::Height::from_consensus(800_000).unwrap());
+        psbt.add_input(input).unwrap();
+        psbt.add_output(OutputV2::new(Amount::from_sat(1_000), ScriptBuf::new())).unwrap();
+        assert_eq!(psbt.lock_time(), Ok(absolute::LockTime::from_consensus(800_000)));
+
+        // A time based lock time conflicts with the first input.
+        let mut input = InputV2::new(OutPoint { vout: 1, ..OutPoint::null() });
+        input.required_time_lock_time = Some(absolute::Time::from_consensus(600_000_000).unwrap());
+        assert_eq!(psbt.add_input(input), Err(ModifyError::LockTimeConflict));
+        assert_eq!(psbt.inputs.len(), 1);
+
+        psbt.tx_modifiable = PsbtV2::OUTPUTS_MODIFIABLE;
+        assert_eq!(
+            psbt.add_input(InputV2::new(OutPoint::null())),
+            Err(ModifyError::InputsNotModifiable)
+        );
+        psbt.tx_modifiable = 0;
+        assert_eq!(
+            psbt.add_output(OutputV2::new(Amount::ZERO, ScriptBuf::new())),
+            Err(ModifyError::OutputsNotModifiable)
+        );
+
+        let bytes = psbt.serialize();
+        assert_eq!(PsbtV2::deserialize(&bytes).unwrap(), psbt);
+        let v0 = psbt.into_v0().unwrap();
+        assert_eq!(v0.unsigned_tx.lock_time, absolute::LockTime::from_consensus(800_000));
+        assert_eq!(v0.unsigned_tx.input[0].sequence, Sequence::MAX);
+    }
+}
@@ -0,0 +1,242 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Verification of the signatures in a PSBT.
+
+use core::fmt;
+
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::sighash::SighashCache;
+use bitcoin::{PublicKey, TapLeafHash, XOnlyPublicKey};
+use bitcoin_internals::write_err;
+
+use crate::prelude::*;
+use crate::{Psbt, PsbtSighashType, SignError};
+
+impl Psbt {
+    /// Verifies every partial signature and Taproot signature against the sighash it signs.
+    ///
+    /// ECDSA signatures are checked against the public key they are stored under and Taproot
+    /// script path signatures against their x-only key, using the sighash type of each signature.
+    /// The Taproot key path signature is checked against the output key of the spent output.
+    ///
+    /// Returns the results for each input, in order. Final scriptSigs and witnesses are not
+    /// verified, this requires a script interpreter.
+    pub fn verify_sigs<C: Verification>(&self, secp: &Secp256k1<C>) -> Vec<InputSigs> {
+        let mut cache = SighashCache::new(&self.unsigned_tx);
+        let mut results = Vec::with_capacity(self.inputs.len());
+
+        for (index, input) in self.inputs.iter().enumerate() {
+            let mut result = InputSigs::default();
+
+            for (pubkey, sig) in &input.partial_sigs {
+                let verified = self
+                    .sighash_ecdsa_with_type(index, &mut cache, Some(sig.sighash_type))
+                    .map_err(VerifySigError::Sighash)
+                    .and_then(|(msg, _)| {
+                        secp.verify_ecdsa(&msg, &sig.signature, &pubkey.inner)
+                            .map_err(|_| VerifySigError::Invalid)
+                    });
+                result.partial_sigs.insert(*pubkey, verified);
+            }
+
+            if let Some(sig) = &input.tap_key_sig {
+                let verified = self
+                    .sighash_taproot_with_type(index, &mut cache, None, Some(sig.sighash_type))
+                    .and_then(|(msg, _)| {
+                        let spk = &self.spend_utxo(index)?.script_pubkey;
+                        let output_key = XOnlyPublicKey::from_slice(&spk.as_bytes()[2..])
+                            .map_err(|_| SignError::Unsupported)?;
+                        Ok((msg, output_key))
+                    })
+                    .map_err(VerifySigError::Sighash)
+                    .and_then(|(msg, output_key)| {
+                        secp.verify_schnorr(&sig.signature, &msg, &output_key)
+                            .map_err(|_| VerifySigError::Invalid)
+                    });
+                result.tap_key_sig = Some(verified);
+            }
+
+            for (&(pubkey, leaf_hash), sig) in &input.tap_script_sigs {
+                let verified = self
+                    .sighash_taproot_with_type(
+                        index,
+                        &mut cache,
+                        Some(leaf_hash),
+                        Some(sig.sighash_type),
+                    )
+                    .map_err(VerifySigError::Sighash)
+                    .and_then(|(msg, _)| {
+                        secp.verify_schnorr(&sig.signature, &msg, &pubkey)
+                            .map_err(|_| VerifySigError::Invalid)
+                    });
+                result.tap_script_sigs.insert((pubkey, leaf_hash), verified);
+            }
+
+            results.push(result);
+        }
+        results
+    }
+
+    /// Removes every signature that [`Psbt::verify_sigs`] does not verify.
+    ///
+    /// Signatures whose sighash can not be computed, for example because the spent outputs are
+    /// missing, are removed too. Returns the number of signatures removed.
+    pub fn remove_invalid_sigs<C: Verification>(&mut self, secp: &Secp256k1<C>) -> usize {
+        let results = self.verify_sigs(secp);
+        let mut removed = 0;
+
+        for (input, result) in self.inputs.iter_mut().zip(results) {
+            for (pubkey, verified) in result.partial_sigs {
+                if verified.is_err() {
+                    input.partial_sigs.remove(&pubkey);
+                    removed += 1;
+                }
+            }
+            if let Some(Err(_)) = result.tap_key_sig {
+                input.tap_key_sig = None;
+                removed += 1;
+            }
+            for (key, verified) in result.tap_script_sigs {
+                if verified.is_err() {
+                    input.tap_script_sigs.remove(&key);
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
+}
+
+/// The result of verifying each signature of an input, see [`Psbt::verify_sigs`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputSigs {
+    /// The ECDSA partial signatures, by public key.
+    pub partial_sigs: BTreeMap<PublicKey, Result<(), VerifySigError>>,
+    /// The Taproot key path signature, if the input has one.
+    pub tap_key_sig: Option<Result<(), VerifySigError>>,
+    /// The Taproot script path signatures, by x-only public key and leaf hash.
+    pub tap_script_sigs: BTreeMap<(XOnlyPublicKey, TapLeafHash), Result<(), VerifySigError>>,
+}
+
+impl InputSigs {
+    /// Returns true if every signature of the input verified.
+    pub fn is_valid(&self) -> bool {
+        self.partial_sigs
+            .values()
+            .chain(self.tap_key_sig.iter())
+            .chain(self.tap_script_sigs.values())
+            .all(Result::is_ok)
+    }
+}
+
+/// Error verifying a signature in a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VerifySigError {
+    /// The sighash the signature signs can not be computed.
+    Sighash(SignError),
+    /// The signature does not verify.
+    Invalid,
+    /// The sighash type of the signature is not allowed, see [`Psbt::verify_sigs_with_policy`].
+    DisallowedSighashType(PsbtSighashType),
+}
+
+bitcoin_internals::impl_from_infallible!(VerifySigError);
+
+impl fmt::Display for VerifySigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use VerifySigError::*;
+
+        match *self {
+            Sighash(ref e) => write_err!(f, "failed to compute the sighash"; e),
+            Invalid => f.write_str("invalid signature"),
+            DisallowedSighashType(sighash_type) =>
+                write!(f, "sighash type {} is not allowed by the policy", sighash_type),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerifySigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use VerifySigError::*;
+
+        match *self {
+            Sighash(ref e) => Some(e),
+            Invalid | DisallowedSighashType(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::key::{Keypair, TapTweak};
+    use bitcoin::secp256k1::{self, Message};
+    use bitcoin::sighash::EcdsaSighashType;
+    use bitcoin::{
+        absolute, ecdsa, taproot, transaction, Amount, CompressedPublicKey, ScriptBuf, Transaction,
+        TxIn, TxOut,
+    };
+
+    use super::*;
+
+    #[test]
+    fn verify_sigs() {
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = CompressedPublicKey(sk.public_key(&secp));
+        let keypair = Keypair::from_secret_key(&secp, &sk);
+        let (internal_key, _) = keypair.x_only_public_key();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&pk.wpubkey_hash()),
+        });
+        psbt.inputs[1].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(20_000),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, internal_key, None),
+        });
+
+        let mut cache = SighashCache::new(&psbt.unsigned_tx);
+        let (msg, _) = psbt.sighash_ecdsa(0, &mut cache).unwrap();
+        let ecdsa_sig = ecdsa::Signature {
+            signature: secp.sign_ecdsa(&msg, &sk),
+            sighash_type: EcdsaSighashType::All,
+        };
+        let (msg, sighash_type) = psbt.sighash_taproot(1, &mut cache, None).unwrap();
+        let tweaked = keypair.tap_tweak(&secp, None).to_inner();
+        let tap_sig = taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&msg, &tweaked),
+            sighash_type,
+        };
+
+        psbt.inputs[0].partial_sigs.insert(pk.into(), ecdsa_sig);
+        psbt.inputs[1].tap_key_sig = Some(tap_sig);
+        // A signature by the right key over the wrong message.
+        let other =
+            PublicKey::new(secp256k1::SecretKey::from_slice(&[2; 32]).unwrap().public_key(&secp));
+        let wrong = secp.sign_ecdsa(&Message::from_digest([3; 32]), &sk);
+        psbt.inputs[0].partial_sigs.insert(
+            other,
+            ecdsa::Signature { signature: wrong, sighash_type: EcdsaSighashType::All },
+        );
+
+        let results = psbt.verify_sigs(&secp);
+        assert_eq!(results[0].partial_sigs[&pk.into()], Ok(()));
+        assert_eq!(results[0].partial_sigs[&other], Err(VerifySigError::Invalid));
+        assert!(!results[0].is_valid());
+        assert_eq!(results[1].tap_key_sig, Some(Ok(())));
+        assert!(results[1].is_valid());
+
+        assert_eq!(psbt.remove_invalid_sigs(&secp), 1);
+        assert!(psbt.verify_sigs(&secp).iter().all(InputSigs::is_valid));
+        assert_eq!(psbt.inputs[0].partial_sigs.len(), 1);
+    }
+}
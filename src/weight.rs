@@ -0,0 +1,368 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Estimation of the weight of the final transaction before it is signed.
+
+use core::{cmp, fmt};
+
+use bitcoin::{Script, VarInt, Weight};
+use bitcoin_internals::write_err;
+
+use crate::{Input, Psbt, TapError, TapSpendPath};
+
+/// The maximum size of a DER encoded ECDSA signature including the sighash byte.
+const ECDSA_SIG_SIZE: usize = 72;
+/// The size of a compressed public key.
+const PUBKEY_SIZE: usize = 33;
+/// The size of an uncompressed public key.
+const UNCOMPRESSED_PUBKEY_SIZE: usize = 65;
+
+impl Psbt {
+    /// Estimates the maximum weight of the transaction once every input is finalized.
+    ///
+    /// Finalized inputs contribute their final scriptSig and witness. For the other inputs the
+    /// satisfaction is predicted from the script pubkey of the spent output and the redeem and
+    /// witness scripts in the input: P2PK, P2PKH, bare multisig, P2WPKH, and their P2SH and P2WSH
+    /// wrapped forms are understood, as are any scripts that parse as miniscript when the
+    /// `miniscript` feature is enabled. ECDSA signatures are assumed to be 72 bytes and public
+    /// keys to be compressed.
+    ///
+    /// A Taproot input is assumed to be spent using the most expensive of the key path and the
+    /// leaves in `tap_scripts`, see [`Input::tap_satisfaction_weight`]. Use
+    /// [`Psbt::max_weight_to_satisfy`] if the spend paths are known.
+    ///
+    /// # Errors
+    ///
+    /// If an input lacks UTXO information, the redeem or witness script it needs, or spends a
+    /// script whose satisfaction can not be predicted.
+    pub fn estimate_weight(&self) -> Result<Weight, EstimateWeightError> {
+        let mut weight = self.unsigned_tx.weight();
+        let mut witnesses = vec![];
+        for (input_index, (utxo, input)) in self.iter_funding_utxos().zip(&self.inputs).enumerate()
+        {
+            let (script_sig, witness) = if let Some(script_sig) = &input.final_script_sig {
+                (script_sig.len(), input.final_script_witness.as_ref().map(|w| w.size()))
+            } else if let Some(witness) = &input.final_script_witness {
+                (0, Some(witness.size()))
+            } else {
+                let utxo = utxo.map_err(|_| EstimateWeightError::MissingUtxo { input_index })?;
+                input
+                    .estimate_satisfaction(&utxo.script_pubkey)
+                    .map_err(|error| EstimateWeightError::Input { input_index, error })?
+            };
+
+            // The unsigned transaction already accounts for the length of an empty scriptSig.
+            let script_sig_size = VarInt(script_sig as u64).size() + script_sig - 1;
+            weight += Weight::from_non_witness_data_size(script_sig_size as u64);
+            witnesses.push(witness);
+        }
+
+        if witnesses.iter().any(Option::is_some) {
+            // The segwit marker and flag, inputs without a witness still have an element count.
+            let size = 2 + witnesses.iter().map(|size| size.unwrap_or(1)).sum::<usize>();
+            weight += Weight::from_witness_data_size(size as u64);
+        }
+        Ok(weight)
+    }
+
+    /// Estimates the virtual size of the transaction once every input is finalized.
+    ///
+    /// See [`Psbt::estimate_weight`].
+    pub fn estimate_vsize(&self) -> Result<u64, EstimateWeightError> {
+        self.estimate_weight().map(Weight::to_vbytes_ceil)
+    }
+}
+
+impl Input {
+    /// Returns the predicted size of the scriptSig and witness (if any) spending `spk`.
+    fn estimate_satisfaction(
+        &self,
+        spk: &Script,
+    ) -> Result<(usize, Option<usize>), EstimateInputError> {
+        if spk.is_p2sh() {
+            let redeem_script =
+                self.redeem_script.as_ref().ok_or(EstimateInputError::MissingRedeemScript)?;
+            let push_redeem_script = push_size(redeem_script.len());
+            if redeem_script.is_witness_program() {
+                let (_, witness) = self.estimate_segwit_satisfaction(redeem_script)?;
+                Ok((push_redeem_script, witness))
+            } else {
+                let (_, size) = self
+                    .script_satisfaction(redeem_script, false)
+                    .ok_or(EstimateInputError::UnsupportedScript)?;
+                Ok((size + push_redeem_script, None))
+            }
+        } else if spk.is_witness_program() {
+            self.estimate_segwit_satisfaction(spk)
+        } else {
+            let (_, size) = self
+                .script_satisfaction(spk, false)
+                .ok_or(EstimateInputError::UnsupportedScript)?;
+            Ok((size, None))
+        }
+    }
+
+    /// Returns the predicted scriptSig and witness size for a native segwit `program`.
+    fn estimate_segwit_satisfaction(
+        &self,
+        program: &Script,
+    ) -> Result<(usize, Option<usize>), EstimateInputError> {
+        let size = if program.is_p2wpkh() {
+            VarInt(2).size() + (1 + ECDSA_SIG_SIZE) + (1 + PUBKEY_SIZE)
+        } else if program.is_p2wsh() {
+            let witness_script =
+                self.witness_script.as_ref().ok_or(EstimateInputError::MissingWitnessScript)?;
+            let (count, size) = self
+                .script_satisfaction(witness_script, true)
+                .ok_or(EstimateInputError::UnsupportedScript)?;
+            VarInt(count as u64 + 1).size()
+                + size
+                + VarInt(witness_script.len() as u64).size()
+                + witness_script.len()
+        } else if program.is_p2tr() {
+            let mut weight = self.tap_satisfaction_weight(TapSpendPath::KeySpend)?;
+            for (script, ver) in self.tap_scripts.values() {
+                let leaf_hash = bitcoin::TapLeafHash::from_script(script, *ver);
+                weight = cmp::max(
+                    weight,
+                    self.tap_satisfaction_weight(TapSpendPath::ScriptSpend(leaf_hash))?,
+                );
+            }
+            weight.to_wu() as usize
+        } else {
+            return Err(EstimateInputError::UnsupportedScript);
+        };
+        Ok((0, Some(size)))
+    }
+
+    /// Returns the number of stack elements satisfying `script` and their total size, including
+    /// the length prefixes (`segwit`) or push opcodes.
+    fn script_satisfaction(&self, script: &Script, segwit: bool) -> Option<(usize, usize)> {
+        // Every element is less than 76 bytes so its prefix, or the opcode pushing it, is one
+        // byte.
+        let elements = |sizes: &[usize]| (sizes.len(), sizes.iter().map(|size| size + 1).sum());
+
+        if script.is_p2pk() {
+            Some(elements(&[ECDSA_SIG_SIZE]))
+        } else if script.is_p2pkh() {
+            Some(elements(&[ECDSA_SIG_SIZE, self.pkh_pubkey_size(script)]))
+        } else if let Some((threshold, _)) = crate::script::multisig(script) {
+            // The dummy element consumed by OP_CHECKMULTISIG.
+            let (count, size) = elements(&vec![ECDSA_SIG_SIZE; threshold]);
+            Some((count + 1, size + 1))
+        } else {
+            miniscript_satisfaction(script, segwit)
+        }
+    }
+
+    /// Returns the size of the public key spending the P2PKH `script`.
+    ///
+    /// Legacy outputs may be locked to an uncompressed key, which is only known from a partial
+    /// signature. Keys are assumed to be compressed otherwise.
+    fn pkh_pubkey_size(&self, script: &Script) -> usize {
+        let uncompressed = self.partial_sigs.keys().any(|pk| {
+            !pk.compressed && bitcoin::ScriptBuf::new_p2pkh(&pk.pubkey_hash()) == *script
+        });
+        if uncompressed {
+            UNCOMPRESSED_PUBKEY_SIZE
+        } else {
+            PUBKEY_SIZE
+        }
+    }
+}
+
+#[cfg(feature = "miniscript")]
+fn miniscript_satisfaction(script: &Script, segwit: bool) -> Option<(usize, usize)> {
+    use miniscript::{Legacy, Miniscript, ScriptContext, Segwitv0};
+
+    fn satisfaction<Ctx: ScriptContext>(script: &Script) -> Option<(usize, usize)> {
+        let ms = Miniscript::<Ctx::Key, Ctx>::parse_insane(script).ok()?;
+        // The element count includes the witness script.
+        let count = ms.max_satisfaction_witness_elements().ok()? - 1;
+        Some((count, ms.max_satisfaction_size().ok()?))
+    }
+
+    if segwit {
+        satisfaction::<Segwitv0>(script)
+    } else {
+        satisfaction::<Legacy>(script)
+    }
+}
+
+#[cfg(not(feature = "miniscript"))]
+fn miniscript_satisfaction(_: &Script, _: bool) -> Option<(usize, usize)> { None }
+
+/// Returns the size of a script push of `len` bytes, including the push opcode.
+fn push_size(len: usize) -> usize {
+    let opcode = match len {
+        0..=75 => 1,
+        76..=0xff => 2,
+        0x100..=0xffff => 3,
+        _ => 5,
+    };
+    opcode + len
+}
+
+/// Error returned by [`Psbt::estimate_weight`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EstimateWeightError {
+    /// UTXO information for a non-finalized input is not present or is invalid.
+    MissingUtxo {
+        /// The index of the input.
+        input_index: usize,
+    },
+    /// The satisfaction of an input can not be predicted.
+    Input {
+        /// The index of the input.
+        input_index: usize,
+        /// The reason the satisfaction can not be predicted.
+        error: EstimateInputError,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(EstimateWeightError);
+
+impl fmt::Display for EstimateWeightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use EstimateWeightError::*;
+
+        match *self {
+            MissingUtxo { input_index } =>
+                write!(f, "UTXO information is not available for input {}", input_index),
+            Input { input_index, ref error } =>
+                write_err!(f, "can not estimate the weight of input {}", input_index; error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EstimateWeightError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use EstimateWeightError::*;
+
+        match *self {
+            MissingUtxo { .. } => None,
+            Input { ref error, .. } => Some(error),
+        }
+    }
+}
+
+/// The reason the satisfaction of an input can not be predicted, see [`EstimateWeightError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EstimateInputError {
+    /// The input spends a P2SH output but has no redeem script.
+    MissingRedeemScript,
+    /// The input spends a P2WSH output but has no witness script.
+    MissingWitnessScript,
+    /// The spent script is not one of the understood templates.
+    UnsupportedScript,
+    /// The Taproot satisfaction can not be predicted.
+    Tap(TapError),
+}
+
+bitcoin_internals::impl_from_infallible!(EstimateInputError);
+
+impl fmt::Display for EstimateInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use EstimateInputError::*;
+
+        match *self {
+            MissingRedeemScript => f.write_str("the redeem script is missing"),
+            MissingWitnessScript => f.write_str("the witness script is missing"),
+            UnsupportedScript => f.write_str("the satisfaction of the script is not understood"),
+            Tap(ref e) => write_err!(f, "taproot"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EstimateInputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use EstimateInputError::*;
+
+        match *self {
+            MissingRedeemScript | MissingWitnessScript | UnsupportedScript => None,
+            Tap(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<TapError> for EstimateInputError {
+    fn from(e: TapError) -> Self { Self::Tap(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey, XOnlyPublicKey};
+    use bitcoin::{
+        absolute, transaction, Amount, NetworkKind, PrivateKey, ScriptBuf, Transaction, TxIn,
+        TxOut, WPubkeyHash, Witness,
+    };
+
+    use super::*;
+
+    #[test]
+    fn estimate_weight() {
+        let secp = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PrivateKey::new(sk, NetworkKind::Test).public_key(&secp);
+        let multisig = bitcoin::blockdata::script::Builder::new()
+            .push_int(1)
+            .push_key(&pk)
+            .push_key(&pk)
+            .push_int(2)
+            .push_opcode(bitcoin::opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        let wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+
+        let spks = [
+            ScriptBuf::new_p2pkh(&pk.pubkey_hash()),
+            wpkh.clone(),
+            ScriptBuf::new_p2sh(&wpkh.script_hash()),
+            ScriptBuf::new_p2wsh(&multisig.wscript_hash()),
+            ScriptBuf::new_p2tr(&secp, XOnlyPublicKey::from(pk.inner), None),
+        ];
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(); spks.len()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        for (input, spk) in psbt.inputs.iter_mut().zip(spks) {
+            input.witness_utxo = Some(TxOut { value: Amount::ZERO, script_pubkey: spk });
+        }
+        assert_eq!(
+            psbt.estimate_weight(),
+            Err(EstimateWeightError::Input {
+                input_index: 2,
+                error: EstimateInputError::MissingRedeemScript
+            })
+        );
+        psbt.inputs[2].redeem_script = Some(wpkh);
+        psbt.inputs[3].witness_script = Some(multisig.clone());
+
+        // Build the transaction with dummy satisfactions of the maximum size.
+        let sig = vec![0; ECDSA_SIG_SIZE];
+        let key = vec![0; PUBKEY_SIZE];
+        let mut tx = psbt.unsigned_tx.clone();
+        tx.input[0].script_sig = bitcoin::blockdata::script::Builder::new()
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(&sig[..]).unwrap())
+            .push_slice(<&bitcoin::script::PushBytes>::try_from(&key[..]).unwrap())
+            .into_script();
+        tx.input[1].witness = Witness::from_slice(&[&sig, &key]);
+        tx.input[2].script_sig = ScriptBuf::from_bytes(vec![0; 23]);
+        tx.input[2].witness = Witness::from_slice(&[&sig, &key]);
+        tx.input[3].witness = Witness::from_slice(&[&[][..], &sig, multisig.as_bytes()]);
+        tx.input[4].witness = Witness::from_slice(&[vec![0; 65]]);
+        assert_eq!(psbt.estimate_weight(), Ok(tx.weight()));
+
+        // Finalized inputs use their actual satisfaction.
+        psbt.inputs[4].final_script_witness = Some(Witness::from_slice(&[vec![0; 64]]));
+        tx.input[4].witness = Witness::from_slice(&[vec![0; 64]]);
+        assert_eq!(psbt.estimate_weight(), Ok(tx.weight()));
+        assert_eq!(psbt.estimate_vsize(), Ok(tx.vsize() as u64));
+    }
+}
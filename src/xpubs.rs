@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Managing the global xpubs and checking the key origins against them.
+//!
+//! Multisig wallets register their cosigners' extended keys in `PSBT_GLOBAL_XPUB`. A signer can
+//! then check that every key claimed to come from a cosigner does derive from that cosigner's
+//! xpub, instead of trusting the BIP 32 derivations of each input and output.
+
+use core::fmt;
+
+use bitcoin::bip32::{DerivationPath, Fingerprint, KeySource, Xpub};
+use bitcoin::secp256k1::{self, Secp256k1, Verification};
+
+use crate::prelude::*;
+use crate::{IndexOutOfBoundsError, MapLocation, Psbt};
+
+impl Psbt {
+    /// Adds `xpub`, derived from the master key with `fingerprint` along `path`, to the global
+    /// xpubs.
+    ///
+    /// # Errors
+    ///
+    /// If `path` does not have the depth and last child number of `xpub`, or `xpub` is already
+    /// present with a different key source.
+    pub fn add_global_xpub(
+        &mut self,
+        xpub: Xpub,
+        fingerprint: Fingerprint,
+        path: DerivationPath,
+    ) -> Result<(), XpubError> {
+        if path.len() != usize::from(xpub.depth) {
+            return Err(XpubError::DepthMismatch { depth: xpub.depth, path_len: path.len() });
+        }
+        if path.as_ref().last().map_or(false, |child| *child != xpub.child_number) {
+            return Err(XpubError::ChildNumberMismatch);
+        }
+        let source = (fingerprint, path);
+        match self.xpub.get(&xpub) {
+            Some(existing) if *existing != source =>
+                Err(XpubError::InconsistentKeySource(Box::new(xpub))),
+            _ => {
+                self.xpub.insert(xpub, source);
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks that every key origin under the fingerprint of a global xpub derives from one.
+    ///
+    /// The BIP 32 derivations and Taproot key origins of all inputs and outputs are checked.
+    /// Origins with a fingerprint no global xpub has are not checked.
+    ///
+    /// # Errors
+    ///
+    /// On the first key origin whose key does not derive from a global xpub with its fingerprint
+    /// along its path.
+    pub fn verify_global_xpubs<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), XpubError> {
+        let inputs = self.inputs.iter().enumerate().map(|(index, input)| {
+            (MapLocation::Input(index), &input.bip32_derivation, &input.tap_key_origins)
+        });
+        let outputs = self.outputs.iter().enumerate().map(|(index, output)| {
+            (MapLocation::Output(index), &output.bip32_derivation, &output.tap_key_origins)
+        });
+        for (location, bip32_derivation, tap_key_origins) in inputs.chain(outputs) {
+            let ecdsa = bip32_derivation.iter().map(|(pk, source)| (*pk, false, source));
+            let taproot = tap_key_origins.iter().map(|(xonly, (_, source))| {
+                (xonly.public_key(secp256k1::Parity::Even), true, source)
+            });
+            for (pubkey, x_only, source) in ecdsa.chain(taproot) {
+                if !self.xpub.values().any(|(fingerprint, _)| *fingerprint == source.0) {
+                    continue;
+                }
+                let derives = self.xpub.iter().any(|(xpub, xpub_source)| {
+                    derive(xpub, xpub_source, source, secp).map_or(false, |derived| {
+                        derived == pubkey
+                            || (x_only
+                                && derived.x_only_public_key().0 == pubkey.x_only_public_key().0)
+                    })
+                });
+                if !derives {
+                    return Err(XpubError::NotDerivable { location, source: source.clone() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the global xpubs the keys of input `input_index` derive from, in the order of the
+    /// global map.
+    pub fn xpub_for_input<C: Verification>(
+        &self,
+        input_index: usize,
+        secp: &Secp256k1<C>,
+    ) -> Result<Vec<&Xpub>, IndexOutOfBoundsError> {
+        let input = self.checked_input(input_index)?;
+        let ecdsa =
+            input.bip32_derivation.iter().map(|(pk, source)| (pk.x_only_public_key().0, source));
+        let taproot = input.tap_key_origins.iter().map(|(xonly, (_, source))| (*xonly, source));
+        let keys = ecdsa.chain(taproot).collect::<Vec<_>>();
+
+        Ok(self
+            .xpub
+            .iter()
+            .filter(|(xpub, xpub_source)| {
+                keys.iter().any(|(key, source)| {
+                    derive(xpub, xpub_source, source, secp)
+                        .map_or(false, |derived| derived.x_only_public_key().0 == *key)
+                })
+            })
+            .map(|(xpub, _)| xpub)
+            .collect())
+    }
+}
+
+/// Derives the key at `source` from `xpub`, which has `xpub_source`.
+///
+/// Returns `None` if `source` is not under `xpub_source` or needs a hardened derivation.
+fn derive<C: Verification>(
+    xpub: &Xpub,
+    xpub_source: &KeySource,
+    source: &KeySource,
+    secp: &Secp256k1<C>,
+) -> Option<secp256k1::PublicKey> {
+    let (xpub_path, path) = (xpub_source.1.as_ref(), source.1.as_ref());
+    if xpub_source.0 != source.0 || !path.starts_with(xpub_path) {
+        return None;
+    }
+    let remaining = &path[xpub_path.len()..];
+    if remaining.iter().any(|child| child.is_hardened()) {
+        return None;
+    }
+    xpub.derive_pub(secp, &remaining).ok().map(|derived| derived.public_key)
+}
+
+/// Error managing or checking the global xpubs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum XpubError {
+    /// The length of the derivation path is not the depth of the xpub.
+    DepthMismatch {
+        /// The depth of the xpub.
+        depth: u8,
+        /// The length of the derivation path.
+        path_len: usize,
+    },
+    /// The last child number of the derivation path is not the child number of the xpub.
+    ChildNumberMismatch,
+    /// The xpub is already present with a different key source.
+    InconsistentKeySource(Box<Xpub>),
+    /// A key origin under the fingerprint of a global xpub does not derive from one.
+    NotDerivable {
+        /// The map the key origin is in.
+        location: MapLocation,
+        /// The claimed origin of the key.
+        source: KeySource,
+    },
+}
+
+bitcoin_internals::impl_from_infallible!(XpubError);
+
+impl fmt::Display for XpubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use XpubError::*;
+
+        match *self {
+            DepthMismatch { depth, path_len } =>
+                write!(f, "derivation path of length {} for an xpub of depth {}", path_len, depth),
+            ChildNumberMismatch =>
+                f.write_str("the derivation path does not end in the child number of the xpub"),
+            InconsistentKeySource(ref xpub) =>
+                write!(f, "xpub {} is already present with a different key source", xpub),
+            NotDerivable { location, ref source } => write!(
+                f,
+                "the key at [{}]{} in the {} map does not derive from a global xpub",
+                source.0, source.1, location
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for XpubError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use XpubError::*;
+
+        match *self {
+            DepthMismatch { .. }
+            | ChildNumberMismatch
+            | InconsistentKeySource(_)
+            | NotDerivable { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::{absolute, transaction, Network, Transaction, TxIn};
+
+    use super::*;
+
+    #[test]
+    fn global_xpubs() {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Bitcoin, &[1; 32]).unwrap();
+        let fingerprint = master.fingerprint(&secp);
+        let account_path = "m/48'/0'/0'/2'".parse::<DerivationPath>().unwrap();
+        let account = Xpub::from_priv(&secp, &master.derive_priv(&secp, &account_path).unwrap());
+        let other = Xpub::from_priv(&secp, &Xpriv::new_master(Network::Bitcoin, &[2; 32]).unwrap());
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        })
+        .unwrap();
+        assert_eq!(
+            psbt.add_global_xpub(account, fingerprint, "m/48'/0'/0'".parse().unwrap()),
+            Err(XpubError::DepthMismatch { depth: 4, path_len: 3 })
+        );
+        assert_eq!(
+            psbt.add_global_xpub(account, fingerprint, "m/48'/0'/0'/1'".parse().unwrap()),
+            Err(XpubError::ChildNumberMismatch)
+        );
+        psbt.add_global_xpub(account, fingerprint, account_path.clone()).unwrap();
+        psbt.add_global_xpub(other, other.fingerprint(), DerivationPath::master()).unwrap();
+        assert!(matches!(
+            psbt.add_global_xpub(account, other.fingerprint(), account_path.clone()),
+            Err(XpubError::InconsistentKeySource(_))
+        ));
+
+        let path = account_path.extend("0/1".parse::<DerivationPath>().unwrap());
+        let key = master.derive_priv(&secp, &path).unwrap().private_key.public_key(&secp);
+        psbt.inputs[0].bip32_derivation.insert(key, (fingerprint, path.clone()));
+        psbt.verify_global_xpubs(&secp).unwrap();
+        assert_eq!(psbt.xpub_for_input(0, &secp), Ok(vec![&account]));
+
+        // A key claimed to be under the account that is not.
+        let bogus = other.derive_pub(&secp, &"m/0".parse::<DerivationPath>().unwrap()).unwrap();
+        let bogus_path = account_path.extend("0/2".parse::<DerivationPath>().unwrap());
+        psbt.inputs[0].bip32_derivation.insert(bogus.public_key, (fingerprint, bogus_path.clone()));
+        assert_eq!(
+            psbt.verify_global_xpubs(&secp),
+            Err(XpubError::NotDerivable {
+                location: MapLocation::Input(0),
+                source: (fingerprint, bogus_path)
+            })
+        );
+        assert_eq!(psbt.xpub_for_input(0, &secp), Ok(vec![&account]));
+    }
+}
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Conformance tests against Bitcoin Core.
+//!
+//! Run with `cargo test --features _test-core --test core -- --ignored`. A regtest node is started
+//! by `corepc-node` using the `bitcoind` executable in `BITCOIND_EXE`, or found in `PATH`. It
+//! must be Bitcoin Core 28 or later, the wallet is a descriptor wallet funded by mining to it.
+
+#![cfg(feature = "_test-core")]
+
+use std::str::FromStr;
+
+use corepc_node::serde_json::{json, Value};
+use corepc_node::Node;
+use psbt_v0::Psbt;
+
+#[test]
+#[ignore = "needs a bitcoind executable, set BITCOIND_EXE and run with --ignored"]
+fn conformance_with_core() {
+    let exe = corepc_node::exe_path().expect("no bitcoind, set BITCOIND_EXE");
+    let mut node = Node::new(exe).unwrap();
+    let client = &mut node.client;
+
+    let address = client.call::<String>("getnewaddress", &[]).unwrap();
+    client.call::<Value>("generatetoaddress", &[101.into(), address.into()]).unwrap();
+
+    for address_type in ["legacy", "p2sh-segwit", "bech32", "bech32m"] {
+        let to = client.call::<String>("getnewaddress", &["".into(), address_type.into()]).unwrap();
+        let outputs = json!([{ (to): 1 }]);
+        let created =
+            client.call::<Value>("walletcreatefundedpsbt", &[json!([]), outputs]).unwrap();
+        let psbt = Psbt::from_str(created["psbt"].as_str().unwrap()).unwrap();
+
+        let (finalized, issues) = psbt.check_conformance(client).unwrap();
+        assert!(issues.is_empty(), "{}: {:?}", address_type, issues);
+        assert!(finalized.inputs.iter().all(|input| input.is_finalized()));
+    }
+}